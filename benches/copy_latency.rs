@@ -0,0 +1,73 @@
+//! End-to-end copy latency against a local synthetic feed: `cargo bench`.
+//!
+//! Results are kept in `target/copy-latency.json` and the next run is
+//! compared with them. Set `COPY_BENCH_TOLERANCE` (e.g. `0.25`) to fail
+//! when a stage's p99 got more than that much slower.
+
+use polymarket_copy_bot::bench::{self, BenchReport};
+use polymarket_copy_bot::builder::BotBuilder;
+use polymarket_copy_bot::synthetic::FeedPlan;
+use std::path::Path;
+use std::time::Duration;
+
+const RESULTS: &str = "target/copy-latency.json";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = BotBuilder::new().account("0xbe0c", "a".repeat(64)).config().clone();
+    let mut failed = false;
+    for (label, interval) in [
+        ("steady, 1 trade/ms", Duration::from_millis(1)),
+        ("burst", Duration::ZERO),
+    ] {
+        let plan = FeedPlan {
+            trades: 2_000,
+            interval,
+            ..Default::default()
+        };
+        let report = bench::run(config.clone(), plan).await?;
+        println!("copy latency ({})", label);
+        print!("{}", report);
+        println!();
+        if label == "burst" {
+            failed = compare(&report)?;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Prints the change from the last run's results, then keeps these; `true`
+/// if a stage regressed past `COPY_BENCH_TOLERANCE`.
+fn compare(report: &BenchReport) -> anyhow::Result<bool> {
+    let path = Path::new(RESULTS);
+    let mut failed = false;
+    if let Ok(json) = std::fs::read_to_string(path) {
+        let baseline: BenchReport = serde_json::from_str(&json)?;
+        for now in &report.stages {
+            if let Some(before) = baseline.stage(&now.stage) {
+                println!(
+                    "{:<8} p99 {:>8.0}us -> {:>8.0}us ({:+.1}%)",
+                    now.stage,
+                    before.p99_us,
+                    now.p99_us,
+                    (now.p99_us / before.p99_us.max(f64::EPSILON) - 1.0) * 100.0
+                );
+            }
+        }
+        if let Ok(tolerance) = std::env::var("COPY_BENCH_TOLERANCE") {
+            let regressions = report.regressions(&baseline, tolerance.parse()?);
+            for (stage, ratio) in &regressions {
+                println!("regression: {} p99 is {:.2}x the last run's", stage, ratio);
+            }
+            failed = !regressions.is_empty();
+        }
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+    Ok(failed)
+}
@@ -0,0 +1,438 @@
+//! Authenticated control endpoints for operating the bot remotely.
+//!
+//! With `admin_token` set, the HTTP server on `health_addr` accepts these
+//! requests carrying `Authorization: Bearer <admin_token>`:
+//!
+//! - `POST /admin/pause`, `POST /admin/resume`
+//! - `POST /admin/orders/cancel` - cancels every open order on the account
+//! - `POST /admin/config/reload` - re-reads the config; risk limits, sizing
+//!   and tracked wallets apply at once, other changes are reported as
+//!   needing a restart
+//! - `POST /admin/wallets` with `{"wallet": "0x..."}`, and
+//!   `DELETE /admin/wallets/<wallet>` - until the next restart or reload
+//! - `POST /admin/kill-switch/trip` with an optional `{"reason": "..."}`,
+//!   and `POST /admin/kill-switch/reset`
+//! - `GET /admin/log-filter`, and `POST /admin/log-filter` with e.g.
+//!   `{"filter": "info,polymarket_copy_bot::watcher=debug", "for": "10m"}`
+//!   to change what gets logged, optionally only for a while
+//!
+//! Answers are JSON with a `message` for the operator. Every action is
+//! logged.
+
+use crate::audit::AuditAction;
+use crate::config::validate_config;
+use crate::executor::TradeExecutor;
+use crate::http::{Handler, Request, Response};
+use crate::logging;
+use crate::notify::{BotControl, Command};
+use crate::sizing::PositionSizer;
+use crate::storage::Storage;
+use crate::types::Config;
+use crate::units::{format_duration, parse_duration};
+use crate::watcher::TradeSource;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Config keys a reload applies without a restart.
+pub const LIVE_KEYS: &[&str] = &[
+    "wallets_to_track",
+    "sizing_mode",
+    "fixed_stake",
+    "proportional_ratio",
+    "min_stake",
+    "max_stake",
+    "max_exposure_per_event",
+    "max_daily_volume",
+    "max_daily_loss",
+    "min_liquidity",
+    "cb_consecutive_trigger",
+    "cb_min_depth_usd",
+];
+
+/// Produces the current config from its sources, for reloads.
+pub type ConfigSource = Arc<dyn Fn() -> Result<Config> + Send + Sync>;
+
+#[derive(Debug, Default, Deserialize)]
+struct Body {
+    wallet: Option<String>,
+    reason: Option<String>,
+    filter: Option<String>,
+    #[serde(rename = "for")]
+    duration: Option<String>,
+}
+
+/// Serves the `/admin` endpoints.
+pub struct AdminApi {
+    token: String,
+    control: Arc<BotControl>,
+    watcher: Arc<dyn TradeSource>,
+    executor: Arc<TradeExecutor>,
+    sizer: Arc<PositionSizer>,
+    storage: Option<Arc<dyn Storage>>,
+    /// The config as last loaded, to tell what a reload changed
+    config: Mutex<Config>,
+    source: OnceLock<ConfigSource>,
+}
+
+impl AdminApi {
+    pub fn new(
+        config: &Config,
+        control: Arc<BotControl>,
+        watcher: Arc<dyn TradeSource>,
+        executor: Arc<TradeExecutor>,
+        sizer: Arc<PositionSizer>,
+        storage: Option<Arc<dyn Storage>>,
+    ) -> Self {
+        Self {
+            token: config.admin_token.clone(),
+            control,
+            watcher,
+            executor,
+            sizer,
+            storage,
+            config: Mutex::new(config.clone()),
+            source: OnceLock::new(),
+        }
+    }
+
+    /// Where `/admin/config/reload` reads the config from; reloads fail
+    /// until this is set.
+    pub fn set_config_source(&self, source: ConfigSource) {
+        let _ = self.source.set(source);
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = request.header("authorization").and_then(|h| h.strip_prefix("Bearer ")) else {
+            return false;
+        };
+        // Compare digests so the time taken says nothing about the token
+        let (given, expected) = (Sha256::digest(token.trim()), Sha256::digest(&self.token));
+        given.iter().zip(expected.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+
+    fn audit(&self, action: AuditAction, mut detail: Value) {
+        detail["via"] = json!("admin_api");
+        self.control.audit().record(action, "operator", detail);
+    }
+
+    async fn cancel_orders(&self) -> Result<Value> {
+        let (cancelled, failed) = self.executor.cancel_open_orders().await?;
+        if let Some(storage) = &self.storage {
+            for order in storage.open_orders().await? {
+                if order
+                    .exchange_order_id
+                    .as_ref()
+                    .is_some_and(|id| cancelled.contains(id))
+                {
+                    storage.update_order(order.id, "cancelled", None, None).await?;
+                }
+            }
+        }
+        self.audit(
+            AuditAction::OrdersCancelled,
+            json!({ "cancelled": cancelled, "failed": failed }),
+        );
+        let message = format!("Cancelled {} open orders", cancelled.len());
+        if failed.is_empty() {
+            tracing::warn!("🛑 {} by admin request", message);
+        } else {
+            tracing::error!(
+                "🛑 {} by admin request, {} failed: {}",
+                message,
+                failed.len(),
+                failed.join("; ")
+            );
+        }
+        Ok(json!({ "message": message, "cancelled": cancelled, "failed": failed }))
+    }
+
+    async fn reload(&self) -> Result<Value> {
+        let source = self.source.get().context("No config source to reload from")?;
+        let new = source()?;
+        validate_config(&new)?;
+        let changed = changed_keys(&self.config.lock().unwrap(), &new)?;
+
+        self.control.risk().reconfigure(new.clone());
+        self.sizer.reconfigure(new.clone());
+        let running = self.watcher.wallets();
+        for wallet in running.iter().filter(|w| !new.wallets_to_track.contains(w)) {
+            self.watcher.remove_wallet(wallet)?;
+        }
+        for wallet in new.wallets_to_track.iter().filter(|w| !running.contains(w)) {
+            self.watcher.add_wallet(wallet)?;
+        }
+        *self.config.lock().unwrap() = new;
+
+        let (applied, restart): (Vec<String>, Vec<String>) =
+            changed.into_iter().partition(|key| LIVE_KEYS.contains(&key.as_str()));
+        self.audit(
+            AuditAction::ConfigReloaded,
+            json!({ "applied": applied, "restart_required": restart }),
+        );
+        let message = if restart.is_empty() {
+            format!("Config reloaded, {} settings changed", applied.len())
+        } else {
+            format!("Config reloaded; restart to apply {}", restart.join(", "))
+        };
+        tracing::info!("🔄 {} by admin request", message);
+        Ok(json!({ "message": message, "applied": applied, "restart_required": restart }))
+    }
+
+    fn add_wallet(&self, body: &Body) -> Result<Value> {
+        let wallet = body.wallet.as_deref().map(str::trim).unwrap_or_default();
+        anyhow::ensure!(
+            wallet.starts_with("0x") && wallet.len() > 2,
+            "Expected {{\"wallet\": \"0x...\"}}"
+        );
+        let added = self.watcher.add_wallet(wallet)?;
+        self.control.risk().set_tracked(wallet, true);
+        let message = if added {
+            self.audit(AuditAction::WalletAdded, json!({ "wallet": wallet }));
+            tracing::info!("👀 Now watching {} by admin request", wallet);
+            format!("Watching {}", wallet)
+        } else {
+            format!("Already watching {}", wallet)
+        };
+        Ok(json!({ "message": message }))
+    }
+
+    fn remove_wallet(&self, wallet: &str) -> Result<Value> {
+        let removed = self.watcher.remove_wallet(wallet)?;
+        self.control.risk().set_tracked(wallet, false);
+        let message = if removed {
+            self.audit(AuditAction::WalletRemoved, json!({ "wallet": wallet }));
+            tracing::info!("🙈 Stopped watching {} by admin request", wallet);
+            format!("Stopped watching {}", wallet)
+        } else {
+            format!("Wasn't watching {}", wallet)
+        };
+        Ok(json!({ "message": message }))
+    }
+
+    fn trip(&self, body: &Body) -> Value {
+        let reason = body.reason.as_deref().unwrap_or("Kill switch tripped by admin request");
+        self.control.risk().trip(reason);
+        self.audit(AuditAction::KillSwitchTripped, json!({ "reason": reason }));
+        json!({ "message": format!("Kill switch tripped: {}", reason) })
+    }
+
+    fn reset(&self) -> Value {
+        self.control.risk().reset_circuit_breaker();
+        self.audit(AuditAction::KillSwitchReset, json!({}));
+        tracing::warn!("🔓 Kill switch reset by admin request");
+        json!({ "message": "Kill switch reset" })
+    }
+
+    fn set_log_filter(&self, body: &Body) -> Result<Value> {
+        let filter = body.filter.as_deref().context("Expected {\"filter\": \"...\"}")?;
+        let revert_after = body.duration.as_deref().map(parse_duration).transpose()?;
+        logging::set_filter(filter, revert_after)?;
+        let message = match revert_after {
+            Some(after) => format!("Log filter set to '{}' for {}", filter, format_duration(after)),
+            None => format!("Log filter set to '{}'", filter),
+        };
+        Ok(json!({ "message": message, "filter": logging::current_filter() }))
+    }
+
+    async fn act(&self, request: &Request) -> Result<Option<Value>> {
+        let body: Body = if request.body.is_empty() {
+            Body::default()
+        } else {
+            serde_json::from_slice(&request.body).context("Body must be a JSON object")?
+        };
+        Ok(Some(
+            match (request.method.as_str(), request.path.trim_end_matches('/')) {
+                ("POST", "/admin/pause") => json!({ "message": self.control.execute(Command::Pause) }),
+                ("POST", "/admin/resume") => json!({ "message": self.control.execute(Command::Resume) }),
+                ("POST", "/admin/orders/cancel") => self.cancel_orders().await?,
+                ("POST", "/admin/config/reload") => self.reload().await?,
+                ("POST", "/admin/wallets") => self.add_wallet(&body)?,
+                ("DELETE", path) if path.starts_with("/admin/wallets/") => {
+                    self.remove_wallet(&path["/admin/wallets/".len()..])?
+                }
+                ("POST", "/admin/kill-switch/trip") => self.trip(&body),
+                ("POST", "/admin/kill-switch/reset") => self.reset(),
+                ("GET", "/admin/log-filter") => json!({ "filter": logging::current_filter() }),
+                ("POST", "/admin/log-filter") => self.set_log_filter(&body)?,
+                _ => return Ok(None),
+            },
+        ))
+    }
+}
+
+#[async_trait]
+impl Handler for AdminApi {
+    async fn handle(&self, request: &Request) -> Option<Response> {
+        if !request.path.starts_with("/admin/") {
+            return None;
+        }
+        if self.token.is_empty() || !self.authorized(request) {
+            tracing::warn!(
+                "🔐 Rejected unauthorized admin request {} {}",
+                request.method,
+                request.path
+            );
+            return Some(Response::error(401, "unauthorized"));
+        }
+        Some(match self.act(request).await {
+            Ok(Some(answer)) => Response::json(200, &answer),
+            Ok(None) => Response::error(404, "not found"),
+            Err(e) => {
+                tracing::warn!("Admin request {} {} failed: {:#}", request.method, request.path, e);
+                Response::error(400, format!("{:#}", e))
+            }
+        })
+    }
+}
+
+/// Top-level config keys whose values differ.
+fn changed_keys(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let as_map = |config: &Config| -> Result<Map<String, Value>> {
+        match serde_json::to_value(config)? {
+            Value::Object(map) => Ok(map),
+            _ => anyhow::bail!("Config did not serialize to an object"),
+        }
+    };
+    let (old, new) = (as_map(old)?, as_map(new)?);
+    Ok(new
+        .iter()
+        .filter(|(key, value)| old.get(*key) != Some(value))
+        .map(|(key, _)| key.clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::UsdcAmount;
+    use crate::api::PolymarketApi;
+    use crate::portfolio::Portfolio;
+    use crate::risk::RiskManager;
+    use crate::types::CostBasis;
+    use crate::watcher::WalletWatcher;
+
+    fn admin(token: &str) -> AdminApi {
+        let config = Config {
+            admin_token: token.to_string(),
+            wallets_to_track: vec!["0xabc".to_string()],
+            ..Default::default()
+        };
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Average, None));
+        let control = Arc::new(BotControl::new(portfolio, Arc::new(RiskManager::new(config.clone()))));
+        AdminApi::new(
+            &config,
+            control,
+            Arc::new(WalletWatcher::new(String::new(), config.wallets_to_track.clone())),
+            Arc::new(TradeExecutor::new(PolymarketApi::new(String::new()), config.clone())),
+            Arc::new(PositionSizer::new(config.clone())),
+            None,
+        )
+    }
+
+    fn post(path: &str, token: &str, body: &str) -> Request {
+        Request {
+            method: "POST".to_string(),
+            path: path.to_string(),
+            headers: vec![("authorization".to_string(), format!("Bearer {}", token))],
+            body: body.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requires_token() {
+        let api = admin("s3cret");
+        assert!(api.handle(&post("/status", "s3cret", "")).await.is_none());
+        assert_eq!(
+            api.handle(&post("/admin/pause", "wrong", "")).await.unwrap().status,
+            401
+        );
+        assert_eq!(api.handle(&post("/admin/pause", "", "")).await.unwrap().status, 401);
+        assert!(!api.control.is_paused());
+
+        // Without a configured token nothing gets in
+        let api = admin("");
+        assert_eq!(api.handle(&post("/admin/pause", "", "")).await.unwrap().status, 401);
+    }
+
+    #[tokio::test]
+    async fn test_control_actions() {
+        let api = admin("s3cret");
+        assert_eq!(
+            api.handle(&post("/admin/pause", "s3cret", "")).await.unwrap().status,
+            200
+        );
+        assert!(api.control.is_paused());
+        api.handle(&post("/admin/resume", "s3cret", "")).await.unwrap();
+        assert!(!api.control.is_paused());
+
+        let tripped = api
+            .handle(&post(
+                "/admin/kill-switch/trip",
+                "s3cret",
+                r#"{"reason": "exchange outage"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(tripped.status, 200);
+        let state = api.control.risk().get_state();
+        assert!(state.is_tripped);
+        assert_eq!(state.trip_reason.as_deref(), Some("exchange outage"));
+        api.handle(&post("/admin/kill-switch/reset", "s3cret", ""))
+            .await
+            .unwrap();
+        assert!(!api.control.risk().get_state().is_tripped);
+
+        assert_eq!(
+            api.handle(&post("/admin/wallets", "s3cret", "{}"))
+                .await
+                .unwrap()
+                .status,
+            400
+        );
+        assert_eq!(
+            api.handle(&post("/admin/config/reload", "s3cret", ""))
+                .await
+                .unwrap()
+                .status,
+            400
+        );
+        assert_eq!(
+            api.handle(&post("/admin/nope", "s3cret", "")).await.unwrap().status,
+            404
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_filter() {
+        let api = admin("s3cret");
+        let mut get = post("/admin/log-filter", "s3cret", "");
+        get.method = "GET".to_string();
+        assert_eq!(api.handle(&get).await.unwrap().status, 200);
+        let missing = api.handle(&post("/admin/log-filter", "s3cret", "{}")).await.unwrap();
+        assert_eq!(missing.status, 400);
+        let bad_duration = r#"{"filter": "debug", "for": "soon"}"#;
+        let bad_duration = api
+            .handle(&post("/admin/log-filter", "s3cret", bad_duration))
+            .await
+            .unwrap();
+        assert_eq!(bad_duration.status, 400);
+    }
+
+    #[test]
+    fn test_changed_keys() {
+        let old = Config::default();
+        let new = Config {
+            max_daily_volume: UsdcAmount(old.max_daily_volume.as_f64() + 1.0),
+            ws_url: "wss://elsewhere".to_string(),
+            ..old.clone()
+        };
+        let mut changed = changed_keys(&old, &new).unwrap();
+        changed.sort();
+        assert_eq!(changed, vec!["max_daily_volume", "ws_url"]);
+    }
+}
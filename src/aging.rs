@@ -0,0 +1,352 @@
+//! How long open positions have been held, and which ones the leader left.
+//!
+//! Each held market is aged from its oldest open lot and set against the
+//! market's end date and the leaders whose copies opened it (from the
+//! journal's copied decisions). A leader has exited once the trades of
+//! theirs the bot saw in the market net out to (nearly) nothing after a
+//! sell; their last sell is the exit. A position every leader exited
+//! `stale_position_after` ago or more is stale: the bot missed or skipped
+//! the exit and is still holding. Each stale position raises one
+//! `stale_position` anomaly notification until it's closed or a leader
+//! buys back in.
+//!
+//! Needs `storage_url`; the ages are served on `/status/aging`.
+
+use crate::clock::Clock;
+use crate::marks::Marks;
+use crate::notify::{Notification, Notifications};
+use crate::portfolio::{Holding, Portfolio};
+use crate::storage::{DecisionRecord, LeaderTradeRecord, Storage, TimeRange};
+use crate::types::{Config, Market, TradeSide};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often positions are aged.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How long before its fill a copy may have been decided.
+const DECISION_LEAD_MS: i64 = 3_600_000;
+
+/// A leader whose observed net shares are within this fraction of what
+/// they bought has exited.
+const EXIT_FRACTION: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionAge {
+    pub market_id: String,
+    pub shares: f64,
+    /// Unix ms the oldest open lot was bought
+    pub opened_at: i64,
+    pub held_ms: i64,
+    /// Leaders whose copies opened the position
+    pub leaders: Vec<String>,
+    /// When the last of them exited; `None` while any still holds
+    pub leader_exited_at: Option<i64>,
+    /// How long the bot has held on since
+    pub since_leader_exit_ms: Option<i64>,
+    /// Unix ms of the market's end date, if known
+    pub ends_at: Option<i64>,
+    /// Until the end date; negative once past it
+    pub ends_in_ms: Option<i64>,
+    pub stale: bool,
+}
+
+/// When `wallet` exited `market_id`, judged from their trades in `trades`
+/// (oldest first); `None` if they're still in.
+pub fn exit_time(trades: &[&LeaderTradeRecord], wallet: &str, market_id: &str) -> Option<i64> {
+    let mut bought = 0.0;
+    let mut net = 0.0;
+    let mut exited_at = None;
+    for t in trades
+        .iter()
+        .filter(|t| t.trade.wallet.eq_ignore_ascii_case(wallet) && t.trade.market_id == market_id)
+    {
+        match t.trade.side {
+            TradeSide::BUY => {
+                bought += t.trade.shares;
+                net += t.trade.shares;
+                exited_at = None;
+            }
+            TradeSide::SELL => {
+                net -= t.trade.shares;
+                if net <= bought * EXIT_FRACTION {
+                    exited_at = Some(t.observed_at);
+                }
+            }
+        }
+    }
+    exited_at
+}
+
+/// Ages `holdings` at `now_ms`. `decisions` tie markets to the leaders
+/// copied into them and `trades` (oldest first) show when those leaders
+/// exited; positions held on `stale_after` past their exit are stale.
+pub fn compute(
+    holdings: &[Holding],
+    decisions: &[DecisionRecord],
+    trades: &[LeaderTradeRecord],
+    markets: &HashMap<String, Market>,
+    stale_after: Duration,
+    now_ms: i64,
+) -> Vec<PositionAge> {
+    let mut leaders: HashMap<&str, BTreeSet<String>> = HashMap::new();
+    for d in decisions.iter().filter(|d| d.copied) {
+        leaders
+            .entry(d.market_id.as_str())
+            .or_default()
+            .insert(d.wallet.to_lowercase());
+    }
+    let trades: Vec<&LeaderTradeRecord> = trades.iter().collect();
+
+    let mut ages: Vec<PositionAge> = holdings
+        .iter()
+        .filter(|h| h.shares() > 1e-9)
+        .map(|holding| {
+            let opened_at = holding.lots.iter().map(|l| l.opened_at).min().unwrap_or(now_ms);
+            let wallets: Vec<String> = leaders
+                .get(holding.market_id.as_str())
+                .map(|l| l.iter().cloned().collect())
+                .unwrap_or_default();
+            let exits: Vec<Option<i64>> = wallets
+                .iter()
+                .map(|w| exit_time(&trades, w, &holding.market_id))
+                .collect();
+            let leader_exited_at = if !exits.is_empty() && exits.iter().all(Option::is_some) {
+                exits.into_iter().flatten().max()
+            } else {
+                None
+            };
+            let since_leader_exit_ms = leader_exited_at.map(|at| now_ms - at);
+            let ends_at = markets
+                .get(&holding.market_id)
+                .and_then(|m| m.end_date)
+                .map(|d| d * 1000);
+            PositionAge {
+                market_id: holding.market_id.clone(),
+                shares: holding.shares(),
+                opened_at,
+                held_ms: now_ms - opened_at,
+                leaders: wallets,
+                leader_exited_at,
+                since_leader_exit_ms,
+                ends_at,
+                ends_in_ms: ends_at.map(|at| at - now_ms),
+                stale: !stale_after.is_zero()
+                    && since_leader_exit_ms.is_some_and(|ms| ms >= stale_after.as_millis() as i64),
+            }
+        })
+        .collect();
+    ages.sort_by_key(|a| std::cmp::Reverse(a.held_ms));
+    ages
+}
+
+/// Ages a running bot's positions and alerts on stale ones.
+pub struct PositionAging {
+    storage: Arc<dyn Storage>,
+    portfolio: Arc<Portfolio>,
+    marks: Arc<Marks>,
+    notifications: Notifications,
+    stale_after: Duration,
+    clock: Arc<dyn Clock>,
+    /// Stale markets already alerted on
+    alerted: Mutex<HashSet<String>>,
+    latest: Mutex<Vec<PositionAge>>,
+}
+
+impl PositionAging {
+    pub fn from_config(
+        config: &Config,
+        storage: Arc<dyn Storage>,
+        portfolio: Arc<Portfolio>,
+        marks: Arc<Marks>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            storage,
+            portfolio,
+            marks,
+            notifications: Notifications::default(),
+            stale_after: config.stale_position_after,
+            clock,
+            alerted: Mutex::new(HashSet::new()),
+            latest: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// As of the last check, longest held first.
+    pub fn latest(&self) -> Vec<PositionAge> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Ages every open position, alerting once on each newly stale one.
+    pub async fn check(&self) -> Result<Vec<PositionAge>> {
+        let holdings = self.portfolio.holdings();
+        let now = self.clock.now_ms();
+        let since = holdings
+            .iter()
+            .flat_map(|h| h.lots.iter().map(|l| l.opened_at))
+            .min()
+            .unwrap_or(now);
+        // Copies are decided a little before they fill; leaders' exits come
+        // later, but their earlier buys count towards what they hold
+        let decisions = self
+            .storage
+            .decisions(TimeRange::since(since - DECISION_LEAD_MS))
+            .await?;
+        let trades = self.storage.leader_trades(TimeRange::all()).await?;
+        let mut markets = HashMap::new();
+        for holding in &holdings {
+            if let Ok(market) = self.marks.markets().get(&holding.market_id).await {
+                markets.insert(holding.market_id.clone(), market);
+            }
+        }
+        let ages = compute(&holdings, &decisions, &trades, &markets, self.stale_after, now);
+
+        let mut alerted = self.alerted.lock().unwrap();
+        alerted.retain(|market_id| ages.iter().any(|a| a.stale && &a.market_id == market_id));
+        for age in ages.iter().filter(|a| a.stale) {
+            if !alerted.insert(age.market_id.clone()) {
+                continue;
+            }
+            let summary = format!(
+                "still holding {:.2} shares of {}, {}h after {} exited",
+                age.shares,
+                age.market_id,
+                age.since_leader_exit_ms.unwrap_or_default() / 3_600_000,
+                age.leaders.join(", ")
+            );
+            tracing::warn!("⏳ Stale position: {}", summary);
+            self.notifications.send(Notification::Anomaly {
+                rule: "stale_position".to_string(),
+                summary,
+            });
+        }
+        drop(alerted);
+        *self.latest.lock().unwrap() = ages.clone();
+        Ok(ages)
+    }
+
+    /// Checks every few minutes.
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.check().await {
+                    tracing::warn!("Failed to age positions: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::Lot;
+    use crate::types::Trade;
+
+    #[test]
+    fn test_ages_positions_and_flags_ones_the_leader_left() {
+        let hour = 3_600_000;
+        let now = 100 * hour;
+        let trade = |wallet: &str, market_id: &str, side, shares, observed_at| LeaderTradeRecord {
+            id: 0,
+            trade: Trade {
+                wallet: wallet.to_string(),
+                event_id: String::new(),
+                market_id: market_id.to_string(),
+                side,
+                shares,
+                price: 0.5,
+                timestamp: 0,
+                tx_hash: None,
+            },
+            observed_at,
+        };
+        let decision = |wallet: &str, market_id: &str| DecisionRecord {
+            id: 0,
+            leader_trade_id: None,
+            wallet: wallet.to_string(),
+            market_id: market_id.to_string(),
+            side: "BUY".to_string(),
+            copied: true,
+            reason: None,
+            detail: None,
+            size_usd: Some(10.0),
+            decided_at: 0,
+            arrival_mid: None,
+            arrival_book: None,
+        };
+        let holding = |market_id: &str, opened_at| Holding {
+            market_id: market_id.to_string(),
+            lots: vec![Lot {
+                shares: 20.0,
+                price: 0.5,
+                opened_at,
+            }],
+        };
+        // 0xa left m1 at hour 80; 0xb trimmed m2 but still holds; m3's two
+        // leaders left at hours 90 and 98
+        let trades = [
+            trade("0xa", "m1", TradeSide::BUY, 100.0, 10 * hour),
+            trade("0xa", "m1", TradeSide::SELL, 60.0, 50 * hour),
+            trade("0xa", "m1", TradeSide::SELL, 40.0, 80 * hour),
+            trade("0xb", "m2", TradeSide::BUY, 100.0, 10 * hour),
+            trade("0xb", "m2", TradeSide::SELL, 50.0, 80 * hour),
+            trade("0xa", "m3", TradeSide::BUY, 10.0, 10 * hour),
+            trade("0xa", "m3", TradeSide::SELL, 10.0, 90 * hour),
+            trade("0xc", "m3", TradeSide::BUY, 10.0, 10 * hour),
+            trade("0xc", "m3", TradeSide::SELL, 10.0, 98 * hour),
+        ];
+        let decisions = [
+            decision("0xA", "m1"),
+            decision("0xb", "m2"),
+            decision("0xa", "m3"),
+            decision("0xc", "m3"),
+        ];
+        let holdings = [
+            holding("m1", 10 * hour),
+            holding("m2", 20 * hour),
+            holding("m3", 30 * hour),
+        ];
+        let markets = HashMap::from([(
+            "m1".to_string(),
+            Market {
+                id: "m1".to_string(),
+                yes_price: 0.5,
+                no_price: 0.5,
+                end_date: Some(now / 1000 + 3600),
+                ..Default::default()
+            },
+        )]);
+
+        let ages = compute(
+            &holdings,
+            &decisions,
+            &trades,
+            &markets,
+            Duration::from_secs(6 * 3600),
+            now,
+        );
+        let ids: Vec<&str> = ages.iter().map(|a| a.market_id.as_str()).collect();
+        assert_eq!(ids, ["m1", "m2", "m3"]);
+        assert_eq!(ages[0].held_ms, 90 * hour);
+        assert_eq!(ages[0].leader_exited_at, Some(80 * hour));
+        assert_eq!(ages[0].ends_in_ms, Some(hour));
+        assert!(ages[0].stale);
+        assert_eq!((ages[1].leader_exited_at, ages[1].stale), (None, false));
+        // The last of m3's leaders left only 2h ago
+        assert_eq!(ages[2].since_leader_exit_ms, Some(2 * hour));
+        assert!(!ages[2].stale);
+        assert!(!compute(&holdings, &decisions, &trades, &markets, Duration::ZERO, now)[0].stale);
+    }
+}
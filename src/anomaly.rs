@@ -0,0 +1,326 @@
+//! Self-monitoring for a copy pipeline that has gone quiet for no reason.
+//!
+//! Crashes and feed outages announce themselves; a bot that keeps running
+//! but stops copying doesn't. The detector counts what each stage did over
+//! the last `anomaly_window` and raises a critical alert when the counts
+//! don't add up:
+//!
+//! - **no_decisions**: at least `anomaly_min_events` leader trades arrived
+//!   but none reached a copy or skip decision
+//! - **no_orders**: at least `anomaly_min_events` copies were started but
+//!   no order was accepted by the exchange
+//! - **single_skip_reason**: at least `anomaly_min_events` decisions, every
+//!   one skipped for the same reason (pauses and trading hours excepted,
+//!   since those are the operator's choice)
+//!
+//! Each alert is followed by a `recovered` notification once the counts
+//! look normal again.
+
+use crate::notify::{Notification, Notifications};
+use crate::types::{Config, SkipReason};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnomalyRule {
+    NoDecisions,
+    NoOrders,
+    SingleSkipReason,
+}
+
+impl AnomalyRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyRule::NoDecisions => "no_decisions",
+            AnomalyRule::NoOrders => "no_orders",
+            AnomalyRule::SingleSkipReason => "single_skip_reason",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub rule: AnomalyRule,
+    pub summary: String,
+}
+
+impl Anomaly {
+    pub fn notification(&self) -> Notification {
+        Notification::Anomaly {
+            rule: self.rule.as_str().to_string(),
+            summary: self.summary.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnomalyChange {
+    Raised(Anomaly),
+    Cleared { anomaly: Anomaly, lasted: Duration },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    TradeSeen,
+    /// `None` for a copy
+    Decision(Option<SkipReason>),
+    CopyStarted,
+    OrderPlaced,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Unix ms and what happened, within the window
+    events: VecDeque<(i64, Event)>,
+    /// Active anomalies and when they were raised
+    active: BTreeMap<AnomalyRule, (i64, Anomaly)>,
+}
+
+/// Counts pipeline stages over a sliding window and flags the patterns
+/// above.
+pub struct AnomalyDetector {
+    window_ms: i64,
+    min_events: usize,
+    notifications: Notifications,
+    state: Mutex<State>,
+}
+
+impl AnomalyDetector {
+    /// A zero `window` disables the detector.
+    pub fn new(window: Duration, min_events: usize) -> Self {
+        Self {
+            window_ms: window.as_millis() as i64,
+            min_events: min_events.max(1),
+            notifications: Notifications::default(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.anomaly_window, config.anomaly_min_events as usize)
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    fn push(&self, now_ms: i64, event: Event) {
+        if self.window_ms > 0 {
+            self.state.lock().unwrap().events.push_back((now_ms, event));
+        }
+    }
+
+    /// A leader trade reached the trade loop.
+    pub fn trade_seen(&self, now_ms: i64) {
+        self.push(now_ms, Event::TradeSeen);
+    }
+
+    /// A trade was decided on; `skip` is the reason it wasn't copied.
+    pub fn decision(&self, now_ms: i64, skip: Option<SkipReason>) {
+        self.push(now_ms, Event::Decision(skip));
+    }
+
+    /// A copy went to execution, after any approval.
+    pub fn copy_started(&self, now_ms: i64) {
+        self.push(now_ms, Event::CopyStarted);
+    }
+
+    /// The exchange accepted a copy order.
+    pub fn order_placed(&self, now_ms: i64) {
+        self.push(now_ms, Event::OrderPlaced);
+    }
+
+    /// Anomalies that were raised or cleared by `now_ms`.
+    pub fn evaluate(&self, now_ms: i64) -> Vec<AnomalyChange> {
+        let mut state = self.state.lock().unwrap();
+        while state.events.front().is_some_and(|(at, _)| now_ms - at > self.window_ms) {
+            state.events.pop_front();
+        }
+
+        let count = |wanted: fn(&Event) -> bool| state.events.iter().filter(|(_, e)| wanted(e)).count();
+        let trades = count(|e| *e == Event::TradeSeen);
+        let decisions = count(|e| matches!(e, Event::Decision(_)));
+        let copies = count(|e| *e == Event::CopyStarted);
+        let placed = count(|e| *e == Event::OrderPlaced);
+        let window = crate::units::format_duration(Duration::from_millis(self.window_ms as u64));
+
+        let mut found = Vec::new();
+        if trades >= self.min_events && decisions == 0 {
+            found.push(Anomaly {
+                rule: AnomalyRule::NoDecisions,
+                summary: format!(
+                    "{} leader trades arrived in the last {} but none reached a decision",
+                    trades, window
+                ),
+            });
+        }
+        if copies >= self.min_events && placed == 0 {
+            found.push(Anomaly {
+                rule: AnomalyRule::NoOrders,
+                summary: format!(
+                    "{} copies started in the last {} but the exchange accepted no orders",
+                    copies, window
+                ),
+            });
+        }
+        if decisions >= self.min_events {
+            if let Some(reason) = Self::single_skip_reason(&state.events) {
+                found.push(Anomaly {
+                    rule: AnomalyRule::SingleSkipReason,
+                    summary: format!(
+                        "All {} decisions in the last {} skipped the trade as {}",
+                        decisions,
+                        window,
+                        reason.as_str()
+                    ),
+                });
+            }
+        }
+
+        let mut changes = Vec::new();
+        let cleared: Vec<AnomalyRule> = state
+            .active
+            .keys()
+            .filter(|rule| !found.iter().any(|a| a.rule == **rule))
+            .copied()
+            .collect();
+        for rule in cleared {
+            if let Some((since, anomaly)) = state.active.remove(&rule) {
+                let lasted = Duration::from_millis((now_ms - since).max(0) as u64);
+                changes.push(AnomalyChange::Cleared { anomaly, lasted });
+            }
+        }
+        for anomaly in found {
+            if let Entry::Vacant(entry) = state.active.entry(anomaly.rule) {
+                entry.insert((now_ms, anomaly.clone()));
+                changes.push(AnomalyChange::Raised(anomaly));
+            }
+        }
+        changes
+    }
+
+    /// The one reason every decision in `events` was skipped for, unless
+    /// any was copied or the reason is an operator choice.
+    fn single_skip_reason(events: &VecDeque<(i64, Event)>) -> Option<SkipReason> {
+        let mut reasons = events.iter().filter_map(|(_, e)| match e {
+            Event::Decision(skip) => Some(*skip),
+            _ => None,
+        });
+        let first = reasons.next()??;
+        if matches!(first, SkipReason::Paused | SkipReason::OutsideTradingWindow) {
+            return None;
+        }
+        reasons.all(|r| r == Some(first)).then_some(first)
+    }
+
+    /// Checks the counts every half minute and alerts on changes; does
+    /// nothing when disabled.
+    pub fn spawn(self: Arc<Self>) {
+        if self.window_ms <= 0 {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                for change in self.evaluate(chrono::Utc::now().timestamp_millis()) {
+                    match change {
+                        AnomalyChange::Raised(anomaly) => {
+                            tracing::error!("🚨 Anomaly {}: {}", anomaly.rule.as_str(), anomaly.summary);
+                            self.notifications.send(anomaly.notification());
+                        }
+                        AnomalyChange::Cleared { anomaly, lasted } => {
+                            tracing::info!("✅ Anomaly {} cleared", anomaly.rule.as_str());
+                            self.notifications.send(Notification::Recovered {
+                                down_secs: lasted.as_secs(),
+                                last: Box::new(anomaly.notification()),
+                            });
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+
+    fn rules(changes: &[AnomalyChange]) -> Vec<(bool, AnomalyRule)> {
+        changes
+            .iter()
+            .map(|c| match c {
+                AnomalyChange::Raised(a) => (true, a.rule),
+                AnomalyChange::Cleared { anomaly, .. } => (false, anomaly.rule),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trades_without_decisions() {
+        let detector = AnomalyDetector::new(Duration::from_secs(30 * 60), 3);
+        for at in 0..3 {
+            detector.trade_seen(at * MINUTE);
+        }
+        assert_eq!(
+            rules(&detector.evaluate(5 * MINUTE)),
+            vec![(true, AnomalyRule::NoDecisions)]
+        );
+        assert!(detector.evaluate(6 * MINUTE).is_empty());
+
+        detector.trade_seen(7 * MINUTE);
+        detector.decision(7 * MINUTE, None);
+        let changes = detector.evaluate(8 * MINUTE);
+        assert!(matches!(
+            &changes[..],
+            [AnomalyChange::Cleared { lasted, .. }] if *lasted == Duration::from_secs(180)
+        ));
+    }
+
+    #[test]
+    fn test_copies_without_orders() {
+        let detector = AnomalyDetector::new(Duration::from_secs(10 * 60), 2);
+        detector.copy_started(0);
+        detector.copy_started(MINUTE);
+        assert_eq!(
+            rules(&detector.evaluate(2 * MINUTE)),
+            vec![(true, AnomalyRule::NoOrders)]
+        );
+        // Both copies fall out of the window
+        assert_eq!(
+            rules(&detector.evaluate(12 * MINUTE)),
+            vec![(false, AnomalyRule::NoOrders)]
+        );
+    }
+
+    #[test]
+    fn test_single_skip_reason() {
+        let detector = AnomalyDetector::new(Duration::from_secs(30 * 60), 3);
+        for at in 0..3 {
+            detector.decision(at, Some(SkipReason::Stale));
+        }
+        let changes = detector.evaluate(MINUTE);
+        assert_eq!(rules(&changes), vec![(true, AnomalyRule::SingleSkipReason)]);
+        assert!(matches!(&changes[0], AnomalyChange::Raised(a) if a.summary.contains("as stale")));
+
+        // One copy breaks the pattern
+        detector.decision(2 * MINUTE, None);
+        assert_eq!(
+            rules(&detector.evaluate(2 * MINUTE)),
+            vec![(false, AnomalyRule::SingleSkipReason)]
+        );
+
+        // Pauses are deliberate
+        let paused = AnomalyDetector::new(Duration::from_secs(30 * 60), 3);
+        for at in 0..5 {
+            paused.decision(at, Some(SkipReason::Paused));
+        }
+        assert!(paused.evaluate(MINUTE).is_empty());
+    }
+}
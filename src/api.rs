@@ -1,4 +1,4 @@
-use crate::types::{Market, Trade, OrderRequest, OrderResponse, TradeSide};
+use crate::types::{ExchangeOrder, LeaderboardEntry, Market, Trade, OrderRequest, OrderResponse, TokenBalance, TradeSide};
 use anyhow::{Context, Result};
 use reqwest::Client;
 use serde_json::json;
@@ -23,18 +23,64 @@ impl PolymarketApi {
             .send()
             .await
             .context("Failed to fetch market")?
+            .error_for_status()?
             .json::<serde_json::Value>()
             .await?;
         
-        Ok(Market {
-            id: market_id.to_string(),
-            event_id: resp["event_id"].as_str().unwrap_or("").to_string(),
-            question: resp["question"].as_str().unwrap_or("").to_string(),
-            yes_price: resp["yes_price"].as_f64().unwrap_or(0.5),
-            no_price: resp["no_price"].as_f64().unwrap_or(0.5),
-            liquidity: resp["liquidity"].as_f64().unwrap_or(0.0),
-            volume_24h: resp["volume_24h"].as_f64().unwrap_or(0.0),
-        })
+        Ok(parse_market(market_id, &resp))
+    }
+    
+    /// What one share of the market's first outcome (the one positions are
+    /// marked by) pays out once the market has resolved; `None` while it
+    /// hasn't.
+    pub async fn get_resolution(&self, market_id: &str) -> Result<Option<f64>> {
+        let url = format!("{}/markets/{}", self.base_url, market_id);
+        let resp = self.client.get(&url)
+            .send()
+            .await
+            .context("Failed to fetch market resolution")?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        
+        Ok(parse_resolution(&resp))
+    }
+    
+    /// The market's CTF condition id (0x hex), which redeeming it needs.
+    pub async fn get_condition_id(&self, market_id: &str) -> Result<String> {
+        let url = format!("{}/markets/{}", self.base_url, market_id);
+        let resp = self.client.get(&url)
+            .send()
+            .await
+            .context("Failed to fetch market")?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        
+        resp["condition_id"].as_str()
+            .or_else(|| resp["conditionId"].as_str())
+            .map(|id| id.to_string())
+            .with_context(|| format!("Market {} has no condition id", market_id))
+    }
+    
+    /// Markets whose question or slug matches `query`, at most `limit`.
+    pub async fn search_markets(&self, query: &str, limit: usize) -> Result<Vec<Market>> {
+        let url = format!("{}/markets", self.base_url);
+        let resp = self.client.get(&url)
+            .query(&[("search", query), ("limit", &limit.to_string())])
+            .send()
+            .await
+            .context("Failed to search markets")?
+            .error_for_status()?
+            .json::<Vec<serde_json::Value>>()
+            .await?;
+        
+        Ok(resp.iter()
+            .filter_map(|item| {
+                let id = item["id"].as_str().or_else(|| item["condition_id"].as_str())?;
+                Some(parse_market(id, item))
+            })
+            .collect())
     }
     
     pub async fn get_trades(&self, wallet: &str, since: i64) -> Result<Vec<Trade>> {
@@ -68,6 +114,42 @@ impl PolymarketApi {
         Ok(trades)
     }
     
+    /// A token's price every `fidelity_minutes` between two unix times, as
+    /// (unix seconds, price), oldest first.
+    pub async fn get_price_history(
+        &self,
+        token_id: &str,
+        start_ts: i64,
+        end_ts: i64,
+        fidelity_minutes: u64,
+    ) -> Result<Vec<(i64, f64)>> {
+        let url = format!("{}/prices-history", self.base_url);
+        let resp = self.client.get(&url)
+            .query(&[
+                ("market", token_id),
+                ("startTs", &start_ts.to_string()),
+                ("endTs", &end_ts.to_string()),
+                ("fidelity", &fidelity_minutes.to_string()),
+            ])
+            .send()
+            .await
+            .context("Failed to fetch price history")?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        
+        let mut points: Vec<(i64, f64)> = resp["history"]
+            .as_array()
+            .map(|points| {
+                points.iter()
+                    .filter_map(|p| Some((p["t"].as_i64()?, p["p"].as_f64()?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        points.sort_by_key(|(t, _)| *t);
+        Ok(points)
+    }
+    
     pub async fn get_orderbook(&self, market_id: &str) -> Result<(Vec<(f64, f64)>, Vec<(f64, f64)>)> {
         let url = format!("{}/orderbook/{}", self.base_url, market_id);
         let resp = self.client.get(&url)
@@ -105,6 +187,23 @@ impl PolymarketApi {
     }
     
     pub async fn place_order(&self, req: OrderRequest, api_key: &str) -> Result<OrderResponse> {
+        let request = self.order_request(&req, api_key)?;
+        let resp = self.client.execute(request)
+            .await
+            .context("Failed to place order")?
+            .json::<serde_json::Value>()
+            .await?;
+        
+        Ok(OrderResponse {
+            order_id: resp["order_id"].as_str().unwrap_or("").to_string(),
+            status: resp["status"].as_str().unwrap_or("").to_string(),
+            filled_shares: resp["filled_shares"].as_f64().unwrap_or(0.0),
+            avg_fill_price: resp["avg_fill_price"].as_f64().unwrap_or(0.0),
+        })
+    }
+    
+    /// The authenticated request [`place_order`](Self::place_order) sends, built but not sent.
+    pub fn order_request(&self, req: &OrderRequest, api_key: &str) -> Result<reqwest::Request> {
         let url = format!("{}/orders", self.base_url);
         
         let body = json!({
@@ -116,23 +215,26 @@ impl PolymarketApi {
             "shares": req.shares,
             "price": req.price,
             "type": format!("{:?}", req.order_type),
+            "client_order_id": req.client_order_id,
         });
         
-        let resp = self.client.post(&url)
+        self.client.post(&url)
             .header("Authorization", format!("Bearer {}", api_key))
             .json(&body)
+            .build()
+            .context("Failed to build the order request")
+    }
+    
+    pub async fn cancel_order(&self, order_id: &str, api_key: &str) -> Result<()> {
+        let url = format!("{}/orders/{}", self.base_url, order_id);
+        self.client.delete(&url)
+            .header("Authorization", format!("Bearer {}", api_key))
             .send()
             .await
-            .context("Failed to place order")?
-            .json::<serde_json::Value>()
-            .await?;
-        
-        Ok(OrderResponse {
-            order_id: resp["order_id"].as_str().unwrap_or("").to_string(),
-            status: resp["status"].as_str().unwrap_or("").to_string(),
-            filled_shares: resp["filled_shares"].as_f64().unwrap_or(0.0),
-            avg_fill_price: resp["avg_fill_price"].as_f64().unwrap_or(0.0),
-        })
+            .context("Failed to cancel order")?
+            .error_for_status()
+            .context("Exchange refused to cancel the order")?;
+        Ok(())
     }
     
     pub async fn get_balance(&self, wallet: &str) -> Result<f64> {
@@ -146,4 +248,196 @@ impl PolymarketApi {
         
         Ok(resp["balance"].as_f64().unwrap_or(0.0))
     }
+    
+    /// Checks that the exchange accepts `api_key` by listing the account's
+    /// open orders with it.
+    pub async fn verify_credentials(&self, wallet: &str, api_key: &str) -> Result<()> {
+        let url = format!("{}/orders", self.base_url);
+        self.client.get(&url)
+            .query(&[("wallet", wallet), ("status", "open")])
+            .header("Authorization", format!("Bearer {}", api_key))
+            .send()
+            .await
+            .context("Failed to reach the exchange")?
+            .error_for_status()
+            .context("Exchange rejected the credentials")?;
+        Ok(())
+    }
+    
+    pub async fn get_open_orders(&self, wallet: &str) -> Result<Vec<ExchangeOrder>> {
+        let url = format!("{}/orders", self.base_url);
+        let resp = self.client.get(&url)
+            .query(&[("wallet", wallet), ("status", "open")])
+            .send()
+            .await
+            .context("Failed to fetch open orders")?
+            .json::<Vec<serde_json::Value>>()
+            .await?;
+        
+        Ok(resp.iter().map(parse_exchange_order).collect())
+    }
+    
+    pub async fn get_order(&self, order_id: &str) -> Result<ExchangeOrder> {
+        let url = format!("{}/orders/{}", self.base_url, order_id);
+        let resp = self.client.get(&url)
+            .send()
+            .await
+            .context("Failed to fetch order")?
+            .json::<serde_json::Value>()
+            .await?;
+        
+        let mut order = parse_exchange_order(&resp);
+        if order.order_id.is_empty() {
+            order.order_id = order_id.to_string();
+        }
+        Ok(order)
+    }
+    
+    /// Looks up an order (open or closed) by the id we submitted it with.
+    /// `None` means the exchange never accepted it.
+    pub async fn get_order_by_client_id(&self, client_order_id: &str) -> Result<Option<ExchangeOrder>> {
+        let url = format!("{}/orders", self.base_url);
+        let resp = self.client.get(&url)
+            .query(&[("client_order_id", client_order_id)])
+            .send()
+            .await
+            .context("Failed to look up order")?
+            .error_for_status()?
+            .json::<Vec<serde_json::Value>>()
+            .await?;
+        
+        Ok(resp.iter()
+            .map(parse_exchange_order)
+            .find(|o| o.client_order_id.as_deref() == Some(client_order_id)))
+    }
+    
+    /// The top `limit` wallets by PnL over `window` (day, week, month or all).
+    pub async fn get_leaderboard(&self, window: &str, limit: usize) -> Result<Vec<LeaderboardEntry>> {
+        let url = format!("{}/leaderboard", self.base_url);
+        let resp = self.client.get(&url)
+            .query(&[("window", window), ("limit", &limit.to_string())])
+            .send()
+            .await
+            .context("Failed to fetch the leaderboard")?
+            .error_for_status()?
+            .json::<Vec<serde_json::Value>>()
+            .await?;
+        
+        Ok(resp.iter()
+            .filter_map(|item| {
+                let wallet = item["wallet"].as_str().or_else(|| item["proxyWallet"].as_str())?;
+                Some(LeaderboardEntry {
+                    wallet: wallet.to_string(),
+                    name: item["name"].as_str()
+                        .or_else(|| item["userName"].as_str())
+                        .filter(|n| !n.is_empty())
+                        .map(|n| n.to_string()),
+                    pnl: item["pnl"].as_f64().unwrap_or(0.0),
+                    volume: item["volume"].as_f64().or_else(|| item["vol"].as_f64()).unwrap_or(0.0),
+                })
+            })
+            .collect())
+    }
+    
+    /// Outcome token holdings as the exchange sees them.
+    pub async fn get_positions(&self, wallet: &str) -> Result<Vec<TokenBalance>> {
+        let url = format!("{}/positions/{}", self.base_url, wallet);
+        let resp = self.client.get(&url)
+            .send()
+            .await
+            .context("Failed to fetch positions")?
+            .json::<Vec<serde_json::Value>>()
+            .await?;
+        
+        Ok(resp.iter()
+            .map(|item| TokenBalance {
+                market_id: item["market_id"].as_str().unwrap_or("").to_string(),
+                token_id: item["token_id"].as_str().map(|s| s.to_string()),
+                shares: item["shares"].as_f64().unwrap_or(0.0),
+                avg_price: item["avg_price"].as_f64(),
+            })
+            .collect())
+    }
+}
+
+/// Lists arrive either as JSON arrays or JSON-encoded inside a string.
+fn parse_market(market_id: &str, resp: &serde_json::Value) -> Market {
+    Market {
+        id: market_id.to_string(),
+        event_id: resp["event_id"].as_str().unwrap_or("").to_string(),
+        question: resp["question"].as_str().unwrap_or("").to_string(),
+        yes_price: resp["yes_price"].as_f64().unwrap_or(0.5),
+        no_price: resp["no_price"].as_f64().unwrap_or(0.5),
+        liquidity: resp["liquidity"].as_f64().unwrap_or(0.0),
+        volume_24h: resp["volume_24h"].as_f64().unwrap_or(0.0),
+        slug: resp["slug"].as_str().or_else(|| resp["market_slug"].as_str()).unwrap_or("").to_string(),
+        outcomes: string_list(&resp["outcomes"]),
+        token_ids: string_list(resp.get("token_ids").unwrap_or(&resp["clob_token_ids"])),
+        tick_size: resp["tick_size"].as_f64()
+            .or_else(|| resp["minimum_tick_size"].as_f64())
+            .unwrap_or(0.01),
+        end_date: unix_seconds(&resp["end_date"]),
+        category: resp["category"].as_str().unwrap_or("").to_string(),
+        resolution_source: resp["resolution_source"].as_str().unwrap_or("").to_string(),
+    }
+}
+
+/// A closed market has resolved once it names a winner or its outcome prices
+/// have settled at 0 and 1; until then it may still be disputed.
+fn parse_resolution(resp: &serde_json::Value) -> Option<f64> {
+    let closed = resp["resolved"].as_bool().or_else(|| resp["closed"].as_bool()).unwrap_or(false);
+    if !closed {
+        return None;
+    }
+    let winner = resp["winning_outcome"].as_str().or_else(|| resp["winner"].as_str());
+    if let Some(winner) = winner {
+        let first = string_list(&resp["outcomes"]).into_iter().next().unwrap_or_else(|| "Yes".to_string());
+        return Some(if winner.eq_ignore_ascii_case(&first) { 1.0 } else { 0.0 });
+    }
+    let prices = resp.get("outcome_prices").unwrap_or(&resp["outcomePrices"]);
+    let payout = match prices {
+        serde_json::Value::Array(items) => items.first()
+            .and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok())),
+        _ => string_list(prices).first().and_then(|p| p.parse().ok()),
+    }?;
+    (payout == 0.0 || payout == 1.0).then_some(payout)
+}
+
+pub(crate) fn string_list(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::Array(items) => items.iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        serde_json::Value::String(s) => serde_json::from_str(s).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// A number the exchange's APIs may send as a string or a number.
+pub(crate) fn number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
+/// Unix seconds, or an RFC 3339 date.
+pub(crate) fn unix_seconds(value: &serde_json::Value) -> Option<i64> {
+    value.as_i64().or_else(|| {
+        let s = value.as_str()?;
+        chrono::DateTime::parse_from_rfc3339(s).ok().map(|d| d.timestamp())
+    })
+}
+
+fn parse_exchange_order(item: &serde_json::Value) -> ExchangeOrder {
+    ExchangeOrder {
+        order_id: item["order_id"].as_str().unwrap_or("").to_string(),
+        market_id: item["market_id"].as_str().unwrap_or("").to_string(),
+        side: item["side"].as_str().and_then(TradeSide::parse).unwrap_or(TradeSide::BUY),
+        shares: item["shares"].as_f64().unwrap_or(0.0),
+        filled_shares: item["filled_shares"].as_f64().unwrap_or(0.0),
+        avg_fill_price: item["avg_fill_price"].as_f64().unwrap_or(0.0),
+        status: item["status"].as_str().unwrap_or("").to_string(),
+        client_order_id: item["client_order_id"].as_str().map(|s| s.to_string()),
+    }
 }
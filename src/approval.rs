@@ -0,0 +1,207 @@
+//! Manual approval for large copies.
+//!
+//! Copies of `approval_threshold` or more are decided as usual but parked
+//! here instead of executed, and the operator is asked (with Approve /
+//! Reject / Half size buttons in Telegram). The verdict comes back to the
+//! bot's trade loop, which executes or skips the copy; copies nobody
+//! answers within `approval_timeout` expire and are skipped.
+
+use crate::types::{Config, Trade};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// What happened to a parked copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Approve,
+    Reject,
+    /// Approve at half the decided size
+    HalfSize,
+    /// Nobody answered in time
+    Expired,
+}
+
+impl Verdict {
+    /// Parses an operator's answer; `Expired` is never an answer.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "approve" => Some(Verdict::Approve),
+            "reject" => Some(Verdict::Reject),
+            "half" => Some(Verdict::HalfSize),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Approve => "approve",
+            Verdict::Reject => "reject",
+            Verdict::HalfSize => "half",
+            Verdict::Expired => "expired",
+        }
+    }
+}
+
+/// A decided copy waiting for the operator.
+#[derive(Debug, Clone)]
+pub struct PendingCopy {
+    pub id: u64,
+    pub trade: Trade,
+    pub trade_id: Option<i64>,
+    pub decision_id: Option<i64>,
+    pub size_usd: f64,
+    pub shares: f64,
+    /// Unix ms
+    pub expires_at: i64,
+}
+
+/// Copies awaiting approval, shared by the bot and the command handlers.
+pub struct Approvals {
+    threshold: f64,
+    timeout: Duration,
+    pending: Mutex<BTreeMap<u64, PendingCopy>>,
+    next_id: AtomicU64,
+    verdicts: mpsc::UnboundedSender<(PendingCopy, Verdict)>,
+    receiver: tokio::sync::Mutex<mpsc::UnboundedReceiver<(PendingCopy, Verdict)>>,
+}
+
+impl Approvals {
+    /// A zero `threshold` never asks.
+    pub fn new(threshold: f64, timeout: Duration) -> Self {
+        let (verdicts, receiver) = mpsc::unbounded_channel();
+        Self {
+            threshold,
+            timeout,
+            pending: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            verdicts,
+            receiver: tokio::sync::Mutex::new(receiver),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.approval_threshold, config.approval_timeout)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Whether a copy of `size_usd` needs the operator's approval.
+    pub fn required(&self, size_usd: f64) -> bool {
+        self.threshold > 0.0 && size_usd >= self.threshold
+    }
+
+    /// Parks a copy and returns it with its id and expiry filled in.
+    pub fn submit(
+        &self,
+        trade: Trade,
+        trade_id: Option<i64>,
+        decision_id: Option<i64>,
+        size_usd: f64,
+        shares: f64,
+        now_ms: i64,
+    ) -> PendingCopy {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let pending = PendingCopy {
+            id,
+            trade,
+            trade_id,
+            decision_id,
+            size_usd,
+            shares,
+            expires_at: now_ms + self.timeout.as_millis() as i64,
+        };
+        self.pending.lock().unwrap().insert(id, pending.clone());
+        pending
+    }
+
+    pub fn pending(&self) -> Vec<PendingCopy> {
+        self.pending.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Applies an operator's answer and returns the reply for them.
+    pub fn resolve(&self, id: u64, verdict: Verdict, now_ms: i64) -> String {
+        let Some(pending) = self.pending.lock().unwrap().remove(&id) else {
+            return format!("Copy #{} is no longer waiting for approval", id);
+        };
+        let (verdict, reply) = if now_ms >= pending.expires_at {
+            (Verdict::Expired, format!("Copy #{} already expired", id))
+        } else {
+            let reply = match verdict {
+                Verdict::Approve => format!("✅ Copy #{} approved (${:.2})", id, pending.size_usd),
+                Verdict::HalfSize => format!("✅ Copy #{} approved at half size (${:.2})", id, pending.size_usd / 2.0),
+                Verdict::Reject | Verdict::Expired => format!("🚫 Copy #{} rejected", id),
+            };
+            (verdict, reply)
+        };
+        let _ = self.verdicts.send((pending, verdict));
+        reply
+    }
+
+    /// Expires every copy whose time ran out by `now_ms`.
+    pub fn expire(&self, now_ms: i64) {
+        let mut pending = self.pending.lock().unwrap();
+        let expired: Vec<u64> = pending
+            .values()
+            .filter(|p| now_ms >= p.expires_at)
+            .map(|p| p.id)
+            .collect();
+        for id in expired {
+            if let Some(p) = pending.remove(&id) {
+                let _ = self.verdicts.send((p, Verdict::Expired));
+            }
+        }
+    }
+
+    /// The next resolved copy, for the bot's trade loop.
+    pub async fn next_verdict(&self) -> Option<(PendingCopy, Verdict)> {
+        self.receiver.lock().await.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TradeSide;
+
+    fn trade() -> Trade {
+        Trade {
+            wallet: "0xabc".to_string(),
+            event_id: "e1".to_string(),
+            market_id: "m1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.5,
+            timestamp: 0,
+            tx_hash: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_and_expire() {
+        let approvals = Approvals::new(100.0, Duration::from_secs(60));
+        assert!(!approvals.required(99.0));
+        assert!(approvals.required(100.0));
+
+        let first = approvals.submit(trade(), None, None, 150.0, 300.0, 0);
+        let second = approvals.submit(trade(), None, None, 200.0, 400.0, 0);
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.expires_at, 60_000);
+
+        assert!(approvals.resolve(first.id, Verdict::HalfSize, 1_000).contains("$75.00"));
+        assert!(approvals
+            .resolve(first.id, Verdict::Approve, 2_000)
+            .contains("no longer waiting"));
+        let (copy, verdict) = approvals.next_verdict().await.unwrap();
+        assert_eq!((copy.id, verdict), (first.id, Verdict::HalfSize));
+
+        approvals.expire(60_000);
+        let (copy, verdict) = approvals.next_verdict().await.unwrap();
+        assert_eq!((copy.id, verdict), (second.id, Verdict::Expired));
+        assert!(approvals.pending().is_empty());
+    }
+}
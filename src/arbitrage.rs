@@ -0,0 +1,578 @@
+//! Price gaps between equivalent Polymarket and Kalshi markets.
+//!
+//! `arb_pairs` maps Polymarket markets (slug or id) to the Kalshi markets
+//! asking the same question, `slug=TICKER`, yes meaning yes on both; with
+//! `arb_auto_pair` on, the most active markets of both venues are also
+//! paired by their questions and end dates (see [`match_markets`]).
+//!
+//! Every `arb_interval` [`ArbitrageDetector`] reads both books of each pair.
+//! Buying yes on one venue and no on the other pays $1 a share whatever
+//! the outcome, so when the two asks plus fees cost less than $1 by
+//! `arb_min_edge` or more, the pair is an [`Opportunity`]: published as a
+//! [`BotEvent::Arbitrage`] and sent as a [`Notification::Arbitrage`],
+//! once until the gap closes. Fees per share are the venue's rate times
+//! p × (1 − p), the shape of Kalshi's taker fee (`arb_kalshi_fee`, 7%) and
+//! of Polymarket's where it charges one (`arb_polymarket_fee`).
+//!
+//! Size is what both best levels offer, in whole shares as Kalshi takes,
+//! capped at `arb_max_stake` dollars. With `arb_auto_execute` on (and not
+//! paper trading), opportunities go to the bot's subscribers (see
+//! [`ArbitrageDetector::subscribe`]), which take them the way copies are
+//! placed: risk checked, intent journaled, fills booked into the portfolio.
+//! The legs are fill-and-kill, taken one after the other (see
+//! [`Opportunity::legs`]); the second is sized to what the first filled.
+
+use crate::events::{BotEvent, EventBus};
+use crate::exchange::{kalshi, Exchange, KalshiApi, PolymarketExchange};
+use crate::gamma::{self, Query};
+use crate::markets::MarketCache;
+use crate::notify::{Notification, Notifications};
+use crate::search;
+use crate::types::{Config, Market, OrderBook, Venue};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// How often automatic pairs are looked for again.
+const PAIR_REFRESH: Duration = Duration::from_secs(3600);
+
+/// Most active markets of each venue automatic pairing reads.
+const PAIR_CANDIDATES: usize = 500;
+
+/// Word overlap two questions need to be paired automatically.
+pub const MIN_SIMILARITY: f64 = 0.6;
+
+/// How far apart paired markets' end dates may be.
+const END_DATE_SLACK_SECS: i64 = 3 * 86_400;
+
+/// Words too common in questions to tell markets apart.
+const STOP_WORDS: &[&str] = &["will", "the", "be", "a", "an", "of", "in", "on", "by", "to", "at", "for", "before"];
+
+/// A Polymarket market and the Kalshi market asking the same question.
+#[derive(Debug, Clone)]
+pub struct VenuePair {
+    pub polymarket: Market,
+    pub kalshi: Market,
+}
+
+/// One order of an opportunity: buying `token` at `price` on `venue`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Leg {
+    pub venue: Venue,
+    pub token: String,
+    /// "yes" or "no"
+    pub outcome: String,
+    pub price: f64,
+}
+
+/// Yes on one venue and no on the other, for less than the $1 it pays.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Opportunity {
+    pub polymarket_id: String,
+    pub kalshi_ticker: String,
+    pub question: String,
+    pub yes: Leg,
+    pub no: Leg,
+    /// Both asks and both fees, per share
+    pub cost: f64,
+    /// 1 - cost
+    pub edge: f64,
+    pub shares: f64,
+    /// Unix ms
+    pub at: i64,
+}
+
+impl Opportunity {
+    /// Profit at the sized shares, in dollars.
+    pub fn profit(&self) -> f64 {
+        self.edge * self.shares
+    }
+
+    /// Both legs in the order they are taken: Kalshi's first, as it fills
+    /// whole contracts only, so the Polymarket leg can match what it got.
+    pub fn legs(&self) -> [&Leg; 2] {
+        if self.no.venue == Venue::Kalshi {
+            [&self.no, &self.yes]
+        } else {
+            [&self.yes, &self.no]
+        }
+    }
+
+    fn key(&self) -> (String, Venue) {
+        (self.polymarket_id.clone(), self.yes.venue)
+    }
+}
+
+impl From<&Opportunity> for Notification {
+    fn from(opportunity: &Opportunity) -> Self {
+        Notification::Arbitrage {
+            market_id: opportunity.polymarket_id.clone(),
+            question: opportunity.question.clone(),
+            yes_venue: opportunity.yes.venue.as_str().to_string(),
+            no_venue: opportunity.no.venue.as_str().to_string(),
+            cost: opportunity.cost,
+            shares: opportunity.shares,
+        }
+    }
+}
+
+/// Fee rates per venue, as fractions of p × (1 − p).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fees {
+    pub polymarket: f64,
+    pub kalshi: f64,
+}
+
+impl Fees {
+    pub fn per_share(&self, venue: Venue, price: f64) -> f64 {
+        let rate = match venue {
+            Venue::Polymarket => self.polymarket,
+            Venue::Kalshi => self.kalshi,
+        };
+        rate * price * (1.0 - price)
+    }
+}
+
+/// (price, shares)
+type Level = (f64, f64);
+
+/// The best ask of yes and of no from yes's book: no is asked at 1 minus
+/// yes's best bid.
+fn asks(book: &OrderBook) -> (Option<Level>, Option<Level>) {
+    let yes = book.asks.first().copied();
+    let no = book.bids.first().map(|(price, size)| (1.0 - price, *size));
+    (yes, no)
+}
+
+/// The better of the two ways to hedge `pair` at these books, if it clears
+/// `min_edge` after fees with at least one whole share under `max_stake`.
+pub fn evaluate(
+    pair: &VenuePair,
+    polymarket: &OrderBook,
+    kalshi: &OrderBook,
+    fees: Fees,
+    min_edge: f64,
+    max_stake: f64,
+    now: i64,
+) -> Option<Opportunity> {
+    let (poly_yes, poly_no) = asks(polymarket);
+    let (kalshi_yes, kalshi_no) = asks(kalshi);
+    let poly_token = |i: usize| pair.polymarket.token_ids.get(i).cloned().unwrap_or_else(|| pair.polymarket.id.clone());
+    let leg = |venue: Venue, outcome: &str, price: f64| Leg {
+        venue,
+        token: match (venue, outcome) {
+            (Venue::Polymarket, "yes") => poly_token(0),
+            (Venue::Polymarket, _) => poly_token(1),
+            (Venue::Kalshi, "yes") => kalshi::token_id(&pair.kalshi.id, kalshi::Side::Yes),
+            (Venue::Kalshi, _) => kalshi::token_id(&pair.kalshi.id, kalshi::Side::No),
+        },
+        outcome: outcome.to_string(),
+        price,
+    };
+    let candidates = [
+        poly_yes.zip(kalshi_no).map(|(yes, no)| (leg(Venue::Polymarket, "yes", yes.0), leg(Venue::Kalshi, "no", no.0), yes.1.min(no.1))),
+        kalshi_yes.zip(poly_no).map(|(yes, no)| (leg(Venue::Kalshi, "yes", yes.0), leg(Venue::Polymarket, "no", no.0), yes.1.min(no.1))),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .filter_map(|(yes, no, depth)| {
+            let cost = yes.price + no.price + fees.per_share(yes.venue, yes.price) + fees.per_share(no.venue, no.price);
+            let edge = 1.0 - cost;
+            let shares = depth.min(max_stake / cost).floor();
+            (edge >= min_edge && shares >= 1.0).then(|| Opportunity {
+                polymarket_id: pair.polymarket.id.clone(),
+                kalshi_ticker: pair.kalshi.id.clone(),
+                question: pair.polymarket.question.clone(),
+                yes,
+                no,
+                cost,
+                edge,
+                shares,
+                at: now,
+            })
+        })
+        .max_by(|a, b| a.edge.total_cmp(&b.edge))
+}
+
+fn question_words(market: &Market) -> HashSet<String> {
+    search::words(&market.question)
+        .into_iter()
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+        .collect()
+}
+
+/// How alike two questions are: shared words over all their words.
+pub fn similarity(a: &Market, b: &Market) -> f64 {
+    let (a, b) = (question_words(a), question_words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Pairs of Polymarket and Kalshi markets whose questions are at least
+/// `min_similarity` alike and whose end dates, where both have one, are
+/// within three days; each market in one pair at most, most alike first.
+pub fn match_markets(polymarket: &[Market], kalshi: &[Market], min_similarity: f64) -> Vec<VenuePair> {
+    let mut scored: Vec<(f64, usize, usize)> = Vec::new();
+    for (i, p) in polymarket.iter().enumerate() {
+        for (j, k) in kalshi.iter().enumerate() {
+            if let (Some(a), Some(b)) = (p.end_date, k.end_date) {
+                if (a - b).abs() > END_DATE_SLACK_SECS {
+                    continue;
+                }
+            }
+            let score = similarity(p, k);
+            if score >= min_similarity {
+                scored.push((score, i, j));
+            }
+        }
+    }
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let (mut used_poly, mut used_kalshi) = (HashSet::new(), HashSet::new());
+    scored
+        .into_iter()
+        .filter(|(_, i, j)| used_poly.insert(*i) && used_kalshi.insert(*j))
+        .map(|(_, i, j)| VenuePair {
+            polymarket: polymarket[i].clone(),
+            kalshi: kalshi[j].clone(),
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct State {
+    manual: Vec<VenuePair>,
+    automatic: Vec<VenuePair>,
+    paired_at: Option<Instant>,
+    /// Opportunities flagged and still open, by Polymarket market and the
+    /// venue yes is bought on
+    flagged: HashSet<(String, Venue)>,
+}
+
+pub struct ArbitrageDetector {
+    markets: Arc<MarketCache>,
+    polymarket: Arc<dyn Exchange>,
+    kalshi: Arc<dyn Exchange>,
+    /// `arb_pairs` entries, resolved on the first scan
+    pair_specs: Vec<(String, String)>,
+    auto_pair: bool,
+    interval: Duration,
+    fees: Fees,
+    min_edge: f64,
+    max_stake: f64,
+    auto_execute: bool,
+    /// Opportunities to take, with `arb_auto_execute` on
+    to_take: broadcast::Sender<Opportunity>,
+    state: Mutex<State>,
+    events: Arc<EventBus>,
+    notifications: Notifications,
+}
+
+impl ArbitrageDetector {
+    pub fn new(markets: Arc<MarketCache>, polymarket: Arc<dyn Exchange>, kalshi: Arc<dyn Exchange>, config: &Config) -> Result<Self> {
+        let pair_specs = config
+            .arb_pairs
+            .iter()
+            .map(|spec| {
+                spec.split_once('=')
+                    .map(|(poly, ticker)| (poly.trim().to_string(), ticker.trim().to_string()))
+                    .filter(|(poly, ticker)| !poly.is_empty() && !ticker.is_empty())
+                    .with_context(|| format!("Expected polymarket_slug=KALSHI_TICKER, got '{}'", spec))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            markets,
+            polymarket,
+            kalshi,
+            pair_specs,
+            auto_pair: config.arb_auto_pair,
+            interval: config.arb_interval,
+            fees: Fees {
+                polymarket: config.arb_polymarket_fee,
+                kalshi: config.arb_kalshi_fee,
+            },
+            min_edge: config.arb_min_edge,
+            max_stake: config.arb_max_stake,
+            auto_execute: config.arb_auto_execute && !config.paper_trading,
+            to_take: broadcast::channel(16).0,
+            state: Mutex::new(State::default()),
+            events: Arc::new(EventBus::new()),
+            notifications: Notifications::default(),
+        })
+    }
+
+    /// `None` when `arb_interval` is zero or nothing is to be paired.
+    pub fn from_config(config: &Config, markets: Arc<MarketCache>, polymarket_api: crate::api::PolymarketApi) -> Result<Option<Self>> {
+        if config.arb_interval.is_zero() || (config.arb_pairs.is_empty() && !config.arb_auto_pair) {
+            return Ok(None);
+        }
+        let polymarket = Arc::new(PolymarketExchange::from_config(config, polymarket_api));
+        let kalshi = Arc::new(KalshiApi::from_config(config)?);
+        Self::new(markets, polymarket, kalshi, config).map(Some)
+    }
+
+    pub fn with_events(mut self, events: Arc<EventBus>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Resolves `arb_pairs` the first time, and pairs markets automatically
+    /// every [`PAIR_REFRESH`].
+    async fn pairs(&self) -> Vec<VenuePair> {
+        let (resolve, repair) = {
+            let state = self.state.lock().unwrap();
+            let resolve = state.manual.len() < self.pair_specs.len();
+            let repair = self.auto_pair && state.paired_at.is_none_or(|at| at.elapsed() >= PAIR_REFRESH);
+            (resolve, repair)
+        };
+        if resolve {
+            let mut manual = Vec::new();
+            for (poly, ticker) in &self.pair_specs {
+                let resolved = async {
+                    let polymarket = self.markets.find(poly).await?;
+                    let kalshi = self.kalshi.market(ticker).await?;
+                    anyhow::Ok(VenuePair { polymarket, kalshi })
+                };
+                match resolved.await {
+                    Ok(pair) => manual.push(pair),
+                    Err(e) => tracing::warn!("Arbitrage pair {}={} unavailable: {:#}", poly, ticker, e),
+                }
+            }
+            self.state.lock().unwrap().manual = manual;
+        }
+        if repair {
+            match self.auto_pairs().await {
+                Ok(pairs) => {
+                    tracing::info!("⚖️ Paired {} Polymarket and Kalshi markets by question", pairs.len());
+                    let mut state = self.state.lock().unwrap();
+                    state.automatic = pairs;
+                    state.paired_at = Some(Instant::now());
+                }
+                Err(e) => tracing::warn!("Automatic arbitrage pairing failed: {:#}", e),
+            }
+        }
+        let state = self.state.lock().unwrap();
+        let manual: HashSet<&str> = state.manual.iter().map(|p| p.polymarket.id.as_str()).collect();
+        state
+            .manual
+            .iter()
+            .chain(state.automatic.iter().filter(|p| !manual.contains(p.polymarket.id.as_str())))
+            .cloned()
+            .collect()
+    }
+
+    async fn auto_pairs(&self) -> Result<Vec<VenuePair>> {
+        let gamma: &Arc<gamma::Client> = self.markets.gamma().context("Automatic pairing needs GAMMA_API")?;
+        let query = Query {
+            active: Some(true),
+            closed: Some(false),
+            limit: PAIR_CANDIDATES,
+            ..Default::default()
+        };
+        let polymarket = gamma.markets(&query).await?;
+        let mut kalshi = self.kalshi.markets("", 10 * PAIR_CANDIDATES).await?;
+        kalshi.sort_by(|a, b| b.volume_24h.total_cmp(&a.volume_24h));
+        kalshi.truncate(PAIR_CANDIDATES);
+        Ok(match_markets(&polymarket, &kalshi, MIN_SIMILARITY))
+    }
+
+    /// The opportunities of a scan not flagged yet, flagging them; those
+    /// the scan didn't find again are cleared.
+    fn flag(&self, found: &[Opportunity]) -> Vec<Opportunity> {
+        let mut state = self.state.lock().unwrap();
+        let open: HashSet<(String, Venue)> = found.iter().map(Opportunity::key).collect();
+        state.flagged.retain(|key| open.contains(key));
+        found.iter().filter(|o| state.flagged.insert(o.key())).cloned().collect()
+    }
+
+    fn emit(&self, opportunity: &Opportunity) {
+        tracing::info!(
+            "⚖️ Arbitrage in {}: yes on {} @ ${:.3} + no on {} @ ${:.3}, {:.1}% edge on {} shares",
+            opportunity.question,
+            opportunity.yes.venue.as_str(),
+            opportunity.yes.price,
+            opportunity.no.venue.as_str(),
+            opportunity.no.price,
+            opportunity.edge * 100.0,
+            opportunity.shares
+        );
+        self.events.publish(BotEvent::Arbitrage {
+            opportunity: opportunity.clone(),
+        });
+        self.notifications.send(Notification::from(opportunity));
+    }
+
+    /// Whether opportunities are to be taken as found.
+    pub fn auto_execute(&self) -> bool {
+        self.auto_execute
+    }
+
+    /// New opportunities as they're found, with `arb_auto_execute` on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Opportunity> {
+        self.to_take.subscribe()
+    }
+
+    /// The venue a leg is bought on.
+    pub fn exchange(&self, venue: Venue) -> &Arc<dyn Exchange> {
+        match venue {
+            Venue::Polymarket => &self.polymarket,
+            Venue::Kalshi => &self.kalshi,
+        }
+    }
+
+    /// Checks every pair once; returns the opportunities newly found.
+    pub async fn scan(&self) -> Result<Vec<Opportunity>> {
+        let mut found = Vec::new();
+        for pair in self.pairs().await {
+            let Some(yes_token) = pair.polymarket.token_ids.first() else {
+                continue;
+            };
+            let kalshi_yes = kalshi::token_id(&pair.kalshi.id, kalshi::Side::Yes);
+            let books = tokio::try_join!(self.polymarket.orderbook(yes_token), self.kalshi.orderbook(&kalshi_yes));
+            let (poly_book, kalshi_book) = match books {
+                Ok(books) => books,
+                Err(e) => {
+                    tracing::debug!("No books for {}={}: {:#}", pair.polymarket.id, pair.kalshi.id, e);
+                    continue;
+                }
+            };
+            let now = chrono::Utc::now().timestamp_millis();
+            found.extend(evaluate(&pair, &poly_book, &kalshi_book, self.fees, self.min_edge, self.max_stake, now));
+        }
+        let fresh = self.flag(&found);
+        for opportunity in &fresh {
+            self.emit(opportunity);
+            if self.auto_execute && self.to_take.send(opportunity.clone()).is_err() {
+                tracing::warn!("⚖️ Arbitrage in {} not taken: nothing is taking opportunities", opportunity.question);
+            }
+        }
+        Ok(fresh)
+    }
+
+    /// Scans every `arb_interval` in the background.
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.scan().await {
+                    tracing::warn!("Arbitrage scan failed: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(id: &str, question: &str, end_date: Option<i64>) -> Market {
+        Market {
+            id: id.to_string(),
+            question: question.to_string(),
+            yes_price: 0.5,
+            no_price: 0.5,
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            token_ids: vec![format!("{}-yes", id), format!("{}-no", id)],
+            end_date,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_finds_the_cheaper_hedge_after_fees() {
+        let pair = VenuePair {
+            polymarket: market("p1", "Fed cuts rates in December?", None),
+            kalshi: market("FED-25DEC", "Fed cuts rates in December?", None),
+        };
+        // Yes asked at 0.40 on Polymarket; no at 1 - 0.52 = 0.48 on Kalshi
+        let poly = OrderBook::new(vec![(0.38, 500.0)], vec![(0.40, 200.0)]);
+        let kalshi = OrderBook::new(vec![(0.52, 150.0)], vec![(0.55, 100.0)]);
+        let fees = Fees { polymarket: 0.0, kalshi: 0.07 };
+
+        let found = evaluate(&pair, &poly, &kalshi, fees, 0.02, 1_000.0, 0).unwrap();
+        assert_eq!((found.yes.venue, found.no.venue), (Venue::Polymarket, Venue::Kalshi));
+        assert_eq!(found.no.token, "FED-25DEC:no");
+        assert_eq!(found.yes.token, "p1-yes");
+        // 0.40 + 0.48 + 0.07 * 0.48 * 0.52 = 0.8975
+        assert!((found.cost - 0.897472).abs() < 1e-6);
+        assert_eq!(found.shares, 150.0);
+        // The Kalshi leg goes first
+        assert_eq!(found.legs().map(|leg| leg.venue), [Venue::Kalshi, Venue::Polymarket]);
+        // Capped by the stake, in whole shares
+        assert_eq!(evaluate(&pair, &poly, &kalshi, fees, 0.02, 50.0, 0).unwrap().shares, 55.0);
+        // Not worth it past the minimum edge
+        assert!(evaluate(&pair, &poly, &kalshi, fees, 0.15, 1_000.0, 0).is_none());
+        // Books in line leave nothing
+        let fair = OrderBook::new(vec![(0.58, 100.0)], vec![(0.61, 100.0)]);
+        assert!(evaluate(&pair, &fair, &kalshi, fees, 0.02, 1_000.0, 0).is_none());
+    }
+
+    #[test]
+    fn test_pairs_markets_by_question_and_end_date() {
+        let day = 86_400;
+        let polymarket = vec![
+            market("p1", "Will the Fed cut rates in December 2025?", Some(100 * day)),
+            market("p2", "Will Bitcoin reach $150,000 in 2025?", Some(200 * day)),
+            market("p3", "Will it snow in Miami?", None),
+        ];
+        let kalshi = vec![
+            market("K-BTC", "Bitcoin reach $150,000 in 2025?", Some(201 * day)),
+            market("K-FED", "Fed cut rates in December 2025?", Some(90 * day)),
+            market("K-FED2", "Will the Fed cut rates in December 2025?", Some(101 * day)),
+        ];
+        let pairs = match_markets(&polymarket, &kalshi, MIN_SIMILARITY);
+        let ids: Vec<(&str, &str)> = pairs.iter().map(|p| (p.polymarket.id.as_str(), p.kalshi.id.as_str())).collect();
+        // K-FED ends too far from p1
+        assert_eq!(ids, [("p1", "K-FED2"), ("p2", "K-BTC")]);
+    }
+
+    #[test]
+    fn test_flags_an_opportunity_until_it_closes() {
+        let config = Config {
+            arb_pairs: vec!["fed-december=FED-25DEC".to_string()],
+            ..Default::default()
+        };
+        let api = crate::api::PolymarketApi::new(String::new());
+        let markets = Arc::new(MarketCache::new(api.clone(), None, Duration::ZERO, Duration::ZERO));
+        let polymarket: Arc<dyn Exchange> = Arc::new(PolymarketExchange::new(api, String::new(), String::new()));
+        let kalshi: Arc<dyn Exchange> = Arc::new(KalshiApi::new("https://example".to_string(), String::new(), None));
+        let detector = ArbitrageDetector::new(markets, polymarket, kalshi, &config).unwrap();
+        assert_eq!(detector.pair_specs, [("fed-december".to_string(), "FED-25DEC".to_string())]);
+
+        let pair = VenuePair {
+            polymarket: market("p1", "Fed cuts?", None),
+            kalshi: market("FED-25DEC", "Fed cuts?", None),
+        };
+        let poly = OrderBook::new(vec![(0.38, 500.0)], vec![(0.40, 200.0)]);
+        let kalshi = OrderBook::new(vec![(0.52, 150.0)], vec![(0.55, 100.0)]);
+        let found = evaluate(&pair, &poly, &kalshi, Fees::default(), 0.0, 100.0, 0).unwrap();
+        assert_eq!(detector.flag(std::slice::from_ref(&found)).len(), 1);
+        assert!(detector.flag(std::slice::from_ref(&found)).is_empty());
+        assert!(detector.flag(&[]).is_empty());
+        assert_eq!(Notification::from(&found).kind(), "arbitrage");
+        assert_eq!(detector.flag(&[found]).len(), 1);
+
+        let bad = Config {
+            arb_pairs: vec!["no-ticker".to_string()],
+            ..Default::default()
+        };
+        let api = crate::api::PolymarketApi::new(String::new());
+        let markets = Arc::new(MarketCache::new(api.clone(), None, Duration::ZERO, Duration::ZERO));
+        let polymarket: Arc<dyn Exchange> = Arc::new(PolymarketExchange::new(api, String::new(), String::new()));
+        let kalshi: Arc<dyn Exchange> = Arc::new(KalshiApi::new(String::new(), String::new(), None));
+        assert!(ArbitrageDetector::new(markets, polymarket, kalshi, &bad).is_err());
+    }
+}
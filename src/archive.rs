@@ -0,0 +1,382 @@
+//! Tabular views of journal rows, written as Parquet or CSV. Retention uses
+//! these to archive rows before deleting them; `--export` for analysis.
+
+use crate::storage::{
+    DecisionRecord, ExpiredJournal, FillRecord, FrameRecord, LeaderTradeRecord, OrderRecord, PriceSample,
+};
+use anyhow::{Context, Result};
+use parquet::basic::Compression;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// One column of a table. Column names and types are a stable interface for
+/// external tools; add columns at the end rather than changing existing ones.
+pub enum Column {
+    I64(Vec<i64>),
+    OptI64(Vec<Option<i64>>),
+    F64(Vec<f64>),
+    OptF64(Vec<Option<f64>>),
+    Bool(Vec<bool>),
+    Str(Vec<String>),
+    OptStr(Vec<Option<String>>),
+}
+
+impl Column {
+    fn schema_type(&self) -> &'static str {
+        match self {
+            Column::I64(_) => "REQUIRED INT64",
+            Column::OptI64(_) => "OPTIONAL INT64",
+            Column::F64(_) => "REQUIRED DOUBLE",
+            Column::OptF64(_) => "OPTIONAL DOUBLE",
+            Column::Bool(_) => "REQUIRED BOOLEAN",
+            Column::Str(_) => "REQUIRED BYTE_ARRAY",
+            Column::OptStr(_) => "OPTIONAL BYTE_ARRAY",
+        }
+    }
+
+    fn is_string(&self) -> bool {
+        matches!(self, Column::Str(_) | Column::OptStr(_))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Column::I64(v) => v.len(),
+            Column::OptI64(v) => v.len(),
+            Column::F64(v) => v.len(),
+            Column::OptF64(v) => v.len(),
+            Column::Bool(v) => v.len(),
+            Column::Str(v) => v.len(),
+            Column::OptStr(v) => v.len(),
+        }
+    }
+
+    /// The value in `row` as a CSV field; nulls are empty.
+    fn csv_field(&self, row: usize) -> String {
+        match self {
+            Column::I64(v) => v[row].to_string(),
+            Column::OptI64(v) => v[row].map(|x| x.to_string()).unwrap_or_default(),
+            Column::F64(v) => v[row].to_string(),
+            Column::OptF64(v) => v[row].map(|x| x.to_string()).unwrap_or_default(),
+            Column::Bool(v) => v[row].to_string(),
+            Column::Str(v) => csv_escape(&v[row]),
+            Column::OptStr(v) => v[row].as_deref().map(csv_escape).unwrap_or_default(),
+        }
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Named columns of equal length.
+pub struct Table {
+    pub name: &'static str,
+    pub columns: Vec<(&'static str, Column)>,
+}
+
+impl Table {
+    pub fn rows(&self) -> usize {
+        self.columns.first().map(|(_, c)| c.len()).unwrap_or(0)
+    }
+
+    pub fn write_parquet(&self, path: &Path) -> Result<()> {
+        write_table(path, self.name, &self.columns)
+    }
+
+    /// Header row plus one line per row, RFC 4180 quoting.
+    pub fn write_csv(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        let header: Vec<&str> = self.columns.iter().map(|(name, _)| *name).collect();
+        writeln!(out, "{}", header.join(","))?;
+        for row in 0..self.rows() {
+            let fields: Vec<String> = self.columns.iter().map(|(_, c)| c.csv_field(row)).collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Values present in an optional column plus its definition levels.
+fn split_optional<T: Clone>(values: &[Option<T>]) -> (Vec<T>, Vec<i16>) {
+    let present = values.iter().flatten().cloned().collect();
+    let levels = values.iter().map(|v| v.is_some() as i16).collect();
+    (present, levels)
+}
+
+fn byte_arrays<'a>(values: impl Iterator<Item = &'a String>) -> Vec<ByteArray> {
+    values.map(|s| ByteArray::from(s.as_str())).collect()
+}
+
+/// Writes `columns` as a single row group to `path`.
+fn write_table(path: &Path, name: &str, columns: &[(&str, Column)]) -> Result<()> {
+    let fields: String = columns
+        .iter()
+        .map(|(field, col)| {
+            let annotation = if col.is_string() { " (UTF8)" } else { "" };
+            format!("{} {}{};", col.schema_type(), field, annotation)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let schema = Arc::new(parse_message_type(&format!("message {} {{ {} }}", name, fields))?);
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = SerializedFileWriter::new(file, schema, props)?;
+    let mut row_group = writer.next_row_group()?;
+
+    for (_, column) in columns {
+        let mut out = row_group
+            .next_column()?
+            .context("Parquet schema and columns out of step")?;
+        match column {
+            Column::I64(v) => {
+                out.typed::<Int64Type>().write_batch(v, None, None)?;
+            }
+            Column::OptI64(v) => {
+                let (values, levels) = split_optional(v);
+                out.typed::<Int64Type>().write_batch(&values, Some(&levels), None)?;
+            }
+            Column::F64(v) => {
+                out.typed::<DoubleType>().write_batch(v, None, None)?;
+            }
+            Column::OptF64(v) => {
+                let (values, levels) = split_optional(v);
+                out.typed::<DoubleType>().write_batch(&values, Some(&levels), None)?;
+            }
+            Column::Bool(v) => {
+                out.typed::<BoolType>().write_batch(v, None, None)?;
+            }
+            Column::Str(v) => {
+                out.typed::<ByteArrayType>().write_batch(&byte_arrays(v.iter()), None, None)?;
+            }
+            Column::OptStr(v) => {
+                let (values, levels) = split_optional(v);
+                out.typed::<ByteArrayType>()
+                    .write_batch(&byte_arrays(values.iter()), Some(&levels), None)?;
+            }
+        }
+        out.close()?;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+pub fn leader_trades_table(t: &[LeaderTradeRecord]) -> Table {
+    Table {
+        name: "leader_trades",
+        columns: vec![
+            ("id", Column::I64(t.iter().map(|r| r.id).collect())),
+            ("wallet", Column::Str(t.iter().map(|r| r.trade.wallet.clone()).collect())),
+            ("event_id", Column::Str(t.iter().map(|r| r.trade.event_id.clone()).collect())),
+            ("market_id", Column::Str(t.iter().map(|r| r.trade.market_id.clone()).collect())),
+            ("side", Column::Str(t.iter().map(|r| r.trade.side.as_str().to_string()).collect())),
+            ("shares", Column::F64(t.iter().map(|r| r.trade.shares).collect())),
+            ("price", Column::F64(t.iter().map(|r| r.trade.price).collect())),
+            ("timestamp", Column::I64(t.iter().map(|r| r.trade.timestamp).collect())),
+            ("tx_hash", Column::OptStr(t.iter().map(|r| r.trade.tx_hash.clone()).collect())),
+            ("observed_at", Column::I64(t.iter().map(|r| r.observed_at).collect())),
+        ],
+    }
+}
+
+pub fn decisions_table(d: &[DecisionRecord]) -> Table {
+    Table {
+        name: "decisions",
+        columns: vec![
+            ("id", Column::I64(d.iter().map(|r| r.id).collect())),
+            ("leader_trade_id", Column::OptI64(d.iter().map(|r| r.leader_trade_id).collect())),
+            ("wallet", Column::Str(d.iter().map(|r| r.wallet.clone()).collect())),
+            ("market_id", Column::Str(d.iter().map(|r| r.market_id.clone()).collect())),
+            ("side", Column::Str(d.iter().map(|r| r.side.clone()).collect())),
+            ("copied", Column::Bool(d.iter().map(|r| r.copied).collect())),
+            (
+                "reason",
+                Column::OptStr(d.iter().map(|r| r.reason.map(|x| x.as_str().to_string())).collect()),
+            ),
+            ("detail", Column::OptStr(d.iter().map(|r| r.detail.clone()).collect())),
+            ("size_usd", Column::OptF64(d.iter().map(|r| r.size_usd).collect())),
+            ("decided_at", Column::I64(d.iter().map(|r| r.decided_at).collect())),
+            ("arrival_mid", Column::OptF64(d.iter().map(|r| r.arrival_mid).collect())),
+            (
+                "arrival_book",
+                Column::OptStr(
+                    d.iter()
+                        .map(|r| r.arrival_book.as_ref().and_then(|b| serde_json::to_string(b).ok()))
+                        .collect(),
+                ),
+            ),
+        ],
+    }
+}
+
+pub fn orders_table(o: &[OrderRecord]) -> Table {
+    Table {
+        name: "orders",
+        columns: vec![
+            ("id", Column::I64(o.iter().map(|r| r.id).collect())),
+            ("decision_id", Column::OptI64(o.iter().map(|r| r.decision_id).collect())),
+            (
+                "exchange_order_id",
+                Column::OptStr(o.iter().map(|r| r.exchange_order_id.clone()).collect()),
+            ),
+            ("market_id", Column::Str(o.iter().map(|r| r.market_id.clone()).collect())),
+            ("side", Column::Str(o.iter().map(|r| r.side.clone()).collect())),
+            ("shares", Column::F64(o.iter().map(|r| r.shares).collect())),
+            ("limit_price", Column::OptF64(o.iter().map(|r| r.limit_price).collect())),
+            ("order_type", Column::Str(o.iter().map(|r| r.order_type.clone()).collect())),
+            ("status", Column::Str(o.iter().map(|r| r.status.clone()).collect())),
+            ("error", Column::OptStr(o.iter().map(|r| r.error.clone()).collect())),
+            ("submitted_at", Column::I64(o.iter().map(|r| r.submitted_at).collect())),
+            (
+                "client_order_id",
+                Column::OptStr(o.iter().map(|r| r.client_order_id.clone()).collect()),
+            ),
+        ],
+    }
+}
+
+pub fn fills_table(f: &[FillRecord]) -> Table {
+    Table {
+        name: "fills",
+        columns: vec![
+            ("id", Column::I64(f.iter().map(|r| r.id).collect())),
+            ("order_id", Column::I64(f.iter().map(|r| r.order_id).collect())),
+            ("market_id", Column::Str(f.iter().map(|r| r.market_id.clone()).collect())),
+            ("side", Column::Str(f.iter().map(|r| r.side.clone()).collect())),
+            ("shares", Column::F64(f.iter().map(|r| r.shares).collect())),
+            ("price", Column::F64(f.iter().map(|r| r.price).collect())),
+            ("fee", Column::F64(f.iter().map(|r| r.fee).collect())),
+            ("filled_at", Column::I64(f.iter().map(|r| r.filled_at).collect())),
+        ],
+    }
+}
+
+pub fn frames_table(frames: &[FrameRecord]) -> Table {
+    Table {
+        name: "ws_frames",
+        columns: vec![
+            ("id", Column::I64(frames.iter().map(|r| r.id).collect())),
+            ("wallet", Column::Str(frames.iter().map(|r| r.wallet.clone()).collect())),
+            ("payload", Column::Str(frames.iter().map(|r| r.payload.clone()).collect())),
+            ("received_at", Column::I64(frames.iter().map(|r| r.received_at).collect())),
+        ],
+    }
+}
+
+/// Writes each non-empty table of `expired` to `<dir>/<table>-<stamp>.parquet`.
+pub fn prices_table(samples: &[PriceSample]) -> Table {
+    Table {
+        name: "price_samples",
+        columns: vec![
+            ("market_id", Column::Str(samples.iter().map(|r| r.market_id.clone()).collect())),
+            ("sampled_at", Column::I64(samples.iter().map(|r| r.sampled_at).collect())),
+            ("mid", Column::OptF64(samples.iter().map(|r| r.mid).collect())),
+            ("last", Column::OptF64(samples.iter().map(|r| r.last).collect())),
+            (
+                "book",
+                Column::OptStr(
+                    samples
+                        .iter()
+                        .map(|r| r.book.as_ref().and_then(|b| serde_json::to_string(b).ok()))
+                        .collect(),
+                ),
+            ),
+        ],
+    }
+}
+
+pub fn write_journal(dir: &Path, expired: &ExpiredJournal, stamp: &str) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let tables = [
+        leader_trades_table(&expired.leader_trades),
+        decisions_table(&expired.decisions),
+        orders_table(&expired.orders),
+        fills_table(&expired.fills),
+    ];
+
+    let mut written = Vec::new();
+    for table in tables.iter().filter(|t| t.rows() > 0) {
+        let path = dir.join(format!("{}-{}.parquet", table.name, stamp));
+        table.write_parquet(&path)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Writes `frames` to `<dir>/ws_frames-<stamp>.parquet`, if there are any.
+pub fn write_frames(dir: &Path, frames: &[FrameRecord], stamp: &str) -> Result<Option<PathBuf>> {
+    if frames.is_empty() {
+        return Ok(None);
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("ws_frames-{}.parquet", stamp));
+    frames_table(frames).write_parquet(&path)?;
+    Ok(Some(path))
+}
+
+pub fn write_prices(dir: &Path, samples: &[PriceSample], stamp: &str) -> Result<Option<PathBuf>> {
+    if samples.is_empty() {
+        return Ok(None);
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("price_samples-{}.parquet", stamp));
+    prices_table(samples).write_parquet(&path)?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    #[test]
+    fn test_write_journal_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("archive-test-{}", std::process::id()));
+        let expired = ExpiredJournal {
+            fills: vec![FillRecord {
+                id: 7,
+                order_id: 3,
+                market_id: "market1".to_string(),
+                side: "BUY".to_string(),
+                shares: 10.0,
+                price: 0.5,
+                fee: 0.0,
+                filled_at: 1_000,
+            }],
+            ..Default::default()
+        };
+
+        let written = write_journal(&dir, &expired, "test").unwrap();
+        assert_eq!(written.len(), 1);
+
+        let reader = SerializedFileReader::new(File::open(&written[0]).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,898 @@
+//! `mybot backtest`: what copying the configured leaders would have made.
+//!
+//! Historical leader trades, from the trades API, a dataset file or the
+//! journal (see [`crate::dataset`]), are
+//! replayed in time order through the live decision pipeline (the same
+//! prechecks, sizing and risk checks, with the time the trade was seen plus
+//! the fill model's latency as "now"). Only journaled trades know when the
+//! live bot saw them; others are taken as seen when made. Copies are limited to the price the live
+//! executor would accept and fill through a [`FillModel`] against a
+//! simulated cash balance, and positions are kept per leader and
+//! market so every dollar of PnL is attributed to the leader it was copied
+//! from.
+//!
+//! Equity is marked at the last price leaders traded in each market,
+//! including at the end of the run. Market
+//! metadata (liquidity for the risk checks) is today's, and leader balances
+//! for proportional sizing are their current ones.
+
+use crate::api::PolymarketApi;
+use crate::bot::{Bot, UNKNOWN_WHALE_BALANCE};
+use crate::clock::{Clock, SimClock};
+use crate::dedup::trade_key;
+use crate::executor::limit_price;
+use crate::fills::{FillModel, SimOrder};
+use crate::montecarlo::MonteCarloReport;
+use crate::paper::EquityPoint;
+use crate::portfolio::{self, Lot};
+use crate::storage::{Storage, TimeRange};
+use crate::types::{CostBasis, Decision, Market, SkipReason, Trade, TradeSide};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+
+pub(crate) fn as_secs<S: serde::Serializer>(d: &Duration, s: S) -> std::result::Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64())
+}
+
+/// What a backtest replays.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub trades: Vec<Trade>,
+    /// By market id; trades in markets missing here are skipped
+    pub markets: HashMap<String, Market>,
+    /// By wallet; leaders missing here size as if their balance were unknown
+    pub leader_balances: HashMap<String, f64>,
+    /// Unix ms a journaled trade was seen, by [`trade_key`]
+    pub observed_at: HashMap<String, i64>,
+}
+
+/// Where a backtest's leader trades come from.
+pub enum TradeSource<'a> {
+    /// The trades API
+    Api,
+    /// A dataset file; see [`load_dataset`]
+    File(&'a Path),
+    /// The journal's leader trades, e.g. stored by `data fetch`
+    Journal(&'a dyn Storage),
+}
+
+impl History {
+    /// The trades of `wallets` from `source` (those of the last `since`, or
+    /// all of them from a file or the journal), with the markets they touch
+    /// and the leaders' balances.
+    pub async fn load(
+        api: &PolymarketApi,
+        wallets: &[String],
+        source: TradeSource<'_>,
+        since: Option<Duration>,
+    ) -> Result<Self> {
+        let since = since.map(|since| chrono::Utc::now().timestamp() - since.as_secs() as i64);
+        let mut observed = Vec::new();
+        let mut trades = match source {
+            TradeSource::File(path) => load_dataset(path)?,
+            TradeSource::Journal(storage) => {
+                let range = since
+                    .map(|since| TimeRange::since(since * 1000))
+                    .unwrap_or_else(TimeRange::all);
+                let records = storage.leader_trades(range).await?;
+                observed = records.iter().map(|r| r.observed_at).collect();
+                records.into_iter().map(|r| r.trade).collect()
+            }
+            TradeSource::Api => {
+                let since = since.context("Fetching trades from the API needs --since")?;
+                let mut trades = Vec::new();
+                for wallet in wallets {
+                    let fetched = api
+                        .get_trades(wallet, since)
+                        .await
+                        .with_context(|| format!("Failed to fetch trades of {}", wallet))?;
+                    tracing::info!("📥 {} trades of {}", fetched.len(), wallet);
+                    trades.extend(fetched);
+                }
+                trades
+            }
+        };
+
+        // Keep the configured spelling, which is what the pipeline checks against
+        let mut observed_at = HashMap::new();
+        let mut observed = observed.into_iter();
+        trades.retain_mut(|t| {
+            let seen = observed.next();
+            let Some(wallet) = wallets.iter().find(|w| w.eq_ignore_ascii_case(&t.wallet)) else {
+                return false;
+            };
+            t.wallet = wallet.clone();
+            if let Some(seen) = seen {
+                observed_at.insert(trade_key(t), seen);
+            }
+            since.is_none_or(|since| t.timestamp >= since)
+        });
+        trades.sort_by_key(|t| t.timestamp);
+
+        let mut history = History {
+            trades,
+            observed_at,
+            ..Default::default()
+        };
+        for trade in &history.trades {
+            if history.markets.contains_key(&trade.market_id) {
+                continue;
+            }
+            match api.get_market(&trade.market_id).await {
+                Ok(market) => {
+                    history.markets.insert(trade.market_id.clone(), market);
+                }
+                Err(e) => tracing::warn!(
+                    "No market data for {}, its trades will be skipped: {:#}",
+                    trade.market_id,
+                    e
+                ),
+            }
+        }
+        for wallet in wallets {
+            match api.get_balance(wallet).await {
+                Ok(balance) => {
+                    history.leader_balances.insert(wallet.clone(), balance);
+                }
+                Err(e) => tracing::warn!("No balance for {}: {:#}", wallet, e),
+            }
+        }
+        Ok(history)
+    }
+
+    /// Unix ms `trade` was seen: when journaled, else when it was made.
+    pub fn seen_at(&self, trade: &Trade) -> i64 {
+        match self.observed_at.is_empty() {
+            true => trade.timestamp * 1000,
+            false => self
+                .observed_at
+                .get(&trade_key(trade))
+                .copied()
+                .unwrap_or(trade.timestamp * 1000),
+        }
+    }
+
+    /// The trades made in `[from, to)`, unix seconds, with the same markets
+    /// and balances.
+    pub fn between(&self, from: i64, to: i64) -> History {
+        History {
+            trades: self
+                .trades
+                .iter()
+                .filter(|t| (from..to).contains(&t.timestamp))
+                .cloned()
+                .collect(),
+            markets: self.markets.clone(),
+            leader_balances: self.leader_balances.clone(),
+            observed_at: self.observed_at.clone(),
+        }
+    }
+}
+
+/// Reads leader trades from a JSONL file of trades or a CSV written by
+/// `export trades`.
+pub fn load_dataset(path: &Path) -> Result<Vec<Trade>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    if extension.as_deref() != Some("csv") {
+        return text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| format!("{} line {}: not a trade", path.display(), i + 1))
+            })
+            .collect();
+    }
+
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header = csv_fields(lines.next().unwrap_or_default());
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h == name)
+            .with_context(|| format!("{} has no {} column", path.display(), name))
+    };
+    let (wallet, event_id, market_id, side, shares, price, timestamp) = (
+        column("wallet")?,
+        column("event_id")?,
+        column("market_id")?,
+        column("side")?,
+        column("shares")?,
+        column("price")?,
+        column("timestamp")?,
+    );
+    let tx_hash = column("tx_hash").ok();
+    lines
+        .enumerate()
+        .map(|(i, line)| {
+            let row = csv_fields(line);
+            let field = |index: usize| row.get(index).map(String::as_str).unwrap_or_default();
+            let number = |index: usize| {
+                field(index)
+                    .parse::<f64>()
+                    .with_context(|| format!("{} row {}: bad number '{}'", path.display(), i + 1, field(index)))
+            };
+            Ok(Trade {
+                wallet: field(wallet).to_string(),
+                event_id: field(event_id).to_string(),
+                market_id: field(market_id).to_string(),
+                side: TradeSide::parse(field(side))
+                    .with_context(|| format!("{} row {}: bad side '{}'", path.display(), i + 1, field(side)))?,
+                shares: number(shares)?,
+                price: number(price)?,
+                timestamp: number(timestamp)? as i64,
+                tx_hash: tx_hash.map(field).filter(|h| !h.is_empty()).map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Splits an RFC 4180 line.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("at least one field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// One leader's share of the result.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LeaderAttribution {
+    pub wallet: String,
+    pub trades: usize,
+    pub copied: usize,
+    pub volume_usd: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    /// Markets copied from this leader, and those that made money
+    pub positions: usize,
+    pub wins: usize,
+}
+
+impl LeaderAttribution {
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+/// One market category's share of the result; markets the catalog gives
+/// no category count as "other".
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CategoryAttribution {
+    pub category: String,
+    pub volume_usd: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub positions: usize,
+    pub wins: usize,
+}
+
+impl CategoryAttribution {
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+/// A copied position's result: one leader, one market.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PositionResult {
+    pub wallet: String,
+    pub market_id: String,
+    pub category: String,
+    pub volume_usd: f64,
+    /// What its buys cost
+    pub cost_usd: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+}
+
+impl PositionResult {
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+/// A copy's fill, against the price the leader got.
+#[derive(Debug, Clone, Serialize)]
+pub struct CopiedFill {
+    /// Unix ms
+    pub at: i64,
+    pub wallet: String,
+    pub market_id: String,
+    pub side: TradeSide,
+    pub leader_price: f64,
+    pub price: f64,
+    pub shares: f64,
+}
+
+impl CopiedFill {
+    /// How much worse than the leader's price the copy filled, as a
+    /// fraction of it; negative when it filled better.
+    pub fn slippage(&self) -> f64 {
+        let worse = match self.side {
+            TradeSide::BUY => self.price - self.leader_price,
+            TradeSide::SELL => self.leader_price - self.price,
+        };
+        worse / self.leader_price.max(f64::EPSILON)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BacktestReport {
+    /// "Backtest" or "Paper run"
+    pub kind: &'static str,
+    /// Unix seconds of the first and last trade replayed
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub fill_model: &'static str,
+    #[serde(serialize_with = "as_secs")]
+    pub latency: Duration,
+    pub fee_rate: f64,
+    pub trades: usize,
+    pub copied: usize,
+    /// Copies the fill model left unfilled
+    pub missed: usize,
+    /// Copied sells of positions not held
+    pub nothing_to_sell: usize,
+    pub skipped: BTreeMap<&'static str, usize>,
+    pub starting_balance: f64,
+    pub final_equity: f64,
+    pub volume_usd: f64,
+    pub fees: f64,
+    /// Fees included
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    /// Largest fall from a peak in equity
+    pub max_drawdown: f64,
+    pub max_drawdown_pct: f64,
+    /// By PnL, best first
+    pub leaders: Vec<LeaderAttribution>,
+    /// By PnL, best first
+    pub categories: Vec<CategoryAttribution>,
+    /// By PnL, worst first
+    pub positions: Vec<PositionResult>,
+    pub fills: Vec<CopiedFill>,
+    /// After every fill, from the starting balance on
+    pub equity_curve: Vec<EquityPoint>,
+    /// Drawdown and ruin over resampled orderings of the positions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monte_carlo: Option<MonteCarloReport>,
+}
+
+impl BacktestReport {
+    pub fn pnl(&self) -> f64 {
+        self.final_equity - self.starting_balance
+    }
+
+    /// Of the positions copied, the share that made money.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let positions: usize = self.leaders.iter().map(|l| l.positions).sum();
+        let wins: usize = self.leaders.iter().map(|l| l.wins).sum();
+        (positions > 0).then(|| wins as f64 / positions as f64)
+    }
+
+    /// Fills in the largest drawdown along the equity curve.
+    pub(crate) fn measure_drawdown(&mut self) {
+        let mut peak = self.starting_balance;
+        for point in &self.equity_curve {
+            peak = peak.max(point.equity);
+            if peak - point.equity > self.max_drawdown {
+                self.max_drawdown = peak - point.equity;
+                self.max_drawdown_pct = (peak - point.equity) / peak.max(f64::EPSILON);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let date = |ts: Option<i64>| {
+            ts.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        writeln!(
+            f,
+            "{} of {} leader trades, {} to {}",
+            self.kind,
+            self.trades,
+            date(self.from),
+            date(self.to)
+        )?;
+        writeln!(
+            f,
+            "Fills: {} model, {}s latency, {:.2}% fees",
+            self.fill_model,
+            self.latency.as_secs_f64(),
+            self.fee_rate * 100.0
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Copied:         {} (${:.2})", self.copied, self.volume_usd)?;
+        if self.missed > 0 {
+            writeln!(f, "Unfilled:       {} (nothing within the limit price)", self.missed)?;
+        }
+        if self.nothing_to_sell > 0 {
+            writeln!(f, "Nothing held:   {} sells", self.nothing_to_sell)?;
+        }
+        for (reason, count) in &self.skipped {
+            writeln!(f, "Skipped:        {} ({})", count, reason)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Equity:         ${:.2} -> ${:.2} (${:+.2}, {:+.2}%)",
+            self.starting_balance,
+            self.final_equity,
+            self.pnl(),
+            self.pnl() / self.starting_balance.max(f64::EPSILON) * 100.0
+        )?;
+        writeln!(f, "Realized PnL:   ${:+.2} (${:.2} fees)", self.realized_pnl, self.fees)?;
+        writeln!(f, "Unrealized PnL: ${:+.2}", self.unrealized_pnl)?;
+        writeln!(
+            f,
+            "Max drawdown:   ${:.2} ({:.2}%)",
+            self.max_drawdown,
+            self.max_drawdown_pct * 100.0
+        )?;
+        match self.hit_rate() {
+            Some(rate) => writeln!(f, "Hit rate:       {:.1}% of positions", rate * 100.0)?,
+            None => writeln!(f, "Hit rate:       -")?,
+        }
+        if let Some(monte_carlo) = &self.monte_carlo {
+            writeln!(f)?;
+            write!(f, "{}", monte_carlo)?;
+        }
+
+        if self.leaders.is_empty() {
+            return Ok(());
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<44} {:>6} {:>6} {:>12} {:>12} {:>8}",
+            "leader", "trades", "copied", "volume", "pnl", "hit"
+        )?;
+        for l in &self.leaders {
+            let hit = match l.positions {
+                0 => "-".to_string(),
+                n => format!("{:.0}%", l.wins as f64 / n as f64 * 100.0),
+            };
+            writeln!(
+                f,
+                "{:<44} {:>6} {:>6} {:>12.2} {:>+12.2} {:>8}",
+                l.wallet,
+                l.trades,
+                l.copied,
+                l.volume_usd,
+                l.pnl(),
+                hit
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A copied position: one leader, one market.
+#[derive(Default)]
+struct Position {
+    lots: Vec<Lot>,
+    volume_usd: f64,
+    cost_usd: f64,
+    realized_pnl: f64,
+}
+
+/// Copies booked per leader and market, for backtests and paper reports.
+#[derive(Default)]
+pub(crate) struct Ledger {
+    leaders: BTreeMap<String, LeaderAttribution>,
+    positions: BTreeMap<(String, String), Position>,
+}
+
+impl Ledger {
+    pub(crate) fn leader(&mut self, wallet: &str) -> &mut LeaderAttribution {
+        self.leaders
+            .entry(wallet.to_string())
+            .or_insert_with(|| LeaderAttribution {
+                wallet: wallet.to_string(),
+                ..Default::default()
+            })
+    }
+
+    fn position(&mut self, wallet: &str, market_id: &str) -> &mut Position {
+        self.positions
+            .entry((wallet.to_string(), market_id.to_string()))
+            .or_default()
+    }
+
+    /// Shares held from copying `wallet` in `market_id`.
+    pub(crate) fn held(&self, wallet: &str, market_id: &str) -> f64 {
+        self.positions
+            .get(&(wallet.to_string(), market_id.to_string()))
+            .map_or(0.0, |p| p.lots.iter().map(|l| l.shares).sum())
+    }
+
+    /// Books a copied fill and returns the PnL it realized, net of `fee`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn fill(
+        &mut self,
+        wallet: &str,
+        market_id: &str,
+        side: &TradeSide,
+        shares: f64,
+        price: f64,
+        fee: f64,
+        at: i64,
+        method: CostBasis,
+    ) -> f64 {
+        let notional = shares * price;
+        let leader = self.leader(wallet);
+        leader.copied += 1;
+        leader.volume_usd += notional;
+        let position = self.position(wallet, market_id);
+        position.volume_usd += notional;
+        let pnl = match side {
+            TradeSide::BUY => {
+                position.cost_usd += notional;
+                portfolio::buy(&mut position.lots, method, shares, price, at);
+                0.0
+            }
+            TradeSide::SELL => portfolio::sell(&mut position.lots, shares, price),
+        } - fee;
+        position.realized_pnl += pnl;
+        pnl
+    }
+
+    /// Closes every leader's shares in a resolved market at `payout`.
+    pub(crate) fn settle(&mut self, market_id: &str, payout: f64) {
+        for ((_, market), position) in self.positions.iter_mut() {
+            if market == market_id {
+                let shares: f64 = position.lots.iter().map(|l| l.shares).sum();
+                position.realized_pnl += portfolio::sell(&mut position.lots, shares, payout);
+            }
+        }
+    }
+
+    /// What the open shares are worth at `marks`.
+    pub(crate) fn value(&self, marks: &HashMap<String, f64>) -> f64 {
+        self.positions
+            .iter()
+            .map(|((_, market_id), p)| {
+                let price = marks.get(market_id).copied().unwrap_or_default();
+                p.lots.iter().map(|l| l.shares * price).sum::<f64>()
+            })
+            .sum()
+    }
+
+    /// Marks what's still open at `marks` and fills in the report's
+    /// attributions and PnL.
+    pub(crate) fn finish(
+        mut self,
+        report: &mut BacktestReport,
+        marks: &HashMap<String, f64>,
+        markets: &HashMap<String, Market>,
+    ) {
+        let mut categories: BTreeMap<String, CategoryAttribution> = BTreeMap::new();
+        for ((wallet, market_id), position) in std::mem::take(&mut self.positions) {
+            let shares: f64 = position.lots.iter().map(|l| l.shares).sum();
+            let cost: f64 = position.lots.iter().map(|l| l.shares * l.price).sum();
+            let unrealized = shares * marks.get(&market_id).copied().unwrap_or_default() - cost;
+            let won = position.realized_pnl + unrealized > 0.0;
+            let leader = self.leader(&wallet);
+            leader.realized_pnl += position.realized_pnl;
+            leader.unrealized_pnl += unrealized;
+            leader.positions += 1;
+            leader.wins += won as usize;
+
+            let category = markets
+                .get(&market_id)
+                .map(|m| m.category.as_str())
+                .filter(|c| !c.is_empty())
+                .unwrap_or("other")
+                .to_string();
+            let totals = categories
+                .entry(category.clone())
+                .or_insert_with(|| CategoryAttribution {
+                    category: category.clone(),
+                    ..Default::default()
+                });
+            totals.volume_usd += position.volume_usd;
+            totals.realized_pnl += position.realized_pnl;
+            totals.unrealized_pnl += unrealized;
+            totals.positions += 1;
+            totals.wins += won as usize;
+
+            report.positions.push(PositionResult {
+                wallet,
+                market_id,
+                category,
+                volume_usd: position.volume_usd,
+                cost_usd: position.cost_usd,
+                realized_pnl: position.realized_pnl,
+                unrealized_pnl: unrealized,
+            });
+        }
+        report.positions.sort_by(|a, b| a.pnl().total_cmp(&b.pnl()));
+        report.leaders = self.leaders.into_values().collect();
+        report.leaders.sort_by(|a, b| b.pnl().total_cmp(&a.pnl()));
+        report.categories = categories.into_values().collect();
+        report.categories.sort_by(|a, b| b.pnl().total_cmp(&a.pnl()));
+        report.realized_pnl = report.leaders.iter().map(|l| l.realized_pnl).sum();
+        report.unrealized_pnl = report.leaders.iter().map(|l| l.unrealized_pnl).sum();
+        report.measure_drawdown();
+    }
+}
+
+/// Replays `history` through `bot` with `starting_balance` in cash, paying
+/// `fee_rate` of each fill's notional. Build the bot with paper trading, no
+/// storage or event log, and `clock` as its clock, so the run has no side
+/// effects and each trade is decided at the time it was seen.
+pub async fn run(
+    bot: &Bot,
+    clock: &SimClock,
+    history: &History,
+    starting_balance: f64,
+    fills: &dyn FillModel,
+    fee_rate: f64,
+) -> BacktestReport {
+    let config = bot.config();
+    let mut report = BacktestReport {
+        kind: "Backtest",
+        from: history.trades.first().map(|t| t.timestamp),
+        to: history.trades.last().map(|t| t.timestamp),
+        fill_model: fills.name(),
+        latency: fills.latency(),
+        fee_rate,
+        starting_balance,
+        ..Default::default()
+    };
+    let mut ledger = Ledger::default();
+    let mut last_price: HashMap<String, f64> = HashMap::new();
+    let mut cash = starting_balance;
+    let mut day = None;
+    if let Some(first) = history.trades.first() {
+        report.equity_curve.push(EquityPoint {
+            at: history.seen_at(first),
+            cash,
+            equity: cash,
+        });
+    }
+
+    for trade in &history.trades {
+        let seen_ms = history.seen_at(trade);
+        clock.set(seen_ms);
+        clock.advance(fills.latency());
+        let now = clock.now();
+        if day.replace(now.date_naive()).is_some_and(|d| d != now.date_naive()) {
+            bot.risk().reset_daily_stats();
+        }
+        last_price.insert(trade.market_id.clone(), trade.price);
+        report.trades += 1;
+        ledger.leader(&trade.wallet).trades += 1;
+
+        let decision = match bot.precheck(trade) {
+            Some(skip) => skip,
+            None => match history.markets.get(&trade.market_id) {
+                Some(market) => {
+                    let whale_balance = history
+                        .leader_balances
+                        .get(&trade.wallet)
+                        .copied()
+                        .unwrap_or(UNKNOWN_WHALE_BALANCE);
+                    bot.size_and_check(trade, market, cash, whale_balance).await
+                }
+                None => Decision::skip(SkipReason::MarketUnavailable, "no market data"),
+            },
+        };
+        let size_usd = match decision {
+            Decision::Copy { size_usd, .. } => size_usd,
+            Decision::Skip { reason, .. } => {
+                *report.skipped.entry(reason.as_str()).or_default() += 1;
+                continue;
+            }
+        };
+
+        let mut shares = size_usd / trade.price;
+        if trade.side == TradeSide::SELL {
+            shares = shares.min(ledger.held(&trade.wallet, &trade.market_id));
+            if shares <= 1e-9 {
+                report.nothing_to_sell += 1;
+                continue;
+            }
+        }
+        let order = SimOrder {
+            market_id: trade.market_id.clone(),
+            side: trade.side.clone(),
+            shares,
+            reference_price: trade.price,
+            limit: Some(limit_price(trade, config.max_slippage.as_f64())),
+            at_ms: seen_ms,
+        };
+        let Some(fill) = fills.fill(&order).await else {
+            report.missed += 1;
+            continue;
+        };
+
+        let notional = fill.shares * fill.avg_price;
+        let fee = notional * fee_rate;
+        report.fees += fee;
+        let pnl = ledger.fill(
+            &trade.wallet,
+            &trade.market_id,
+            &trade.side,
+            fill.shares,
+            fill.avg_price,
+            fee,
+            trade.timestamp,
+            config.cost_basis,
+        );
+        match trade.side {
+            TradeSide::BUY => cash -= notional + fee,
+            TradeSide::SELL => {
+                cash += notional - fee;
+                bot.risk().record_realized_pnl(pnl);
+            }
+        }
+        bot.risk().record_trade(trade, notional);
+        report.copied += 1;
+        report.volume_usd += notional;
+        report.fills.push(CopiedFill {
+            at: seen_ms,
+            wallet: trade.wallet.clone(),
+            market_id: trade.market_id.clone(),
+            side: trade.side.clone(),
+            leader_price: trade.price,
+            price: fill.avg_price,
+            shares: fill.shares,
+        });
+        report.equity_curve.push(EquityPoint {
+            at: seen_ms,
+            cash,
+            equity: cash + ledger.value(&last_price),
+        });
+    }
+
+    report.final_equity = cash + ledger.value(&last_price);
+    ledger.finish(&mut report, &last_price, &history.markets);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::UsdcAmount;
+    use crate::builder::{BotBuilder, RiskSettings, SizingSettings};
+    use crate::fills::{Immediate, LatencyPenalized, RecordedTape};
+    use crate::storage::PriceSample;
+    use crate::types::SizingMode;
+    use std::sync::Arc;
+
+    fn trade(wallet: &str, market: &str, side: TradeSide, price: f64, timestamp: i64) -> Trade {
+        Trade {
+            wallet: wallet.to_string(),
+            event_id: market.to_string(),
+            market_id: market.to_string(),
+            side,
+            shares: 100.0,
+            price,
+            timestamp,
+            tx_hash: None,
+        }
+    }
+
+    fn market(id: &str) -> Market {
+        Market {
+            id: id.to_string(),
+            event_id: id.to_string(),
+            question: "Will it?".to_string(),
+            yes_price: 0.5,
+            no_price: 0.5,
+            liquidity: 50_000.0,
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            ..Default::default()
+        }
+    }
+
+    async fn test_bot(clock: &SimClock) -> Bot {
+        BotBuilder::new()
+            .account("0xme", "a".repeat(64))
+            .watch_wallets(["0xgood", "0xbad"])
+            .with_sizing(SizingSettings {
+                mode: SizingMode::Fixed,
+                fixed_stake: UsdcAmount(10.0),
+                min_stake: UsdcAmount(1.0),
+                max_stake: UsdcAmount(10.0),
+                ..Default::default()
+            })
+            .with_risk(RiskSettings {
+                max_daily_volume: UsdcAmount(1_000.0),
+                max_exposure_per_event: UsdcAmount(1_000.0),
+                min_liquidity: 0.0,
+                cb_min_depth_usd: 0.0,
+                ..Default::default()
+            })
+            .clock(Arc::new(clock.clone()))
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_backtest_attributes_pnl() {
+        let clock = SimClock::default();
+        let bot = test_bot(&clock).await;
+        let history = History {
+            trades: vec![
+                trade("0xgood", "m1", TradeSide::BUY, 0.50, 1_700_000_000),
+                trade("0xbad", "m2", TradeSide::BUY, 0.50, 1_700_000_010),
+                trade("0xnobody", "m1", TradeSide::BUY, 0.50, 1_700_000_015),
+                trade("0xgood", "m1", TradeSide::SELL, 0.75, 1_700_000_020),
+                trade("0xbad", "m2", TradeSide::SELL, 0.25, 1_700_000_030),
+                trade("0xbad", "m3", TradeSide::BUY, 0.50, 1_700_000_040),
+            ],
+            markets: [market("m1"), market("m2")].map(|m| (m.id.clone(), m)).into(),
+            leader_balances: HashMap::new(),
+            observed_at: HashMap::new(),
+        };
+
+        let report = run(&bot, &clock, &history, 100.0, &Immediate, 0.0).await;
+        assert_eq!((report.trades, report.copied), (6, 4));
+        assert_eq!(report.skipped.get("unverified_wallet"), Some(&1));
+        assert_eq!(report.skipped.get("market_unavailable"), Some(&1));
+        // Bought 20 shares each; m1 sold at +$5, m2 at -$5
+        assert!((report.final_equity - 100.0).abs() < 1e-9);
+        assert!((report.max_drawdown - 5.0).abs() < 1e-9);
+        assert_eq!(report.hit_rate(), Some(0.5));
+        assert_eq!(report.leaders[0].wallet, "0xgood");
+        assert!((report.leaders[0].pnl() - 5.0).abs() < 1e-9);
+        assert_eq!(report.positions[0].market_id, "m2");
+        assert!((report.positions[0].pnl() + 5.0).abs() < 1e-9);
+        assert_eq!(report.categories[0].category, "other");
+        assert_eq!((report.fills.len(), report.equity_curve.len()), (4, 5));
+
+        // A market that ran away from the leader leaves the buys unfilled,
+        // and so nothing to sell
+        let ran_away = ["m1", "m2"].map(|market_id| PriceSample {
+            market_id: market_id.to_string(),
+            sampled_at: 1_700_000_001_000,
+            mid: Some(0.9),
+            last: None,
+            book: None,
+        });
+        let tape = Arc::new(RecordedTape::new(ran_away.to_vec()));
+        let late = LatencyPenalized::new(tape, Duration::from_secs(1));
+        let report = run(&test_bot(&clock).await, &clock, &history, 100.0, &late, 0.0).await;
+        assert_eq!((report.copied, report.missed, report.nothing_to_sell), (0, 2, 2));
+
+        // An `export trades` CSV reads back as trades
+        let path = std::env::temp_dir().join(format!("backtest-test-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "id,wallet,event_id,market_id,side,shares,price,timestamp,tx_hash,observed_at\n\
+             1,0xgood,\"e,1\",m1,SELL,10,0.5,1700000000,,1700000000123\n",
+        )
+        .unwrap();
+        let trades = load_dataset(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            (trades[0].event_id.as_str(), &trades[0].side),
+            ("e,1", &TradeSide::SELL)
+        );
+        assert_eq!(trades[0].tx_hash, None);
+    }
+}
@@ -0,0 +1,258 @@
+//! OHLC bars of market trades.
+//!
+//! [`Bars`] folds every streamed trade (see [`crate::ticker`]) into 1m, 5m
+//! and 1h bars per market. The latest [`KEPT_BARS`] of each are kept in
+//! memory for the status API, charts and strategy filters such as
+//! [`Bars::momentum`]. With `storage_url` set, bars that changed are saved
+//! every [`FLUSH_INTERVAL`] and age out with the price samples.
+//!
+//! A bar only exists for an interval with trades in it; quiet stretches
+//! leave gaps rather than flat bars.
+
+use crate::storage::{Bar, Storage, TimeRange};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Bars of each market and interval kept in memory.
+pub const KEPT_BARS: usize = 240;
+
+/// How often changed bars are saved.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Interval {
+    #[serde(rename = "1m")]
+    M1,
+    #[serde(rename = "5m")]
+    M5,
+    #[serde(rename = "1h")]
+    H1,
+}
+
+impl Interval {
+    pub const ALL: &'static [Interval] = &[Interval::M1, Interval::M5, Interval::H1];
+
+    pub fn secs(&self) -> i64 {
+        match self {
+            Interval::M1 => 60,
+            Interval::M5 => 300,
+            Interval::H1 => 3600,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::M1 => "1m",
+            Interval::M5 => "5m",
+            Interval::H1 => "1h",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|i| i.as_str() == s)
+    }
+
+    /// Start (unix ms) of the bar holding `at`.
+    pub fn open_of(&self, at: i64) -> i64 {
+        let ms = self.secs() * 1000;
+        at.div_euclid(ms) * ms
+    }
+}
+
+#[derive(Default)]
+struct State {
+    series: HashMap<(String, Interval), VecDeque<Bar>>,
+    /// Bars changed since the last flush: market, interval, open time
+    dirty: HashSet<(String, Interval, i64)>,
+}
+
+pub struct Bars {
+    state: Mutex<State>,
+    storage: Option<Arc<dyn Storage>>,
+}
+
+impl Bars {
+    pub fn new(storage: Option<Arc<dyn Storage>>) -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            storage,
+        }
+    }
+
+    /// Folds a trade of `size` shares at `price` into every interval's bar.
+    pub fn record_trade(&self, market_id: &str, price: f64, size: f64, at: i64) {
+        let mut state = self.state.lock().unwrap();
+        for interval in Interval::ALL {
+            let opened_at = interval.open_of(at);
+            let series = state.series.entry((market_id.to_string(), *interval)).or_default();
+            let is_latest = series.back().is_some_and(|b| b.opened_at == opened_at);
+            let is_new = series.back().is_none_or(|b| b.opened_at < opened_at);
+            match series.iter_mut().rev().find(|b| b.opened_at == opened_at) {
+                Some(bar) => {
+                    bar.high = bar.high.max(price);
+                    bar.low = bar.low.min(price);
+                    // A late trade for an earlier bar doesn't move its close
+                    if is_latest {
+                        bar.close = price;
+                    }
+                    bar.volume += size;
+                    bar.trades += 1;
+                }
+                None if is_new => {
+                    series.push_back(Bar {
+                        market_id: market_id.to_string(),
+                        interval_secs: interval.secs(),
+                        opened_at,
+                        open: price,
+                        high: price,
+                        low: price,
+                        close: price,
+                        volume: size,
+                        trades: 1,
+                    });
+                    if series.len() > KEPT_BARS {
+                        series.pop_front();
+                    }
+                }
+                // Older than any bar kept
+                None => continue,
+            }
+            state.dirty.insert((market_id.to_string(), *interval, opened_at));
+        }
+    }
+
+    /// Up to `limit` of the market's latest bars, oldest first.
+    pub fn recent(&self, market_id: &str, interval: Interval, limit: usize) -> Vec<Bar> {
+        let state = self.state.lock().unwrap();
+        let Some(series) = state.series.get(&(market_id.to_string(), interval)) else {
+            return Vec::new();
+        };
+        series
+            .iter()
+            .skip(series.len().saturating_sub(limit))
+            .cloned()
+            .collect()
+    }
+
+    /// The market's change over its last `bars` bars, as a fraction of the
+    /// first one's open; `None` without that many.
+    pub fn momentum(&self, market_id: &str, interval: Interval, bars: usize) -> Option<f64> {
+        let recent = self.recent(market_id, interval, bars);
+        if bars == 0 || recent.len() < bars {
+            return None;
+        }
+        let (first, last) = (recent.first()?, recent.last()?);
+        (first.open > 0.0).then(|| (last.close - first.open) / first.open)
+    }
+
+    /// The market's bars within `range`: from storage when there is some,
+    /// from memory otherwise.
+    pub async fn history(&self, market_id: &str, interval: Interval, range: TimeRange) -> Result<Vec<Bar>> {
+        if let Some(storage) = &self.storage {
+            self.flush().await?;
+            return storage.bars(market_id, interval.secs(), range).await;
+        }
+        Ok(self
+            .recent(market_id, interval, KEPT_BARS)
+            .into_iter()
+            .filter(|b| b.opened_at >= range.from_ms && b.opened_at <= range.to_ms)
+            .collect())
+    }
+
+    /// Saves the bars changed since the last flush; returns how many.
+    pub async fn flush(&self) -> Result<usize> {
+        let Some(storage) = &self.storage else {
+            return Ok(0);
+        };
+        let changed: Vec<Bar> = {
+            let mut state = self.state.lock().unwrap();
+            let dirty = std::mem::take(&mut state.dirty);
+            dirty
+                .into_iter()
+                .filter_map(|(market_id, interval, opened_at)| {
+                    let series = state.series.get(&(market_id, interval))?;
+                    series.iter().find(|b| b.opened_at == opened_at).cloned()
+                })
+                .collect()
+        };
+        if !changed.is_empty() {
+            storage.record_bars(&changed).await?;
+        }
+        Ok(changed.len())
+    }
+
+    /// Flushes every [`FLUSH_INTERVAL`] in the background.
+    pub fn spawn_flush(self: Arc<Self>) {
+        if self.storage.is_none() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.flush().await {
+                    tracing::warn!("Failed to save bars: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+
+    #[tokio::test]
+    async fn test_aggregates_trades_into_bars() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let bars = Bars::new(Some(Arc::clone(&storage)));
+        for (at, price, size) in [
+            (10_000, 0.50, 10.0),
+            (20_000, 0.55, 5.0),
+            (30_000, 0.48, 5.0),
+            (70_000, 0.52, 20.0),
+            (65_000, 0.60, 1.0),
+            // Late print for the first minute: counted, but not its close
+            (59_000, 0.61, 1.0),
+            (400_000, 0.58, 10.0),
+        ] {
+            bars.record_trade("m1", price, size, at);
+        }
+
+        let minutes = bars.recent("m1", Interval::M1, 10);
+        let ohlc: Vec<(i64, f64, f64, f64, f64)> = minutes
+            .iter()
+            .map(|b| (b.opened_at, b.open, b.high, b.low, b.close))
+            .collect();
+        assert_eq!(
+            ohlc,
+            [
+                (0, 0.50, 0.61, 0.48, 0.48),
+                (60_000, 0.52, 0.60, 0.52, 0.60),
+                (360_000, 0.58, 0.58, 0.58, 0.58)
+            ]
+        );
+        assert_eq!((minutes[0].volume, minutes[0].trades), (21.0, 4));
+        let fives = bars.recent("m1", Interval::M5, 10);
+        assert_eq!(fives.len(), 2);
+        assert_eq!((fives[0].close, fives[0].volume), (0.61, 42.0));
+        assert_eq!(bars.recent("m1", Interval::H1, 10)[0].trades, 7);
+
+        let momentum = bars.momentum("m1", Interval::M5, 2).unwrap();
+        assert!((momentum - 0.16).abs() < 1e-9);
+        assert_eq!(bars.momentum("m1", Interval::M5, 3), None);
+
+        assert_eq!(bars.flush().await.unwrap(), 6);
+        assert_eq!(bars.flush().await.unwrap(), 0);
+        let stored = bars
+            .history("m1", Interval::M1, TimeRange::since(60_000))
+            .await
+            .unwrap();
+        assert_eq!(stored, minutes[1..]);
+        assert_eq!(Interval::parse("5m"), Some(Interval::M5));
+    }
+}
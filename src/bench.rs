@@ -0,0 +1,224 @@
+//! End-to-end copy latency, measured locally against a synthetic feed.
+//!
+//! [`run`] serves trades from a [`SyntheticFeed`] to a real
+//! [`WalletWatcher`] and takes each one the way the bot does, without the
+//! network: decided against a synthetic market with paper cash, then built
+//! into the order and the authenticated HTTP request the exchange would
+//! get, down to its body bytes. Orders aren't signed locally, so building
+//! the request is the signing stage. Each stage is timed per trade:
+//!
+//! - `feed` - from the frame being sent to the trade coming off the
+//!   watcher's channel (WebSocket, parse and queue)
+//! - `decide` - prechecks, sizing and risk checks
+//! - `sign` - order, authenticated request and its serialized body
+//! - `total` - the frame being sent to the request bytes
+//!
+//! `cargo bench` runs it (`benches/copy_latency.rs`) and compares the
+//! result with the previous run's.
+
+use crate::api::PolymarketApi;
+use crate::bot::{Bot, UNKNOWN_WHALE_BALANCE};
+use crate::builder::BotBuilder;
+use crate::executor::TradeExecutor;
+use crate::notify;
+use crate::synthetic::{self, FeedPlan, SyntheticFeed};
+use crate::types::{Config, Decision, Market, Trade};
+use crate::watcher::WalletWatcher;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the next trade before giving up on the rest.
+const TRADE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const STAGES: [&str; 4] = ["feed", "decide", "sign", "total"];
+
+/// One stage's latencies, in microseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageLatency {
+    pub stage: String,
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+impl StageLatency {
+    fn of(stage: &str, mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        let at = |q: f64| match samples.len() {
+            0 => 0.0,
+            n => samples[((n - 1) as f64 * q).round() as usize].as_secs_f64() * 1e6,
+        };
+        Self {
+            stage: stage.to_string(),
+            p50_us: at(0.5),
+            p90_us: at(0.9),
+            p99_us: at(0.99),
+            max_us: at(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub trades: usize,
+    pub copied: usize,
+    /// Trades per second through the whole pipeline
+    pub throughput: f64,
+    /// Mean size of an order request's body
+    pub request_bytes: usize,
+    pub stages: Vec<StageLatency>,
+}
+
+impl BenchReport {
+    pub fn stage(&self, stage: &str) -> Option<&StageLatency> {
+        self.stages.iter().find(|s| s.stage == stage)
+    }
+
+    /// Stages whose p99 got more than `tolerance` (a fraction) slower than
+    /// in `baseline`, with the ratio.
+    pub fn regressions(&self, baseline: &BenchReport, tolerance: f64) -> Vec<(String, f64)> {
+        self.stages
+            .iter()
+            .filter_map(|now| {
+                let before = baseline.stage(&now.stage)?;
+                let ratio = now.p99_us / before.p99_us.max(f64::EPSILON);
+                (ratio > 1.0 + tolerance).then(|| (now.stage.clone(), ratio))
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} trades ({} copied), {:.0} trades/s, {} byte requests",
+            self.trades, self.copied, self.throughput, self.request_bytes
+        )?;
+        writeln!(
+            f,
+            "{:<8} {:>10} {:>10} {:>10} {:>10}",
+            "stage", "p50", "p90", "p99", "max"
+        )?;
+        for s in &self.stages {
+            writeln!(
+                f,
+                "{:<8} {:>8.0}us {:>8.0}us {:>8.0}us {:>8.0}us",
+                s.stage, s.p50_us, s.p90_us, s.p99_us, s.max_us
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A bot copying only `wallet`, with `config`'s sizing and risk settings
+/// and isolated like a backtest.
+pub(crate) async fn engine(mut config: Config, wallet: &str) -> Result<Bot> {
+    config.storage_url.clear();
+    config.event_log.clear();
+    notify::disable(&mut config);
+    config.paper_trading = true;
+    config.shadow = None;
+    config.wallets_to_track = vec![wallet.to_string()];
+    BotBuilder::from_config(config).build().await
+}
+
+/// What `bot` decides on `trade` in `market` with its paper cash, without
+/// the network.
+pub(crate) async fn decide(bot: &Bot, trade: &Trade, market: &Market) -> Decision {
+    match bot.precheck(trade) {
+        Some(skip) => skip,
+        None => {
+            bot.size_and_check(trade, market, bot.config().paper_balance, UNKNOWN_WHALE_BALANCE)
+                .await
+        }
+    }
+}
+
+/// Runs `plan` through the pipeline with `config`'s sizing and risk
+/// settings, isolated like a backtest.
+pub async fn run(config: Config, plan: FeedPlan) -> Result<BenchReport> {
+    let wallet = "0xbe0c0000000000000000000000000000000000be".to_string();
+    let bot = engine(config, &wallet).await?;
+    let config = bot.config();
+    let api = PolymarketApi::new(config.polymarket_api.clone());
+    let executor = TradeExecutor::new(api.clone(), config.clone());
+    let market = synthetic::market(&plan);
+
+    let trades = plan.trades;
+    let feed = SyntheticFeed::start(plan).await?;
+    let watcher = WalletWatcher::new(feed.url().to_string(), vec![wallet.clone()]);
+    let rx = watcher.start().await?;
+
+    let mut samples: Vec<Vec<Duration>> = vec![Vec::with_capacity(trades); STAGES.len()];
+    let (mut copied, mut bytes) = (0, 0);
+    let mut first_sent: Option<Instant> = None;
+    for _ in 0..trades {
+        let trade = tokio::time::timeout(TRADE_TIMEOUT, rx.recv())
+            .await
+            .context("The synthetic feed stalled")??;
+        let received = Instant::now();
+        let sent = feed.sent_at(&trade.event_id).unwrap_or(received);
+        first_sent.get_or_insert(sent);
+
+        let decision = decide(&bot, &trade, &market).await;
+        let decided = Instant::now();
+        let signed = match decision {
+            Decision::Copy { shares, .. } => {
+                let order = executor.copy_order(&trade, shares);
+                let request = api.order_request(&order, &config.private_key)?;
+                bytes += request.body().and_then(|b| b.as_bytes()).map_or(0, <[u8]>::len);
+                copied += 1;
+                Instant::now()
+            }
+            Decision::Skip { .. } => decided,
+        };
+
+        let latencies = [received - sent, decided - received, signed - decided, signed - sent];
+        for (stage, latency) in samples.iter_mut().zip(latencies) {
+            stage.push(latency);
+        }
+    }
+    watcher.remove_wallet(&wallet)?;
+
+    let elapsed = first_sent.map_or(Duration::ZERO, |t| t.elapsed());
+    Ok(BenchReport {
+        trades,
+        copied,
+        throughput: trades as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        request_bytes: bytes.checked_div(copied).unwrap_or(0),
+        stages: STAGES
+            .iter()
+            .zip(samples)
+            .map(|(stage, samples)| StageLatency::of(stage, samples))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bench_times_every_trade_through_the_pipeline() {
+        let config = BotBuilder::new().account("0xme", "a".repeat(64)).config().clone();
+        let plan = FeedPlan {
+            trades: 20,
+            interval: Duration::ZERO,
+            ..Default::default()
+        };
+        let report = run(config, plan).await.unwrap();
+
+        assert_eq!((report.trades, report.copied), (20, 20));
+        assert!(report.request_bytes > 0);
+        let total = report.stage("total").unwrap();
+        assert!(total.p50_us > 0.0 && total.p50_us <= total.max_us);
+
+        let mut slower = report.clone();
+        slower.stages[3].p99_us = total.p99_us * 2.0;
+        assert_eq!(slower.regressions(&report, 0.5), vec![("total".to_string(), 2.0)]);
+        assert!(report.regressions(&report, 0.5).is_empty());
+    }
+}
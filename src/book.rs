@@ -0,0 +1,837 @@
+//! Live order books streamed from the CLOB market channel.
+//!
+//! [`BookStream`] subscribes to `book_ws_url` for the `book_tokens` token
+//! ids, those [`watch`](BookStream::watch)ed (the price alerts') and, with
+//! `book_held_markets` or `stream_held_markets`, every held market's, and
+//! rebuilds each token's book locally: a `book` message replaces it with a
+//! snapshot, `price_change` messages set the size resting at a price (zero
+//! removes the level). Updates are checked as they're applied:
+//!
+//! - an update for a token without a snapshot, or older than the book, is
+//!   out of sequence
+//! - a `seq` that isn't one more than the last one seen is a gap
+//! - when the exchange sends its best bid and ask with a change, the local
+//!   book must agree, and it must never be crossed
+//!
+//! A book that fails a check is dropped and resubscribed, which has the
+//! exchange send a fresh snapshot; it reads as empty until then.
+//!
+//! The exchange limits the tokens one connection may subscribe to, so the
+//! tokens are sharded across connections of at most
+//! `book_tokens_per_connection` each (see [`Shards`]). As positions open
+//! and close the shards are rebalanced: tokens are subscribed and
+//! unsubscribed on their connection, connections are opened as the others
+//! fill up and closed once the rest can take their tokens. Each connection
+//! reconnects with backoff on its own, resubscribing its tokens.
+//!
+//! Strategies read best bid and ask, depth at a price or whole books, the
+//! [`Imbalance`] between the two sides near the mid, and can
+//! [`subscribe`](BookStream::subscribe) to [`BookEvent`]s, the streamed
+//! tokens' last trades included.
+
+use crate::api::number;
+use crate::portfolio::Portfolio;
+use crate::types::{Config, OrderBook, TradeSide};
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// Book events a subscriber may fall behind by before it misses some.
+pub const BOOK_EVENT_CAPACITY: usize = 4096;
+
+/// Prices are keyed in units of this, finer than any tick size.
+const PRICE_SCALE: f64 = 1_000_000.0;
+
+/// Size changes below this are rounding noise.
+const EPSILON: f64 = 1e-9;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How often the connections are matched up with the tokens to stream.
+pub const REBALANCE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The exchange's price increment.
+pub const TICK: f64 = 0.01;
+
+/// What changed in a token's book.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum BookEvent {
+    /// A fresh snapshot replaced the book
+    Snapshot {
+        token_id: String,
+        best_bid: Option<f64>,
+        best_ask: Option<f64>,
+    },
+    /// A level's size changed
+    Changed {
+        token_id: String,
+        side: TradeSide,
+        price: f64,
+        size: f64,
+        best_bid: Option<f64>,
+        best_ask: Option<f64>,
+    },
+    /// The book failed a check and was dropped until the next snapshot
+    Resync { token_id: String, reason: String },
+    /// Shares traded at a price
+    Trade { token_id: String, price: f64, size: f64 },
+}
+
+/// Shares resting on each side within a few ticks of the mid, and which
+/// way they lean.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Imbalance {
+    pub bid_shares: f64,
+    pub ask_shares: f64,
+    /// (bids - asks) / (bids + asks): 1 is all bids, -1 all asks
+    pub ratio: f64,
+}
+
+impl Imbalance {
+    /// How much thinner the side a copy on `side` takes from is than the
+    /// other: positive when a buy would lift thin offers or a sell hit
+    /// thin bids.
+    pub fn pressure(&self, side: &TradeSide) -> f64 {
+        match side {
+            TradeSide::BUY => self.ratio,
+            TradeSide::SELL => -self.ratio,
+        }
+    }
+}
+
+fn key(price: f64) -> i64 {
+    (price * PRICE_SCALE).round() as i64
+}
+
+fn price(key: i64) -> f64 {
+    key as f64 / PRICE_SCALE
+}
+
+/// One token's book, rebuilt from the stream.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocalBook {
+    bids: BTreeMap<i64, f64>,
+    asks: BTreeMap<i64, f64>,
+    /// Exchange timestamp (ms) of the last message applied
+    pub timestamp: i64,
+    /// Last `seq` seen, when the exchange numbers its messages
+    pub seq: Option<u64>,
+    pub hash: Option<String>,
+}
+
+impl LocalBook {
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.keys().next_back().map(|k| price(*k))
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.keys().next().map(|k| price(*k))
+    }
+
+    /// Shares resting at exactly `at` on `side`.
+    pub fn depth_at(&self, side: &TradeSide, at: f64) -> f64 {
+        self.levels(side).get(&key(at)).copied().unwrap_or(0.0)
+    }
+
+    fn levels(&self, side: &TradeSide) -> &BTreeMap<i64, f64> {
+        match side {
+            TradeSide::BUY => &self.bids,
+            TradeSide::SELL => &self.asks,
+        }
+    }
+
+    fn set(&mut self, side: &TradeSide, at: f64, size: f64) {
+        let levels = match side {
+            TradeSide::BUY => &mut self.bids,
+            TradeSide::SELL => &mut self.asks,
+        };
+        if size > EPSILON {
+            levels.insert(key(at), size);
+        } else {
+            levels.remove(&key(at));
+        }
+    }
+
+    /// The imbalance within `ticks` ticks of the mid; `None` unless both
+    /// sides have orders.
+    pub fn imbalance(&self, ticks: u32) -> Option<Imbalance> {
+        let mid = (self.best_bid()? + self.best_ask()?) / 2.0;
+        let reach = key(ticks as f64 * TICK);
+        let bid_shares: f64 = self.bids.range(key(mid) - reach..).map(|(_, s)| s).sum();
+        let ask_shares: f64 = self.asks.range(..=key(mid) + reach).map(|(_, s)| s).sum();
+        let total = bid_shares + ask_shares;
+        (total > EPSILON).then(|| Imbalance {
+            bid_shares,
+            ask_shares,
+            ratio: (bid_shares - ask_shares) / total,
+        })
+    }
+
+    pub fn is_crossed(&self) -> bool {
+        matches!((self.best_bid(), self.best_ask()), (Some(bid), Some(ask)) if bid >= ask)
+    }
+
+    /// Best price first on each side.
+    pub fn to_order_book(&self) -> OrderBook {
+        OrderBook {
+            bids: self.bids.iter().rev().map(|(k, s)| (price(*k), *s)).collect(),
+            asks: self.asks.iter().map(|(k, s)| (price(*k), *s)).collect(),
+        }
+    }
+}
+
+fn timestamp(message: &Value) -> i64 {
+    number(&message["timestamp"]).map_or(0, |t| t as i64)
+}
+
+fn side(value: &Value) -> Option<TradeSide> {
+    match value.as_str()?.to_uppercase().as_str() {
+        "BUY" | "BID" => Some(TradeSide::BUY),
+        "SELL" | "ASK" => Some(TradeSide::SELL),
+        _ => None,
+    }
+}
+
+fn levels(value: &Value) -> Vec<(f64, f64)> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|l| Some((number(&l["price"])?, number(&l["size"])?)))
+        .collect()
+}
+
+/// Every token's book, and what's listening for changes.
+pub struct Books {
+    books: Mutex<HashMap<String, LocalBook>>,
+    events: broadcast::Sender<BookEvent>,
+}
+
+impl Default for Books {
+    fn default() -> Self {
+        Self {
+            books: Mutex::new(HashMap::new()),
+            events: broadcast::channel(BOOK_EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl Books {
+    fn publish(&self, event: BookEvent) {
+        if self.events.receiver_count() > 0 {
+            let _ = self.events.send(event);
+        }
+    }
+
+    fn resync(&self, token_id: &str, reason: String, resync: &mut Vec<String>) {
+        tracing::warn!("📖 Book for {} out of sync ({}); resubscribing", token_id, reason);
+        self.books.lock().unwrap().remove(token_id);
+        self.publish(BookEvent::Resync {
+            token_id: token_id.to_string(),
+            reason,
+        });
+        if !resync.iter().any(|t| t == token_id) {
+            resync.push(token_id.to_string());
+        }
+    }
+
+    /// Applies one frame from the market channel; returns the tokens whose
+    /// books were dropped and need a fresh snapshot.
+    pub fn apply(&self, text: &str) -> Vec<String> {
+        let mut resync = Vec::new();
+        let Ok(frame) = serde_json::from_str::<Value>(text) else {
+            return resync;
+        };
+        let messages = match frame {
+            Value::Array(messages) => messages,
+            message => vec![message],
+        };
+        for message in &messages {
+            match message["event_type"].as_str() {
+                Some("book") => self.snapshot(message),
+                Some("price_change") => self.price_change(message, &mut resync),
+                Some("last_trade_price") => self.trade(message),
+                _ => {}
+            }
+        }
+        resync
+    }
+
+    fn snapshot(&self, message: &Value) {
+        let Some(token_id) = message["asset_id"].as_str() else {
+            return;
+        };
+        let mut book = LocalBook {
+            timestamp: timestamp(message),
+            seq: message["seq"].as_u64(),
+            hash: message["hash"].as_str().map(str::to_string),
+            ..Default::default()
+        };
+        for (at, size) in levels(&message["bids"]) {
+            book.set(&TradeSide::BUY, at, size);
+        }
+        for (at, size) in levels(&message["asks"]) {
+            book.set(&TradeSide::SELL, at, size);
+        }
+        let event = BookEvent::Snapshot {
+            token_id: token_id.to_string(),
+            best_bid: book.best_bid(),
+            best_ask: book.best_ask(),
+        };
+        self.books.lock().unwrap().insert(token_id.to_string(), book);
+        self.publish(event);
+    }
+
+    fn trade(&self, message: &Value) {
+        let (Some(token_id), Some(price), Some(size)) = (
+            message["asset_id"].as_str(),
+            number(&message["price"]),
+            number(&message["size"]),
+        ) else {
+            return;
+        };
+        self.publish(BookEvent::Trade {
+            token_id: token_id.to_string(),
+            price,
+            size,
+        });
+    }
+
+    fn price_change(&self, message: &Value, resync: &mut Vec<String>) {
+        let at = timestamp(message);
+        let seq = message["seq"].as_u64();
+        // Older messages carry one token and its changes; newer ones a list
+        // of changes, each with its token and the resulting best prices
+        let changes: Vec<&Value> = match message["price_changes"].as_array() {
+            Some(changes) => changes.iter().collect(),
+            None => message["changes"].as_array().into_iter().flatten().collect(),
+        };
+        let mut checked: HashMap<String, Option<u64>> = HashMap::new();
+        for change in changes {
+            let Some(token_id) = change["asset_id"].as_str().or(message["asset_id"].as_str()) else {
+                continue;
+            };
+            if resync.iter().any(|t| t == token_id) {
+                continue;
+            }
+            let (Some(side), Some(level), Some(size)) =
+                (side(&change["side"]), number(&change["price"]), number(&change["size"]))
+            else {
+                continue;
+            };
+            let outcome = {
+                let mut books = self.books.lock().unwrap();
+                match books.get_mut(token_id) {
+                    None => Err("update before a snapshot".to_string()),
+                    Some(book) if at < book.timestamp => {
+                        Err(format!("update at {} older than the book at {}", at, book.timestamp))
+                    }
+                    Some(book) => {
+                        // A message's seq applies once per token
+                        let expected = book.seq.map(|s| s + 1);
+                        let first = !checked.contains_key(token_id);
+                        match (seq, expected) {
+                            (Some(seq), Some(expected)) if first && seq != expected => {
+                                Err(format!("expected seq {}, got {}", expected, seq))
+                            }
+                            _ => {
+                                checked.insert(token_id.to_string(), seq);
+                                book.set(&side, level, size);
+                                book.timestamp = at;
+                                book.seq = seq.or(book.seq);
+                                if let Some(hash) = change["hash"].as_str().or(message["hash"].as_str()) {
+                                    book.hash = Some(hash.to_string());
+                                }
+                                let disagrees = |ours: Option<f64>, theirs: Option<f64>| {
+                                    theirs.is_some_and(|t| t > 0.0 && ours.is_none_or(|o| (o - t).abs() > EPSILON))
+                                };
+                                if book.is_crossed() {
+                                    Err("crossed book".to_string())
+                                } else if disagrees(book.best_bid(), number(&change["best_bid"]))
+                                    || disagrees(book.best_ask(), number(&change["best_ask"]).filter(|a| *a < 1.0))
+                                {
+                                    Err("best bid or ask differs from the exchange's".to_string())
+                                } else {
+                                    Ok(BookEvent::Changed {
+                                        token_id: token_id.to_string(),
+                                        side,
+                                        price: level,
+                                        size,
+                                        best_bid: book.best_bid(),
+                                        best_ask: book.best_ask(),
+                                    })
+                                }
+                            }
+                        }
+                    }
+                }
+            };
+            match outcome {
+                Ok(event) => self.publish(event),
+                Err(reason) => self.resync(token_id, reason, resync),
+            }
+        }
+    }
+}
+
+/// Which market channel connection streams which tokens.
+///
+/// Tokens stay on the connection they were added to; a connection is
+/// opened once the others are full, and the smallest one is emptied into
+/// the rest once they can hold all its tokens.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Shards {
+    per_connection: usize,
+    shards: BTreeMap<usize, BTreeSet<String>>,
+    next_id: usize,
+}
+
+/// What [`Shards::rebalance`] changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rebalance {
+    /// Shards whose tokens changed, new ones included
+    pub changed: Vec<usize>,
+    pub closed: Vec<usize>,
+}
+
+impl Shards {
+    /// At most `per_connection` tokens a connection; zero is no limit.
+    pub fn new(per_connection: usize) -> Self {
+        Self {
+            per_connection: if per_connection == 0 {
+                usize::MAX
+            } else {
+                per_connection
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn tokens(&self, shard: usize) -> BTreeSet<String> {
+        self.shards.get(&shard).cloned().unwrap_or_default()
+    }
+
+    pub fn shard_of(&self, token_id: &str) -> Option<usize> {
+        self.shards
+            .iter()
+            .find(|(_, tokens)| tokens.contains(token_id))
+            .map(|(shard, _)| *shard)
+    }
+
+    pub fn connections(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The least loaded shard with room for another token.
+    fn with_room(&self) -> Option<usize> {
+        self.shards
+            .iter()
+            .filter(|(_, tokens)| tokens.len() < self.per_connection)
+            .min_by_key(|(_, tokens)| tokens.len())
+            .map(|(shard, _)| *shard)
+    }
+
+    /// Streams exactly `wanted` from now on.
+    pub fn rebalance(&mut self, wanted: &BTreeSet<String>) -> Rebalance {
+        let mut changed = BTreeSet::new();
+        let mut closed = Vec::new();
+        for (shard, tokens) in self.shards.iter_mut() {
+            let before = tokens.len();
+            tokens.retain(|t| wanted.contains(t));
+            if tokens.len() != before {
+                changed.insert(*shard);
+            }
+        }
+        self.shards.retain(|shard, tokens| {
+            if tokens.is_empty() {
+                closed.push(*shard);
+            }
+            !tokens.is_empty()
+        });
+
+        let needed = wanted.len().div_ceil(self.per_connection);
+        while self.shards.len() > needed {
+            let Some((&smallest, _)) = self.shards.iter().min_by_key(|(_, tokens)| tokens.len()) else {
+                break;
+            };
+            let moving = self.shards.remove(&smallest).unwrap_or_default();
+            closed.push(smallest);
+            changed.remove(&smallest);
+            for token in moving {
+                let shard = self.with_room().expect("the other shards hold every token");
+                self.shards.get_mut(&shard).unwrap().insert(token);
+                changed.insert(shard);
+            }
+        }
+
+        let assigned: BTreeSet<String> = self.shards.values().flatten().cloned().collect();
+        for token in wanted.difference(&assigned) {
+            let shard = match self.with_room() {
+                Some(shard) => shard,
+                None => {
+                    self.next_id += 1;
+                    self.shards.insert(self.next_id, BTreeSet::new());
+                    self.next_id
+                }
+            };
+            self.shards.get_mut(&shard).unwrap().insert(token.clone());
+            changed.insert(shard);
+        }
+        Rebalance {
+            changed: changed.into_iter().collect(),
+            closed,
+        }
+    }
+}
+
+/// Streams the books of the configured tokens and, with a portfolio, of
+/// every held market, across as many connections as they need.
+pub struct BookStream {
+    ws_url: String,
+    // Streamed whatever is held: book_tokens and watched ones
+    pinned: Mutex<BTreeSet<String>>,
+    portfolio: Option<Arc<Portfolio>>,
+    books: Books,
+    shards: Mutex<Shards>,
+    // Nudges a shard's connection when its tokens change; dropping the
+    // sender closes the connection
+    connections: Mutex<HashMap<usize, mpsc::UnboundedSender<()>>>,
+}
+
+impl BookStream {
+    pub fn new(ws_url: impl Into<String>, token_ids: Vec<String>) -> Self {
+        Self {
+            ws_url: ws_url.into(),
+            pinned: Mutex::new(token_ids.into_iter().collect()),
+            portfolio: None,
+            books: Books::default(),
+            shards: Mutex::new(Shards::new(0)),
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Streams `book_tokens` from `book_ws_url`, `book_tokens_per_connection`
+    /// a connection; `None` unless there are tokens, `price_alerts` to
+    /// watch or held markets to stream (see [`Self::streams_held_markets`]).
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.book_tokens.is_empty() && config.price_alerts.is_empty() && !Self::streams_held_markets(config) {
+            return None;
+        }
+        Some(
+            Self::new(&config.book_ws_url, config.book_tokens.clone())
+                .with_tokens_per_connection(config.book_tokens_per_connection as usize),
+        )
+    }
+
+    /// Whether every held market is streamed too, for `book_held_markets`
+    /// or the ticker (`stream_held_markets`).
+    pub fn streams_held_markets(config: &Config) -> bool {
+        config.book_held_markets || config.stream_held_markets
+    }
+
+    /// At most `limit` tokens on one connection; zero is no limit.
+    pub fn with_tokens_per_connection(self, limit: usize) -> Self {
+        *self.shards.lock().unwrap() = Shards::new(limit);
+        self
+    }
+
+    /// Also streams the book of every market `portfolio` holds.
+    pub fn with_portfolio(mut self, portfolio: Arc<Portfolio>) -> Self {
+        self.portfolio = Some(portfolio);
+        self
+    }
+
+    /// Every token whose book is streamed, or will be by the next
+    /// rebalance.
+    pub fn token_ids(&self) -> Vec<String> {
+        let mut tokens = self.pinned.lock().unwrap().clone();
+        if let Some(portfolio) = &self.portfolio {
+            tokens.extend(portfolio.holdings().into_iter().map(|h| h.market_id));
+        }
+        tokens.into_iter().collect()
+    }
+
+    /// Streams the tokens' books from the next rebalance on.
+    pub fn watch(&self, token_ids: &[String]) {
+        self.pinned.lock().unwrap().extend(token_ids.iter().cloned());
+    }
+
+    /// Stops streaming the tokens' books, unless they're held.
+    pub fn unwatch(&self, token_ids: &[String]) {
+        self.pinned.lock().unwrap().retain(|t| !token_ids.contains(t));
+    }
+
+    /// Which connection streams which tokens.
+    pub fn shards(&self) -> Shards {
+        self.shards.lock().unwrap().clone()
+    }
+
+    /// Changes to every book from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<BookEvent> {
+        self.books.events.subscribe()
+    }
+
+    /// Applies a frame as if it came off the stream.
+    pub fn apply(&self, text: &str) -> Vec<String> {
+        self.books.apply(text)
+    }
+
+    /// The token's book, `None` until its snapshot arrives.
+    pub fn book(&self, token_id: &str) -> Option<LocalBook> {
+        self.books.books.lock().unwrap().get(token_id).cloned()
+    }
+
+    pub fn best_bid(&self, token_id: &str) -> Option<f64> {
+        self.book(token_id)?.best_bid()
+    }
+
+    pub fn best_ask(&self, token_id: &str) -> Option<f64> {
+        self.book(token_id)?.best_ask()
+    }
+
+    /// Shares resting at exactly `price` on `side` of the token's book.
+    pub fn depth_at(&self, token_id: &str, side: &TradeSide, price: f64) -> f64 {
+        self.book(token_id).map_or(0.0, |b| b.depth_at(side, price))
+    }
+
+    /// See [`LocalBook::imbalance`].
+    pub fn imbalance(&self, token_id: &str, ticks: u32) -> Option<Imbalance> {
+        self.book(token_id)?.imbalance(ticks)
+    }
+
+    fn subscription(token_ids: &BTreeSet<String>) -> Message {
+        Message::Text(json!({ "type": "market", "assets_ids": token_ids }).to_string())
+    }
+
+    fn shard_tokens(&self, shard: usize) -> BTreeSet<String> {
+        self.shards.lock().unwrap().tokens(shard)
+    }
+
+    fn drop_books(&self, token_ids: &BTreeSet<String>) {
+        self.books.books.lock().unwrap().retain(|t, _| !token_ids.contains(t));
+    }
+
+    /// Matches the connections up with the tokens to stream, opening and
+    /// closing them as needed.
+    fn rebalance(self: &Arc<Self>) {
+        let wanted: BTreeSet<String> = self.token_ids().into_iter().collect();
+        let (rebalance, connections) = {
+            let mut shards = self.shards.lock().unwrap();
+            (shards.rebalance(&wanted), shards.connections())
+        };
+        if rebalance.changed.is_empty() && rebalance.closed.is_empty() {
+            return;
+        }
+        self.books.books.lock().unwrap().retain(|t, _| wanted.contains(t));
+        let mut senders = self.connections.lock().unwrap();
+        for shard in &rebalance.closed {
+            senders.remove(shard);
+        }
+        for shard in rebalance.changed {
+            match senders.get(&shard) {
+                Some(nudge) => {
+                    let _ = nudge.send(());
+                }
+                None => {
+                    let (nudge, nudges) = mpsc::unbounded_channel();
+                    senders.insert(shard, nudge);
+                    tokio::spawn(Arc::clone(self).run_shard(shard, nudges));
+                }
+            }
+        }
+        tracing::info!(
+            "📖 Streaming {} order books over {} connections",
+            wanted.len(),
+            connections
+        );
+    }
+
+    /// Streams one shard's books until the connection drops, or returns
+    /// `Ok` once the shard is closed.
+    async fn connect(&self, shard: usize, nudges: &mut mpsc::UnboundedReceiver<()>) -> Result<()> {
+        let (ws, _) = tokio::time::timeout(Duration::from_secs(30), connect_async(self.ws_url.as_str()))
+            .await
+            .context("Market channel connection timeout")?
+            .context("Failed to connect to the market channel")?;
+        let (mut write, mut read) = ws.split();
+        let mut subscribed = self.shard_tokens(shard);
+        write.send(Self::subscription(&subscribed)).await?;
+        tracing::debug!("📖 Connection {} streaming {} order books", shard, subscribed.len());
+
+        let (resync_tx, mut resync_rx) = mpsc::unbounded_channel::<Vec<String>>();
+        let mut ping = tokio::time::interval(Duration::from_secs(10));
+        loop {
+            tokio::select! {
+                msg = read.next() => match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        let resync = self.books.apply(&text);
+                        if !resync.is_empty() {
+                            let _ = resync_tx.send(resync);
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => anyhow::bail!("Market channel closed"),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e).context("Market channel error"),
+                },
+                Some(tokens) = resync_rx.recv() => {
+                    let tokens = tokens.into_iter().filter(|t| subscribed.contains(t)).collect();
+                    write.send(Self::subscription(&tokens)).await?;
+                }
+                nudge = nudges.recv() => {
+                    if nudge.is_none() {
+                        return Ok(());
+                    }
+                    let tokens = self.shard_tokens(shard);
+                    let added: Vec<&String> = tokens.difference(&subscribed).collect();
+                    let removed: Vec<&String> = subscribed.difference(&tokens).collect();
+                    if !added.is_empty() {
+                        let subscribe = json!({ "assets_ids": added, "operation": "subscribe" });
+                        write.send(Message::Text(subscribe.to_string())).await?;
+                    }
+                    if !removed.is_empty() {
+                        let unsubscribe = json!({ "assets_ids": removed, "operation": "unsubscribe" });
+                        write.send(Message::Text(unsubscribe.to_string())).await?;
+                    }
+                    subscribed = tokens;
+                }
+                _ = ping.tick() => write.send(Message::Ping(Vec::new())).await?,
+            }
+        }
+    }
+
+    /// Keeps one shard streaming, reconnecting with backoff, until it's
+    /// closed.
+    async fn run_shard(self: Arc<Self>, shard: usize, mut nudges: mpsc::UnboundedReceiver<()>) {
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            let started = tokio::time::Instant::now();
+            match self.connect(shard, &mut nudges).await {
+                Ok(()) => return,
+                Err(e) => tracing::warn!("Order book stream {} dropped: {:#}", shard, e),
+            }
+            self.drop_books(&self.shard_tokens(shard));
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = Duration::from_secs(1);
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            if !self.connections.lock().unwrap().contains_key(&shard) {
+                return;
+            }
+        }
+    }
+
+    /// Streams in the background, rebalancing the connections every
+    /// [`REBALANCE_INTERVAL`] as tokens are watched and positions open and
+    /// close.
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REBALANCE_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.rebalance();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebuilds_books_and_resyncs_on_gaps() {
+        let stream = BookStream::new("wss://example", vec!["t1".to_string()]);
+        let mut events = stream.subscribe();
+        // Changes before a snapshot can't be applied
+        let early = json!({ "event_type": "price_change", "asset_id": "t1", "timestamp": "5",
+            "changes": [{ "price": "0.5", "side": "BUY", "size": "10" }] });
+        assert_eq!(stream.apply(&early.to_string()), ["t1"]);
+
+        let snapshot = json!([{ "event_type": "book", "asset_id": "t1", "timestamp": "10", "seq": 1,
+            "bids": [{ "price": "0.48", "size": "30" }, { "price": "0.47", "size": "50" }],
+            "asks": [{ "price": "0.52", "size": "25" }] }]);
+        assert!(stream.apply(&snapshot.to_string()).is_empty());
+        assert_eq!((stream.best_bid("t1"), stream.best_ask("t1")), (Some(0.48), Some(0.52)));
+
+        let change = json!({ "event_type": "price_change", "market": "0xm", "timestamp": "11", "seq": 2,
+            "price_changes": [
+                { "asset_id": "t1", "price": "0.49", "side": "BUY", "size": "5", "best_bid": "0.49", "best_ask": "0.52" },
+                { "asset_id": "t1", "price": "0.48", "side": "BUY", "size": "0", "best_bid": "0.49", "best_ask": "0.52" }
+            ] });
+        assert!(stream.apply(&change.to_string()).is_empty());
+        assert_eq!(stream.best_bid("t1"), Some(0.49));
+        assert_eq!(stream.depth_at("t1", &TradeSide::BUY, 0.48), 0.0);
+        assert_eq!(stream.depth_at("t1", &TradeSide::BUY, 0.47), 50.0);
+        let book = stream.book("t1").unwrap().to_order_book();
+        assert_eq!(book.bids, [(0.49, 5.0), (0.47, 50.0)]);
+        // Mid 0.505: one tick reaches 0.495..0.515, three reach 0.475..0.535
+        assert_eq!(stream.imbalance("t1", 1), None);
+        let imbalance = stream.imbalance("t1", 3).unwrap();
+        assert_eq!((imbalance.bid_shares, imbalance.ask_shares), (5.0, 25.0));
+        assert!((imbalance.pressure(&TradeSide::SELL) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stream.imbalance("t1", 4).unwrap().bid_shares, 55.0);
+
+        // A skipped seq drops the book until the next snapshot
+        let gap = json!({ "event_type": "price_change", "asset_id": "t1", "timestamp": "12", "seq": 4,
+            "changes": [{ "price": "0.50", "side": "SELL", "size": "10" }] });
+        assert_eq!(stream.apply(&gap.to_string()), ["t1"]);
+        assert_eq!(stream.best_bid("t1"), None);
+        assert!(stream.apply(&snapshot.to_string()).is_empty());
+        // So does a book crossing itself
+        let crossed = json!({ "event_type": "price_change", "asset_id": "t1", "timestamp": "13",
+            "changes": [{ "price": "0.53", "side": "BUY", "size": "10" }] });
+        assert_eq!(stream.apply(&crossed.to_string()), ["t1"]);
+        let trade = json!({ "event_type": "last_trade_price", "asset_id": "t1", "price": "0.5", "size": "12" });
+        assert!(stream.apply(&trade.to_string()).is_empty());
+
+        let mut kinds = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            kinds.push(match event {
+                BookEvent::Snapshot { .. } => "snapshot",
+                BookEvent::Changed { .. } => "changed",
+                BookEvent::Resync { .. } => "resync",
+                BookEvent::Trade { .. } => "trade",
+            });
+        }
+        assert_eq!(
+            kinds,
+            ["resync", "snapshot", "changed", "changed", "resync", "snapshot", "resync", "trade"]
+        );
+    }
+
+    #[test]
+    fn test_shards_tokens_across_connections() {
+        let tokens = |ids: &[&str]| ids.iter().map(|t| t.to_string()).collect::<BTreeSet<String>>();
+        let mut shards = Shards::new(2);
+        let opened = shards.rebalance(&tokens(&["a", "b", "c"]));
+        assert_eq!((opened.changed, shards.connections()), (vec![1, 2], 2));
+        assert_eq!(shards.tokens(1), tokens(&["a", "b"]));
+
+        // New tokens fill the connection with room before opening another
+        let more = shards.rebalance(&tokens(&["a", "b", "c", "d", "e"]));
+        assert_eq!(more.changed, [2, 3]);
+        assert_eq!(shards.shard_of("d"), Some(2));
+        assert_eq!(
+            shards.rebalance(&tokens(&["a", "b", "c", "d", "e"])),
+            Rebalance::default()
+        );
+
+        // Once the others can take a connection's tokens it's closed
+        let closing = shards.rebalance(&tokens(&["a", "c", "d", "e"]));
+        assert_eq!((closing.changed, closing.closed), (vec![3], vec![1]));
+        assert_eq!(shards.tokens(3), tokens(&["a", "e"]));
+        let compacted = shards.rebalance(&tokens(&["a", "d"]));
+        assert_eq!((compacted.changed, compacted.closed), (vec![3], vec![2]));
+        assert_eq!(shards.tokens(3), tokens(&["a", "d"]));
+        assert_eq!(shards.rebalance(&BTreeSet::new()).closed, [3]);
+        assert_eq!(Shards::new(0).rebalance(&tokens(&["a", "b", "c"])).changed, [1]);
+    }
+}
@@ -0,0 +1,1974 @@
+use crate::admin::{AdminApi, ConfigSource};
+use crate::aging::PositionAging;
+use crate::api::PolymarketApi;
+use crate::approval::{Approvals, PendingCopy, Verdict};
+use crate::audit::{self, AuditAction, AuditTrail};
+use crate::bars::Bars;
+use crate::book::BookStream;
+use crate::cashflow::CashFlowWatcher;
+use crate::clock::{self, Clock};
+use crate::consistency;
+use crate::dedup::TradeDeduper;
+use crate::daemon;
+use crate::equity::EquityTracker;
+use crate::exchange::{self, Exchange, PolymarketExchange};
+use crate::executor::TradeExecutor;
+use crate::gauges::{self, Gauges};
+use crate::health::{FeedStatus, HealthChecker};
+use crate::heartbeat::Heartbeat;
+use crate::http::{self, Handler};
+use crate::anomaly::AnomalyDetector;
+use crate::incidents::IncidentMonitor;
+use crate::lease::InstanceLease;
+use crate::latency::{LatencyStats, Stage};
+use crate::liquidity::LiquidityStats;
+use crate::leaders::{self, LeaderBook, LeaderEntry, LEADER_STATS_KEY};
+use crate::logging;
+use crate::marks::Marks;
+use crate::markets::MarketCache;
+use crate::notify::{self, BotControl, Notification, NotifierRegistry, Notifications, TradeCard};
+use crate::paper::PaperAccount;
+use crate::pnl::PnlTracker;
+use crate::portfolio::Portfolio;
+use crate::disputes::DisputeWatcher;
+use crate::price_alerts::PriceAlerts;
+use crate::spikes::SpikeDetector;
+use crate::arbitrage::{ArbitrageDetector, Leg, Opportunity};
+use crate::spot::SpotFeed;
+use crate::prices::{self, PriceRecorder};
+use crate::reconcile::Reconciler;
+use crate::resolution::Resolutions;
+use crate::recovery::{self, ChainBalances, RecoveryReport};
+use crate::retention::{self, RetentionPolicy};
+use crate::risk::{RiskManager, RiskSnapshot, RISK_STATE_KEY};
+use crate::rpc::RpcStats;
+use crate::skips::SkipStats;
+use crate::schedule::TradingSchedule;
+use crate::shadow::Shadow;
+use crate::sizing::PositionSizer;
+use crate::status::{RecentDecisions, StatusApi};
+use crate::telemetry;
+use crate::ticker::{ExitReason, ExitRule, MarketTicker, Tick};
+use crate::storage::{self, DecisionRecord, FillRecord, OrderRecord, Storage};
+use crate::events::{BotEvent, EventBus, EventLog};
+use crate::types::{Config, Decision, Market, OrderBook, OrderRequest, OrderResponse, OrderType, ShutdownOrders, SkipReason, Trade, TradeFeed, TradeSide, Venue};
+use crate::subgraph::SubgraphWatcher;
+use crate::watcher::{TradeSource, WalletWatcher};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tracing::Instrument;
+
+/// A fully wired copy-trading bot. Build one with [`crate::builder::BotBuilder`].
+pub struct Bot {
+    config: Config,
+    api: PolymarketApi,
+    watcher: Arc<dyn TradeSource>,
+    sizer: Arc<PositionSizer>,
+    risk: Arc<RiskManager>,
+    executor: Arc<TradeExecutor>,
+    schedule: TradingSchedule,
+    dedup: Arc<TradeDeduper>,
+    portfolio: Arc<Portfolio>,
+    paper: Option<Arc<PaperAccount>>,
+    resolutions: Option<Arc<Resolutions>>,
+    shadow: Option<Arc<Shadow>>,
+    markets: Arc<MarketCache>,
+    marks: Arc<Marks>,
+    prices: Option<Arc<PriceRecorder>>,
+    pnl: Option<Arc<PnlTracker>>,
+    aging: Option<Arc<PositionAging>>,
+    books: Option<Arc<BookStream>>,
+    ticker: Option<Arc<MarketTicker>>,
+    bars: Option<Arc<Bars>>,
+    price_alerts: Option<Arc<PriceAlerts>>,
+    spikes: Option<Arc<SpikeDetector>>,
+    arbitrage: Option<Arc<ArbitrageDetector>>,
+    disputes: Option<Arc<DisputeWatcher>>,
+    spot: Option<Arc<SpotFeed>>,
+    exits: ExitRule,
+    // Markets with an exit order in flight
+    exiting: Mutex<HashSet<String>>,
+    leaders: Arc<LeaderBook>,
+    lease: OnceLock<Arc<InstanceLease>>,
+    control: Arc<BotControl>,
+    notifications: Notifications,
+    incidents: Arc<IncidentMonitor>,
+    anomalies: Arc<AnomalyDetector>,
+    health: Arc<HealthChecker>,
+    latency: Arc<LatencyStats>,
+    rpc: Arc<RpcStats>,
+    skips: Arc<SkipStats>,
+    decisions: Arc<RecentDecisions>,
+    status: Arc<StatusApi>,
+    admin: Arc<AdminApi>,
+    heartbeat: Option<Arc<Heartbeat>>,
+    storage: Option<Arc<dyn Storage>>,
+    events: Arc<EventBus>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Bot {
+    /// Wires up all components from an already validated config.
+    pub async fn new(config: Config) -> Result<Self> {
+        Self::with_notifiers(config, NotifierRegistry::builtin()).await
+    }
+
+    /// Like [`Bot::new`], with custom notifiers registered alongside (or
+    /// instead of) the built-in ones.
+    pub async fn with_notifiers(config: Config, notifiers: NotifierRegistry) -> Result<Self> {
+        Self::with_clock(config, notifiers, clock::system()).await
+    }
+
+    /// Like [`Bot::with_notifiers`], telling the time by `clock`; see
+    /// [`crate::clock`].
+    pub async fn with_clock(mut config: Config, notifiers: NotifierRegistry, clock: Arc<dyn Clock>) -> Result<Self> {
+        let schedule = TradingSchedule::from_config(&config)?;
+        let storage = if config.storage_url.is_empty() {
+            None
+        } else {
+            Some(storage::open(&config.storage_url).await?)
+        };
+        let registry = match &storage {
+            Some(storage) => leaders::load_registry(storage.as_ref()).await?,
+            None => Vec::new(),
+        };
+        config.wallets_to_track = leaders::tracked_wallets(&config.wallets_to_track, &registry);
+        let mut events = EventBus::new();
+        if !config.event_log.is_empty() {
+            events = events.with_log(EventLog::open(&config.event_log)?);
+        }
+        let events = Arc::new(events);
+        let api = PolymarketApi::new(config.polymarket_api.clone());
+        let notifications = Notifications::start(
+            notifiers.build(&config)?,
+            notify::Router::from_config(&config, &notifiers)?,
+        )
+        .with_digest(config.notify_digest_interval)
+        .with_alert_dedup(config.notify_dedup_window, config.notify_recovery_delay);
+        let incidents = Arc::new(IncidentMonitor::from_config(&config));
+        let anomalies = Arc::new(AnomalyDetector::from_config(&config).with_notifications(notifications.clone()));
+        let feeds = Arc::new(FeedStatus::new(&config.wallets_to_track, clock.now_ms()));
+        let latency = Arc::new(LatencyStats::new());
+        let rpc = Arc::new(RpcStats::new());
+        let skips = Arc::new(SkipStats::new());
+        let gauges = Arc::new(Gauges::new());
+        let health = Arc::new(
+            HealthChecker::new(
+                Arc::clone(&feeds),
+                Arc::clone(&incidents),
+                &config.rpc_url,
+                TradeExecutor::new(api.clone(), config.clone()),
+                storage.clone(),
+                Arc::clone(&latency),
+            )
+            .with_rpc_stats(Arc::clone(&rpc))
+            .with_skip_stats(Arc::clone(&skips))
+            .with_gauges(Arc::clone(&gauges)),
+        );
+        let markets = Arc::new(MarketCache::from_config(&config, api.clone(), storage.clone()));
+        markets.load().await.context("Failed to load market cache")?;
+        let watcher: Arc<dyn TradeSource> = match config.trade_source {
+            TradeFeed::WebSocket => {
+                let frames = storage.clone().filter(|_| config.capture_ws_frames);
+                Arc::new(WalletWatcher::new(config.ws_url.clone(), config.wallets_to_track.clone())
+                    .with_events(Arc::clone(&events))
+                    .with_frame_capture(frames)
+                    .with_notifications(notifications.clone())
+                    .with_outage_alert(config.feed_down_alert)
+                    .with_incidents(Arc::clone(&incidents))
+                    .with_feed_status(Arc::clone(&feeds))
+                    .with_latency(Arc::clone(&latency)))
+            }
+            TradeFeed::Subgraph => Arc::new(
+                SubgraphWatcher::from_config(&config)
+                    .with_markets(Arc::clone(&markets))
+                    .with_events(Arc::clone(&events))
+                    .with_notifications(notifications.clone())
+                    .with_outage_alert(config.feed_down_alert)
+                    .with_incidents(Arc::clone(&incidents))
+                    .with_feed_status(Arc::clone(&feeds))
+                    .with_latency(Arc::clone(&latency)),
+            ),
+        };
+        let sizer = Arc::new(PositionSizer::new(config.clone()));
+        let liquidity = Arc::new(LiquidityStats::new(config.liquidity_window));
+        let risk = Arc::new(RiskManager::new(config.clone()).with_liquidity(Arc::clone(&liquidity)));
+        let executor = Arc::new(
+            TradeExecutor::new(api.clone(), config.clone())
+                .with_exchange(exchange::from_config(&config, api.clone())?)
+                .with_clock(Arc::clone(&clock)),
+        );
+        let dedup = Arc::new(TradeDeduper::new(config.dedup_window, storage.clone()));
+        register_gauges(&gauges, &watcher, &dedup);
+        let portfolio = Arc::new(Portfolio::new(config.cost_basis, storage.clone()));
+        portfolio.load().await.context("Failed to load positions")?;
+        let marks = Arc::new(
+            Marks::new(Arc::clone(&markets), config.mark_max_age, Arc::clone(&clock)).with_liquidity(liquidity),
+        );
+        if let Some(storage) = &storage {
+            marks.load(storage.as_ref()).await.context("Failed to load prices")?;
+        }
+        let paper = if config.paper_trading {
+            let paper = PaperAccount::from_config(
+                &config,
+                api.clone(),
+                Arc::clone(&marks),
+                Arc::clone(&portfolio),
+                storage.clone(),
+                Arc::clone(&clock),
+            );
+            paper.load().await.context("Failed to load the paper account")?;
+            Some(Arc::new(paper))
+        } else {
+            None
+        };
+        let resolutions = if config.paper_trading {
+            None
+        } else {
+            let resolutions = Resolutions::from_config(
+                &config,
+                api.clone(),
+                Arc::clone(&portfolio),
+                Arc::clone(&risk),
+                storage.clone(),
+                Arc::clone(&rpc),
+                Arc::clone(&clock),
+            );
+            resolutions.load().await.context("Failed to load resolved positions")?;
+            Some(Arc::new(resolutions))
+        };
+        let mut equity = EquityTracker::from_config(
+            &config,
+            api.clone(),
+            Arc::clone(&marks),
+            Arc::clone(&portfolio),
+            paper.clone(),
+            storage.clone(),
+            Arc::clone(&clock),
+        );
+        if let Some(resolutions) = &resolutions {
+            equity = equity.with_resolutions(Arc::clone(resolutions));
+        }
+        equity.load().await.context("Failed to load the equity curve")?;
+        let equity = Arc::new(equity);
+        let shadow = match config.shadow.take() {
+            Some(shadow) => {
+                let shadow = Shadow::start(*shadow, &config, Arc::clone(&marks), Arc::clone(&clock)).await?;
+                Some(Arc::new(shadow))
+            }
+            None => None,
+        };
+        let prices = storage.clone().filter(|_| !config.price_sample_interval.is_zero()).map(|storage| {
+            Arc::new(PriceRecorder::from_config(
+                &config,
+                api.clone(),
+                Arc::clone(&markets),
+                Arc::clone(&portfolio),
+                storage,
+            )
+            .with_marks(Arc::clone(&marks)))
+        });
+        let pnl = storage.clone().map(|storage| {
+            Arc::new(PnlTracker::new(
+                storage,
+                Arc::clone(&marks),
+                config.cost_basis,
+                config.pnl_interval,
+            ))
+        });
+        if let Some(pnl) = &pnl {
+            pnl.register_gauges(&gauges);
+        }
+        let aging = storage.clone().map(|storage| {
+            Arc::new(
+                PositionAging::from_config(
+                    &config,
+                    storage,
+                    Arc::clone(&portfolio),
+                    Arc::clone(&marks),
+                    Arc::clone(&clock),
+                )
+                .with_notifications(notifications.clone()),
+            )
+        });
+        let books = BookStream::from_config(&config).map(|books| {
+            Arc::new(if BookStream::streams_held_markets(&config) {
+                books.with_portfolio(Arc::clone(&portfolio))
+            } else {
+                books
+            })
+        });
+        let exits = ExitRule::from_config(&config);
+        let bars = config.stream_held_markets.then(|| Arc::new(Bars::new(storage.clone())));
+        let ticker = bars.as_ref().zip(books.as_ref()).map(|(bars, books)| {
+            Arc::new(
+                MarketTicker::new(
+                    Arc::clone(books),
+                    Arc::clone(&portfolio),
+                    Arc::clone(&marks),
+                    Arc::clone(&clock),
+                )
+                .with_bars(Arc::clone(bars)),
+            )
+        });
+        let price_alerts = PriceAlerts::from_config(&config, Arc::clone(&clock))
+            .context("Invalid PRICE_ALERTS")?
+            .map(|alerts| Arc::new(alerts.with_notifications(notifications.clone())));
+        let spikes = SpikeDetector::from_config(&config, markets.gamma().cloned()).map(|spikes| {
+            Arc::new(
+                spikes
+                    .with_events(Arc::clone(&events))
+                    .with_notifications(notifications.clone()),
+            )
+        });
+        let arbitrage = ArbitrageDetector::from_config(&config, Arc::clone(&markets), api.clone())
+            .context("Invalid arbitrage settings")?
+            .map(|arbitrage| {
+                Arc::new(
+                    arbitrage
+                        .with_events(Arc::clone(&events))
+                        .with_notifications(notifications.clone()),
+                )
+            });
+        let spot = SpotFeed::from_config(&config, Arc::clone(&clock)).map(Arc::new);
+        let disputes = markets
+            .gamma()
+            .filter(|_| !config.dispute_interval.is_zero())
+            .map(|gamma| {
+                Arc::new(
+                    DisputeWatcher::new(Arc::clone(gamma), Arc::clone(&portfolio), config.dispute_interval)
+                        .with_notifications(notifications.clone()),
+                )
+            });
+        let mut control = BotControl::new(Arc::clone(&portfolio), Arc::clone(&risk))
+            .with_approvals(Arc::new(Approvals::from_config(&config)))
+            .with_audit(Arc::new(AuditTrail::new(storage.clone())))
+            .with_equity(Arc::clone(&equity));
+        if let Some(pnl) = &pnl {
+            control = control.with_pnl(Arc::clone(pnl));
+        }
+        let control = Arc::new(control);
+        let leaders = Arc::new(LeaderBook::new().with_labels(leaders::parse_labels(&config.leader_labels)?));
+        for entry in &registry {
+            if let Some(label) = &entry.label {
+                leaders.set_label(&entry.wallet, label);
+            }
+        }
+        if let Some(storage) = &storage {
+            load_runtime_state(storage.as_ref(), &risk, &leaders, clock.now().date_naive()).await?;
+        }
+        let heartbeat = Heartbeat::from_config(&config, Arc::clone(&control), Arc::clone(&feeds)).map(Arc::new);
+        let decisions = Arc::new(RecentDecisions::default());
+        let mut status = StatusApi::new(
+            Arc::clone(&control),
+            Arc::clone(&leaders),
+            feeds,
+            Arc::clone(&decisions),
+            storage.clone(),
+        )
+        .with_rpc_stats(Arc::clone(&rpc))
+        .with_skip_stats(Arc::clone(&skips))
+        .with_marks(Arc::clone(&marks));
+        if let Some(paper) = &paper {
+            status = status.with_paper(Arc::clone(paper));
+        }
+        if let Some(shadow) = &shadow {
+            status = status.with_shadow(Arc::clone(shadow));
+        }
+        if let Some(pnl) = &pnl {
+            status = status.with_pnl(Arc::clone(pnl));
+        }
+        if let Some(resolutions) = &resolutions {
+            status = status.with_resolutions(Arc::clone(resolutions));
+        }
+        if let Some(aging) = &aging {
+            status = status.with_aging(Arc::clone(aging));
+        }
+        if let Some(bars) = &bars {
+            status = status.with_bars(Arc::clone(bars));
+        }
+        if let Some(disputes) = &disputes {
+            status = status.with_disputes(Arc::clone(disputes));
+        }
+        let status = Arc::new(status);
+        let admin = Arc::new(AdminApi::new(
+            &config,
+            Arc::clone(&control),
+            Arc::clone(&watcher),
+            Arc::clone(&executor),
+            Arc::clone(&sizer),
+            storage.clone(),
+        ));
+
+        Ok(Self {
+            config,
+            api,
+            watcher,
+            sizer,
+            risk,
+            executor,
+            schedule,
+            dedup,
+            portfolio,
+            paper,
+            resolutions,
+            shadow,
+            markets,
+            marks,
+            prices,
+            pnl,
+            aging,
+            books,
+            ticker,
+            bars,
+            price_alerts,
+            spikes,
+            arbitrage,
+            disputes,
+            spot,
+            exits,
+            exiting: Mutex::new(HashSet::new()),
+            leaders,
+            lease: OnceLock::new(),
+            control,
+            notifications,
+            incidents,
+            anomalies,
+            health,
+            latency,
+            rpc,
+            skips,
+            decisions,
+            status,
+            admin,
+            heartbeat,
+            storage,
+            events,
+            clock,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn health(&self) -> &Arc<HealthChecker> {
+        &self.health
+    }
+
+    pub fn api(&self) -> &PolymarketApi {
+        &self.api
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    pub(crate) fn paper(&self) -> Option<&Arc<PaperAccount>> {
+        self.paper.as_ref()
+    }
+
+    pub fn risk(&self) -> Arc<RiskManager> {
+        Arc::clone(&self.risk)
+    }
+
+    pub fn portfolio(&self) -> Arc<Portfolio> {
+        Arc::clone(&self.portfolio)
+    }
+
+    /// Streamed order books, with `book_tokens`, `price_alerts`,
+    /// `book_held_markets` or `stream_held_markets`.
+    pub fn books(&self) -> Option<Arc<BookStream>> {
+        self.books.clone()
+    }
+
+    /// OHLC bars of the held markets' trades, with `stream_held_markets`.
+    pub fn bars(&self) -> Option<Arc<Bars>> {
+        self.bars.clone()
+    }
+
+    /// Spot prices of crypto assets, when `spot_assets` are set.
+    pub fn spot(&self) -> Option<Arc<SpotFeed>> {
+        self.spot.clone()
+    }
+
+    /// The market spike detector, when `spike_markets` is set.
+    pub fn spikes(&self) -> Option<Arc<SpikeDetector>> {
+        self.spikes.clone()
+    }
+
+    /// The cross-venue arbitrage detector, when `arb_interval` is set.
+    pub fn arbitrage(&self) -> Option<Arc<ArbitrageDetector>> {
+        self.arbitrage.clone()
+    }
+
+    /// Where an admin config reload reads the config from.
+    pub fn set_config_source(&self, source: ConfigSource) {
+        self.admin.set_config_source(source);
+    }
+
+    /// Health checks, metrics and, when enabled, the status and admin APIs on `addr`.
+    async fn serve_http(&self, addr: &str) -> Result<()> {
+        let mut handlers: Vec<Arc<dyn Handler>> = vec![self.health.clone()];
+        if self.config.status_api {
+            handlers.push(self.status.clone());
+        }
+        if !self.config.admin_token.is_empty() {
+            handlers.push(self.admin.clone());
+        }
+        http::serve(addr, handlers).await?;
+        tracing::info!("🩺 Health checks on http://{}/healthz and /readyz, metrics on /metrics", addr);
+        if self.config.status_api {
+            tracing::info!("📋 Status API on http://{}/status", addr);
+        }
+        if !self.config.admin_token.is_empty() {
+            tracing::info!("🔐 Admin API on http://{}/admin", addr);
+        }
+        Ok(())
+    }
+
+    /// Everything the bot sees and does, for embedding applications to
+    /// subscribe to.
+    pub fn events(&self) -> &Arc<EventBus> {
+        &self.events
+    }
+
+    /// Pause switch and status used by remote commands.
+    pub fn control(&self) -> Arc<BotControl> {
+        Arc::clone(&self.control)
+    }
+
+    pub fn leaders(&self) -> &LeaderBook {
+        &self.leaders
+    }
+
+    /// The journal, if `storage_url` is configured.
+    pub fn storage(&self) -> Option<Arc<dyn Storage>> {
+        self.storage.clone()
+    }
+
+    /// Reconciles the journal with the exchange and chain. Returns `None`
+    /// when there is nothing to reconcile (no journal, or paper trading).
+    pub async fn recover(&self) -> Result<Option<RecoveryReport>> {
+        let Some(storage) = &self.storage else { return Ok(None) };
+        if self.config.paper_trading {
+            return Ok(None);
+        }
+
+        let chain = if self.config.rpc_url.starts_with("ws") {
+            match ChainBalances::connect(&self.config.rpc_url, &self.config.your_wallet, &self.rpc).await {
+                Ok(chain) => Some(chain),
+                Err(e) => {
+                    tracing::warn!("On-chain checks unavailable: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Every venue the bot trades on reconciles the legs it holds there;
+        // Polymarket always, as copies made on it before a switch remain
+        let mut exchanges = vec![Arc::clone(self.executor.exchange())];
+        let kalshi = self.arbitrage.as_ref().map(|arbitrage| Arc::clone(arbitrage.exchange(Venue::Kalshi)));
+        let polymarket: Arc<dyn Exchange> = Arc::new(PolymarketExchange::from_config(&self.config, self.api.clone()));
+        for exchange in kalshi.into_iter().chain([polymarket]) {
+            if exchanges.iter().all(|e| e.venue() != exchange.venue()) {
+                exchanges.push(exchange);
+            }
+        }
+        let report = recovery::recover(&exchanges, storage.as_ref(), chain.as_ref()).await?;
+        Ok(Some(report))
+    }
+
+    /// Starts the wallet watchers and copies trades until the feed closes.
+    pub async fn run(&self) -> Result<()> {
+        telemetry::start(&self.config);
+        let _pid_file = match self.config.pid_file.as_str() {
+            "" => None,
+            path => Some(daemon::PidFile::create(path)?),
+        };
+        self.acquire_lease().await?;
+        if let Some(report) = self.recover().await.context("Startup recovery failed")? {
+            if report.is_clean() {
+                tracing::info!("🩺 Recovery: {}", report);
+            } else {
+                tracing::warn!("🩺 Recovery: {}", report);
+            }
+            // Recovery may have corrected positions under the portfolio
+            self.portfolio.load().await.context("Failed to reload positions")?;
+        }
+        if let Some(resolutions) = &self.resolutions {
+            Arc::clone(resolutions).spawn();
+            let reconciler = Reconciler::from_config(
+                &self.config,
+                self.api.clone(),
+                Arc::clone(&self.markets),
+                Arc::clone(&self.portfolio),
+                Arc::clone(&self.rpc),
+                Arc::clone(&self.clock),
+            )
+            .with_audit(Arc::clone(self.control.audit()))
+            .with_notifications(self.notifications.clone())
+            .with_resolutions(Arc::clone(resolutions));
+            Arc::new(reconciler).spawn();
+            if let Some(storage) = &self.storage {
+                let cash_flows = CashFlowWatcher::from_config(
+                    &self.config,
+                    Arc::clone(storage),
+                    Arc::clone(&self.portfolio),
+                    Arc::clone(&self.rpc),
+                    Arc::clone(&self.clock),
+                );
+                Arc::new(cash_flows).spawn();
+            }
+        }
+
+        self.notifications.listen(&self.control);
+        self.notifications.spawn_digest(&self.control);
+        Arc::clone(&self.incidents).spawn();
+        Arc::clone(&self.anomalies).spawn();
+        if !self.config.health_addr.is_empty() {
+            self.serve_http(&self.config.health_addr).await?;
+        }
+        Arc::clone(&self.health).spawn_watchdog();
+        Arc::clone(&self.latency).spawn_summary(self.config.latency_summary_interval);
+        if let Some(heartbeat) = &self.heartbeat {
+            Arc::clone(heartbeat).spawn();
+        }
+        let trade_rx = self.watcher.start().await?;
+        tracing::info!("✅ WebSocket watchers started");
+        Arc::clone(&self.health).spawn_ready_notice();
+        if let Some(storage) = &self.storage {
+            self.spawn_leader_registry(Arc::clone(storage));
+        }
+
+        // Reset daily stats once the UTC date changes
+        let risk_clone = Arc::clone(&self.risk);
+        let events = self.events.clone();
+        let clock = Arc::clone(&self.clock);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if risk_clone.roll_over(clock.now().date_naive()) {
+                    events.publish(BotEvent::DailyReset);
+                }
+            }
+        });
+
+        Arc::clone(&self.markets).spawn_refresh();
+        if let Some(paper) = &self.paper {
+            Arc::clone(paper).spawn(Arc::clone(&self.risk));
+        }
+        if let Some(shadow) = &self.shadow {
+            Arc::clone(shadow).spawn();
+        }
+        if let Some(prices) = &self.prices {
+            Arc::clone(prices).spawn();
+        }
+        if let Some(pnl) = &self.pnl {
+            Arc::clone(pnl).spawn();
+        }
+        if let Some(equity) = self.control.equity() {
+            Arc::clone(equity).spawn();
+        }
+        if let Some(aging) = &self.aging {
+            Arc::clone(aging).spawn();
+        }
+        // Alert tokens are watched before the stream's first rebalance
+        if let (Some(alerts), Some(books)) = (&self.price_alerts, &self.books) {
+            Arc::clone(alerts).spawn(books);
+        }
+        if let Some(books) = &self.books {
+            Arc::clone(books).spawn();
+        }
+        let mut ticks = self
+            .ticker
+            .as_ref()
+            .filter(|_| self.exits.is_enabled())
+            .map(|ticker| ticker.subscribe());
+        if let Some(ticker) = &self.ticker {
+            Arc::clone(ticker).spawn();
+        }
+        if let Some(bars) = &self.bars {
+            Arc::clone(bars).spawn_flush();
+        }
+        if let Some(spikes) = &self.spikes {
+            Arc::clone(spikes).spawn();
+        }
+        let mut opportunities = self
+            .arbitrage
+            .as_ref()
+            .filter(|arbitrage| arbitrage.auto_execute())
+            .map(|arbitrage| arbitrage.subscribe());
+        if let Some(arbitrage) = &self.arbitrage {
+            Arc::clone(arbitrage).spawn();
+        }
+        if let Some(disputes) = &self.disputes {
+            Arc::clone(disputes).spawn();
+        }
+        if let Some(spot) = &self.spot {
+            Arc::clone(spot).spawn();
+        }
+
+        if !self.schedule.is_always_open() {
+            self.spawn_schedule_monitor();
+        }
+
+        if let Some(storage) = &self.storage {
+            let policy = RetentionPolicy::from_config(&self.config);
+            if !policy.keeps_everything() && !policy.interval.is_zero() {
+                retention::spawn(Arc::clone(storage), policy);
+            }
+        }
+
+        tracing::info!("🎯 Bot is now live and monitoring trades...");
+
+        let approvals = Arc::clone(self.control.approvals());
+        let mut expiry = tokio::time::interval(tokio::time::Duration::from_secs(1));
+        let shutdown = daemon::shutdown_signal();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                trade = trade_rx.recv() => {
+                    let Ok(whale_trade) = trade else { break };
+                    self.anomalies.trade_seen(self.clock.now_ms());
+                    self.handle_trade(whale_trade).await;
+                    self.incidents.trade_processed(self.clock.now_ms());
+                    tracing::info!("---");
+                }
+                Some((pending, verdict)) = approvals.next_verdict() => {
+                    self.handle_verdict(pending, verdict).await;
+                    tracing::info!("---");
+                }
+                Some(tick) = next_broadcast(&mut ticks) => self.check_exit(tick).await,
+                Some(opportunity) = next_broadcast(&mut opportunities) => self.take_arbitrage(opportunity).await,
+                _ = expiry.tick() => approvals.expire(self.clock.now_ms()),
+            }
+        }
+
+        daemon::notify("STOPPING=1");
+        if self.config.shutdown_orders == ShutdownOrders::Cancel {
+            if let Err(e) = self.cancel_resting_orders().await {
+                tracing::error!("❌ Failed to cancel resting orders on shutdown: {:#}", e);
+            }
+        }
+
+        if let Some(lease) = self.lease.get() {
+            if let Err(e) = lease.release().await {
+                tracing::warn!("Failed to release the trading lease: {}", e);
+            }
+        }
+        tracing::info!("Bot stopped");
+        Ok(())
+    }
+
+    /// Cancels every open order on the way out (`shutdown_orders = cancel`),
+    /// marking them in the journal and auditing it before the process ends.
+    async fn cancel_resting_orders(&self) -> Result<()> {
+        let (cancelled, failed) = self.executor.cancel_open_orders().await?;
+        tracing::info!("🧹 Cancelled {} resting orders on shutdown", cancelled.len());
+        for error in &failed {
+            tracing::warn!("Failed to cancel {}", error);
+        }
+        let Some(storage) = &self.storage else { return Ok(()) };
+        for order in storage.open_orders().await? {
+            if order.exchange_order_id.as_ref().is_some_and(|id| cancelled.contains(id)) {
+                storage.update_order(order.id, "cancelled", None, None).await?;
+            }
+        }
+        // Written directly; the background audit trail may not get to run again
+        storage
+            .append_audit(&audit::entry(
+                AuditAction::OrdersCancelled,
+                "bot",
+                json!({ "cancelled": cancelled, "failed": failed, "via": "shutdown" }),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Live trading against a journal holds the account's instance lease.
+    async fn acquire_lease(&self) -> Result<()> {
+        let Some(storage) = &self.storage else { return Ok(()) };
+        if self.config.paper_trading || self.config.instance_lease_ttl.is_zero() {
+            return Ok(());
+        }
+        let lease = InstanceLease::acquire(
+            Arc::clone(storage),
+            &self.config.your_wallet,
+            self.config.instance_lease_ttl,
+            self.config.force_instance_lease,
+        )
+        .await?;
+        tracing::info!("🔒 Holding the trading lease as {}", lease.holder());
+        let lease = Arc::new(lease);
+        Arc::clone(&lease).spawn_heartbeat();
+        let _ = self.lease.set(lease);
+        Ok(())
+    }
+
+    /// Applies changes made with `mybot leaders` to the running watchers.
+    fn spawn_leader_registry(&self, storage: Arc<dyn Storage>) {
+        let watcher = Arc::clone(&self.watcher);
+        let risk = Arc::clone(&self.risk);
+        let leaders = Arc::clone(&self.leaders);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(leaders::REGISTRY_POLL);
+            loop {
+                interval.tick().await;
+                match leaders::load_registry(storage.as_ref()).await {
+                    Ok(registry) => apply_registry(&registry, watcher.as_ref(), &risk, &leaders),
+                    Err(e) => tracing::warn!("Failed to read the leader registry: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Logs a boundary event whenever the trading schedule opens or closes.
+    fn spawn_schedule_monitor(&self) {
+        let schedule = self.schedule.clone();
+        let clock = Arc::clone(&self.clock);
+        tokio::spawn(async move {
+            let mut was_open = schedule.is_open(clock.now());
+            log_schedule_state(&schedule, was_open, clock.now());
+
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                let is_open = schedule.is_open(clock.now());
+                if is_open != was_open {
+                    log_schedule_state(&schedule, is_open, clock.now());
+                    was_open = is_open;
+                }
+            }
+        });
+    }
+
+    /// Runs one leader trade through verification, sizing, risk and execution.
+    /// Everything logged about the trade carries its correlation id.
+    pub async fn handle_trade(&self, whale_trade: Trade) {
+        let span = trade_span(&whale_trade);
+        async {
+            let was_tripped = self.risk.get_state().is_tripped;
+            self.copy_trade(whale_trade).await;
+            self.notify_if_tripped(was_tripped);
+        }
+        .instrument(span)
+        .await
+    }
+
+    fn notify_if_tripped(&self, was_tripped: bool) {
+        let state = self.risk.get_state();
+        if state.is_tripped && !was_tripped {
+            let reason = state.trip_reason.clone().unwrap_or_default();
+            self.control.audit().record(AuditAction::KillSwitchTripped, "bot", json!({ "reason": reason }));
+            self.notifications.send(Notification::RiskTripped {
+                reason: state.trip_reason.unwrap_or_default(),
+            });
+        }
+    }
+
+    async fn copy_trade(&self, whale_trade: Trade) {
+        if self.lease.get().is_some_and(|lease| !lease.is_held()) {
+            tracing::warn!("🔒 Trading lease lost to another instance, ignoring trade");
+            return;
+        }
+        if !self.dedup.first_seen(&whale_trade, self.clock.now_ms()).await {
+            tracing::info!("🔁 Already handled this trade, ignoring");
+            return;
+        }
+
+        tracing::info!(
+            side = whale_trade.side.as_str(),
+            shares = whale_trade.shares,
+            price = whale_trade.price,
+            "📊 Detected trade from {}: {} {:.2} shares @ ${:.4}",
+            &whale_trade.wallet[..10.min(whale_trade.wallet.len())],
+            whale_trade.side.as_str(),
+            whale_trade.shares,
+            whale_trade.price
+        );
+
+        self.emit(BotEvent::TradeSeen { trade: whale_trade.clone() });
+        if let Some(prices) = &self.prices {
+            prices.watch(&whale_trade.market_id, self.clock.now_ms());
+        }
+        self.marks
+            .record_trade(&whale_trade.market_id, whale_trade.price, self.clock.now_ms());
+        let trade_id = match &self.storage {
+            Some(s) => self.journaled(s.record_leader_trade(&whale_trade, self.clock.now_ms()).await, "leader trade"),
+            None => None,
+        };
+
+        let started = Instant::now();
+        let (decision, arrival_book) = tokio::join!(
+            async {
+                let decision = self.decide(&whale_trade).instrument(tracing::info_span!("decide")).await;
+                self.latency.record(Stage::Decide, started.elapsed());
+                decision
+            },
+            self.arrival_book(&whale_trade.market_id)
+        );
+        self.emit(BotEvent::Decision {
+            wallet: whale_trade.wallet.clone(),
+            market_id: whale_trade.market_id.clone(),
+            decision: decision.clone(),
+        });
+        let decision_id = self
+            .record_decision(trade_id, &whale_trade, &decision, arrival_book)
+            .await;
+        self.anomalies.decision(
+            self.clock.now_ms(),
+            match &decision {
+                Decision::Skip { reason, .. } => Some(*reason),
+                Decision::Copy { .. } => None,
+            },
+        );
+        match &decision {
+            Decision::Copy { size_usd, shares } => {
+                tracing::info!(decision = "copy", size_usd, shares, decision_id, "🧮 Decided to copy")
+            }
+            Decision::Skip { reason, detail } => {
+                tracing::info!(
+                    decision = "skip",
+                    reason = reason.as_str(),
+                    detail = %detail,
+                    decision_id,
+                    "🧮 Decided to skip"
+                )
+            }
+        }
+        self.leaders.record(&whale_trade.wallet, &decision, self.clock.now_ms());
+        if let Some(shadow) = &self.shadow {
+            shadow.observe(&whale_trade, &decision);
+        }
+
+        let (size_usd, shares) = match decision {
+            Decision::Skip { reason, detail } => {
+                self.notifications.send(Notification::TradeSkipped {
+                    wallet: whale_trade.wallet.clone(),
+                    market_id: whale_trade.market_id.clone(),
+                    side: whale_trade.side.as_str().to_string(),
+                    reason,
+                    detail,
+                    card: self.trade_card(&whale_trade).await,
+                });
+                self.save_runtime_state().await;
+                return;
+            }
+            Decision::Copy { size_usd, shares } => (size_usd, shares),
+        };
+
+        let approvals = self.control.approvals();
+        if approvals.required(size_usd) {
+            let pending = approvals.submit(whale_trade, trade_id, decision_id, size_usd, shares, self.clock.now_ms());
+            tracing::info!(
+                approval_id = pending.id,
+                size_usd,
+                "✋ Copy #{} of ${:.2} is waiting for approval",
+                pending.id,
+                size_usd
+            );
+            self.notifications.send(Notification::ApprovalRequested {
+                id: pending.id,
+                wallet: pending.trade.wallet.clone(),
+                market_id: pending.trade.market_id.clone(),
+                side: pending.trade.side.as_str().to_string(),
+                size_usd,
+                shares,
+                expires_in_secs: approvals.timeout().as_secs(),
+                card: self.trade_card(&pending.trade).await,
+            });
+            self.save_runtime_state().await;
+            return;
+        }
+        self.execute_copy(whale_trade, decision_id, size_usd, shares).await;
+    }
+
+    /// Second phase of a copy that needed approval.
+    pub async fn handle_verdict(&self, pending: PendingCopy, verdict: Verdict) {
+        let span = trade_span(&pending.trade);
+        span.record("approval_id", pending.id);
+        self.apply_verdict(pending, verdict).instrument(span).await
+    }
+
+    async fn apply_verdict(&self, pending: PendingCopy, verdict: Verdict) {
+        let was_tripped = self.risk.get_state().is_tripped;
+        self.control.audit().record(
+            AuditAction::ApprovalVerdict,
+            if verdict == Verdict::Expired { "bot" } else { "operator" },
+            json!({
+                "approval_id": pending.id,
+                "verdict": verdict.as_str(),
+                "wallet": pending.trade.wallet,
+                "market_id": pending.trade.market_id,
+                "size_usd": pending.size_usd,
+            }),
+        );
+        let blocked = match verdict {
+            Verdict::Reject => Some((SkipReason::Rejected, "rejected by operator".to_string())),
+            Verdict::Expired => Some((SkipReason::ApprovalExpired, "nobody approved in time".to_string())),
+            _ if self.control.is_paused() => Some((SkipReason::Paused, "paused by operator".to_string())),
+            _ => {
+                let state = self.risk.get_state();
+                state
+                    .is_tripped
+                    .then(|| (SkipReason::RiskBlocked, state.trip_reason.unwrap_or_default()))
+            }
+        };
+        if let Some((reason, detail)) = blocked {
+            tracing::info!("⏭️  Copy #{} not executed: {} ({})", pending.id, reason.as_str(), detail);
+            let decision = Decision::skip(reason, detail.clone());
+            self.record_decision(pending.trade_id, &pending.trade, &decision, None).await;
+            self.notifications.send(Notification::TradeSkipped {
+                wallet: pending.trade.wallet.clone(),
+                market_id: pending.trade.market_id.clone(),
+                side: pending.trade.side.as_str().to_string(),
+                reason,
+                detail,
+                card: self.trade_card(&pending.trade).await,
+            });
+            return;
+        }
+
+        let scale = if verdict == Verdict::HalfSize { 0.5 } else { 1.0 };
+        self.execute_copy(
+            pending.trade,
+            pending.decision_id,
+            pending.size_usd * scale,
+            pending.shares * scale,
+        )
+        .await;
+        self.notify_if_tripped(was_tripped);
+    }
+
+    pub(crate) async fn execute_copy(&self, whale_trade: Trade, decision_id: Option<i64>, size_usd: f64, shares: f64) {
+        self.anomalies.copy_started(self.clock.now_ms());
+        let card = self.trade_card(&whale_trade).await;
+        self.notifications.send(Notification::TradeCopied {
+            wallet: whale_trade.wallet.clone(),
+            market_id: whale_trade.market_id.clone(),
+            side: whale_trade.side.as_str().to_string(),
+            size_usd,
+            shares,
+            card: card.clone(),
+        });
+
+        // Orders aren't signed locally (the exchange client authenticates the
+        // request), so building and journaling the order is the step before
+        // submission
+        let started = Instant::now();
+        let Some((order, order_id)) = self
+            .prepare_order(&whale_trade, decision_id, shares)
+            .instrument(tracing::info_span!("prepare"))
+            .await
+        else {
+            return;
+        };
+        self.latency.record(Stage::Prepare, started.elapsed());
+        self.emit(BotEvent::OrderSubmitted { order: order.clone() });
+        let started = Instant::now();
+        let result = self
+            .executor
+            .execute_order(&whale_trade, order.clone())
+            .instrument(tracing::info_span!("submit", client_order_id = %order.client_order_id))
+            .await;
+        self.latency.record(Stage::Submit, started.elapsed());
+        self.finish_order(&whale_trade, size_usd, card, order, order_id, result)
+            .instrument(tracing::info_span!("fill"))
+            .await;
+    }
+
+    /// Builds the mirror order and records the intent to place it; `None`
+    /// if the intent couldn't be recorded.
+    async fn prepare_order(
+        &self,
+        whale_trade: &Trade,
+        decision_id: Option<i64>,
+        shares: f64,
+    ) -> Option<(OrderRequest, Option<i64>)> {
+        let order = self.executor.copy_order(whale_trade, shares);
+        tracing::info!(
+            client_order_id = %order.client_order_id,
+            shares,
+            limit_price = order.price,
+            "🔄 Executing mirror trade..."
+        );
+        match self.record_intent(decision_id, &order).await {
+            Ok(id) => Some((order, id)),
+            Err(e) => {
+                // Without the intent a crash could leave an order nobody knows about
+                tracing::error!("❌ Could not record order intent, not submitting: {}", e);
+                self.risk.record_error(&format!("Order intent not recorded: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Records, reports and accounts for the result of a submitted order.
+    async fn finish_order(
+        &self,
+        whale_trade: &Trade,
+        size_usd: f64,
+        card: TradeCard,
+        order: OrderRequest,
+        order_id: Option<i64>,
+        result: Result<OrderResponse>,
+    ) {
+        self.emit(BotEvent::OrderResult {
+            market_id: order.market_id.clone(),
+            response: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        self.record_outcome(order_id, &order, &result).await;
+        self.incidents.order_result(self.clock.now_ms(), result.is_err());
+        if result.is_ok() {
+            self.anomalies.order_placed(self.clock.now_ms());
+        }
+        self.control.audit().record(
+            AuditAction::OrderPlaced,
+            "bot",
+            json!({
+                "client_order_id": order.client_order_id,
+                "market_id": order.market_id,
+                "side": order.side.as_str(),
+                "shares": order.shares,
+                "limit_price": order.price,
+                "size_usd": size_usd,
+                "leader": whale_trade.wallet,
+                "leader_tx": whale_trade.tx_hash,
+                "exchange_order_id": result.as_ref().ok().map(|r| r.order_id.clone()),
+                "error": result.as_ref().err().map(|e| e.to_string()),
+            }),
+        );
+
+        match &result {
+            Ok(resp) if resp.filled_shares > 0.0 => self.notifications.send(Notification::OrderFilled {
+                market_id: order.market_id.clone(),
+                side: order.side.as_str().to_string(),
+                shares: resp.filled_shares,
+                price: resp.avg_fill_price,
+                card,
+            }),
+            Ok(_) => {}
+            Err(e) => self.notifications.send(Notification::OrderFailed {
+                market_id: order.market_id.clone(),
+                error: e.to_string(),
+            }),
+        }
+
+        match result {
+            Ok(resp) => {
+                tracing::info!(
+                    order_id = %resp.order_id,
+                    filled_shares = resp.filled_shares,
+                    avg_price = resp.avg_fill_price,
+                    total_usd = resp.filled_shares * resp.avg_fill_price,
+                    "✅ Trade executed: {:.2} shares @ ${:.4} (${:.2}), order {}",
+                    resp.filled_shares,
+                    resp.avg_fill_price,
+                    resp.filled_shares * resp.avg_fill_price,
+                    resp.order_id
+                );
+
+                self.risk.record_trade(whale_trade, size_usd);
+            }
+            Err(e) => {
+                tracing::error!(client_order_id = %order.client_order_id, "❌ Trade execution failed: {}", e);
+                self.risk.record_error(&format!("Execution failed: {}", e));
+            }
+        }
+
+        self.save_runtime_state().await;
+
+        // Show circuit breaker status
+        let cb_state = self.risk.get_state();
+        tracing::info!("📈 Daily stats: {} trades, ${:.2} volume",
+            cb_state.total_trades_today,
+            cb_state.total_volume_today
+        );
+
+        if cb_state.is_tripped {
+            tracing::error!("⚠️  CIRCUIT BREAKER TRIPPED - Bot paused!");
+        }
+    }
+
+    /// Closes the position in the tick's market if an exit rule says so.
+    async fn check_exit(&self, tick: Tick) {
+        let Some(holding) = self.portfolio.holding(&tick.market_id) else { return };
+        let avg_price = holding.avg_price();
+        let Some(reason) = self.exits.check(avg_price, tick.price) else { return };
+        if !self.exiting.lock().unwrap().insert(tick.market_id.clone()) {
+            return;
+        }
+        let shares = holding.shares();
+        tracing::warn!(
+            market_id = %tick.market_id,
+            reason = reason.as_str(),
+            "{} {}: ${:.4} against an entry of ${:.4}, selling {:.2} shares",
+            if reason == ExitReason::TakeProfit { "🎯" } else { "🛑" },
+            reason.as_str(),
+            tick.price,
+            avg_price,
+            shares
+        );
+        self.notifications.send(Notification::PositionExit {
+            market_id: tick.market_id.clone(),
+            reason: reason.as_str().to_string(),
+            shares,
+            price: tick.price,
+            avg_price,
+        });
+
+        let order = self.executor.close_order(&tick.market_id, shares, TradeSide::BUY);
+        match self.record_intent(None, &order).await {
+            Ok(order_id) => {
+                self.emit(BotEvent::OrderSubmitted { order: order.clone() });
+                let result = self.executor.submit_order(order.clone()).await;
+                self.emit(BotEvent::OrderResult {
+                    market_id: order.market_id.clone(),
+                    response: result.as_ref().ok().cloned(),
+                    error: result.as_ref().err().map(|e| e.to_string()),
+                });
+                self.record_outcome(order_id, &order, &result).await;
+                self.control.audit().record(
+                    AuditAction::OrderPlaced,
+                    "bot",
+                    json!({
+                        "client_order_id": order.client_order_id,
+                        "market_id": order.market_id,
+                        "side": order.side.as_str(),
+                        "shares": order.shares,
+                        "exit": reason.as_str(),
+                        "trigger_price": tick.price,
+                        "exchange_order_id": result.as_ref().ok().map(|r| r.order_id.clone()),
+                        "error": result.as_ref().err().map(|e| e.to_string()),
+                    }),
+                );
+                match &result {
+                    Ok(resp) => tracing::info!(
+                        "✅ Exit executed: {:.2} shares @ ${:.4}, order {}",
+                        resp.filled_shares,
+                        resp.avg_fill_price,
+                        resp.order_id
+                    ),
+                    Err(e) => {
+                        tracing::error!(client_order_id = %order.client_order_id, "❌ Exit failed: {}", e);
+                        self.risk.record_error(&format!("Exit failed: {}", e));
+                        self.notifications.send(Notification::OrderFailed {
+                            market_id: order.market_id.clone(),
+                            error: e.to_string(),
+                        });
+                    }
+                }
+                self.save_runtime_state().await;
+            }
+            Err(e) => tracing::error!("❌ Could not record exit order intent, not submitting: {}", e),
+        }
+        self.exiting.lock().unwrap().remove(&tick.market_id);
+    }
+
+    /// Takes both legs of a cross-venue `opportunity`, reporting a failure
+    /// the way a failed copy is.
+    async fn take_arbitrage(&self, opportunity: Opportunity) {
+        let Some(arbitrage) = &self.arbitrage else { return };
+        match self.take_legs(arbitrage, &opportunity).await {
+            Ok(shares) if shares > 0.0 => tracing::info!(
+                "⚖️ Took {:.0} shares of {} for ${:.2} profit",
+                shares,
+                opportunity.question,
+                opportunity.edge * shares
+            ),
+            Ok(_) => tracing::info!("⚖️ Arbitrage in {} filled nothing", opportunity.question),
+            Err(e) => {
+                tracing::warn!("⚖️ Arbitrage in {} not taken: {:#}", opportunity.question, e);
+                self.notifications.send(Notification::OrderFailed {
+                    market_id: opportunity.polymarket_id.clone(),
+                    error: format!("Arbitrage: {:#}", e),
+                });
+            }
+        }
+        self.save_runtime_state().await;
+    }
+
+    /// Buys the first leg, then as many shares of the second as the first
+    /// filled, so nothing is left unhedged unless the second leg falls
+    /// short; returns the hedged shares.
+    async fn take_legs(&self, arbitrage: &ArbitrageDetector, opportunity: &Opportunity) -> Result<f64> {
+        let market = self.markets.get(&opportunity.polymarket_id).await?;
+        let [first, second] = opportunity.legs();
+        let size_usd = opportunity.cost * opportunity.shares;
+        let trade = arbitrage_trade(&market, first, opportunity.shares, self.clock.now_ms() / 1000);
+        self.risk.check_can_trade(&trade, &market, size_usd)?;
+
+        let filled = self
+            .take_leg(arbitrage, &market, first, opportunity.shares)
+            .await
+            .with_context(|| format!("{} leg on {} failed", first.outcome, first.venue.as_str()))?;
+        if filled <= 0.0 {
+            return Ok(0.0);
+        }
+        let hedged = self.take_leg(arbitrage, &market, second, filled).await.with_context(|| {
+            format!(
+                "{} leg on {} failed; {:.0} {} shares on {} are unhedged",
+                second.outcome,
+                second.venue.as_str(),
+                filled,
+                first.outcome,
+                first.venue.as_str()
+            )
+        })?;
+        if filled - hedged > 1e-9 {
+            anyhow::bail!(
+                "legs filled {:.0} {} and {:.0} {} shares; {:.0} are unhedged",
+                filled,
+                first.outcome,
+                hedged,
+                second.outcome,
+                filled - hedged
+            );
+        }
+        Ok(hedged)
+    }
+
+    /// Buys `shares` of `leg`, fill-and-kill, through the journal; returns
+    /// the shares filled.
+    async fn take_leg(&self, arbitrage: &ArbitrageDetector, market: &Market, leg: &Leg, shares: f64) -> Result<f64> {
+        let order = OrderRequest {
+            market_id: leg.token.clone(),
+            side: TradeSide::BUY,
+            shares,
+            price: Some(leg.price),
+            order_type: OrderType::FAK,
+            client_order_id: crate::executor::new_client_order_id(),
+        };
+        let context = json!({ "arbitrage": market.id, "venue": leg.venue.as_str() });
+        let resp = self
+            .place_journaled(arbitrage.exchange(leg.venue).as_ref(), order, context)
+            .await?;
+        let filled = resp.filled_shares;
+        if filled > 0.0 {
+            let trade = arbitrage_trade(market, leg, filled, self.clock.now_ms() / 1000);
+            self.risk.record_trade(&trade, filled * resp.avg_fill_price);
+        }
+        Ok(filled)
+    }
+
+    /// Places `order` on `exchange` the way copies are: the intent journaled
+    /// first (failing that blocks the order), then the outcome, any fill
+    /// booked into the portfolio, and an audit entry with `context`.
+    async fn place_journaled(&self, exchange: &dyn Exchange, order: OrderRequest, context: Value) -> Result<OrderResponse> {
+        let order_id = self
+            .record_intent(None, &order)
+            .await
+            .context("Could not record order intent, not submitting")?;
+        self.emit(BotEvent::OrderSubmitted { order: order.clone() });
+        let result = exchange.place_order(&order).await;
+        self.emit(BotEvent::OrderResult {
+            market_id: order.market_id.clone(),
+            response: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        self.record_outcome(order_id, &order, &result).await;
+        self.incidents.order_result(self.clock.now_ms(), result.is_err());
+        let mut details = json!({
+            "client_order_id": order.client_order_id,
+            "market_id": order.market_id,
+            "side": order.side.as_str(),
+            "shares": order.shares,
+            "limit_price": order.price,
+            "exchange_order_id": result.as_ref().ok().map(|r| r.order_id.clone()),
+            "error": result.as_ref().err().map(|e| e.to_string()),
+        });
+        if let (Some(details), Value::Object(context)) = (details.as_object_mut(), context) {
+            details.extend(context);
+        }
+        self.control.audit().record(AuditAction::OrderPlaced, "bot", details);
+        result
+    }
+
+    /// Context for notifications about `trade`; the market comes from the
+    /// cache and is left out if it can't be had.
+    async fn trade_card(&self, trade: &Trade) -> TradeCard {
+        let market = self.markets.get(&trade.market_id).await.ok();
+        TradeCard::new(trade, self.leaders.label(&trade.wallet), market.as_ref())
+    }
+
+    /// Skips a trade in a neg-risk event priced out of line with the event's
+    /// other outcomes; see [`consistency`].
+    async fn check_neg_risk(&self, whale_trade: &Trade, market: &Market) -> Option<Decision> {
+        let tolerance = self.config.max_neg_risk_deviation;
+        if tolerance <= 0.0 || market.event_id.is_empty() {
+            return None;
+        }
+        let group = match self.markets.gamma()?.neg_risk_group(&market.event_id).await {
+            Ok(group) => group?,
+            Err(e) => {
+                tracing::debug!("No neg-risk group for event {}: {}", market.event_id, e);
+                return None;
+            }
+        };
+        let check = consistency::check(&group, market, whale_trade)?;
+        if check.is_outlier(tolerance) {
+            tracing::warn!("🧮 Price {:.3} is {:+.3} off the {:.3} the other {} outcomes imply; skipping",
+                check.price, check.deviation(), check.implied, check.legs - 1);
+            return Some(Decision::skip(
+                SkipReason::RiskBlocked,
+                format!("Price {:.3} out of line with its neg-risk event (implied {:.3})", check.price, check.implied),
+            ));
+        }
+        if check.deviation().abs() > tolerance {
+            tracing::info!("🧮 Neg-risk event {} sums to {:.3}; copying as priced",
+                check.event_id, check.catalog_sum);
+        }
+        None
+    }
+
+    /// Skips buying into a crypto price question the underlying's spot
+    /// price leaves next to no chance; see [`crate::spot`].
+    fn check_spot(&self, whale_trade: &Trade, market: &Market) -> Option<Decision> {
+        let min = self.config.spot_min_probability;
+        if min <= 0.0 || whale_trade.side != TradeSide::BUY {
+            return None;
+        }
+        let (threshold, spot, yes) = self.spot.as_ref()?.assess(&market.question, market.end_date)?;
+        // Trading the second outcome token is buying no
+        let buys_no = market.token_ids.iter().position(|t| *t == whale_trade.market_id) == Some(1);
+        let chance = if buys_no { 1.0 - yes } else { yes };
+        tracing::info!("   {} spot ${:.2} vs ${:.2} strike: {:.1}% chance", threshold.asset, spot,
+            threshold.strike, chance * 100.0);
+        (chance < min).then(|| {
+            Decision::skip(
+                SkipReason::RiskBlocked,
+                format!(
+                    "{} at ${:.2} leaves a {:.1}% chance against the ${:.2} strike",
+                    threshold.asset, spot, chance * 100.0, threshold.strike
+                ),
+            )
+        })
+    }
+
+    /// Decides whether and how much to copy, without placing any order.
+    pub(crate) async fn decide(&self, whale_trade: &Trade) -> Decision {
+        if let Some(skip) = self.precheck(whale_trade) {
+            return skip;
+        }
+
+        // Get market info
+        let market = match self.markets.get(&whale_trade.market_id).await {
+            Ok(m) => m,
+            Err(e) => {
+                tracing::error!("Failed to fetch market: {}", e);
+                self.risk.record_error(&format!("Market fetch failed: {}", e));
+                return Decision::skip(SkipReason::MarketUnavailable, e.to_string());
+            }
+        };
+        self.emit(BotEvent::MarketFetched { market: market.clone() });
+        // Leaders' sells still go through, so held positions can be exited
+        if whale_trade.side == TradeSide::BUY {
+            if let Some(phrase) = self.markets.blacklisted(&market) {
+                tracing::info!("🚫 Market matches blacklist rule '{}'; skipping", phrase);
+                return Decision::skip(SkipReason::RiskBlocked, format!("Market blacklisted by '{}'", phrase));
+            }
+        }
+        if let Some(skip) = self.check_neg_risk(whale_trade, &market).await {
+            return skip;
+        }
+        if let Some(skip) = self.check_spot(whale_trade, &market) {
+            return skip;
+        }
+
+        // Get balances; paper trading spends its virtual cash
+        let balance = match &self.paper {
+            Some(paper) => Ok(paper.cash()),
+            None => self.executor.exchange().balance().await,
+        };
+        let your_balance = match balance {
+            Ok(b) => b,
+            Err(e) => {
+                tracing::error!("Failed to fetch your balance: {}", e);
+                self.risk.record_error(&format!("Balance fetch failed: {}", e));
+                return Decision::skip(SkipReason::BalanceUnavailable, e.to_string());
+            }
+        };
+        self.emit(BotEvent::BalanceFetched {
+            wallet: self.config.your_wallet.clone(),
+            balance: your_balance,
+        });
+
+        let whale_balance = match self.api.get_balance(&whale_trade.wallet).await {
+            Ok(b) => {
+                self.emit(BotEvent::BalanceFetched {
+                    wallet: whale_trade.wallet.clone(),
+                    balance: b,
+                });
+                b
+            }
+            Err(e) => {
+                tracing::error!("Failed to fetch whale balance: {}", e);
+                UNKNOWN_WHALE_BALANCE
+            }
+        };
+
+        self.size_and_check(whale_trade, &market, your_balance, whale_balance).await
+    }
+
+    /// Checks that need no market data: wallet, trading window, staleness and
+    /// price.
+    pub(crate) fn precheck(&self, whale_trade: &Trade) -> Option<Decision> {
+        let now = self.clock.now();
+        if self.control.is_paused() {
+            tracing::info!("⏸️  Copying is paused, skipping");
+            return Some(Decision::skip(SkipReason::Paused, "paused by operator"));
+        }
+
+        // Verify whale
+        if !self.risk.is_whale_verified(&whale_trade.wallet) {
+            tracing::warn!("⚠️  Unverified wallet, skipping");
+            return Some(Decision::skip(SkipReason::UnverifiedWallet, "wallet is not tracked"));
+        }
+
+        // Respect configured trading windows
+        if !self.schedule.is_open(now) {
+            tracing::info!("🌙 Outside trading window, skipping");
+            return Some(Decision::skip(SkipReason::OutsideTradingWindow, "outside trading window"));
+        }
+
+        // Skip trades that are already too old to copy profitably
+        let budget = self.config.latency_budget;
+        if !budget.is_zero() {
+            let age_ms = now.timestamp_millis() - whale_trade.timestamp * 1000;
+            if age_ms > budget.as_millis() as i64 {
+                tracing::warn!("⏱️  Trade is {}ms old (budget {}ms), skipping",
+                    age_ms, budget.as_millis());
+                return Some(Decision::skip(
+                    SkipReason::Stale,
+                    format!("{}ms old, budget {}ms", age_ms, budget.as_millis()),
+                ));
+            }
+        }
+
+        // Near-certain outcomes leave little upside and long shots little liquidity
+        let (min_price, max_price) = (self.config.min_price, self.config.max_price);
+        if whale_trade.price < min_price || whale_trade.price > max_price {
+            tracing::info!("🎯 Price {:.3} outside {:.3}-{:.3}, skipping",
+                whale_trade.price, min_price, max_price);
+            return Some(Decision::skip(
+                SkipReason::PriceOutOfRange,
+                format!("price {:.3} outside {:.3}-{:.3}", whale_trade.price, min_price, max_price),
+            ));
+        }
+
+        None
+    }
+
+    /// Sizing and risk checks against already fetched market data.
+    pub(crate) async fn size_and_check(
+        &self,
+        whale_trade: &Trade,
+        market: &Market,
+        your_balance: f64,
+        whale_balance: f64,
+    ) -> Decision {
+        tracing::info!("   Market: {}", market.question);
+        tracing::info!("   Liquidity: ${:.2}", market.liquidity);
+        let imbalance = self
+            .books
+            .as_ref()
+            .and_then(|b| b.imbalance(&whale_trade.market_id, self.config().imbalance_ticks));
+        if let Some(imbalance) = imbalance {
+            let pressure = imbalance.pressure(&whale_trade.side);
+            tracing::info!("   Book imbalance: {:+.2} ({:.0} bid / {:.0} ask shares)",
+                imbalance.ratio, imbalance.bid_shares, imbalance.ask_shares);
+            let max = self.config().max_chase_imbalance;
+            if max > 0.0 && pressure > max {
+                tracing::warn!("⚖️ Skipping copy chasing into a thin book ({:.0}% > {:.0}%)",
+                    pressure * 100.0, max * 100.0);
+                return Decision::skip(
+                    SkipReason::RiskBlocked,
+                    format!("Book imbalance {:.0}% against the copy", pressure * 100.0),
+                );
+            }
+        }
+
+        // Calculate position size
+        let size_usd = match self.sizer.calculate_size(whale_trade, your_balance, whale_balance).await {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::error!("Failed to calculate size: {}", e);
+                self.risk.record_error(&format!("Sizing failed: {}", e));
+                return Decision::skip(SkipReason::SizingFailed, e.to_string());
+            }
+        };
+
+        let shares = self.sizer.shares_from_usd(size_usd, whale_trade.price);
+
+        tracing::info!("   Your size: ${:.2} ({:.2} shares)", size_usd, shares);
+
+        // Risk checks
+        let checked = tracing::info_span!("risk", size_usd).in_scope(|| {
+            let checked = self.risk.check_can_trade(whale_trade, market, size_usd);
+            if let Err(e) = &checked {
+                tracing::error!("❌ Risk check failed: {}", e);
+            }
+            checked
+        });
+        if let Err(e) = checked {
+            return Decision::skip(SkipReason::RiskBlocked, e.to_string());
+        }
+        let (size_usd, shares) =
+            match self.risk.check_liquidity(&whale_trade.market_id, &whale_trade.side, size_usd, self.clock.now_ms()) {
+                Ok(s) if s < size_usd => (s, self.sizer.shares_from_usd(s, whale_trade.price)),
+                Ok(_) => (size_usd, shares),
+                Err(e) => {
+                    tracing::error!("❌ Liquidity check failed: {}", e);
+                    return Decision::skip(SkipReason::RiskBlocked, e.to_string());
+                }
+            };
+
+        tracing::info!("✅ Risk checks passed");
+
+        Decision::Copy { size_usd, shares }
+    }
+
+    fn emit(&self, event: BotEvent) {
+        self.events.publish(event);
+    }
+
+    /// The book of `market_id` as a trade is decided on, journaled with
+    /// the decision for slippage analytics and simulated fills: the
+    /// streamed one when there is one, the exchange's otherwise; `None`
+    /// without a journal.
+    async fn arrival_book(&self, market_id: &str) -> Option<OrderBook> {
+        self.storage.as_ref()?;
+        if let Some(book) = self.books.as_ref().and_then(|b| b.book(market_id)) {
+            return Some(book.to_order_book());
+        }
+        match self.api.get_orderbook(market_id).await {
+            Ok((bids, asks)) => {
+                self.marks.record_book(market_id, &bids, &asks, self.clock.now_ms());
+                Some(OrderBook::new(bids, asks))
+            }
+            Err(e) => {
+                tracing::debug!("No arrival book for {}: {}", market_id, e);
+                None
+            }
+        }
+    }
+
+    /// Journals the decision and keeps it for the status API.
+    async fn record_decision(
+        &self,
+        trade_id: Option<i64>,
+        trade: &Trade,
+        decision: &Decision,
+        arrival_book: Option<OrderBook>,
+    ) -> Option<i64> {
+        let (copied, reason, detail, size_usd) = match decision {
+            Decision::Skip { reason, detail } => (false, Some(*reason), Some(detail.clone()), None),
+            Decision::Copy { size_usd, .. } => (true, None, None, Some(*size_usd)),
+        };
+        let mut record = DecisionRecord {
+            id: 0,
+            leader_trade_id: trade_id,
+            wallet: trade.wallet.clone(),
+            market_id: trade.market_id.clone(),
+            side: trade.side.as_str().to_string(),
+            copied,
+            reason,
+            detail,
+            size_usd,
+            decided_at: self.clock.now_ms(),
+            arrival_mid: arrival_book.as_ref().and_then(|b| prices::mid_price(&b.bids, &b.asks)),
+            arrival_book: arrival_book
+                .filter(|_| self.config().decision_book_levels > 0)
+                .map(|b| b.top(self.config().decision_book_levels as usize)),
+        };
+        let id = match &self.storage {
+            Some(storage) => self.journaled(storage.record_decision(&record).await, "decision"),
+            None => None,
+        };
+        record.id = id.unwrap_or_default();
+        self.skips.record(record.decided_at, reason);
+        self.decisions.push(record);
+        id
+    }
+
+    /// Write-ahead intent: journals the order and its client id before it is
+    /// sent, so startup recovery can tell whether a crash mid-submit reached
+    /// the exchange. Unlike other journal writes, failing this blocks the order.
+    async fn record_intent(&self, decision_id: Option<i64>, order: &OrderRequest) -> Result<Option<i64>> {
+        let Some(storage) = &self.storage else { return Ok(None) };
+        let record = OrderRecord {
+            id: 0,
+            decision_id,
+            exchange_order_id: None,
+            market_id: order.market_id.clone(),
+            side: order.side.as_str().to_string(),
+            shares: order.shares,
+            limit_price: order.price,
+            order_type: format!("{:?}", order.order_type),
+            status: "submitting".to_string(),
+            error: None,
+            submitted_at: self.clock.now_ms(),
+            client_order_id: Some(order.client_order_id.clone()),
+        };
+        storage.record_order(&record).await.map(Some)
+    }
+
+    /// Journal writes never block trading; failures are reported and dropped.
+    fn journaled(&self, result: Result<i64>, what: &str) -> Option<i64> {
+        match result {
+            Ok(id) => Some(id),
+            Err(e) => {
+                self.storage_failed(&format!("journal {}", what), &e);
+                None
+            }
+        }
+    }
+
+    fn storage_failed(&self, operation: &str, error: &anyhow::Error) {
+        tracing::warn!("Failed to {}: {}", operation, error);
+        self.notifications.send(Notification::StorageFailed {
+            operation: operation.to_string(),
+            error: error.to_string(),
+        });
+    }
+
+    /// Persists risk counters and leader stats so a restart picks them up.
+    async fn save_runtime_state(&self) {
+        let Some(storage) = &self.storage else { return };
+        let risk = self.risk.snapshot(self.clock.now().date_naive());
+        let saved = async {
+            let now = self.clock.now_ms();
+            storage.save_state(RISK_STATE_KEY, &serde_json::to_string(&risk)?, now).await?;
+            storage
+                .save_state(LEADER_STATS_KEY, &serde_json::to_string(&self.leaders.all())?, now)
+                .await
+        };
+        if let Err(e) = saved.await {
+            self.storage_failed("save runtime state", &e);
+        }
+    }
+
+    /// Journals the exchange's answer and books any fill into the portfolio.
+    async fn record_outcome(&self, order_id: Option<i64>, order: &OrderRequest, result: &Result<OrderResponse>) {
+        let journal = self.storage.as_ref().zip(order_id);
+        if let Some((storage, order_id)) = journal {
+            let update = match result {
+                Ok(resp) => storage.update_order(order_id, &resp.status, Some(&resp.order_id), None).await,
+                Err(e) => storage.update_order(order_id, "failed", None, Some(&e.to_string())).await,
+            };
+            if let Err(e) = update {
+                self.storage_failed("journal order outcome", &e);
+            }
+        }
+
+        let Ok(resp) = result else { return };
+        if resp.filled_shares <= 0.0 {
+            return;
+        }
+        let fill = FillRecord {
+            id: 0,
+            order_id: order_id.unwrap_or(0),
+            market_id: order.market_id.clone(),
+            side: order.side.as_str().to_string(),
+            shares: resp.filled_shares,
+            price: resp.avg_fill_price,
+            fee: 0.0,
+            filled_at: self.clock.now_ms(),
+        };
+        if let Some((storage, _)) = journal {
+            self.journaled(storage.record_fill(&fill).await, "fill");
+        }
+
+        if let Some(paper) = &self.paper {
+            paper.apply_fill(&fill).await;
+        }
+        if let Some(shadow) = &self.shadow {
+            shadow.live_fill(&fill).await;
+        }
+        self.marks.record_trade(&fill.market_id, fill.price, fill.filled_at);
+        let realized = self.portfolio.apply_fill(&fill).await;
+        if realized != 0.0 {
+            tracing::info!("💰 Realized PnL: ${:.2}", realized);
+            self.risk.record_realized_pnl(realized);
+        }
+    }
+}
+
+/// The next tick for the exit rules or opportunity to take; never resolves
+/// without a subscription.
+async fn next_broadcast<T: Clone>(rx: &mut Option<tokio::sync::broadcast::Receiver<T>>) -> Option<T> {
+    let Some(rx) = rx else { return std::future::pending().await };
+    loop {
+        match rx.recv().await {
+            Ok(tick) => return Some(tick),
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// A bought arbitrage leg as the risk manager counts it: exposure to the
+/// Polymarket market's event, whichever venue it was bought on, at unix
+/// seconds `timestamp`.
+fn arbitrage_trade(market: &Market, leg: &Leg, shares: f64, timestamp: i64) -> Trade {
+    Trade {
+        wallet: "arbitrage".to_string(),
+        event_id: market.event_id.clone(),
+        market_id: leg.token.clone(),
+        side: TradeSide::BUY,
+        shares,
+        price: leg.price,
+        timestamp,
+        tx_hash: None,
+    }
+}
+
+/// Assumed leader bankroll when it can't be fetched.
+pub(crate) const UNKNOWN_WHALE_BALANCE: f64 = 1_000_000.0;
+
+/// Restores risk counters and leader stats saved by a previous run.
+async fn load_runtime_state(
+    storage: &dyn Storage,
+    risk: &RiskManager,
+    leaders: &LeaderBook,
+    today: chrono::NaiveDate,
+) -> Result<()> {
+    if let Some(json) = storage.load_state(RISK_STATE_KEY).await? {
+        let snapshot: RiskSnapshot = serde_json::from_str(&json).context("Stored risk state is corrupt")?;
+        risk.restore(snapshot, today);
+    }
+    if let Some(json) = storage.load_state(LEADER_STATS_KEY).await? {
+        leaders.replace(serde_json::from_str(&json).context("Stored leader stats are corrupt")?);
+    }
+    Ok(())
+}
+
+/// Starts watching active registry leaders that aren't watched yet and stops
+/// watching paused ones; a config reload may have undone either.
+fn apply_registry(registry: &[LeaderEntry], watcher: &dyn TradeSource, risk: &RiskManager, leaders: &LeaderBook) {
+    let running = watcher.wallets();
+    for entry in registry {
+        if let Some(label) = &entry.label {
+            leaders.set_label(&entry.wallet, label);
+        }
+        let watched = running.iter().find(|w| w.eq_ignore_ascii_case(&entry.wallet));
+        let changed = match (entry.paused, watched) {
+            (true, Some(wallet)) => watcher.remove_wallet(wallet).map(|removed| (removed, wallet.as_str())),
+            (false, None) => watcher.add_wallet(&entry.wallet).map(|added| (added, entry.wallet.as_str())),
+            _ => continue,
+        };
+        match changed {
+            Ok((true, wallet)) => {
+                risk.set_tracked(wallet, !entry.paused);
+                if entry.paused {
+                    tracing::info!("⏸️  Paused leader {}", leaders.label(wallet));
+                } else {
+                    tracing::info!("👀 Now watching leader {}", leaders.label(wallet));
+                }
+            }
+            Ok((false, _)) => {}
+            Err(e) => tracing::warn!("Failed to apply the leader registry to {}: {}", entry.wallet, e),
+        }
+    }
+}
+
+/// The backpressure gauges served at `/metrics`.
+fn register_gauges(gauges: &Gauges, watcher: &Arc<dyn TradeSource>, dedup: &Arc<TradeDeduper>) {
+    let watcher = Arc::clone(watcher);
+    gauges.register(
+        "polymarket_bot_trade_channel_depth",
+        "Leader trades waiting for the trade loop.",
+        move || watcher.queue_depth().map(|depth| depth as f64),
+    );
+    gauges.register(
+        "polymarket_bot_trade_channel_capacity",
+        "Leader trades the channel holds before the feeds block.",
+        || Some(crate::watcher::TRADE_CHANNEL_CAPACITY as f64),
+    );
+    let dedup = Arc::clone(dedup);
+    gauges.register(
+        "polymarket_bot_dedup_cache_entries",
+        "Leader trades remembered for deduplication.",
+        move || Some(dedup.cached() as f64),
+    );
+    gauges.register(
+        "polymarket_bot_resident_memory_bytes",
+        "Resident memory of the bot process.",
+        gauges::resident_memory_bytes,
+    );
+}
+
+/// Span for handling one leader trade, carrying its correlation id.
+fn trade_span(trade: &Trade) -> tracing::Span {
+    tracing::info_span!(
+        "copy",
+        cid = %logging::correlation_id(trade),
+        wallet = %trade.wallet,
+        market = %trade.market_id,
+        approval_id = tracing::field::Empty,
+    )
+}
+
+fn log_schedule_state(schedule: &TradingSchedule, open: bool, now: chrono::DateTime<chrono::Utc>) {
+    let next = schedule
+        .next_transition(now)
+        .map(|t| t.with_timezone(&schedule.timezone()).format("%Y-%m-%d %H:%M %Z").to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    if open {
+        tracing::info!("☀️  Trading window open - copying resumed (closes {})", next);
+    } else {
+        tracing::warn!("🌙 Trading window closed - copying paused (reopens {})", next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::BotBuilder;
+    use crate::storage::TimeRange;
+
+    #[tokio::test]
+    async fn test_orders_are_blocked_when_their_intent_cant_be_journaled() {
+        let mut config = BotBuilder::new()
+            .account("0xme", "a".repeat(64))
+            .watch_wallet("0xleader")
+            .storage("sqlite::memory:")
+            .config()
+            .clone();
+        notify::disable(&mut config);
+        config.paper_trading = true;
+        let bot = BotBuilder::from_config(config).build().await.unwrap();
+        let storage = bot.storage().unwrap();
+        let trade = Trade {
+            wallet: "0xleader".to_string(),
+            event_id: "e1".to_string(),
+            market_id: "m1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.4,
+            timestamp: 0,
+            tx_hash: None,
+        };
+
+        // The journal refuses an order for a decision it never recorded
+        assert!(bot.prepare_order(&trade, Some(404), 10.0).await.is_none());
+        assert!(storage.orders(TimeRange::all()).await.unwrap().is_empty());
+
+        // Otherwise the intent is journaled before anything is sent
+        let (order, id) = bot.prepare_order(&trade, None, 10.0).await.unwrap();
+        let orders = storage.orders(TimeRange::all()).await.unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(Some(orders[0].id), id);
+        assert_eq!(orders[0].status, "submitting");
+        assert_eq!(orders[0].client_order_id.as_deref(), Some(order.client_order_id.as_str()));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::bot::Bot;
+use crate::clock::{self, Clock};
+use crate::config::{self, validate_config};
+use crate::notify::{Notifier, NotifierRegistry};
+use crate::types::{Config, SizingMode};
+use crate::units::{Ratio, UsdcAmount};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Position sizing knobs, mirroring the sizing section of the config file.
+#[derive(Debug, Clone)]
+pub struct SizingSettings {
+    pub mode: SizingMode,
+    pub fixed_stake: UsdcAmount,
+    pub proportional_ratio: Ratio,
+    pub min_stake: UsdcAmount,
+    pub max_stake: UsdcAmount,
+}
+
+impl Default for SizingSettings {
+    fn default() -> Self {
+        let config = config::defaults();
+        Self {
+            mode: config.sizing_mode,
+            fixed_stake: config.fixed_stake,
+            proportional_ratio: config.proportional_ratio,
+            min_stake: config.min_stake,
+            max_stake: config.max_stake,
+        }
+    }
+}
+
+/// Risk limits and circuit breaker thresholds.
+#[derive(Debug, Clone)]
+pub struct RiskSettings {
+    pub max_exposure_per_event: UsdcAmount,
+    pub max_daily_volume: UsdcAmount,
+    pub min_liquidity: f64,
+    pub cb_consecutive_trigger: u32,
+    pub cb_min_depth_usd: f64,
+}
+
+impl Default for RiskSettings {
+    fn default() -> Self {
+        let config = config::defaults();
+        Self {
+            max_exposure_per_event: config.max_exposure_per_event,
+            max_daily_volume: config.max_daily_volume,
+            min_liquidity: config.min_liquidity,
+            cb_consecutive_trigger: config.cb_consecutive_trigger,
+            cb_min_depth_usd: config.cb_min_depth_usd,
+        }
+    }
+}
+
+/// Order submission behaviour.
+#[derive(Debug, Clone)]
+pub struct ExecutorSettings {
+    pub retry_attempts: u32,
+    pub retry_delay: Duration,
+    pub latency_budget: Duration,
+}
+
+impl Default for ExecutorSettings {
+    fn default() -> Self {
+        let config = config::defaults();
+        Self {
+            retry_attempts: config.retry_attempts,
+            retry_delay: Duration::from_millis(config.retry_delay_ms),
+            latency_budget: config.latency_budget,
+        }
+    }
+}
+
+/// Configures a [`Bot`] in code, as an alternative to the config file.
+///
+/// ```no_run
+/// # use polymarket_copy_bot::builder::{BotBuilder, RiskSettings};
+/// # use polymarket_copy_bot::units::UsdcAmount;
+/// let bot = BotBuilder::new()
+///     .account("0xYourWallet", "your-private-key")
+///     .watch_wallet("0xLeader")
+///     .with_risk(RiskSettings { max_daily_volume: UsdcAmount(500.0), ..Default::default() })
+///     .build();
+/// # let _ = bot;
+/// ```
+#[derive(Debug, Clone)]
+pub struct BotBuilder {
+    config: Config,
+    notifiers: NotifierRegistry,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for BotBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BotBuilder {
+    /// Starts from the same defaults the config file uses.
+    pub fn new() -> Self {
+        Self::from_config(config::defaults())
+    }
+
+    /// Starts from an existing config, e.g. one loaded from file and tweaked.
+    pub fn from_config(config: Config) -> Self {
+        Self {
+            config,
+            notifiers: NotifierRegistry::builtin(),
+            clock: clock::system(),
+        }
+    }
+
+    pub fn watch_wallet(mut self, wallet: impl Into<String>) -> Self {
+        self.config.wallets_to_track.push(wallet.into());
+        self
+    }
+
+    pub fn watch_wallets<I, S>(mut self, wallets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.wallets_to_track.extend(wallets.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the trading wallet and the key used to sign its orders.
+    pub fn account(mut self, wallet: impl Into<String>, private_key: impl Into<String>) -> Self {
+        self.config.your_wallet = wallet.into();
+        self.config.private_key = private_key.into();
+        self
+    }
+
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.config.polymarket_api = url.into();
+        self
+    }
+
+    pub fn ws_url(mut self, url: impl Into<String>) -> Self {
+        self.config.ws_url = url.into();
+        self
+    }
+
+    pub fn rpc_url(mut self, url: impl Into<String>) -> Self {
+        self.config.rpc_url = url.into();
+        self
+    }
+
+    pub fn with_sizing(mut self, sizing: SizingSettings) -> Self {
+        self.config.sizing_mode = sizing.mode;
+        self.config.fixed_stake = sizing.fixed_stake;
+        self.config.proportional_ratio = sizing.proportional_ratio;
+        self.config.min_stake = sizing.min_stake;
+        self.config.max_stake = sizing.max_stake;
+        self
+    }
+
+    pub fn with_risk(mut self, risk: RiskSettings) -> Self {
+        self.config.max_exposure_per_event = risk.max_exposure_per_event;
+        self.config.max_daily_volume = risk.max_daily_volume;
+        self.config.min_liquidity = risk.min_liquidity;
+        self.config.cb_consecutive_trigger = risk.cb_consecutive_trigger;
+        self.config.cb_min_depth_usd = risk.cb_min_depth_usd;
+        self
+    }
+
+    /// Restricts copying to daily windows like `09:00-23:00` in `timezone`.
+    pub fn trading_windows<I, S>(mut self, timezone: impl Into<String>, windows: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.trading_timezone = timezone.into();
+        self.config.trading_windows = windows.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Adds a local date (`YYYY-MM-DD`) on which no copies are made.
+    pub fn blackout_date(mut self, date: impl Into<String>) -> Self {
+        self.config.blackout_dates.push(date.into());
+        self
+    }
+
+    pub fn with_executor(mut self, executor: ExecutorSettings) -> Self {
+        self.config.retry_attempts = executor.retry_attempts;
+        self.config.retry_delay_ms = executor.retry_delay.as_millis() as u64;
+        self.config.latency_budget = executor.latency_budget;
+        self
+    }
+
+    /// The config as it stands, before validation.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Journals trades, decisions, orders and fills to e.g. `sqlite://bot.db`.
+    pub fn storage(mut self, url: impl Into<String>) -> Self {
+        self.config.storage_url = url.into();
+        self
+    }
+
+    /// Appends every event to a JSONL file that `--replay` can re-run.
+    pub fn event_log(mut self, path: impl Into<String>) -> Self {
+        self.config.event_log = path.into();
+        self
+    }
+
+    /// Sends notifications to a custom transport as well, under its own name.
+    pub fn notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifiers.add(notifier);
+        self
+    }
+
+    /// Registers a notifier built from the config when the bot starts, e.g.
+    /// one that stays off unless its settings are present.
+    pub fn register_notifier<F>(mut self, name: &str, factory: F) -> Self
+    where
+        F: Fn(&Config) -> Result<Option<Arc<dyn Notifier>>> + Send + Sync + 'static,
+    {
+        self.notifiers.register(name, factory);
+        self
+    }
+
+    /// Tells the time by `clock` instead of the system's, e.g. a
+    /// [`crate::clock::SimClock`] for replays.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Validates the config exactly like the file-based path and wires the bot.
+    pub async fn build(self) -> Result<Bot> {
+        validate_config(&self.config)?;
+        Bot::with_clock(self.config, self.notifiers, self.clock).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_builder_applies_settings() {
+        let builder = BotBuilder::new()
+            .account("0xme", "a".repeat(64))
+            .watch_wallet("0xleader")
+            .with_risk(RiskSettings {
+                max_daily_volume: UsdcAmount(750.0),
+                ..Default::default()
+            });
+
+        assert_eq!(builder.config().wallets_to_track, vec!["0xleader".to_string()]);
+        assert_eq!(builder.config().max_daily_volume, UsdcAmount(750.0));
+        assert!(builder.build().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_builder_uses_config_validation() {
+        let result = BotBuilder::new()
+            .account("0xme", "a".repeat(64))
+            .with_sizing(SizingSettings {
+                min_stake: UsdcAmount(50.0),
+                max_stake: UsdcAmount(10.0),
+                ..Default::default()
+            })
+            .watch_wallet("0xleader")
+            .build()
+            .await;
+
+        assert!(result.is_err());
+    }
+}
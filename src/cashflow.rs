@@ -0,0 +1,533 @@
+//! Ledger of the money moving into and out of the trading wallet.
+//!
+//! Trading PnL says how the trades did; ROI needs the capital behind them.
+//! Every `cash_flow_interval` live trading scans the chain for USDC
+//! transfers into or out of `your_wallet` and journals each as a deposit
+//! or withdrawal. Transfers with the exchange and CTF contracts are trades
+//! and redemptions, already in the journal as fills, so they're left out.
+//! The first scan records the opening capital instead: the wallet's USDC
+//! plus open positions at cost, since nothing before it is scanned.
+//!
+//! Redemptions (see [`crate::resolution`]) record the gas they spent, in
+//! USD at `gas_token_usd` a POL. Trading fees come from the journal's
+//! fills. [`Ledger`] puts it together with the latest point of the equity
+//! curve (see [`crate::equity`]): profit is equity less the net capital
+//! put in, so fees, gas and deposits can't pass for returns. Served on
+//! `/status/ledger`; `mybot ledger` prints it.
+
+use crate::clock::Clock;
+use crate::doctor::{SPENDERS, USDC_CONTRACT, USDC_DECIMALS};
+use crate::equity::EQUITY_STATE_KEY;
+use crate::paper::EquityPoint;
+use crate::portfolio::Portfolio;
+use crate::recovery::CTF_CONTRACT;
+use crate::rpc::{self, Metered, RpcStats};
+use crate::storage::{CashFlowRecord, FillRecord, Storage, TimeRange};
+use crate::types::Config;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::providers::{Middleware, Provider, Ws};
+use ethers::types::{Address, Bytes, Filter, TransactionRequest, H256, U256};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where the last block scanned for transfers is kept in the journal's
+/// state.
+pub const CASH_FLOW_STATE_KEY: &str = "cash_flow_block";
+
+pub const OPENING: &str = "opening";
+pub const DEPOSIT: &str = "deposit";
+pub const WITHDRAWAL: &str = "withdrawal";
+pub const GAS: &str = "gas";
+
+/// Blocks asked for per `eth_getLogs` call; providers cap the range.
+const SCAN_BLOCKS: u64 = 2_000;
+
+/// `balanceOf(address)`
+const BALANCE_OF_SELECTOR: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// A USDC transfer into or out of the wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub tx_hash: String,
+    pub log_index: u64,
+    /// Unix ms of its block
+    pub at: i64,
+    pub from: Address,
+    pub to: Address,
+    /// USD
+    pub amount: f64,
+}
+
+/// Where the wallet's USDC is read from.
+#[async_trait]
+pub trait UsdcTransfers: Send + Sync {
+    async fn head(&self) -> Result<u64>;
+
+    /// The wallet's USDC balance now.
+    async fn balance(&self) -> Result<f64>;
+
+    /// Transfers into or out of the wallet in blocks `from..=to`.
+    async fn transfers(&self, from: u64, to: u64) -> Result<Vec<Transfer>>;
+}
+
+/// Reads USDC transfers from the chain's event logs.
+pub struct ChainTransfers {
+    provider: Provider<Metered<Ws>>,
+    owner: Address,
+    usdc: Address,
+}
+
+impl ChainTransfers {
+    pub async fn connect(rpc_url: &str, owner: &str, stats: &Arc<RpcStats>) -> Result<Self> {
+        let owner: Address = owner.parse().context("Invalid wallet address")?;
+        let provider = rpc::connect_ws(rpc_url, stats).await?;
+        Ok(Self {
+            provider,
+            owner,
+            usdc: USDC_CONTRACT.parse()?,
+        })
+    }
+
+    async fn logs(&self, filter: Filter, timestamps: &mut HashMap<u64, i64>) -> Result<Vec<Transfer>> {
+        let logs = self.provider.get_logs(&filter).await.context("eth_getLogs failed")?;
+        let mut transfers = Vec::new();
+        for log in logs {
+            let (Some(block), Some(tx_hash)) = (log.block_number, log.transaction_hash) else {
+                continue;
+            };
+            if log.topics.len() < 3 {
+                continue;
+            }
+            let block = block.as_u64();
+            let at = match timestamps.get(&block) {
+                Some(at) => *at,
+                None => {
+                    let header = self
+                        .provider
+                        .get_block(block)
+                        .await?
+                        .with_context(|| format!("Block {} not found", block))?;
+                    let at = header.timestamp.as_u64() as i64 * 1000;
+                    timestamps.insert(block, at);
+                    at
+                }
+            };
+            transfers.push(Transfer {
+                tx_hash: format!("{:?}", tx_hash),
+                log_index: log.log_index.map_or(0, |i| i.as_u64()),
+                at,
+                from: Address::from(log.topics[1]),
+                to: Address::from(log.topics[2]),
+                amount: U256::from_big_endian(&log.data).as_u128() as f64 / USDC_DECIMALS,
+            });
+        }
+        Ok(transfers)
+    }
+}
+
+#[async_trait]
+impl UsdcTransfers for ChainTransfers {
+    async fn head(&self) -> Result<u64> {
+        Ok(self.provider.get_block_number().await?.as_u64())
+    }
+
+    async fn balance(&self) -> Result<f64> {
+        let mut data = BALANCE_OF_SELECTOR.to_vec();
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(self.owner.as_bytes());
+        let tx = TransactionRequest::new().to(self.usdc).data(Bytes::from(data));
+        let out = self
+            .provider
+            .call(&tx.into(), None)
+            .await
+            .context("balanceOf call failed")?;
+        Ok(U256::from_big_endian(&out).as_u128() as f64 / USDC_DECIMALS)
+    }
+
+    async fn transfers(&self, from: u64, to: u64) -> Result<Vec<Transfer>> {
+        let topic = H256::from(ethers::utils::keccak256("Transfer(address,address,uint256)"));
+        let owner = H256::from(self.owner);
+        let filter = Filter::new()
+            .address(self.usdc)
+            .topic0(topic)
+            .from_block(from)
+            .to_block(to);
+        let mut timestamps = HashMap::new();
+        let mut transfers = self.logs(filter.clone().topic2(owner), &mut timestamps).await?;
+        transfers.extend(self.logs(filter.topic1(owner), &mut timestamps).await?);
+        transfers.sort_by_key(|t| (t.at, t.log_index));
+        Ok(transfers)
+    }
+}
+
+/// The deposit or withdrawal a transfer is, if it's not a trade or
+/// redemption.
+pub fn classify(transfer: &Transfer, wallet: Address) -> Option<CashFlowRecord> {
+    let (kind, amount, counterparty) = if transfer.to == wallet {
+        (DEPOSIT, transfer.amount, transfer.from)
+    } else if transfer.from == wallet {
+        (WITHDRAWAL, -transfer.amount, transfer.to)
+    } else {
+        return None;
+    };
+    let trading = SPENDERS
+        .iter()
+        .map(|(_, address)| *address)
+        .chain([CTF_CONTRACT])
+        .any(|address| address.parse::<Address>().is_ok_and(|a| a == counterparty));
+    if trading || transfer.from == transfer.to {
+        return None;
+    }
+    Some(CashFlowRecord {
+        id: 0,
+        key: format!("{}:{}", transfer.tx_hash, transfer.log_index),
+        kind: kind.to_string(),
+        amount,
+        tx_hash: Some(transfer.tx_hash.clone()),
+        detail: Some(json!({ "counterparty": format!("{:?}", counterparty) }).to_string()),
+        occurred_at: transfer.at,
+    })
+}
+
+/// Journals the gas a transaction spent, `gas` POL at `token_usd` a POL.
+pub async fn record_gas(storage: &dyn Storage, tx_hash: &str, gas: f64, token_usd: f64, at: i64) -> Result<()> {
+    storage
+        .record_cash_flow(&CashFlowRecord {
+            id: 0,
+            key: format!("{}:gas", tx_hash),
+            kind: GAS.to_string(),
+            amount: -gas * token_usd,
+            tx_hash: Some(tx_hash.to_string()),
+            detail: Some(json!({ "pol": gas, "pol_usd": token_usd }).to_string()),
+            occurred_at: at,
+        })
+        .await?;
+    Ok(())
+}
+
+/// Capital in and out, costs, and the return on the capital.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Ledger {
+    pub opening: f64,
+    pub deposits: f64,
+    /// As a positive amount
+    pub withdrawals: f64,
+    /// Opening capital plus deposits less withdrawals
+    pub net_capital: f64,
+    pub fees: f64,
+    pub gas: f64,
+    /// The equity curve's last point; `None` before one is recorded
+    pub equity: Option<f64>,
+    /// Equity less net capital
+    pub profit: Option<f64>,
+    /// Profit over net capital
+    pub roi: Option<f64>,
+    /// Oldest first
+    pub flows: Vec<CashFlowRecord>,
+}
+
+/// The ledger of `flows` and `fills`, valued at `equity`.
+pub fn compute(flows: Vec<CashFlowRecord>, fills: &[FillRecord], equity: Option<f64>) -> Ledger {
+    let mut ledger = Ledger {
+        fees: fills.iter().map(|f| f.fee).sum(),
+        equity,
+        ..Default::default()
+    };
+    for flow in &flows {
+        match flow.kind.as_str() {
+            OPENING => ledger.opening += flow.amount,
+            DEPOSIT => ledger.deposits += flow.amount,
+            WITHDRAWAL => ledger.withdrawals -= flow.amount,
+            GAS => ledger.gas -= flow.amount,
+            _ => {}
+        }
+    }
+    ledger.net_capital = ledger.opening + ledger.deposits - ledger.withdrawals;
+    ledger.profit = equity.map(|e| e - ledger.net_capital);
+    ledger.roi = ledger
+        .profit
+        .filter(|_| ledger.net_capital > 0.0)
+        .map(|p| p / ledger.net_capital);
+    ledger.flows = flows;
+    ledger
+}
+
+/// The ledger since tracking began, from the journal.
+pub async fn from_journal(storage: &dyn Storage) -> Result<Ledger> {
+    let flows = storage.cash_flows(TimeRange::all()).await?;
+    let fills = storage.fills(TimeRange::all()).await?;
+    let equity = match storage.load_state(EQUITY_STATE_KEY).await? {
+        Some(json) => {
+            let curve: Vec<EquityPoint> = serde_json::from_str(&json).context("Stored equity curve is corrupt")?;
+            curve.last().map(|p| p.equity)
+        }
+        None => None,
+    };
+    Ok(compute(flows, &fills, equity))
+}
+
+impl fmt::Display for Ledger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Opening capital: ${:.2}", self.opening)?;
+        writeln!(f, "Deposits:        ${:.2}", self.deposits)?;
+        writeln!(f, "Withdrawals:     ${:.2}", self.withdrawals)?;
+        writeln!(f, "Net capital:     ${:.2}", self.net_capital)?;
+        writeln!(f, "Trading fees:    ${:.2}", self.fees)?;
+        writeln!(f, "Gas:             ${:.2}", self.gas)?;
+        match (self.equity, self.profit) {
+            (Some(equity), Some(profit)) => {
+                writeln!(f, "Equity:          ${:.2}", equity)?;
+                write!(f, "Profit:          ${:.2}", profit)?;
+                if let Some(roi) = self.roi {
+                    write!(f, " ({:+.2}% ROI)", roi * 100.0)?;
+                }
+                writeln!(f)?;
+            }
+            _ => writeln!(f, "Equity:          not recorded yet")?,
+        }
+        let moves: Vec<&CashFlowRecord> = self.flows.iter().filter(|c| c.kind != GAS).collect();
+        if !moves.is_empty() {
+            writeln!(f, "\nCapital flows:")?;
+        }
+        for flow in moves {
+            let at = chrono::DateTime::from_timestamp_millis(flow.occurred_at).unwrap_or_default();
+            writeln!(
+                f,
+                "  {}  {:<10} {:>12.2}  {}",
+                at.format("%Y-%m-%d %H:%M"),
+                flow.kind,
+                flow.amount,
+                flow.tx_hash.as_deref().unwrap_or("")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Journals the live wallet's deposits and withdrawals.
+pub struct CashFlowWatcher {
+    storage: Arc<dyn Storage>,
+    portfolio: Arc<Portfolio>,
+    wallet: String,
+    rpc_url: String,
+    rpc: Arc<RpcStats>,
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl CashFlowWatcher {
+    pub fn from_config(
+        config: &Config,
+        storage: Arc<dyn Storage>,
+        portfolio: Arc<Portfolio>,
+        rpc: Arc<RpcStats>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            storage,
+            portfolio,
+            wallet: config.your_wallet.clone(),
+            rpc_url: config.rpc_url.clone(),
+            rpc,
+            interval: config.cash_flow_interval,
+            clock,
+        }
+    }
+
+    /// Journals the transfers since the last scan, or the opening capital
+    /// on the first; returns the flows recorded.
+    pub async fn scan(&self, chain: &dyn UsdcTransfers) -> Result<Vec<CashFlowRecord>> {
+        let wallet: Address = self.wallet.parse().context("Invalid wallet address")?;
+        let head = chain.head().await?;
+        let last = match self.storage.load_state(CASH_FLOW_STATE_KEY).await? {
+            Some(block) => block.parse::<u64>().context("Stored cash flow block is corrupt")?,
+            None => {
+                let usdc = chain.balance().await?;
+                let positions: f64 = self.portfolio.holdings().iter().map(|h| h.cost()).sum();
+                let opening = CashFlowRecord {
+                    id: 0,
+                    key: OPENING.to_string(),
+                    kind: OPENING.to_string(),
+                    amount: usdc + positions,
+                    tx_hash: None,
+                    detail: Some(json!({ "usdc": usdc, "positions_at_cost": positions, "block": head }).to_string()),
+                    occurred_at: self.clock.now_ms(),
+                };
+                self.storage.record_cash_flow(&opening).await?;
+                self.save(head).await?;
+                return Ok(vec![opening]);
+            }
+        };
+
+        let mut recorded = Vec::new();
+        let mut from = last + 1;
+        while from <= head {
+            let to = head.min(from + SCAN_BLOCKS - 1);
+            for transfer in chain.transfers(from, to).await? {
+                let Some(flow) = classify(&transfer, wallet) else {
+                    continue;
+                };
+                if self.storage.record_cash_flow(&flow).await? {
+                    recorded.push(flow);
+                }
+            }
+            self.save(to).await?;
+            from = to + 1;
+        }
+        Ok(recorded)
+    }
+
+    async fn save(&self, block: u64) -> Result<()> {
+        self.storage
+            .save_state(CASH_FLOW_STATE_KEY, &block.to_string(), self.clock.now_ms())
+            .await
+    }
+
+    /// Connects to `rpc_url` and scans once.
+    pub async fn run(&self) -> Result<Vec<CashFlowRecord>> {
+        let chain = ChainTransfers::connect(&self.rpc_url, &self.wallet, &self.rpc).await?;
+        let flows = self.scan(&chain).await?;
+        for flow in &flows {
+            tracing::info!(
+                "💵 {} of ${:.2} in {}",
+                flow.kind,
+                flow.amount.abs(),
+                flow.tx_hash.as_deref().unwrap_or("-")
+            );
+        }
+        Ok(flows)
+    }
+
+    /// Scans every `cash_flow_interval`; a zero interval or a
+    /// non-WebSocket `rpc_url` disables it.
+    pub fn spawn(self: Arc<Self>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        if !self.rpc_url.starts_with("ws") {
+            tracing::warn!("⚠️  Tracking deposits and withdrawals needs a ws:// or wss:// RPC_URL; disabled");
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run().await {
+                    tracing::warn!("Cash flow scan failed: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimClock;
+    use crate::storage::sqlite::SqliteStore;
+    use crate::types::CostBasis;
+
+    const WALLET: &str = "0x1111111111111111111111111111111111111111";
+    const FRIEND: &str = "0x2222222222222222222222222222222222222222";
+
+    /// A chain at block 500 with a deposit, a withdrawal and a trade.
+    struct Chain;
+
+    #[async_trait]
+    impl UsdcTransfers for Chain {
+        async fn head(&self) -> Result<u64> {
+            Ok(500)
+        }
+
+        async fn balance(&self) -> Result<f64> {
+            Ok(900.0)
+        }
+
+        async fn transfers(&self, from: u64, to: u64) -> Result<Vec<Transfer>> {
+            assert_eq!((from, to), (101, 500));
+            let wallet: Address = WALLET.parse()?;
+            let friend: Address = FRIEND.parse()?;
+            let exchange: Address = SPENDERS[0].1.parse()?;
+            let transfer = |tx: &str, from, to, amount| Transfer {
+                tx_hash: tx.to_string(),
+                log_index: 0,
+                at: 1_000,
+                from,
+                to,
+                amount,
+            };
+            Ok(vec![
+                transfer("0xa", friend, wallet, 500.0),
+                transfer("0xb", wallet, friend, 200.0),
+                transfer("0xc", wallet, exchange, 50.0),
+            ])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_journals_capital_flows_and_computes_roi() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Fifo, None));
+        portfolio
+            .apply_fill(&FillRecord {
+                id: 0,
+                order_id: 1,
+                market_id: "m1".to_string(),
+                side: "BUY".to_string(),
+                shares: 250.0,
+                price: 0.4,
+                fee: 0.5,
+                filled_at: 0,
+            })
+            .await;
+        let config = Config {
+            your_wallet: WALLET.to_string(),
+            ..Config::default()
+        };
+        let watcher = CashFlowWatcher::from_config(
+            &config,
+            storage.clone(),
+            portfolio,
+            Arc::new(RpcStats::new()),
+            Arc::new(SimClock::at(0)),
+        );
+
+        // The first scan books the opening capital: USDC plus positions at cost
+        let opening = watcher.scan(&Chain).await.unwrap();
+        assert_eq!(opening.len(), 1);
+        assert_eq!(opening[0].amount, 1_000.0);
+
+        // Only blocks after the last scan, and trades are left out
+        storage.save_state(CASH_FLOW_STATE_KEY, "100", 0).await.unwrap();
+        let flows = watcher.scan(&Chain).await.unwrap();
+        let kinds: Vec<(&str, f64)> = flows.iter().map(|f| (f.kind.as_str(), f.amount)).collect();
+        assert_eq!(kinds, [(DEPOSIT, 500.0), (WITHDRAWAL, -200.0)]);
+        storage.save_state(CASH_FLOW_STATE_KEY, "100", 0).await.unwrap();
+        assert!(watcher.scan(&Chain).await.unwrap().is_empty());
+        record_gas(storage.as_ref(), "0xd", 0.02, 0.5, 2_000).await.unwrap();
+
+        let fills = [FillRecord {
+            id: 1,
+            order_id: 1,
+            market_id: "m1".to_string(),
+            side: "BUY".to_string(),
+            shares: 250.0,
+            price: 0.4,
+            fee: 0.5,
+            filled_at: 0,
+        }];
+        let flows = storage.cash_flows(TimeRange::all()).await.unwrap();
+        let ledger = compute(flows, &fills, Some(1_430.0));
+        assert_eq!(ledger.net_capital, 1_300.0);
+        assert!((ledger.gas - 0.01).abs() < 1e-9);
+        assert_eq!(ledger.fees, 0.5);
+        assert_eq!(ledger.profit, Some(130.0));
+        assert!((ledger.roi.unwrap() - 0.1).abs() < 1e-9);
+        assert_eq!(compute(Vec::new(), &[], Some(10.0)).roi, None);
+    }
+}
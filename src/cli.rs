@@ -0,0 +1,1183 @@
+//! Command line of the `mybot` binary.
+//!
+//! `mybot <command> [options]`, where options apply to every command:
+//!
+//! - `--config <path>` - config file layered under the environment
+//! - `--set key=value` - override one setting, repeatable
+//! - `--force` - take over the trading lease from another instance
+//! - `--output table|json` - how read commands print their results
+//!
+//! Without a command the bot runs. The older flag forms (`--check-config`,
+//! `--export <table>`, ...) still work and select the matching command.
+
+use crate::config::CliOverrides;
+use crate::export::ExportTable;
+use crate::manual::CancelTarget;
+use crate::synthetic::FeedPlan;
+use crate::tail::Only;
+use crate::types::{FillModelKind, OrderType, TradeSide};
+use crate::units::{parse_duration, Ratio, UsdcAmount};
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How far back `report wallet` looks without `--since`.
+const DEFAULT_REPORT_WINDOW: Duration = Duration::from_secs(7 * 24 * 3600);
+/// How far back `backtest` fetches trades without `--data`, `--journal` or
+/// `--since`.
+const DEFAULT_BACKTEST_WINDOW: Duration = Duration::from_secs(30 * 24 * 3600);
+/// Cash a backtest starts with without `--balance`.
+const DEFAULT_BACKTEST_BALANCE: f64 = 1_000.0;
+/// How far back `scout` looks without `--since`.
+const DEFAULT_SCOUT_WINDOW: Duration = Duration::from_secs(30 * 24 * 3600);
+/// Leaderboard wallets `scout` screens without `--limit`.
+const DEFAULT_SCOUT_LIMIT: usize = 50;
+/// Resampled runs `backtest` adds without `--simulations`.
+const DEFAULT_SIMULATIONS: usize = 1_000;
+/// Walk-forward folds of `sweep` without `--folds`.
+const DEFAULT_SWEEP_FOLDS: usize = 4;
+/// Spacing of the price history `data fetch` stores without `--interval`.
+const DEFAULT_FETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+pub const USAGE: &str = "\
+Usage: mybot [command] [--config <path>] [--set key=value]... [--force] [--output table|json]
+
+Commands:
+  run                      Copy trades (the default)
+  paper                    Copy trades with simulated orders
+  watch                    Print leader trades from the feeds without trading
+  tui                      Live dashboard of a running bot (needs its STATUS_API)
+  mempool                  Watch the mempool for pending leader transactions
+  tail [events.jsonl] [--follow] [--wallet <wallet>] [--market <market>]
+       [--only decisions|copies|skips|fills|connections]
+                           Print decisions, fills and connections from the event log
+  positions [list]         Print positions from the journal with cost basis and mark
+  positions show <market>  Print a position's lots, cost basis and mark
+  positions close <market> [--shares N] [--yes]
+                           Sell a position at market, after confirming
+  pnl                      Print realized and unrealized PnL per position, leader and category
+                           from the journal, marked at current prices
+  slippage [--from YYYY-MM-DD] [--to YYYY-MM-DD]
+                           Break copies' slippage against the leader's price into latency and
+                           execution, by leader, category and hour of day, from the journal
+  benchmark                Compare each leader's copies with perfect copies at the leader's price,
+                           splitting the gap into skipped trades and execution, from the journal
+  ledger                   Print deposits, withdrawals, gas and fees, and ROI on the net capital
+                           put in, from the journal
+  orders [list]            Print orders that may still fill, from the journal
+  orders cancel <id|all|--market <market>>
+                           Cancel open orders on the exchange
+  orders place --token <market> --side buy|sell --size N [--price P [--tif gtc|gtd|fak]] [--yes]
+                           Send an order, after confirming (a market order without --price)
+  leaders [list]           Print leaders from the config and the registry
+  leaders add <wallet> [--label <name>] [--profile <url>]
+                           Copy a leader (or rename one); a running bot picks it up
+  leaders remove <wallet>  Drop a leader from the registry
+  leaders pause <wallet>   Stop copying a leader until resumed
+  leaders resume <wallet>  Copy a paused leader again
+  leaders stats [--from YYYY-MM-DD] [--to YYYY-MM-DD]
+                           Rank leaders by the PnL of copying them, with win rate, slippage
+                           against their prices and copy latency, from the journal
+  markets search <query>   Find markets by question, slug or category, typos and all, in the
+                           catalog and the API
+  markets show <slug|id>   Print a market's token ids, tick size, book top, volume and end date
+  report wallet <wallet> [--since 7d]
+                           Volume, markets and estimated PnL of a wallet, from the journal and API
+  report paper [--from YYYY-MM-DD] [--to YYYY-MM-DD] [--out <report.html|report.md>]
+                           Equity, attribution, slippage and worst positions of a paper run
+  scout [--since 30d] [--limit 50] [--wallet a,b] [--latency 2s]
+                           Rank leaderboard wallets by ROI, consistency and copyability
+  backtest [--data <trades.jsonl|trades.csv> | --journal] [--since 30d] [--wallet <wallet>] [--balance 1000]
+           [--fill immediate|book|latency] [--latency 2s] [--fee 0%] [--out <report.html|report.md>]
+           [--simulations 1000] [--seed <n>]
+                           Replay leader history through sizing and risk with simulated fills, then
+                           resample its positions for drawdown and risk-of-ruin odds (0 runs: off)
+  sweep [backtest options] [--folds 4] [--ratio 1%,2%] [--slippage 0%,1%] [--budget 5s,30s]
+        [--prices 0-1,0.05-0.95]
+                           Tune copy parameters walk-forward and print out-of-sample results
+  stress [--trades 1000] [--burst 1] [--interval 5ms] [--malformed 0%] [--duplicates 0%]
+         [--disconnect-every 0] [--seed 0]
+                           Load and chaos test the watcher and engine on a local synthetic feed
+  data fetch --from YYYY-MM-DD [--to YYYY-MM-DD] [--wallet a,b] [--market m1,m2] [--interval 1m]
+                           Store leaders' trades and market price history in the journal
+  export <table> --out <file.csv|file.parquet> [--from YYYY-MM-DD] [--to YYYY-MM-DD]
+                           Export trades, decisions, orders, fills, pnl, prices or audit;
+                           lots (per-lot cost basis and gains) or cointracking for taxes
+  init                     Write a config file (bot.toml or --config) by answering questions
+  check-config             Validate the config and print lints
+  doctor                   Check the RPC node, feeds, exchange, signer, balances, approvals,
+                           clock and journal, with hints for what fails
+  show-config              Print every setting and where it came from
+  seal <fragment.toml>     Encrypt a config section with CONFIG_PASSPHRASE or CONFIG_KEYFILE
+  replay <events.jsonl>    Re-decide recorded trades against the current config
+  snapshot <file>          Save the journal's state to a file
+  restore <file>           Restore the journal's state from a snapshot
+  completions <bash|zsh|fish>
+                           Print a shell completion script
+  help                     Print this message
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Run,
+    Paper,
+    Watch,
+    Tui,
+    Mempool,
+    Tail {
+        /// The configured `event_log` when not given
+        path: Option<PathBuf>,
+        follow: bool,
+        wallet: Option<String>,
+        market: Option<String>,
+        only: Option<Only>,
+    },
+    Positions(PositionsCommand),
+    Pnl,
+    Slippage {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    Benchmark,
+    Ledger,
+    Orders(OrdersCommand),
+    Leaders(LeadersCommand),
+    Markets(MarketsCommand),
+    Report(ReportCommand),
+    /// Screen leaderboard wallets (and `wallets`) as leaders to copy
+    Scout {
+        since: Duration,
+        limit: usize,
+        wallets: Vec<String>,
+        /// The configured `fill_latency` when not set
+        latency: Option<Duration>,
+    },
+    Backtest {
+        options: BacktestOptions,
+        /// Also write a tear sheet here (.html or .md)
+        out: Option<PathBuf>,
+        /// Monte Carlo runs over the positions; none when zero
+        simulations: usize,
+        /// A random seed when not set
+        seed: Option<u64>,
+    },
+    /// Walk-forward sweep of copy parameters; a parameter not given keeps
+    /// its configured value
+    Sweep {
+        backtest: BacktestOptions,
+        folds: usize,
+        ratios: Vec<f64>,
+        slippages: Vec<f64>,
+        budgets: Vec<Duration>,
+        price_bands: Vec<(f64, f64)>,
+    },
+    /// Load and chaos test of the watcher and engine on a synthetic feed
+    Stress(FeedPlan),
+    Data(DataCommand),
+    Export {
+        table: ExportTable,
+        out: PathBuf,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    Init,
+    CheckConfig,
+    Doctor,
+    ShowConfig,
+    Seal(PathBuf),
+    Replay(PathBuf),
+    Snapshot(PathBuf),
+    Restore(PathBuf),
+    Completions(Shell),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionsCommand {
+    List,
+    Show(String),
+    /// Sell `shares` (all by default) at market; `yes` skips the prompt
+    Close {
+        market: String,
+        shares: Option<f64>,
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrdersCommand {
+    List,
+    Cancel(CancelTarget),
+    /// A limit order when `price` is set, otherwise a market order
+    Place {
+        token: String,
+        side: TradeSide,
+        price: Option<f64>,
+        size: f64,
+        order_type: OrderType,
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LeadersCommand {
+    List,
+    Add {
+        wallet: String,
+        label: Option<String>,
+        profile: Option<String>,
+    },
+    Remove(String),
+    Pause(String),
+    Resume(String),
+    Stats {
+        from: Option<String>,
+        to: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketsCommand {
+    Search(String),
+    /// By slug or condition id
+    Show(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportCommand {
+    /// A wallet's trades over the last `since`
+    Wallet { wallet: String, since: Duration },
+    /// The paper run between the dates (YYYY-MM-DD), from the journal
+    Paper {
+        from: Option<String>,
+        to: Option<String>,
+        /// A tear sheet (.html or .md) instead of the printed summary
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestOptions {
+    /// A dataset file instead of the trades API
+    pub data: Option<PathBuf>,
+    /// The journal's leader trades instead of the trades API
+    pub journal: bool,
+    /// Only this leader instead of every tracked one
+    pub wallet: Option<String>,
+    /// Every trade in `data` or the journal when not set
+    pub since: Option<Duration>,
+    pub balance: f64,
+    /// The configured `fill_model` and `fill_latency` when not set
+    pub fill: Option<FillModelKind>,
+    pub latency: Option<Duration>,
+    /// Fraction of each fill's notional
+    pub fee: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataCommand {
+    Fetch {
+        /// The tracked leaders when empty
+        wallets: Vec<String>,
+        /// The markets the fetched trades touched when empty
+        markets: Vec<String>,
+        from: String,
+        /// Today when not set
+        to: Option<String>,
+        interval: Duration,
+    },
+}
+
+/// How read commands (positions, orders, leaders, show-config,
+/// check-config) print their results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            other => anyhow::bail!("Unknown --output {} (table or json)", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub command: Command,
+    pub cli: CliOverrides,
+    pub output: OutputFormat,
+}
+
+/// Every command with its subcommands (or, for export, its tables), for
+/// shell completions.
+pub const COMMANDS: &[(&str, &[&str])] = &[
+    ("run", &[]),
+    ("paper", &[]),
+    ("watch", &[]),
+    ("tui", &[]),
+    ("mempool", &[]),
+    ("tail", &[]),
+    ("positions", &["list", "show", "close"]),
+    ("pnl", &[]),
+    ("slippage", &[]),
+    ("benchmark", &[]),
+    ("ledger", &[]),
+    ("orders", &["list", "cancel", "place"]),
+    ("leaders", &["list", "add", "remove", "pause", "resume", "stats"]),
+    ("markets", &["search", "show"]),
+    ("report", &["wallet", "paper"]),
+    ("scout", &[]),
+    ("backtest", &[]),
+    ("sweep", &[]),
+    ("stress", &[]),
+    ("data", &["fetch"]),
+    (
+        "export",
+        &[
+            "trades",
+            "decisions",
+            "orders",
+            "fills",
+            "pnl",
+            "prices",
+            "audit",
+            "lots",
+            "cointracking",
+        ],
+    ),
+    ("init", &[]),
+    ("check-config", &[]),
+    ("doctor", &[]),
+    ("show-config", &[]),
+    ("seal", &[]),
+    ("replay", &[]),
+    ("snapshot", &[]),
+    ("restore", &[]),
+    ("completions", &["bash", "zsh", "fish"]),
+    ("help", &[]),
+];
+
+/// Options every command takes.
+pub const GLOBAL_OPTIONS: &[&str] = &["--config", "--set", "--force", "--output"];
+
+/// Options that take a value and only apply to some commands.
+pub const COMMAND_OPTIONS: &[&str] = &[
+    "--out",
+    "--from",
+    "--to",
+    "--shares",
+    "--market",
+    "--token",
+    "--side",
+    "--price",
+    "--size",
+    "--tif",
+    "--label",
+    "--profile",
+    "--wallet",
+    "--only",
+    "--since",
+    "--data",
+    "--balance",
+    "--fill",
+    "--fee",
+    "--latency",
+    "--interval",
+    "--folds",
+    "--ratio",
+    "--slippage",
+    "--budget",
+    "--prices",
+    "--limit",
+    "--simulations",
+    "--seed",
+    "--trades",
+    "--burst",
+    "--malformed",
+    "--duplicates",
+    "--disconnect-every",
+];
+pub const COMMAND_SWITCHES: &[&str] = &["--yes", "--follow", "--journal"];
+
+/// The command line as clap reads it; [`parse`] turns it into [`Args`].
+/// Help is [`USAGE`], printed by the `help` command.
+#[derive(Debug, Parser)]
+#[command(
+    name = "mybot",
+    override_usage = "mybot [command] [--config <path>] [--set key=value]... [--force] [--output table|json]",
+    disable_help_flag = true,
+    disable_help_subcommand = true
+)]
+struct Cli {
+    /// Config file layered under the environment
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Override one setting
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    set: Vec<String>,
+    /// Take over the trading lease from another instance
+    #[arg(long, global = true)]
+    force: bool,
+    #[arg(long, global = true, value_name = "table|json")]
+    output: Option<OutputFormat>,
+    #[arg(short = 'h', long = "help", global = true)]
+    help: bool,
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    Run,
+    Paper,
+    Watch,
+    Tui,
+    Mempool,
+    Tail {
+        path: Option<PathBuf>,
+        #[arg(long)]
+        follow: bool,
+        #[arg(long)]
+        wallet: Option<String>,
+        #[arg(long)]
+        market: Option<String>,
+        #[arg(long)]
+        only: Option<Only>,
+    },
+    Positions {
+        #[command(subcommand)]
+        command: Option<PositionsCli>,
+    },
+    Pnl,
+    Slippage {
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+    },
+    Benchmark,
+    Ledger,
+    Orders {
+        #[command(subcommand)]
+        command: Option<OrdersCli>,
+    },
+    Leaders {
+        #[command(subcommand)]
+        command: Option<LeadersCli>,
+    },
+    Markets {
+        #[command(subcommand)]
+        command: MarketsCli,
+    },
+    Report {
+        #[command(subcommand)]
+        command: ReportCli,
+    },
+    Scout {
+        #[arg(long, value_parser = parse_duration)]
+        since: Option<Duration>,
+        #[arg(long)]
+        limit: Option<usize>,
+        #[arg(long, value_delimiter = ',')]
+        wallet: Vec<String>,
+        #[arg(long, value_parser = parse_duration)]
+        latency: Option<Duration>,
+    },
+    Backtest {
+        #[command(flatten)]
+        options: BacktestArgs,
+        #[arg(long)]
+        out: Option<PathBuf>,
+        #[arg(long)]
+        simulations: Option<usize>,
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    Sweep {
+        #[command(flatten)]
+        backtest: BacktestArgs,
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        folds: Option<u64>,
+        #[arg(long, value_delimiter = ',')]
+        ratio: Vec<Ratio>,
+        #[arg(long, value_delimiter = ',')]
+        slippage: Vec<Ratio>,
+        #[arg(long, value_delimiter = ',', value_parser = parse_duration)]
+        budget: Vec<Duration>,
+        #[arg(long, value_delimiter = ',', value_parser = parse_price_band)]
+        prices: Vec<(f64, f64)>,
+    },
+    Stress {
+        #[arg(long)]
+        trades: Option<usize>,
+        #[arg(long)]
+        burst: Option<usize>,
+        #[arg(long, value_parser = parse_duration)]
+        interval: Option<Duration>,
+        #[arg(long)]
+        malformed: Option<Ratio>,
+        #[arg(long)]
+        duplicates: Option<Ratio>,
+        #[arg(long)]
+        disconnect_every: Option<usize>,
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    Data {
+        #[command(subcommand)]
+        command: DataCli,
+    },
+    Export {
+        table: ExportTable,
+        #[arg(long, value_name = "file.csv|file.parquet")]
+        out: PathBuf,
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+    },
+    Init,
+    CheckConfig,
+    Doctor,
+    ShowConfig,
+    Seal {
+        path: PathBuf,
+    },
+    Replay {
+        path: PathBuf,
+    },
+    Snapshot {
+        path: PathBuf,
+    },
+    Restore {
+        path: PathBuf,
+    },
+    Completions {
+        shell: Shell,
+    },
+    Help,
+}
+
+#[derive(Debug, Subcommand)]
+enum PositionsCli {
+    List,
+    Show {
+        market: String,
+    },
+    Close {
+        market: String,
+        #[arg(long)]
+        shares: Option<f64>,
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OrdersCli {
+    List,
+    Cancel {
+        /// An order id or `all`
+        #[arg(required_unless_present = "market", conflicts_with = "market")]
+        order: Option<String>,
+        #[arg(long)]
+        market: Option<String>,
+    },
+    Place {
+        #[arg(long)]
+        token: String,
+        #[arg(long, value_parser = parse_side)]
+        side: TradeSide,
+        #[arg(long)]
+        size: f64,
+        #[arg(long)]
+        price: Option<f64>,
+        /// Without a price the order is a market order
+        #[arg(long, requires = "price", value_parser = parse_tif)]
+        tif: Option<OrderType>,
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum LeadersCli {
+    List,
+    Add {
+        wallet: String,
+        #[arg(long)]
+        label: Option<String>,
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    Remove {
+        wallet: String,
+    },
+    Pause {
+        wallet: String,
+    },
+    Resume {
+        wallet: String,
+    },
+    Stats {
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum MarketsCli {
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    Show {
+        /// A slug or condition id
+        market: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ReportCli {
+    Wallet {
+        wallet: String,
+        #[arg(long, value_parser = parse_duration)]
+        since: Option<Duration>,
+    },
+    Paper {
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+/// The options `backtest` and `sweep` share.
+#[derive(Debug, clap::Args)]
+struct BacktestArgs {
+    #[arg(long, conflicts_with = "journal")]
+    data: Option<PathBuf>,
+    #[arg(long)]
+    journal: bool,
+    #[arg(long)]
+    wallet: Option<String>,
+    #[arg(long, value_parser = parse_duration)]
+    since: Option<Duration>,
+    #[arg(long)]
+    balance: Option<UsdcAmount>,
+    #[arg(long)]
+    fill: Option<FillModelKind>,
+    #[arg(long, value_parser = parse_duration)]
+    latency: Option<Duration>,
+    #[arg(long)]
+    fee: Option<Ratio>,
+}
+
+#[derive(Debug, Subcommand)]
+enum DataCli {
+    Fetch {
+        #[arg(long, value_delimiter = ',')]
+        wallet: Vec<String>,
+        #[arg(long, value_delimiter = ',')]
+        market: Vec<String>,
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        from: String,
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        to: Option<String>,
+        #[arg(long, value_parser = parse_duration)]
+        interval: Option<Duration>,
+    },
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args> {
+    let args = std::iter::once("mybot".to_string()).chain(older_forms(args)?);
+    let parsed = Cli::try_parse_from(args)?;
+
+    let mut cli = CliOverrides {
+        config_file: parsed.config,
+        ..Default::default()
+    };
+    for assignment in &parsed.set {
+        cli.push_assignment(assignment)?;
+    }
+    if parsed.force {
+        cli.push_assignment("force_instance_lease=true")?;
+    }
+    let command = match parsed.command {
+        _ if parsed.help => Command::Help,
+        None => Command::Run,
+        Some(command) => command.into_command()?,
+    };
+    Ok(Args {
+        command,
+        cli,
+        output: parsed.output.unwrap_or_default(),
+    })
+}
+
+/// The older flag forms (`--check-config`, `--export <table>`, ...) as the
+/// commands they select, put first.
+fn older_forms(args: impl IntoIterator<Item = String>) -> Result<Vec<String>> {
+    let mut command = Vec::new();
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check-config" | "--show-config" => command.push(arg[2..].to_string()),
+            "--seal" | "--replay" | "--snapshot" | "--restore" | "--export" => {
+                let what = if arg == "--export" { "a table" } else { "a path" };
+                let value = args.next().with_context(|| format!("{} requires {}", arg, what))?;
+                command.extend([arg[2..].to_string(), value]);
+            }
+            _ => rest.push(arg),
+        }
+    }
+    command.extend(rest);
+    Ok(command)
+}
+
+impl CliCommand {
+    fn into_command(self) -> Result<Command> {
+        Ok(match self {
+            CliCommand::Run => Command::Run,
+            CliCommand::Paper => Command::Paper,
+            CliCommand::Watch => Command::Watch,
+            CliCommand::Tui => Command::Tui,
+            CliCommand::Mempool => Command::Mempool,
+            CliCommand::Tail {
+                path,
+                follow,
+                wallet,
+                market,
+                only,
+            } => Command::Tail {
+                path,
+                follow,
+                wallet,
+                market,
+                only,
+            },
+            CliCommand::Positions { command } => Command::Positions(match command {
+                None | Some(PositionsCli::List) => PositionsCommand::List,
+                Some(PositionsCli::Show { market }) => PositionsCommand::Show(market),
+                Some(PositionsCli::Close { market, shares, yes }) => PositionsCommand::Close { market, shares, yes },
+            }),
+            CliCommand::Pnl => Command::Pnl,
+            CliCommand::Slippage { from, to } => Command::Slippage { from, to },
+            CliCommand::Benchmark => Command::Benchmark,
+            CliCommand::Ledger => Command::Ledger,
+            CliCommand::Orders { command } => Command::Orders(match command {
+                None | Some(OrdersCli::List) => OrdersCommand::List,
+                Some(OrdersCli::Cancel { order, market }) => OrdersCommand::Cancel(match (market, order) {
+                    (Some(market), _) => CancelTarget::Market(market),
+                    (None, Some(all)) if all == "all" => CancelTarget::All,
+                    (None, order) => CancelTarget::Order(order.unwrap_or_default()),
+                }),
+                Some(OrdersCli::Place {
+                    token,
+                    side,
+                    size,
+                    price,
+                    tif,
+                    yes,
+                }) => OrdersCommand::Place {
+                    token,
+                    side,
+                    order_type: match (tif, price) {
+                        (Some(tif), _) => tif,
+                        (None, Some(_)) => OrderType::LIMIT,
+                        (None, None) => OrderType::MARKET,
+                    },
+                    price,
+                    size,
+                    yes,
+                },
+            }),
+            CliCommand::Leaders { command } => Command::Leaders(match command {
+                None | Some(LeadersCli::List) => LeadersCommand::List,
+                Some(LeadersCli::Add { wallet, label, profile }) => LeadersCommand::Add { wallet, label, profile },
+                Some(LeadersCli::Remove { wallet }) => LeadersCommand::Remove(wallet),
+                Some(LeadersCli::Pause { wallet }) => LeadersCommand::Pause(wallet),
+                Some(LeadersCli::Resume { wallet }) => LeadersCommand::Resume(wallet),
+                Some(LeadersCli::Stats { from, to }) => LeadersCommand::Stats { from, to },
+            }),
+            CliCommand::Markets { command } => Command::Markets(match command {
+                MarketsCli::Search { query } => MarketsCommand::Search(query.join(" ")),
+                MarketsCli::Show { market } => MarketsCommand::Show(market),
+            }),
+            CliCommand::Report { command } => Command::Report(match command {
+                ReportCli::Wallet { wallet, since } => ReportCommand::Wallet {
+                    wallet,
+                    since: since.unwrap_or(DEFAULT_REPORT_WINDOW),
+                },
+                ReportCli::Paper { from, to, out } => ReportCommand::Paper { from, to, out },
+            }),
+            CliCommand::Scout {
+                since,
+                limit,
+                wallet,
+                latency,
+            } => Command::Scout {
+                since: since.unwrap_or(DEFAULT_SCOUT_WINDOW),
+                limit: limit.unwrap_or(DEFAULT_SCOUT_LIMIT),
+                wallets: wallet,
+                latency,
+            },
+            CliCommand::Backtest {
+                options,
+                out,
+                simulations,
+                seed,
+            } => Command::Backtest {
+                options: options.into_options(),
+                out,
+                simulations: simulations.unwrap_or(DEFAULT_SIMULATIONS),
+                seed,
+            },
+            CliCommand::Sweep {
+                backtest,
+                folds,
+                ratio,
+                slippage,
+                budget,
+                prices,
+            } => Command::Sweep {
+                backtest: backtest.into_options(),
+                folds: folds.map_or(DEFAULT_SWEEP_FOLDS, |f| f as usize),
+                ratios: ratio.into_iter().map(Ratio::as_f64).collect(),
+                slippages: slippage.into_iter().map(Ratio::as_f64).collect(),
+                budgets: budget,
+                price_bands: prices,
+            },
+            CliCommand::Stress {
+                trades,
+                burst,
+                interval,
+                malformed,
+                duplicates,
+                disconnect_every,
+                seed,
+            } => {
+                let defaults = FeedPlan::default();
+                Command::Stress(FeedPlan {
+                    trades: trades.unwrap_or(defaults.trades),
+                    burst: burst.unwrap_or(defaults.burst).max(1),
+                    interval: interval.unwrap_or(defaults.interval),
+                    malformed: malformed.map_or(0.0, |r| r.as_f64()),
+                    duplicates: duplicates.map_or(0.0, |r| r.as_f64()),
+                    disconnect_every: disconnect_every.unwrap_or(defaults.disconnect_every),
+                    seed: seed.unwrap_or(defaults.seed),
+                    ..defaults
+                })
+            }
+            CliCommand::Data {
+                command:
+                    DataCli::Fetch {
+                        wallet,
+                        market,
+                        from,
+                        to,
+                        interval,
+                    },
+            } => Command::Data(DataCommand::Fetch {
+                wallets: wallet,
+                markets: market,
+                from,
+                to,
+                interval: interval.unwrap_or(DEFAULT_FETCH_INTERVAL),
+            }),
+            CliCommand::Export { table, out, from, to } => Command::Export { table, out, from, to },
+            CliCommand::Init => Command::Init,
+            CliCommand::CheckConfig => Command::CheckConfig,
+            CliCommand::Doctor => Command::Doctor,
+            CliCommand::ShowConfig => Command::ShowConfig,
+            CliCommand::Seal { path } => Command::Seal(path),
+            CliCommand::Replay { path } => Command::Replay(path),
+            CliCommand::Snapshot { path } => Command::Snapshot(path),
+            CliCommand::Restore { path } => Command::Restore(path),
+            CliCommand::Completions { shell } => Command::Completions(shell),
+            CliCommand::Help => Command::Help,
+        })
+    }
+}
+
+impl BacktestArgs {
+    fn into_options(self) -> BacktestOptions {
+        let recorded = self.data.is_some() || self.journal;
+        BacktestOptions {
+            since: self.since.or((!recorded).then_some(DEFAULT_BACKTEST_WINDOW)),
+            data: self.data,
+            journal: self.journal,
+            wallet: self.wallet,
+            balance: self.balance.map_or(DEFAULT_BACKTEST_BALANCE, |b| b.as_f64()),
+            fill: self.fill,
+            latency: self.latency,
+            fee: self.fee.map_or(0.0, |f| f.as_f64()),
+        }
+    }
+}
+
+fn parse_side(side: &str) -> Result<TradeSide> {
+    TradeSide::parse(side).with_context(|| format!("Unknown side {} (buy or sell)", side))
+}
+
+fn parse_tif(tif: &str) -> Result<OrderType> {
+    OrderType::from_tif(tif).with_context(|| format!("Unknown time in force {} (gtc, gtd or fak)", tif))
+}
+
+/// `0.05-0.95`: copy leader trades priced from 0.05 to 0.95.
+fn parse_price_band(band: &str) -> Result<(f64, f64)> {
+    let invalid = || format!("Invalid price band {} (e.g. 0.05-0.95)", band);
+    let (min, max) = band.split_once('-').with_context(invalid)?;
+    let (min, max): (f64, f64) = (
+        min.trim().parse().with_context(invalid)?,
+        max.trim().parse().with_context(invalid)?,
+    );
+    if !(0.0..=1.0).contains(&min) || !(min..=1.0).contains(&max) {
+        anyhow::bail!(invalid());
+    }
+    Ok((min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Args> {
+        parse(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let args = parse_str("").unwrap();
+        assert_eq!(args.command, Command::Run);
+
+        let args = parse_str("paper --config bot.toml --set max_trade_size=5").unwrap();
+        assert_eq!(args.command, Command::Paper);
+        assert_eq!(args.cli.config_file, Some(PathBuf::from("bot.toml")));
+        assert_eq!(args.cli.values, vec![("max_trade_size".to_string(), "5".to_string())]);
+
+        let export = Command::Export {
+            table: ExportTable::Fills,
+            out: PathBuf::from("fills.csv"),
+            from: Some("2024-01-01".to_string()),
+            to: None,
+        };
+        assert_eq!(
+            parse_str("export fills --out fills.csv --from 2024-01-01")
+                .unwrap()
+                .command,
+            export
+        );
+        // The older flag form
+        assert_eq!(
+            parse_str("--export fills --from 2024-01-01 --out fills.csv")
+                .unwrap()
+                .command,
+            export
+        );
+        assert_eq!(parse_str("--check-config").unwrap().command, Command::CheckConfig);
+        assert_eq!(parse_str("pnl --output json").unwrap().command, Command::Pnl);
+        assert_eq!(
+            parse_str("slippage --from 2024-05-01").unwrap().command,
+            Command::Slippage {
+                from: Some("2024-05-01".to_string()),
+                to: None
+            }
+        );
+        assert_eq!(parse_str("benchmark").unwrap().command, Command::Benchmark);
+        assert_eq!(parse_str("ledger").unwrap().command, Command::Ledger);
+        assert_eq!(
+            parse_str("replay events.jsonl").unwrap().command,
+            Command::Replay(PathBuf::from("events.jsonl"))
+        );
+
+        assert_eq!(
+            parse_str("positions").unwrap().command,
+            Command::Positions(PositionsCommand::List)
+        );
+        assert_eq!(
+            parse_str("positions close m1 --shares 5 --yes").unwrap().command,
+            Command::Positions(PositionsCommand::Close {
+                market: "m1".to_string(),
+                shares: Some(5.0),
+                yes: true
+            })
+        );
+        assert!(parse_str("positions show").is_err());
+        assert_eq!(
+            parse_str("orders cancel --market m1").unwrap().command,
+            Command::Orders(OrdersCommand::Cancel(CancelTarget::Market("m1".to_string())))
+        );
+        assert_eq!(
+            parse_str("orders place --token m1 --side sell --size 10 --price 0.4 --tif fak")
+                .unwrap()
+                .command,
+            Command::Orders(OrdersCommand::Place {
+                token: "m1".to_string(),
+                side: TradeSide::SELL,
+                price: Some(0.4),
+                size: 10.0,
+                order_type: OrderType::FAK,
+                yes: false
+            })
+        );
+        assert_eq!(
+            parse_str("leaders add 0xabc --label Theo").unwrap().command,
+            Command::Leaders(LeadersCommand::Add {
+                wallet: "0xabc".to_string(),
+                label: Some("Theo".to_string()),
+                profile: None
+            })
+        );
+        assert_eq!(
+            parse_str("tail --follow --only skips --wallet 0xabc").unwrap().command,
+            Command::Tail {
+                path: None,
+                follow: true,
+                wallet: Some("0xabc".to_string()),
+                market: None,
+                only: Some(Only::Skips)
+            }
+        );
+        assert_eq!(
+            parse_str("report wallet 0xabc --since 30d").unwrap().command,
+            Command::Report(ReportCommand::Wallet {
+                wallet: "0xabc".to_string(),
+                since: Duration::from_secs(30 * 24 * 3600)
+            })
+        );
+        assert_eq!(
+            parse_str("markets search us election").unwrap().command,
+            Command::Markets(MarketsCommand::Search("us election".to_string()))
+        );
+        assert_eq!(
+            parse_str("backtest --data trades.csv --fill latency --latency 2s --fee 10bps")
+                .unwrap()
+                .command,
+            Command::Backtest {
+                options: BacktestOptions {
+                    data: Some(PathBuf::from("trades.csv")),
+                    journal: false,
+                    wallet: None,
+                    since: None,
+                    balance: 1_000.0,
+                    fill: Some(FillModelKind::Latency),
+                    latency: Some(Duration::from_secs(2)),
+                    fee: 0.001,
+                },
+                out: None,
+                simulations: 1_000,
+                seed: None,
+            }
+        );
+        assert!(matches!(
+            parse_str("backtest --simulations 0 --seed 42").unwrap().command,
+            Command::Backtest {
+                simulations: 0,
+                seed: Some(42),
+                ..
+            }
+        ));
+        assert!(parse_str("backtest --simulations 2.5").is_err());
+        assert_eq!(
+            parse_str("stress --trades 500 --burst 50 --malformed 5% --disconnect-every 100")
+                .unwrap()
+                .command,
+            Command::Stress(FeedPlan {
+                trades: 500,
+                burst: 50,
+                malformed: 0.05,
+                disconnect_every: 100,
+                ..Default::default()
+            })
+        );
+        assert!(parse_str("stress --duplicates lots").is_err());
+        assert_eq!(
+            parse_str("report paper --from 2024-01-01 --out paper.html")
+                .unwrap()
+                .command,
+            Command::Report(ReportCommand::Paper {
+                from: Some("2024-01-01".to_string()),
+                to: None,
+                out: Some(PathBuf::from("paper.html")),
+            })
+        );
+        match parse_str("sweep --journal --folds 3 --ratio 1%,2% --prices 0-1,0.05-0.95")
+            .unwrap()
+            .command
+        {
+            Command::Sweep {
+                backtest,
+                folds,
+                ratios,
+                slippages,
+                price_bands,
+                ..
+            } => {
+                assert!(backtest.journal && backtest.since.is_none());
+                assert_eq!((folds, ratios, slippages), (3, vec![0.01, 0.02], vec![]));
+                assert_eq!(price_bands, vec![(0.0, 1.0), (0.05, 0.95)]);
+            }
+            other => panic!("not a sweep: {:?}", other),
+        }
+        assert!(parse_str("sweep --prices 0.9-0.1").is_err());
+        assert_eq!(
+            parse_str("scout --limit 0 --wallet 0xa").unwrap().command,
+            Command::Scout {
+                since: Duration::from_secs(30 * 24 * 3600),
+                limit: 0,
+                wallets: vec!["0xa".to_string()],
+                latency: None,
+            }
+        );
+        assert!(parse_str("sweep --folds 0").is_err());
+        assert!(parse_str("backtest --data trades.csv --journal").is_err());
+        assert_eq!(
+            parse_str("data fetch --from 2024-01-01 --wallet 0xa,0xb --interval 5m")
+                .unwrap()
+                .command,
+            Command::Data(DataCommand::Fetch {
+                wallets: vec!["0xa".to_string(), "0xb".to_string()],
+                markets: vec![],
+                from: "2024-01-01".to_string(),
+                to: None,
+                interval: Duration::from_secs(300),
+            })
+        );
+        assert!(parse_str("data fetch --wallet 0xa").is_err());
+        assert!(parse_str("orders place --token m1 --side buy --size 10 --tif gtc").is_err());
+        assert!(parse_str("positions list --yes").is_err());
+
+        let args = parse_str("orders --output json").unwrap();
+        assert_eq!(args.output, OutputFormat::Json);
+        assert!(parse_str("orders --output yaml").is_err());
+
+        assert!(parse_str("export fills").is_err());
+        assert!(parse_str("run --out x.csv").is_err());
+        assert!(parse_str("run watch").is_err());
+        assert!(parse_str("trade").is_err());
+        assert!(parse_str("--verbose").is_err());
+    }
+}
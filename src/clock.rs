@@ -0,0 +1,107 @@
+//! Where the bot's time comes from.
+//!
+//! Everything that depends on the time of day or on time passing (latency
+//! budgets, trading windows, approval expiry, retry delays, daily resets)
+//! asks the bot's [`Clock`] rather than the system. Live bots run on
+//! [`SystemClock`]; replays and backtests on a [`SimClock`] they move to each
+//! recorded trade, so a decision is the same on every run and waiting costs
+//! nothing.
+//!
+//! Durations measured for latency stats stay on the system's monotonic
+//! clock: they describe the bot's own speed, not the market's time.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[async_trait]
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Unix ms.
+    fn now_ms(&self) -> i64 {
+        self.now().timestamp_millis()
+    }
+
+    /// Waits until `duration` has passed on this clock.
+    async fn sleep(&self, duration: Duration);
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// Virtual time, moved by hand. Sleeping moves it forward at once. Clones
+/// share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct SimClock {
+    now_ms: Arc<AtomicI64>,
+}
+
+impl SimClock {
+    /// Starting at unix ms `at_ms`.
+    pub fn at(at_ms: i64) -> Self {
+        let clock = Self::default();
+        clock.set(at_ms);
+        clock
+    }
+
+    /// Moves to unix ms `at_ms`, backwards too.
+    pub fn set(&self, at_ms: i64) {
+        self.now_ms.store(at_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now_ms.fetch_add(duration.as_millis() as i64, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl Clock for SimClock {
+    fn now(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.now_ms()).unwrap_or_default()
+    }
+
+    fn now_ms(&self) -> i64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+        tokio::task::yield_now().await;
+    }
+}
+
+/// The system clock, shared.
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sim_clock_sleep_moves_time_at_once() {
+        let clock = SimClock::at(1_700_000_000_000);
+        let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+        let started = std::time::Instant::now();
+        shared.sleep(Duration::from_secs(3600)).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(clock.now_ms(), 1_700_003_600_000);
+        assert_eq!(shared.now().timestamp(), 1_700_003_600);
+    }
+}
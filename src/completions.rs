@@ -0,0 +1,174 @@
+//! Shell completion scripts for `mybot`, generated from the command table
+//! in [`crate::cli`] so they can't drift from what the parser accepts.
+//!
+//! ```bash
+//! mybot completions bash > /etc/bash_completion.d/mybot
+//! mybot completions zsh > "${fpath[1]}/_mybot"
+//! mybot completions fish > ~/.config/fish/completions/mybot.fish
+//! ```
+
+use crate::cli::{Shell, COMMANDS, COMMAND_OPTIONS, COMMAND_SWITCHES, GLOBAL_OPTIONS};
+use std::fmt::Write;
+
+/// Values offered after options that take one of a few.
+const OPTION_VALUES: &[(&str, &str)] = &[
+    ("--output", "table json"),
+    ("--only", "decisions copies skips fills connections"),
+    ("--side", "buy sell"),
+    ("--tif", "gtc gtd fak"),
+];
+
+/// Options whose value is a path.
+const PATH_OPTIONS: &[&str] = &["--config", "--out"];
+
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+fn command_names() -> String {
+    COMMANDS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(" ")
+}
+
+fn all_options() -> String {
+    GLOBAL_OPTIONS
+        .iter()
+        .chain(COMMAND_OPTIONS)
+        .chain(COMMAND_SWITCHES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bash() -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "_mybot() {{");
+    let _ = writeln!(
+        out,
+        "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\""
+    );
+    let _ = writeln!(out, "    case \"$prev\" in");
+    let _ = writeln!(
+        out,
+        "        {}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;",
+        PATH_OPTIONS.join("|")
+    );
+    for (option, values) in OPTION_VALUES {
+        let _ = writeln!(
+            out,
+            "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+            option, values
+        );
+    }
+    let _ = writeln!(out, "    esac");
+    let _ = writeln!(out, "    if [[ \"$cur\" == -* ]]; then");
+    let _ = writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        all_options()
+    );
+    let _ = writeln!(out, "    elif [[ $COMP_CWORD -eq 1 ]]; then");
+    let _ = writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        command_names()
+    );
+    let _ = writeln!(out, "    elif [[ $COMP_CWORD -eq 2 ]]; then");
+    let _ = writeln!(out, "        case \"${{COMP_WORDS[1]}}\" in");
+    for (name, subcommands) in COMMANDS.iter().filter(|(_, subs)| !subs.is_empty()) {
+        let _ = writeln!(
+            out,
+            "            {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+            name,
+            subcommands.join(" ")
+        );
+    }
+    let _ = writeln!(out, "            *) COMPREPLY=($(compgen -f -- \"$cur\")) ;;");
+    let _ = writeln!(out, "        esac");
+    let _ = writeln!(out, "    fi");
+    let _ = writeln!(out, "}}");
+    let _ = writeln!(out, "complete -F _mybot mybot");
+    out
+}
+
+fn zsh() -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "#compdef mybot");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "_mybot() {{");
+    let _ = writeln!(out, "    case $words[CURRENT-1] in");
+    let _ = writeln!(out, "        {}) _files; return ;;", PATH_OPTIONS.join("|"));
+    for (option, values) in OPTION_VALUES {
+        let _ = writeln!(out, "        {}) compadd {}; return ;;", option, values);
+    }
+    let _ = writeln!(out, "    esac");
+    let _ = writeln!(out, "    if [[ $PREFIX == -* ]]; then");
+    let _ = writeln!(out, "        compadd -- {}", all_options());
+    let _ = writeln!(out, "    elif (( CURRENT == 2 )); then");
+    let _ = writeln!(out, "        compadd {}", command_names());
+    let _ = writeln!(out, "    elif (( CURRENT == 3 )); then");
+    let _ = writeln!(out, "        case $words[2] in");
+    for (name, subcommands) in COMMANDS.iter().filter(|(_, subs)| !subs.is_empty()) {
+        let _ = writeln!(out, "            {}) compadd {} ;;", name, subcommands.join(" "));
+    }
+    let _ = writeln!(out, "            *) _files ;;");
+    let _ = writeln!(out, "        esac");
+    let _ = writeln!(out, "    fi");
+    let _ = writeln!(out, "}}");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "_mybot \"$@\"");
+    out
+}
+
+fn fish() -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "complete -c mybot -f");
+    let _ = writeln!(
+        out,
+        "complete -c mybot -n __fish_use_subcommand -a \"{}\"",
+        command_names()
+    );
+    for (name, subcommands) in COMMANDS.iter().filter(|(_, subs)| !subs.is_empty()) {
+        let _ = writeln!(
+            out,
+            "complete -c mybot -n \"__fish_seen_subcommand_from {}\" -a \"{}\"",
+            name,
+            subcommands.join(" ")
+        );
+    }
+    for option in GLOBAL_OPTIONS.iter().chain(COMMAND_OPTIONS).chain(COMMAND_SWITCHES) {
+        let name = &option[2..];
+        let _ = match OPTION_VALUES.iter().find(|(o, _)| o == option) {
+            Some((_, values)) => writeln!(out, "complete -c mybot -l {} -x -a \"{}\"", name, values),
+            None if PATH_OPTIONS.contains(option) => writeln!(out, "complete -c mybot -l {} -r -F", name),
+            None if COMMAND_SWITCHES.contains(option) || *option == "--force" => {
+                writeln!(out, "complete -c mybot -l {}", name)
+            }
+            None => writeln!(out, "complete -c mybot -l {} -x", name),
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_cover_every_command() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell);
+            for (name, subcommands) in COMMANDS {
+                assert!(script.contains(name), "{:?} misses {}", shell, name);
+                for sub in *subcommands {
+                    assert!(script.contains(sub), "{:?} misses {} {}", shell, name, sub);
+                }
+            }
+            assert!(script.contains("table json"));
+        }
+        assert!(script(Shell::Bash).ends_with("complete -F _mybot mybot\n"));
+    }
+}
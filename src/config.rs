@@ -1,99 +1,1007 @@
-use crate::types::{Config, SizingMode};
+use crate::config_migration::{self, MigrationMode};
+use crate::leaders;
+use crate::schedule::TradingSchedule;
+use crate::sealed;
+use crate::types::{Config, CostBasis, Severity, ShutdownOrders, SizingMode, TradeFeed, Venue};
+use crate::units::{parse_duration, Ratio, UnitError, UsdcAmount};
 use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Config file picked up when neither `--config` nor `CONFIG_FILE` is given.
+pub const DEFAULT_CONFIG_FILE: &str = "bot.toml";
+
+/// Every known config key with its built-in default. Keys without a default
+/// are required. The env var for a key is its upper-cased name.
+const KEYS: &[(&str, Option<&str>)] = &[
+    ("wallets_to_track", None),
+    ("leader_labels", Some("")),
+    ("your_wallet", None),
+    ("private_key", None),
+    ("polymarket_api", Some("https://api.polymarket.com")),
+    ("exchange", Some("polymarket")),
+    ("kalshi_api", Some("https://api.elections.kalshi.com/trade-api/v2")),
+    ("kalshi_key_id", Some("")),
+    ("kalshi_private_key", Some("")),
+    ("gamma_api", Some("https://gamma-api.polymarket.com")),
+    ("data_api", Some("https://data-api.polymarket.com")),
+    ("data_api_rate", Some("5")),
+    ("ws_url", Some("wss://ws-subscriptions-clob.polymarket.com/ws")),
+    ("trade_source", Some("websocket")),
+    (
+        "subgraph_url",
+        Some("https://api.goldsky.com/api/public/project_cl6mb8i9h0003e201j6li0diw/subgraphs/orderbook-subgraph/0.0.1/gn"),
+    ),
+    ("subgraph_poll_interval", Some("5s")),
+    ("book_ws_url", Some("wss://ws-subscriptions-clob.polymarket.com/ws/market")),
+    ("book_tokens", Some("")),
+    ("book_held_markets", Some("false")),
+    ("book_tokens_per_connection", Some("200")),
+    ("decision_book_levels", Some("10")),
+    ("price_alerts", Some("")),
+    ("imbalance_ticks", Some("5")),
+    ("max_chase_imbalance", Some("0%")),
+    ("stream_held_markets", Some("true")),
+    ("rpc_url", None),
+    ("sizing_mode", Some("fixed")),
+    ("fixed_stake", Some("25.0")),
+    ("proportional_ratio", Some("0.02")),
+    ("min_stake", Some("5.0")),
+    ("max_stake", Some("100.0")),
+    ("max_exposure_per_event", Some("500.0")),
+    ("max_daily_volume", Some("2000.0")),
+    ("min_liquidity", Some("1000.0")),
+    ("liquidity_window", Some("30m")),
+    ("max_spread", Some("0")),
+    ("max_neg_risk_deviation", Some("0")),
+    ("spot_ws_url", Some("wss://stream.binance.com:9443/ws")),
+    ("spot_assets", Some("")),
+    ("spot_volatility", Some("60%")),
+    ("spot_min_probability", Some("0%")),
+    ("arb_pairs", Some("")),
+    ("arb_auto_pair", Some("false")),
+    ("arb_interval", Some("0s")),
+    ("arb_min_edge", Some("2%")),
+    ("arb_polymarket_fee", Some("0%")),
+    ("arb_kalshi_fee", Some("7%")),
+    ("arb_max_stake", Some("50.0")),
+    ("arb_auto_execute", Some("false")),
+    ("max_book_share", Some("0%")),
+    ("min_trades_per_hour", Some("0")),
+    ("cb_consecutive_trigger", Some("3")),
+    ("cb_min_depth_usd", Some("100.0")),
+    ("retry_attempts", Some("4")),
+    ("retry_delay", Some("500ms")),
+    ("max_slippage", Some("0%")),
+    ("max_daily_loss", Some("0")),
+    ("stop_loss", Some("0%")),
+    ("take_profit", Some("0%")),
+    ("paper_trading", Some("false")),
+    ("fill_model", Some("immediate")),
+    ("fill_latency", Some("1s")),
+    ("paper_balance", Some("1000.0")),
+    ("paper_settle_interval", Some("10m")),
+    ("shadow", Some("")),
+    ("storage_url", Some("sqlite://bot.db")),
+    ("cost_basis", Some("average")),
+    ("pnl_interval", Some("1m")),
+    ("equity_interval", Some("1h")),
+    ("cash_flow_interval", Some("5m")),
+    ("gas_token_usd", Some("0.25")),
+    ("reconcile_interval", Some("10m")),
+    ("stale_position_after", Some("6h")),
+    ("reconcile_auto_correct", Some("false")),
+    ("resolution_interval", Some("10m")),
+    ("dispute_interval", Some("10m")),
+    ("auto_redeem", Some("true")),
+    ("instance_lease_ttl", Some("30s")),
+    ("force_instance_lease", Some("false")),
+    ("event_log", Some("")),
+    ("capture_ws_frames", Some("false")),
+    ("ws_frame_retention", Some("7d")),
+    ("journal_retention", Some("180d")),
+    ("archive_dir", Some("archive")),
+    ("compaction_interval", Some("6h")),
+    ("latency_budget", Some("0s")),
+    ("min_price", Some("0")),
+    ("max_price", Some("1")),
+    ("dedup_window", Some("24h")),
+    ("market_cache_ttl", Some("60s")),
+    ("market_max_stale", Some("10m")),
+    ("market_blacklist", Some("")),
+    ("price_sample_interval", Some("30s")),
+    ("price_watch_window", Some("15m")),
+    ("price_retention", Some("90d")),
+    ("mark_max_age", Some("2m")),
+    ("trading_timezone", Some("UTC")),
+    ("trading_windows", Some("")),
+    ("blackout_dates", Some("")),
+    ("telegram_bot_token", Some("")),
+    ("telegram_chat_id", Some("")),
+    ("discord_webhook", Some("")),
+    ("discord_trades_webhook", Some("")),
+    ("discord_errors_webhook", Some("")),
+    ("discord_bot_token", Some("")),
+    ("discord_command_channel", Some("")),
+    ("webhook_urls", Some("")),
+    ("webhook_secret", Some("")),
+    ("webhook_retries", Some("3")),
+    ("pagerduty_routing_key", Some("")),
+    ("smtp_url", Some("")),
+    ("email_from", Some("")),
+    ("email_to", Some("")),
+    ("email_max_per_hour", Some("6")),
+    ("feed_down_alert", Some("5m")),
+    ("approval_threshold", Some("0")),
+    ("approval_timeout", Some("5m")),
+    ("health_addr", Some("")),
+    ("status_api", Some("false")),
+    ("admin_token", Some("")),
+    ("pid_file", Some("")),
+    ("shutdown_orders", Some("keep")),
+    ("otel_exporter_otlp_endpoint", Some("")),
+    ("otel_service_name", Some("polymarket-bot")),
+    ("latency_summary_interval", Some("15m")),
+    ("heartbeat_url", Some("")),
+    ("heartbeat_interval", Some("60s")),
+    ("opsgenie_api_key", Some("")),
+    ("opsgenie_url", Some("https://api.opsgenie.com")),
+    ("incident_stall_after", Some("30m")),
+    ("incident_error_rate", Some("0.5")),
+    ("incident_error_window", Some("15m")),
+    ("incident_min_orders", Some("5")),
+    ("anomaly_window", Some("30m")),
+    ("anomaly_min_events", Some("5")),
+    ("spike_markets", Some("0")),
+    ("spike_interval", Some("5m")),
+    ("spike_window", Some("6h")),
+    ("spike_factor", Some("3")),
+    ("spike_holder_change", Some("50%")),
+    ("notify_routes", Some("")),
+    ("notify_min_severity", Some("info")),
+    ("notify_leaders", Some("")),
+    ("notify_digest_interval", Some("0s")),
+    ("notify_dedup_window", Some("5m")),
+    ("notify_recovery_delay", Some("2m")),
+];
+
+/// The built-in default of a key; `None` for required keys.
+pub fn default_value(key: &str) -> Option<&'static str> {
+    KEYS.iter().find(|(k, _)| *k == key).and_then(|(_, default)| *default)
+}
+
+/// Keys whose values are never printed in provenance reports.
+const SECRET_KEYS: &[&str] = &[
+    "private_key",
+    "kalshi_private_key",
+    "admin_token",
+    "heartbeat_url",
+    "telegram_bot_token",
+    "discord_webhook",
+    "discord_trades_webhook",
+    "discord_errors_webhook",
+    "discord_bot_token",
+    "webhook_secret",
+    "pagerduty_routing_key",
+    "smtp_url",
+    "opsgenie_api_key",
+    "webhook_urls",
+];
+
+/// A value as provenance reports print it: secrets hidden, the password
+/// taken out of a storage url and the RPC url cut to its host, since
+/// providers put the API key in the path.
+fn shown_value(key: &str, value: &str) -> String {
+    match key {
+        _ if SECRET_KEYS.contains(&key) => "<redacted>".to_string(),
+        "storage_url" => without_password(value),
+        "rpc_url" => crate::rpc::provider_name(value),
+        _ => value.to_string(),
+    }
+}
+
+/// `postgres://bot:hunter2@db/bot` as `postgres://bot:<redacted>@db/bot`; a
+/// `password` query parameter hides the whole query.
+fn without_password(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) if query.to_lowercase().contains("password") => (rest, "?<redacted>".to_string()),
+        Some((rest, query)) => (rest, format!("?{}", query)),
+        None => (rest, String::new()),
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let authority = match authority.rsplit_once('@') {
+        Some((userinfo, host)) if userinfo.contains(':') => {
+            format!("{}:<redacted>@{}", userinfo.split(':').next().unwrap_or_default(), host)
+        }
+        _ => authority.to_string(),
+    };
+    format!("{}://{}{}{}", scheme, authority, path, query)
+}
+
+/// Where a config value came from, ordered from lowest to highest precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueSource {
+    Default,
+    File,
+    /// Decrypted from a `[sealed]` table in the config file.
+    Sealed,
+    Env,
+    Cli,
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueSource::Default => "default",
+            ValueSource::File => "file",
+            ValueSource::Sealed => "file (sealed)",
+            ValueSource::Env => "env",
+            ValueSource::Cli => "cli",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Overrides supplied on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct CliOverrides {
+    pub config_file: Option<PathBuf>,
+    pub values: Vec<(String, String)>,
+}
+
+impl CliOverrides {
+    /// Parses a `key=value` pair as given to `--set`.
+    pub fn push_assignment(&mut self, assignment: &str) -> Result<()> {
+        let (key, value) = assignment
+            .split_once('=')
+            .with_context(|| format!("Expected key=value, got '{}'", assignment))?;
+        self.values.push((key.trim().to_lowercase(), value.trim().to_string()));
+        Ok(())
+    }
+}
+
+/// Raw values per key from every layer, in the order they were applied.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLayers {
+    values: BTreeMap<String, Vec<(String, ValueSource)>>,
+}
+
+impl ConfigLayers {
+    /// Just the built-in defaults of `KEYS`.
+    pub fn defaults() -> Self {
+        let mut layers = Self::default();
+        for (key, default) in KEYS {
+            if let Some(default) = default {
+                layers.set(key, default.to_string(), ValueSource::Default);
+            }
+        }
+        layers
+    }
+
+    pub fn set(&mut self, key: &str, value: String, source: ValueSource) {
+        self.values.entry(key.to_string()).or_default().push((value, source));
+    }
+
+    /// The effective (highest precedence) value for a key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .get(key)
+            .and_then(|layers| layers.last())
+            .map(|(value, _)| value.as_str())
+    }
+
+    pub fn source(&self, key: &str) -> Option<ValueSource> {
+        self.values
+            .get(key)
+            .and_then(|layers| layers.last())
+            .map(|(_, source)| *source)
+    }
+
+    fn required(&self, key: &str) -> Result<String> {
+        self.get(key)
+            .map(|v| v.to_string())
+            .with_context(|| format!("{} not set", key.to_uppercase()))
+    }
+
+    fn parse<T>(&self, key: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let raw = self.required(key)?;
+        raw.parse::<T>().map_err(|e| self.invalid(key, &raw, e))
+    }
+
+    /// Comma-separated list; empty entries are dropped.
+    fn list(&self, key: &str) -> Result<Vec<String>> {
+        Ok(self
+            .required(key)?
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect())
+    }
+
+    fn flag(&self, key: &str) -> Result<bool> {
+        let raw = self.required(key)?;
+        match raw.to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(true),
+            "false" | "0" | "no" | "off" => Ok(false),
+            _ => Err(self.invalid(key, &raw, "expected true or false")),
+        }
+    }
+
+    fn usdc(&self, key: &str) -> Result<UsdcAmount> {
+        self.parse(key)
+    }
+
+    fn ratio(&self, key: &str) -> Result<Ratio> {
+        self.parse(key)
+    }
+
+    fn duration(&self, key: &str) -> Result<Duration> {
+        let raw = self.required(key)?;
+        parse_duration(&raw).map_err(|e: UnitError| self.invalid(key, &raw, e))
+    }
+
+    fn invalid(&self, key: &str, raw: &str, err: impl fmt::Display) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Invalid value for {} ('{}' from {}): {}",
+            key,
+            raw,
+            self.source(key).unwrap_or(ValueSource::Default),
+            err
+        )
+    }
+}
+
+/// One line of a provenance report.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProvenanceEntry {
+    pub key: String,
+    pub value: String,
+    pub source: ValueSource,
+    /// Lower-precedence layers that also set this key and were overridden.
+    pub overridden: Vec<ValueSource>,
+}
+
+/// A resolved config together with the layers it was built from.
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub file: Option<PathBuf>,
+    pub layers: ConfigLayers,
+}
+
+impl LoadedConfig {
+    pub fn provenance(&self) -> Vec<ProvenanceEntry> {
+        self.layers
+            .values
+            .iter()
+            .filter_map(|(key, layers)| {
+                let (value, source) = layers.last()?;
+                let value = shown_value(key, value);
+                let overridden = layers[..layers.len() - 1]
+                    .iter()
+                    .map(|(_, s)| *s)
+                    .filter(|s| s != source)
+                    .collect();
+                Some(ProvenanceEntry {
+                    key: key.clone(),
+                    value,
+                    source: *source,
+                    overridden,
+                })
+            })
+            .collect()
+    }
+
+    /// Human-readable table of every effective value and its origin.
+    pub fn provenance_report(&self) -> String {
+        let mut out = String::new();
+        match &self.file {
+            Some(path) => out.push_str(&format!("Config file: {}\n", path.display())),
+            None => out.push_str("Config file: (none)\n"),
+        }
+        for entry in self.provenance() {
+            out.push_str(&format!("{:<24} = {:<48} [{}", entry.key, entry.value, entry.source));
+            if !entry.overridden.is_empty() {
+                let shadowed: Vec<String> = entry.overridden.iter().map(|s| s.to_string()).collect();
+                out.push_str(&format!(", overrides {}", shadowed.join(", ")));
+            }
+            out.push_str("]\n");
+        }
+        out
+    }
+}
 
 pub fn load_config() -> Result<Config> {
+    Ok(load_layered(&CliOverrides::default())?.config)
+}
+
+/// Resolves the config from defaults, then the config file, then the
+/// environment, then command line overrides.
+pub fn load_layered(cli: &CliOverrides) -> Result<LoadedConfig> {
     dotenv::dotenv().ok();
-    
-    let wallets = env::var("WALLETS_TO_TRACK")
-        .context("WALLETS_TO_TRACK not set")?
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .collect();
-    
-    let sizing_mode = match env::var("SIZING_MODE")
-        .unwrap_or_else(|_| "fixed".to_string())
-        .to_lowercase()
-        .as_str()
-    {
+
+    let mut layers = ConfigLayers::defaults();
+
+    let file = resolve_config_file(cli)?;
+    if let Some(path) = &file {
+        if let Some(report) = config_migration::migrate_config_file(path, MigrationMode::from_env()?)? {
+            tracing::warn!(
+                "Migrated {} from config schema v{} to v{} (backup at {})",
+                path.display(),
+                report.from_version,
+                report.to_version,
+                report.backup.display()
+            );
+            for change in &report.changes {
+                tracing::warn!("   {}", change);
+            }
+        }
+        for (key, value, source) in read_config_file(path)? {
+            layers.set(&key, value, source);
+        }
+    }
+
+    for rename in config_migration::RENAMED_KEYS {
+        let legacy = env::var(rename.old.to_uppercase())
+            .ok()
+            .and_then(|value| upgrade_legacy_key(rename.old, &value, "env"));
+        if let Some((key, value)) = legacy {
+            layers.set(key, value, ValueSource::Env);
+        }
+    }
+
+    for (key, _) in KEYS {
+        if let Ok(value) = env::var(key.to_uppercase()) {
+            layers.set(key, value, ValueSource::Env);
+        }
+    }
+
+    for (key, value) in &cli.values {
+        if let Some((key, value)) = upgrade_legacy_key(key, value, "--set") {
+            layers.set(key, value, ValueSource::Cli);
+            continue;
+        }
+        if !is_known_key(key) {
+            anyhow::bail!("Unknown config key '{}'", key);
+        }
+        layers.set(key, value.clone(), ValueSource::Cli);
+    }
+
+    let config = build_config(&layers)?;
+
+    Ok(LoadedConfig { config, file, layers })
+}
+
+/// The config from the built-in defaults alone, with the required keys
+/// (wallets and credentials) left empty for the caller to fill in.
+pub fn defaults() -> Config {
+    let mut layers = ConfigLayers::defaults();
+    for (key, _) in KEYS.iter().filter(|(_, default)| default.is_none()) {
+        layers.set(key, String::new(), ValueSource::Default);
+    }
+    build_config(&layers).expect("the built-in config defaults parse")
+}
+
+/// Translates a key renamed in a later schema version, warning about it.
+fn upgrade_legacy_key(key: &str, value: &str, origin: &str) -> Option<(&'static str, String)> {
+    let (new_key, new_value) = config_migration::translate_legacy_key(key, value)?;
+    tracing::warn!(
+        "{} uses deprecated key '{}'; use '{} = {}' instead",
+        origin,
+        key,
+        new_key,
+        new_value
+    );
+    Some((new_key, new_value))
+}
+
+fn is_known_key(key: &str) -> bool {
+    KEYS.iter().any(|(k, _)| *k == key)
+}
+
+fn resolve_config_file(cli: &CliOverrides) -> Result<Option<PathBuf>> {
+    if let Some(path) = &cli.config_file {
+        if !path.exists() {
+            anyhow::bail!("Config file {} does not exist", path.display());
+        }
+        return Ok(Some(path.clone()));
+    }
+
+    if let Ok(path) = env::var("CONFIG_FILE") {
+        let path = PathBuf::from(path);
+        if !path.exists() {
+            anyhow::bail!("CONFIG_FILE {} does not exist", path.display());
+        }
+        return Ok(Some(path));
+    }
+
+    let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+    Ok(default.exists().then_some(default))
+}
+
+/// Reads a flat TOML config file into raw `key -> value` strings. Entries of
+/// the `[sealed]` table are decrypted and their keys merged in.
+fn read_config_file(path: &Path) -> Result<Vec<(String, String, ValueSource)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let mut table: toml::Table = contents
+        .parse()
+        .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+    let sealed = table.remove("sealed");
+    table.remove("config_version");
+
+    let mut values = Vec::new();
+    flatten_table(table, ValueSource::File, &mut values)?;
+
+    if let Some(sealed) = sealed {
+        let sections = match sealed {
+            toml::Value::Table(sections) => sections,
+            _ => anyhow::bail!("[sealed] in {} must be a table", path.display()),
+        };
+        let secret = sealed::load_secret()?.with_context(|| {
+            format!(
+                "{} has sealed sections but neither CONFIG_PASSPHRASE nor CONFIG_KEYFILE is set",
+                path.display()
+            )
+        })?;
+
+        for (name, blob) in sections {
+            let blob = blob
+                .as_str()
+                .with_context(|| format!("Sealed section '{}' must be a string", name))?;
+            let fragment: toml::Table = sealed::unseal(blob, &secret)
+                .with_context(|| format!("Failed to unseal section '{}'", name))?
+                .parse()
+                .with_context(|| format!("Sealed section '{}' is not valid TOML", name))?;
+            flatten_table(fragment, ValueSource::Sealed, &mut values)?;
+            tracing::info!("Unsealed config section '{}'", name);
+        }
+    }
+
+    Ok(values)
+}
+
+fn flatten_table(
+    table: toml::Table,
+    source: ValueSource,
+    out: &mut Vec<(String, String, ValueSource)>,
+) -> Result<()> {
+    for (key, value) in table {
+        let key = key.to_lowercase();
+        let raw = toml_value_to_string(&value)?;
+        if let Some((new_key, new_value)) = upgrade_legacy_key(&key, &raw, "config file") {
+            out.push((new_key.to_string(), new_value, source));
+            continue;
+        }
+        if !is_known_key(&key) {
+            tracing::warn!("Ignoring unknown config key '{}'", key);
+            continue;
+        }
+        out.push((key, raw, source));
+    }
+    Ok(())
+}
+
+fn toml_value_to_string(value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) => f.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(toml_value_to_string)
+            .collect::<Result<Vec<_>>>()?
+            .join(","),
+        other => anyhow::bail!("Unsupported config value: {}", other),
+    })
+}
+
+fn build_config(layers: &ConfigLayers) -> Result<Config> {
+    let wallets = layers.list("wallets_to_track")?;
+
+    let sizing_mode = match layers.required("sizing_mode")?.to_lowercase().as_str() {
         "proportional" => SizingMode::Proportional,
         "tier" | "tierbased" => SizingMode::TierBased,
         _ => SizingMode::Fixed,
     };
-    
+
+    let cost_basis = match layers.required("cost_basis")?.to_lowercase().as_str() {
+        "fifo" => CostBasis::Fifo,
+        "average" | "avg" => CostBasis::Average,
+        other => anyhow::bail!("COST_BASIS must be fifo or average, got '{}'", other),
+    };
+
+    let shutdown_orders = match layers.required("shutdown_orders")?.to_lowercase().as_str() {
+        "keep" => ShutdownOrders::Keep,
+        "cancel" => ShutdownOrders::Cancel,
+        other => anyhow::bail!("SHUTDOWN_ORDERS must be keep or cancel, got '{}'", other),
+    };
+
+    let min_severity = layers.required("notify_min_severity")?;
+    let notify_min_severity = Severity::parse(&min_severity).with_context(|| {
+        format!(
+            "NOTIFY_MIN_SEVERITY must be debug, info, warn or critical, got '{}'",
+            min_severity
+        )
+    })?;
+
     Ok(Config {
         wallets_to_track: wallets,
-        your_wallet: env::var("YOUR_WALLET")
-            .context("YOUR_WALLET not set")?,
-        private_key: env::var("PRIVATE_KEY")
-            .context("PRIVATE_KEY not set")?,
-        polymarket_api: env::var("POLYMARKET_API")
-            .unwrap_or_else(|_| "https://api.polymarket.com".to_string()),
-        ws_url: env::var("WS_URL")
-            .unwrap_or_else(|_| "wss://ws-subscriptions-clob.polymarket.com/ws".to_string()),
-        rpc_url: env::var("RPC_URL")
+        leader_labels: layers.list("leader_labels")?,
+        your_wallet: layers.required("your_wallet")?,
+        private_key: layers.required("private_key")?,
+        polymarket_api: layers.required("polymarket_api")?,
+        exchange: layers
+            .required("exchange")?
+            .parse()
+            .context("EXCHANGE must be polymarket or kalshi")?,
+        kalshi_api: layers.required("kalshi_api")?,
+        kalshi_key_id: layers.required("kalshi_key_id")?,
+        kalshi_private_key: layers.required("kalshi_private_key")?,
+        gamma_api: layers.required("gamma_api")?,
+        data_api: layers.required("data_api")?,
+        data_api_rate: layers.parse("data_api_rate")?,
+        ws_url: layers.required("ws_url")?,
+        trade_source: layers
+            .required("trade_source")?
+            .parse()
+            .context("TRADE_SOURCE must be websocket or subgraph")?,
+        subgraph_url: layers.required("subgraph_url")?,
+        subgraph_poll_interval: layers.duration("subgraph_poll_interval")?,
+        book_ws_url: layers.required("book_ws_url")?,
+        book_tokens: layers.list("book_tokens")?,
+        book_held_markets: layers.flag("book_held_markets")?,
+        book_tokens_per_connection: layers.parse("book_tokens_per_connection")?,
+        decision_book_levels: layers.parse("decision_book_levels")?,
+        price_alerts: layers.list("price_alerts")?,
+        imbalance_ticks: layers.parse("imbalance_ticks")?,
+        max_chase_imbalance: layers.ratio("max_chase_imbalance")?.as_f64(),
+        stream_held_markets: layers.flag("stream_held_markets")?,
+        rpc_url: layers
+            .required("rpc_url")
             .context("RPC_URL not set (use Alchemy/Infura)")?,
-        
+
         sizing_mode,
-        fixed_stake: env::var("FIXED_STAKE")
-            .unwrap_or_else(|_| "25.0".to_string())
-            .parse()?,
-        proportional_ratio: env::var("PROPORTIONAL_RATIO")
-            .unwrap_or_else(|_| "0.02".to_string())
-            .parse()?,
-        min_stake: env::var("MIN_STAKE")
-            .unwrap_or_else(|_| "5.0".to_string())
-            .parse()?,
-        max_stake: env::var("MAX_STAKE")
-            .unwrap_or_else(|_| "100.0".to_string())
-            .parse()?,
-        
-        max_exposure_per_event: env::var("MAX_EXPOSURE_PER_EVENT")
-            .unwrap_or_else(|_| "500.0".to_string())
-            .parse()?,
-        max_daily_volume: env::var("MAX_DAILY_VOLUME")
-            .unwrap_or_else(|_| "2000.0".to_string())
-            .parse()?,
-        min_liquidity: env::var("MIN_LIQUIDITY")
-            .unwrap_or_else(|_| "1000.0".to_string())
-            .parse()?,
-        cb_consecutive_trigger: env::var("CB_CONSECUTIVE_TRIGGER")
-            .unwrap_or_else(|_| "3".to_string())
-            .parse()?,
-        cb_min_depth_usd: env::var("CB_MIN_DEPTH_USD")
-            .unwrap_or_else(|_| "100.0".to_string())
-            .parse()?,
-        
-        retry_attempts: env::var("RETRY_ATTEMPTS")
-            .unwrap_or_else(|_| "4".to_string())
-            .parse()?,
-        retry_delay_ms: env::var("RETRY_DELAY_MS")
-            .unwrap_or_else(|_| "500".to_string())
-            .parse()?,
+        fixed_stake: layers.usdc("fixed_stake")?,
+        proportional_ratio: layers.ratio("proportional_ratio")?,
+        min_stake: layers.usdc("min_stake")?,
+        max_stake: layers.usdc("max_stake")?,
+
+        max_exposure_per_event: layers.usdc("max_exposure_per_event")?,
+        max_daily_volume: layers.usdc("max_daily_volume")?,
+        min_liquidity: layers.usdc("min_liquidity")?.as_f64(),
+        liquidity_window: layers.duration("liquidity_window")?,
+        max_spread: layers.usdc("max_spread")?.as_f64(),
+        max_neg_risk_deviation: layers.usdc("max_neg_risk_deviation")?.as_f64(),
+        spot_ws_url: layers.required("spot_ws_url")?,
+        spot_assets: layers.list("spot_assets")?,
+        spot_volatility: layers.ratio("spot_volatility")?.as_f64(),
+        spot_min_probability: layers.ratio("spot_min_probability")?.as_f64(),
+        arb_pairs: layers.list("arb_pairs")?,
+        arb_auto_pair: layers.flag("arb_auto_pair")?,
+        arb_interval: layers.duration("arb_interval")?,
+        arb_min_edge: layers.ratio("arb_min_edge")?.as_f64(),
+        arb_polymarket_fee: layers.ratio("arb_polymarket_fee")?.as_f64(),
+        arb_kalshi_fee: layers.ratio("arb_kalshi_fee")?.as_f64(),
+        arb_max_stake: layers.usdc("arb_max_stake")?.as_f64(),
+        arb_auto_execute: layers.flag("arb_auto_execute")?,
+        max_book_share: layers.ratio("max_book_share")?.as_f64(),
+        min_trades_per_hour: layers.parse("min_trades_per_hour")?,
+        cb_consecutive_trigger: layers.parse("cb_consecutive_trigger")?,
+        cb_min_depth_usd: layers.usdc("cb_min_depth_usd")?.as_f64(),
+
+        retry_attempts: layers.parse("retry_attempts")?,
+        retry_delay_ms: layers.duration("retry_delay")?.as_millis() as u64,
+        max_slippage: layers.ratio("max_slippage")?,
+        max_daily_loss: layers.usdc("max_daily_loss")?,
+        stop_loss: layers.ratio("stop_loss")?.as_f64(),
+        take_profit: layers.ratio("take_profit")?.as_f64(),
+        paper_trading: layers.flag("paper_trading")?,
+        fill_model: layers
+            .required("fill_model")?
+            .parse()
+            .context("FILL_MODEL must be immediate, book or latency")?,
+        fill_latency: layers.duration("fill_latency")?,
+        paper_balance: layers.usdc("paper_balance")?.as_f64(),
+        paper_settle_interval: layers.duration("paper_settle_interval")?,
+        shadow: shadow_config(layers)?,
+
+        storage_url: layers.required("storage_url")?,
+        cost_basis,
+        pnl_interval: layers.duration("pnl_interval")?,
+        equity_interval: layers.duration("equity_interval")?,
+        cash_flow_interval: layers.duration("cash_flow_interval")?,
+        gas_token_usd: layers.usdc("gas_token_usd")?.as_f64(),
+        reconcile_interval: layers.duration("reconcile_interval")?,
+        stale_position_after: layers.duration("stale_position_after")?,
+        reconcile_auto_correct: layers.flag("reconcile_auto_correct")?,
+        resolution_interval: layers.duration("resolution_interval")?,
+        dispute_interval: layers.duration("dispute_interval")?,
+        auto_redeem: layers.flag("auto_redeem")?,
+        instance_lease_ttl: layers.duration("instance_lease_ttl")?,
+        force_instance_lease: layers.flag("force_instance_lease")?,
+        event_log: layers.required("event_log")?,
+        capture_ws_frames: layers.flag("capture_ws_frames")?,
+        ws_frame_retention: layers.duration("ws_frame_retention")?,
+        journal_retention: layers.duration("journal_retention")?,
+        archive_dir: layers.required("archive_dir")?,
+        compaction_interval: layers.duration("compaction_interval")?,
+        latency_budget: layers.duration("latency_budget")?,
+        min_price: layers.parse("min_price")?,
+        max_price: layers.parse("max_price")?,
+        dedup_window: layers.duration("dedup_window")?,
+        market_cache_ttl: layers.duration("market_cache_ttl")?,
+        market_max_stale: layers.duration("market_max_stale")?,
+        market_blacklist: layers.list("market_blacklist")?,
+        price_sample_interval: layers.duration("price_sample_interval")?,
+        price_watch_window: layers.duration("price_watch_window")?,
+        price_retention: layers.duration("price_retention")?,
+        mark_max_age: layers.duration("mark_max_age")?,
+
+        trading_timezone: layers.required("trading_timezone")?,
+        trading_windows: layers.list("trading_windows")?,
+        blackout_dates: layers.list("blackout_dates")?,
+        telegram_bot_token: layers.required("telegram_bot_token")?,
+        telegram_chat_id: layers.required("telegram_chat_id")?,
+        discord_webhook: layers.required("discord_webhook")?,
+        discord_trades_webhook: layers.required("discord_trades_webhook")?,
+        discord_errors_webhook: layers.required("discord_errors_webhook")?,
+        discord_bot_token: layers.required("discord_bot_token")?,
+        discord_command_channel: layers.required("discord_command_channel")?,
+        webhook_urls: layers.list("webhook_urls")?,
+        webhook_secret: layers.required("webhook_secret")?,
+        webhook_retries: layers.parse("webhook_retries")?,
+        pagerduty_routing_key: layers.required("pagerduty_routing_key")?,
+        smtp_url: layers.required("smtp_url")?,
+        email_from: layers.required("email_from")?,
+        email_to: layers.list("email_to")?,
+        email_max_per_hour: layers.parse("email_max_per_hour")?,
+        feed_down_alert: layers.duration("feed_down_alert")?,
+        approval_threshold: layers.usdc("approval_threshold")?.as_f64(),
+        approval_timeout: layers.duration("approval_timeout")?,
+        health_addr: layers.required("health_addr")?,
+        status_api: layers.flag("status_api")?,
+        admin_token: layers.required("admin_token")?,
+        pid_file: layers.required("pid_file")?,
+        shutdown_orders,
+        otel_exporter_otlp_endpoint: layers.required("otel_exporter_otlp_endpoint")?,
+        otel_service_name: layers.required("otel_service_name")?,
+        latency_summary_interval: layers.duration("latency_summary_interval")?,
+        heartbeat_url: layers.required("heartbeat_url")?,
+        heartbeat_interval: layers.duration("heartbeat_interval")?,
+        opsgenie_api_key: layers.required("opsgenie_api_key")?,
+        opsgenie_url: layers.required("opsgenie_url")?,
+        incident_stall_after: layers.duration("incident_stall_after")?,
+        incident_error_rate: layers.ratio("incident_error_rate")?.as_f64(),
+        incident_error_window: layers.duration("incident_error_window")?,
+        incident_min_orders: layers.parse("incident_min_orders")?,
+        anomaly_window: layers.duration("anomaly_window")?,
+        anomaly_min_events: layers.parse("anomaly_min_events")?,
+        spike_markets: layers.parse("spike_markets")?,
+        spike_interval: layers.duration("spike_interval")?,
+        spike_window: layers.duration("spike_window")?,
+        spike_factor: layers.parse("spike_factor")?,
+        spike_holder_change: layers.ratio("spike_holder_change")?.as_f64(),
+        notify_routes: layers.list("notify_routes")?,
+        notify_min_severity,
+        notify_leaders: layers.list("notify_leaders")?,
+        notify_digest_interval: layers.duration("notify_digest_interval")?,
+        notify_dedup_window: layers.duration("notify_dedup_window")?,
+        notify_recovery_delay: layers.duration("notify_recovery_delay")?,
     })
 }
 
+/// The shadow strategy: every setting as resolved, then the `shadow` list's
+/// `key=value` overrides, paper trading. Overrides can't hold commas, so
+/// list settings keep their values.
+fn shadow_config(layers: &ConfigLayers) -> Result<Option<Box<Config>>> {
+    let overrides = layers.list("shadow")?;
+    if overrides.is_empty() {
+        return Ok(None);
+    }
+    let source = layers.source("shadow").unwrap_or(ValueSource::Default);
+    let mut shadow = layers.clone();
+    shadow.set("shadow", String::new(), source);
+    for entry in &overrides {
+        let (key, value) = entry
+            .split_once('=')
+            .with_context(|| format!("SHADOW entries are key=value, got '{}'", entry))?;
+        let key = key.trim().to_lowercase();
+        if !is_known_key(&key) || key == "shadow" {
+            anyhow::bail!("Unknown config key '{}' in SHADOW", key);
+        }
+        shadow.set(&key, value.trim().to_string(), source);
+    }
+    let mut config = build_config(&shadow).context("Invalid SHADOW overrides")?;
+    config.paper_trading = true;
+    Ok(Some(Box::new(config)))
+}
+
 pub fn validate_config(config: &Config) -> Result<()> {
     if config.wallets_to_track.is_empty() {
         anyhow::bail!("No wallets to track configured");
     }
-    
+
     if config.your_wallet.is_empty() {
         anyhow::bail!("YOUR_WALLET not configured");
     }
-    
+
     if config.private_key.is_empty() || config.private_key.len() < 64 {
         anyhow::bail!("Invalid PRIVATE_KEY");
     }
-    
+
     if config.fixed_stake < config.min_stake {
         anyhow::bail!("FIXED_STAKE must be >= MIN_STAKE");
     }
-    
+
     if config.max_stake < config.min_stake {
         anyhow::bail!("MAX_STAKE must be >= MIN_STAKE");
     }
+
+    if !(0.0..=1.0).contains(&config.min_price) || !(config.min_price..=1.0).contains(&config.max_price) {
+        anyhow::bail!("MIN_PRICE and MAX_PRICE must satisfy 0 <= MIN_PRICE <= MAX_PRICE <= 1");
+    }
+
+    // Leader trades are Polymarket tokens, and nothing maps them to Kalshi
+    // markets yet; the KALSHI_* keys are still used by arbitrage
+    if config.exchange == Venue::Kalshi {
+        anyhow::bail!("EXCHANGE=kalshi is not supported yet: copies of Polymarket trades have no Kalshi market to go to");
+    }
+
+    if config.trade_source == TradeFeed::Subgraph && config.subgraph_poll_interval.is_zero() {
+        anyhow::bail!("SUBGRAPH_POLL_INTERVAL must be above zero with TRADE_SOURCE=subgraph");
+    }
     
+    TradingSchedule::from_config(config)?;
+    leaders::parse_labels(&config.leader_labels)?;
+
     tracing::info!("Config validation passed");
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_later_layers_take_precedence() {
+        let mut layers = ConfigLayers::default();
+        layers.set("fixed_stake", "25.0".to_string(), ValueSource::Default);
+        layers.set("fixed_stake", "40.0".to_string(), ValueSource::File);
+        layers.set("fixed_stake", "30.0".to_string(), ValueSource::Env);
+
+        assert_eq!(layers.get("fixed_stake"), Some("30.0"));
+        assert_eq!(layers.source("fixed_stake"), Some(ValueSource::Env));
+
+        let loaded = LoadedConfig {
+            config: Config::default(),
+            file: None,
+            layers,
+        };
+        let entry = &loaded.provenance()[0];
+        assert_eq!(entry.source, ValueSource::Env);
+        assert_eq!(entry.overridden, vec![ValueSource::Default, ValueSource::File]);
+    }
+
+    #[test]
+    fn test_secrets_are_redacted() {
+        let mut layers = ConfigLayers::default();
+        layers.set("private_key", "deadbeef".to_string(), ValueSource::Env);
+        let loaded = LoadedConfig {
+            config: Config::default(),
+            file: None,
+            layers,
+        };
+        assert!(!loaded.provenance_report().contains("deadbeef"));
+    }
+
+    #[test]
+    fn test_rpc_and_webhook_urls_are_redacted() {
+        let mut layers = ConfigLayers::default();
+        layers.set("rpc_url", "https://polygon-mainnet.g.alchemy.com/v2/alchemykey".to_string(), ValueSource::Env);
+        layers.set("webhook_urls", "https://hooks.example.com/T0/hooksecret".to_string(), ValueSource::File);
+        let loaded = LoadedConfig {
+            config: Config::default(),
+            file: None,
+            layers,
+        };
+        let report = loaded.provenance_report();
+        assert!(report.contains("polygon-mainnet.g.alchemy.com"));
+        assert!(!report.contains("alchemykey"));
+        assert!(!report.contains("hooksecret"));
+    }
+
+    #[test]
+    fn test_storage_url_passwords_are_redacted() {
+        let mut layers = ConfigLayers::defaults();
+        layers.set("storage_url", "postgres://bot:hunter2@db:5432/bot?sslmode=require".to_string(), ValueSource::Env);
+        let loaded = LoadedConfig {
+            config: Config::default(),
+            file: None,
+            layers,
+        };
+        let storage = loaded.provenance().into_iter().find(|e| e.key == "storage_url").unwrap();
+        assert_eq!(storage.value, "postgres://bot:<redacted>@db:5432/bot?sslmode=require");
+        assert!(!loaded.provenance_report().contains("hunter2"));
+
+        assert_eq!(without_password("postgres://db/bot?user=bot&password=hunter2"), "postgres://db/bot?<redacted>");
+        assert_eq!(without_password("sqlite://bot.db"), "sqlite://bot.db");
+    }
+
+    #[test]
+    fn test_unit_errors_name_key_and_source() {
+        let mut layers = ConfigLayers::default();
+        layers.set("max_stake", "250 ETH".to_string(), ValueSource::File);
+        let err = layers.usdc("max_stake").unwrap_err().to_string();
+        assert!(err.contains("max_stake"));
+        assert!(err.contains("from file"));
+
+        layers.set("latency_budget", "750ms".to_string(), ValueSource::Cli);
+        assert_eq!(layers.duration("latency_budget").unwrap(), Duration::from_millis(750));
+    }
+
+    #[test]
+    fn test_shadow_overrides_build_a_paper_config() {
+        let mut layers = ConfigLayers::default();
+        for (key, default) in KEYS {
+            layers.set(key, default.unwrap_or("0xabc").to_string(), ValueSource::Default);
+        }
+        layers.set("fixed_stake", "40".to_string(), ValueSource::File);
+        layers.set("shadow", "FIXED_STAKE=60, max_slippage=2%".to_string(), ValueSource::Env);
+        let config = build_config(&layers).unwrap();
+        let shadow = config.shadow.unwrap();
+        assert_eq!((config.fixed_stake, config.paper_trading), (UsdcAmount(40.0), false));
+        assert_eq!((shadow.fixed_stake, shadow.max_slippage), (UsdcAmount(60.0), Ratio(0.02)));
+        assert!(shadow.paper_trading && shadow.shadow.is_none());
+
+        layers.set("shadow", "fixed_stake".to_string(), ValueSource::Env);
+        assert!(build_config(&layers).is_err());
+        layers.set("shadow", "no_such_key=1".to_string(), ValueSource::Env);
+        assert!(build_config(&layers).is_err());
+    }
+
+    #[test]
+    fn test_cli_assignment_parsing() {
+        let mut cli = CliOverrides::default();
+        cli.push_assignment("FIXED_STAKE = 12.5").unwrap();
+        assert_eq!(cli.values, vec![("fixed_stake".to_string(), "12.5".to_string())]);
+        assert!(cli.push_assignment("no-equals-sign").is_err());
+    }
+
+    #[test]
+    fn test_defaults_come_from_the_key_table() {
+        let config = defaults();
+        assert_eq!(Some(config.polymarket_api.as_str()), default_value("polymarket_api"));
+        assert_eq!(Some(config.book_ws_url.as_str()), default_value("book_ws_url"));
+        assert_eq!(config.fixed_stake, default_value("fixed_stake").unwrap().parse().unwrap());
+        assert!(config.wallets_to_track.is_empty() && config.private_key.is_empty());
+    }
+
+    #[test]
+    fn test_copies_onto_kalshi_are_rejected() {
+        let mut config = Config {
+            wallets_to_track: vec!["0xleader".to_string()],
+            your_wallet: "0xme".to_string(),
+            private_key: "a".repeat(64),
+            ..Default::default()
+        };
+        assert!(validate_config(&config).is_ok());
+        config.exchange = Venue::Kalshi;
+        assert!(validate_config(&config).unwrap_err().to_string().contains("EXCHANGE=kalshi"));
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, DocumentMut};
+
+/// Schema version written to `config_version` in the config file. Files
+/// without the key are treated as version 1.
+pub const CURRENT_CONFIG_VERSION: i64 = 2;
+
+struct Migration {
+    from: i64,
+    apply: fn(&mut DocumentMut) -> Vec<String>,
+}
+
+/// Ordered upgrade steps; each one moves a document from `from` to `from + 1`.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    apply: v1_to_v2,
+}];
+
+/// A key that was renamed between schema versions. Used to translate values
+/// that can't be rewritten on disk: env vars, CLI overrides, sealed sections.
+pub struct RenamedKey {
+    pub old: &'static str,
+    pub new: &'static str,
+    convert: fn(&str) -> String,
+}
+
+pub const RENAMED_KEYS: &[RenamedKey] = &[RenamedKey {
+    old: "retry_delay_ms",
+    new: "retry_delay",
+    convert: millis_to_duration,
+}];
+
+/// Whether outdated config files are rewritten in place or rejected with
+/// instructions. Controlled by `CONFIG_MIGRATE=auto|manual`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationMode {
+    Auto,
+    Manual,
+}
+
+impl MigrationMode {
+    pub fn from_env() -> Result<Self> {
+        match env::var("CONFIG_MIGRATE").as_deref() {
+            Err(_) | Ok("auto") => Ok(MigrationMode::Auto),
+            Ok("manual") => Ok(MigrationMode::Manual),
+            Ok(other) => anyhow::bail!("CONFIG_MIGRATE must be 'auto' or 'manual', got '{}'", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+    pub from_version: i64,
+    pub to_version: i64,
+    pub changes: Vec<String>,
+    pub backup: PathBuf,
+}
+
+/// Maps a legacy key to its current name and value format.
+pub fn translate_legacy_key(key: &str, raw: &str) -> Option<(&'static str, String)> {
+    RENAMED_KEYS
+        .iter()
+        .find(|r| r.old == key)
+        .map(|r| (r.new, (r.convert)(raw)))
+}
+
+/// Brings the config file at `path` up to [`CURRENT_CONFIG_VERSION`].
+/// Returns `None` if it was already current.
+pub fn migrate_config_file(path: &Path, mode: MigrationMode) -> Result<Option<MigrationReport>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let mut doc: DocumentMut = contents
+        .parse()
+        .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+    let from_version = config_version(&doc)?;
+    if from_version == CURRENT_CONFIG_VERSION {
+        return Ok(None);
+    }
+    if from_version > CURRENT_CONFIG_VERSION {
+        anyhow::bail!(
+            "{} uses config schema v{}, but this build only understands up to v{}; upgrade the bot",
+            path.display(),
+            from_version,
+            CURRENT_CONFIG_VERSION
+        );
+    }
+
+    let mut changes = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from_version) {
+        changes.extend((migration.apply)(&mut doc));
+    }
+    doc["config_version"] = value(CURRENT_CONFIG_VERSION);
+    changes.push(format!("set `config_version = {}`", CURRENT_CONFIG_VERSION));
+
+    if mode == MigrationMode::Manual {
+        let steps: Vec<String> = changes.iter().map(|c| format!("  - {}", c)).collect();
+        anyhow::bail!(
+            "{} uses config schema v{} (current is v{}). Apply these changes, or unset CONFIG_MIGRATE=manual to migrate automatically:\n{}",
+            path.display(),
+            from_version,
+            CURRENT_CONFIG_VERSION,
+            steps.join("\n")
+        );
+    }
+
+    let backup = backup_path(path, from_version);
+    std::fs::copy(path, &backup)
+        .with_context(|| format!("Failed to write config backup {}", backup.display()))?;
+    std::fs::write(path, doc.to_string())
+        .with_context(|| format!("Failed to write migrated config {}", path.display()))?;
+
+    Ok(Some(MigrationReport {
+        from_version,
+        to_version: CURRENT_CONFIG_VERSION,
+        changes,
+        backup,
+    }))
+}
+
+fn config_version(doc: &DocumentMut) -> Result<i64> {
+    match doc.get("config_version") {
+        None => Ok(1),
+        Some(item) => item
+            .as_integer()
+            .context("config_version must be an integer"),
+    }
+}
+
+fn backup_path(path: &Path, version: i64) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".v{}.bak", version));
+    PathBuf::from(name)
+}
+
+/// v2 replaced `retry_delay_ms = 500` with a unit-bearing `retry_delay = "500ms"`.
+fn v1_to_v2(doc: &mut DocumentMut) -> Vec<String> {
+    let mut changes = Vec::new();
+    if let Some(old) = doc.remove("retry_delay_ms") {
+        let raw = old
+            .as_integer()
+            .map(|i| i.to_string())
+            .or_else(|| old.as_str().map(|s| s.to_string()))
+            .unwrap_or_default();
+        let converted = millis_to_duration(&raw);
+        changes.push(format!(
+            "replace `retry_delay_ms = {}` with `retry_delay = \"{}\"`",
+            raw, converted
+        ));
+        doc["retry_delay"] = value(converted);
+    }
+    changes
+}
+
+fn millis_to_duration(raw: &str) -> String {
+    format!("{}ms", raw.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_file_is_migrated_with_backup() {
+        let dir = std::env::temp_dir().join(format!("cfg-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bot.toml");
+        std::fs::write(&path, "# my settings\nfixed_stake = 10\nretry_delay_ms = 750\n").unwrap();
+
+        let err = migrate_config_file(&path, MigrationMode::Manual).unwrap_err();
+        assert!(err.to_string().contains("retry_delay = \"750ms\""));
+
+        let report = migrate_config_file(&path, MigrationMode::Auto).unwrap().unwrap();
+        assert_eq!(report.from_version, 1);
+        assert!(report.backup.exists());
+
+        let migrated = std::fs::read_to_string(&path).unwrap();
+        assert!(migrated.contains("# my settings"));
+        assert!(migrated.contains("retry_delay = \"750ms\""));
+        assert!(!migrated.contains("retry_delay_ms"));
+        assert!(migrate_config_file(&path, MigrationMode::Auto).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_legacy_keys_translate() {
+        assert_eq!(
+            translate_legacy_key("retry_delay_ms", "500"),
+            Some(("retry_delay", "500ms".to_string()))
+        );
+        assert_eq!(translate_legacy_key("fixed_stake", "5"), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! Whether a print in a neg-risk event agrees with the rest of the event.
+//!
+//! The outcomes of a neg-risk event (see [`crate::gamma::NegRiskGroup`])
+//! are mutually exclusive, so their yes prices should add up to about 1,
+//! and each leg's price is implied by the others: one less their sum.
+//! A leader trade whose price makes the group sum drift more than
+//! `max_neg_risk_deviation` from 1, while the catalog prices of the group
+//! agree, is a stale or pushed print in that one leg and isn't copied.
+//! When the catalog prices disagree too, the whole event is loosely priced
+//! and the trade is only flagged.
+
+use crate::gamma::NegRiskGroup;
+use crate::types::{Market, Trade};
+use serde::Serialize;
+
+/// How a trade's price compares to what the rest of its event implies.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LegCheck {
+    pub event_id: String,
+    /// The leg's market id in the group
+    pub market_id: String,
+    pub legs: usize,
+    /// Yes price of the leg as traded
+    pub price: f64,
+    /// One less the other legs' yes prices
+    pub implied: f64,
+    /// Sum of every leg's catalog yes price
+    pub catalog_sum: f64,
+}
+
+impl LegCheck {
+    /// Group sum with the traded price, less 1.
+    pub fn deviation(&self) -> f64 {
+        self.price - self.implied
+    }
+
+    pub fn catalog_deviation(&self) -> f64 {
+        self.catalog_sum - 1.0
+    }
+
+    /// Whether the trade is off by more than `tolerance` while the rest of
+    /// the event agrees with itself.
+    pub fn is_outlier(&self, tolerance: f64) -> bool {
+        self.deviation().abs() > tolerance && self.catalog_deviation().abs() <= tolerance
+    }
+}
+
+/// Whether the trade buys or sells the yes side of its market: trading
+/// the second outcome token is trading no.
+fn yes_price(trade: &Trade, leg: &Market) -> f64 {
+    match leg.token_ids.iter().position(|t| *t == trade.market_id) {
+        Some(1) => 1.0 - trade.price,
+        _ => trade.price,
+    }
+}
+
+/// Checks `trade` against the other legs of `group`; `None` when the trade
+/// isn't on one of the group's markets or there's no other leg.
+pub fn check(group: &NegRiskGroup, market: &Market, trade: &Trade) -> Option<LegCheck> {
+    let is_leg = |m: &Market| m.id == market.id || m.id == trade.market_id || m.token_ids.contains(&trade.market_id);
+    let leg = group.markets.iter().find(|m| is_leg(m))?;
+    if group.markets.len() < 2 {
+        return None;
+    }
+    let others: f64 = group
+        .markets
+        .iter()
+        .filter(|m| m.id != leg.id)
+        .map(|m| m.yes_price)
+        .sum();
+    Some(LegCheck {
+        event_id: group.event_id.clone(),
+        market_id: leg.id.clone(),
+        legs: group.markets.len(),
+        price: yes_price(trade, leg),
+        implied: 1.0 - others,
+        catalog_sum: others + leg.yes_price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TradeSide;
+
+    fn leg(id: &str, yes_price: f64) -> Market {
+        Market {
+            id: id.to_string(),
+            event_id: "e1".to_string(),
+            question: format!("Will {} win?", id),
+            yes_price,
+            no_price: 1.0 - yes_price,
+            liquidity: 10_000.0,
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            token_ids: vec![format!("{}-yes", id), format!("{}-no", id)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flags_prints_out_of_line_with_the_other_legs() {
+        let group = NegRiskGroup {
+            id: "0xnr".to_string(),
+            event_id: "e1".to_string(),
+            markets: vec![leg("a", 0.50), leg("b", 0.30), leg("c", 0.21)],
+        };
+        let trade = |token: &str, price: f64| Trade {
+            wallet: "0xwhale".to_string(),
+            event_id: "e1".to_string(),
+            market_id: token.to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price,
+            timestamp: 0,
+            tx_hash: None,
+        };
+
+        let fair = check(&group, &group.markets[0], &trade("a-yes", 0.50)).unwrap();
+        assert!((fair.implied - 0.49).abs() < 1e-9);
+        assert!(!fair.is_outlier(0.05));
+
+        let stale = check(&group, &group.markets[1], &trade("b-yes", 0.42)).unwrap();
+        assert!((stale.deviation() - 0.13).abs() < 1e-9);
+        assert!(stale.is_outlier(0.05));
+        // Buying no at 0.58 is the same print as yes at 0.42
+        let no = check(&group, &group.markets[1], &trade("b-no", 0.58)).unwrap();
+        assert!((no.price - 0.42).abs() < 1e-9);
+        assert!(no.is_outlier(0.05));
+
+        // A loosely priced event doesn't single out one leg
+        let mut loose = group.clone();
+        loose.markets[2].yes_price = 0.40;
+        assert!(!check(&loose, &loose.markets[1], &trade("b-yes", 0.42))
+            .unwrap()
+            .is_outlier(0.05));
+        assert!(check(&group, &leg("z", 0.5), &trade("z-yes", 0.5)).is_none());
+    }
+}
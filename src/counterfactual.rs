@@ -0,0 +1,316 @@
+//! How the copies did against perfect copies of the same leader trades.
+//!
+//! A perfect copy takes every leader trade the bot decided on instantly at
+//! the leader's price, without fees, sized at what the bot meant to stake
+//! (`fixed_stake` for trades it skipped before sizing them). Sells close
+//! the same stake's worth of shares, redemptions close every book in the
+//! market at the payout, and what's still open is marked like the real
+//! positions (see [`crate::pnl`]). Against the bot's actual PnL per
+//! leader this separates:
+//!
+//! - leader quality: the perfect copies' PnL, what following the leader
+//!   was worth at all
+//! - skips: the perfect PnL of the trades the bot didn't copy
+//! - execution: actual PnL less the perfect PnL of the trades it did copy;
+//!   slippage, latency, partial fills and fees
+//!
+//! The gap (actual less perfect) is the sum of the last two, give or take
+//! books where copied and skipped trades overlap.
+
+use crate::marks::{Mark, Marks};
+use crate::pnl::{self, MANUAL};
+use crate::portfolio::{self, Lot};
+use crate::storage::{now_ms, DecisionRecord, FillRecord, LeaderTradeRecord, OrderRecord, Storage, TimeRange};
+use crate::types::{CostBasis, TradeSide};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// One leader's copies against their perfect copies.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Benchmark {
+    pub name: String,
+    /// Leader trades decided on
+    pub trades: usize,
+    pub copied: usize,
+    /// Perfect copies of every trade
+    pub perfect_pnl: f64,
+    /// Perfect copies of the trades the bot copied
+    pub perfect_copied_pnl: f64,
+    pub actual_pnl: f64,
+}
+
+impl Benchmark {
+    /// Actual less perfect PnL.
+    pub fn gap(&self) -> f64 {
+        self.actual_pnl - self.perfect_pnl
+    }
+
+    /// What skipped trades would have made, as a (usually negative) gap.
+    pub fn skip_gap(&self) -> f64 {
+        self.perfect_copied_pnl - self.perfect_pnl
+    }
+
+    /// What filling the copied trades cost against perfect copies.
+    pub fn execution_gap(&self) -> f64 {
+        self.actual_pnl - self.perfect_copied_pnl
+    }
+
+    /// Actual PnL as a share of the perfect PnL; `None` unless the perfect
+    /// copies made money.
+    pub fn capture(&self) -> Option<f64> {
+        (self.perfect_pnl > 0.0).then(|| self.actual_pnl / self.perfect_pnl)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BenchmarkReport {
+    /// Unix ms
+    pub at: i64,
+    pub overall: Benchmark,
+    /// Largest perfect PnL first
+    pub leaders: Vec<Benchmark>,
+}
+
+/// Perfect-copy PnL per leader of the decisions `copied` picks out.
+fn perfect_pnl(
+    copies: &[(&DecisionRecord, &LeaderTradeRecord, f64)],
+    payouts: &HashMap<&str, f64>,
+    marks: &HashMap<String, Mark>,
+    copied: impl Fn(&DecisionRecord) -> bool,
+) -> BTreeMap<String, f64> {
+    let mut books: BTreeMap<(&str, &str), (Vec<Lot>, f64)> = BTreeMap::new();
+    for (decision, trade, stake) in copies.iter().filter(|(d, ..)| copied(d)) {
+        let price = trade.trade.price;
+        if price <= 0.0 {
+            continue;
+        }
+        let (lots, realized) = books
+            .entry((decision.wallet.as_str(), decision.market_id.as_str()))
+            .or_default();
+        match trade.trade.side {
+            TradeSide::BUY => portfolio::buy(lots, CostBasis::Fifo, stake / price, price, trade.trade.timestamp),
+            TradeSide::SELL => *realized += portfolio::sell(lots, stake / price, price),
+        }
+    }
+    let mut leaders: BTreeMap<String, f64> = BTreeMap::new();
+    for ((leader, market_id), (mut lots, mut realized)) in books {
+        let price = payouts
+            .get(market_id)
+            .copied()
+            .or_else(|| marks.get(market_id).map(|m| m.price));
+        if let Some(price) = price {
+            let shares: f64 = lots.iter().map(|l| l.shares).sum();
+            realized += portfolio::sell(&mut lots, shares, price);
+        }
+        *leaders.entry(leader.to_string()).or_default() += realized;
+    }
+    leaders
+}
+
+/// The bot's copies in the journal against perfect copies, marked at
+/// `marks`; trades skipped before sizing are staked at `default_stake`.
+pub fn compute(
+    trades: &[LeaderTradeRecord],
+    decisions: &[DecisionRecord],
+    orders: &[OrderRecord],
+    fills: &[FillRecord],
+    marks: &HashMap<String, Mark>,
+    default_stake: f64,
+    at: i64,
+) -> BenchmarkReport {
+    let trades: HashMap<i64, &LeaderTradeRecord> = trades.iter().map(|t| (t.id, t)).collect();
+    let copies: Vec<(&DecisionRecord, &LeaderTradeRecord, f64)> = decisions
+        .iter()
+        .filter_map(|d| {
+            let trade = trades.get(&d.leader_trade_id?)?;
+            Some((d, *trade, d.size_usd.filter(|s| *s > 0.0).unwrap_or(default_stake)))
+        })
+        .collect();
+    // Redemptions are journaled as sells without an order
+    let payouts: HashMap<&str, f64> = fills
+        .iter()
+        .filter(|f| f.order_id == 0 && f.side.eq_ignore_ascii_case("SELL"))
+        .map(|f| (f.market_id.as_str(), f.price))
+        .collect();
+    let perfect = perfect_pnl(&copies, &payouts, marks, |_| true);
+    let perfect_copied = perfect_pnl(&copies, &payouts, marks, |d| d.copied);
+    let actual = pnl::compute(decisions, orders, fills, CostBasis::Fifo, &HashMap::new(), marks, at);
+
+    let mut leaders: BTreeMap<&str, Benchmark> = BTreeMap::new();
+    for (decision, ..) in &copies {
+        let leader = leaders.entry(&decision.wallet).or_insert_with(|| Benchmark {
+            name: decision.wallet.clone(),
+            ..Default::default()
+        });
+        leader.trades += 1;
+        leader.copied += usize::from(decision.copied);
+    }
+    let mut report = BenchmarkReport {
+        at,
+        overall: Benchmark {
+            name: "all".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    for (name, mut leader) in leaders {
+        leader.perfect_pnl = perfect.get(name).copied().unwrap_or_default();
+        leader.perfect_copied_pnl = perfect_copied.get(name).copied().unwrap_or_default();
+        leader.actual_pnl = actual
+            .leaders
+            .iter()
+            .find(|t| t.name == name && t.name != MANUAL)
+            .map_or(0.0, |t| t.pnl());
+        report.overall.trades += leader.trades;
+        report.overall.copied += leader.copied;
+        report.overall.perfect_pnl += leader.perfect_pnl;
+        report.overall.perfect_copied_pnl += leader.perfect_copied_pnl;
+        report.overall.actual_pnl += leader.actual_pnl;
+        report.leaders.push(leader);
+    }
+    report.leaders.sort_by(|a, b| b.perfect_pnl.total_cmp(&a.perfect_pnl));
+    report
+}
+
+/// The whole journal against perfect copies, marked by `marks`.
+pub async fn from_journal(storage: &dyn Storage, marks: &Marks, default_stake: f64) -> Result<BenchmarkReport> {
+    let trades = storage.leader_trades(TimeRange::all()).await?;
+    let decisions = storage.decisions(TimeRange::all()).await?;
+    let orders = storage.orders(TimeRange::all()).await?;
+    let fills = storage.fills(TimeRange::all()).await?;
+    let market_ids: Vec<&str> = decisions.iter().map(|d| d.market_id.as_str()).collect();
+    let marked = marks.marks(&market_ids).await;
+    Ok(compute(
+        &trades,
+        &decisions,
+        &orders,
+        &fills,
+        &marked,
+        default_stake,
+        now_ms(),
+    ))
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<44} {:>7} {:>7} {:>12} {:>12} {:>12} {:>12} {:>8}",
+            "leader", "trades", "copied", "perfect", "actual", "skips", "execution", "capture"
+        )?;
+        for b in self.leaders.iter().chain([&self.overall]) {
+            writeln!(
+                f,
+                "{:<44} {:>7} {:>7} {:>+12.2} {:>+12.2} {:>+12.2} {:>+12.2} {:>8}",
+                b.name,
+                b.trades,
+                b.copied,
+                b.perfect_pnl,
+                b.actual_pnl,
+                b.skip_gap(),
+                b.execution_gap(),
+                b.capture().map_or("-".to_string(), |c| format!("{:.0}%", c * 100.0))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marks::MarkSource;
+    use crate::types::Trade;
+
+    #[test]
+    fn test_separates_skips_and_execution_from_leader_quality() {
+        let trade = |id, market_id: &str, side, price| LeaderTradeRecord {
+            id,
+            trade: Trade {
+                wallet: "0xa".to_string(),
+                event_id: String::new(),
+                market_id: market_id.to_string(),
+                side,
+                shares: 100.0,
+                price,
+                timestamp: 0,
+                tx_hash: None,
+            },
+            observed_at: 0,
+        };
+        let decision = |id, market_id: &str, copied, size_usd| DecisionRecord {
+            id,
+            leader_trade_id: Some(id),
+            wallet: "0xa".to_string(),
+            market_id: market_id.to_string(),
+            side: String::new(),
+            copied,
+            reason: None,
+            detail: None,
+            size_usd,
+            decided_at: 0,
+            arrival_mid: None,
+            arrival_book: None,
+        };
+        let fill = |order_id, market_id: &str, side: &str, shares, price, fee| FillRecord {
+            id: 0,
+            order_id,
+            market_id: market_id.to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee,
+            filled_at: 0,
+        };
+        // m1: bought at 0.40 for $40, filled at 0.50, resolved at 1;
+        // m2: skipped at 0.50, now marked at 0.60
+        let trades = [
+            trade(1, "m1", TradeSide::BUY, 0.40),
+            trade(2, "m2", TradeSide::BUY, 0.50),
+        ];
+        let decisions = [decision(1, "m1", true, Some(40.0)), decision(2, "m2", false, None)];
+        let orders = [OrderRecord {
+            id: 7,
+            decision_id: Some(1),
+            exchange_order_id: None,
+            market_id: "m1".to_string(),
+            side: "BUY".to_string(),
+            shares: 80.0,
+            limit_price: None,
+            order_type: "FOK".to_string(),
+            status: "filled".to_string(),
+            error: None,
+            submitted_at: 0,
+            client_order_id: None,
+        }];
+        let fills = [
+            fill(7, "m1", "BUY", 80.0, 0.50, 1.0),
+            fill(0, "m1", "SELL", 80.0, 1.0, 0.0),
+        ];
+        let marks = HashMap::from([(
+            "m2".to_string(),
+            Mark {
+                price: 0.60,
+                source: MarkSource::Mid,
+                at: 0,
+                stale: false,
+            },
+        )]);
+
+        let report = compute(&trades, &decisions, &orders, &fills, &marks, 25.0, 0);
+        let b = &report.overall;
+        assert_eq!((b.trades, b.copied), (2, 1));
+        // 100 shares at 0.40 paying 1, plus 50 at 0.50 marked at 0.60
+        assert!((b.perfect_pnl - 65.0).abs() < 1e-9);
+        assert!((b.perfect_copied_pnl - 60.0).abs() < 1e-9);
+        // 80 shares at 0.50 paying 1, less the fee
+        assert!((b.actual_pnl - 39.0).abs() < 1e-9);
+        assert!((b.skip_gap() + 5.0).abs() < 1e-9);
+        assert!((b.execution_gap() + 21.0).abs() < 1e-9);
+        assert!((b.gap() - b.skip_gap() - b.execution_gap()).abs() < 1e-9);
+        assert!((b.capture().unwrap() - 0.6).abs() < 1e-9);
+        assert_eq!(report.leaders.len(), 1);
+    }
+}
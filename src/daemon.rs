@@ -0,0 +1,179 @@
+//! Running as a service under systemd (`Type=notify`) or another supervisor.
+//!
+//! When systemd sets `NOTIFY_SOCKET` the bot reports `READY=1` once
+//! `/readyz` would pass (every feed connected, RPC, exchange credentials
+//! and storage answering), pings the watchdog while live if `WatchdogSec=`
+//! is set, and reports `STOPPING=1` on the way out. SIGTERM and Ctrl-C stop
+//! the trade loop cleanly; resting orders are then kept or cancelled as
+//! `shutdown_orders` says. With `pid_file` set the process id is written
+//! there while the bot runs. Supervisor notification and SIGTERM are Unix
+//! only; elsewhere the notifications are no-ops and only Ctrl-C stops the
+//! bot.
+//!
+//! ```ini
+//! [Service]
+//! Type=notify
+//! WatchdogSec=30
+//! ExecStart=/usr/local/bin/mybot --config /etc/mybot/bot.toml
+//! ```
+
+use anyhow::{Context, Result};
+#[cfg(unix)]
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The supervisor's notification socket, if there is one.
+#[cfg(unix)]
+fn notify_socket() -> Option<SocketAddr> {
+    let path = std::env::var("NOTIFY_SOCKET").ok().filter(|p| !p.is_empty())?;
+    let addr = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name.as_bytes())
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            tracing::warn!("Abstract NOTIFY_SOCKET {} is only supported on Linux", path);
+            return None;
+        }
+        None => SocketAddr::from_pathname(&path),
+    };
+    match addr {
+        Ok(addr) => Some(addr),
+        Err(e) => {
+            tracing::warn!("Ignoring NOTIFY_SOCKET {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Supervisor notification needs Unix datagram sockets.
+#[cfg(not(unix))]
+fn notify_socket() -> Option<()> {
+    None
+}
+
+/// Whether the bot was started with a notification socket.
+pub fn is_supervised() -> bool {
+    notify_socket().is_some()
+}
+
+/// Sends `state` (e.g. `READY=1`) to the supervisor. Does nothing without
+/// `NOTIFY_SOCKET`; failures are only warned about.
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    let Some(addr) = notify_socket() else { return };
+    let sent = UnixDatagram::unbound().and_then(|socket| socket.send_to_addr(state.as_bytes(), &addr));
+    if let Err(e) = sent {
+        tracing::warn!("Failed to notify systemd ({}): {}", state.lines().next().unwrap_or_default(), e);
+    }
+}
+
+/// Sends `state` to the supervisor; there is none off Unix.
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+/// The watchdog timeout systemd expects pings within, if it asked this
+/// process for them.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    notify_socket()?;
+    Some(Duration::from_micros(usec))
+}
+
+/// Resolves on SIGTERM or Ctrl-C.
+#[cfg(unix)]
+pub async fn shutdown_signal() {
+    let mut term = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+        Ok(term) => term,
+        Err(e) => {
+            tracing::warn!("Could not listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = term.recv() => tracing::info!("🛑 SIGTERM received, shutting down"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("🛑 Interrupted, shutting down"),
+    }
+}
+
+/// Resolves on Ctrl-C.
+#[cfg(not(unix))]
+pub async fn shutdown_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+    tracing::info!("🛑 Interrupted, shutting down");
+}
+
+/// The process id in a file, removed again when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Writes this process's id to `path`. Fails if the file names a
+    /// process that is still running; a stale one is replaced.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Ok(existing) = std::fs::read_to_string(path) {
+            let pid = existing.trim();
+            if !pid.is_empty() && pid != std::process::id().to_string() && Path::new("/proc").join(pid).exists() {
+                anyhow::bail!("{} says the bot is already running as process {}", path.display(), pid);
+            }
+        }
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write pid file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!("Failed to remove pid file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pid_file_and_notify() {
+        let dir = std::env::temp_dir().join(format!("daemon-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bot.pid");
+
+        // A stale pid is replaced; a live one refuses to start a second bot
+        std::fs::write(&path, "4294967295\n").unwrap();
+        let pid = PidFile::create(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), std::process::id().to_string());
+        std::fs::write(&path, "1\n").unwrap();
+        assert!(PidFile::create(&path).is_err());
+        drop(pid);
+        assert!(!path.exists());
+
+        let socket_path = dir.join("notify.sock");
+        let socket = UnixDatagram::bind(&socket_path).unwrap();
+        std::env::set_var("NOTIFY_SOCKET", &socket_path);
+        notify("READY=1");
+        std::env::remove_var("NOTIFY_SOCKET");
+        let mut buf = [0u8; 64];
+        let n = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
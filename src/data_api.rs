@@ -0,0 +1,336 @@
+//! Client for Polymarket's public data API: what a wallet did, what it
+//! holds, and who holds a market.
+//!
+//! Listings are paged inside the client, following `next_cursor` when the
+//! API hands one out and `offset` otherwise, until the caller's limit or
+//! the last page. Requests are spaced to at most `data_api_rate` a second,
+//! and a 429 waits out `Retry-After` (or a second) before trying again.
+//!
+//! With `data_api` set, leader screening ([`crate::scout`]) and `mybot
+//! report wallet` ([`crate::report`]) read wallet trades from here.
+
+use crate::api::number;
+use crate::types::{Config, Trade, TradeSide};
+use anyhow::{Context, Result};
+use reqwest::{Client as HttpClient, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Items asked for per page.
+const PAGE_SIZE: usize = 500;
+
+/// Tries at a rate-limited request before giving up.
+const MAX_ATTEMPTS: usize = 4;
+
+/// The cursor the API sends after the last page.
+const END_CURSOR: &str = "LTE=";
+
+/// Something a wallet did on chain: a trade, split, merge, redemption, ...
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Activity {
+    pub wallet: String,
+    /// "TRADE", "REDEEM", "SPLIT", "MERGE", ...
+    pub kind: String,
+    /// Condition id
+    pub market_id: String,
+    /// Outcome token id
+    pub asset: String,
+    pub outcome: String,
+    pub title: String,
+    /// "BUY" or "SELL" for trades
+    pub side: String,
+    pub shares: f64,
+    pub price: f64,
+    pub usd: f64,
+    /// Unix seconds
+    pub timestamp: i64,
+    pub tx_hash: Option<String>,
+}
+
+impl Activity {
+    /// The activity as a leader trade; `None` unless it's a trade.
+    pub fn to_trade(&self) -> Option<Trade> {
+        if !self.kind.eq_ignore_ascii_case("TRADE") {
+            return None;
+        }
+        Some(Trade {
+            wallet: self.wallet.to_lowercase(),
+            event_id: String::new(),
+            market_id: self.market_id.clone(),
+            side: TradeSide::parse(&self.side)?,
+            shares: self.shares,
+            price: self.price,
+            timestamp: self.timestamp,
+            tx_hash: self.tx_hash.clone(),
+        })
+    }
+}
+
+/// An open position of a wallet, valued by the API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Position {
+    pub market_id: String,
+    pub asset: String,
+    pub outcome: String,
+    pub title: String,
+    pub shares: f64,
+    pub avg_price: f64,
+    pub current_price: f64,
+    pub initial_value: f64,
+    pub current_value: f64,
+    pub cash_pnl: f64,
+    pub redeemable: bool,
+}
+
+/// A wallet holding one of a market's outcome tokens.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Holder {
+    pub wallet: String,
+    pub name: Option<String>,
+    pub asset: String,
+    pub outcome_index: u32,
+    pub shares: f64,
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+pub fn parse_activity(item: &Value) -> Activity {
+    Activity {
+        wallet: text(&item["proxyWallet"]),
+        kind: text(&item["type"]),
+        market_id: text(&item["conditionId"]),
+        asset: text(&item["asset"]),
+        outcome: text(&item["outcome"]),
+        title: text(&item["title"]),
+        side: text(&item["side"]),
+        shares: number(&item["size"]).unwrap_or(0.0),
+        price: number(&item["price"]).unwrap_or(0.0),
+        usd: number(&item["usdcSize"]).unwrap_or(0.0),
+        timestamp: item["timestamp"].as_i64().unwrap_or(0),
+        tx_hash: item["transactionHash"]
+            .as_str()
+            .filter(|h| !h.is_empty())
+            .map(str::to_string),
+    }
+}
+
+pub fn parse_position(item: &Value) -> Position {
+    Position {
+        market_id: text(&item["conditionId"]),
+        asset: text(&item["asset"]),
+        outcome: text(&item["outcome"]),
+        title: text(&item["title"]),
+        shares: number(&item["size"]).unwrap_or(0.0),
+        avg_price: number(&item["avgPrice"]).unwrap_or(0.0),
+        current_price: number(&item["curPrice"]).unwrap_or(0.0),
+        initial_value: number(&item["initialValue"]).unwrap_or(0.0),
+        current_value: number(&item["currentValue"]).unwrap_or(0.0),
+        cash_pnl: number(&item["cashPnl"]).unwrap_or(0.0),
+        redeemable: item["redeemable"].as_bool().unwrap_or(false),
+    }
+}
+
+/// Holders arrive grouped by outcome token.
+pub fn parse_holders(value: &Value) -> Vec<Holder> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|token| {
+            token["holders"].as_array().into_iter().flatten().map(move |h| Holder {
+                wallet: text(&h["proxyWallet"]),
+                name: h["name"]
+                    .as_str()
+                    .or_else(|| h["pseudonym"].as_str())
+                    .filter(|n| !n.is_empty())
+                    .map(str::to_string),
+                asset: text(h.get("asset").unwrap_or(&token["token"])),
+                outcome_index: h["outcomeIndex"].as_u64().unwrap_or(0) as u32,
+                shares: number(&h["amount"]).unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+/// One page of a listing and where the next one starts.
+fn page(value: Value) -> (Vec<Value>, Option<String>) {
+    match value {
+        Value::Array(items) => (items, None),
+        mut other => {
+            let items = other["data"].as_array().cloned().unwrap_or_default();
+            let cursor = other["next_cursor"]
+                .take()
+                .as_str()
+                .filter(|c| !c.is_empty() && *c != END_CURSOR)
+                .map(str::to_string);
+            (items, cursor)
+        }
+    }
+}
+
+pub struct Client {
+    http: HttpClient,
+    base_url: String,
+    /// Least time between requests
+    spacing: Duration,
+    next_request: Mutex<Instant>,
+}
+
+impl Client {
+    /// At most `rate` requests a second; zero is unlimited.
+    pub fn new(base_url: impl Into<String>, rate: u32) -> Self {
+        Self {
+            http: HttpClient::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            spacing: match rate {
+                0 => Duration::ZERO,
+                rate => Duration::from_secs(1) / rate,
+            },
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// `None` when `data_api` is empty.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (!config.data_api.is_empty()).then(|| Self::new(&config.data_api, config.data_api_rate))
+    }
+
+    /// Waits for the next request slot.
+    async fn throttle(&self) {
+        let mut next = self.next_request.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep_until(*next).await;
+        }
+        *next = Instant::now() + self.spacing;
+    }
+
+    async fn get(&self, path: &str, params: &[(&str, String)]) -> Result<Value> {
+        let url = format!("{}{}", self.base_url, path);
+        for attempt in 1.. {
+            self.throttle().await;
+            let resp = self
+                .http
+                .get(&url)
+                .query(params)
+                .send()
+                .await
+                .with_context(|| format!("Failed to fetch {}", path))?;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS && attempt < MAX_ATTEMPTS {
+                let wait = resp
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok()?.parse().ok())
+                    .map_or(Duration::from_secs(1), Duration::from_secs);
+                tracing::debug!("Data API rate limited, retrying {} in {:?}", path, wait);
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+            return Ok(resp.error_for_status()?.json::<Value>().await?);
+        }
+        unreachable!()
+    }
+
+    /// Up to `limit` items of a listing, a page at a time.
+    async fn list(&self, path: &str, params: &[(&str, String)], limit: usize) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        while items.len() < limit {
+            let page_size = PAGE_SIZE.min(limit - items.len());
+            let mut page_params = params.to_vec();
+            page_params.push(("limit", page_size.to_string()));
+            match &cursor {
+                Some(cursor) => page_params.push(("next_cursor", cursor.clone())),
+                None => page_params.push(("offset", items.len().to_string())),
+            }
+            let (page, next) = page(self.get(path, &page_params).await?);
+            let done = page.len() < page_size && next.is_none();
+            let empty = page.is_empty();
+            items.extend(page);
+            cursor = next;
+            if done || empty {
+                break;
+            }
+        }
+        items.truncate(limit);
+        Ok(items)
+    }
+
+    /// The wallet's activity since `since` (unix seconds), newest first.
+    pub async fn activity(&self, wallet: &str, since: i64, limit: usize) -> Result<Vec<Activity>> {
+        let params = [("user", wallet.to_string()), ("start", since.to_string())];
+        let items = self.list("/activity", &params, limit).await?;
+        Ok(items.iter().map(parse_activity).collect())
+    }
+
+    /// The wallet's trades since `since` (unix seconds).
+    pub async fn trades(&self, wallet: &str, since: i64) -> Result<Vec<Trade>> {
+        let activity = self.activity(wallet, since, usize::MAX).await?;
+        Ok(activity.iter().filter_map(Activity::to_trade).collect())
+    }
+
+    pub async fn positions(&self, wallet: &str) -> Result<Vec<Position>> {
+        let items = self
+            .list("/positions", &[("user", wallet.to_string())], usize::MAX)
+            .await?;
+        Ok(items.iter().map(parse_position).collect())
+    }
+
+    /// The largest holders of each of the market's outcome tokens.
+    pub async fn holders(&self, market_id: &str, limit: usize) -> Result<Vec<Holder>> {
+        let params = [("market", market_id.to_string()), ("limit", limit.to_string())];
+        Ok(parse_holders(&self.get("/holders", &params).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parses_activity_positions_holders_and_pages() {
+        let trade = json!({
+            "proxyWallet": "0xABC", "timestamp": 1760000000, "conditionId": "0xc1", "type": "TRADE",
+            "size": 120.5, "usdcSize": 60.25, "transactionHash": "0xtx", "price": 0.5,
+            "asset": "111", "side": "BUY", "outcome": "Yes", "title": "Will it?"
+        });
+        let activity = parse_activity(&trade);
+        let copied = activity.to_trade().unwrap();
+        assert_eq!((copied.wallet.as_str(), copied.market_id.as_str()), ("0xabc", "0xc1"));
+        assert_eq!((copied.side, copied.shares, copied.price), (TradeSide::BUY, 120.5, 0.5));
+        assert_eq!(copied.tx_hash.as_deref(), Some("0xtx"));
+        let redeem = parse_activity(&json!({ "type": "REDEEM", "conditionId": "0xc1", "size": 10 }));
+        assert!(redeem.to_trade().is_none());
+
+        let position = parse_position(&json!({
+            "conditionId": "0xc1", "asset": "111", "size": "50", "avgPrice": 0.4, "curPrice": 0.55,
+            "initialValue": 20, "currentValue": 27.5, "cashPnl": 7.5, "redeemable": false
+        }));
+        assert_eq!((position.shares, position.cash_pnl), (50.0, 7.5));
+
+        let holders = parse_holders(&json!([
+            { "token": "111", "holders": [{ "proxyWallet": "0xa", "amount": 900, "outcomeIndex": 0, "name": "" }] },
+            { "token": "222", "holders": [{ "proxyWallet": "0xb", "amount": "40", "outcomeIndex": 1, "pseudonym": "Bee" }] }
+        ]));
+        assert_eq!(holders.len(), 2);
+        assert_eq!((holders[0].asset.as_str(), holders[0].name.as_deref()), ("111", None));
+        assert_eq!((holders[1].outcome_index, holders[1].name.as_deref()), (1, Some("Bee")));
+
+        assert_eq!(page(json!([1, 2])), (vec![json!(1), json!(2)], None));
+        assert_eq!(
+            page(json!({ "data": [1], "next_cursor": "MTAw" })),
+            (vec![json!(1)], Some("MTAw".to_string()))
+        );
+        assert_eq!(page(json!({ "data": [], "next_cursor": END_CURSOR })).1, None);
+    }
+}
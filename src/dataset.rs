@@ -0,0 +1,230 @@
+//! `mybot data fetch`: history for backtests and leader screening, kept in
+//! the journal.
+//!
+//! Leader trades go into `leader_trades`, observed at the time they were
+//! made and left out when already journaled, so fetching twice is harmless.
+//! The markets they touched (or those asked for) are saved to the market
+//! catalog, and each market's price history to `price_samples` as `last`
+//! prices, one per `interval`. `backtest --journal` replays the trades, and
+//! the latency fill model prices copies from the samples.
+
+use crate::api::PolymarketApi;
+use crate::dedup::trade_key;
+use crate::markets::MarketCache;
+use crate::storage::{PriceSample, Storage, TimeRange};
+use crate::types::Trade;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::time::Duration;
+
+/// Journaled trades this close to the range are checked for duplicates too,
+/// since a live bot observes trades a little after they're made.
+const OBSERVED_SLACK_MS: i64 = 24 * 3600 * 1000;
+
+#[derive(Debug, Clone)]
+pub struct FetchRequest {
+    pub wallets: Vec<String>,
+    /// The markets the fetched trades touched when empty
+    pub markets: Vec<String>,
+    pub range: TimeRange,
+    /// Spacing of price samples
+    pub interval: Duration,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FetchReport {
+    pub trades_fetched: usize,
+    /// New to the journal
+    pub trades_stored: usize,
+    pub markets: usize,
+    pub price_samples: usize,
+    /// Wallets and markets that couldn't be fetched, with why
+    pub failed: Vec<String>,
+}
+
+impl std::fmt::Display for FetchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Trades:        {} fetched, {} new to the journal",
+            self.trades_fetched, self.trades_stored
+        )?;
+        writeln!(f, "Markets:       {}", self.markets)?;
+        writeln!(f, "Price samples: {}", self.price_samples)?;
+        for failure in &self.failed {
+            writeln!(f, "❌ {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// The key a trade is deduplicated on; the API and the feed may spell a
+/// wallet differently.
+fn key(trade: &Trade) -> String {
+    trade_key(&Trade {
+        wallet: trade.wallet.to_lowercase(),
+        ..trade.clone()
+    })
+}
+
+/// Fetches `request` into `storage`.
+pub async fn fetch(
+    api: &PolymarketApi,
+    catalog: &MarketCache,
+    storage: &dyn Storage,
+    request: &FetchRequest,
+) -> Result<FetchReport> {
+    let mut report = FetchReport::default();
+    let range = request.range;
+    let journaled = storage
+        .leader_trades(TimeRange {
+            from_ms: range.from_ms.saturating_sub(OBSERVED_SLACK_MS),
+            to_ms: range.to_ms.saturating_add(OBSERVED_SLACK_MS),
+        })
+        .await?;
+    let mut seen: HashSet<String> = journaled.iter().map(|r| key(&r.trade)).collect();
+
+    let mut touched = BTreeSet::new();
+    for wallet in &request.wallets {
+        let trades = match api.get_trades(wallet, range.from_ms / 1000).await {
+            Ok(trades) => trades,
+            Err(e) => {
+                tracing::warn!("Failed to fetch trades of {}: {:#}", wallet, e);
+                report.failed.push(format!("trades of {}: {:#}", wallet, e));
+                continue;
+            }
+        };
+        let trades: Vec<Trade> = trades
+            .into_iter()
+            .filter(|t| (range.from_ms..=range.to_ms).contains(&(t.timestamp * 1000)))
+            .collect();
+        tracing::info!("📥 {} trades of {}", trades.len(), wallet);
+        report.trades_fetched += trades.len();
+        for trade in trades {
+            touched.insert(trade.market_id.clone());
+            if seen.insert(key(&trade)) {
+                storage.record_leader_trade(&trade, trade.timestamp * 1000).await?;
+                report.trades_stored += 1;
+            }
+        }
+    }
+
+    let markets: Vec<String> = match request.markets.is_empty() {
+        true => touched.into_iter().collect(),
+        false => request.markets.clone(),
+    };
+    let now = chrono::Utc::now().timestamp();
+    let (start, end) = (range.from_ms / 1000, (range.to_ms / 1000).min(now));
+    let fidelity = (request.interval.as_secs() / 60).max(1);
+    for market_id in markets {
+        // The price history is per token; samples are kept by market, YES first
+        let token = match catalog.get(&market_id).await {
+            Ok(market) => {
+                report.markets += 1;
+                market.token_ids.first().cloned().unwrap_or_else(|| market_id.clone())
+            }
+            Err(e) => {
+                tracing::warn!("Failed to fetch market {}: {:#}", market_id, e);
+                report.failed.push(format!("market {}: {:#}", market_id, e));
+                continue;
+            }
+        };
+        let points = match api.get_price_history(&token, start, end, fidelity).await {
+            Ok(points) => points,
+            Err(e) => {
+                tracing::warn!("Failed to fetch prices of {}: {:#}", market_id, e);
+                report.failed.push(format!("prices of {}: {:#}", market_id, e));
+                continue;
+            }
+        };
+        let samples: Vec<PriceSample> = points
+            .into_iter()
+            .map(|(t, price)| PriceSample {
+                market_id: market_id.clone(),
+                sampled_at: t * 1000,
+                mid: None,
+                last: Some(price),
+                book: None,
+            })
+            .collect();
+        storage.record_prices(&samples).await?;
+        tracing::info!("📈 {} price samples of {}", samples.len(), market_id);
+        report.price_samples += samples.len();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{serve, Handler, Request, Response};
+    use crate::storage::sqlite::SqliteStore;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    /// The data API, as far as fetching needs it.
+    struct DataApi;
+
+    #[async_trait]
+    impl Handler for DataApi {
+        async fn handle(&self, request: &Request) -> Option<Response> {
+            let body = match request.path.as_str() {
+                "/trades" => json!([
+                    { "wallet": "0xLEADER", "event_id": "e1", "market_id": "m1", "side": "BUY",
+                      "shares": 10.0, "price": 0.5, "timestamp": 1_000, "tx_hash": "0x1" },
+                    { "wallet": "0xLEADER", "event_id": "e1", "market_id": "m1", "side": "SELL",
+                      "shares": 10.0, "price": 0.6, "timestamp": 9_000, "tx_hash": "0x2" },
+                ]),
+                "/markets/m1" => json!({ "question": "Will it?", "token_ids": ["yes-token", "no-token"] }),
+                "/prices-history" if request.query_param("market") == Some("yes-token") => {
+                    json!({ "history": [{ "t": 1_000, "p": 0.5 }, { "t": 1_060, "p": 0.52 }] })
+                }
+                _ => return None,
+            };
+            Some(Response::json(200, &body))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_is_idempotent() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        serve(&format!("127.0.0.1:{}", port), vec![Arc::new(DataApi)])
+            .await
+            .unwrap();
+        let api = PolymarketApi::new(format!("http://127.0.0.1:{}", port));
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let catalog = MarketCache::new(
+            api.clone(),
+            Some(storage.clone()),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        );
+        let request = FetchRequest {
+            wallets: vec!["0xleader".to_string()],
+            markets: vec![],
+            range: TimeRange {
+                from_ms: 0,
+                to_ms: 5_000_000,
+            },
+            interval: Duration::from_secs(60),
+        };
+
+        let report = fetch(&api, &catalog, storage.as_ref(), &request).await.unwrap();
+        assert_eq!((report.trades_fetched, report.trades_stored), (1, 1));
+        assert_eq!((report.markets, report.price_samples), (1, 2));
+        assert!(report.failed.is_empty());
+
+        let again = fetch(&api, &catalog, storage.as_ref(), &request).await.unwrap();
+        assert_eq!(again.trades_stored, 0);
+        let trades = storage.leader_trades(TimeRange::all()).await.unwrap();
+        assert_eq!((trades.len(), trades[0].observed_at), (1, 1_000_000));
+        assert_eq!(storage.prices(Some("m1"), TimeRange::all()).await.unwrap().len(), 2);
+        assert_eq!(storage.markets().await.unwrap().len(), 1);
+    }
+}
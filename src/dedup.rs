@@ -0,0 +1,114 @@
+//! Drops leader trades that were already handled: re-sent after a WebSocket
+//! reconnect, or seen again after a restart.
+
+use crate::storage::Storage;
+use crate::types::Trade;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub struct TradeDeduper {
+    window: Duration,
+    recent: Mutex<HashMap<String, i64>>,
+    storage: Option<Arc<dyn Storage>>,
+}
+
+impl TradeDeduper {
+    /// Remembers trades for `window` (zero disables deduplication). With
+    /// `storage`, the memory survives restarts and is shared by instances
+    /// on the same database.
+    pub fn new(window: Duration, storage: Option<Arc<dyn Storage>>) -> Self {
+        Self {
+            window,
+            recent: Mutex::new(HashMap::new()),
+            storage,
+        }
+    }
+
+    /// Trades remembered in memory, including any past the window that
+    /// haven't been pruned yet.
+    pub fn cached(&self) -> usize {
+        self.recent.lock().unwrap().len()
+    }
+
+    /// True the first time `trade` is seen within the window.
+    pub async fn first_seen(&self, trade: &Trade, now_ms: i64) -> bool {
+        if self.window.is_zero() {
+            return true;
+        }
+        let key = trade_key(trade);
+        let not_before = now_ms - self.window.as_millis() as i64;
+        {
+            let mut recent = self.recent.lock().unwrap();
+            recent.retain(|_, seen_at| *seen_at >= not_before);
+            if recent.contains_key(&key) {
+                return false;
+            }
+            recent.insert(key.clone(), now_ms);
+        }
+
+        let Some(storage) = &self.storage else { return true };
+        match storage.mark_seen(&key, now_ms, not_before).await {
+            Ok(first) => first,
+            Err(e) => {
+                // In-memory dedup still covers this process
+                tracing::warn!("Failed to check seen trades: {}", e);
+                true
+            }
+        }
+    }
+}
+
+/// Identifies a leader fill: its transaction when known, otherwise every
+/// field the feed sends.
+pub fn trade_key(trade: &Trade) -> String {
+    let id = match &trade.tx_hash {
+        Some(tx) => tx.clone(),
+        None => format!("{}@{}x{}", trade.timestamp, trade.price, trade.shares),
+    };
+    format!(
+        "{}:{}:{}:{}:{}",
+        trade.wallet,
+        trade.event_id,
+        trade.market_id,
+        trade.side.as_str(),
+        id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+    use crate::types::TradeSide;
+
+    #[tokio::test]
+    async fn test_dedup_survives_restart_within_window() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let trade = Trade {
+            wallet: "0xleader".to_string(),
+            event_id: "event1".to_string(),
+            market_id: "market1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.5,
+            timestamp: 1_700_000_000,
+            tx_hash: Some("0xabc".to_string()),
+        };
+        let hour = 3_600_000;
+
+        let first = TradeDeduper::new(Duration::from_secs(3600), Some(Arc::clone(&storage)));
+        assert!(first.first_seen(&trade, 0).await);
+        assert!(!first.first_seen(&trade, 1).await);
+
+        let restarted = TradeDeduper::new(Duration::from_secs(3600), Some(Arc::clone(&storage)));
+        assert!(!restarted.first_seen(&trade, hour / 2).await);
+        assert!(restarted.first_seen(&trade, 2 * hour).await);
+
+        let other = Trade {
+            tx_hash: Some("0xdef".to_string()),
+            ..trade
+        };
+        assert!(restarted.first_seen(&other, 2 * hour).await);
+    }
+}
@@ -0,0 +1,169 @@
+//! UMA oracle requests of held markets.
+//!
+//! Polymarket markets resolve through UMA's optimistic oracle: someone
+//! proposes an outcome, which stands unless disputed within the challenge
+//! window; a disputed proposal is re-proposed or goes to a vote of UMA
+//! holders. Prices gap while a proposal is open to dispute, and more so
+//! once it's disputed.
+//!
+//! Every `dispute_interval` [`DisputeWatcher`] looks up each held market's
+//! [`Resolution`] on the Gamma API and sends a
+//! [`Notification::ResolutionUpdate`] when one is newly proposed or
+//! disputed, naming the resolution source the outcome is read from. A
+//! market disputed again between two looks counts as newly disputed.
+
+use crate::gamma::{self, Resolution, UmaStatus};
+use crate::notify::{Notification, Notifications};
+use crate::portfolio::Portfolio;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub struct DisputeWatcher {
+    gamma: Arc<gamma::Client>,
+    portfolio: Arc<Portfolio>,
+    interval: Duration,
+    // Latest resolution of each held market
+    seen: Mutex<HashMap<String, Resolution>>,
+    notifications: Notifications,
+}
+
+impl DisputeWatcher {
+    pub fn new(gamma: Arc<gamma::Client>, portfolio: Arc<Portfolio>, interval: Duration) -> Self {
+        Self {
+            gamma,
+            portfolio,
+            interval,
+            seen: Mutex::new(HashMap::new()),
+            notifications: Notifications::default(),
+        }
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// The latest resolution of every held market looked up.
+    pub fn resolutions(&self) -> Vec<Resolution> {
+        let mut resolutions: Vec<Resolution> = self.seen.lock().unwrap().values().cloned().collect();
+        resolutions.sort_by(|a, b| a.market_id.cmp(&b.market_id));
+        resolutions
+    }
+
+    /// Records the market's resolution; returns the alert when it was just
+    /// proposed or disputed.
+    pub fn observe(&self, question: &str, resolution: Resolution) -> Option<Notification> {
+        let previous = self
+            .seen
+            .lock()
+            .unwrap()
+            .insert(resolution.market_id.clone(), resolution.clone());
+        let changed = match &previous {
+            Some(previous) => previous.status != resolution.status || resolution.disputes() > previous.disputes(),
+            None => true,
+        };
+        let alerting = matches!(resolution.status, UmaStatus::Proposed | UmaStatus::Disputed);
+        (changed && alerting).then(|| Notification::ResolutionUpdate {
+            market_id: resolution.market_id,
+            question: question.to_string(),
+            status: resolution.status.as_str().to_string(),
+            source: resolution.source,
+        })
+    }
+
+    /// Looks up every held market once; returns the alerts sent.
+    pub async fn check(&self) -> Vec<Notification> {
+        let held: Vec<String> = self.portfolio.holdings().into_iter().map(|h| h.market_id).collect();
+        self.seen.lock().unwrap().retain(|id, _| held.contains(id));
+        let mut alerts = Vec::new();
+        for market_id in &held {
+            let looked_up = match self.gamma.market(market_id).await {
+                Ok(market) => self.gamma.resolution(market_id).await.map(|r| (market.question, r)),
+                Err(e) => Err(e),
+            };
+            let (question, resolution) = match looked_up {
+                Ok(found) => found,
+                Err(e) => {
+                    tracing::warn!("Failed to look up the resolution of {}: {:#}", market_id, e);
+                    continue;
+                }
+            };
+            if let Some(alert) = self.observe(&question, resolution) {
+                tracing::warn!("{}", alert);
+                self.notifications.send(alert.clone());
+                alerts.push(alert);
+            }
+        }
+        alerts
+    }
+
+    /// Checks every `dispute_interval` in the background; a zero interval
+    /// disables it.
+    pub fn spawn(self: Arc<Self>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                self.check().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CostBasis;
+    use serde_json::json;
+
+    #[test]
+    fn test_alerts_when_a_held_market_is_proposed_or_disputed() {
+        let gamma = Arc::new(gamma::Client::new("https://example", Duration::ZERO));
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Average, None));
+        let watcher = DisputeWatcher::new(gamma, portfolio, Duration::from_secs(600));
+        let resolution = |statuses: &[&str]| {
+            gamma::parse_resolution(
+                "0xc1",
+                &json!({
+                    "resolutionSource": "https://www.bls.gov",
+                    "umaResolutionStatuses": serde_json::to_string(statuses).unwrap(),
+                    "umaBond": "500",
+                }),
+            )
+        };
+
+        assert!(watcher.observe("CPI above 3%?", resolution(&[])).is_none());
+        let proposed = watcher.observe("CPI above 3%?", resolution(&["proposed"])).unwrap();
+        assert_eq!(
+            proposed,
+            Notification::ResolutionUpdate {
+                market_id: "0xc1".to_string(),
+                question: "CPI above 3%?".to_string(),
+                status: "proposed".to_string(),
+                source: "https://www.bls.gov".to_string(),
+            }
+        );
+        assert!(watcher.observe("CPI above 3%?", resolution(&["proposed"])).is_none());
+        assert!(watcher
+            .observe("CPI above 3%?", resolution(&["proposed", "disputed"]))
+            .is_some());
+        // Re-proposed and disputed again between two looks
+        let again = resolution(&["proposed", "disputed", "proposed", "disputed"]);
+        assert_eq!(
+            (again.status, again.disputes(), again.bond),
+            (UmaStatus::Disputed, 2, 500.0)
+        );
+        assert!(watcher.observe("CPI above 3%?", again).is_some());
+        assert!(watcher
+            .observe(
+                "CPI above 3%?",
+                resolution(&["proposed", "disputed", "proposed", "disputed", "resolved"])
+            )
+            .is_none());
+        assert_eq!(watcher.resolutions()[0].status, UmaStatus::Resolved);
+    }
+}
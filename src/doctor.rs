@@ -0,0 +1,466 @@
+//! `mybot doctor`: checks everything the bot depends on outside its config
+//! and says how to fix what fails.
+//!
+//! Goes through the RPC node (and, over WebSocket, the subscriptions
+//! mempool mode needs), the trade feed, the exchange credentials, the
+//! signer, balances and token approvals on Polygon, the clock and the
+//! journal. Each check has its own timeout, so one dead endpoint doesn't
+//! hide the rest.
+
+use crate::api::PolymarketApi;
+use crate::config;
+use crate::exchange;
+use crate::executor::TradeExecutor;
+use crate::recovery::CTF_CONTRACT;
+use crate::rpc::{self, RpcStats};
+use crate::storage::{self, now_ms};
+use crate::types::{Config, Venue};
+use anyhow::{Context, Result};
+use ethers::providers::{JsonRpcClient, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, Bytes, TransactionRequest, U256};
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long any one check may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const POLYGON_CHAIN_ID: u64 = 137;
+
+/// Bridged USDC (USDC.e) on Polygon, which Polymarket settles in.
+pub const USDC_CONTRACT: &str = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174";
+pub const USDC_DECIMALS: f64 = 1_000_000.0;
+
+/// Contracts that move the signer's USDC and outcome tokens when orders fill.
+pub const SPENDERS: &[(&str, &str)] = &[
+    ("CTF exchange", "0x4bFB41d5B3570DeFd03C39a9A4D8dE6Bd8B8982E"),
+    ("neg-risk exchange", "0xC5d563A36AE78145C45a50134d48A1215220f80a"),
+    ("neg-risk adapter", "0xd91E80cF2E7be2e162c6513ceD06f1dD0dA35296"),
+];
+
+/// `allowance(address,address)`
+const ALLOWANCE_SELECTOR: [u8; 4] = [0xdd, 0x62, 0xed, 0x3e];
+/// `isApprovedForAll(address,address)`
+const APPROVED_FOR_ALL_SELECTOR: [u8; 4] = [0xe9, 0x85, 0xe9, 0xc5];
+
+/// Clock drift worth a warning, and drift that fails the check; leader
+/// trade ages and order expiries are computed from the local clock.
+const SKEW_WARN: Duration = Duration::from_secs(1);
+const SKEW_FAIL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+    /// Not applicable to this config
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnosis {
+    pub check: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+    /// How to fix a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icon = match self.outcome {
+            Outcome::Pass => "✅",
+            Outcome::Warn => "⚠️ ",
+            Outcome::Fail => "❌",
+            Outcome::Skip => "➖",
+        };
+        write!(f, "{} {:<14} {}", icon, self.check, self.detail)?;
+        if let Some(hint) = self.hint {
+            write!(f, "\n   → {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// The checks' results, in the order they ran.
+#[derive(Debug, Default)]
+struct Findings(Vec<Diagnosis>);
+
+impl Findings {
+    fn push(&mut self, check: &'static str, outcome: Outcome, detail: impl Into<String>, hint: Option<&'static str>) {
+        self.0.push(Diagnosis {
+            check,
+            outcome,
+            detail: detail.into(),
+            hint,
+        });
+    }
+
+    fn pass(&mut self, check: &'static str, detail: impl Into<String>) {
+        self.push(check, Outcome::Pass, detail, None);
+    }
+
+    fn skip(&mut self, check: &'static str, detail: impl Into<String>) {
+        self.push(check, Outcome::Skip, detail, None);
+    }
+
+    /// Passes with `detail` on success, fails with the error and `hint`.
+    fn result<T>(
+        &mut self,
+        check: &'static str,
+        result: Result<T>,
+        detail: impl FnOnce(T) -> String,
+        hint: &'static str,
+    ) {
+        match result {
+            Ok(value) => self.pass(check, detail(value)),
+            Err(e) => self.push(check, Outcome::Fail, format!("{:#}", e), Some(hint)),
+        }
+    }
+}
+
+async fn timed<T>(check: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| anyhow::bail!("no answer within {}s", TIMEOUT.as_secs()))
+}
+
+/// Runs every check against `config`.
+pub async fn diagnose(config: &Config) -> Vec<Diagnosis> {
+    let mut findings = Findings::default();
+    let live = !config.paper_trading;
+
+    findings.result(
+        "config",
+        config::validate_config(config),
+        |_| "valid".to_string(),
+        "Fix the setting named above, then run mybot check-config",
+    );
+
+    let signer = signer_address(config);
+    match (&signer, live) {
+        (Ok(address), _) => findings.pass("signer", format!("private key signs as {:?}", address)),
+        (Err(e), false) => findings.skip("signer", format!("paper trading ({:#})", e)),
+        (Err(e), true) => findings.push(
+            "signer",
+            Outcome::Fail,
+            format!("{:#}", e),
+            Some("Set PRIVATE_KEY (or a sealed signer section) to the key of YOUR_WALLET"),
+        ),
+    }
+
+    let stats = Arc::new(RpcStats::new());
+    if config.rpc_url.is_empty() {
+        findings.push(
+            "rpc",
+            Outcome::Fail,
+            "RPC_URL is not set",
+            Some("Set RPC_URL to a Polygon node, e.g. wss://polygon-mainnet.g.alchemy.com/v2/<key>"),
+        );
+    } else if config.rpc_url.starts_with("ws") {
+        match timed(rpc::connect_ws(&config.rpc_url, &stats)).await {
+            Ok(provider) => {
+                chain_checks(&provider, config, &mut findings).await;
+                let blocks = timed(async { Ok(provider.subscribe_blocks().await?) }).await;
+                let pending = timed(async { Ok(provider.subscribe_pending_txs().await?) }).await;
+                match (blocks, pending) {
+                    (Ok(_), Ok(_)) => findings.pass("subscriptions", "newHeads and newPendingTransactions"),
+                    (Ok(_), Err(e)) => findings.push(
+                        "subscriptions",
+                        Outcome::Warn,
+                        format!("newHeads only; newPendingTransactions failed: {:#}", e),
+                        Some("Mempool mode needs a node that streams pending transactions"),
+                    ),
+                    (Err(e), _) => findings.push(
+                        "subscriptions",
+                        Outcome::Fail,
+                        format!("eth_subscribe failed: {:#}", e),
+                        Some("Use a node or plan that supports eth_subscribe over WebSocket"),
+                    ),
+                }
+            }
+            Err(e) => {
+                rpc_down(&mut findings, e);
+                findings.skip("subscriptions", "RPC unavailable");
+            }
+        }
+    } else {
+        match rpc::http(&config.rpc_url, &stats) {
+            Ok(provider) => chain_checks(&provider, config, &mut findings).await,
+            Err(e) => rpc_down(&mut findings, e),
+        }
+        findings.skip("subscriptions", "HTTP RPC_URL; mempool mode needs wss://");
+    }
+
+    findings.result(
+        "trade feed",
+        timed(async {
+            let (mut socket, _) = tokio_tungstenite::connect_async(config.ws_url.as_str()).await?;
+            let _ = socket.close(None).await;
+            Ok(())
+        })
+        .await,
+        |_| format!("connected to {}", config.ws_url),
+        "Check WS_URL and that outbound WebSocket connections are allowed",
+    );
+
+    let api = PolymarketApi::new(config.polymarket_api.clone());
+    if live {
+        let checked = match exchange::from_config(config, api.clone()) {
+            Ok(exchange) => timed(TradeExecutor::new(api.clone(), config.clone()).with_exchange(exchange).check_auth()).await,
+            Err(e) => Err(e),
+        };
+        let hint = match config.exchange {
+            Venue::Polymarket => {
+                "The exchange rejected the key; check PRIVATE_KEY and that YOUR_WALLET is its Polymarket account"
+            }
+            Venue::Kalshi => "Kalshi rejected the request; check KALSHI_KEY_ID and KALSHI_PRIVATE_KEY",
+        };
+        findings.result("exchange auth", checked, |_| "credentials accepted".to_string(), hint);
+    } else {
+        findings.skip("exchange auth", "paper trading");
+    }
+
+    match timed(api.get_balance(&config.your_wallet)).await {
+        Ok(balance) if live && balance < config.min_stake.as_f64() => findings.push(
+            "balance",
+            Outcome::Warn,
+            format!(
+                "${:.2} on the exchange, below min_stake ${:.2}",
+                balance, config.min_stake.as_f64()
+            ),
+            Some("Deposit USDC to the Polymarket account before trading live"),
+        ),
+        Ok(balance) => findings.pass("balance", format!("${:.2} on the exchange", balance)),
+        Err(e) => findings.push(
+            "balance",
+            Outcome::Fail,
+            format!("{:#}", e),
+            Some("Check POLYMARKET_API and YOUR_WALLET"),
+        ),
+    }
+
+    match timed(server_clock_skew(&config.polymarket_api)).await {
+        Ok(skew) if skew.abs() >= SKEW_FAIL.as_millis() as i64 => findings.push(
+            "clock",
+            Outcome::Fail,
+            format!("{}ms off the exchange's clock", skew),
+            Some("Enable time sync (e.g. timedatectl set-ntp true or chrony)"),
+        ),
+        Ok(skew) if skew.abs() >= SKEW_WARN.as_millis() as i64 => findings.push(
+            "clock",
+            Outcome::Warn,
+            format!("{}ms off the exchange's clock", skew),
+            Some("Enable time sync (e.g. timedatectl set-ntp true or chrony)"),
+        ),
+        Ok(skew) => findings.pass("clock", format!("{}ms off the exchange's clock", skew)),
+        Err(e) => findings.push("clock", Outcome::Warn, format!("could not compare: {:#}", e), None),
+    }
+
+    if config.storage_url.is_empty() {
+        findings.skip("storage", "STORAGE_URL is empty; nothing is journaled");
+    } else {
+        findings.result(
+            "storage",
+            timed(async {
+                let storage = storage::open(&config.storage_url).await?;
+                storage.save_state("doctor", &now_ms().to_string(), now_ms()).await?;
+                storage.load_state("doctor").await
+            })
+            .await,
+            |_| format!("{} readable and writable", config.storage_url),
+            "Check STORAGE_URL, that the database is up and that the file or user may write",
+        );
+    }
+
+    findings.0
+}
+
+fn rpc_down(findings: &mut Findings, e: anyhow::Error) {
+    findings.push(
+        "rpc",
+        Outcome::Fail,
+        format!("{:#}", e),
+        Some("Check RPC_URL, its API key and that the node is reachable from here"),
+    );
+    findings.skip("allowances", "RPC unavailable");
+}
+
+/// Block height and network, then the signer's gas balance and approvals.
+async fn chain_checks<P: JsonRpcClient>(provider: &Provider<P>, config: &Config, findings: &mut Findings) {
+    let network = timed(async { Ok((provider.get_chainid().await?, provider.get_block_number().await?)) }).await;
+    match network {
+        Ok((chain, block)) if chain.as_u64() != POLYGON_CHAIN_ID => findings.push(
+            "rpc",
+            Outcome::Fail,
+            format!("chain {} at block {}, not Polygon", chain, block),
+            Some("Point RPC_URL at a Polygon mainnet node"),
+        ),
+        Ok((_, block)) => findings.pass("rpc", format!("Polygon at block {}", block)),
+        Err(e) => {
+            findings.push(
+                "rpc",
+                Outcome::Fail,
+                format!("{:#}", e),
+                Some("Check RPC_URL, its API key and that the node is reachable from here"),
+            );
+            findings.skip("allowances", "RPC unavailable");
+            return;
+        }
+    }
+
+    let Ok(owner) = config.your_wallet.parse::<Address>() else {
+        findings.skip("allowances", "YOUR_WALLET is not an address");
+        return;
+    };
+    match timed(approvals(provider, owner)).await {
+        Ok(missing) if missing.is_empty() => findings.pass("allowances", "USDC and outcome tokens approved"),
+        Ok(missing) => findings.push(
+            "allowances",
+            if config.paper_trading {
+                Outcome::Warn
+            } else {
+                Outcome::Fail
+            },
+            format!("not approved: {}", missing.join(", ")),
+            Some("Approve once by trading on polymarket.com with this wallet, or set the approvals with your wallet"),
+        ),
+        Err(e) => findings.push("allowances", Outcome::Warn, format!("could not read: {:#}", e), None),
+    }
+}
+
+/// Which spenders lack a USDC allowance or approval for the outcome tokens.
+async fn approvals<P: JsonRpcClient>(provider: &Provider<P>, owner: Address) -> Result<Vec<String>> {
+    let mut missing = Vec::new();
+    for (name, spender) in SPENDERS {
+        let spender: Address = spender.parse()?;
+        let allowance = call(provider, USDC_CONTRACT, ALLOWANCE_SELECTOR, owner, spender).await?;
+        missing.extend(usdc_allowance_missing(name, allowance));
+        if call(provider, CTF_CONTRACT, APPROVED_FOR_ALL_SELECTOR, owner, spender)
+            .await?
+            .is_zero()
+        {
+            missing.push(format!("outcome tokens for the {}", name));
+        }
+    }
+    Ok(missing)
+}
+
+/// What a USDC `allowance` for the `name` spender lacks, if anything.
+/// Compared as a uint256: the unlimited approval polymarket.com sets is
+/// `U256::MAX`.
+fn usdc_allowance_missing(name: &str, allowance: U256) -> Option<String> {
+    if allowance.is_zero() {
+        Some(format!("USDC for the {}", name))
+    } else if allowance < U256::from(USDC_DECIMALS as u64) {
+        Some(format!("USDC for the {} (under $1 left)", name))
+    } else {
+        None
+    }
+}
+
+/// Calls `selector(owner, spender)` on `contract` and reads a uint256.
+async fn call<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    contract: &str,
+    selector: [u8; 4],
+    owner: Address,
+    spender: Address,
+) -> Result<U256> {
+    let mut data = selector.to_vec();
+    for address in [owner, spender] {
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(address.as_bytes());
+    }
+    let tx = TransactionRequest::new()
+        .to(contract.parse::<Address>()?)
+        .data(Bytes::from(data));
+    let out = provider.call(&tx.into(), None).await?;
+    anyhow::ensure!(out.len() <= 32, "{} returned {} bytes for a uint256", contract, out.len());
+    Ok(U256::from_big_endian(&out))
+}
+
+/// The address the private key signs as, checked against `your_wallet`.
+fn signer_address(config: &Config) -> Result<Address> {
+    if config.private_key.is_empty() {
+        anyhow::bail!("no private key configured");
+    }
+    let wallet: LocalWallet = config
+        .private_key
+        .trim_start_matches("0x")
+        .parse()
+        .context("private key is not a 32-byte hex key")?;
+    let address = wallet.address();
+    let expected: Address = config.your_wallet.parse().context("YOUR_WALLET is not an address")?;
+    anyhow::ensure!(
+        address == expected,
+        "private key signs as {:?}, not YOUR_WALLET {:?}",
+        address,
+        expected
+    );
+    Ok(address)
+}
+
+/// Local clock minus the server's (from its `Date` header), in ms, taking
+/// the midpoint of the request as when the server answered.
+async fn server_clock_skew(url: &str) -> Result<i64> {
+    let sent = now_ms();
+    let response = reqwest::Client::new().head(url).send().await?;
+    let received = now_ms();
+    let date = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .context("no Date header")?
+        .to_str()?;
+    let server = chrono::DateTime::parse_from_rfc2822(date)?.timestamp_millis();
+    // The header has whole seconds; compare against the middle of that second
+    Ok((sent + received) / 2 - (server + 500))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited_usdc_allowance_is_enough() {
+        assert_eq!(usdc_allowance_missing("exchange", U256::MAX), None);
+        assert_eq!(usdc_allowance_missing("exchange", U256::from(1_000_000u64)), None);
+        assert_eq!(
+            usdc_allowance_missing("exchange", U256::from(999_999u64)).as_deref(),
+            Some("USDC for the exchange (under $1 left)")
+        );
+        assert_eq!(usdc_allowance_missing("exchange", U256::zero()).as_deref(), Some("USDC for the exchange"));
+    }
+
+    #[test]
+    fn test_signer_must_match_wallet() {
+        // The well-known first Hardhat/Anvil test key
+        let key = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        let mut config = Config {
+            private_key: key.to_string(),
+            your_wallet: "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266".to_string(),
+            ..Config::default()
+        };
+        assert!(signer_address(&config).is_ok());
+        config.your_wallet = format!("0x{}", "1".repeat(40));
+        assert!(signer_address(&config)
+            .unwrap_err()
+            .to_string()
+            .contains("not YOUR_WALLET"));
+        config.private_key = "nope".to_string();
+        assert!(signer_address(&config).is_err());
+
+        let failed = Diagnosis {
+            check: "rpc",
+            outcome: Outcome::Fail,
+            detail: "refused".to_string(),
+            hint: Some("Check RPC_URL"),
+        };
+        assert_eq!(failed.to_string(), "❌ rpc            refused\n   → Check RPC_URL");
+    }
+}
@@ -0,0 +1,282 @@
+//! The account's equity curve and what it says about risk.
+//!
+//! Every `equity_interval` a running bot adds the account's equity to a
+//! curve: its cash (the wallet's USDC, or the paper account's virtual cash)
+//! plus open positions at their marks, at cost when there's no price to
+//! hand, plus won shares booked at resolution but not yet redeemed. The
+//! curve is kept in the journal's state and survives restarts.
+//!
+//! [`stats`] reads the current and maximum drawdown off the curve, the
+//! returns over the last day and week, and a Sharpe-like ratio: the mean
+//! of daily returns (close to close, UTC) over their standard deviation,
+//! annualized over 365 days with no risk-free rate. Deposits and
+//! withdrawals count as returns, so they skew all of it; the ROI in
+//! [`crate::cashflow`] nets them out. The stats are served on
+//! `/status/equity` and shown in the TUI and digests.
+
+use crate::api::PolymarketApi;
+use crate::clock::Clock;
+use crate::marks::Marks;
+use crate::paper::{EquityPoint, PaperAccount};
+use crate::portfolio::Portfolio;
+use crate::resolution::{Redemption, Resolutions};
+use crate::storage::Storage;
+use crate::types::Config;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where the curve is kept in the journal's state.
+pub const EQUITY_STATE_KEY: &str = "equity_curve";
+
+/// Points kept; over a year at the default interval.
+const MAX_CURVE_POINTS: usize = 10_000;
+
+const DAY_MS: i64 = 86_400_000;
+
+/// Drawdown and returns as of the curve's last point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EquityStats {
+    /// Unix ms
+    pub at: i64,
+    pub equity: f64,
+    /// Highest equity on the curve
+    pub peak: f64,
+    /// How far equity is below the peak
+    pub drawdown: f64,
+    pub drawdown_pct: f64,
+    /// Largest fall from a peak anywhere on the curve
+    pub max_drawdown: f64,
+    pub max_drawdown_pct: f64,
+    /// Against the last point at least a day (a week) old; `None` until
+    /// the curve is that long
+    pub return_1d: Option<f64>,
+    pub return_7d: Option<f64>,
+    /// Standard deviation of daily returns
+    pub daily_volatility: Option<f64>,
+    /// Annualized mean over standard deviation of daily returns
+    pub sharpe: Option<f64>,
+    pub points: usize,
+}
+
+fn change(from: f64, to: f64) -> Option<f64> {
+    (from > 0.0).then(|| to / from - 1.0)
+}
+
+/// Close-to-close returns of the curve's UTC days, oldest first.
+pub fn daily_returns(curve: &[EquityPoint]) -> Vec<f64> {
+    let mut closes: Vec<(i64, f64)> = Vec::new();
+    for point in curve {
+        let day = point.at.div_euclid(DAY_MS);
+        match closes.last_mut() {
+            Some(close) if close.0 == day => close.1 = point.equity,
+            _ => closes.push((day, point.equity)),
+        }
+    }
+    closes.windows(2).filter_map(|w| change(w[0].1, w[1].1)).collect()
+}
+
+/// Stats of `curve` (oldest first); `None` when it's empty.
+pub fn stats(curve: &[EquityPoint]) -> Option<EquityStats> {
+    let last = *curve.last()?;
+    let mut stats = EquityStats {
+        at: last.at,
+        equity: last.equity,
+        peak: f64::MIN,
+        points: curve.len(),
+        ..Default::default()
+    };
+    for point in curve {
+        stats.peak = stats.peak.max(point.equity);
+        let drawdown = stats.peak - point.equity;
+        if drawdown > stats.max_drawdown {
+            stats.max_drawdown = drawdown;
+            stats.max_drawdown_pct = drawdown / stats.peak.max(f64::EPSILON);
+        }
+    }
+    stats.drawdown = stats.peak - last.equity;
+    stats.drawdown_pct = stats.drawdown / stats.peak.max(f64::EPSILON);
+
+    let since = |age_ms: i64| {
+        curve
+            .iter()
+            .rev()
+            .find(|p| p.at <= last.at - age_ms)
+            .and_then(|p| change(p.equity, last.equity))
+    };
+    stats.return_1d = since(DAY_MS);
+    stats.return_7d = since(7 * DAY_MS);
+
+    let returns = daily_returns(curve);
+    if returns.len() >= 2 {
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let volatility = variance.sqrt();
+        stats.daily_volatility = Some(volatility);
+        stats.sharpe = (volatility > 0.0).then(|| mean / volatility * 365f64.sqrt());
+    }
+    Some(stats)
+}
+
+/// The equity curve of a running bot.
+pub struct EquityTracker {
+    api: PolymarketApi,
+    wallet: String,
+    marks: Arc<Marks>,
+    portfolio: Arc<Portfolio>,
+    paper: Option<Arc<PaperAccount>>,
+    resolutions: Option<Arc<Resolutions>>,
+    storage: Option<Arc<dyn Storage>>,
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+    /// Oldest first
+    curve: Mutex<Vec<EquityPoint>>,
+}
+
+impl EquityTracker {
+    /// Cash is read from `paper` when paper trading, else the wallet.
+    pub fn from_config(
+        config: &Config,
+        api: PolymarketApi,
+        marks: Arc<Marks>,
+        portfolio: Arc<Portfolio>,
+        paper: Option<Arc<PaperAccount>>,
+        storage: Option<Arc<dyn Storage>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            api,
+            wallet: config.your_wallet.clone(),
+            marks,
+            portfolio,
+            paper,
+            resolutions: None,
+            storage,
+            clock,
+            interval: config.equity_interval,
+            curve: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Counts won shares awaiting redemption at their payout.
+    pub fn with_resolutions(mut self, resolutions: Arc<Resolutions>) -> Self {
+        self.resolutions = Some(resolutions);
+        self
+    }
+
+    /// Picks up the curve a previous run left in the journal, if any.
+    pub async fn load(&self) -> Result<()> {
+        let Some(storage) = &self.storage else { return Ok(()) };
+        if let Some(json) = storage.load_state(EQUITY_STATE_KEY).await? {
+            let curve = serde_json::from_str(&json).context("Stored equity curve is corrupt")?;
+            *self.curve.lock().unwrap() = curve;
+        }
+        Ok(())
+    }
+
+    pub fn curve(&self) -> Vec<EquityPoint> {
+        self.curve.lock().unwrap().clone()
+    }
+
+    pub fn stats(&self) -> Option<EquityStats> {
+        stats(&self.curve.lock().unwrap())
+    }
+
+    /// Appends the account's current equity to the curve.
+    pub async fn record(&self) -> Result<EquityPoint> {
+        let cash = match &self.paper {
+            Some(paper) => paper.cash(),
+            None => self.api.get_balance(&self.wallet).await?,
+        };
+        let mut holdings_value = 0.0;
+        for holding in self.portfolio.holdings() {
+            holdings_value += match self.marks.mark(&holding.market_id).await {
+                Some(mark) => holding.shares() * mark.price,
+                None => holding.cost(),
+            };
+        }
+        let unredeemed: f64 = self
+            .resolutions
+            .iter()
+            .flat_map(|r| r.resolved())
+            .filter(|p| p.won() && matches!(p.redemption, Redemption::Pending { .. }))
+            .map(|p| p.shares * p.payout_per_share)
+            .sum();
+        let point = EquityPoint {
+            at: self.clock.now_ms(),
+            cash,
+            equity: cash + holdings_value + unredeemed,
+        };
+        let json = {
+            let mut curve = self.curve.lock().unwrap();
+            curve.push(point);
+            if curve.len() > MAX_CURVE_POINTS {
+                let excess = curve.len() - MAX_CURVE_POINTS;
+                curve.drain(..excess);
+            }
+            serde_json::to_string(&*curve)?
+        };
+        if let Some(storage) = &self.storage {
+            storage.save_state(EQUITY_STATE_KEY, &json, point.at).await?;
+        }
+        Ok(point)
+    }
+
+    /// Records every `equity_interval`; a zero interval disables it.
+    pub fn spawn(self: Arc<Self>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.record().await {
+                    tracing::warn!("Failed to record equity: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drawdown_returns_and_sharpe() {
+        let point = |hours: i64, equity: f64| EquityPoint {
+            at: hours * 3_600_000,
+            cash: 0.0,
+            equity,
+        };
+        assert_eq!(stats(&[]), None);
+
+        // Daily closes 100, 110, 99, 104.5 with an intraday high of 120
+        let curve = [
+            point(0, 100.0),
+            point(30, 120.0),
+            point(40, 110.0),
+            point(60, 99.0),
+            point(80, 104.5),
+        ];
+        assert_eq!(daily_returns(&curve).len(), 3);
+        let s = stats(&curve).unwrap();
+        assert_eq!(s.equity, 104.5);
+        assert_eq!(s.peak, 120.0);
+        assert!((s.drawdown - 15.5).abs() < 1e-9);
+        assert!((s.max_drawdown - 21.0).abs() < 1e-9);
+        assert!((s.max_drawdown_pct - 0.175).abs() < 1e-9);
+        // A day before hour 80 the curve last stood at 110, at hour 40
+        assert!((s.return_1d.unwrap() + 0.05).abs() < 1e-9);
+        assert_eq!(s.return_7d, None);
+
+        let returns = [0.1, -0.1, 0.055_555_555_555_555_6];
+        let mean = returns.iter().sum::<f64>() / 3.0;
+        let sd = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / 2.0).sqrt();
+        assert!((s.daily_volatility.unwrap() - sd).abs() < 1e-9);
+        assert!((s.sharpe.unwrap() - mean / sd * 365f64.sqrt()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,403 @@
+//! Everything the bot sees and does, as typed [`BotEvent`]s.
+//!
+//! Events go through an [`EventBus`]: applications embedding the crate
+//! subscribe to the kinds they care about, and with `event_log` set every
+//! event is also appended to a JSONL file. Besides trades, decisions and
+//! orders the log records the market data and balances each decision was
+//! based on, so `--replay` can re-drive the decision engine offline without
+//! touching the network.
+//!
+//! ```no_run
+//! # use polymarket_copy_bot::builder::BotBuilder;
+//! # use polymarket_copy_bot::events::{BotEvent, EventKind};
+//! # async fn run() -> anyhow::Result<()> {
+//! let bot = BotBuilder::new().build().await?;
+//! let mut fills = bot.events().subscribe(&[EventKind::OrderResult]);
+//! tokio::spawn(async move {
+//!     while let Some(event) = fills.recv().await {
+//!         if let BotEvent::OrderResult { response: Some(response), .. } = event {
+//!             println!("filled {} shares", response.filled_shares);
+//!         }
+//!     }
+//! });
+//! bot.run().await
+//! # }
+//! ```
+
+use crate::spikes::MarketAnomaly;
+use crate::arbitrage::Opportunity;
+use crate::types::{Decision, Market, OrderRequest, OrderResponse, Trade};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+}
+
+/// What a [`BotEvent`] is about, for subscribing to some of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    TradeSeen,
+    MarketFetched,
+    BalanceFetched,
+    Decision,
+    OrderSubmitted,
+    /// Fills and failed orders
+    OrderResult,
+    Connection,
+    DailyReset,
+    /// Volume or holder spikes in any market
+    MarketAnomaly,
+    /// Price gaps between Polymarket and Kalshi
+    Arbitrage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotEvent {
+    TradeSeen {
+        trade: Trade,
+    },
+    MarketFetched {
+        market: Market,
+    },
+    BalanceFetched {
+        wallet: String,
+        balance: f64,
+    },
+    Decision {
+        wallet: String,
+        market_id: String,
+        decision: Decision,
+    },
+    OrderSubmitted {
+        order: OrderRequest,
+    },
+    OrderResult {
+        market_id: String,
+        response: Option<OrderResponse>,
+        error: Option<String>,
+    },
+    Connection {
+        wallet: String,
+        state: ConnectionState,
+        detail: Option<String>,
+    },
+    DailyReset,
+    MarketAnomaly {
+        anomaly: MarketAnomaly,
+    },
+    Arbitrage {
+        opportunity: Opportunity,
+    },
+}
+
+impl BotEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            BotEvent::TradeSeen { .. } => EventKind::TradeSeen,
+            BotEvent::MarketFetched { .. } => EventKind::MarketFetched,
+            BotEvent::BalanceFetched { .. } => EventKind::BalanceFetched,
+            BotEvent::Decision { .. } => EventKind::Decision,
+            BotEvent::OrderSubmitted { .. } => EventKind::OrderSubmitted,
+            BotEvent::OrderResult { .. } => EventKind::OrderResult,
+            BotEvent::Connection { .. } => EventKind::Connection,
+            BotEvent::DailyReset => EventKind::DailyReset,
+            BotEvent::MarketAnomaly { .. } => EventKind::MarketAnomaly,
+            BotEvent::Arbitrage { .. } => EventKind::Arbitrage,
+        }
+    }
+}
+
+/// One line of the log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub seq: u64,
+    pub at_ms: i64,
+    #[serde(flatten)]
+    pub event: BotEvent,
+}
+
+/// Appends events to a JSONL file, one flushed line per event.
+pub struct EventLog {
+    inner: Mutex<Writer>,
+}
+
+struct Writer {
+    next_seq: u64,
+    out: BufWriter<File>,
+}
+
+impl EventLog {
+    /// Opens `path` for appending, continuing the sequence of any existing log.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+        }
+
+        let next_seq = if path.exists() {
+            drop_torn_tail(path)?;
+            read_events(path)?.last().map(|r| r.seq + 1).unwrap_or(0)
+        } else {
+            0
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open event log {}", path.display()))?;
+
+        Ok(Self {
+            inner: Mutex::new(Writer {
+                next_seq,
+                out: BufWriter::new(file),
+            }),
+        })
+    }
+
+    /// Logging never blocks trading; write failures are only warned about.
+    pub fn append(&self, event: BotEvent) {
+        if let Err(e) = self.try_append(event) {
+            tracing::warn!("Failed to write event log: {}", e);
+        }
+    }
+
+    fn try_append(&self, event: BotEvent) -> Result<()> {
+        let mut writer = self.inner.lock().unwrap();
+        let record = EventRecord {
+            seq: writer.next_seq,
+            at_ms: chrono::Utc::now().timestamp_millis(),
+            event,
+        };
+        serde_json::to_writer(&mut writer.out, &record)?;
+        writer.out.write_all(b"\n")?;
+        writer.out.flush()?;
+        writer.next_seq += 1;
+        Ok(())
+    }
+}
+
+/// Events a subscriber may fall behind by before it misses some.
+pub const BUS_CAPACITY: usize = 1024;
+
+/// Hands every event to subscribers and, if set, the event log.
+pub struct EventBus {
+    tx: broadcast::Sender<Arc<BotEvent>>,
+    log: Option<EventLog>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(BUS_CAPACITY).0,
+            log: None,
+        }
+    }
+
+    /// Also appends every event to `log`.
+    pub fn with_log(mut self, log: EventLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Receives events of the given kinds from now on; all of them if
+    /// `kinds` is empty.
+    pub fn subscribe(&self, kinds: &[EventKind]) -> Subscription {
+        Subscription {
+            rx: self.tx.subscribe(),
+            kinds: kinds.iter().copied().collect(),
+            missed: 0,
+        }
+    }
+
+    pub fn publish(&self, event: BotEvent) {
+        if self.tx.receiver_count() > 0 {
+            let _ = self.tx.send(Arc::new(event.clone()));
+        }
+        if let Some(log) = &self.log {
+            log.append(event);
+        }
+    }
+}
+
+/// A subscriber's handle on the [`EventBus`]; dropping it unsubscribes.
+pub struct Subscription {
+    rx: broadcast::Receiver<Arc<BotEvent>>,
+    kinds: HashSet<EventKind>,
+    missed: u64,
+}
+
+impl Subscription {
+    /// The next matching event; `None` once the bot is gone. A subscriber
+    /// more than [`BUS_CAPACITY`] events behind skips ahead, see
+    /// [`missed`](Self::missed).
+    pub async fn recv(&mut self) -> Option<BotEvent> {
+        loop {
+            match self.rx.recv().await {
+                Ok(event) if self.wants(&event) => return Some((*event).clone()),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => self.missed += n,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Like [`recv`](Self::recv) without waiting; `None` if nothing matching
+    /// is queued.
+    pub fn try_recv(&mut self) -> Option<BotEvent> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(event) if self.wants(&event) => return Some((*event).clone()),
+                Ok(_) => {}
+                Err(broadcast::error::TryRecvError::Lagged(n)) => self.missed += n,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Events (of any kind) dropped because this subscriber fell behind.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    fn wants(&self, event: &BotEvent) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&event.kind())
+    }
+}
+
+/// Cuts off a partially written last line so new events start on a fresh one.
+fn drop_torn_tail(path: &Path) -> Result<()> {
+    let bytes = std::fs::read(path)?;
+    if bytes.last().is_none_or(|b| *b == b'\n') {
+        return Ok(());
+    }
+    let keep = bytes.iter().rposition(|b| *b == b'\n').map(|i| i + 1).unwrap_or(0);
+    tracing::warn!("Dropping torn last event in {}", path.display());
+    OpenOptions::new().write(true).open(path)?.set_len(keep as u64)?;
+    Ok(())
+}
+
+/// Reads a log back. A torn final line (crash mid-write) is dropped with a
+/// warning; corruption anywhere else is an error.
+pub fn read_events(path: impl AsRef<Path>) -> Result<Vec<EventRecord>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Failed to open event log {}", path.display()))?;
+    let lines: Vec<String> = BufReader::new(file).lines().collect::<std::io::Result<_>>()?;
+
+    let mut records = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<EventRecord>(line) {
+            Ok(record) => records.push(record),
+            Err(e) if i + 1 == lines.len() => {
+                tracing::warn!("Ignoring torn last line of {}: {}", path.display(), e);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("{}:{}: invalid event", path.display(), i + 1));
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SkipReason, TradeSide};
+
+    #[test]
+    fn test_log_roundtrip_and_resume() {
+        let dir = std::env::temp_dir().join(format!("events-test-{}", std::process::id()));
+        let path = dir.join("events.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        let trade = Trade {
+            wallet: "0xwhale".to_string(),
+            event_id: "event1".to_string(),
+            market_id: "market1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.5,
+            timestamp: 1_700_000_000,
+            tx_hash: None,
+        };
+        {
+            let log = EventLog::open(&path).unwrap();
+            log.append(BotEvent::TradeSeen { trade });
+        }
+        {
+            let log = EventLog::open(&path).unwrap();
+            log.append(BotEvent::Decision {
+                wallet: "0xwhale".to_string(),
+                market_id: "market1".to_string(),
+                decision: Decision::skip(SkipReason::Stale, "too old"),
+            });
+        }
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"seq\":2,\"at_")
+            .unwrap();
+
+        assert_eq!(read_events(&path).unwrap().len(), 2);
+
+        EventLog::open(&path).unwrap().append(BotEvent::DailyReset);
+        let records = read_events(&path).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].seq, 2);
+        assert!(matches!(records[0].event, BotEvent::TradeSeen { .. }));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_filter_by_kind() {
+        let bus = EventBus::new();
+        bus.publish(BotEvent::DailyReset);
+
+        let mut everything = bus.subscribe(&[]);
+        let mut connections = bus.subscribe(&[EventKind::Connection]);
+        bus.publish(BotEvent::DailyReset);
+        bus.publish(BotEvent::Connection {
+            wallet: "0xwhale".to_string(),
+            state: ConnectionState::Connected,
+            detail: None,
+        });
+
+        assert!(matches!(everything.recv().await, Some(BotEvent::DailyReset)));
+        assert!(matches!(everything.recv().await, Some(BotEvent::Connection { .. })));
+        assert!(matches!(connections.try_recv(), Some(BotEvent::Connection { .. })));
+        assert!(connections.try_recv().is_none());
+
+        for _ in 0..BUS_CAPACITY + 5 {
+            bus.publish(BotEvent::DailyReset);
+        }
+        assert!(everything.try_recv().is_some());
+        assert_eq!(everything.missed(), 5);
+
+        drop(bus);
+        assert!(connections.recv().await.is_none());
+    }
+}
@@ -0,0 +1,575 @@
+//! Kalshi as an [`Exchange`].
+//!
+//! Every request is signed: `KALSHI-ACCESS-SIGNATURE` is the RSA-PSS
+//! (SHA-256) signature of the millisecond timestamp, the method and the
+//! path, made with the private key registered under `kalshi_key_id`.
+//!
+//! A Kalshi market is a yes/no contract identified by its ticker; the bot
+//! trades its sides as two tokens, `TICKER:yes` and `TICKER:no` (see
+//! [`token_id`]), priced in dollars like Polymarket's. Kalshi quotes in
+//! cents and only takes whole contracts, so order sizes are rounded down
+//! and market orders become limit orders at the worst price that fill
+//! what they can at once.
+//!
+//! Kalshi doesn't publish who traded, so [`Exchange::trades`] only
+//! returns the authenticated account's own fills, whatever the account.
+
+use super::Exchange;
+use crate::types::{
+    Config, ExchangeOrder, Market, OrderBook, OrderRequest, OrderResponse, OrderType, TokenBalance, Trade, TradeSide,
+    Venue,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use reqwest::{Client, Method};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PSS_SHA256};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Markets listed per page, Kalshi's maximum.
+const PAGE_SIZE: usize = 1000;
+
+/// Pages of open markets a search reads through, Kalshi having no text
+/// search of its own.
+const SEARCH_PAGES: usize = 5;
+
+/// Which side of a Kalshi market a token is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Yes,
+    No,
+}
+
+impl Side {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Yes => "yes",
+            Side::No => "no",
+        }
+    }
+}
+
+/// The token trading `side` of the market `ticker`.
+pub fn token_id(ticker: &str, side: Side) -> String {
+    format!("{}:{}", ticker, side.as_str())
+}
+
+/// The ticker and side of a token; a bare ticker is its yes side.
+pub fn parse_token(token: &str) -> (&str, Side) {
+    match token.rsplit_once(':') {
+        Some((ticker, "no")) => (ticker, Side::No),
+        Some((ticker, "yes")) => (ticker, Side::Yes),
+        _ => (token, Side::Yes),
+    }
+}
+
+pub struct KalshiApi {
+    client: Client,
+    base_url: String,
+    key_id: String,
+    key: Option<Arc<RsaKeyPair>>,
+}
+
+impl KalshiApi {
+    /// Without a key only public market data can be read.
+    pub fn new(base_url: String, key_id: String, key: Option<RsaKeyPair>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            key_id,
+            key: key.map(Arc::new),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let key = match config.kalshi_private_key.as_str() {
+            "" => None,
+            pem => Some(read_key(pem).context("Invalid KALSHI_PRIVATE_KEY")?),
+        };
+        if key.is_some() && config.kalshi_key_id.is_empty() {
+            anyhow::bail!("KALSHI_KEY_ID not set");
+        }
+        Ok(Self::new(config.kalshi_api.clone(), config.kalshi_key_id.clone(), key))
+    }
+
+    /// The path Kalshi signs: the base URL's path plus `path`, no query.
+    fn signed_path(&self, path: &str) -> String {
+        let base_path = url::Url::parse(&self.base_url)
+            .map(|u| u.path().trim_end_matches('/').to_string())
+            .unwrap_or_default();
+        format!("{}{}", base_path, path)
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<reqwest::RequestBuilder> {
+        let builder = self.client.request(method.clone(), format!("{}{}", self.base_url, path));
+        let Some(key) = &self.key else { return Ok(builder) };
+        let timestamp = chrono::Utc::now().timestamp_millis().to_string();
+        let message = signing_message(&timestamp, method.as_str(), &self.signed_path(path));
+        let mut signature = vec![0; key.public().modulus_len()];
+        key.sign(&RSA_PSS_SHA256, &SystemRandom::new(), message.as_bytes(), &mut signature)
+            .map_err(|_| anyhow::anyhow!("Failed to sign the Kalshi request"))?;
+        Ok(builder
+            .header("KALSHI-ACCESS-KEY", &self.key_id)
+            .header("KALSHI-ACCESS-TIMESTAMP", timestamp)
+            .header("KALSHI-ACCESS-SIGNATURE", STANDARD.encode(signature)))
+    }
+
+    async fn send(&self, builder: reqwest::RequestBuilder, what: &str) -> Result<Value> {
+        let resp = builder.send().await.with_context(|| format!("Failed to {}", what))?;
+        let status = resp.status();
+        let body: Value = resp.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let message = body["error"]["message"].as_str().unwrap_or_else(|| status.as_str());
+            anyhow::bail!("Kalshi refused to {}: {}", what, message);
+        }
+        Ok(body)
+    }
+
+    async fn get(&self, path: &str, query: &[(&str, String)], what: &str) -> Result<Value> {
+        self.send(self.request(Method::GET, path)?.query(query), what).await
+    }
+
+    fn require_key(&self) -> Result<()> {
+        if self.key.is_none() {
+            anyhow::bail!("KALSHI_PRIVATE_KEY not set");
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Exchange for KalshiApi {
+    fn venue(&self) -> Venue {
+        Venue::Kalshi
+    }
+
+    async fn trades(&self, account: &str, since: i64) -> Result<Vec<Trade>> {
+        self.require_key()?;
+        let resp = self
+            .get("/portfolio/fills", &[("min_ts", since.to_string()), ("limit", "1000".to_string())], "fetch fills")
+            .await?;
+        let mut trades: Vec<Trade> = resp["fills"]
+            .as_array()
+            .map(|fills| fills.iter().filter_map(|f| parse_fill(account, f)).collect())
+            .unwrap_or_default();
+        trades.sort_by_key(|t| t.timestamp);
+        Ok(trades)
+    }
+
+    async fn market(&self, market_id: &str) -> Result<Market> {
+        let (ticker, _) = parse_token(market_id);
+        let resp = self.get(&format!("/markets/{}", ticker), &[], "fetch market").await?;
+        Ok(parse_market(&resp["market"]))
+    }
+
+    async fn markets(&self, query: &str, limit: usize) -> Result<Vec<Market>> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut found = Vec::new();
+        let mut cursor = String::new();
+        for _ in 0..SEARCH_PAGES {
+            let resp = self
+                .get(
+                    "/markets",
+                    &[("status", "open".to_string()), ("limit", PAGE_SIZE.to_string()), ("cursor", cursor.clone())],
+                    "list markets",
+                )
+                .await?;
+            for item in resp["markets"].as_array().into_iter().flatten() {
+                let market = parse_market(item);
+                let text = format!("{} {} {}", market.question, market.id, market.event_id).to_lowercase();
+                if words.iter().all(|w| text.contains(w.as_str())) {
+                    found.push(market);
+                    if found.len() >= limit {
+                        return Ok(found);
+                    }
+                }
+            }
+            match resp["cursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = next.to_string(),
+                _ => break,
+            }
+        }
+        Ok(found)
+    }
+
+    async fn orderbook(&self, market_id: &str) -> Result<OrderBook> {
+        let (ticker, side) = parse_token(market_id);
+        let resp = self.get(&format!("/markets/{}/orderbook", ticker), &[], "fetch orderbook").await?;
+        Ok(parse_orderbook(&resp["orderbook"], side))
+    }
+
+    async fn place_order(&self, order: &OrderRequest) -> Result<OrderResponse> {
+        self.require_key()?;
+        let body = order_body(order)?;
+        let resp = self
+            .send(self.request(Method::POST, "/portfolio/orders")?.json(&body), "place order")
+            .await?;
+        let placed = parse_order(&resp["order"]);
+        Ok(OrderResponse {
+            order_id: placed.order_id,
+            status: placed.status,
+            filled_shares: placed.filled_shares,
+            avg_fill_price: placed.avg_fill_price,
+        })
+    }
+
+    async fn cancel_order(&self, order_id: &str) -> Result<()> {
+        self.require_key()?;
+        let path = format!("/portfolio/orders/{}", order_id);
+        self.send(self.request(Method::DELETE, &path)?, "cancel order").await?;
+        Ok(())
+    }
+
+    async fn open_orders(&self) -> Result<Vec<ExchangeOrder>> {
+        self.require_key()?;
+        let resp = self
+            .get("/portfolio/orders", &[("status", "resting".to_string())], "fetch open orders")
+            .await?;
+        Ok(resp["orders"].as_array().into_iter().flatten().map(parse_order).collect())
+    }
+
+    async fn order(&self, order_id: &str) -> Result<ExchangeOrder> {
+        self.require_key()?;
+        let resp = self.get(&format!("/portfolio/orders/{}", order_id), &[], "fetch order").await?;
+        Ok(parse_order(&resp["order"]))
+    }
+
+    /// Kalshi can't filter by client id, so the latest orders are searched.
+    async fn order_by_client_id(&self, client_order_id: &str) -> Result<Option<ExchangeOrder>> {
+        self.require_key()?;
+        let resp = self
+            .get("/portfolio/orders", &[("limit", PAGE_SIZE.to_string())], "look up order")
+            .await?;
+        Ok(resp["orders"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(parse_order)
+            .find(|o| o.client_order_id.as_deref() == Some(client_order_id)))
+    }
+
+    async fn positions(&self) -> Result<Vec<TokenBalance>> {
+        self.require_key()?;
+        let resp = self.get("/portfolio/positions", &[], "fetch positions").await?;
+        Ok(resp["market_positions"].as_array().into_iter().flatten().filter_map(parse_position).collect())
+    }
+
+    async fn balance(&self) -> Result<f64> {
+        self.require_key()?;
+        let resp = self.get("/portfolio/balance", &[], "fetch balance").await?;
+        Ok(resp["balance"].as_f64().unwrap_or(0.0) / 100.0)
+    }
+
+    async fn verify_credentials(&self) -> Result<()> {
+        self.balance().await.map(|_| ())
+    }
+}
+
+/// An RSA private key as PEM (PKCS#1 or PKCS#8), or the path of a PEM file.
+fn read_key(pem_or_path: &str) -> Result<RsaKeyPair> {
+    let pem = if pem_or_path.contains("-----BEGIN") {
+        pem_or_path.to_string()
+    } else {
+        std::fs::read_to_string(pem_or_path).with_context(|| format!("Failed to read {}", pem_or_path))?
+    };
+    let body: String = pem
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with("-----"))
+        .collect();
+    let der = STANDARD.decode(body).context("Key is not valid PEM")?;
+    if pem.contains("BEGIN RSA PRIVATE KEY") {
+        RsaKeyPair::from_der(&der)
+    } else {
+        RsaKeyPair::from_pkcs8(&der)
+    }
+    .map_err(|e| anyhow::anyhow!("Key rejected: {}", e))
+}
+
+/// What a request's signature covers.
+fn signing_message(timestamp_ms: &str, method: &str, path: &str) -> String {
+    format!("{}{}{}", timestamp_ms, method, path)
+}
+
+/// Dollars from a field quoted in cents, or its `_dollars` string twin.
+fn dollars(item: &Value, field: &str) -> Option<f64> {
+    item[field]
+        .as_f64()
+        .map(|cents| cents / 100.0)
+        .or_else(|| item[format!("{}_dollars", field)].as_str()?.parse().ok())
+}
+
+fn parse_market(item: &Value) -> Market {
+    let ticker = item["ticker"].as_str().unwrap_or("").to_string();
+    let bid = dollars(item, "yes_bid").filter(|p| *p > 0.0);
+    let ask = dollars(item, "yes_ask").filter(|p| *p > 0.0 && *p < 1.0);
+    let yes_price = match (bid, ask) {
+        (Some(bid), Some(ask)) => (bid + ask) / 2.0,
+        _ => dollars(item, "last_price").unwrap_or(0.5),
+    };
+    let question = match item["yes_sub_title"].as_str().filter(|s| !s.is_empty()) {
+        Some(sub) if !item["title"].as_str().unwrap_or("").contains(sub) => {
+            format!("{} ({})", item["title"].as_str().unwrap_or(""), sub)
+        }
+        _ => item["title"].as_str().unwrap_or("").to_string(),
+    };
+    Market {
+        id: ticker.clone(),
+        event_id: item["event_ticker"].as_str().unwrap_or("").to_string(),
+        question,
+        yes_price,
+        no_price: 1.0 - yes_price,
+        liquidity: dollars(item, "liquidity").unwrap_or(0.0),
+        volume_24h: item["volume_24h"].as_f64().unwrap_or(0.0),
+        slug: ticker.to_lowercase(),
+        outcomes: vec!["Yes".to_string(), "No".to_string()],
+        token_ids: vec![token_id(&ticker, Side::Yes), token_id(&ticker, Side::No)],
+        tick_size: item["tick_size"].as_f64().map(|c| c / 100.0).unwrap_or(0.01),
+        end_date: crate::api::unix_seconds(&item["close_time"]),
+        category: item["category"].as_str().unwrap_or("").to_string(),
+        resolution_source: item["rules_primary"].as_str().unwrap_or("").to_string(),
+    }
+}
+
+/// Kalshi lists bids on yes and on no; a no bid at p is a yes ask at 1 - p.
+fn parse_orderbook(book: &Value, side: Side) -> OrderBook {
+    let levels = |field: &str| -> Vec<(f64, f64)> {
+        book[field]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|level| Some((level[0].as_f64()? / 100.0, level[1].as_f64()?)))
+            .collect()
+    };
+    let (own, other) = match side {
+        Side::Yes => (levels("yes"), levels("no")),
+        Side::No => (levels("no"), levels("yes")),
+    };
+    let asks = other.into_iter().map(|(price, size)| (1.0 - price, size)).collect();
+    OrderBook::new(own, asks)
+}
+
+fn order_body(order: &OrderRequest) -> Result<Value> {
+    let (ticker, side) = parse_token(&order.market_id);
+    let count = order.shares.floor();
+    if count < 1.0 {
+        anyhow::bail!("Kalshi only trades whole contracts, not {:.2}", order.shares);
+    }
+    let price = match order.price {
+        Some(price) => price,
+        None => match order.side {
+            TradeSide::BUY => 0.99,
+            TradeSide::SELL => 0.01,
+        },
+    };
+    let cents = ((price * 100.0).round() as i64).clamp(1, 99);
+    let mut body = json!({
+        "ticker": ticker,
+        "action": match order.side {
+            TradeSide::BUY => "buy",
+            TradeSide::SELL => "sell",
+        },
+        "side": side.as_str(),
+        "count": count as i64,
+        "type": "limit",
+        "client_order_id": order.client_order_id,
+    });
+    body[format!("{}_price", side.as_str())] = json!(cents);
+    if matches!(order.order_type, OrderType::FAK | OrderType::MARKET) || order.price.is_none() {
+        body["time_in_force"] = json!("immediate_or_cancel");
+    }
+    Ok(body)
+}
+
+/// Kalshi's order statuses in the bot's terms: "filled", "partially_filled",
+/// "open" or "cancelled".
+fn order_status(status: &str, filled: f64, remaining: f64) -> &'static str {
+    match status {
+        "executed" => "filled",
+        _ if filled > 0.0 && (status == "canceled" || remaining > 0.0) => "partially_filled",
+        "canceled" => "cancelled",
+        _ => "open",
+    }
+}
+
+fn parse_order(item: &Value) -> ExchangeOrder {
+    let (side, price_field) = match item["side"].as_str() {
+        Some("no") => (Side::No, "no_price"),
+        _ => (Side::Yes, "yes_price"),
+    };
+    let ticker = item["ticker"].as_str().unwrap_or("");
+    let remaining = item["remaining_count"].as_f64().unwrap_or(0.0);
+    let status = item["status"].as_str().unwrap_or("");
+    let filled = item["fill_count"].as_f64().unwrap_or(match status {
+        "executed" => item["initial_count"].as_f64().unwrap_or(0.0) - remaining,
+        _ => 0.0,
+    });
+    let cost_cents = item["taker_fill_cost"].as_f64().unwrap_or(0.0) + item["maker_fill_cost"].as_f64().unwrap_or(0.0);
+    let avg_fill_price = if filled > 0.0 && cost_cents > 0.0 {
+        cost_cents / filled / 100.0
+    } else {
+        dollars(item, price_field).unwrap_or(0.0)
+    };
+    ExchangeOrder {
+        order_id: item["order_id"].as_str().unwrap_or("").to_string(),
+        market_id: token_id(ticker, side),
+        side: match item["action"].as_str() {
+            Some("sell") => TradeSide::SELL,
+            _ => TradeSide::BUY,
+        },
+        shares: item["initial_count"].as_f64().unwrap_or(filled + remaining),
+        filled_shares: filled,
+        avg_fill_price,
+        status: order_status(status, filled, remaining).to_string(),
+        client_order_id: item["client_order_id"].as_str().map(|s| s.to_string()),
+    }
+}
+
+/// A positive position holds yes contracts, a negative one no; either is
+/// keyed by its token, as orders and fills are.
+fn parse_position(item: &Value) -> Option<TokenBalance> {
+    let ticker = item["ticker"].as_str()?;
+    let position = item["position"].as_f64()?;
+    if position == 0.0 {
+        return None;
+    }
+    let side = if position > 0.0 { Side::Yes } else { Side::No };
+    let shares = position.abs();
+    let token = token_id(ticker, side);
+    Some(TokenBalance {
+        market_id: token.clone(),
+        token_id: Some(token),
+        shares,
+        avg_price: dollars(item, "market_exposure").map(|cost| cost / shares),
+    })
+}
+
+fn parse_fill(account: &str, item: &Value) -> Option<Trade> {
+    let ticker = item["ticker"].as_str()?;
+    let (side, price_field) = match item["side"].as_str()? {
+        "no" => (Side::No, "no_price"),
+        _ => (Side::Yes, "yes_price"),
+    };
+    let timestamp = crate::api::unix_seconds(&item["created_time"]).or_else(|| item["ts"].as_i64())?;
+    Some(Trade {
+        wallet: account.to_string(),
+        event_id: String::new(),
+        market_id: token_id(ticker, side),
+        side: match item["action"].as_str()? {
+            "sell" => TradeSide::SELL,
+            _ => TradeSide::BUY,
+        },
+        shares: item["count"].as_f64()?,
+        price: dollars(item, price_field)?,
+        timestamp,
+        tx_hash: item["trade_id"].as_str().map(|s| s.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_markets_and_books_per_side() {
+        let market = parse_market(&json!({
+            "ticker": "KXBTCD-25DEC31-T100000",
+            "event_ticker": "KXBTCD-25DEC31",
+            "title": "Bitcoin above $100,000 on Dec 31?",
+            "yes_bid": 40,
+            "yes_ask": 44,
+            "liquidity": 250000,
+            "volume_24h": 1200,
+            "close_time": "2025-12-31T22:00:00Z",
+            "tick_size": 1,
+        }));
+        assert_eq!(market.id, "KXBTCD-25DEC31-T100000");
+        assert!((market.yes_price - 0.42).abs() < 1e-9);
+        assert_eq!(market.liquidity, 2500.0);
+        assert_eq!(market.token_ids[1], "KXBTCD-25DEC31-T100000:no");
+        assert_eq!(market.tick_size, 0.01);
+        assert!(market.end_date.is_some());
+
+        let book = json!({ "yes": [[38, 100], [40, 50]], "no": [[55, 20], [56, 10]] });
+        let yes = parse_orderbook(&book, Side::Yes);
+        assert_eq!(yes.bids[0], (0.40, 50.0));
+        assert!((yes.asks[0].0 - 0.44).abs() < 1e-9);
+        let no = parse_orderbook(&book, Side::No);
+        assert_eq!(no.bids[0], (0.56, 10.0));
+        assert!((no.asks[0].0 - 0.60).abs() < 1e-9);
+
+        assert_eq!(parse_token("KXBTCD-25DEC31-T100000:no"), ("KXBTCD-25DEC31-T100000", Side::No));
+        assert_eq!(parse_token("KXBTCD-25DEC31-T100000"), ("KXBTCD-25DEC31-T100000", Side::Yes));
+    }
+
+    #[test]
+    fn test_orders_round_to_contracts_in_cents() {
+        let order = OrderRequest {
+            market_id: "FED-25DEC-T4.00:no".to_string(),
+            side: TradeSide::BUY,
+            shares: 12.7,
+            price: Some(0.374),
+            order_type: OrderType::FAK,
+            client_order_id: "cb-1".to_string(),
+        };
+        let body = order_body(&order).unwrap();
+        assert_eq!(body["ticker"], "FED-25DEC-T4.00");
+        assert_eq!(body["side"], "no");
+        assert_eq!(body["action"], "buy");
+        assert_eq!(body["count"], 12);
+        assert_eq!(body["no_price"], 37);
+        assert_eq!(body["time_in_force"], "immediate_or_cancel");
+
+        let market_sell = OrderRequest { side: TradeSide::SELL, price: None, order_type: OrderType::MARKET, ..order.clone() };
+        assert_eq!(order_body(&market_sell).unwrap()["no_price"], 1);
+        assert!(order_body(&OrderRequest { shares: 0.5, ..order }).is_err());
+
+        let placed = parse_order(&json!({
+            "order_id": "o1",
+            "ticker": "FED-25DEC-T4.00",
+            "side": "no",
+            "action": "buy",
+            "status": "canceled",
+            "initial_count": 12,
+            "fill_count": 5,
+            "remaining_count": 0,
+            "taker_fill_cost": 185,
+            "no_price": 37,
+        }));
+        assert_eq!(placed.status, "partially_filled");
+        assert_eq!(placed.filled_shares, 5.0);
+        assert!((placed.avg_fill_price - 0.37).abs() < 1e-9);
+        assert_eq!(placed.market_id, "FED-25DEC-T4.00:no");
+    }
+
+    #[test]
+    fn test_reads_positions_and_fills() {
+        let short = parse_position(&json!({ "ticker": "T1", "position": -4, "market_exposure": 120 })).unwrap();
+        assert_eq!((short.market_id.as_str(), short.token_id.as_deref()), ("T1:no", Some("T1:no")));
+        assert_eq!(short.shares, 4.0);
+        assert_eq!(short.avg_price, Some(0.3));
+        assert!(parse_position(&json!({ "ticker": "T2", "position": 0 })).is_none());
+
+        let fill = parse_fill(
+            "me",
+            &json!({
+                "trade_id": "f1",
+                "ticker": "T1",
+                "side": "yes",
+                "action": "sell",
+                "count": 3,
+                "yes_price": 61,
+                "created_time": "2025-10-01T12:00:00Z",
+            }),
+        )
+        .unwrap();
+        assert_eq!(fill.market_id, "T1:yes");
+        assert_eq!(fill.side, TradeSide::SELL);
+        assert_eq!(fill.price, 0.61);
+        assert_eq!(fill.timestamp, 1_759_320_000);
+        assert_eq!(signing_message("1700000000000", "GET", "/trade-api/v2/portfolio/balance"),
+            "1700000000000GET/trade-api/v2/portfolio/balance");
+    }
+}
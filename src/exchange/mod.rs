@@ -0,0 +1,80 @@
+//! Trading venues behind one interface.
+//!
+//! An [`Exchange`] reads an account's trades, markets and books, and
+//! places, cancels and lists the account's orders, so the executor and the
+//! risk checks work the same on every venue. [`polymarket`] wraps
+//! [`PolymarketApi`] with the account's credentials; [`kalshi`] talks to
+//! Kalshi's trade API. `exchange` picks the venue copies are placed on;
+//! only Polymarket for now, as leader trades aren't mapped to Kalshi markets
+//! (arbitrage trades on both).
+//!
+//! Market ids are venue-native: Polymarket token ids, and for Kalshi a
+//! market ticker with the side traded (see [`kalshi::token_id`]). Prices
+//! are dollars per share on both.
+
+pub mod kalshi;
+pub mod polymarket;
+
+use crate::api::PolymarketApi;
+use crate::types::{Config, ExchangeOrder, Market, OrderBook, OrderRequest, OrderResponse, TokenBalance, Trade, Venue};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub use kalshi::KalshiApi;
+pub use polymarket::PolymarketExchange;
+
+/// One venue and the account trading on it.
+#[async_trait]
+pub trait Exchange: Send + Sync {
+    fn venue(&self) -> Venue;
+
+    /// Trades `account` made since unix seconds `since`, oldest first.
+    async fn trades(&self, account: &str, since: i64) -> Result<Vec<Trade>>;
+
+    async fn market(&self, market_id: &str) -> Result<Market>;
+
+    /// Markets matching `query`, at most `limit`.
+    async fn markets(&self, query: &str, limit: usize) -> Result<Vec<Market>>;
+
+    async fn orderbook(&self, market_id: &str) -> Result<OrderBook>;
+
+    async fn place_order(&self, order: &OrderRequest) -> Result<OrderResponse>;
+
+    async fn cancel_order(&self, order_id: &str) -> Result<()>;
+
+    /// The account's resting orders.
+    async fn open_orders(&self) -> Result<Vec<ExchangeOrder>>;
+
+    /// One of the account's orders, resting or not.
+    async fn order(&self, order_id: &str) -> Result<ExchangeOrder>;
+
+    /// The order sent with `client_order_id`, if the venue ever received it.
+    async fn order_by_client_id(&self, client_order_id: &str) -> Result<Option<ExchangeOrder>>;
+
+    /// The account's holdings as the venue sees them.
+    async fn positions(&self) -> Result<Vec<TokenBalance>>;
+
+    /// The account's cash, in dollars.
+    async fn balance(&self) -> Result<f64>;
+
+    /// Checks that the venue accepts the account's credentials.
+    async fn verify_credentials(&self) -> Result<()>;
+}
+
+/// The venue a market id trades on: Kalshi's end in the side traded,
+/// Polymarket's token ids never do.
+pub fn venue_of(market_id: &str) -> Venue {
+    match market_id.rsplit_once(':') {
+        Some((_, "yes" | "no")) => Venue::Kalshi,
+        _ => Venue::Polymarket,
+    }
+}
+
+/// The venue `exchange` names, trading as the configured account.
+pub fn from_config(config: &Config, api: PolymarketApi) -> Result<Arc<dyn Exchange>> {
+    Ok(match config.exchange {
+        Venue::Polymarket => Arc::new(PolymarketExchange::from_config(config, api)),
+        Venue::Kalshi => Arc::new(KalshiApi::from_config(config)?),
+    })
+}
@@ -0,0 +1,87 @@
+//! Polymarket as an [`Exchange`]: [`PolymarketApi`] with the account's
+//! wallet and the key orders are authorized with.
+
+use super::Exchange;
+use crate::api::PolymarketApi;
+use crate::types::{Config, ExchangeOrder, Market, OrderBook, OrderRequest, OrderResponse, TokenBalance, Trade, Venue};
+use anyhow::Result;
+use async_trait::async_trait;
+
+#[derive(Clone)]
+pub struct PolymarketExchange {
+    api: PolymarketApi,
+    wallet: String,
+    api_key: String,
+}
+
+impl PolymarketExchange {
+    pub fn new(api: PolymarketApi, wallet: String, api_key: String) -> Self {
+        Self { api, wallet, api_key }
+    }
+
+    pub fn from_config(config: &Config, api: PolymarketApi) -> Self {
+        Self::new(api, config.your_wallet.clone(), config.private_key.clone())
+    }
+
+    pub fn api(&self) -> &PolymarketApi {
+        &self.api
+    }
+}
+
+#[async_trait]
+impl Exchange for PolymarketExchange {
+    fn venue(&self) -> Venue {
+        Venue::Polymarket
+    }
+
+    async fn trades(&self, account: &str, since: i64) -> Result<Vec<Trade>> {
+        let mut trades = self.api.get_trades(account, since).await?;
+        trades.sort_by_key(|t| t.timestamp);
+        Ok(trades)
+    }
+
+    async fn market(&self, market_id: &str) -> Result<Market> {
+        self.api.get_market(market_id).await
+    }
+
+    async fn markets(&self, query: &str, limit: usize) -> Result<Vec<Market>> {
+        self.api.search_markets(query, limit).await
+    }
+
+    async fn orderbook(&self, market_id: &str) -> Result<OrderBook> {
+        let (bids, asks) = self.api.get_orderbook(market_id).await?;
+        Ok(OrderBook::new(bids, asks))
+    }
+
+    async fn place_order(&self, order: &OrderRequest) -> Result<OrderResponse> {
+        self.api.place_order(order.clone(), &self.api_key).await
+    }
+
+    async fn cancel_order(&self, order_id: &str) -> Result<()> {
+        self.api.cancel_order(order_id, &self.api_key).await
+    }
+
+    async fn open_orders(&self) -> Result<Vec<ExchangeOrder>> {
+        self.api.get_open_orders(&self.wallet).await
+    }
+
+    async fn order(&self, order_id: &str) -> Result<ExchangeOrder> {
+        self.api.get_order(order_id).await
+    }
+
+    async fn order_by_client_id(&self, client_order_id: &str) -> Result<Option<ExchangeOrder>> {
+        self.api.get_order_by_client_id(client_order_id).await
+    }
+
+    async fn positions(&self) -> Result<Vec<TokenBalance>> {
+        self.api.get_positions(&self.wallet).await
+    }
+
+    async fn balance(&self) -> Result<f64> {
+        self.api.get_balance(&self.wallet).await
+    }
+
+    async fn verify_credentials(&self) -> Result<()> {
+        self.api.verify_credentials(&self.wallet, &self.api_key).await
+    }
+}
@@ -1,33 +1,106 @@
 use crate::api::PolymarketApi;
-use crate::types::{Config, Trade, TradeSide, OrderRequest, OrderType, OrderResponse};
+use crate::clock::{self, Clock};
+use crate::exchange::{Exchange, PolymarketExchange};
+use crate::fills::{self, FillModel, LiveTape, SimOrder};
+use crate::types::{Config, ExchangeOrder, Trade, TradeSide, OrderRequest, OrderType, OrderResponse};
+use rand::Rng;
 use anyhow::Result;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct TradeExecutor {
-    api: PolymarketApi,
+    /// Where orders are placed, cancelled and priced
+    exchange: Arc<dyn Exchange>,
     config: Config,
+    /// How orders fill when paper trading
+    fills: Arc<dyn FillModel>,
+    clock: Arc<dyn Clock>,
 }
 
 impl TradeExecutor {
     pub fn new(api: PolymarketApi, config: Config) -> Self {
-        Self { api, config }
+        let tape = Arc::new(LiveTape::new(api.clone()));
+        let fills = fills::model(config.fill_model, config.fill_latency, tape);
+        let exchange = Arc::new(PolymarketExchange::from_config(&config, api));
+        Self { exchange, config, fills, clock: clock::system() }
     }
     
-    pub async fn execute_trade(&self, trade: &Trade, shares: f64) -> Result<OrderResponse> {
+    /// Places orders on `exchange` rather than Polymarket.
+    pub fn with_exchange(mut self, exchange: Arc<dyn Exchange>) -> Self {
+        self.exchange = exchange;
+        self
+    }
+    
+    pub fn exchange(&self) -> &Arc<dyn Exchange> {
+        &self.exchange
+    }
+    
+    /// Times paper fills and waits between retries on `clock`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Whether the exchange accepts the credentials orders are signed with.
+    pub async fn check_auth(&self) -> Result<()> {
+        self.exchange.verify_credentials().await
+    }
+    
+    /// Cancels every open order on the account. Returns the exchange ids
+    /// of the cancelled orders and an error for each that couldn't be.
+    pub async fn cancel_open_orders(&self) -> Result<(Vec<String>, Vec<String>)> {
+        self.cancel_orders(|_| true).await
+    }
+    
+    /// Cancels the open orders on the account that `matching` picks.
+    pub async fn cancel_orders(&self, matching: impl Fn(&ExchangeOrder) -> bool) -> Result<(Vec<String>, Vec<String>)> {
+        if self.config.paper_trading {
+            return Ok((vec![], vec![]));
+        }
+        let orders = self.exchange.open_orders().await?;
+        let mut cancelled = Vec::new();
+        let mut failed = Vec::new();
+        for order in orders.into_iter().filter(|o| matching(o)) {
+            match self.exchange.cancel_order(&order.order_id).await {
+                Ok(()) => cancelled.push(order.order_id),
+                Err(e) => failed.push(format!("{}: {:#}", order.order_id, e)),
+            }
+        }
+        Ok((cancelled, failed))
+    }
+    
+    /// Cancels one order by its exchange id.
+    pub async fn cancel_order(&self, order_id: &str) -> Result<()> {
+        if self.config.paper_trading {
+            return Ok(());
+        }
+        self.exchange.cancel_order(order_id).await
+    }
+    
+    /// The order that mirrors a leader trade.
+    pub fn copy_order(&self, trade: &Trade, shares: f64) -> OrderRequest {
         let order_type = match trade.side {
             TradeSide::BUY => OrderType::FAK,  // Fill-And-Kill for buys
             TradeSide::SELL => OrderType::GTD,  // Good-Till-Date for sells
         };
         
-        let order = OrderRequest {
+        OrderRequest {
             market_id: trade.market_id.clone(),
             side: trade.side.clone(),
             shares,
-            price: Some(trade.price),
+            price: Some(self.limit_price(trade)),
             order_type,
-        };
-        
-        let result = self.execute_with_retry(order).await;
+            client_order_id: new_client_order_id(),
+        }
+    }
+    
+    pub async fn execute_trade(&self, trade: &Trade, shares: f64) -> Result<OrderResponse> {
+        self.execute_order(trade, self.copy_order(trade, shares)).await
+    }
+    
+    /// Submits a prepared copy order for `trade`, with retries.
+    pub async fn execute_order(&self, trade: &Trade, order: OrderRequest) -> Result<OrderResponse> {
+        let result = self.execute_with_retry(order, Some(trade.price)).await;
         
         match &result {
             Ok(resp) => {
@@ -50,22 +123,59 @@ impl TradeExecutor {
         result
     }
     
-    async fn execute_with_retry(&self, order: OrderRequest) -> Result<OrderResponse> {
+    fn limit_price(&self, trade: &Trade) -> f64 {
+        limit_price(trade, self.config.max_slippage.as_f64())
+    }
+    
+    /// Fills the order through the configured fill model without touching
+    /// the exchange. `reference` is the leader's price for copies; other
+    /// orders are referenced to their limit, or the book top for market
+    /// orders.
+    async fn simulate_fill(&self, order: &OrderRequest, reference: Option<f64>) -> Result<OrderResponse> {
+        let reference_price = match reference.or(order.price) {
+            Some(p) => p,
+            None => self.get_estimated_price(&order.market_id, &order.side).await?,
+        };
+        let sim = SimOrder {
+            market_id: order.market_id.clone(),
+            side: order.side.clone(),
+            shares: order.shares,
+            reference_price,
+            limit: order.price,
+            at_ms: self.clock.now_ms(),
+        };
+        let Some(fill) = self.fills.fill(&sim).await else {
+            anyhow::bail!("Paper order would not have filled ({} fill model)", self.fills.name());
+        };
+        
+        Ok(OrderResponse {
+            order_id: format!("paper-{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()),
+            status: if fill.shares < order.shares - 1e-9 { "partially_filled" } else { "filled" }.to_string(),
+            filled_shares: fill.shares,
+            avg_fill_price: fill.avg_price,
+        })
+    }
+    
+    /// Places `order`, retrying only when the exchange can't be reached. Any
+    /// answer but cancelled or rejected (filled, or resting as live/open)
+    /// means the exchange has the order, so it's never sent twice.
+    async fn execute_with_retry(&self, order: OrderRequest, reference: Option<f64>) -> Result<OrderResponse> {
+        if self.config.paper_trading {
+            return self.simulate_fill(&order, reference).await;
+        }
+        
         let mut attempts = 0;
         let mut last_error = None;
         
         while attempts < self.config.retry_attempts {
             attempts += 1;
             
-            match self.api.place_order(order.clone(), &self.config.private_key).await {
+            match self.exchange.place_order(&order).await {
                 Ok(resp) => {
-                    if resp.status == "filled" || resp.status == "partially_filled" {
-                        return Ok(resp);
-                    }
-                    
                     if resp.status == "cancelled" || resp.status == "rejected" {
                         anyhow::bail!("Order {} by exchange: {}", resp.status, resp.order_id);
                     }
+                    return Ok(resp);
                 }
                 Err(e) => {
                     last_error = Some(e);
@@ -78,7 +188,7 @@ impl TradeExecutor {
                             self.config.retry_delay_ms
                         );
                         
-                        tokio::time::sleep(Duration::from_millis(
+                        self.clock.sleep(Duration::from_millis(
                             self.config.retry_delay_ms * (attempts as u64)
                         )).await;
                     }
@@ -86,7 +196,8 @@ impl TradeExecutor {
             }
         }
         
-        Err(last_error.unwrap().context(format!(
+        let error = last_error.unwrap_or_else(|| anyhow::anyhow!("retry_attempts is 0, so nothing was sent"));
+        Err(error.context(format!(
             "Failed to execute order after {} attempts",
             self.config.retry_attempts
         )))
@@ -105,12 +216,14 @@ impl TradeExecutor {
             shares,
             price: None,  // Market order
             order_type: OrderType::MARKET,
+            client_order_id: new_client_order_id(),
         };
         
-        self.execute_with_retry(order).await
+        self.execute_with_retry(order, Some(trade.price)).await
     }
     
-    pub async fn close_position(&self, market_id: &str, shares: f64, side: TradeSide) -> Result<OrderResponse> {
+    /// The market order that closes `shares` of a position opened on `side`.
+    pub fn close_order(&self, market_id: &str, shares: f64, side: TradeSide) -> OrderRequest {
         // To close a BUY position, we SELL
         // To close a SELL position, we BUY
         let close_side = match side {
@@ -118,28 +231,36 @@ impl TradeExecutor {
             TradeSide::SELL => TradeSide::BUY,
         };
         
-        let order = OrderRequest {
+        OrderRequest {
             market_id: market_id.to_string(),
             side: close_side,
             shares,
             price: None,
             order_type: OrderType::MARKET,
-        };
+            client_order_id: new_client_order_id(),
+        }
+    }
+    
+    pub async fn close_position(&self, market_id: &str, shares: f64, side: TradeSide) -> Result<OrderResponse> {
+        let order = self.close_order(market_id, shares, side);
         
         tracing::info!("Closing position: {} {:.2} shares on {}", 
-            match side {
-                TradeSide::BUY => "SELL",
-                TradeSide::SELL => "BUY",
-            },
+            order.side.as_str(),
             shares, 
             market_id
         );
         
-        self.execute_with_retry(order).await
+        self.execute_with_retry(order, None).await
+    }
+    
+    /// Submits a prepared order, with retries; simulated when paper trading.
+    pub async fn submit_order(&self, order: OrderRequest) -> Result<OrderResponse> {
+        self.execute_with_retry(order, None).await
     }
     
     pub async fn get_estimated_price(&self, market_id: &str, side: &TradeSide) -> Result<f64> {
-        let (bids, asks) = self.api.get_orderbook(market_id).await?;
+        let book = self.exchange.orderbook(market_id).await?;
+        let (bids, asks) = (book.bids, book.asks);
         
         let price = match side {
             TradeSide::BUY => {
@@ -155,3 +276,121 @@ impl TradeExecutor {
         Ok(price)
     }
 }
+
+/// Leader price widened by the `max_slippage` tolerance.
+pub fn limit_price(trade: &Trade, max_slippage: f64) -> f64 {
+    let price = match trade.side {
+        TradeSide::BUY => trade.price * (1.0 + max_slippage),
+        TradeSide::SELL => trade.price * (1.0 - max_slippage),
+    };
+    price.clamp(0.001, 0.999)
+}
+
+/// A fresh idempotency key for an order: millisecond time plus 64 random bits.
+pub fn new_client_order_id() -> String {
+    format!(
+        "cb-{:x}-{:016x}",
+        chrono::Utc::now().timestamp_millis(),
+        rand::thread_rng().gen::<u64>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Market, OrderBook, TokenBalance, Venue};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Accepts every order as resting on the book.
+    #[derive(Default)]
+    struct Resting {
+        placed: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Exchange for Resting {
+        fn venue(&self) -> Venue {
+            Venue::Polymarket
+        }
+        async fn trades(&self, _account: &str, _since: i64) -> Result<Vec<Trade>> {
+            Ok(vec![])
+        }
+        async fn market(&self, market_id: &str) -> Result<Market> {
+            anyhow::bail!("no market {}", market_id)
+        }
+        async fn markets(&self, _query: &str, _limit: usize) -> Result<Vec<Market>> {
+            Ok(vec![])
+        }
+        async fn orderbook(&self, _market_id: &str) -> Result<OrderBook> {
+            Ok(OrderBook::new(vec![], vec![]))
+        }
+        async fn place_order(&self, order: &OrderRequest) -> Result<OrderResponse> {
+            self.placed.fetch_add(1, Ordering::SeqCst);
+            Ok(OrderResponse {
+                order_id: format!("ex-{}", order.client_order_id),
+                status: "live".to_string(),
+                filled_shares: 0.0,
+                avg_fill_price: 0.0,
+            })
+        }
+        async fn cancel_order(&self, _order_id: &str) -> Result<()> {
+            Ok(())
+        }
+        async fn open_orders(&self) -> Result<Vec<ExchangeOrder>> {
+            Ok(vec![])
+        }
+        async fn order(&self, order_id: &str) -> Result<ExchangeOrder> {
+            anyhow::bail!("no order {}", order_id)
+        }
+        async fn order_by_client_id(&self, _client_order_id: &str) -> Result<Option<ExchangeOrder>> {
+            Ok(None)
+        }
+        async fn positions(&self) -> Result<Vec<TokenBalance>> {
+            Ok(vec![])
+        }
+        async fn balance(&self) -> Result<f64> {
+            Ok(0.0)
+        }
+        async fn verify_credentials(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn limit_order() -> OrderRequest {
+        OrderRequest {
+            market_id: "token1".to_string(),
+            side: TradeSide::BUY,
+            shares: 10.0,
+            price: Some(0.4),
+            order_type: OrderType::LIMIT,
+            client_order_id: "c-1".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resting_orders_are_placed_once() {
+        let exchange = Arc::new(Resting::default());
+        let config = Config {
+            retry_attempts: 3,
+            ..Config::default()
+        };
+        let executor = TradeExecutor::new(PolymarketApi::new("http://127.0.0.1:9".to_string()), config.clone())
+            .with_exchange(exchange.clone());
+
+        let resp = executor.submit_order(limit_order()).await.unwrap();
+        assert_eq!((resp.status.as_str(), resp.order_id.as_str()), ("live", "ex-c-1"));
+        assert_eq!(exchange.placed.load(Ordering::SeqCst), 1);
+
+        let executor = TradeExecutor::new(
+            PolymarketApi::new("http://127.0.0.1:9".to_string()),
+            Config {
+                retry_attempts: 0,
+                ..config
+            },
+        )
+        .with_exchange(exchange.clone());
+        assert!(executor.submit_order(limit_order()).await.is_err());
+        assert_eq!(exchange.placed.load(Ordering::SeqCst), 1);
+    }
+}
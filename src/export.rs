@@ -0,0 +1,582 @@
+//! Journal exports for analysis in pandas, DuckDB and friends, and for
+//! tax software: `lots` pairs every acquired lot with its disposal, and
+//! `cointracking` lays the fills out for CoinTracking's CSV import.
+
+use crate::archive::{self, Column, Table};
+use crate::audit;
+use crate::portfolio::{self, Lot};
+use crate::storage::{FillRecord, Storage, TimeRange};
+use crate::types::CostBasis;
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportTable {
+    Trades,
+    Decisions,
+    Orders,
+    Fills,
+    Pnl,
+    Prices,
+    Audit,
+    Lots,
+    Cointracking,
+}
+
+impl FromStr for ExportTable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "trades" => ExportTable::Trades,
+            "decisions" => ExportTable::Decisions,
+            "orders" => ExportTable::Orders,
+            "fills" => ExportTable::Fills,
+            "pnl" => ExportTable::Pnl,
+            "prices" => ExportTable::Prices,
+            "audit" => ExportTable::Audit,
+            "lots" => ExportTable::Lots,
+            "cointracking" => ExportTable::Cointracking,
+            other => anyhow::bail!(
+                "Unknown export table '{}' (expected trades, decisions, orders, fills, pnl, prices, audit, lots or \
+                 cointracking)",
+                other
+            ),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Picks the format from the output file's extension.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("csv") => Ok(ExportFormat::Csv),
+            Some("parquet") => Ok(ExportFormat::Parquet),
+            _ => anyhow::bail!("Export path must end in .csv or .parquet: {}", path.display()),
+        }
+    }
+}
+
+/// Realized PnL and volume for one market on one UTC day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyPnl {
+    pub date: NaiveDate,
+    pub market_id: String,
+    pub bought_shares: f64,
+    pub sold_shares: f64,
+    pub buy_usd: f64,
+    pub sell_usd: f64,
+    pub fees: f64,
+    pub realized_pnl: f64,
+}
+
+/// Folds `fills` (any order) into per-day, per-market PnL. Fills before the
+/// period of interest still matter: they set the cost basis of later sells.
+pub fn daily_pnl(fills: &[FillRecord], cost_basis: CostBasis) -> Vec<DailyPnl> {
+    let mut fills: Vec<&FillRecord> = fills.iter().collect();
+    fills.sort_by_key(|f| (f.filled_at, f.id));
+
+    let mut holdings: HashMap<&str, Vec<Lot>> = HashMap::new();
+    let mut days: BTreeMap<(NaiveDate, &str), DailyPnl> = BTreeMap::new();
+
+    for fill in fills {
+        let date = chrono::DateTime::from_timestamp_millis(fill.filled_at)
+            .unwrap_or_default()
+            .date_naive();
+        let day = days.entry((date, fill.market_id.as_str())).or_insert_with(|| DailyPnl {
+            date,
+            market_id: fill.market_id.clone(),
+            bought_shares: 0.0,
+            sold_shares: 0.0,
+            buy_usd: 0.0,
+            sell_usd: 0.0,
+            fees: 0.0,
+            realized_pnl: 0.0,
+        });
+        let lots = holdings.entry(fill.market_id.as_str()).or_default();
+
+        day.fees += fill.fee;
+        day.realized_pnl -= fill.fee;
+        if fill.side.eq_ignore_ascii_case("SELL") {
+            day.sold_shares += fill.shares;
+            day.sell_usd += fill.shares * fill.price;
+            day.realized_pnl += portfolio::sell(lots, fill.shares, fill.price);
+        } else {
+            day.bought_shares += fill.shares;
+            day.buy_usd += fill.shares * fill.price;
+            portfolio::buy(lots, cost_basis, fill.shares, fill.price, fill.filled_at);
+        }
+    }
+
+    days.into_values().collect()
+}
+
+pub fn pnl_table(rows: &[DailyPnl]) -> Table {
+    Table {
+        name: "daily_pnl",
+        columns: vec![
+            ("date", Column::Str(rows.iter().map(|r| r.date.to_string()).collect())),
+            (
+                "market_id",
+                Column::Str(rows.iter().map(|r| r.market_id.clone()).collect()),
+            ),
+            (
+                "bought_shares",
+                Column::F64(rows.iter().map(|r| r.bought_shares).collect()),
+            ),
+            ("sold_shares", Column::F64(rows.iter().map(|r| r.sold_shares).collect())),
+            ("buy_usd", Column::F64(rows.iter().map(|r| r.buy_usd).collect())),
+            ("sell_usd", Column::F64(rows.iter().map(|r| r.sell_usd).collect())),
+            ("fees", Column::F64(rows.iter().map(|r| r.fees).collect())),
+            (
+                "realized_pnl",
+                Column::F64(rows.iter().map(|r| r.realized_pnl).collect()),
+            ),
+        ],
+    }
+}
+
+/// Currency CoinTracking books cash in.
+const CASH: &str = "USDC";
+/// Exchange CoinTracking books trades on.
+const EXCHANGE: &str = "Polymarket";
+
+/// Shares of one lot, from the fill that bought them to the one that sold
+/// or redeemed them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxLot {
+    /// Id of the buy fill; `None` for shares sold that the journal never
+    /// saw bought
+    pub lot_id: Option<i64>,
+    pub market_id: String,
+    /// Unix ms
+    pub acquired_at: Option<i64>,
+    /// Unix ms; `None` while held
+    pub disposed_at: Option<i64>,
+    /// "sell" or "redemption"
+    pub disposal: Option<&'static str>,
+    pub shares: f64,
+    /// Per share
+    pub cost_price: f64,
+    pub proceeds_price: Option<f64>,
+    /// The lot's share of the buying and selling fills' fees
+    pub buy_fee: f64,
+    pub sell_fee: f64,
+}
+
+impl TaxLot {
+    /// What the shares cost, fees included.
+    pub fn cost_basis(&self) -> f64 {
+        self.shares * self.cost_price + self.buy_fee
+    }
+
+    /// What disposing of the shares brought in, net of fees.
+    pub fn proceeds(&self) -> Option<f64> {
+        self.proceeds_price.map(|price| self.shares * price - self.sell_fee)
+    }
+
+    pub fn gain(&self) -> Option<f64> {
+        self.proceeds().map(|proceeds| proceeds - self.cost_basis())
+    }
+}
+
+struct OpenLot {
+    id: i64,
+    shares: f64,
+    price: f64,
+    fee_per_share: f64,
+    at: i64,
+}
+
+/// Splits `fills` (any order) into tax lots: every buy fill opens a lot,
+/// and sells and redemptions (sells without an order) dispose of the
+/// oldest first, splitting lots as needed. Lots are matched first in,
+/// first out whatever `cost_basis` is, as reporting individual lots needs
+/// them kept apart. Lots still held come last.
+pub fn tax_lots(fills: &[FillRecord]) -> Vec<TaxLot> {
+    let mut fills: Vec<&FillRecord> = fills.iter().collect();
+    fills.sort_by_key(|f| (f.filled_at, f.id));
+
+    let mut open: BTreeMap<&str, VecDeque<OpenLot>> = BTreeMap::new();
+    let mut lots = Vec::new();
+    for fill in fills {
+        let held = open.entry(fill.market_id.as_str()).or_default();
+        let fee_per_share = if fill.shares > 0.0 { fill.fee / fill.shares } else { 0.0 };
+        if !fill.side.eq_ignore_ascii_case("SELL") {
+            held.push_back(OpenLot {
+                id: fill.id,
+                shares: fill.shares,
+                price: fill.price,
+                fee_per_share,
+                at: fill.filled_at,
+            });
+            continue;
+        }
+        let disposal = if fill.order_id == 0 { "redemption" } else { "sell" };
+        let mut remaining = fill.shares;
+        while remaining > 1e-9 {
+            let lot = held.front_mut();
+            let take = lot.as_ref().map_or(remaining, |lot| remaining.min(lot.shares));
+            lots.push(TaxLot {
+                lot_id: lot.as_ref().map(|lot| lot.id),
+                market_id: fill.market_id.clone(),
+                acquired_at: lot.as_ref().map(|lot| lot.at),
+                disposed_at: Some(fill.filled_at),
+                disposal: Some(disposal),
+                shares: take,
+                cost_price: lot.as_ref().map_or(0.0, |lot| lot.price),
+                proceeds_price: Some(fill.price),
+                buy_fee: lot.as_ref().map_or(0.0, |lot| take * lot.fee_per_share),
+                sell_fee: take * fee_per_share,
+            });
+            remaining -= take;
+            match lot {
+                Some(lot) => {
+                    lot.shares -= take;
+                    if lot.shares <= 1e-9 {
+                        held.pop_front();
+                    }
+                }
+                None => tracing::warn!(
+                    "⚠️  Fill {} sold {:.2} shares of {} the journal never saw bought; exported without a cost basis",
+                    fill.id,
+                    take,
+                    fill.market_id
+                ),
+            }
+        }
+    }
+    for (market_id, held) in open {
+        for lot in held {
+            lots.push(TaxLot {
+                lot_id: Some(lot.id),
+                market_id: market_id.to_string(),
+                acquired_at: Some(lot.at),
+                disposed_at: None,
+                disposal: None,
+                shares: lot.shares,
+                cost_price: lot.price,
+                proceeds_price: None,
+                buy_fee: lot.shares * lot.fee_per_share,
+                sell_fee: 0.0,
+            });
+        }
+    }
+    lots
+}
+
+/// Unix ms as a UTC timestamp tax software reads, like
+/// `2024-01-02T03:04:05Z`.
+fn utc(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+pub fn tax_lots_table(rows: &[TaxLot]) -> Table {
+    Table {
+        name: "tax_lots",
+        columns: vec![
+            ("lot_id", Column::OptI64(rows.iter().map(|r| r.lot_id).collect())),
+            (
+                "market_id",
+                Column::Str(rows.iter().map(|r| r.market_id.clone()).collect()),
+            ),
+            (
+                "acquired_at",
+                Column::OptStr(rows.iter().map(|r| r.acquired_at.map(utc)).collect()),
+            ),
+            (
+                "disposed_at",
+                Column::OptStr(rows.iter().map(|r| r.disposed_at.map(utc)).collect()),
+            ),
+            (
+                "disposal",
+                Column::OptStr(rows.iter().map(|r| r.disposal.map(str::to_string)).collect()),
+            ),
+            ("shares", Column::F64(rows.iter().map(|r| r.shares).collect())),
+            ("cost_price", Column::F64(rows.iter().map(|r| r.cost_price).collect())),
+            (
+                "proceeds_price",
+                Column::OptF64(rows.iter().map(|r| r.proceeds_price).collect()),
+            ),
+            ("buy_fee", Column::F64(rows.iter().map(|r| r.buy_fee).collect())),
+            ("sell_fee", Column::F64(rows.iter().map(|r| r.sell_fee).collect())),
+            ("cost_basis", Column::F64(rows.iter().map(|r| r.cost_basis()).collect())),
+            ("proceeds", Column::OptF64(rows.iter().map(|r| r.proceeds()).collect())),
+            ("gain", Column::OptF64(rows.iter().map(|r| r.gain()).collect())),
+        ],
+    }
+}
+
+/// `fills` in the layout of CoinTracking's CSV import: each fill trades a
+/// market's shares (as `PM-<market id>`) against USDC, and a redemption
+/// that pays nothing books the shares as lost.
+pub fn cointracking_table(fills: &[FillRecord]) -> Table {
+    let mut fills: Vec<&FillRecord> = fills.iter().collect();
+    fills.sort_by_key(|f| (f.filled_at, f.id));
+
+    let mut kind = Vec::new();
+    let mut buy_amount = Vec::new();
+    let mut buy_currency = Vec::new();
+    let mut sell_amount = Vec::new();
+    let mut sell_currency = Vec::new();
+    let mut comment = Vec::new();
+    for fill in &fills {
+        let shares = format!("PM-{}", fill.market_id);
+        let cash = fill.shares * fill.price;
+        let redemption = fill.order_id == 0;
+        if !fill.side.eq_ignore_ascii_case("SELL") {
+            kind.push("Trade");
+            buy_amount.push(Some(fill.shares));
+            buy_currency.push(Some(shares));
+            sell_amount.push(Some(cash));
+            sell_currency.push(Some(CASH.to_string()));
+        } else if redemption && cash <= 0.0 {
+            kind.push("Lost");
+            buy_amount.push(None);
+            buy_currency.push(None);
+            sell_amount.push(Some(fill.shares));
+            sell_currency.push(Some(shares));
+        } else {
+            kind.push("Trade");
+            buy_amount.push(Some(cash));
+            buy_currency.push(Some(CASH.to_string()));
+            sell_amount.push(Some(fill.shares));
+            sell_currency.push(Some(shares));
+        }
+        comment.push(if redemption {
+            format!("redemption {}", fill.id)
+        } else {
+            format!("fill {}", fill.id)
+        });
+    }
+    Table {
+        name: "cointracking",
+        columns: vec![
+            ("Type", Column::Str(kind.into_iter().map(str::to_string).collect())),
+            ("Buy Amount", Column::OptF64(buy_amount)),
+            ("Buy Currency", Column::OptStr(buy_currency)),
+            ("Sell Amount", Column::OptF64(sell_amount)),
+            ("Sell Currency", Column::OptStr(sell_currency)),
+            ("Fee", Column::F64(fills.iter().map(|f| f.fee).collect())),
+            ("Fee Currency", Column::Str(vec![CASH.to_string(); fills.len()])),
+            ("Exchange", Column::Str(vec![EXCHANGE.to_string(); fills.len()])),
+            ("Trade-Group", Column::Str(vec![String::new(); fills.len()])),
+            ("Comment", Column::Str(comment)),
+            (
+                "Date",
+                Column::Str(
+                    fills
+                        .iter()
+                        .map(|f| {
+                            chrono::DateTime::from_timestamp_millis(f.filled_at)
+                                .unwrap_or_default()
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string()
+                        })
+                        .collect(),
+                ),
+            ),
+        ],
+    }
+}
+
+/// Inclusive UTC date range; open ends default to the beginning/end of time.
+pub fn date_range(from: Option<&str>, to: Option<&str>) -> Result<TimeRange> {
+    let parse = |s: &str| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").with_context(|| format!("Invalid date '{}' (expected YYYY-MM-DD)", s))
+    };
+    let mut range = TimeRange::all();
+    if let Some(from) = from {
+        range.from_ms = parse(from)?.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis();
+    }
+    if let Some(to) = to {
+        let next_day = parse(to)?.succ_opt().context("Date out of range")?;
+        range.to_ms = next_day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis() - 1;
+    }
+    if range.from_ms > range.to_ms {
+        anyhow::bail!("--from is after --to");
+    }
+    Ok(range)
+}
+
+/// Writes `table` rows within `range` to `path` (format by extension); PnL
+/// is realized using `cost_basis`. Returns the number of rows written.
+pub async fn export(
+    storage: &dyn Storage,
+    table: ExportTable,
+    range: TimeRange,
+    cost_basis: CostBasis,
+    path: &Path,
+) -> Result<usize> {
+    let format = ExportFormat::from_path(path)?;
+    let table = match table {
+        ExportTable::Trades => archive::leader_trades_table(&storage.leader_trades(range).await?),
+        ExportTable::Decisions => archive::decisions_table(&storage.decisions(range).await?),
+        ExportTable::Orders => archive::orders_table(&storage.orders(range).await?),
+        ExportTable::Fills => archive::fills_table(&storage.fills(range).await?),
+        ExportTable::Prices => archive::prices_table(&storage.prices(None, range).await?),
+        ExportTable::Audit => {
+            let records = storage.audit_records(range).await?;
+            match audit::verify(&records) {
+                Ok(()) => tracing::info!("🔏 Audit chain intact across {} records", records.len()),
+                Err(e) => tracing::error!("🚨 {:#}", e),
+            }
+            audit::audit_table(&records)
+        }
+        ExportTable::Pnl => {
+            let fills = storage
+                .fills(TimeRange {
+                    from_ms: 0,
+                    to_ms: range.to_ms,
+                })
+                .await?;
+            let first_day = chrono::DateTime::from_timestamp_millis(range.from_ms)
+                .unwrap_or_default()
+                .date_naive();
+            let rows: Vec<DailyPnl> = daily_pnl(&fills, cost_basis)
+                .into_iter()
+                .filter(|r| r.date >= first_day)
+                .collect();
+            pnl_table(&rows)
+        }
+        ExportTable::Lots => {
+            // Earlier buys are the lots later sells dispose of
+            let fills = storage
+                .fills(TimeRange {
+                    from_ms: 0,
+                    to_ms: range.to_ms,
+                })
+                .await?;
+            let rows: Vec<TaxLot> = tax_lots(&fills)
+                .into_iter()
+                .filter(|lot| lot.disposed_at.is_none_or(|at| at >= range.from_ms))
+                .collect();
+            tax_lots_table(&rows)
+        }
+        ExportTable::Cointracking => {
+            if format != ExportFormat::Csv {
+                anyhow::bail!("The cointracking export is CSV only");
+            }
+            cointracking_table(&storage.fills(range).await?)
+        }
+    };
+
+    match format {
+        ExportFormat::Csv => table.write_csv(path)?,
+        ExportFormat::Parquet => table.write_parquet(path)?,
+    }
+    Ok(table.rows())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(id: i64, side: &str, shares: f64, price: f64, filled_at: i64) -> FillRecord {
+        FillRecord {
+            id,
+            order_id: id,
+            market_id: "market1".to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee: 0.0,
+            filled_at,
+        }
+    }
+
+    #[test]
+    fn test_daily_pnl_follows_cost_basis() {
+        let day = 86_400_000;
+        let fills = vec![
+            fill(1, "BUY", 10.0, 0.40, 0),
+            fill(2, "BUY", 10.0, 0.60, 1_000),
+            fill(3, "SELL", 10.0, 0.70, day),
+        ];
+
+        let rows = daily_pnl(&fills, CostBasis::Average);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].realized_pnl, 0.0);
+        assert!((rows[1].realized_pnl - 2.0).abs() < 1e-9);
+
+        let fifo = daily_pnl(&fills, CostBasis::Fifo);
+        assert!((fifo[1].realized_pnl - 3.0).abs() < 1e-9);
+        assert_eq!(rows[1].date.to_string(), "1970-01-02");
+    }
+
+    #[test]
+    fn test_tax_lots_split_across_sells_and_redemptions() {
+        let mut buy = fill(1, "BUY", 10.0, 0.40, 0);
+        buy.fee = 0.20;
+        let mut sell = fill(3, "SELL", 15.0, 0.70, 2_000);
+        sell.fee = 0.30;
+        let mut redemption = fill(4, "SELL", 5.0, 1.0, 3_000);
+        redemption.order_id = 0;
+        let mut unseen = fill(6, "SELL", 2.0, 0.10, 5_000);
+        unseen.market_id = "market2".to_string();
+        let fills = vec![
+            redemption,
+            sell,
+            buy,
+            fill(2, "BUY", 10.0, 0.60, 1_000),
+            fill(5, "BUY", 4.0, 0.50, 4_000),
+            // Never seen bought
+            unseen,
+        ];
+
+        let lots = tax_lots(&fills);
+        let ids: Vec<Option<i64>> = lots.iter().map(|l| l.lot_id).collect();
+        assert_eq!(ids, vec![Some(1), Some(2), Some(2), None, Some(5)]);
+        // The whole first lot and half the second go in the sell
+        assert_eq!((lots[0].shares, lots[1].shares), (10.0, 5.0));
+        assert!((lots[0].cost_basis() - 4.2).abs() < 1e-9);
+        assert!((lots[0].proceeds().unwrap() - 6.8).abs() < 1e-9);
+        assert!((lots[0].gain().unwrap() - 2.6).abs() < 1e-9);
+        assert!((lots[1].sell_fee - 0.1).abs() < 1e-9);
+        assert_eq!(
+            (lots[2].disposal, lots[2].acquired_at),
+            (Some("redemption"), Some(1_000))
+        );
+        assert!((lots[2].gain().unwrap() - 2.0).abs() < 1e-9);
+        assert_eq!((lots[3].cost_basis(), lots[3].disposal), (0.0, Some("sell")));
+        assert_eq!((lots[4].disposed_at, lots[4].gain()), (None, None));
+
+        let table = tax_lots_table(&lots);
+        assert_eq!(table.rows(), 5);
+        let cointracking = cointracking_table(&fills);
+        assert_eq!(cointracking.rows(), 6);
+        let Column::Str(kinds) = &cointracking.columns[0].1 else {
+            panic!("Type should be text")
+        };
+        assert!(kinds.iter().all(|k| k == "Trade"));
+        let Column::OptStr(bought) = &cointracking.columns[2].1 else {
+            panic!("Buy Currency should be optional text")
+        };
+        assert_eq!(bought[0].as_deref(), Some("PM-market1"));
+        assert_eq!(bought[2].as_deref(), Some("USDC"));
+        assert_eq!(utc(86_400_000), "1970-01-02T00:00:00Z");
+    }
+
+    #[test]
+    fn test_date_range_is_inclusive() {
+        let range = date_range(Some("2024-01-01"), Some("2024-01-01")).unwrap();
+        assert_eq!(range.to_ms - range.from_ms, 86_399_999);
+        assert!(date_range(Some("2024-01-02"), Some("2024-01-01")).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Where the open positions' money is, for dashboards.
+//!
+//! Sums the portfolio's holdings at their marks (at cost when there's no
+//! price to hand) by market category, by how soon the market is due to
+//! resolve, and by the leader whose trades opened them, plus a category by
+//! resolution grid for heatmaps. Leaders come from the last PnL refresh
+//! (see [`crate::pnl`]), so they need `storage_url` and can trail the
+//! portfolio by up to `pnl_interval`. Served on `/status/exposure`.
+
+use crate::marks::{Mark, Marks};
+use crate::pnl::PnlReport;
+use crate::portfolio::Holding;
+use crate::types::Market;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Resolution buckets by time left until the market's end date, in order.
+pub const RESOLUTION_BUCKETS: &[&str] = &["past_due", "<1d", "1-7d", "7-30d", "30-90d", ">90d", "unknown"];
+
+const DAY_MS: i64 = 86_400_000;
+
+/// Which of [`RESOLUTION_BUCKETS`] a market ending at `end_date` (unix
+/// seconds) falls in at `now_ms`.
+pub fn resolution_bucket(end_date: Option<i64>, now_ms: i64) -> &'static str {
+    let Some(end_date) = end_date else { return "unknown" };
+    match end_date * 1000 - now_ms {
+        left if left < 0 => "past_due",
+        left if left < DAY_MS => "<1d",
+        left if left < 7 * DAY_MS => "1-7d",
+        left if left < 30 * DAY_MS => "7-30d",
+        left if left < 90 * DAY_MS => "30-90d",
+        _ => ">90d",
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExposureBucket {
+    pub name: String,
+    pub positions: usize,
+    /// What the shares cost
+    pub cost: f64,
+    /// At their marks, else their cost
+    pub value: f64,
+}
+
+impl ExposureBucket {
+    fn add(&mut self, cost: f64, value: f64) {
+        self.positions += 1;
+        self.cost += cost;
+        self.value += value;
+    }
+}
+
+/// One cell of the category by resolution grid.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExposureCell {
+    pub category: String,
+    pub resolves: &'static str,
+    pub positions: usize,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExposureReport {
+    /// Unix ms
+    pub at: i64,
+    pub positions: usize,
+    pub cost: f64,
+    pub value: f64,
+    /// Positions valued at cost for want of a mark
+    pub unmarked: usize,
+    /// Largest first; markets without a category count as "other"
+    pub categories: Vec<ExposureBucket>,
+    /// In the order of [`RESOLUTION_BUCKETS`], empty ones left out
+    pub resolution: Vec<ExposureBucket>,
+    /// Largest first; `None` without a PnL report to attribute from
+    pub leaders: Option<Vec<ExposureBucket>>,
+    /// Non-empty cells only
+    pub cells: Vec<ExposureCell>,
+}
+
+fn largest_first(buckets: BTreeMap<String, ExposureBucket>) -> Vec<ExposureBucket> {
+    let mut buckets: Vec<ExposureBucket> = buckets.into_values().collect();
+    buckets.sort_by(|a, b| b.value.total_cmp(&a.value));
+    buckets
+}
+
+/// Exposure of `holdings` at `now_ms`; `markets` give categories and end
+/// dates, and `pnl`'s open positions the leaders.
+pub fn compute(
+    holdings: &[Holding],
+    markets: &HashMap<String, Market>,
+    marks: &HashMap<String, Mark>,
+    pnl: Option<&PnlReport>,
+    now_ms: i64,
+) -> ExposureReport {
+    let mut report = ExposureReport {
+        at: now_ms,
+        ..Default::default()
+    };
+    let mut categories: BTreeMap<String, ExposureBucket> = BTreeMap::new();
+    let mut resolution: BTreeMap<&str, ExposureBucket> = BTreeMap::new();
+    let mut cells: BTreeMap<(String, &'static str), ExposureCell> = BTreeMap::new();
+    for holding in holdings {
+        let cost = holding.cost();
+        let value = match marks.get(&holding.market_id) {
+            Some(mark) => holding.shares() * mark.price,
+            None => {
+                report.unmarked += 1;
+                cost
+            }
+        };
+        let market = markets.get(&holding.market_id);
+        let category = market
+            .map(|m| m.category.as_str())
+            .filter(|c| !c.is_empty())
+            .unwrap_or("other")
+            .to_string();
+        let resolves = resolution_bucket(market.and_then(|m| m.end_date), now_ms);
+
+        report.positions += 1;
+        report.cost += cost;
+        report.value += value;
+        categories
+            .entry(category.clone())
+            .or_insert_with(|| ExposureBucket {
+                name: category.clone(),
+                ..Default::default()
+            })
+            .add(cost, value);
+        resolution
+            .entry(resolves)
+            .or_insert_with(|| ExposureBucket {
+                name: resolves.to_string(),
+                ..Default::default()
+            })
+            .add(cost, value);
+        let cell = cells
+            .entry((category.clone(), resolves))
+            .or_insert_with(|| ExposureCell {
+                category,
+                resolves,
+                positions: 0,
+                value: 0.0,
+            });
+        cell.positions += 1;
+        cell.value += value;
+    }
+    report.categories = largest_first(categories);
+    report.resolution = RESOLUTION_BUCKETS
+        .iter()
+        .filter_map(|bucket| resolution.remove(bucket))
+        .collect();
+    report.cells = cells.into_values().collect();
+
+    report.leaders = pnl.map(|pnl| {
+        let mut leaders: BTreeMap<String, ExposureBucket> = BTreeMap::new();
+        for position in pnl.positions.iter().filter(|p| p.shares > 1e-9) {
+            let value = match marks.get(&position.market_id).or(position.mark.as_ref()) {
+                Some(mark) => position.shares * mark.price,
+                None => position.open_cost,
+            };
+            leaders
+                .entry(position.leader.clone())
+                .or_insert_with(|| ExposureBucket {
+                    name: position.leader.clone(),
+                    ..Default::default()
+                })
+                .add(position.open_cost, value);
+        }
+        largest_first(leaders)
+    });
+    report
+}
+
+/// Exposure of `holdings` now, looking their markets and marks up in
+/// `marks`.
+pub async fn snapshot(holdings: &[Holding], marks: &Marks, pnl: Option<&PnlReport>, now_ms: i64) -> ExposureReport {
+    let mut markets = HashMap::new();
+    for holding in holdings {
+        match marks.markets().get(&holding.market_id).await {
+            Ok(market) => {
+                markets.insert(holding.market_id.clone(), market);
+            }
+            Err(e) => tracing::debug!("No market for {}: {}", holding.market_id, e),
+        }
+    }
+    let ids: Vec<&str> = holdings.iter().map(|h| h.market_id.as_str()).collect();
+    let marked = marks.marks(&ids).await;
+    compute(holdings, &markets, &marked, pnl, now_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marks::MarkSource;
+    use crate::pnl::PositionPnl;
+    use crate::portfolio::Lot;
+
+    #[test]
+    fn test_exposure_by_category_resolution_and_leader() {
+        let now = 1_700_000_000_000;
+        let holding = |market_id: &str, shares, price| Holding {
+            market_id: market_id.to_string(),
+            lots: vec![Lot {
+                shares,
+                price,
+                opened_at: 0,
+            }],
+        };
+        let market = |id: &str, category: &str, days_left: Option<i64>| Market {
+            id: id.to_string(),
+            event_id: id.to_string(),
+            question: "Will it?".to_string(),
+            yes_price: 0.5,
+            no_price: 0.5,
+            end_date: days_left.map(|d| now / 1000 + d * 86_400 + 60),
+            category: category.to_string(),
+            ..Default::default()
+        };
+        let holdings = [
+            holding("m1", 100.0, 0.40),
+            holding("m2", 50.0, 0.20),
+            holding("m3", 10.0, 0.50),
+        ];
+        let markets = HashMap::from([
+            ("m1".to_string(), market("m1", "Politics", Some(3))),
+            ("m2".to_string(), market("m2", "Politics", Some(45))),
+            ("m3".to_string(), market("m3", "", None)),
+        ]);
+        let marks = HashMap::from([
+            (
+                "m1".to_string(),
+                Mark {
+                    price: 0.50,
+                    source: MarkSource::Mid,
+                    at: now,
+                    stale: false,
+                },
+            ),
+            (
+                "m2".to_string(),
+                Mark {
+                    price: 0.30,
+                    source: MarkSource::LastTrade,
+                    at: now,
+                    stale: false,
+                },
+            ),
+        ]);
+        let position = |leader: &str, market_id: &str, shares, open_cost| PositionPnl {
+            leader: leader.to_string(),
+            market_id: market_id.to_string(),
+            shares,
+            open_cost,
+            ..Default::default()
+        };
+        let pnl = PnlReport {
+            positions: vec![
+                position("0xa", "m1", 60.0, 24.0),
+                position("0xb", "m1", 40.0, 16.0),
+                position("0xb", "m2", 50.0, 10.0),
+                position("0xa", "m4", 0.0, 0.0),
+            ],
+            ..Default::default()
+        };
+
+        let report = compute(&holdings, &markets, &marks, Some(&pnl), now);
+        assert_eq!((report.positions, report.unmarked), (3, 1));
+        assert!((report.value - 70.0).abs() < 1e-9);
+        assert!((report.cost - 55.0).abs() < 1e-9);
+        let names = |b: &[ExposureBucket]| b.iter().map(|b| b.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&report.categories), ["Politics", "other"]);
+        assert!((report.categories[0].value - 65.0).abs() < 1e-9);
+        assert_eq!(names(&report.resolution), ["1-7d", "30-90d", "unknown"]);
+        let leaders = report.leaders.unwrap();
+        assert_eq!(names(&leaders), ["0xb", "0xa"]);
+        assert!((leaders[0].value - 35.0).abs() < 1e-9);
+        assert_eq!(leaders[1].positions, 1);
+        assert_eq!(report.cells.len(), 3);
+        assert_eq!(
+            (report.cells[0].category.as_str(), report.cells[0].resolves),
+            ("Politics", "1-7d")
+        );
+        assert_eq!(resolution_bucket(Some(now / 1000 - 1), now), "past_due");
+    }
+}
@@ -0,0 +1,359 @@
+//! Simulated execution, for paper trading and backtests.
+//!
+//! A [`FillModel`] decides how much of an order fills and at what price:
+//!
+//! - [`Immediate`]: all of it at the reference (the leader's) price
+//! - [`BookWalk`]: taking levels from the order book up to the limit price,
+//!   possibly in part
+//! - [`LatencyPenalized`]: all of it at the price some time later, or none
+//!   if that price is past the limit
+//!
+//! The book and later prices come from a [`MarketTape`]: the journal's price
+//! samples and the books journaled with each decision for backtests
+//! ([`RecordedTape`]), the exchange itself when paper trading
+//! ([`LiveTape`]).
+
+use crate::api::PolymarketApi;
+use crate::prices::mid_price;
+use crate::storage::{DecisionRecord, PriceSample, Storage, TimeRange};
+use crate::types::{FillModelKind, OrderBook, TradeSide};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Market data a fill model reads.
+#[async_trait]
+pub trait MarketTape: Send + Sync {
+    /// The book as last seen at or before `at_ms`.
+    async fn book_at(&self, market_id: &str, at_ms: i64) -> Option<OrderBook>;
+    /// The price first seen at or after `at_ms`.
+    async fn price_at(&self, market_id: &str, at_ms: i64) -> Option<f64>;
+}
+
+/// The book a decision was made against, as a sample.
+fn arrival_sample(decision: &DecisionRecord) -> Option<PriceSample> {
+    let book = decision.arrival_book.clone()?;
+    Some(PriceSample {
+        market_id: decision.market_id.clone(),
+        sampled_at: decision.decided_at,
+        mid: decision.arrival_mid.or_else(|| mid_price(&book.bids, &book.asks)),
+        last: None,
+        book: Some(book),
+    })
+}
+
+/// Price samples from the journal, replayed.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedTape {
+    /// By market, oldest first
+    samples: HashMap<String, Vec<PriceSample>>,
+}
+
+impl RecordedTape {
+    pub fn new(samples: Vec<PriceSample>) -> Self {
+        let mut tape = Self::default();
+        for sample in samples {
+            tape.samples.entry(sample.market_id.clone()).or_default().push(sample);
+        }
+        for samples in tape.samples.values_mut() {
+            samples.sort_by_key(|s| s.sampled_at);
+        }
+        tape
+    }
+
+    /// The journal's samples of `range`, and the books journaled with the
+    /// decisions in it.
+    pub async fn load(storage: &dyn Storage, range: TimeRange) -> Result<Self> {
+        let mut samples = storage.prices(None, range).await?;
+        samples.extend(storage.decisions(range).await?.iter().filter_map(arrival_sample));
+        Ok(Self::new(samples))
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl MarketTape for RecordedTape {
+    async fn book_at(&self, market_id: &str, at_ms: i64) -> Option<OrderBook> {
+        self.samples
+            .get(market_id)?
+            .iter()
+            .rev()
+            .filter(|s| s.sampled_at <= at_ms)
+            .find_map(|s| s.book.clone())
+    }
+
+    async fn price_at(&self, market_id: &str, at_ms: i64) -> Option<f64> {
+        self.samples
+            .get(market_id)?
+            .iter()
+            .filter(|s| s.sampled_at >= at_ms)
+            .find_map(|s| s.mid.or(s.last))
+    }
+}
+
+/// The exchange's order book, now. A price in the future is waited for.
+pub struct LiveTape {
+    api: PolymarketApi,
+}
+
+impl LiveTape {
+    pub fn new(api: PolymarketApi) -> Self {
+        Self { api }
+    }
+}
+
+#[async_trait]
+impl MarketTape for LiveTape {
+    async fn book_at(&self, market_id: &str, _at_ms: i64) -> Option<OrderBook> {
+        match self.api.get_orderbook(market_id).await {
+            Ok((bids, asks)) => Some(OrderBook::new(bids, asks)),
+            Err(e) => {
+                tracing::warn!("No book for {} to fill against: {:#}", market_id, e);
+                None
+            }
+        }
+    }
+
+    async fn price_at(&self, market_id: &str, at_ms: i64) -> Option<f64> {
+        let wait = at_ms - chrono::Utc::now().timestamp_millis();
+        if wait > 0 {
+            tokio::time::sleep(Duration::from_millis(wait as u64)).await;
+        }
+        let book = self.book_at(market_id, at_ms).await?;
+        mid_price(&book.bids, &book.asks)
+    }
+}
+
+/// An order to simulate.
+#[derive(Debug, Clone)]
+pub struct SimOrder {
+    pub market_id: String,
+    pub side: TradeSide,
+    pub shares: f64,
+    /// The price the order was decided at: the leader's for copies
+    pub reference_price: f64,
+    /// Worst acceptable price; none for market orders
+    pub limit: Option<f64>,
+    /// Unix ms the order was decided
+    pub at_ms: i64,
+}
+
+impl SimOrder {
+    /// Whether `price` is no worse than the limit.
+    fn accepts(&self, price: f64) -> bool {
+        match (self.limit, &self.side) {
+            (None, _) => true,
+            (Some(limit), TradeSide::BUY) => price <= limit + 1e-12,
+            (Some(limit), TradeSide::SELL) => price >= limit - 1e-12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimFill {
+    pub shares: f64,
+    pub avg_price: f64,
+}
+
+#[async_trait]
+pub trait FillModel: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// How long after being decided an order reaches the market.
+    fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// What of `order` fills; `None` when nothing would.
+    async fn fill(&self, order: &SimOrder) -> Option<SimFill>;
+}
+
+/// Fills everything at the reference price, as if the copy traded
+/// alongside the leader.
+pub struct Immediate;
+
+#[async_trait]
+impl FillModel for Immediate {
+    fn name(&self) -> &'static str {
+        "immediate"
+    }
+
+    async fn fill(&self, order: &SimOrder) -> Option<SimFill> {
+        Some(SimFill {
+            shares: order.shares,
+            avg_price: order.reference_price,
+        })
+    }
+}
+
+/// Takes liquidity from the book as of the order, best level first, until
+/// the order is filled or the next level is past the limit.
+pub struct BookWalk {
+    tape: Arc<dyn MarketTape>,
+}
+
+impl BookWalk {
+    pub fn new(tape: Arc<dyn MarketTape>) -> Self {
+        Self { tape }
+    }
+}
+
+#[async_trait]
+impl FillModel for BookWalk {
+    fn name(&self) -> &'static str {
+        "book"
+    }
+
+    async fn fill(&self, order: &SimOrder) -> Option<SimFill> {
+        let book = self.tape.book_at(&order.market_id, order.at_ms).await?;
+        let (mut shares, mut cost) = (0.0, 0.0);
+        for &(price, size) in book.opposite(&order.side) {
+            if shares >= order.shares - 1e-9 || !order.accepts(price) {
+                break;
+            }
+            let take = size.min(order.shares - shares);
+            shares += take;
+            cost += take * price;
+        }
+        (shares > 1e-9).then(|| SimFill {
+            shares,
+            avg_price: cost / shares,
+        })
+    }
+}
+
+/// Fills everything at the price `latency` after the order, or nothing if
+/// the market has moved past the limit by then.
+pub struct LatencyPenalized {
+    tape: Arc<dyn MarketTape>,
+    latency: Duration,
+}
+
+impl LatencyPenalized {
+    pub fn new(tape: Arc<dyn MarketTape>, latency: Duration) -> Self {
+        Self { tape, latency }
+    }
+}
+
+#[async_trait]
+impl FillModel for LatencyPenalized {
+    fn name(&self) -> &'static str {
+        "latency"
+    }
+
+    fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    async fn fill(&self, order: &SimOrder) -> Option<SimFill> {
+        let at = order.at_ms + self.latency.as_millis() as i64;
+        let price = self.tape.price_at(&order.market_id, at).await?;
+        order.accepts(price).then_some(SimFill {
+            shares: order.shares,
+            avg_price: price,
+        })
+    }
+}
+
+/// The model `kind` names, reading `tape`.
+pub fn model(kind: FillModelKind, latency: Duration, tape: Arc<dyn MarketTape>) -> Arc<dyn FillModel> {
+    match kind {
+        FillModelKind::Immediate => Arc::new(Immediate),
+        FillModelKind::Book => Arc::new(BookWalk::new(tape)),
+        FillModelKind::Latency => Arc::new(LatencyPenalized::new(tape, latency)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+    use crate::storage::{Journal, PriceHistory};
+
+    fn sample(sampled_at: i64, mid: f64, book: Option<OrderBook>) -> PriceSample {
+        PriceSample {
+            market_id: "m1".to_string(),
+            sampled_at,
+            mid: Some(mid),
+            last: None,
+            book,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fill_models() {
+        let book = OrderBook::new(vec![(0.48, 50.0)], vec![(0.52, 30.0), (0.50, 20.0), (0.60, 100.0)]);
+        let tape: Arc<dyn MarketTape> = Arc::new(RecordedTape::new(vec![
+            sample(1_000, 0.49, Some(book)),
+            sample(3_000, 0.53, None),
+            sample(5_000, 0.58, None),
+        ]));
+        let order = SimOrder {
+            market_id: "m1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            reference_price: 0.50,
+            limit: Some(0.55),
+            at_ms: 2_000,
+        };
+
+        let immediate = model(FillModelKind::Immediate, Duration::ZERO, tape.clone());
+        assert_eq!(immediate.fill(&order).await.unwrap().avg_price, 0.50);
+
+        // 20 at 0.50 and 30 at 0.52; 0.60 is past the limit
+        let walked = model(FillModelKind::Book, Duration::ZERO, tape.clone())
+            .fill(&order)
+            .await
+            .unwrap();
+        assert_eq!(walked.shares, 50.0);
+        assert!((walked.avg_price - 0.512).abs() < 1e-9);
+        let early = SimOrder {
+            at_ms: 500,
+            ..order.clone()
+        };
+        assert!(model(FillModelKind::Book, Duration::ZERO, tape.clone())
+            .fill(&early)
+            .await
+            .is_none());
+
+        let one_second = model(FillModelKind::Latency, Duration::from_secs(1), tape.clone());
+        assert_eq!(one_second.fill(&order).await.unwrap().avg_price, 0.53);
+        let three_seconds = model(FillModelKind::Latency, Duration::from_secs(3), tape);
+        assert!(three_seconds.fill(&order).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tape_replays_decision_books() {
+        let storage = SqliteStore::open_in_memory().unwrap();
+        storage.record_prices(&[sample(1_000, 0.49, None)]).await.unwrap();
+        let decision = DecisionRecord {
+            id: 0,
+            leader_trade_id: None,
+            wallet: "0xwhale".to_string(),
+            market_id: "m1".to_string(),
+            side: "BUY".to_string(),
+            copied: true,
+            reason: None,
+            detail: None,
+            size_usd: Some(10.0),
+            decided_at: 2_000,
+            arrival_mid: None,
+            arrival_book: Some(OrderBook::new(vec![(0.47, 10.0)], vec![(0.51, 25.0)])),
+        };
+        storage.record_decision(&decision).await.unwrap();
+
+        let tape = RecordedTape::load(&storage, TimeRange::all()).await.unwrap();
+        assert_eq!(tape.len(), 2);
+        assert_eq!(tape.book_at("m1", 2_500).await.unwrap().asks, [(0.51, 25.0)]);
+        assert_eq!(tape.price_at("m1", 1_500).await, Some(0.49));
+    }
+}
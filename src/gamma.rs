@@ -0,0 +1,467 @@
+//! Client for the Gamma API, Polymarket's catalog of events, markets and
+//! tags.
+//!
+//! Markets come back as the bot's [`Market`], keyed by the id they were
+//! asked for: a condition id (0x hex), a CLOB token id or a Gamma id.
+//! Events carry their tags and markets, and a neg-risk event's markets form
+//! a [`NegRiskGroup`] whose outcomes are mutually exclusive. A market's
+//! [`Resolution`] is where its UMA oracle request stands. Listings are
+//! paged through `limit`/`offset` up to the caller's limit, and responses
+//! are reused for `market_cache_ttl`.
+//!
+//! With `gamma_api` set the market catalog ([`crate::markets`]) and leader
+//! screening ([`crate::scout`]) look markets up here.
+
+use crate::api::{number, string_list, unix_seconds};
+use crate::types::{Config, Market};
+use anyhow::{Context, Result};
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Items asked for per page of a listing.
+const PAGE_SIZE: usize = 100;
+
+/// Responses kept before the oldest are dropped.
+const CACHE_CAPACITY: usize = 2048;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Tag {
+    pub id: String,
+    pub label: String,
+    pub slug: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Event {
+    pub id: String,
+    pub slug: String,
+    pub title: String,
+    /// The event's category, or its first tag's label
+    pub category: String,
+    pub tags: Vec<Tag>,
+    pub neg_risk: bool,
+    pub neg_risk_market_id: Option<String>,
+    /// Unix seconds
+    pub end_date: Option<i64>,
+    pub markets: Vec<Market>,
+}
+
+/// The markets of a neg-risk event: one per outcome, exactly one of which
+/// resolves yes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NegRiskGroup {
+    /// The neg-risk market id shared by the group
+    pub id: String,
+    pub event_id: String,
+    pub markets: Vec<Market>,
+}
+
+/// Filters for [`Client::events`] and [`Client::markets`]; unset ones
+/// aren't sent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    /// Tag slug
+    pub tag: Option<String>,
+    pub slug: Option<String>,
+    pub active: Option<bool>,
+    pub closed: Option<bool>,
+    /// Most items to return, across pages
+    pub limit: usize,
+}
+
+impl Query {
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(tag) = &self.tag {
+            params.push(("tag_slug", tag.clone()));
+        }
+        if let Some(slug) = &self.slug {
+            params.push(("slug", slug.clone()));
+        }
+        if let Some(active) = self.active {
+            params.push(("active", active.to_string()));
+        }
+        if let Some(closed) = self.closed {
+            params.push(("closed", closed.to_string()));
+        }
+        params
+    }
+}
+
+/// Where a market's UMA oracle request stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UmaStatus {
+    /// Nothing proposed yet
+    Open,
+    /// An outcome is proposed and can be disputed until the window closes
+    Proposed,
+    /// A proposal was disputed: re-proposed, or voted on by UMA holders
+    Disputed,
+    Resolved,
+}
+
+impl UmaStatus {
+    pub fn parse(s: &str) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "proposed" => UmaStatus::Proposed,
+            "disputed" | "challenged" => UmaStatus::Disputed,
+            "resolved" | "settled" => UmaStatus::Resolved,
+            _ => UmaStatus::Open,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UmaStatus::Open => "open",
+            UmaStatus::Proposed => "proposed",
+            UmaStatus::Disputed => "disputed",
+            UmaStatus::Resolved => "resolved",
+        }
+    }
+}
+
+/// How a market resolves and how far along it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub market_id: String,
+    /// Where the outcome is read from, usually a URL
+    pub source: String,
+    pub status: UmaStatus,
+    /// Every status the request went through, oldest first
+    pub history: Vec<UmaStatus>,
+    /// USDC a proposer or disputer puts up
+    pub bond: f64,
+    /// USDC paid to the proposer
+    pub reward: f64,
+}
+
+impl Resolution {
+    /// Times the request was disputed.
+    pub fn disputes(&self) -> usize {
+        self.history.iter().filter(|s| **s == UmaStatus::Disputed).count()
+    }
+}
+
+pub fn parse_resolution(id: &str, item: &Value) -> Resolution {
+    let history: Vec<UmaStatus> = string_list(&item["umaResolutionStatuses"])
+        .iter()
+        .map(|s| UmaStatus::parse(s))
+        .collect();
+    let status = Some(text(&item["umaResolutionStatus"]))
+        .filter(|s| !s.is_empty())
+        .map(|s| UmaStatus::parse(&s))
+        .or_else(|| history.last().copied())
+        .unwrap_or(UmaStatus::Open);
+    Resolution {
+        market_id: id.to_string(),
+        source: text(&item["resolutionSource"]),
+        status,
+        history,
+        bond: number(&item["umaBond"]).unwrap_or(0.0),
+        reward: number(&item["umaReward"]).unwrap_or(0.0),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn parse_tags(value: &Value) -> Vec<Tag> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|t| Tag {
+            id: text(&t["id"]),
+            label: text(&t["label"]),
+            slug: text(&t["slug"]),
+        })
+        .collect()
+}
+
+/// A Gamma market as the bot's [`Market`], with `id` as its id; `event`
+/// fills in what the market itself leaves out.
+pub fn parse_market(id: &str, item: &Value, event: Option<&Value>) -> Market {
+    let event = event.or_else(|| item["events"].as_array()?.first());
+    let prices: Vec<f64> = string_list(&item["outcomePrices"])
+        .iter()
+        .filter_map(|p| p.parse().ok())
+        .collect();
+    let category = [&item["category"], event.map_or(&Value::Null, |e| &e["category"])]
+        .into_iter()
+        .map(text)
+        .find(|c| !c.is_empty())
+        .or_else(|| parse_tags(&event?["tags"]).into_iter().next().map(|t| t.label))
+        .unwrap_or_default();
+    Market {
+        id: id.to_string(),
+        event_id: event.map(|e| text(&e["id"])).unwrap_or_default(),
+        question: text(&item["question"]),
+        yes_price: prices.first().copied().unwrap_or(0.5),
+        no_price: prices.get(1).copied().unwrap_or(0.5),
+        liquidity: number(&item["liquidityNum"])
+            .or_else(|| number(&item["liquidity"]))
+            .unwrap_or(0.0),
+        volume_24h: number(&item["volume24hr"]).unwrap_or(0.0),
+        slug: text(&item["slug"]),
+        outcomes: string_list(&item["outcomes"]),
+        token_ids: string_list(&item["clobTokenIds"]),
+        tick_size: number(&item["orderPriceMinTickSize"]).unwrap_or(0.01),
+        end_date: unix_seconds(&item["endDate"]),
+        category,
+        resolution_source: text(&item["resolutionSource"]).trim().to_string(),
+    }
+}
+
+/// The id a market is known by to the bot: its condition id when it has
+/// one.
+fn market_id(item: &Value) -> String {
+    item["conditionId"]
+        .as_str()
+        .filter(|id| !id.is_empty())
+        .map_or_else(|| text(&item["id"]), str::to_string)
+}
+
+pub fn parse_event(item: &Value) -> Event {
+    let tags = parse_tags(&item["tags"]);
+    let category = Some(text(&item["category"]))
+        .filter(|c| !c.is_empty())
+        .or_else(|| tags.first().map(|t| t.label.clone()))
+        .unwrap_or_default();
+    Event {
+        id: text(&item["id"]),
+        slug: text(&item["slug"]),
+        title: text(&item["title"]),
+        category,
+        tags,
+        neg_risk: item["negRisk"].as_bool().unwrap_or(false),
+        neg_risk_market_id: item["negRiskMarketID"]
+            .as_str()
+            .filter(|id| !id.is_empty())
+            .map(str::to_string),
+        end_date: unix_seconds(&item["endDate"]),
+        markets: item["markets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|m| parse_market(&market_id(m), m, Some(item)))
+            .collect(),
+    }
+}
+
+pub struct Client {
+    http: HttpClient,
+    base_url: String,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, Value)>>,
+}
+
+impl Client {
+    /// A zero `ttl` disables caching.
+    pub fn new(base_url: impl Into<String>, ttl: Duration) -> Self {
+        Self {
+            http: HttpClient::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `None` when `gamma_api` is empty.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (!config.gamma_api.is_empty()).then(|| Self::new(&config.gamma_api, config.market_cache_ttl))
+    }
+
+    async fn get(&self, path: &str, params: &[(&str, String)]) -> Result<Value> {
+        let url = format!("{}{}", self.base_url, path);
+        let key = format!("{}?{:?}", url, params);
+        if !self.ttl.is_zero() {
+            if let Some((at, value)) = self.cache.lock().unwrap().get(&key) {
+                if at.elapsed() < self.ttl {
+                    return Ok(value.clone());
+                }
+            }
+        }
+        let value = self
+            .http
+            .get(&url)
+            .query(params)
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch {}", path))?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+        if !self.ttl.is_zero() {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.len() >= CACHE_CAPACITY {
+                cache.retain(|_, (at, _)| at.elapsed() < self.ttl);
+                if cache.len() >= CACHE_CAPACITY {
+                    cache.clear();
+                }
+            }
+            cache.insert(key, (Instant::now(), value.clone()));
+        }
+        Ok(value)
+    }
+
+    /// Up to `limit` items of a listing, a page at a time.
+    async fn list(&self, path: &str, params: Vec<(&'static str, String)>, limit: usize) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        while items.len() < limit {
+            let page_size = PAGE_SIZE.min(limit - items.len());
+            let mut page_params = params.clone();
+            page_params.push(("limit", page_size.to_string()));
+            page_params.push(("offset", items.len().to_string()));
+            let page = match self.get(path, &page_params).await? {
+                Value::Array(page) => page,
+                other => other["data"].as_array().cloned().unwrap_or_default(),
+            };
+            let done = page.len() < page_size;
+            items.extend(page);
+            if done {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// A market by condition id, CLOB token id or Gamma id.
+    pub async fn market(&self, id: &str) -> Result<Market> {
+        Ok(parse_market(id, &self.market_item(id).await?, None))
+    }
+
+    /// Where the market's oracle request stands.
+    pub async fn resolution(&self, id: &str) -> Result<Resolution> {
+        Ok(parse_resolution(id, &self.market_item(id).await?))
+    }
+
+    async fn market_item(&self, id: &str) -> Result<Value> {
+        Ok(if id.starts_with("0x") || id.len() > 20 {
+            let key = if id.starts_with("0x") {
+                "condition_ids"
+            } else {
+                "clob_token_ids"
+            };
+            self.get("/markets", &[(key, id.to_string())])
+                .await?
+                .as_array()
+                .and_then(|items| items.first().cloned())
+                .with_context(|| format!("No market {}", id))?
+        } else {
+            self.get(&format!("/markets/{}", id), &[]).await?
+        })
+    }
+
+    pub async fn markets(&self, query: &Query) -> Result<Vec<Market>> {
+        let items = self.list("/markets", query.params(), query.limit).await?;
+        Ok(items.iter().map(|m| parse_market(&market_id(m), m, None)).collect())
+    }
+
+    /// Markets of events matching `query`, at most `limit`.
+    pub async fn search_markets(&self, query: &str, limit: usize) -> Result<Vec<Market>> {
+        let found = self
+            .get(
+                "/public-search",
+                &[("q", query.to_string()), ("limit_per_type", limit.to_string())],
+            )
+            .await?;
+        Ok(found["events"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|e| parse_event(e).markets)
+            .take(limit)
+            .collect())
+    }
+
+    pub async fn event(&self, id: &str) -> Result<Event> {
+        Ok(parse_event(&self.get(&format!("/events/{}", id), &[]).await?))
+    }
+
+    pub async fn events(&self, query: &Query) -> Result<Vec<Event>> {
+        let items = self.list("/events", query.params(), query.limit).await?;
+        Ok(items.iter().map(parse_event).collect())
+    }
+
+    /// Every tag, which Polymarket's categories are.
+    pub async fn tags(&self) -> Result<Vec<Tag>> {
+        Ok(parse_tags(&Value::Array(
+            self.list("/tags", Vec::new(), usize::MAX).await?,
+        )))
+    }
+
+    /// The neg-risk group of an event; `None` for an ordinary event.
+    pub async fn neg_risk_group(&self, event_id: &str) -> Result<Option<NegRiskGroup>> {
+        let event = self.event(event_id).await?;
+        Ok(event.neg_risk.then(|| NegRiskGroup {
+            id: event.neg_risk_market_id.clone().unwrap_or_else(|| event.id.clone()),
+            event_id: event.id,
+            markets: event.markets,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parses_events_markets_and_tags() {
+        let event = json!({
+            "id": "903", "slug": "fed-decision", "title": "Fed decision in December?",
+            "negRisk": true, "negRiskMarketID": "0xnr", "endDate": "2026-12-10T12:00:00Z",
+            "tags": [{ "id": "2", "label": "Economy", "slug": "economy" }],
+            "markets": [{
+                "id": "5170", "conditionId": "0xc1", "question": "50 bps cut?", "slug": "50-bps-cut",
+                "outcomes": "[\"Yes\", \"No\"]", "outcomePrices": "[\"0.12\", \"0.88\"]",
+                "clobTokenIds": "[\"111\", \"222\"]", "liquidityNum": 51234.5, "volume24hr": 9000,
+                "orderPriceMinTickSize": 0.001, "endDate": "2026-12-10T12:00:00Z"
+            }, {
+                "id": "5171", "conditionId": "0xc2", "question": "No change?", "liquidity": "800.25"
+            }]
+        });
+        let parsed = parse_event(&event);
+        assert_eq!(parsed.category, "Economy");
+        assert!(parsed.neg_risk);
+        assert_eq!(parsed.neg_risk_market_id.as_deref(), Some("0xnr"));
+        assert_eq!(parsed.markets.len(), 2);
+        let market = &parsed.markets[0];
+        assert_eq!((market.id.as_str(), market.event_id.as_str()), ("0xc1", "903"));
+        assert_eq!((market.yes_price, market.no_price), (0.12, 0.88));
+        assert_eq!(market.token_ids, ["111", "222"]);
+        assert_eq!(
+            (market.liquidity, market.volume_24h, market.tick_size),
+            (51234.5, 9000.0, 0.001)
+        );
+        assert_eq!(market.category, "Economy");
+        assert_eq!(market.end_date, parsed.end_date);
+        assert_eq!(parsed.markets[1].liquidity, 800.25);
+
+        // A market on its own names its event inline
+        let alone = json!({ "id": "5170", "question": "50 bps cut?", "category": "Fed", "events": [{ "id": "903" }] });
+        let market = parse_market("111", &alone, None);
+        assert_eq!((market.id.as_str(), market.event_id.as_str()), ("111", "903"));
+        assert_eq!(market.category, "Fed");
+
+        let query = Query {
+            tag: Some("economy".to_string()),
+            closed: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.params(),
+            [("tag_slug", "economy".to_string()), ("closed", "false".to_string())]
+        );
+    }
+}
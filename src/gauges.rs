@@ -0,0 +1,121 @@
+//! Queue depths and memory, for spotting backpressure before it costs trades.
+//!
+//! Components register a gauge with a closure that reads its current value;
+//! `/metrics` reads them all on every scrape. The bot registers the depth of
+//! the trade channel between the feeds and the trade loop, the number of
+//! trades in the dedup cache and the resident memory of the process. A trade
+//! channel that keeps filling up means the trade loop can't keep pace with
+//! the leaders, and once it is full the feeds stall.
+
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+type Read = Box<dyn Fn() -> Option<f64> + Send + Sync>;
+
+struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    read: Read,
+}
+
+/// A gauge's name and current value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GaugeReading {
+    pub name: &'static str,
+    pub value: f64,
+}
+
+/// Gauges read on demand.
+#[derive(Default)]
+pub struct Gauges {
+    gauges: Mutex<Vec<Gauge>>,
+}
+
+impl Gauges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a gauge; `read` returns `None` when there is no value yet, e.g.
+    /// for a channel that hasn't been created.
+    pub fn register(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        read: impl Fn() -> Option<f64> + Send + Sync + 'static,
+    ) {
+        self.gauges.lock().unwrap().push(Gauge {
+            name,
+            help,
+            read: Box::new(read),
+        });
+    }
+
+    /// Every gauge that has a value, in registration order.
+    pub fn read(&self) -> Vec<GaugeReading> {
+        self.gauges
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|g| (g.read)().map(|value| GaugeReading { name: g.name, value }))
+            .collect()
+    }
+
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        for gauge in self.gauges.lock().unwrap().iter() {
+            let Some(value) = (gauge.read)() else {
+                continue;
+            };
+            let _ = writeln!(out, "# HELP {} {}", gauge.name, gauge.help);
+            let _ = writeln!(out, "# TYPE {} gauge", gauge.name);
+            let _ = writeln!(out, "{} {}", gauge.name, value);
+        }
+        out
+    }
+}
+
+/// Resident memory of this process in bytes; `None` off Linux.
+pub fn resident_memory_bytes() -> Option<f64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb: f64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_gauges_read_on_demand() {
+        let gauges = Gauges::new();
+        let depth = Arc::new(AtomicUsize::new(3));
+        let read = Arc::clone(&depth);
+        gauges.register("queue_depth", "Items waiting.", move || {
+            Some(read.load(Ordering::Relaxed) as f64)
+        });
+        gauges.register("not_started", "Nothing yet.", || None);
+
+        let metrics = gauges.render_prometheus();
+        assert!(metrics.contains("# TYPE queue_depth gauge\nqueue_depth 3\n"));
+        assert!(!metrics.contains("not_started"));
+        depth.store(7, Ordering::Relaxed);
+        assert_eq!(
+            gauges.read(),
+            vec![GaugeReading {
+                name: "queue_depth",
+                value: 7.0
+            }]
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! Health and readiness endpoints for systemd, Docker and Kubernetes.
+//!
+//! With `health_addr` set the bot's HTTP server (see [`crate::http`])
+//! answers two requests with a JSON report of its subsystems:
+//!
+//! - `GET /healthz` (liveness) fails only when the trade loop has stalled,
+//!   i.e. when restarting the process is the fix
+//! - `GET /readyz` (readiness) also requires every leader feed connected,
+//!   the RPC node answering, the exchange accepting the executor's
+//!   credentials and storage reachable
+//!
+//! `GET /metrics` serves the stage latency histograms (see [`crate::latency`])
+//! and per-provider RPC metrics (see [`crate::rpc`]) in Prometheus text
+//! format.
+//!
+//! Failing reports are answered with `503`. Under systemd (see
+//! [`crate::daemon`]) the bot reports ready once `/readyz` would pass and,
+//! with `WatchdogSec=`, pings the watchdog while it is live.
+
+use crate::daemon;
+use crate::events::ConnectionState;
+use crate::executor::TradeExecutor;
+use crate::gauges::Gauges;
+use crate::http::{Handler, Request, Response};
+use crate::incidents::IncidentMonitor;
+use crate::latency::LatencyStats;
+use crate::rpc::{self, RpcStats};
+use crate::skips::SkipStats;
+use crate::storage::{now_ms, Storage};
+use crate::units::format_duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::providers::Middleware;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long a single readiness probe may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Fail,
+    /// Not configured, so not checked
+    Disabled,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl Check {
+    fn ok() -> Self {
+        Self {
+            status: CheckStatus::Ok,
+            detail: None,
+            details: None,
+        }
+    }
+
+    fn fail(detail: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Fail,
+            detail: Some(detail.into()),
+            details: None,
+        }
+    }
+
+    fn disabled(detail: &str) -> Self {
+        Self {
+            status: CheckStatus::Disabled,
+            detail: Some(detail.to_string()),
+            details: None,
+        }
+    }
+
+    fn from_result(result: Result<()>) -> Self {
+        match result {
+            Ok(()) => Self::ok(),
+            Err(e) => Self::fail(format!("{:#}", e)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub status: CheckStatus,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+impl HealthReport {
+    fn new(checks: BTreeMap<&'static str, Check>) -> Self {
+        let failing = checks.values().any(|c| c.status == CheckStatus::Fail);
+        Self {
+            status: if failing { CheckStatus::Fail } else { CheckStatus::Ok },
+            checks,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.status != CheckStatus::Fail
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedState {
+    pub connected: bool,
+    /// Unix ms of the last change
+    pub since: i64,
+    pub detail: Option<String>,
+}
+
+/// Connection state of each leader feed, kept up to date by the watcher.
+#[derive(Debug, Default)]
+pub struct FeedStatus {
+    feeds: Mutex<BTreeMap<String, FeedState>>,
+}
+
+impl FeedStatus {
+    /// Starts tracking `wallets`, all disconnected.
+    pub fn new(wallets: &[String], now_ms: i64) -> Self {
+        let feeds = wallets
+            .iter()
+            .map(|wallet| {
+                let state = FeedState {
+                    connected: false,
+                    since: now_ms,
+                    detail: Some("not connected yet".to_string()),
+                };
+                (wallet.clone(), state)
+            })
+            .collect();
+        Self {
+            feeds: Mutex::new(feeds),
+        }
+    }
+
+    pub fn update(&self, wallet: &str, state: ConnectionState, detail: Option<String>, now_ms: i64) {
+        let connected = state == ConnectionState::Connected;
+        let mut feeds = self.feeds.lock().unwrap();
+        let feed = feeds.entry(wallet.to_string()).or_insert(FeedState {
+            connected,
+            since: now_ms,
+            detail: None,
+        });
+        if feed.connected != connected {
+            feed.since = now_ms;
+        }
+        feed.connected = connected;
+        feed.detail = detail;
+    }
+
+    /// Stops tracking a wallet no longer watched.
+    pub fn remove(&self, wallet: &str) {
+        self.feeds.lock().unwrap().remove(wallet);
+    }
+
+    pub fn states(&self) -> BTreeMap<String, FeedState> {
+        self.feeds.lock().unwrap().clone()
+    }
+
+    fn check(&self, now_ms: i64) -> Check {
+        let feeds = self.feeds.lock().unwrap();
+        let down: Vec<&String> = feeds.iter().filter(|(_, f)| !f.connected).map(|(w, _)| w).collect();
+        let mut check = if down.is_empty() {
+            Check::ok()
+        } else {
+            Check::fail(format!("{} of {} feeds down", down.len(), feeds.len()))
+        };
+        let details = feeds
+            .iter()
+            .map(|(wallet, feed)| {
+                let age = Duration::from_millis((now_ms - feed.since).max(0) as u64);
+                let value = json!({
+                    "connected": feed.connected,
+                    "for": format_duration(age),
+                    "detail": feed.detail,
+                });
+                (wallet.clone(), value)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        check.details = Some(details.into());
+        check
+    }
+}
+
+/// Runs the checks behind `/healthz` and `/readyz`.
+pub struct HealthChecker {
+    feeds: Arc<FeedStatus>,
+    incidents: Arc<IncidentMonitor>,
+    rpc_url: String,
+    executor: TradeExecutor,
+    storage: Option<Arc<dyn Storage>>,
+    latency: Arc<LatencyStats>,
+    rpc: Arc<RpcStats>,
+    skips: Arc<SkipStats>,
+    gauges: Arc<Gauges>,
+}
+
+impl HealthChecker {
+    pub fn new(
+        feeds: Arc<FeedStatus>,
+        incidents: Arc<IncidentMonitor>,
+        rpc_url: &str,
+        executor: TradeExecutor,
+        storage: Option<Arc<dyn Storage>>,
+        latency: Arc<LatencyStats>,
+    ) -> Self {
+        Self {
+            feeds,
+            incidents,
+            rpc_url: rpc_url.to_string(),
+            executor,
+            storage,
+            latency,
+            rpc: Arc::new(RpcStats::new()),
+            skips: Arc::new(SkipStats::new()),
+            gauges: Arc::new(Gauges::new()),
+        }
+    }
+
+    /// Where RPC calls are recorded, and read from for `/metrics`.
+    pub fn with_rpc_stats(mut self, rpc: Arc<RpcStats>) -> Self {
+        self.rpc = rpc;
+        self
+    }
+
+    /// Where copy decisions are counted, for `/metrics`.
+    pub fn with_skip_stats(mut self, skips: Arc<SkipStats>) -> Self {
+        self.skips = skips;
+        self
+    }
+
+    /// Queue depth and memory gauges for `/metrics`.
+    pub fn with_gauges(mut self, gauges: Arc<Gauges>) -> Self {
+        self.gauges = gauges;
+        self
+    }
+
+    fn trade_loop(&self, now_ms: i64) -> Check {
+        match self.incidents.stalled(now_ms) {
+            Some((stalled_for, waiting)) => Check::fail(format!(
+                "no trades processed for {} while {} waited",
+                format_duration(stalled_for),
+                waiting
+            )),
+            None => Check::ok(),
+        }
+    }
+
+    /// Whether the process is worth keeping: only a stalled trade loop
+    /// fails it.
+    pub fn liveness(&self, now_ms: i64) -> HealthReport {
+        HealthReport::new(BTreeMap::from([("trade_loop", self.trade_loop(now_ms))]))
+    }
+
+    /// Whether the bot can copy right now.
+    pub async fn readiness(&self, now_ms: i64) -> HealthReport {
+        let (rpc, executor, storage) = tokio::join!(
+            self.check_rpc(),
+            probe(self.executor.check_auth()),
+            self.check_storage()
+        );
+        HealthReport::new(BTreeMap::from([
+            ("trade_loop", self.trade_loop(now_ms)),
+            ("feeds", self.feeds.check(now_ms)),
+            ("rpc", rpc),
+            ("executor", executor),
+            ("storage", storage),
+        ]))
+    }
+
+    async fn check_rpc(&self) -> Check {
+        if self.rpc_url.is_empty() {
+            return Check::disabled("no rpc_url");
+        }
+        probe(self.query_rpc()).await
+    }
+
+    async fn query_rpc(&self) -> Result<()> {
+        let block = if self.rpc_url.starts_with("ws") {
+            rpc::connect_ws(&self.rpc_url, &self.rpc)
+                .await?
+                .get_block_number()
+                .await?
+        } else {
+            rpc::http(&self.rpc_url, &self.rpc)?.get_block_number().await?
+        };
+        anyhow::ensure!(!block.is_zero(), "RPC node reports block 0");
+        Ok(())
+    }
+
+    async fn check_storage(&self) -> Check {
+        match &self.storage {
+            Some(storage) => probe(async { storage.load_state("health_check").await.map(|_| ()) }).await,
+            None => Check::disabled("no storage_url"),
+        }
+    }
+
+    /// Pings the systemd watchdog at half its interval while the bot is
+    /// live; does nothing unless systemd asked for it.
+    pub fn spawn_watchdog(self: Arc<Self>) {
+        let Some(interval) = daemon::watchdog_interval() else {
+            return;
+        };
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval / 2);
+            loop {
+                ticks.tick().await;
+                if self.liveness(now_ms()).is_healthy() {
+                    daemon::notify("WATCHDOG=1");
+                }
+            }
+        });
+    }
+
+    /// Tells systemd the bot is ready once every readiness check passes.
+    pub fn spawn_ready_notice(self: Arc<Self>) {
+        if !daemon::is_supervised() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                ticks.tick().await;
+                if self.readiness(now_ms()).await.is_healthy() {
+                    tracing::info!("✅ All subsystems ready, notifying systemd");
+                    daemon::notify("READY=1\nSTATUS=Copying trades");
+                    return;
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Handler for HealthChecker {
+    async fn handle(&self, request: &Request) -> Option<Response> {
+        if !matches!(request.path.as_str(), "/healthz" | "/readyz" | "/metrics") {
+            return None;
+        }
+        if !matches!(request.method.as_str(), "GET" | "HEAD") {
+            return Some(Response::error(405, "method not allowed"));
+        }
+        Some(match request.path.as_str() {
+            "/healthz" => report_response(&self.liveness(now_ms())),
+            "/readyz" => report_response(&self.readiness(now_ms()).await),
+            _ => {
+                let metrics = [
+                    self.latency.render_prometheus(),
+                    self.rpc.render_prometheus(),
+                    self.skips.render_prometheus(),
+                    self.gauges.render_prometheus(),
+                ];
+                Response::text(200, "text/plain; version=0.0.4", metrics.concat())
+            }
+        })
+    }
+}
+
+/// Runs a probe, failing it if it takes longer than [`PROBE_TIMEOUT`].
+async fn probe(check: impl Future<Output = Result<()>>) -> Check {
+    match tokio::time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(result) => Check::from_result(result),
+        Err(_) => Check::fail(format!("no answer within {}", format_duration(PROBE_TIMEOUT))),
+    }
+}
+
+fn report_response(report: &HealthReport) -> Response {
+    let status = if report.is_healthy() { 200 } else { 503 };
+    Response::json(status, report)
+}
+
+/// The notify socket and watchdog interval systemd passed, if it expects
+/// this process to ping.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_status() {
+        let wallets = vec!["0xabc".to_string(), "0xdef".to_string()];
+        let feeds = FeedStatus::new(&wallets, 0);
+        assert_eq!(feeds.check(0).status, CheckStatus::Fail);
+
+        feeds.update("0xabc", ConnectionState::Connected, None, 1_000);
+        feeds.update("0xdef", ConnectionState::Connected, None, 1_000);
+        assert_eq!(feeds.check(2_000).status, CheckStatus::Ok);
+
+        feeds.update("0xdef", ConnectionState::Disconnected, Some("reset".to_string()), 3_000);
+        let check = feeds.check(63_000);
+        assert_eq!(check.detail.as_deref(), Some("1 of 2 feeds down"));
+        let details = check.details.unwrap();
+        assert_eq!(details["0xdef"]["connected"], false);
+        assert_eq!(details["0xdef"]["detail"], "reset");
+        assert_eq!(details["0xabc"]["connected"], true);
+    }
+
+    #[test]
+    fn test_report_status() {
+        let report = HealthReport::new(BTreeMap::from([
+            ("rpc", Check::disabled("no rpc_url")),
+            ("storage", Check::ok()),
+        ]));
+        assert!(report.is_healthy());
+        assert_eq!(report_response(&report).status, 200);
+
+        let report = HealthReport::new(BTreeMap::from([("storage", Check::fail("locked"))]));
+        let response = report_response(&report);
+        assert_eq!(response.status, 503);
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["status"], "fail");
+        assert_eq!(body["checks"]["storage"]["detail"], "locked");
+    }
+}
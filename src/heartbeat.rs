@@ -0,0 +1,158 @@
+//! Dead man's switch: an outside monitor that alerts when the bot goes quiet.
+//!
+//! With `heartbeat_url` set (e.g. a healthchecks.io or Cronitor ping URL),
+//! the bot POSTs a compact JSON status there every `heartbeat_interval`. If
+//! the process dies, hangs or loses its network, the pings stop and the
+//! monitor raises the alarm - the one failure the bot can't report itself.
+//!
+//! The body looks like
+//! `{"status":"ok","version":"0.1.0","uptime_secs":3600,"feeds_connected":2,...}`,
+//! with `status` one of `ok`, `paused`, `tripped` or `feeds_down`. Monitors
+//! that filter on body keywords can treat the last three as failures.
+
+use crate::health::FeedStatus;
+use crate::notify::BotControl;
+use crate::types::Config;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub struct Heartbeat {
+    client: reqwest::Client,
+    url: String,
+    interval: Duration,
+    control: Arc<BotControl>,
+    feeds: Arc<FeedStatus>,
+    started: Instant,
+}
+
+impl Heartbeat {
+    pub fn new(url: String, interval: Duration, control: Arc<BotControl>, feeds: Arc<FeedStatus>) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            url,
+            interval,
+            control,
+            feeds,
+            started: Instant::now(),
+        }
+    }
+
+    /// `None` unless `heartbeat_url` is set and `heartbeat_interval` isn't zero.
+    pub fn from_config(config: &Config, control: Arc<BotControl>, feeds: Arc<FeedStatus>) -> Option<Self> {
+        if config.heartbeat_url.is_empty() || config.heartbeat_interval.is_zero() {
+            return None;
+        }
+        Some(Self::new(
+            config.heartbeat_url.clone(),
+            config.heartbeat_interval,
+            control,
+            feeds,
+        ))
+    }
+
+    /// The status sent with each ping.
+    pub fn payload(&self) -> Value {
+        let portfolio = self.control.portfolio();
+        let risk = self.control.risk().headroom();
+        let feeds = self.feeds.states();
+        let connected = feeds.values().filter(|f| f.connected).count();
+        let status = if risk.tripped {
+            "tripped"
+        } else if self.control.is_paused() {
+            "paused"
+        } else if connected < feeds.len() {
+            "feeds_down"
+        } else {
+            "ok"
+        };
+        json!({
+            "status": status,
+            "version": env!("CARGO_PKG_VERSION"),
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "feeds_connected": connected,
+            "feeds": feeds.len(),
+            "positions": portfolio.holdings().len(),
+            "exposure_usd": portfolio.exposure(),
+            "trades_today": risk.trades_today,
+            "realized_pnl_today": risk.realized_pnl_today,
+        })
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(self.payload().to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Pings every interval, the first after one interval so the feeds
+    /// have had time to connect. Failures are logged once until a ping gets
+    /// through again.
+    pub fn spawn(self: Arc<Self>) {
+        tracing::info!("💓 Heartbeat every {}", crate::units::format_duration(self.interval));
+        tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + self.interval;
+            let mut interval = tokio::time::interval_at(start, self.interval);
+            let mut failing = false;
+            loop {
+                interval.tick().await;
+                match self.ping().await {
+                    Ok(()) if failing => {
+                        failing = false;
+                        tracing::info!("💓 Heartbeat delivered again");
+                    }
+                    Ok(()) => {}
+                    Err(e) if !failing => {
+                        failing = true;
+                        tracing::warn!("Heartbeat ping failed: {:#}", e);
+                    }
+                    Err(e) => tracing::debug!("Heartbeat ping failed: {:#}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ConnectionState;
+    use crate::portfolio::Portfolio;
+    use crate::risk::RiskManager;
+    use crate::types::CostBasis;
+
+    #[test]
+    fn test_payload_status() {
+        let config = Config {
+            heartbeat_url: "https://hc-ping.com/uuid".to_string(),
+            ..Default::default()
+        };
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Average, None));
+        let control = Arc::new(BotControl::new(portfolio, Arc::new(RiskManager::new(config.clone()))));
+        let feeds = Arc::new(FeedStatus::new(&["0xabc".to_string()], 0));
+        let heartbeat = Heartbeat::from_config(&config, Arc::clone(&control), Arc::clone(&feeds)).unwrap();
+
+        assert_eq!(heartbeat.payload()["status"], "feeds_down");
+        feeds.update("0xabc", ConnectionState::Connected, None, 10);
+        let payload = heartbeat.payload();
+        assert_eq!(payload["status"], "ok");
+        assert_eq!(payload["feeds_connected"], 1);
+        control.risk().trip("test");
+        assert_eq!(heartbeat.payload()["status"], "tripped");
+
+        let disabled = Config {
+            heartbeat_interval: Duration::ZERO,
+            ..config
+        };
+        assert!(Heartbeat::from_config(&disabled, control, feeds).is_none());
+    }
+}
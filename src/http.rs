@@ -0,0 +1,228 @@
+//! The bot's small built-in HTTP server.
+//!
+//! Health checks, metrics and the status API are all served on
+//! `health_addr` by handlers that each answer the paths they know. It is a
+//! deliberately minimal HTTP/1.1 responder: one request per connection,
+//! bodies up to [`MAX_BODY`], no TLS (bind it to localhost or put a proxy
+//! in front).
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEAD: usize = 16 * 1024;
+pub const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    /// Names lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// A query string parameter, e.g. `limit` in `?limit=20`.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+    }
+
+    fn parse_head(head: &str) -> Result<Self> {
+        let mut lines = head.split("\r\n");
+        let mut words = lines.next().unwrap_or_default().split_whitespace();
+        let method = words.next().context("Empty request")?.to_string();
+        let target = words.next().context("Request line without a path")?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let headers = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        Ok(Self {
+            method,
+            path: path.to_string(),
+            query: query.to_string(),
+            headers,
+            body: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, value: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string_pretty(value).unwrap_or_default(),
+        }
+    }
+
+    pub fn text(status: u16, content_type: &'static str, body: String) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self::json(status, &json!({ "error": message.to_string() }))
+    }
+
+    fn to_http(&self, head_only: bool) -> String {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            500 => "Internal Server Error",
+            _ => "Service Unavailable",
+        };
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len(),
+            if head_only { "" } else { &self.body }
+        )
+    }
+}
+
+/// Answers the requests for some set of paths.
+#[async_trait]
+pub trait Handler: Send + Sync {
+    /// `None` if the path isn't one of this handler's.
+    async fn handle(&self, request: &Request) -> Option<Response>;
+}
+
+/// Serves `handlers` on `addr`, e.g. `127.0.0.1:9090`; each request goes
+/// to the first handler that answers it.
+pub async fn serve(addr: &str, handlers: Vec<Arc<dyn Handler>>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    let handlers: Arc<[Arc<dyn Handler>]> = handlers.into();
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let handlers = Arc::clone(&handlers);
+                    tokio::spawn(async move {
+                        if let Err(e) = answer(stream, &handlers).await {
+                            tracing::debug!("HTTP request failed: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => tracing::warn!("HTTP listener error: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn answer(mut stream: TcpStream, handlers: &[Arc<dyn Handler>]) -> Result<()> {
+    let request = tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream))
+        .await
+        .context("Timed out reading request")?;
+    let (response, head_only) = match request {
+        Ok(request) => (dispatch(handlers, &request).await, request.method == "HEAD"),
+        Err(e) => (Response::error(400, format!("{:#}", e)), false),
+    };
+    stream.write_all(response.to_http(head_only).as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn dispatch(handlers: &[Arc<dyn Handler>], request: &Request) -> Response {
+    for handler in handlers {
+        if let Some(response) = handler.handle(request).await {
+            return response;
+        }
+    }
+    Response::error(404, "not found")
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        anyhow::ensure!(buf.len() < MAX_HEAD, "Request headers too large");
+        let n = stream.read(&mut chunk).await?;
+        anyhow::ensure!(n > 0, "Connection closed mid-request");
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let mut request = Request::parse_head(&String::from_utf8_lossy(&buf[..head_end]))?;
+    let length: usize = match request.header("content-length") {
+        Some(value) => value.parse().context("Invalid Content-Length")?,
+        None => 0,
+    };
+    anyhow::ensure!(length <= MAX_BODY, "Request body over {} bytes", MAX_BODY);
+    let mut body = buf.split_off(head_end);
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        anyhow::ensure!(n > 0, "Connection closed mid-body");
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(length);
+    request.body = body;
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = Request::parse_head(
+            "GET /status/decisions?limit=5&x HTTP/1.1\r\nHost: bot\r\nAuthorization: Bearer s3cret\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/status/decisions");
+        assert_eq!(request.query_param("limit"), Some("5"));
+        assert_eq!(request.query_param("x"), Some(""));
+        assert_eq!(request.query_param("y"), None);
+        assert_eq!(request.header("authorization"), Some("Bearer s3cret"));
+        assert!(Request::parse_head("\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_response() {
+        let head = Response::json(503, &json!({})).to_http(true);
+        assert!(head.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(head.ends_with("Content-Length: 2\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n"));
+    }
+}
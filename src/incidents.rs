@@ -0,0 +1,454 @@
+//! On-call incidents, separate from chat notifications.
+//!
+//! Notifications report single events; incidents track conditions that
+//! last. The monitor opens an incident in PagerDuty or Opsgenie when one
+//! starts and resolves it once it has cleared:
+//!
+//! - **stalled**: leader trades arrived but none has been processed for
+//!   `incident_stall_after`
+//! - **executor_errors**: at least `incident_min_orders` orders were placed
+//!   within `incident_error_window` and more than `incident_error_rate` of
+//!   them failed
+
+use crate::types::Config;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SOURCE: &str = "polymarket-bot";
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IncidentKind {
+    Stalled,
+    ExecutorErrors,
+}
+
+impl IncidentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IncidentKind::Stalled => "stalled",
+            IncidentKind::ExecutorErrors => "executor_errors",
+        }
+    }
+
+    /// Identifies the incident to the on-call service, so it can be
+    /// resolved later and isn't opened twice.
+    pub fn dedup_key(&self) -> String {
+        format!("{}:{}", SOURCE, self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incident {
+    pub kind: IncidentKind,
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IncidentChange {
+    Open(Incident),
+    Resolve(IncidentKind),
+}
+
+/// An on-call service incidents are opened in.
+#[async_trait]
+pub trait IncidentSink: Send + Sync {
+    fn name(&self) -> &str;
+    async fn open(&self, incident: &Incident) -> Result<()>;
+    async fn resolve(&self, kind: IncidentKind) -> Result<()>;
+}
+
+/// PagerDuty Events API v2, with the incident kind as dedup key.
+pub struct PagerDutyIncidents {
+    client: reqwest::Client,
+    routing_key: String,
+}
+
+impl PagerDutyIncidents {
+    pub fn new(routing_key: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            routing_key: routing_key.to_string(),
+        }
+    }
+
+    fn event(&self, kind: IncidentKind, incident: Option<&Incident>) -> serde_json::Value {
+        let mut event = json!({
+            "routing_key": self.routing_key,
+            "event_action": if incident.is_some() { "trigger" } else { "resolve" },
+            "dedup_key": kind.dedup_key(),
+        });
+        if let Some(incident) = incident {
+            event["payload"] = json!({
+                "summary": incident.summary,
+                "source": SOURCE,
+                "severity": "critical",
+                "class": kind.as_str(),
+            });
+        }
+        event
+    }
+
+    async fn send(&self, event: serde_json::Value) -> Result<()> {
+        let resp = self
+            .client
+            .post(PAGERDUTY_EVENTS_URL)
+            .json(&event)
+            .send()
+            .await
+            .context("Failed to reach PagerDuty")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            anyhow::bail!("PagerDuty returned {}: {}", status, body);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IncidentSink for PagerDutyIncidents {
+    fn name(&self) -> &str {
+        "pagerduty"
+    }
+
+    async fn open(&self, incident: &Incident) -> Result<()> {
+        self.send(self.event(incident.kind, Some(incident))).await
+    }
+
+    async fn resolve(&self, kind: IncidentKind) -> Result<()> {
+        self.send(self.event(kind, None)).await
+    }
+}
+
+/// Opsgenie Alert API, with the incident kind as alert alias.
+pub struct OpsgenieIncidents {
+    client: reqwest::Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl OpsgenieIncidents {
+    pub fn new(api_key: &str, base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn alert(incident: &Incident) -> serde_json::Value {
+        // Opsgenie truncates messages beyond 130 characters
+        let message: String = incident.summary.chars().take(130).collect();
+        json!({
+            "message": message,
+            "alias": incident.kind.dedup_key(),
+            "description": incident.summary,
+            "priority": "P1",
+            "source": SOURCE,
+            "tags": [SOURCE, incident.kind.as_str()],
+        })
+    }
+
+    async fn post(&self, url: String, body: serde_json::Value) -> Result<()> {
+        let resp = self
+            .client
+            .post(url)
+            .header("Authorization", format!("GenieKey {}", self.api_key))
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to reach Opsgenie")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Opsgenie returned {}: {}", status, body);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IncidentSink for OpsgenieIncidents {
+    fn name(&self) -> &str {
+        "opsgenie"
+    }
+
+    async fn open(&self, incident: &Incident) -> Result<()> {
+        self.post(format!("{}/v2/alerts", self.base_url), Self::alert(incident))
+            .await
+    }
+
+    async fn resolve(&self, kind: IncidentKind) -> Result<()> {
+        let url = format!(
+            "{}/v2/alerts/{}/close?identifierType=alias",
+            self.base_url,
+            kind.dedup_key()
+        );
+        self.post(url, json!({"source": SOURCE})).await
+    }
+}
+
+/// The on-call services configured in `config`.
+pub fn sinks_from_config(config: &Config) -> Vec<Arc<dyn IncidentSink>> {
+    let mut sinks: Vec<Arc<dyn IncidentSink>> = Vec::new();
+    if !config.pagerduty_routing_key.is_empty() {
+        sinks.push(Arc::new(PagerDutyIncidents::new(&config.pagerduty_routing_key)));
+    }
+    if !config.opsgenie_api_key.is_empty() {
+        sinks.push(Arc::new(OpsgenieIncidents::new(
+            &config.opsgenie_api_key,
+            &config.opsgenie_url,
+        )));
+    }
+    sinks
+}
+
+#[derive(Debug, Default)]
+struct State {
+    trades_seen: u64,
+    trades_processed: u64,
+    /// Unix ms of the last processed trade, or of the first one since then
+    /// that is still waiting
+    last_progress: i64,
+    /// Unix ms and whether it failed, for orders within the error window
+    orders: VecDeque<(i64, bool)>,
+    open: BTreeSet<IncidentKind>,
+}
+
+/// Watches trade processing and order outcomes for incident conditions.
+pub struct IncidentMonitor {
+    stall_after_ms: i64,
+    error_rate: f64,
+    error_window_ms: i64,
+    min_orders: usize,
+    sinks: Vec<Arc<dyn IncidentSink>>,
+    state: Mutex<State>,
+}
+
+impl IncidentMonitor {
+    /// A zero `stall_after` or `error_rate` disables that condition.
+    pub fn new(stall_after: Duration, error_rate: f64, error_window: Duration, min_orders: usize) -> Self {
+        Self {
+            stall_after_ms: stall_after.as_millis() as i64,
+            error_rate,
+            error_window_ms: error_window.as_millis() as i64,
+            min_orders: min_orders.max(1),
+            sinks: Vec::new(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.incident_stall_after,
+            config.incident_error_rate,
+            config.incident_error_window,
+            config.incident_min_orders as usize,
+        )
+        .with_sinks(sinks_from_config(config))
+    }
+
+    pub fn with_sinks(mut self, sinks: Vec<Arc<dyn IncidentSink>>) -> Self {
+        self.sinks = sinks;
+        self
+    }
+
+    /// A leader trade arrived from a feed.
+    pub fn trade_seen(&self, now_ms: i64) {
+        let mut state = self.state.lock().unwrap();
+        if state.trades_seen == state.trades_processed {
+            state.last_progress = now_ms;
+        }
+        state.trades_seen += 1;
+    }
+
+    /// The trade loop finished with a trade.
+    pub fn trade_processed(&self, now_ms: i64) {
+        let mut state = self.state.lock().unwrap();
+        state.trades_processed = (state.trades_processed + 1).min(state.trades_seen);
+        state.last_progress = now_ms;
+    }
+
+    pub fn order_result(&self, now_ms: i64, failed: bool) {
+        self.state.lock().unwrap().orders.push_back((now_ms, failed));
+    }
+
+    /// How long the trade loop has made no progress with trades waiting,
+    /// and how many, once that reaches `incident_stall_after`.
+    pub fn stalled(&self, now_ms: i64) -> Option<(Duration, u64)> {
+        Self::stall(&self.state.lock().unwrap(), self.stall_after_ms, now_ms)
+    }
+
+    fn stall(state: &State, stall_after_ms: i64, now_ms: i64) -> Option<(Duration, u64)> {
+        let waiting = state.trades_seen - state.trades_processed;
+        let stalled_for = now_ms - state.last_progress;
+        (stall_after_ms > 0 && waiting > 0 && stalled_for >= stall_after_ms)
+            .then(|| (Duration::from_millis(stalled_for as u64), waiting))
+    }
+
+    /// Incidents that started or cleared by `now_ms`.
+    pub fn evaluate(&self, now_ms: i64) -> Vec<IncidentChange> {
+        let mut state = self.state.lock().unwrap();
+        while state
+            .orders
+            .front()
+            .is_some_and(|(at, _)| now_ms - at > self.error_window_ms)
+        {
+            state.orders.pop_front();
+        }
+
+        let mut changes = Vec::new();
+        let stalled = Self::stall(&state, self.stall_after_ms, now_ms).map(|(stalled_for, waiting)| {
+            format!(
+                "No leader trades processed for {} while {} waited",
+                crate::units::format_duration(stalled_for),
+                waiting
+            )
+        });
+        changes.extend(Self::transition(&mut state.open, IncidentKind::Stalled, stalled));
+
+        let failed = state.orders.iter().filter(|(_, failed)| *failed).count();
+        let total = state.orders.len();
+        let erroring = (self.error_rate > 0.0
+            && total >= self.min_orders
+            && failed as f64 / total as f64 > self.error_rate)
+            .then(|| {
+                format!(
+                    "Executor error rate {:.0}%: {} of {} orders failed in the last {}",
+                    failed as f64 / total as f64 * 100.0,
+                    failed,
+                    total,
+                    crate::units::format_duration(Duration::from_millis(self.error_window_ms as u64))
+                )
+            });
+        changes.extend(Self::transition(
+            &mut state.open,
+            IncidentKind::ExecutorErrors,
+            erroring,
+        ));
+        changes
+    }
+
+    /// Opens `kind` when `summary` says it holds, resolves it when it no
+    /// longer does.
+    fn transition(
+        open: &mut BTreeSet<IncidentKind>,
+        kind: IncidentKind,
+        summary: Option<String>,
+    ) -> Option<IncidentChange> {
+        match summary {
+            Some(summary) if open.insert(kind) => Some(IncidentChange::Open(Incident { kind, summary })),
+            None if open.remove(&kind) => Some(IncidentChange::Resolve(kind)),
+            _ => None,
+        }
+    }
+
+    async fn deliver(&self, change: &IncidentChange) {
+        for sink in &self.sinks {
+            let result = match change {
+                IncidentChange::Open(incident) => sink.open(incident).await,
+                IncidentChange::Resolve(kind) => sink.resolve(*kind).await,
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to update {} incident: {:#}", sink.name(), e);
+            }
+        }
+    }
+
+    /// Checks the conditions every few seconds and updates the on-call
+    /// services; does nothing without any configured.
+    pub fn spawn(self: Arc<Self>) {
+        if self.sinks.is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(10));
+            loop {
+                interval.tick().await;
+                for change in self.evaluate(chrono::Utc::now().timestamp_millis()) {
+                    match &change {
+                        IncidentChange::Open(incident) => tracing::error!("🚨 Incident opened: {}", incident.summary),
+                        IncidentChange::Resolve(kind) => tracing::info!("✅ Incident resolved: {}", kind.as_str()),
+                    }
+                    self.deliver(&change).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+
+    #[test]
+    fn test_stalled_trade_loop() {
+        let monitor = IncidentMonitor::new(Duration::from_secs(30 * 60), 0.0, Duration::ZERO, 1);
+        monitor.trade_seen(0);
+        monitor.trade_processed(MINUTE);
+        // Nothing waiting: quiet leaders are not a stall
+        assert!(monitor.evaluate(60 * MINUTE).is_empty());
+
+        monitor.trade_seen(61 * MINUTE);
+        assert!(monitor.evaluate(90 * MINUTE).is_empty());
+        let changes = monitor.evaluate(91 * MINUTE);
+        assert!(matches!(&changes[..], [IncidentChange::Open(i)] if i.kind == IncidentKind::Stalled));
+        assert!(monitor.evaluate(92 * MINUTE).is_empty());
+
+        monitor.trade_processed(93 * MINUTE);
+        assert_eq!(
+            monitor.evaluate(93 * MINUTE),
+            vec![IncidentChange::Resolve(IncidentKind::Stalled)]
+        );
+    }
+
+    #[test]
+    fn test_executor_error_rate() {
+        let monitor = IncidentMonitor::new(Duration::ZERO, 0.5, Duration::from_secs(15 * 60), 4);
+        for (at, failed) in [(0, true), (1, true), (2, false)] {
+            monitor.order_result(at * MINUTE, failed);
+        }
+        // Too few orders to judge
+        assert!(monitor.evaluate(3 * MINUTE).is_empty());
+
+        monitor.order_result(3 * MINUTE, true);
+        let changes = monitor.evaluate(3 * MINUTE);
+        let [IncidentChange::Open(incident)] = &changes[..] else {
+            panic!("expected an incident, got {:?}", changes);
+        };
+        assert!(incident.summary.contains("3 of 4 orders failed"));
+
+        // The failures age out of the window
+        assert_eq!(
+            monitor.evaluate(17 * MINUTE),
+            vec![IncidentChange::Resolve(IncidentKind::ExecutorErrors)]
+        );
+    }
+
+    #[test]
+    fn test_payloads() {
+        let incident = Incident {
+            kind: IncidentKind::Stalled,
+            summary: "stuck".to_string(),
+        };
+        let pagerduty = PagerDutyIncidents::new("key");
+        let trigger = pagerduty.event(incident.kind, Some(&incident));
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(trigger["dedup_key"], "polymarket-bot:stalled");
+        let resolve = pagerduty.event(IncidentKind::Stalled, None);
+        assert_eq!(resolve["event_action"], "resolve");
+        assert_eq!(resolve["dedup_key"], trigger["dedup_key"]);
+        assert!(resolve.get("payload").is_none());
+
+        let alert = OpsgenieIncidents::alert(&incident);
+        assert_eq!(alert["alias"], "polymarket-bot:stalled");
+        assert_eq!(alert["priority"], "P1");
+    }
+}
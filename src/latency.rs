@@ -0,0 +1,259 @@
+//! Stage latency histograms, to tell whether copies are fast enough.
+//!
+//! Each stage of handling a leader trade is timed into a fixed-bucket
+//! histogram kept for the life of the process. Percentiles are estimated
+//! within buckets, which is plenty to compare a 200ms decision with a 2s
+//! round trip. The histograms are served at `/metrics` on `health_addr` in
+//! Prometheus text format and summarized in the log every
+//! `latency_summary_interval`.
+
+use crate::units::format_duration;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bounds of the histogram buckets, in milliseconds.
+const BUCKETS_MS: [f64; 16] = [
+    1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1_000.0, 2_500.0, 5_000.0, 10_000.0, 30_000.0, 60_000.0,
+    300_000.0,
+];
+
+const QUANTILES: [f64; 3] = [0.5, 0.95, 0.99];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// From the leader's trade to its frame arriving (the trade timestamp
+    /// only has second resolution)
+    Feed,
+    /// Precheck, market and balance lookups, sizing and risk
+    Decide,
+    /// Building and journaling the order; orders aren't signed locally, so
+    /// this is the step that stands in for signing
+    Prepare,
+    /// HTTP round trip placing the order
+    Submit,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Feed, Stage::Decide, Stage::Prepare, Stage::Submit];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Feed => "feed",
+            Stage::Decide => "decide",
+            Stage::Prepare => "prepare",
+            Stage::Submit => "submit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// Count per bucket in [`BUCKETS_MS`], plus one for anything slower
+    counts: [u64; BUCKETS_MS.len() + 1],
+    count: u64,
+    sum_ms: f64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        let bucket = BUCKETS_MS.iter().position(|&le| ms <= le).unwrap_or(BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum_ms += ms;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Appends the `_bucket`, `_sum` and `_count` series of metric `name`
+    /// in Prometheus text format; `labels` like `stage="submit"`.
+    pub fn write_prometheus(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (i, &le) in BUCKETS_MS.iter().enumerate() {
+            cumulative += self.counts[i];
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name,
+                labels,
+                le / 1000.0,
+                cumulative
+            );
+        }
+        let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, self.count);
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum_ms / 1000.0);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+
+    /// Estimated latency below which `q` of samples fall, interpolated
+    /// linearly within the bucket; `None` without samples.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = q.clamp(0.0, 1.0) * self.count as f64;
+        let mut seen = 0u64;
+        for (i, &n) in self.counts.iter().enumerate() {
+            if n == 0 || ((seen + n) as f64) < rank {
+                seen += n;
+                continue;
+            }
+            let lower = if i == 0 { 0.0 } else { BUCKETS_MS[i - 1] };
+            // Nothing better to say about the overflow bucket than its bound
+            let Some(&upper) = BUCKETS_MS.get(i) else {
+                return Some(Duration::from_secs_f64(lower / 1000.0));
+            };
+            let within = ((rank - seen as f64) / n as f64).clamp(0.0, 1.0);
+            return Some(Duration::from_secs_f64((lower + (upper - lower) * within) / 1000.0));
+        }
+        None
+    }
+}
+
+/// Latency histograms for every [`Stage`], shared by the watcher and bot.
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    stages: Mutex<[Histogram; Stage::ALL.len()]>,
+}
+
+impl LatencyStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, stage: Stage, latency: Duration) {
+        self.stages.lock().unwrap()[stage as usize].record(latency);
+    }
+
+    pub fn histogram(&self, stage: Stage) -> Histogram {
+        self.stages.lock().unwrap()[stage as usize].clone()
+    }
+
+    /// One line per stage with samples, e.g. `decide p50 120ms p95 800ms
+    /// p99 1.2s (412)`; `None` before anything was recorded.
+    pub fn summary(&self) -> Option<String> {
+        let lines: Vec<String> = Stage::ALL
+            .iter()
+            .filter_map(|&stage| {
+                let histogram = self.histogram(stage);
+                let [p50, p95, p99] = QUANTILES.map(|q| histogram.quantile(q).map(format_latency));
+                Some(format!(
+                    "{} p50 {} p95 {} p99 {} ({})",
+                    stage.as_str(),
+                    p50?,
+                    p95?,
+                    p99?,
+                    histogram.count()
+                ))
+            })
+            .collect();
+        (!lines.is_empty()).then(|| lines.join(", "))
+    }
+
+    /// The histograms in Prometheus text format, plus a gauge with the
+    /// estimated p50/p95/p99 of each stage.
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP polymarket_bot_stage_latency_seconds Time spent in each stage of copying a trade."
+        );
+        let _ = writeln!(out, "# TYPE polymarket_bot_stage_latency_seconds histogram");
+        for stage in Stage::ALL {
+            let labels = format!("stage=\"{}\"", stage.as_str());
+            self.histogram(stage)
+                .write_prometheus(&mut out, "polymarket_bot_stage_latency_seconds", &labels);
+        }
+        let _ = writeln!(
+            out,
+            "# HELP polymarket_bot_stage_latency_quantile_seconds Estimated latency percentiles per stage."
+        );
+        let _ = writeln!(out, "# TYPE polymarket_bot_stage_latency_quantile_seconds gauge");
+        for stage in Stage::ALL {
+            let histogram = self.histogram(stage);
+            for q in QUANTILES {
+                if let Some(latency) = histogram.quantile(q) {
+                    let _ = writeln!(
+                        out,
+                        "polymarket_bot_stage_latency_quantile_seconds{{stage=\"{}\",quantile=\"{}\"}} {}",
+                        stage.as_str(),
+                        q,
+                        latency.as_secs_f64()
+                    );
+                }
+            }
+        }
+        out
+    }
+
+    /// Logs [`summary`](Self::summary) every `interval`; zero disables.
+    pub fn spawn_summary(self: Arc<Self>, interval: Duration) {
+        if interval.is_zero() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Some(summary) = self.summary() {
+                    tracing::info!("⏱️  Latency: {}", summary);
+                }
+            }
+        });
+    }
+}
+
+fn format_latency(latency: Duration) -> String {
+    if latency < Duration::from_secs(1) {
+        format!("{}ms", latency.as_millis())
+    } else if latency < Duration::from_secs(60) {
+        format!("{:.1}s", latency.as_secs_f64())
+    } else {
+        format_duration(latency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for _ in 0..90 {
+            histogram.record(Duration::from_millis(40));
+        }
+        for _ in 0..10 {
+            histogram.record(Duration::from_millis(800));
+        }
+        // 40ms falls in the 25-50ms bucket and 800ms in 500ms-1s
+        let p50 = histogram.quantile(0.5).unwrap();
+        assert!(p50 > Duration::from_millis(25) && p50 <= Duration::from_millis(50));
+        let p99 = histogram.quantile(0.99).unwrap();
+        assert!(p99 > Duration::from_millis(500) && p99 <= Duration::from_secs(1));
+
+        histogram.record(Duration::from_secs(3600));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn test_prometheus_output() {
+        let stats = LatencyStats::new();
+        assert_eq!(stats.summary(), None);
+        stats.record(Stage::Submit, Duration::from_millis(300));
+        stats.record(Stage::Submit, Duration::from_millis(700));
+
+        let text = stats.render_prometheus();
+        assert!(text.contains("polymarket_bot_stage_latency_seconds_bucket{stage=\"submit\",le=\"0.5\"} 1\n"));
+        assert!(text.contains("polymarket_bot_stage_latency_seconds_bucket{stage=\"submit\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("polymarket_bot_stage_latency_seconds_count{stage=\"submit\"} 2\n"));
+        assert!(text.contains("polymarket_bot_stage_latency_seconds_count{stage=\"feed\"} 0\n"));
+        assert!(text.contains("quantile_seconds{stage=\"submit\",quantile=\"0.5\"} 0.5\n"));
+        assert!(stats.summary().unwrap().starts_with("submit p50 500ms"));
+    }
+}
@@ -0,0 +1,485 @@
+//! Running per-leader statistics, kept across restarts, and the leader
+//! registry managed with `mybot leaders`.
+//!
+//! The registry lives in the journal's state store on top of
+//! `wallets_to_track`: it adds leaders, pauses configured ones and names
+//! them. A running bot re-reads it every [`REGISTRY_POLL`], so changes apply
+//! without a restart.
+
+use crate::backtest::CopiedFill;
+use crate::marks::{Mark, Marks};
+use crate::pnl;
+use crate::storage::{DecisionRecord, FillRecord, LeaderTradeRecord, OrderRecord, Storage, TimeRange};
+use crate::types::{CostBasis, Decision, Trade, TradeSide};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Key of the persisted stats in the state store.
+pub const LEADER_STATS_KEY: &str = "leader_stats";
+/// Key of the leader registry in the state store.
+pub const LEADER_REGISTRY_KEY: &str = "leader_registry";
+/// How often a running bot picks up registry changes.
+pub const REGISTRY_POLL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LeaderStats {
+    pub wallet: String,
+    pub trades_seen: u64,
+    pub copied: u64,
+    pub skipped: u64,
+    pub copied_usd: f64,
+    /// Unix ms of the last trade seen; zero if none yet
+    pub last_trade_at: i64,
+}
+
+/// Parses `leader_labels` entries like `0xabc...=Theo` into labels by
+/// (lowercased) wallet.
+pub fn parse_labels(entries: &[String]) -> Result<HashMap<String, String>> {
+    entries
+        .iter()
+        .map(|entry| match entry.split_once('=') {
+            Some((wallet, label)) if !wallet.trim().is_empty() && !label.trim().is_empty() => {
+                Ok((wallet.trim().to_lowercase(), label.trim().to_string()))
+            }
+            _ => anyhow::bail!("Invalid leader label '{}' (expected wallet=label)", entry),
+        })
+        .collect()
+}
+
+#[derive(Default)]
+pub struct LeaderBook {
+    stats: Mutex<BTreeMap<String, LeaderStats>>,
+    labels: Mutex<HashMap<String, String>>,
+}
+
+impl LeaderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_labels(self, labels: HashMap<String, String>) -> Self {
+        *self.labels.lock().unwrap() = labels;
+        self
+    }
+
+    pub fn set_label(&self, wallet: &str, label: &str) {
+        self.labels
+            .lock()
+            .unwrap()
+            .insert(wallet.to_lowercase(), label.to_string());
+    }
+
+    /// How notifications name a leader: their label, or the start of
+    /// their address.
+    pub fn label(&self, wallet: &str) -> String {
+        match self.labels.lock().unwrap().get(&wallet.to_lowercase()) {
+            Some(label) => label.clone(),
+            None => wallet[..10.min(wallet.len())].to_string(),
+        }
+    }
+
+    /// Counts one leader trade and what was decided about it.
+    pub fn record(&self, wallet: &str, decision: &Decision, at_ms: i64) {
+        let mut stats = self.stats.lock().unwrap();
+        let entry = stats.entry(wallet.to_string()).or_insert_with(|| LeaderStats {
+            wallet: wallet.to_string(),
+            ..Default::default()
+        });
+        entry.trades_seen += 1;
+        entry.last_trade_at = at_ms;
+        match decision {
+            Decision::Copy { size_usd, .. } => {
+                entry.copied += 1;
+                entry.copied_usd += size_usd;
+            }
+            Decision::Skip { .. } => entry.skipped += 1,
+        }
+    }
+
+    pub fn get(&self, wallet: &str) -> Option<LeaderStats> {
+        self.stats.lock().unwrap().get(wallet).cloned()
+    }
+
+    /// All leaders, ordered by wallet.
+    pub fn all(&self) -> Vec<LeaderStats> {
+        self.stats.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn replace(&self, all: Vec<LeaderStats>) {
+        *self.stats.lock().unwrap() = all.into_iter().map(|s| (s.wallet.clone(), s)).collect();
+    }
+}
+
+/// A leader added, named or paused with `mybot leaders`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderEntry {
+    pub wallet: String,
+    pub label: Option<String>,
+    /// Where to read up on the leader, e.g. their Polymarket profile
+    pub profile: Option<String>,
+    /// Paused leaders aren't watched, even if `wallets_to_track` lists them
+    pub paused: bool,
+    pub updated_at: i64,
+}
+
+pub async fn load_registry(storage: &dyn Storage) -> Result<Vec<LeaderEntry>> {
+    match storage.load_state(LEADER_REGISTRY_KEY).await? {
+        Some(json) => serde_json::from_str(&json).context("Stored leader registry is corrupt"),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub async fn save_registry(storage: &dyn Storage, entries: &[LeaderEntry], now_ms: i64) -> Result<()> {
+    storage
+        .save_state(LEADER_REGISTRY_KEY, &serde_json::to_string(entries)?, now_ms)
+        .await
+}
+
+/// The registry entry for `wallet`, added if there is none.
+pub fn entry_mut<'a>(entries: &'a mut Vec<LeaderEntry>, wallet: &str, now_ms: i64) -> &'a mut LeaderEntry {
+    let index = match entries.iter().position(|e| e.wallet.eq_ignore_ascii_case(wallet)) {
+        Some(index) => index,
+        None => {
+            entries.push(LeaderEntry {
+                wallet: wallet.to_string(),
+                label: None,
+                profile: None,
+                paused: false,
+                updated_at: now_ms,
+            });
+            entries.len() - 1
+        }
+    };
+    let entry = &mut entries[index];
+    entry.updated_at = now_ms;
+    entry
+}
+
+/// The wallets to watch: `configured` plus the registry's active leaders,
+/// without its paused ones.
+pub fn tracked_wallets(configured: &[String], registry: &[LeaderEntry]) -> Vec<String> {
+    let paused = |wallet: &str| {
+        registry
+            .iter()
+            .any(|e| e.paused && e.wallet.eq_ignore_ascii_case(wallet))
+    };
+    let mut wallets: Vec<String> = configured.iter().filter(|w| !paused(w)).cloned().collect();
+    for entry in registry.iter().filter(|e| !e.paused) {
+        if !wallets.iter().any(|w| w.eq_ignore_ascii_case(&entry.wallet)) {
+            wallets.push(entry.wallet.clone());
+        }
+    }
+    wallets
+}
+
+/// How copying one leader has worked out, from the journal.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LeaderPerformance {
+    pub wallet: String,
+    pub decisions: u64,
+    pub copied: u64,
+    pub bought_usd: f64,
+    pub sold_usd: f64,
+    /// PnL of selling or redeeming shares bought copying this leader, net
+    /// of fees
+    pub realized_pnl: f64,
+    /// PnL of the shares still held, at current marks
+    pub unrealized_pnl: f64,
+    /// What the shares still held from copying this leader cost
+    pub open_cost: f64,
+    /// Markets closed out at a profit, and at a loss or even
+    pub wins: u64,
+    pub losses: u64,
+    /// Mean slippage of the copies' fills against the leader's price, as a
+    /// fraction of it; `None` when no fill traces back to a leader trade
+    pub avg_slippage: Option<f64>,
+    /// Mean ms from the leader's trade to the first fill of its copy
+    pub avg_latency_ms: Option<f64>,
+}
+
+impl LeaderPerformance {
+    pub fn copy_rate(&self) -> f64 {
+        if self.decisions == 0 {
+            0.0
+        } else {
+            self.copied as f64 / self.decisions as f64
+        }
+    }
+
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+
+    /// Share of closed markets that made money; `None` before any closed.
+    pub fn win_rate(&self) -> Option<f64> {
+        let closed = self.wins + self.losses;
+        (closed > 0).then(|| self.wins as f64 / closed as f64)
+    }
+}
+
+/// Per-leader results, best PnL first. Fills are attributed to the leader
+/// whose trade the order copied and booked as [`pnl::compute`] books them,
+/// costed with `method` and marked at `marks`; fills of manual orders are
+/// left out. Slippage and latency are measured against the leader trade
+/// each copy's decision was made on, where `trades` has it.
+pub fn performance(
+    decisions: &[DecisionRecord],
+    orders: &[OrderRecord],
+    fills: &[FillRecord],
+    trades: &[LeaderTradeRecord],
+    method: CostBasis,
+    marks: &HashMap<String, Mark>,
+) -> Vec<LeaderPerformance> {
+    let mut by_leader: BTreeMap<&str, LeaderPerformance> = BTreeMap::new();
+    for decision in decisions {
+        let leader = by_leader.entry(&decision.wallet).or_insert_with(|| LeaderPerformance {
+            wallet: decision.wallet.clone(),
+            ..Default::default()
+        });
+        leader.decisions += 1;
+        leader.copied += decision.copied as u64;
+    }
+    let leader_trades: HashMap<i64, &Trade> = trades.iter().map(|t| (t.id, &t.trade)).collect();
+    let decision_by_id: HashMap<i64, &DecisionRecord> = decisions.iter().map(|d| (d.id, d)).collect();
+    // Order id to the leader copied and the trade copied, if known
+    let copy_of_order: HashMap<i64, (&str, Option<&Trade>)> = orders
+        .iter()
+        .filter_map(|o| {
+            let decision = decision_by_id.get(&o.decision_id?)?;
+            let trade = decision.leader_trade_id.and_then(|id| leader_trades.get(&id).copied());
+            Some((o.id, (decision.wallet.as_str(), trade)))
+        })
+        .collect();
+
+    // Sums and counts per leader
+    let mut slippage: HashMap<&str, (f64, u64)> = HashMap::new();
+    let mut latency: HashMap<&str, (f64, u64)> = HashMap::new();
+    let mut first_fill: HashMap<i64, i64> = HashMap::new();
+    for fill in fills {
+        let Some(&(wallet, trade)) = copy_of_order.get(&fill.order_id) else {
+            continue;
+        };
+        let Some(leader) = by_leader.get_mut(wallet) else {
+            continue;
+        };
+        let notional = fill.shares * fill.price;
+        if fill.side.eq_ignore_ascii_case("SELL") {
+            leader.sold_usd += notional;
+        } else {
+            leader.bought_usd += notional;
+        }
+        let Some(trade) = trade else {
+            continue;
+        };
+        if let Some(side) = TradeSide::parse(&fill.side) {
+            let copied = CopiedFill {
+                at: fill.filled_at,
+                wallet: wallet.to_string(),
+                market_id: fill.market_id.clone(),
+                side,
+                leader_price: trade.price,
+                price: fill.price,
+                shares: fill.shares,
+            };
+            let (sum, n) = slippage.entry(wallet).or_default();
+            *sum += copied.slippage();
+            *n += 1;
+        }
+        let first = first_fill.entry(fill.order_id).or_insert(fill.filled_at);
+        *first = (*first).min(fill.filled_at);
+    }
+    for (order_id, filled_at) in first_fill {
+        if let Some(&(wallet, Some(trade))) = copy_of_order.get(&order_id) {
+            let (sum, n) = latency.entry(wallet).or_default();
+            *sum += (filled_at - trade.timestamp * 1000).max(0) as f64;
+            *n += 1;
+        }
+    }
+
+    let booked = pnl::compute(decisions, orders, fills, method, &HashMap::new(), marks, 0);
+    for position in &booked.positions {
+        let Some(leader) = by_leader.get_mut(position.leader.as_str()) else {
+            continue;
+        };
+        leader.realized_pnl += position.realized_pnl;
+        leader.unrealized_pnl += position.unrealized_pnl;
+        leader.open_cost += position.open_cost;
+        if position.shares <= 1e-9 {
+            if position.realized_pnl > 0.0 {
+                leader.wins += 1;
+            } else {
+                leader.losses += 1;
+            }
+        }
+    }
+
+    let mean = |tally: Option<&(f64, u64)>| tally.filter(|(_, n)| *n > 0).map(|(sum, n)| sum / *n as f64);
+    let mut ranked: Vec<LeaderPerformance> = by_leader
+        .into_values()
+        .map(|mut leader| {
+            leader.avg_slippage = mean(slippage.get(leader.wallet.as_str()));
+            leader.avg_latency_ms = mean(latency.get(leader.wallet.as_str()));
+            leader
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.pnl().total_cmp(&a.pnl()));
+    ranked
+}
+
+/// Per-leader results over the journal's `range`, with what's still held
+/// marked by `marks`.
+pub async fn from_journal(
+    storage: &dyn Storage,
+    range: TimeRange,
+    method: CostBasis,
+    marks: &Marks,
+) -> Result<Vec<LeaderPerformance>> {
+    let decisions = storage.decisions(range).await?;
+    let orders = storage.orders(range).await?;
+    let fills = storage.fills(range).await?;
+    let trades = storage.leader_trades(range).await?;
+    let traded_ids: Vec<&str> = fills.iter().map(|f| f.market_id.as_str()).collect();
+    let marked = marks.marks(&traded_ids).await;
+    Ok(performance(&decisions, &orders, &fills, &trades, method, &marked))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        let labels = parse_labels(&["0xABCDEF123456=Theo".to_string()]).unwrap();
+        let book = LeaderBook::new().with_labels(labels);
+        assert_eq!(book.label("0xabcdef123456"), "Theo");
+        assert_eq!(book.label("0x9876543210fedcba"), "0x98765432");
+        assert!(parse_labels(&["0xabc".to_string()]).is_err());
+        assert!(parse_labels(&["0xabc=".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_registry_overrides_config_wallets() {
+        let mut registry = Vec::new();
+        entry_mut(&mut registry, "0xAAA", 1).paused = true;
+        entry_mut(&mut registry, "0xccc", 1).label = Some("Theo".to_string());
+        entry_mut(&mut registry, "0xCCC", 2).profile = Some("@theo".to_string());
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry[1].updated_at, 2);
+
+        let configured = vec!["0xaaa".to_string(), "0xbbb".to_string()];
+        assert_eq!(tracked_wallets(&configured, &registry), vec!["0xbbb", "0xccc"]);
+    }
+
+    #[test]
+    fn test_performance_by_leader() {
+        let decision = |id, wallet: &str, copied| DecisionRecord {
+            id,
+            leader_trade_id: Some(id + 100),
+            wallet: wallet.to_string(),
+            market_id: "m1".to_string(),
+            side: "BUY".to_string(),
+            copied,
+            reason: None,
+            detail: None,
+            size_usd: None,
+            decided_at: 0,
+            arrival_mid: None,
+            arrival_book: None,
+        };
+        let order = |id, decision_id| OrderRecord {
+            id,
+            decision_id,
+            exchange_order_id: None,
+            market_id: "m1".to_string(),
+            side: String::new(),
+            shares: 0.0,
+            limit_price: None,
+            order_type: String::new(),
+            status: "filled".to_string(),
+            error: None,
+            submitted_at: 0,
+            client_order_id: None,
+        };
+        let fill = |order_id, side: &str, shares, price, filled_at| FillRecord {
+            id: 0,
+            order_id,
+            market_id: "m1".to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee: 0.0,
+            filled_at,
+        };
+        let trade = |id, side, price, timestamp| LeaderTradeRecord {
+            id,
+            trade: Trade {
+                wallet: "0xa".to_string(),
+                event_id: String::new(),
+                market_id: "m1".to_string(),
+                side,
+                shares: 0.0,
+                price,
+                timestamp,
+                tx_hash: None,
+            },
+            observed_at: 0,
+        };
+        let decisions = [
+            decision(1, "0xa", true),
+            decision(2, "0xa", true),
+            decision(3, "0xb", false),
+        ];
+        let orders = [order(10, Some(1)), order(11, Some(2)), order(12, None)];
+        let fills = [
+            // Filled in two parts; latency runs to the first
+            fill(10, "BUY", 5.0, 0.40, 1_500),
+            fill(10, "BUY", 5.0, 0.40, 3_000),
+            fill(11, "SELL", 4.0, 0.50, 12_000),
+            // A manual order counts for nobody
+            fill(12, "SELL", 6.0, 0.10, 0),
+        ];
+        // The leader bought at 0.38 and sold at 0.52, so both copies lost ground
+        let trades = [
+            trade(101, TradeSide::BUY, 0.38, 1),
+            trade(102, TradeSide::SELL, 0.52, 10),
+        ];
+
+        let ranked = performance(&decisions, &orders, &fills, &trades, CostBasis::Fifo, &HashMap::new());
+        assert_eq!(ranked.len(), 2);
+        let a = &ranked[0];
+        assert_eq!((a.wallet.as_str(), a.decisions, a.copied), ("0xa", 2, 2));
+        assert!((a.realized_pnl - 0.4).abs() < 1e-9);
+        assert!((a.open_cost - 2.4).abs() < 1e-9);
+        assert_eq!(ranked[1].copy_rate(), 0.0);
+        // Unmarked, so what's held adds nothing and nothing has closed yet
+        assert_eq!((a.unrealized_pnl, a.win_rate()), (0.0, None));
+        let expected = (0.02 / 0.38 * 2.0 + 0.02 / 0.52) / 3.0;
+        assert!((a.avg_slippage.unwrap() - expected).abs() < 1e-9);
+        assert_eq!(a.avg_latency_ms, Some(1_250.0));
+        assert_eq!(ranked[1].avg_slippage, None);
+
+        // Marked and then closed out
+        let marks = HashMap::from([(
+            "m1".to_string(),
+            Mark {
+                price: 0.45,
+                source: crate::marks::MarkSource::Mid,
+                at: 0,
+                stale: false,
+            },
+        )]);
+        let marked = performance(&decisions, &orders, &fills, &trades, CostBasis::Fifo, &marks);
+        assert!((marked[0].unrealized_pnl - 0.3).abs() < 1e-9);
+        assert!((marked[0].pnl() - 0.7).abs() < 1e-9);
+        let mut closed = fills.to_vec();
+        closed.push(fill(0, "SELL", 6.0, 0.0, 20_000));
+        let ranked = performance(&decisions, &orders, &closed, &trades, CostBasis::Fifo, &marks);
+        let a = ranked.iter().find(|p| p.wallet == "0xa").unwrap();
+        assert_eq!((a.wins, a.losses, a.win_rate()), (0, 1, Some(0.0)));
+        assert!((a.realized_pnl + 2.0).abs() < 1e-9);
+        assert_eq!(ranked[1].wallet, "0xa");
+    }
+}
@@ -0,0 +1,136 @@
+//! One live instance per account.
+//!
+//! Two bots trading the same account double every copy and fight over its
+//! positions. Before trading live, the bot takes a lease on its wallet in the
+//! journal and renews it every third of `instance_lease_ttl`. A second
+//! instance refuses to start while the lease is fresh, unless forced; an
+//! instance whose lease was taken over stops copying.
+
+use crate::storage::{now_ms, LeaseRecord, Storage};
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct InstanceLease {
+    storage: Arc<dyn Storage>,
+    key: String,
+    holder: String,
+    ttl: Duration,
+    acquired_at: i64,
+    lost: AtomicBool,
+}
+
+impl InstanceLease {
+    /// Takes the live-trading lease on `wallet`, failing if another instance
+    /// holds it and `force` is not set.
+    pub async fn acquire(storage: Arc<dyn Storage>, wallet: &str, ttl: Duration, force: bool) -> Result<Self> {
+        let now = now_ms();
+        let lease = Self {
+            storage,
+            key: format!("live:{}", wallet.to_lowercase()),
+            holder: holder_id(),
+            ttl,
+            acquired_at: now,
+            lost: AtomicBool::new(false),
+        };
+
+        let current = lease.storage.acquire_lease(&lease.record(now), force).await?;
+        if current.holder != lease.holder {
+            anyhow::bail!(
+                "Another instance ({}) is trading {} (last heartbeat {}s ago); stop it, or start with --force if it is gone",
+                current.holder,
+                wallet,
+                (now - current.heartbeat_at) / 1000
+            );
+        }
+        Ok(lease)
+    }
+
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// False once another instance has taken the lease over.
+    pub fn is_held(&self) -> bool {
+        !self.lost.load(Ordering::SeqCst)
+    }
+
+    /// Extends the lease. Returns whether it is still ours.
+    pub async fn renew(&self) -> Result<bool> {
+        if !self.is_held() {
+            return Ok(false);
+        }
+        let current = self.storage.acquire_lease(&self.record(now_ms()), false).await?;
+        if current.holder != self.holder {
+            self.lost.store(true, Ordering::SeqCst);
+            tracing::error!("🔒 Instance {} took over the trading lease; no longer copying", current.holder);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    pub async fn release(&self) -> Result<()> {
+        self.storage.release_lease(&self.key, &self.holder).await
+    }
+
+    /// Renews every third of the TTL until the lease is lost.
+    pub fn spawn_heartbeat(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let period = self.ttl / 3;
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                match self.renew().await {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(e) => tracing::warn!("Failed to renew the trading lease: {}", e),
+                }
+            }
+        });
+    }
+
+    fn record(&self, now: i64) -> LeaseRecord {
+        LeaseRecord {
+            key: self.key.clone(),
+            holder: self.holder.clone(),
+            acquired_at: self.acquired_at,
+            heartbeat_at: now,
+            expires_at: now + self.ttl.as_millis() as i64,
+        }
+    }
+}
+
+/// Identifies this process to whoever finds the lease taken.
+fn holder_id() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown-host".to_string());
+    format!("{}:{}:{:08x}", host, std::process::id(), rand::random::<u32>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+
+    #[tokio::test]
+    async fn test_second_instance_needs_force() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let ttl = Duration::from_secs(30);
+        let first = InstanceLease::acquire(Arc::clone(&storage), "0xABC", ttl, false).await.unwrap();
+        assert!(first.renew().await.unwrap());
+
+        let err = InstanceLease::acquire(Arc::clone(&storage), "0xabc", ttl, false).await.err().unwrap();
+        assert!(err.to_string().contains(first.holder()));
+
+        let second = InstanceLease::acquire(Arc::clone(&storage), "0xabc", ttl, true).await.unwrap();
+        assert!(!first.renew().await.unwrap());
+        assert!(!first.is_held());
+        assert!(second.renew().await.unwrap());
+
+        // Releasing a lost lease leaves the new holder's alone
+        first.release().await.unwrap();
+        assert!(second.renew().await.unwrap());
+        second.release().await.unwrap();
+        InstanceLease::acquire(storage, "0xabc", ttl, false).await.unwrap();
+    }
+}
@@ -1,7 +1,93 @@
 pub mod types;
 pub mod config;
+pub mod cli;
+pub mod clock;
+pub mod completions;
+pub mod config_migration;
+pub mod wizard;
+pub mod lint;
+pub mod units;
+pub mod logging;
+pub mod telemetry;
+pub mod sealed;
 pub mod api;
 pub mod watcher;
+pub mod subgraph;
+pub mod book;
+pub mod ticker;
+pub mod synthetic;
+pub mod stress;
+pub mod mempool;
 pub mod sizing;
 pub mod risk;
+pub mod exchange;
 pub mod executor;
+pub mod manual;
+pub mod schedule;
+pub mod shadow;
+pub mod scout;
+pub mod storage;
+pub mod dataset;
+pub mod dedup;
+pub mod approval;
+pub mod lease;
+pub mod recovery;
+pub mod reconcile;
+pub mod resolution;
+pub mod portfolio;
+pub mod marks;
+pub mod liquidity;
+pub mod consistency;
+pub mod spot;
+pub mod price_alerts;
+pub mod disputes;
+pub mod spikes;
+pub mod arbitrage;
+pub mod bars;
+pub mod pnl;
+pub mod equity;
+pub mod cashflow;
+pub mod exposure;
+pub mod aging;
+pub mod paper;
+pub mod markets;
+pub mod search;
+pub mod gamma;
+pub mod data_api;
+pub mod prices;
+pub mod leaders;
+pub mod slippage;
+pub mod counterfactual;
+pub mod report;
+pub mod snapshot;
+pub mod sweep;
+pub mod events;
+pub mod notify;
+pub mod incidents;
+pub mod anomaly;
+pub mod http;
+pub mod tls;
+pub mod health;
+pub mod doctor;
+pub mod daemon;
+pub mod heartbeat;
+pub mod latency;
+pub mod gauges;
+pub mod rpc;
+pub mod skips;
+pub mod status;
+pub mod tui;
+pub mod tail;
+pub mod admin;
+pub mod audit;
+pub mod replay;
+pub mod archive;
+pub mod retention;
+pub mod export;
+pub mod fills;
+pub mod backtest;
+pub mod montecarlo;
+pub mod bench;
+pub mod tearsheet;
+pub mod bot;
+pub mod builder;
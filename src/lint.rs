@@ -0,0 +1,184 @@
+use crate::types::{Config, SizingMode};
+use serde::Serialize;
+use std::fmt;
+
+/// Typical Polymarket spread as a fraction of price; slippage tolerances far
+/// above this mostly buy bad fills.
+const TYPICAL_SPREAD: f64 = 0.02;
+
+/// A bankroll smaller than this many max stakes is considered small.
+const SMALL_BANKROLL_STAKES: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Danger,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Danger => "danger",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Lint {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// Flags configurations that pass validation but are likely to lose money
+/// or misbehave. `bankroll` is the trading wallet balance, if known.
+pub fn lint_config(config: &Config, bankroll: Option<f64>) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut push = |code, severity, message: String| {
+        lints.push(Lint { code, severity, message });
+    };
+
+    if config.retry_attempts == 0 {
+        push(
+            "no-retry-attempts",
+            Severity::Danger,
+            "retry_attempts is 0, so no order will ever be submitted".to_string(),
+        );
+    }
+
+    if !config.paper_trading && config.max_daily_loss.as_f64() <= 0.0 {
+        push(
+            "no-daily-loss-limit",
+            Severity::Danger,
+            "live trading is enabled but max_daily_loss is not set".to_string(),
+        );
+    }
+
+    if config.max_slippage.as_f64() > TYPICAL_SPREAD * 2.5 {
+        push(
+            "wide-slippage",
+            Severity::Warning,
+            format!(
+                "max_slippage of {:.1}% is far above a typical {:.0}% spread",
+                config.max_slippage.as_f64() * 100.0,
+                TYPICAL_SPREAD * 100.0
+            ),
+        );
+    }
+
+    let copy_ratio_used = !matches!(config.sizing_mode, SizingMode::Fixed);
+    if copy_ratio_used && config.proportional_ratio.as_f64() > 1.0 {
+        match bankroll {
+            Some(balance) if balance < config.max_stake.as_f64() * SMALL_BANKROLL_STAKES => push(
+                "leveraged-copy-small-bankroll",
+                Severity::Danger,
+                format!(
+                    "copy ratio {:.2} > 1 with a ${:.2} bankroll (under {} max stakes)",
+                    config.proportional_ratio.as_f64(), balance, SMALL_BANKROLL_STAKES
+                ),
+            ),
+            Some(_) => {}
+            None => push(
+                "leveraged-copy",
+                Severity::Warning,
+                format!(
+                    "copy ratio {:.2} > 1 bets more than the leader; check your bankroll",
+                    config.proportional_ratio.as_f64()
+                ),
+            ),
+        }
+    }
+
+    if config.max_stake > config.max_exposure_per_event {
+        push(
+            "stake-exceeds-event-limit",
+            Severity::Warning,
+            format!(
+                "max_stake ${:.2} exceeds max_exposure_per_event ${:.2}; large copies will always be rejected",
+                config.max_stake.as_f64(), config.max_exposure_per_event.as_f64()
+            ),
+        );
+    }
+
+    if config.max_daily_volume < config.min_stake {
+        push(
+            "daily-volume-below-min-stake",
+            Severity::Danger,
+            format!(
+                "max_daily_volume ${:.2} is below min_stake ${:.2}; no trade can pass",
+                config.max_daily_volume.as_f64(), config.min_stake.as_f64()
+            ),
+        );
+    }
+
+    if matches!(config.sizing_mode, SizingMode::Fixed) && config.fixed_stake > config.max_stake {
+        push(
+            "fixed-stake-clamped",
+            Severity::Warning,
+            format!(
+                "fixed_stake ${:.2} is above max_stake ${:.2} and will be clamped",
+                config.fixed_stake.as_f64(), config.max_stake.as_f64()
+            ),
+        );
+    }
+
+    if config.latency_budget.is_zero() {
+        push(
+            "no-latency-budget",
+            Severity::Info,
+            "latency_budget is 0s, so stale leader trades are still copied".to_string(),
+        );
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Ratio, UsdcAmount};
+
+    fn codes(lints: &[Lint]) -> Vec<&'static str> {
+        lints.iter().map(|l| l.code).collect()
+    }
+
+    #[test]
+    fn test_live_without_loss_limit_is_dangerous() {
+        let config = Config::default();
+        let lints = lint_config(&config, None);
+        assert!(codes(&lints).contains(&"no-daily-loss-limit"));
+
+        let paper = Config {
+            paper_trading: true,
+            ..Default::default()
+        };
+        assert!(!codes(&lint_config(&paper, None)).contains(&"no-daily-loss-limit"));
+    }
+
+    #[test]
+    fn test_leveraged_copy_depends_on_bankroll() {
+        let config = Config {
+            sizing_mode: SizingMode::Proportional,
+            proportional_ratio: Ratio(1.5),
+            max_stake: UsdcAmount(100.0),
+            ..Default::default()
+        };
+
+        let small = lint_config(&config, Some(300.0));
+        assert!(codes(&small).contains(&"leveraged-copy-small-bankroll"));
+
+        let large = lint_config(&config, Some(50_000.0));
+        assert!(!codes(&large).iter().any(|c| c.starts_with("leveraged-copy")));
+    }
+}
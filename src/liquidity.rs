@@ -0,0 +1,186 @@
+//! How tradeable a market is right now: rolling spread, depth near the
+//! top of the book and how often it trades.
+//!
+//! [`LiquidityStats`] is fed by [`crate::marks::Marks`]: every book the
+//! bot samples or streams and every trade it sees (leaders', its own and
+//! the market's) lands here too. Over the last `liquidity_window` it keeps
+//! each market's spread, the dollars resting on the top [`TOP_LEVELS`]
+//! levels of each side, and the trade count.
+//!
+//! The risk manager asks it before every copy (see
+//! [`crate::risk::RiskManager::check_liquidity`]): a market whose average
+//! spread is over `max_spread` or that trades less than
+//! `min_trades_per_hour` is skipped, and a copy bigger than
+//! `max_book_share` of the depth it would take is cut down to that share.
+//! Markets without a book in the window pass unchecked.
+
+use crate::types::TradeSide;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Book levels per side counted as depth.
+pub const TOP_LEVELS: usize = 5;
+
+/// One book seen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BookObservation {
+    at: i64,
+    spread: f64,
+    bid_depth: f64,
+    ask_depth: f64,
+}
+
+#[derive(Debug, Default)]
+struct History {
+    books: VecDeque<BookObservation>,
+    trades: VecDeque<i64>,
+}
+
+/// A market's liquidity over the window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Quality {
+    pub market_id: String,
+    /// Books seen in the window
+    pub books: usize,
+    /// Average best ask less best bid
+    pub spread: f64,
+    pub last_spread: f64,
+    /// Average dollars on the top levels of each side
+    pub bid_depth_usd: f64,
+    pub ask_depth_usd: f64,
+    pub trades: usize,
+    pub trades_per_hour: f64,
+}
+
+impl Quality {
+    /// Depth a copy on `side` takes from: the asks for a buy, the bids for
+    /// a sell.
+    pub fn depth_for(&self, side: &TradeSide) -> f64 {
+        match side {
+            TradeSide::BUY => self.ask_depth_usd,
+            TradeSide::SELL => self.bid_depth_usd,
+        }
+    }
+}
+
+/// Dollars resting on the best `TOP_LEVELS` levels, best first.
+fn top_depth(levels: &[(f64, f64)], best_first: impl Fn(f64, f64) -> std::cmp::Ordering) -> f64 {
+    let mut levels = levels.to_vec();
+    levels.sort_by(|a, b| best_first(a.0, b.0));
+    levels.iter().take(TOP_LEVELS).map(|(price, size)| price * size).sum()
+}
+
+pub struct LiquidityStats {
+    window: Duration,
+    markets: Mutex<HashMap<String, History>>,
+}
+
+impl LiquidityStats {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            markets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn window_ms(&self) -> i64 {
+        self.window.as_millis() as i64
+    }
+
+    /// Records a book seen at `at`; one-sided books have no spread and are
+    /// left out.
+    pub fn record_book(&self, market_id: &str, bids: &[(f64, f64)], asks: &[(f64, f64)], at: i64) {
+        let best_bid = bids.iter().map(|l| l.0).reduce(f64::max);
+        let best_ask = asks.iter().map(|l| l.0).reduce(f64::min);
+        let (Some(bid), Some(ask)) = (best_bid, best_ask) else {
+            return;
+        };
+        let observation = BookObservation {
+            at,
+            spread: (ask - bid).max(0.0),
+            bid_depth: top_depth(bids, |a, b| b.total_cmp(&a)),
+            ask_depth: top_depth(asks, |a, b| a.total_cmp(&b)),
+        };
+        let mut markets = self.markets.lock().unwrap();
+        let history = markets.entry(market_id.to_string()).or_default();
+        history.books.push_back(observation);
+        let horizon = at - self.window_ms();
+        while history.books.front().is_some_and(|b| b.at < horizon) {
+            history.books.pop_front();
+        }
+    }
+
+    /// Records a trade in `market_id` at `at`.
+    pub fn record_trade(&self, market_id: &str, at: i64) {
+        let mut markets = self.markets.lock().unwrap();
+        let history = markets.entry(market_id.to_string()).or_default();
+        history.trades.push_back(at);
+        let horizon = at - self.window_ms();
+        while history.trades.front().is_some_and(|t| *t < horizon) {
+            history.trades.pop_front();
+        }
+    }
+
+    /// The market's liquidity over the window ending `now`; `None` without
+    /// a book in it.
+    pub fn quality(&self, market_id: &str, now: i64) -> Option<Quality> {
+        let markets = self.markets.lock().unwrap();
+        let history = markets.get(market_id)?;
+        let horizon = now - self.window_ms();
+        let books: Vec<&BookObservation> = history.books.iter().filter(|b| b.at >= horizon).collect();
+        let last = books.last()?;
+        let n = books.len() as f64;
+        let trades = history.trades.iter().filter(|t| **t >= horizon).count();
+        Some(Quality {
+            market_id: market_id.to_string(),
+            books: books.len(),
+            spread: books.iter().map(|b| b.spread).sum::<f64>() / n,
+            last_spread: last.spread,
+            bid_depth_usd: books.iter().map(|b| b.bid_depth).sum::<f64>() / n,
+            ask_depth_usd: books.iter().map(|b| b.ask_depth).sum::<f64>() / n,
+            trades,
+            trades_per_hour: trades as f64 * 3_600_000.0 / self.window_ms().max(1) as f64,
+        })
+    }
+
+    /// Every market with a book in the window, widest spread first.
+    pub fn all(&self, now: i64) -> Vec<Quality> {
+        let ids: Vec<String> = self.markets.lock().unwrap().keys().cloned().collect();
+        let mut all: Vec<Quality> = ids.iter().filter_map(|id| self.quality(id, now)).collect();
+        all.sort_by(|a, b| b.spread.total_cmp(&a.spread));
+        all
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolls_spread_depth_and_trade_frequency() {
+        let stats = LiquidityStats::new(Duration::from_secs(3600));
+        let asks: Vec<(f64, f64)> = (0..7).map(|i| (0.52 + i as f64 * 0.01, 100.0)).collect();
+        // Too old to count by the time of the next book
+        stats.record_book("m1", &[(0.30, 10.0)], &[(0.70, 10.0)], 0);
+        stats.record_book("m1", &[(0.48, 100.0), (0.47, 50.0)], &asks, 4_000_000);
+        stats.record_book("m1", &[(0.49, 100.0)], &[(0.53, 100.0)], 4_100_000);
+        stats.record_book("m1", &[(0.49, 100.0)], &[], 4_200_000);
+        for at in [100, 3_700_000, 3_800_000, 4_100_000] {
+            stats.record_trade("m1", at);
+        }
+
+        let q = stats.quality("m1", 4_200_000).unwrap();
+        assert_eq!(q.books, 2);
+        assert!((q.spread - 0.04).abs() < 1e-9);
+        assert!((q.last_spread - 0.04).abs() < 1e-9);
+        // Top five asks: 100 shares at 0.52..0.56, then 100 at 0.53
+        assert!((q.ask_depth_usd - (270.0 + 53.0) / 2.0).abs() < 1e-9);
+        assert!((q.bid_depth_usd - (48.0 + 23.5 + 49.0) / 2.0).abs() < 1e-9);
+        assert_eq!(q.depth_for(&TradeSide::BUY), q.ask_depth_usd);
+        assert_eq!((q.trades, q.trades_per_hour), (3, 3.0));
+        assert!(stats.quality("m2", 4_200_000).is_none());
+        assert!(stats.quality("m1", 9_000_000).is_none());
+    }
+}
@@ -0,0 +1,294 @@
+//! Log output: human-readable text, or one JSON object per line.
+//!
+//! Every leader trade gets a correlation id ([`correlation_id`]) when it
+//! arrives. The watcher logs it on ingestion and the bot handles the trade
+//! inside a `copy` span carrying it, so decision, order and fill lines all
+//! have the same `cid` field. In JSON (`LOG_FORMAT=json`) span fields are
+//! merged into every line; text output shows them as `copy{cid=…}`.
+
+use crate::dedup::trade_key;
+use crate::telemetry;
+use crate::types::Trade;
+use crate::units::format_duration;
+use anyhow::{Context as _, Result};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::{reload, EnvFilter};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => anyhow::bail!("Unknown log format '{}' (expected text or json)", other),
+        }
+    }
+
+    /// From `LOG_FORMAT`; text when unset.
+    pub fn from_env() -> Result<Self> {
+        Self::parse(&std::env::var("LOG_FORMAT").unwrap_or_default())
+    }
+}
+
+/// Installs the global subscriber, filtered by `RUST_LOG` (default info),
+/// along with the trace exporter's layer (idle until `telemetry::start`).
+/// The filter can be changed later with [`set_filter`].
+pub fn init(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into());
+    let initial = filter.to_string();
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(Filter {
+        handle,
+        initial,
+        generation: AtomicU64::new(0),
+    });
+    let registry = tracing_subscriber::registry().with(filter).with(telemetry::OtlpLayer);
+    match format {
+        LogFormat::Text => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Json => registry.with(JsonLayer::new(std::io::stdout)).init(),
+    }
+}
+
+static FILTER: OnceLock<Filter> = OnceLock::new();
+
+struct Filter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// The filter at startup, restored when a temporary change runs out
+    initial: String,
+    /// Bumped on every change, so only the latest one's revert applies
+    generation: AtomicU64,
+}
+
+/// The log filter in effect, e.g. `info,polymarket_copy_bot::watcher=debug`;
+/// `None` before [`init`].
+pub fn current_filter() -> Option<String> {
+    let filter = FILTER.get()?;
+    filter.handle.with_current(|f| f.to_string()).ok()
+}
+
+/// Replaces the log filter with `directives` (`RUST_LOG` syntax, e.g.
+/// `info,polymarket_copy_bot::watcher=debug`), going back to the startup
+/// filter after `revert_after` if given. Must be called within a runtime
+/// when reverting.
+pub fn set_filter(directives: &str, revert_after: Option<Duration>) -> Result<()> {
+    let filter = FILTER.get().context("Logging is not initialized")?;
+    let new = EnvFilter::try_new(directives).with_context(|| format!("Invalid log filter '{}'", directives))?;
+    filter.handle.reload(new)?;
+    let generation = filter.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    match revert_after {
+        Some(after) => {
+            tracing::info!("🔧 Log filter set to '{}' for {}", directives, format_duration(after));
+            tokio::spawn(async move {
+                tokio::time::sleep(after).await;
+                if filter.generation.load(Ordering::SeqCst) == generation {
+                    let _ = filter.handle.reload(EnvFilter::new(&filter.initial));
+                    tracing::info!("🔧 Log filter back to '{}'", filter.initial);
+                }
+            });
+        }
+        None => tracing::info!("🔧 Log filter set to '{}'", directives),
+    }
+    Ok(())
+}
+
+/// Short stable id for a leader trade; the same trade seen twice (or
+/// replayed) gets the same id.
+pub fn correlation_id(trade: &Trade) -> String {
+    let digest = Sha256::digest(trade_key(trade).as_bytes());
+    format!("t-{}", hex::encode(&digest[..6]))
+}
+
+/// Fields recorded on a span, kept in its extensions.
+struct SpanFields(Map<String, Value>);
+
+pub(crate) struct JsonVisitor<'a>(pub(crate) &'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.0.insert(field.name().to_string(), Value::from(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+/// Writes each event as a JSON line with its level, target, message, its
+/// own fields and those of every span it happened in.
+pub struct JsonLayer<W> {
+    make_writer: W,
+}
+
+impl<W> JsonLayer<W>
+where
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    pub fn new(make_writer: W) -> Self {
+        Self { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for JsonLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert("timestamp".to_string(), Value::from(chrono::Utc::now().to_rfc3339()));
+        line.insert("level".to_string(), Value::from(metadata.level().as_str()));
+        line.insert("target".to_string(), Value::from(metadata.target()));
+
+        // Outermost span first, so inner spans and the event itself win
+        let mut spans = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                spans.push(Value::from(span.name()));
+                let extensions = span.extensions();
+                if let Some(SpanFields(fields)) = extensions.get::<SpanFields>() {
+                    line.extend(fields.clone());
+                }
+            }
+        }
+        if !spans.is_empty() {
+            line.insert("spans".to_string(), Value::Array(spans));
+        }
+        event.record(&mut JsonVisitor(&mut line));
+
+        let mut writer = self.make_writer.make_writer_for(metadata);
+        let _ = writeln!(writer, "{}", Value::Object(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TradeSide;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trade(tx_hash: &str) -> Trade {
+        Trade {
+            wallet: "0xabc".to_string(),
+            event_id: "e1".to_string(),
+            market_id: "m1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.5,
+            timestamp: 0,
+            tx_hash: Some(tx_hash.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_correlation_id() {
+        let id = correlation_id(&trade("0x01"));
+        assert_eq!(id, correlation_id(&trade("0x01")));
+        assert_ne!(id, correlation_id(&trade("0x02")));
+        assert_eq!(id.len(), 14);
+    }
+
+    #[test]
+    fn test_json_lines_carry_span_fields() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(JsonLayer::new(move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("copy", cid = "t-0123456789ab", wallet = "0xabc");
+            let _guard = span.enter();
+            tracing::info!(shares = 20.0, order_id = "o-1", "Order filled");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "Order filled");
+        assert_eq!(line["cid"], "t-0123456789ab");
+        assert_eq!(line["wallet"], "0xabc");
+        assert_eq!(line["shares"], 20.0);
+        assert_eq!(line["order_id"], "o-1");
+        assert_eq!(line["spans"], serde_json::json!(["copy"]));
+    }
+
+    #[test]
+    fn test_set_filter_needs_init() {
+        // Tests never install the global subscriber
+        assert!(set_filter("debug", None).is_err());
+        assert_eq!(current_filter(), None);
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!(LogFormat::parse("").unwrap(), LogFormat::Text);
+        assert_eq!(LogFormat::parse("JSON").unwrap(), LogFormat::Json);
+        assert!(LogFormat::parse("xml").is_err());
+    }
+}
@@ -1,166 +1,947 @@
 use anyhow::Result;
-use chrono::Timelike;
-use std::sync::Arc;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use polymarket_copy_bot::{api, config, executor, risk, sizing, types, watcher};
+use polymarket_copy_bot::cli::{
+    self, Command, DataCommand, LeadersCommand, MarketsCommand, OrdersCommand, OutputFormat, PositionsCommand,
+    ReportCommand,
+};
+use polymarket_copy_bot::clock::SimClock;
+use polymarket_copy_bot::types::{self, Config, TradeFeed};
+use polymarket_copy_bot::subgraph::SubgraphWatcher;
+use polymarket_copy_bot::watcher::{TradeSource, WalletWatcher};
+use polymarket_copy_bot::{
+    api, audit, backtest, builder, cashflow, clock, completions, config, counterfactual, data_api, dataset, doctor,
+    events, executor, export, fills, gamma, leaders, lint, logging, manual, markets, marks, mempool, montecarlo,
+    notify, paper, pnl, replay, report, scout, sealed, slippage, snapshot, storage, stress, sweep, tail, tearsheet,
+    tui, wizard,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-    
+    // Initialize logging (LOG_FORMAT=json for one JSON object per line)
+    logging::init(logging::LogFormat::from_env()?);
+
+    let mut args = cli::parse(std::env::args().skip(1))?;
+
+    match &args.command {
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Command::Seal(path) => {
+            let plaintext = std::fs::read_to_string(path)?;
+            let secret = sealed::load_secret()?
+                .ok_or_else(|| anyhow::anyhow!("Set CONFIG_PASSPHRASE or CONFIG_KEYFILE to seal a section"))?;
+            println!("{}", sealed::seal(&plaintext, &secret)?);
+            return Ok(());
+        }
+        Command::Completions(shell) => {
+            print!("{}", completions::script(*shell));
+            return Ok(());
+        }
+        Command::Init => {
+            let path = args
+                .cli
+                .config_file
+                .clone()
+                .unwrap_or(config::DEFAULT_CONFIG_FILE.into());
+            return wizard::init(&path);
+        }
+        // An override rather than a flag on the loaded config, so it
+        // survives config reloads
+        Command::Paper => args.cli.push_assignment("paper_trading=true")?,
+        _ => {}
+    }
+
+    // Load configuration (defaults < file < env < CLI)
+    let loaded = config::load_layered(&args.cli)?;
+    let json = args.output == OutputFormat::Json;
+
+    match args.command {
+        Command::ShowConfig if json => print_json(&loaded.provenance()),
+        Command::ShowConfig => {
+            print!("{}", loaded.provenance_report());
+            Ok(())
+        }
+        Command::Replay(path) => {
+            let records = events::read_events(&path)?;
+            let mut config = loaded.config;
+            // Replays must not touch the journal, the log, the exchange or the operator
+            config.storage_url.clear();
+            config.event_log.clear();
+            notify::disable(&mut config);
+            config.paper_trading = true;
+            config.shadow = None;
+            let clock = SimClock::default();
+            let bot = builder::BotBuilder::from_config(config)
+                .clock(std::sync::Arc::new(clock.clone()))
+                .build()
+                .await?;
+            print!("{}", replay::replay(&bot, &clock, &records).await);
+            Ok(())
+        }
+        Command::Snapshot(path) => {
+            let storage = open_journal(&loaded.config, "snapshot").await?;
+            let snap = snapshot::Snapshot::take(storage.as_ref()).await?;
+            snap.write(&path)?;
+            println!("Saved {} to {}", snap, path.display());
+            Ok(())
+        }
+        Command::Restore(path) => {
+            let storage = open_journal(&loaded.config, "restore").await?;
+            let snap = snapshot::Snapshot::read(&path)?;
+            let report = snap.restore(storage.as_ref()).await?;
+            println!("{} from {}", report, path.display());
+            Ok(())
+        }
+        Command::Export { table, out, from, to } => {
+            let range = export::date_range(from.as_deref(), to.as_deref())?;
+            let storage = open_journal(&loaded.config, "export").await?;
+            let rows = export::export(storage.as_ref(), table, range, loaded.config.cost_basis, &out).await?;
+            println!("Exported {} rows to {}", rows, out.display());
+            Ok(())
+        }
+        Command::Positions(command) => {
+            let storage = open_journal(&loaded.config, "positions").await?;
+            positions(&manual::Desk::open(&loaded.config, storage).await?, command, json).await
+        }
+        Command::Pnl => {
+            let storage = open_journal(&loaded.config, "pnl").await?;
+            let marks = journal_marks(&loaded.config, &storage).await?;
+            let report = pnl::from_journal(storage.as_ref(), loaded.config.cost_basis, &marks).await?;
+            if json {
+                print_json(&report)
+            } else {
+                print!("{}", report);
+                Ok(())
+            }
+        }
+        Command::Slippage { from, to } => {
+            let storage = open_journal(&loaded.config, "slippage").await?;
+            let range = export::date_range(from.as_deref(), to.as_deref())?;
+            let marks = journal_marks(&loaded.config, &storage).await?;
+            let report = slippage::from_journal(storage.as_ref(), range, marks.markets()).await?;
+            if json {
+                print_json(&report)
+            } else {
+                print!("{}", report);
+                Ok(())
+            }
+        }
+        Command::Benchmark => {
+            let storage = open_journal(&loaded.config, "benchmark").await?;
+            let marks = journal_marks(&loaded.config, &storage).await?;
+            let report = counterfactual::from_journal(storage.as_ref(), &marks, loaded.config.fixed_stake.as_f64()).await?;
+            if json {
+                print_json(&report)
+            } else {
+                print!("{}", report);
+                Ok(())
+            }
+        }
+        Command::Ledger => {
+            let storage = open_journal(&loaded.config, "ledger").await?;
+            let ledger = cashflow::from_journal(storage.as_ref()).await?;
+            if json {
+                print_json(&ledger)
+            } else {
+                print!("{}", ledger);
+                Ok(())
+            }
+        }
+        Command::Orders(command) => {
+            let storage = open_journal(&loaded.config, "orders").await?;
+            orders(&manual::Desk::open(&loaded.config, storage).await?, command, json).await
+        }
+        Command::Leaders(command) => {
+            let storage = open_journal(&loaded.config, "leaders").await?;
+            leaders(&loaded.config, &storage, command, json).await
+        }
+        Command::Tail {
+            path,
+            follow,
+            wallet,
+            market,
+            only,
+        } => {
+            let path = match path {
+                Some(path) => path,
+                None if !loaded.config.event_log.is_empty() => loaded.config.event_log.clone().into(),
+                None => anyhow::bail!("tail reads the bot's event log; set EVENT_LOG or give a path"),
+            };
+            tail::tail(path, tail::TailFilter::new(wallet, market, only), follow, json).await
+        }
+        Command::Markets(command) => {
+            // The journal's catalog is searched too, when there is one
+            let storage = match loaded.config.storage_url.as_str() {
+                "" => None,
+                url => Some(storage::open(url).await?),
+            };
+            let api = api::PolymarketApi::new(loaded.config.polymarket_api.clone());
+            let catalog = markets::MarketCache::from_config(&loaded.config, api.clone(), storage);
+            catalog.load().await?;
+            market_explorer(&catalog, &api, command, json).await
+        }
+        Command::Backtest {
+            options,
+            out,
+            simulations,
+            seed,
+        } => {
+            let mut config = loaded.config;
+            let (history, model) = prepare_backtest(&mut config, &options).await?;
+            let clock = SimClock::default();
+            let bot = builder::BotBuilder::from_config(config)
+                .clock(std::sync::Arc::new(clock.clone()))
+                .build()
+                .await?;
+            let mut report = backtest::run(&bot, &clock, &history, options.balance, model.as_ref(), options.fee).await;
+            let seed = seed.unwrap_or_else(rand::random);
+            report.monte_carlo = montecarlo::simulate(&report, bot.config(), options.balance, simulations, seed);
+            if let Some(out) = &out {
+                tearsheet::write(&report, "Backtest", out)?;
+                tracing::info!("📝 Wrote the tear sheet to {}", out.display());
+            }
+            if json {
+                return print_json(&report);
+            }
+            print!("{}", report);
+            Ok(())
+        }
+        Command::Sweep {
+            backtest: options,
+            folds,
+            ratios,
+            slippages,
+            budgets,
+            price_bands,
+        } => {
+            let mut config = loaded.config;
+            let (history, model) = prepare_backtest(&mut config, &options).await?;
+            // Parameters not swept keep their configured values
+            let mut grid = sweep::Grid::from_config(&config);
+            grid.ratios = ratios;
+            if !slippages.is_empty() {
+                grid.slippages = slippages;
+            }
+            if !budgets.is_empty() {
+                grid.latency_budgets = budgets;
+            }
+            if !price_bands.is_empty() {
+                grid.price_bands = price_bands;
+            }
+            let report = sweep::run(
+                &config,
+                &history,
+                &grid,
+                folds,
+                options.balance,
+                model.as_ref(),
+                options.fee,
+            )
+            .await?;
+            if json {
+                return print_json(&report);
+            }
+            print!("{}", report);
+            Ok(())
+        }
+        Command::Stress(plan) => {
+            let report = stress::run(loaded.config, plan).await?;
+            if json {
+                print_json(&report)?;
+            } else {
+                print!("{}", report);
+            }
+            if !report.passed() {
+                anyhow::bail!("{} trades lost, {} copied twice", report.lost, report.copied_twice);
+            }
+            Ok(())
+        }
+        Command::Data(DataCommand::Fetch {
+            wallets,
+            markets,
+            from,
+            to,
+            interval,
+        }) => {
+            let config = loaded.config;
+            let storage = open_journal(&config, "data fetch").await?;
+            let wallets = match (wallets.is_empty(), markets.is_empty()) {
+                (true, true) if config.wallets_to_track.is_empty() => {
+                    anyhow::bail!("data fetch needs --wallet, --market or leaders to track")
+                }
+                (true, true) => config.wallets_to_track.clone(),
+                _ => wallets,
+            };
+            let api = api::PolymarketApi::new(config.polymarket_api.clone());
+            let catalog = markets::MarketCache::from_config(&config, api.clone(), Some(storage.clone()));
+            catalog.load().await?;
+            let request = dataset::FetchRequest {
+                wallets,
+                markets,
+                range: export::date_range(Some(&from), to.as_deref())?,
+                interval,
+            };
+            let report = dataset::fetch(&api, &catalog, storage.as_ref(), &request).await?;
+            if json {
+                return print_json(&report);
+            }
+            print!("{}", report);
+            Ok(())
+        }
+        Command::Scout {
+            since,
+            limit,
+            wallets,
+            latency,
+        } => {
+            let config = loaded.config;
+            let api = api::PolymarketApi::new(config.polymarket_api.clone());
+            let request = scout::ScoutRequest {
+                since,
+                limit,
+                wallets,
+                tracked: config.wallets_to_track.clone(),
+                latency: latency.unwrap_or(config.fill_latency),
+                max_slippage: config.max_slippage.as_f64(),
+            };
+            let gamma = gamma::Client::from_config(&config);
+            let data = data_api::Client::from_config(&config);
+            let report = scout::scout(&api, gamma.as_ref(), data.as_ref(), &request).await?;
+            if json {
+                return print_json(&report);
+            }
+            print!("{}", report);
+            Ok(())
+        }
+        Command::Report(ReportCommand::Wallet { wallet, since }) => {
+            // The journal only adds trades of wallets already watched
+            let storage = match loaded.config.storage_url.as_str() {
+                "" => None,
+                url => Some(storage::open(url).await?),
+            };
+            let api = api::PolymarketApi::new(loaded.config.polymarket_api.clone());
+            let data = data_api::Client::from_config(&loaded.config);
+            let report = report::wallet_report(&api, data.as_ref(), storage.as_deref(), &wallet, since).await?;
+            if json {
+                print_json(&report)
+            } else {
+                print!("{}", report);
+                Ok(())
+            }
+        }
+        Command::Report(ReportCommand::Paper { from, to, out }) => {
+            let storage = open_journal(&loaded.config, "report paper").await?;
+            let range = export::date_range(from.as_deref(), to.as_deref())?;
+            // Categories for the markets traded
+            let api = api::PolymarketApi::new(loaded.config.polymarket_api.clone());
+            let mut traded = std::collections::HashMap::new();
+            for fill in storage.fills(range).await? {
+                if let std::collections::hash_map::Entry::Vacant(slot) = traded.entry(fill.market_id) {
+                    match api.get_market(slot.key()).await {
+                        Ok(market) => {
+                            slot.insert(market);
+                        }
+                        Err(e) => tracing::warn!("Failed to fetch market {}: {}", slot.key(), e),
+                    }
+                }
+            }
+            let marks = marks::Marks::new(
+                std::sync::Arc::new(markets::MarketCache::from_config(
+                    &loaded.config,
+                    api,
+                    Some(storage.clone()),
+                )),
+                loaded.config.mark_max_age,
+                clock::system(),
+            );
+            marks.load(storage.as_ref()).await?;
+            let traded_ids: Vec<&str> = traded.keys().map(String::as_str).collect();
+            let marked = marks.marks(&traded_ids).await;
+            let report = paper::report(storage.as_ref(), &loaded.config, range, &traded, &marked).await?;
+            match out {
+                Some(out) => {
+                    tearsheet::write(&report, "Paper run", &out)?;
+                    println!("Wrote the tear sheet to {}", out.display());
+                    Ok(())
+                }
+                None if json => print_json(&report),
+                None => {
+                    print!("{}", report);
+                    Ok(())
+                }
+            }
+        }
+        Command::Watch => watch(&loaded.config).await,
+        Command::Tui => tui::Dashboard::from_config(&loaded.config)?.run().await,
+        Command::Mempool => {
+            tracing::info!("🔍 Mempool Monitor Starting...");
+            mempool::monitor(&loaded.config.rpc_url, &loaded.config.wallets_to_track).await
+        }
+        Command::Doctor => {
+            let findings = doctor::diagnose(&loaded.config).await;
+            if json {
+                print_json(&findings)?;
+            } else {
+                for finding in &findings {
+                    println!("{}", finding);
+                }
+            }
+            let failed = findings.iter().filter(|f| f.outcome == doctor::Outcome::Fail).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} checks failed", failed, findings.len());
+            }
+            Ok(())
+        }
+        Command::CheckConfig => run(args.cli, loaded.config, Some(args.output)).await,
+        _ => run(args.cli, loaded.config, None).await,
+    }
+}
+
+/// Validates the config and builds the bot, then either prints the lints
+/// (`check_config` in that format) or runs it.
+async fn run(cli: config::CliOverrides, config: Config, check_config: Option<OutputFormat>) -> Result<()> {
     tracing::info!("🚀 Polymarket Copy Trading Bot Starting...");
-    
-    // Load configuration
-    let config = config::load_config()?;
-    config::validate_config(&config)?;
-    
+
+    // Validate and initialize components
+    let bot = builder::BotBuilder::from_config(config).build().await?;
+    bot.set_config_source(std::sync::Arc::new(move || Ok(config::load_layered(&cli)?.config)));
+    let config = bot.config();
+
     tracing::info!("✅ Configuration loaded");
     tracing::info!("   Tracking {} wallets", config.wallets_to_track.len());
     tracing::info!("   Sizing mode: {:?}", config.sizing_mode);
     tracing::info!("   Your wallet: {}", &config.your_wallet[..10]);
-    
-    // Initialize components
-    let api = api::PolymarketApi::new(config.polymarket_api.clone());
-    let watcher = watcher::WalletWatcher::new(
-        config.ws_url.clone(),
-        config.wallets_to_track.clone(),
-    );
-    let sizer = sizing::PositionSizer::new(config.clone());
-    let risk = Arc::new(risk::RiskManager::new(config.clone()));
-    let executor = executor::TradeExecutor::new(api.clone(), config.clone());
-    
+
+    let bankroll = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        bot.api().get_balance(&config.your_wallet),
+    )
+    .await
+    .ok()
+    .and_then(|r| r.ok());
+    let lints = lint::lint_config(config, bankroll);
+
+    if let Some(output) = check_config {
+        if output == OutputFormat::Json {
+            print_json(&lints)?;
+        } else {
+            for l in &lints {
+                println!("{}", l);
+            }
+        }
+        if lints.iter().any(|l| l.severity == lint::Severity::Danger) {
+            anyhow::bail!("Config check found dangerous settings");
+        }
+        if output == OutputFormat::Table {
+            println!("Config OK ({} lints)", lints.len());
+        }
+        return Ok(());
+    }
+
+    for l in &lints {
+        match l.severity {
+            lint::Severity::Info => tracing::info!("💡 {}", l),
+            _ => tracing::warn!("⚠️  {}", l),
+        }
+    }
+
+    if config.paper_trading {
+        tracing::info!("📝 Paper trading enabled - orders are simulated");
+    }
     tracing::info!("✅ Components initialized");
-    
-    // Start watching wallets
-    let trade_rx = watcher.start().await?;
-    tracing::info!("✅ WebSocket watchers started");
-    
-    // Reset daily stats at midnight
-    let risk_clone = Arc::clone(&risk);
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600));
-        loop {
-            interval.tick().await;
-            let now = chrono::Utc::now();
-            if now.hour() == 0 && now.minute() < 1 {
-                risk_clone.reset_daily_stats();
-            }
-        }
-    });
-    
-    // Main trading loop
-    tracing::info!("🎯 Bot is now live and monitoring trades...");
-    
-    while let Ok(whale_trade) = trade_rx.recv().await {
-        tracing::info!("📊 Detected trade from {}: {} {:.2} shares @ ${:.4}",
-            &whale_trade.wallet[..10],
-            match whale_trade.side {
-                types::TradeSide::BUY => "BUY",
-                types::TradeSide::SELL => "SELL",
-            },
-            whale_trade.shares,
-            whale_trade.price
-        );
-        
-        // Verify whale
-        if !risk.is_whale_verified(&whale_trade.wallet) {
-            tracing::warn!("⚠️  Unverified wallet, skipping");
-            continue;
-        }
-        
-        // Get market info
-        let market = match api.get_market(&whale_trade.market_id).await {
-            Ok(m) => m,
-            Err(e) => {
-                tracing::error!("Failed to fetch market: {}", e);
-                risk.record_error(&format!("Market fetch failed: {}", e));
-                continue;
-            }
-        };
-        
-        tracing::info!("   Market: {}", market.question);
-        tracing::info!("   Liquidity: ${:.2}", market.liquidity);
-        
-        // Get balances
-        let your_balance = match api.get_balance(&config.your_wallet).await {
-            Ok(b) => b,
-            Err(e) => {
-                tracing::error!("Failed to fetch your balance: {}", e);
-                risk.record_error(&format!("Balance fetch failed: {}", e));
-                continue;
-            }
-        };
-        
-        let whale_balance = match api.get_balance(&whale_trade.wallet).await {
-            Ok(b) => b,
-            Err(e) => {
-                tracing::error!("Failed to fetch whale balance: {}", e);
-                1000000.0 // Default to large number if we can't fetch
-            }
-        };
-        
-        // Calculate position size
-        let size_usd = match sizer.calculate_size(&whale_trade, your_balance, whale_balance).await {
-            Ok(s) => s,
-            Err(e) => {
-                tracing::error!("Failed to calculate size: {}", e);
-                risk.record_error(&format!("Sizing failed: {}", e));
-                continue;
-            }
-        };
-        
-        let shares = sizer.shares_from_usd(size_usd, whale_trade.price);
-        
-        tracing::info!("   Your size: ${:.2} ({:.2} shares)", size_usd, shares);
-        
-        // Risk checks
-        if let Err(e) = risk.check_can_trade(&whale_trade, &market, size_usd) {
-            tracing::error!("❌ Risk check failed: {}", e);
-            continue;
-        }
-        
-        tracing::info!("✅ Risk checks passed");
-        
-        // Execute trade
-        tracing::info!("🔄 Executing mirror trade...");
-        
-        match executor.execute_trade(&whale_trade, shares).await {
-            Ok(resp) => {
-                tracing::info!("✅ Trade executed successfully!");
-                tracing::info!("   Order ID: {}", resp.order_id);
-                tracing::info!("   Filled: {:.2} shares @ ${:.4}", resp.filled_shares, resp.avg_fill_price);
-                tracing::info!("   Total: ${:.2}", resp.filled_shares * resp.avg_fill_price);
-                
-                risk.record_trade(&whale_trade, size_usd);
-            }
-            Err(e) => {
-                tracing::error!("❌ Trade execution failed: {}", e);
-                risk.record_error(&format!("Execution failed: {}", e));
-            }
-        }
-        
-        // Show circuit breaker status
-        let cb_state = risk.get_state();
-        tracing::info!("📈 Daily stats: {} trades, ${:.2} volume", 
-            cb_state.total_trades_today, 
-            cb_state.total_volume_today
-        );
-        
-        if cb_state.is_tripped {
-            tracing::error!("⚠️  CIRCUIT BREAKER TRIPPED - Bot paused!");
-        }
-        
-        tracing::info!("---");
+
+    bot.run().await
+}
+
+/// Prints every leader trade from the feeds as a line of JSON, without
+/// deciding or trading.
+async fn watch(config: &Config) -> Result<()> {
+    let watcher: Box<dyn TradeSource> = match config.trade_source {
+        TradeFeed::WebSocket => Box::new(WalletWatcher::new(config.ws_url.clone(), config.wallets_to_track.clone())),
+        TradeFeed::Subgraph => Box::new(SubgraphWatcher::from_config(config)),
+    };
+    let trades = watcher.start().await?;
+    tracing::info!("👀 Watching {} wallets", config.wallets_to_track.len());
+    while let Ok(trade) = trades.recv().await {
+        println!("{}", serde_json::to_string(&trade)?);
+    }
+    Ok(())
+}
+
+async fn positions(desk: &manual::Desk, command: PositionsCommand, json: bool) -> Result<()> {
+    let price = |p: Option<f64>| p.map(|p| format!("{:.4}", p)).unwrap_or_else(|| "-".to_string());
+    let pnl = |p: Option<f64>| p.map(|p| format!("{:+.2}", p)).unwrap_or_else(|| "-".to_string());
+    match command {
+        PositionsCommand::List if json => {
+            let views = desk.positions().await;
+            print_json(&views.iter().map(manual::PositionView::to_json).collect::<Vec<_>>())?;
+        }
+        PositionsCommand::List => {
+            println!(
+                "{:<66} {:>12} {:>10} {:>12} {:>8} {:>10}",
+                "market", "shares", "avg_price", "cost", "mark", "upnl"
+            );
+            for view in desk.positions().await {
+                let h = &view.holding;
+                println!(
+                    "{:<66} {:>12.2} {:>10.4} {:>12.2} {:>8} {:>10}",
+                    h.market_id,
+                    h.shares(),
+                    h.avg_price(),
+                    h.cost(),
+                    price(view.mark.map(|m| m.price)),
+                    pnl(view.unrealized_pnl())
+                );
+            }
+        }
+        PositionsCommand::Show(market) if json => print_json(&desk.position(&market).await?.to_json())?,
+        PositionsCommand::Show(market) => {
+            let view = desk.position(&market).await?;
+            let h = &view.holding;
+            println!("market:     {}", h.market_id);
+            if let Some(question) = &view.question {
+                println!("question:   {}", question);
+            }
+            println!("shares:     {:.2}", h.shares());
+            println!(
+                "cost:       ${:.2} ({:?}, avg ${:.4})",
+                h.cost(),
+                desk.cost_basis(),
+                h.avg_price()
+            );
+            match view.mark {
+                Some(mark) => println!(
+                    "mark:       {:.4} ({}{})",
+                    mark.price,
+                    mark.source.as_str(),
+                    if mark.stale { ", stale" } else { "" }
+                ),
+                None => println!("mark:       -"),
+            }
+            println!("unrealized: {}", pnl(view.unrealized_pnl()));
+            println!("lots:");
+            for lot in &h.lots {
+                let opened = chrono::DateTime::from_timestamp_millis(lot.opened_at).unwrap_or_default();
+                println!(
+                    "  {:>12.2} @ {:.4}  opened {}",
+                    lot.shares,
+                    lot.price,
+                    opened.format("%Y-%m-%d %H:%M")
+                );
+            }
+        }
+        PositionsCommand::Close { market, shares, yes } => {
+            let quote = desk.quote_close(&market, shares).await?;
+            if !yes && !confirm(&format!("{}?", quote))? {
+                println!("Nothing sent");
+                return Ok(());
+            }
+            print_placed(desk.place(quote.order).await?, json)?;
+        }
+    }
+    Ok(())
+}
+
+async fn orders(desk: &manual::Desk, command: OrdersCommand, json: bool) -> Result<()> {
+    match command {
+        OrdersCommand::List if json => print_json(&desk.open_orders().await?)?,
+        OrdersCommand::List => {
+            println!(
+                "{:>6} {:<66} {:<4} {:>12} {:>8} {:<10}",
+                "id", "market", "side", "shares", "limit", "status"
+            );
+            for order in desk.open_orders().await? {
+                println!(
+                    "{:>6} {:<66} {:<4} {:>12.2} {:>8} {:<10}",
+                    order.id,
+                    order.market_id,
+                    order.side,
+                    order.shares,
+                    order.limit_price.map(|p| format!("{:.4}", p)).unwrap_or_default(),
+                    order.status
+                );
+            }
+        }
+        OrdersCommand::Cancel(target) => {
+            let (cancelled, failed) = desk.cancel(&target).await?;
+            if json {
+                print_json(&serde_json::json!({ "cancelled": cancelled, "failed": failed }))?;
+            } else {
+                println!("Cancelled {} orders", cancelled.len());
+                for id in &cancelled {
+                    println!("  {}", id);
+                }
+                for error in &failed {
+                    eprintln!("  failed: {}", error);
+                }
+            }
+            if !failed.is_empty() {
+                anyhow::bail!("{} orders couldn't be cancelled", failed.len());
+            }
+        }
+        OrdersCommand::Place {
+            token,
+            side,
+            price,
+            size,
+            order_type,
+            yes,
+        } => {
+            let limit = price.map(|p| format!("limit ${:.4} {:?}", p, order_type));
+            let question = format!(
+                "{} {:.2} shares of {} at {}?",
+                side.as_str(),
+                size,
+                token,
+                limit.unwrap_or_else(|| "market".to_string())
+            );
+            if !yes && !confirm(&question)? {
+                println!("Nothing sent");
+                return Ok(());
+            }
+            let order = types::OrderRequest {
+                market_id: token,
+                side,
+                shares: size,
+                price,
+                order_type,
+                client_order_id: executor::new_client_order_id(),
+            };
+            print_placed(desk.place(order).await?, json)?;
+        }
     }
-    
-    tracing::info!("Bot stopped");
     Ok(())
 }
+
+async fn leaders(
+    config: &Config,
+    journal: &std::sync::Arc<dyn storage::Storage>,
+    command: LeadersCommand,
+    json: bool,
+) -> Result<()> {
+    let storage = journal.as_ref();
+    let now = storage::now_ms();
+    let mut registry = leaders::load_registry(storage).await?;
+    let (action, wallet) = match command {
+        LeadersCommand::List => {
+            let labels = leaders::parse_labels(&config.leader_labels)?;
+            let tracked = leaders::tracked_wallets(&config.wallets_to_track, &registry);
+            let mut wallets = config.wallets_to_track.clone();
+            for entry in &registry {
+                if !wallets.iter().any(|w| w.eq_ignore_ascii_case(&entry.wallet)) {
+                    wallets.push(entry.wallet.clone());
+                }
+            }
+            let rows: Vec<serde_json::Value> = wallets
+                .iter()
+                .filter(|w| !w.is_empty())
+                .map(|wallet| {
+                    let entry = registry.iter().find(|e| e.wallet.eq_ignore_ascii_case(wallet));
+                    let configured = config.wallets_to_track.iter().any(|w| w.eq_ignore_ascii_case(wallet));
+                    serde_json::json!({
+                        "wallet": wallet,
+                        "source": if configured { "config" } else { "registry" },
+                        "status": if tracked.contains(wallet) { "copying" } else { "paused" },
+                        "label": entry
+                            .and_then(|e| e.label.clone())
+                            .or_else(|| labels.get(&wallet.to_lowercase()).cloned()),
+                        "profile": entry.and_then(|e| e.profile.clone()),
+                    })
+                })
+                .collect();
+            if json {
+                return print_json(&rows);
+            }
+            println!(
+                "{:<44} {:<8} {:<9} {:<16} profile",
+                "wallet", "source", "status", "label"
+            );
+            let text = |v: &serde_json::Value| v.as_str().unwrap_or_default().to_string();
+            for row in &rows {
+                println!(
+                    "{:<44} {:<8} {:<9} {:<16} {}",
+                    text(&row["wallet"]),
+                    text(&row["source"]),
+                    text(&row["status"]),
+                    text(&row["label"]),
+                    text(&row["profile"])
+                );
+            }
+            return Ok(());
+        }
+        LeadersCommand::Stats { from, to } => {
+            let range = export::date_range(from.as_deref(), to.as_deref())?;
+            let marks = journal_marks(config, journal).await?;
+            let ranked = leaders::from_journal(storage, range, config.cost_basis, &marks).await?;
+            if json {
+                return print_json(&ranked);
+            }
+            println!(
+                "{:<4} {:<44} {:>9} {:>7} {:>12} {:>12} {:>12} {:>12} {:>12} {:>5} {:>9} {:>9}",
+                "rank",
+                "leader",
+                "decisions",
+                "copied",
+                "bought",
+                "sold",
+                "realized",
+                "unrealized",
+                "open_cost",
+                "wins",
+                "slippage",
+                "latency"
+            );
+            let pct = |v: Option<f64>| {
+                v.map(|v| format!("{:.1}%", v * 100.0))
+                    .unwrap_or_else(|| "-".to_string())
+            };
+            for (i, p) in ranked.iter().enumerate() {
+                println!(
+                    "{:<4} {:<44} {:>9} {:>6.0}% {:>12.2} {:>12.2} {:>+12.2} {:>+12.2} {:>12.2} {:>5} {:>9} {:>9}",
+                    i + 1,
+                    p.wallet,
+                    p.decisions,
+                    p.copy_rate() * 100.0,
+                    p.bought_usd,
+                    p.sold_usd,
+                    p.realized_pnl,
+                    p.unrealized_pnl,
+                    p.open_cost,
+                    p.win_rate()
+                        .map(|r| format!("{:.0}%", r * 100.0))
+                        .unwrap_or_else(|| "-".to_string()),
+                    pct(p.avg_slippage),
+                    p.avg_latency_ms
+                        .map(|ms| format!("{:.1}s", ms / 1000.0))
+                        .unwrap_or_else(|| "-".to_string())
+                );
+            }
+            return Ok(());
+        }
+        LeadersCommand::Add { wallet, label, profile } => {
+            if !wallet.starts_with("0x") || wallet.len() <= 2 {
+                anyhow::bail!("Expected a 0x... wallet address, got {}", wallet);
+            }
+            let entry = leaders::entry_mut(&mut registry, &wallet, now);
+            entry.paused = false;
+            entry.label = label.or(entry.label.take());
+            entry.profile = profile.or(entry.profile.take());
+            println!("Copying {}", wallet);
+            (audit::AuditAction::WalletAdded, wallet)
+        }
+        LeadersCommand::Remove(wallet) => {
+            let before = registry.len();
+            registry.retain(|e| !e.wallet.eq_ignore_ascii_case(&wallet));
+            if registry.len() == before {
+                anyhow::bail!("{} isn't in the leader registry", wallet);
+            }
+            if config.wallets_to_track.iter().any(|w| w.eq_ignore_ascii_case(&wallet)) {
+                println!(
+                    "Removed {}; wallets_to_track still lists it, pause it to stop copying",
+                    wallet
+                );
+            } else {
+                println!("Removed {}; a running bot keeps copying it until restarted", wallet);
+            }
+            (audit::AuditAction::WalletRemoved, wallet)
+        }
+        LeadersCommand::Pause(wallet) => {
+            leaders::entry_mut(&mut registry, &wallet, now).paused = true;
+            println!("Paused {}", wallet);
+            (audit::AuditAction::LeaderPaused, wallet)
+        }
+        LeadersCommand::Resume(wallet) => {
+            leaders::entry_mut(&mut registry, &wallet, now).paused = false;
+            println!("Resumed {}", wallet);
+            (audit::AuditAction::LeaderResumed, wallet)
+        }
+    };
+    leaders::save_registry(storage, &registry, now).await?;
+    let entry = registry.iter().find(|e| e.wallet.eq_ignore_ascii_case(&wallet));
+    storage
+        .append_audit(&audit::entry(
+            action,
+            "operator",
+            serde_json::json!({ "wallet": wallet, "leader": entry, "via": "cli" }),
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Markets listed by `markets search`.
+const SEARCH_LIMIT: usize = 20;
+
+async fn market_explorer(
+    catalog: &markets::MarketCache,
+    api: &api::PolymarketApi,
+    command: MarketsCommand,
+    json: bool,
+) -> Result<()> {
+    let ends = |market: &types::Market| {
+        market
+            .end_date
+            .and_then(|s| chrono::DateTime::from_timestamp(s, 0))
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    match command {
+        MarketsCommand::Search(query) => {
+            let found = catalog.search(&query, SEARCH_LIMIT).await;
+            if json {
+                return print_json(&found);
+            }
+            if found.is_empty() {
+                println!("No markets match '{}'", query);
+            }
+            for m in &found {
+                println!("{}", if m.slug.is_empty() { &m.id } else { &m.slug });
+                println!("    {}", m.question);
+                println!(
+                    "    id {}  yes ${:.3}  24h volume ${:.0}  ends {}",
+                    m.id,
+                    m.yes_price,
+                    m.volume_24h,
+                    ends(m)
+                );
+            }
+        }
+        MarketsCommand::Show(slug_or_id) => {
+            let market = catalog.find(&slug_or_id).await?;
+            let book = api.get_orderbook(&market.id).await;
+            let best_bid = book
+                .as_ref()
+                .ok()
+                .and_then(|(bids, _)| bids.iter().copied().max_by(|a, b| a.0.total_cmp(&b.0)));
+            let best_ask = book
+                .as_ref()
+                .ok()
+                .and_then(|(_, asks)| asks.iter().copied().min_by(|a, b| a.0.total_cmp(&b.0)));
+            if json {
+                let level =
+                    |l: Option<(f64, f64)>| l.map(|(price, size)| serde_json::json!({ "price": price, "size": size }));
+                return print_json(&serde_json::json!({
+                    "market": market,
+                    "best_bid": level(best_bid),
+                    "best_ask": level(best_ask),
+                }));
+            }
+            println!("{}", market.question);
+            println!("  slug        {}", market.slug);
+            println!("  id          {}", market.id);
+            println!("  event       {}", market.event_id);
+            for (i, token) in market.token_ids.iter().enumerate() {
+                let outcome = market.outcomes.get(i).map(String::as_str).unwrap_or("?");
+                println!("  {:<11} {:<4} {}", if i == 0 { "tokens" } else { "" }, outcome, token);
+            }
+            println!(
+                "  price       yes ${:.3} / no ${:.3}",
+                market.yes_price, market.no_price
+            );
+            let level = |l: Option<(f64, f64)>| match l {
+                Some((price, size)) => format!("${:.3} x {:.0}", price, size),
+                None => "-".to_string(),
+            };
+            match &book {
+                Ok(_) => println!("  book        bid {}  ask {}", level(best_bid), level(best_ask)),
+                Err(e) => println!("  book        unavailable ({:#})", e),
+            }
+            println!("  tick size   {}", market.tick_size);
+            println!("  liquidity   ${:.0}", market.liquidity);
+            println!("  24h volume  ${:.0}", market.volume_24h);
+            println!("  ends        {}", ends(&market));
+        }
+    }
+    Ok(())
+}
+
+fn print_placed((resp, realized): (types::OrderResponse, f64), json: bool) -> Result<()> {
+    if json {
+        return print_json(&serde_json::json!({ "order": resp, "realized_pnl": realized }));
+    }
+    println!(
+        "Order {} {}: {:.2} shares @ ${:.4}, realized ${:+.2}",
+        resp.order_id, resp.status, resp.filled_shares, resp.avg_fill_price, realized
+    );
+    Ok(())
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal; anything but "y" is a no.
+fn confirm(question: &str) -> Result<bool> {
+    use std::io::Write;
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Loads what `options` asks to replay with the fill model to replay it
+/// through, and isolates `config` for building the bots that replay it.
+async fn prepare_backtest(
+    config: &mut Config,
+    options: &cli::BacktestOptions,
+) -> Result<(backtest::History, std::sync::Arc<dyn fills::FillModel>)> {
+    if let Some(wallet) = &options.wallet {
+        config.wallets_to_track = vec![wallet.clone()];
+    }
+    let api = api::PolymarketApi::new(config.polymarket_api.clone());
+    let kind = options.fill.unwrap_or(config.fill_model);
+    // Trades, books and later prices can all come from the journal
+    let storage = match (options.journal, kind) {
+        (false, types::FillModelKind::Immediate) => None,
+        (true, _) => Some(open_journal(config, "--journal").await?),
+        (false, _) => Some(open_journal(config, "--fill book|latency").await?),
+    };
+    let source = match (&options.data, &storage) {
+        (Some(path), _) => backtest::TradeSource::File(path),
+        (None, Some(storage)) if options.journal => backtest::TradeSource::Journal(storage.as_ref()),
+        _ => backtest::TradeSource::Api,
+    };
+    let history = backtest::History::load(&api, &config.wallets_to_track, source, options.since).await?;
+    let tape = match (&storage, kind) {
+        (Some(storage), types::FillModelKind::Book | types::FillModelKind::Latency) => {
+            let from_ms = history.trades.first().map(|t| t.timestamp * 1000).unwrap_or_default();
+            let tape = fills::RecordedTape::load(storage.as_ref(), storage::TimeRange::since(from_ms)).await?;
+            if tape.is_empty() {
+                tracing::warn!("⚠️  No price samples in the journal for this period; no copy will fill");
+            }
+            tape
+        }
+        _ => fills::RecordedTape::default(),
+    };
+    let model = fills::model(
+        kind,
+        options.latency.unwrap_or(config.fill_latency),
+        std::sync::Arc::new(tape),
+    );
+    // Like replays, backtests must not touch the journal, the log, the exchange or the operator
+    config.storage_url.clear();
+    config.event_log.clear();
+    notify::disable(config);
+    config.paper_trading = true;
+    config.shadow = None;
+    Ok((history, model))
+}
+
+/// Marks for valuing the journal's positions, seeded from its prices.
+async fn journal_marks(config: &Config, storage: &std::sync::Arc<dyn storage::Storage>) -> Result<marks::Marks> {
+    let api = api::PolymarketApi::new(config.polymarket_api.clone());
+    let catalog = markets::MarketCache::from_config(config, api, Some(storage.clone()));
+    catalog.load().await?;
+    let marks = marks::Marks::new(std::sync::Arc::new(catalog), config.mark_max_age, clock::system());
+    marks.load(storage.as_ref()).await?;
+    Ok(marks)
+}
+
+async fn open_journal(config: &Config, command: &str) -> Result<std::sync::Arc<dyn storage::Storage>> {
+    if config.storage_url.is_empty() {
+        anyhow::bail!("{} needs STORAGE_URL to point at a journal", command);
+    }
+    storage::open(&config.storage_url).await
+}
@@ -0,0 +1,341 @@
+//! Manual interventions from the command line.
+//!
+//! `mybot positions ...` and `mybot orders ...` work on the journal's
+//! position store and the exchange directly, so an operator can inspect a
+//! holding, flatten or hedge it, or clear out open orders without writing
+//! SQL or calling the exchange by hand. Orders go through the executor
+//! (simulated with `paper_trading`) and are journaled, booked into the
+//! position store and audited like the bot's own orders, with `operator` as
+//! the actor. A running bot keeps its own copy of the positions, so it only
+//! sees a manual fill after its next restart.
+
+use crate::api::PolymarketApi;
+use crate::audit::{self, AuditAction};
+use crate::exchange;
+use crate::executor::TradeExecutor;
+use crate::clock;
+use crate::marks::{Mark, Marks};
+use crate::markets::MarketCache;
+use crate::portfolio::{Holding, Portfolio};
+use crate::storage::{now_ms, FillRecord, OrderRecord, Storage};
+use crate::types::{Config, CostBasis, OrderRequest, OrderResponse, TradeSide};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// A holding with its current mark, if the market could be fetched.
+#[derive(Debug, Clone)]
+pub struct PositionView {
+    pub holding: Holding,
+    pub question: Option<String>,
+    pub mark: Option<Mark>,
+}
+
+impl PositionView {
+    pub fn unrealized_pnl(&self) -> Option<f64> {
+        self.mark.map(|mark| self.holding.unrealized_pnl(mark.price))
+    }
+
+    /// The fields `/status/positions` serves, plus the question and lots.
+    pub fn to_json(&self) -> Value {
+        let h = &self.holding;
+        json!({
+            "market_id": h.market_id,
+            "question": self.question,
+            "shares": h.shares(),
+            "avg_price": h.avg_price(),
+            "cost_usd": h.cost(),
+            "mark": self.mark.map(|m| m.price),
+            "mark_source": self.mark.map(|m| m.source),
+            "mark_stale": self.mark.map(|m| m.stale),
+            "unrealized_pnl": self.unrealized_pnl(),
+            "lots": h.lots.iter().map(|l| json!({
+                "shares": l.shares,
+                "price": l.price,
+                "opened_at": l.opened_at,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// What closing a position would do, for confirming before it's sent.
+#[derive(Debug, Clone)]
+pub struct CloseQuote {
+    pub order: OrderRequest,
+    /// Best bid, when the book could be read
+    pub estimated_price: Option<f64>,
+    pub avg_price: f64,
+}
+
+impl std::fmt::Display for CloseQuote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:.2} shares of {} at market (bought at ${:.4} avg",
+            self.order.side.as_str(),
+            self.order.shares,
+            self.order.market_id,
+            self.avg_price
+        )?;
+        match self.estimated_price {
+            Some(price) => write!(f, ", best bid ${:.4} for ~${:.2})", price, price * self.order.shares),
+            None => write!(f, ", no bid available)"),
+        }
+    }
+}
+
+/// Which open orders to cancel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CancelTarget {
+    All,
+    /// A journal id or an exchange order id
+    Order(String),
+    Market(String),
+}
+
+/// Positions and manual orders against one journal.
+pub struct Desk {
+    storage: Arc<dyn Storage>,
+    portfolio: Portfolio,
+    marks: Marks,
+    executor: TradeExecutor,
+}
+
+impl Desk {
+    /// Loads the positions in `storage`.
+    pub async fn open(config: &Config, storage: Arc<dyn Storage>) -> Result<Self> {
+        let portfolio = Portfolio::new(config.cost_basis, Some(Arc::clone(&storage)));
+        portfolio.load().await.context("Failed to load positions")?;
+        let api = PolymarketApi::new(config.polymarket_api.clone());
+        let markets = MarketCache::from_config(config, api.clone(), Some(Arc::clone(&storage)));
+        let marks = Marks::new(Arc::new(markets), config.mark_max_age, clock::system());
+        marks.load(storage.as_ref()).await.context("Failed to load prices")?;
+        Ok(Self {
+            marks,
+            executor: TradeExecutor::new(api.clone(), config.clone()).with_exchange(exchange::from_config(config, api)?),
+            storage,
+            portfolio,
+        })
+    }
+
+    pub fn cost_basis(&self) -> CostBasis {
+        self.portfolio.cost_basis()
+    }
+
+    /// Every open position, by market.
+    pub async fn positions(&self) -> Vec<PositionView> {
+        let mut views = Vec::new();
+        for holding in self.portfolio.holdings() {
+            views.push(self.view(holding).await);
+        }
+        views
+    }
+
+    pub async fn position(&self, market_id: &str) -> Result<PositionView> {
+        let holding = self
+            .portfolio
+            .holding(market_id)
+            .with_context(|| format!("No open position in {}", market_id))?;
+        Ok(self.view(holding).await)
+    }
+
+    async fn view(&self, holding: Holding) -> PositionView {
+        let market = self.marks.markets().get(&holding.market_id).await.ok();
+        PositionView {
+            question: market.map(|m| m.question),
+            mark: self.marks.mark(&holding.market_id).await,
+            holding,
+        }
+    }
+
+    /// The order that sells `shares` (all by default) of a position.
+    pub async fn quote_close(&self, market_id: &str, shares: Option<f64>) -> Result<CloseQuote> {
+        let holding = self
+            .portfolio
+            .holding(market_id)
+            .with_context(|| format!("No open position in {}", market_id))?;
+        let held = holding.shares();
+        let shares = shares.unwrap_or(held);
+        if shares <= 0.0 || shares > held + 1e-9 {
+            anyhow::bail!("Can only close between 0 and {:.2} shares of {}", held, market_id);
+        }
+        Ok(CloseQuote {
+            order: self.executor.close_order(market_id, shares, TradeSide::BUY),
+            estimated_price: self
+                .executor
+                .get_estimated_price(market_id, &TradeSide::SELL)
+                .await
+                .ok(),
+            avg_price: holding.avg_price(),
+        })
+    }
+
+    /// Sends an order and books whatever fills. Returns the exchange's
+    /// answer and the PnL realized.
+    pub async fn place(&self, order: OrderRequest) -> Result<(OrderResponse, f64)> {
+        if order.shares <= 0.0 {
+            anyhow::bail!("Order size must be positive");
+        }
+        if order.price.is_some_and(|p| p <= 0.0 || p >= 1.0) {
+            anyhow::bail!("Limit price must be between 0 and 1");
+        }
+        let order_id = self
+            .storage
+            .record_order(&OrderRecord {
+                id: 0,
+                decision_id: None,
+                exchange_order_id: None,
+                market_id: order.market_id.clone(),
+                side: order.side.as_str().to_string(),
+                shares: order.shares,
+                limit_price: order.price,
+                order_type: format!("{:?}", order.order_type),
+                status: "submitting".to_string(),
+                error: None,
+                submitted_at: now_ms(),
+                client_order_id: Some(order.client_order_id.clone()),
+            })
+            .await
+            .context("Failed to journal the order; nothing was sent")?;
+
+        let result = self.executor.submit_order(order.clone()).await;
+        match &result {
+            Ok(resp) => {
+                self.storage
+                    .update_order(order_id, &resp.status, Some(&resp.order_id), None)
+                    .await?
+            }
+            Err(e) => {
+                self.storage
+                    .update_order(order_id, "failed", None, Some(&e.to_string()))
+                    .await?
+            }
+        }
+        self.storage
+            .append_audit(&audit::entry(
+                AuditAction::OrderPlaced,
+                "operator",
+                json!({
+                    "client_order_id": order.client_order_id,
+                    "market_id": order.market_id,
+                    "side": order.side.as_str(),
+                    "shares": order.shares,
+                    "limit_price": order.price,
+                    "via": "cli",
+                    "exchange_order_id": result.as_ref().ok().map(|r| r.order_id.clone()),
+                    "error": result.as_ref().err().map(|e| e.to_string()),
+                }),
+            ))
+            .await?;
+
+        let resp = result?;
+        if resp.filled_shares <= 0.0 {
+            return Ok((resp, 0.0));
+        }
+        let fill = FillRecord {
+            id: 0,
+            order_id,
+            market_id: order.market_id.clone(),
+            side: order.side.as_str().to_string(),
+            shares: resp.filled_shares,
+            price: resp.avg_fill_price,
+            fee: 0.0,
+            filled_at: now_ms(),
+        };
+        self.storage.record_fill(&fill).await?;
+        let realized = self.portfolio.apply_fill(&fill).await;
+        Ok((resp, realized))
+    }
+
+    /// Orders the journal says may still fill.
+    pub async fn open_orders(&self) -> Result<Vec<OrderRecord>> {
+        self.storage.open_orders().await
+    }
+
+    /// Cancels open orders on the exchange and marks them cancelled in the
+    /// journal. Returns the exchange ids cancelled and an error for each
+    /// order that couldn't be.
+    pub async fn cancel(&self, target: &CancelTarget) -> Result<(Vec<String>, Vec<String>)> {
+        let journal = self.storage.open_orders().await?;
+        let (cancelled, failed) = match target {
+            CancelTarget::All => self.executor.cancel_open_orders().await?,
+            CancelTarget::Market(market_id) => self.executor.cancel_orders(|o| &o.market_id == market_id).await?,
+            CancelTarget::Order(id) => {
+                let exchange_id = match journal.iter().find(|o| o.id.to_string() == *id) {
+                    Some(order) => order
+                        .exchange_order_id
+                        .clone()
+                        .with_context(|| format!("Order {} never reached the exchange", id))?,
+                    None => id.clone(),
+                };
+                match self.executor.cancel_order(&exchange_id).await {
+                    Ok(()) => (vec![exchange_id], vec![]),
+                    Err(e) => (vec![], vec![format!("{}: {:#}", exchange_id, e)]),
+                }
+            }
+        };
+        for order in journal {
+            if order
+                .exchange_order_id
+                .as_ref()
+                .is_some_and(|id| cancelled.contains(id))
+            {
+                self.storage.update_order(order.id, "cancelled", None, None).await?;
+            }
+        }
+        self.storage
+            .append_audit(&audit::entry(
+                AuditAction::OrdersCancelled,
+                "operator",
+                json!({ "cancelled": cancelled, "failed": failed, "via": "cli" }),
+            ))
+            .await?;
+        Ok((cancelled, failed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+
+    #[tokio::test]
+    async fn test_positions_from_the_store() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let config = Config {
+            // Nothing listens here, so marks are unavailable
+            polymarket_api: "http://127.0.0.1:9".to_string(),
+            market_cache_ttl: std::time::Duration::ZERO,
+            cost_basis: CostBasis::Fifo,
+            ..Config::default()
+        };
+        let booked = Portfolio::new(config.cost_basis, Some(Arc::clone(&storage)));
+        for (shares, price) in [(10.0, 0.40), (30.0, 0.60)] {
+            let fill = FillRecord {
+                id: 0,
+                order_id: 1,
+                market_id: "m1".to_string(),
+                side: "BUY".to_string(),
+                shares,
+                price,
+                fee: 0.0,
+                filled_at: 1,
+            };
+            booked.apply_fill(&fill).await;
+        }
+
+        let desk = Desk::open(&config, storage).await.unwrap();
+        let views = desk.positions().await;
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].holding.lots.len(), 2);
+        assert!((views[0].holding.avg_price() - 0.55).abs() < 1e-9);
+        assert_eq!(views[0].unrealized_pnl(), None);
+        assert!(desk.position("m2").await.is_err());
+        assert!(desk.quote_close("m1", Some(50.0)).await.is_err());
+
+        let quote = desk.quote_close("m1", None).await.unwrap();
+        assert_eq!(quote.order.side, TradeSide::SELL);
+        assert_eq!(quote.order.shares, 40.0);
+        assert_eq!(quote.estimated_price, None);
+    }
+}
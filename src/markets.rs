@@ -0,0 +1,344 @@
+//! Market data cache.
+//!
+//! Each fetched market is kept (and persisted, with a journal) until its
+//! record expires: `market_cache_ttl` after fetching, or the market's end
+//! date if that comes first. Markets in use are refreshed shortly before
+//! they expire, and when the API is down a record up to `market_max_stale`
+//! old is served instead of failing the trade. With `gamma_api` set,
+//! markets are looked up and searched on the Gamma API (see
+//! [`crate::gamma`]) rather than the trading API.
+//!
+//! The catalog is word-indexed (see [`crate::search`]) for fuzzy searches
+//! and for the `market_blacklist` rules: comma-separated phrases, a market
+//! using every word of one being blacklisted for buys.
+
+use crate::api::PolymarketApi;
+use crate::gamma;
+use crate::search::SearchIndex;
+use crate::storage::{now_ms, MarketRecord, Storage};
+use crate::types::{Config, Market};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct Entry {
+    record: MarketRecord,
+    /// Read since it was last fetched, so worth refreshing ahead of time.
+    used: bool,
+}
+
+pub struct MarketCache {
+    api: PolymarketApi,
+    gamma: Option<Arc<gamma::Client>>,
+    storage: Option<Arc<dyn Storage>>,
+    ttl: Duration,
+    max_stale: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+    index: Mutex<SearchIndex>,
+    blacklist: Vec<String>,
+}
+
+impl MarketCache {
+    /// A zero `ttl` disables caching: every lookup goes to the API.
+    pub fn new(api: PolymarketApi, storage: Option<Arc<dyn Storage>>, ttl: Duration, max_stale: Duration) -> Self {
+        Self {
+            api,
+            gamma: None,
+            storage,
+            ttl,
+            max_stale,
+            entries: Mutex::new(HashMap::new()),
+            index: Mutex::new(SearchIndex::new()),
+            blacklist: Vec::new(),
+        }
+    }
+
+    pub fn from_config(config: &Config, api: PolymarketApi, storage: Option<Arc<dyn Storage>>) -> Self {
+        let cache = Self::new(api, storage, config.market_cache_ttl, config.market_max_stale)
+            .with_blacklist(config.market_blacklist.clone());
+        match gamma::Client::from_config(config) {
+            Some(gamma) => cache.with_gamma(Arc::new(gamma)),
+            None => cache,
+        }
+    }
+
+    /// Looks markets up on the Gamma API instead of the trading API.
+    pub fn with_gamma(mut self, gamma: Arc<gamma::Client>) -> Self {
+        self.gamma = Some(gamma);
+        self
+    }
+
+    /// Blacklists markets using every word of any of `phrases`.
+    pub fn with_blacklist(mut self, phrases: Vec<String>) -> Self {
+        self.blacklist = phrases;
+        self
+    }
+
+    /// The first blacklist phrase `market` matches.
+    pub fn blacklisted(&self, market: &Market) -> Option<&str> {
+        let index = self.index.lock().unwrap();
+        self.blacklist
+            .iter()
+            .find(|phrase| index.has_words(market, phrase))
+            .map(String::as_str)
+    }
+
+    pub fn gamma(&self) -> Option<&Arc<gamma::Client>> {
+        self.gamma.as_ref()
+    }
+
+    async fn lookup(&self, market_id: &str) -> Result<Market> {
+        match &self.gamma {
+            Some(gamma) => gamma.market(market_id).await,
+            None => self.api.get_market(market_id).await,
+        }
+    }
+
+    async fn search_api(&self, query: &str, limit: usize) -> Result<Vec<Market>> {
+        match &self.gamma {
+            Some(gamma) => gamma.search_markets(query, limit).await,
+            None => self.api.search_markets(query, limit).await,
+        }
+    }
+
+    /// Loads the persisted catalog. Returns the number of markets loaded.
+    pub async fn load(&self) -> Result<usize> {
+        let Some(storage) = &self.storage else { return Ok(0) };
+        if self.ttl.is_zero() {
+            return Ok(0);
+        }
+        let records = storage.markets().await?;
+        let count = records.len();
+        let mut entries = self.entries.lock().unwrap();
+        let mut index = self.index.lock().unwrap();
+        for record in records {
+            index.insert(&record.market.id, &record.market);
+            entries.insert(record.market.id.clone(), Entry { record, used: false });
+        }
+        Ok(count)
+    }
+
+    pub async fn get(&self, market_id: &str) -> Result<Market> {
+        if self.ttl.is_zero() {
+            return self.lookup(market_id).await;
+        }
+
+        let now = now_ms();
+        let cached = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.get_mut(market_id).map(|entry| {
+                entry.used = true;
+                entry.record.clone()
+            })
+        };
+        if let Some(record) = &cached {
+            if now < record.expires_at {
+                return Ok(record.market.clone());
+            }
+        }
+
+        match self.fetch(market_id, now).await {
+            Ok(market) => Ok(market),
+            Err(e) => match cached {
+                Some(record) if now - record.fetched_at <= self.max_stale.as_millis() as i64 => {
+                    tracing::warn!(
+                        "📦 Market API unavailable ({}), using data from {}s ago",
+                        e,
+                        (now - record.fetched_at) / 1000
+                    );
+                    Ok(record.market)
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Unix ms `market_id` was last fetched, if it's cached.
+    pub fn fetched_at(&self, market_id: &str) -> Option<i64> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(market_id)
+            .map(|entry| entry.record.fetched_at)
+    }
+
+    /// Catalog markets matching every word of `query`, even misspelt (see
+    /// [`crate::search`]), most relevant then most traded first, followed by
+    /// the API's other matches, most traded first.
+    pub async fn search(&self, query: &str, limit: usize) -> Vec<Market> {
+        let matched = self.index.lock().unwrap().search(query);
+        let mut found: Vec<(Market, f64)> = {
+            let entries = self.entries.lock().unwrap();
+            matched
+                .into_iter()
+                .filter_map(|(id, score)| entries.get(&id).map(|e| (e.record.market.clone(), score)))
+                .collect()
+        };
+        match self.search_api(query, limit).await {
+            Ok(markets) => {
+                for market in markets {
+                    if !found.iter().any(|(m, _)| m.id == market.id) {
+                        found.push((market, 0.0));
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Market search API unavailable, showing the catalog only: {:#}", e),
+        }
+        found.sort_by(|(a, a_score), (b, b_score)| {
+            b_score.total_cmp(a_score).then(b.volume_24h.total_cmp(&a.volume_24h))
+        });
+        found.truncate(limit);
+        found.into_iter().map(|(market, _)| market).collect()
+    }
+
+    /// A market by its slug or id (condition id).
+    pub async fn find(&self, slug_or_id: &str) -> Result<Market> {
+        let by_slug = {
+            let entries = self.entries.lock().unwrap();
+            entries
+                .values()
+                .find(|e| e.record.market.slug == slug_or_id)
+                .map(|e| e.record.market.id.clone())
+        };
+        if let Some(id) = by_slug {
+            return self.get(&id).await;
+        }
+        match self.get(slug_or_id).await {
+            Ok(market) => Ok(market),
+            Err(e) => self
+                .search_api(slug_or_id, 20)
+                .await
+                .ok()
+                .and_then(|markets| markets.into_iter().find(|m| m.slug == slug_or_id))
+                .ok_or(e),
+        }
+    }
+
+    /// Refetches every market that was used since its last fetch and expires
+    /// within `horizon`. Returns the number refreshed.
+    pub async fn refresh_due(&self, horizon: Duration) -> usize {
+        let now = now_ms();
+        let due: Vec<String> = {
+            let entries = self.entries.lock().unwrap();
+            entries
+                .iter()
+                .filter(|(_, e)| e.used && e.record.expires_at <= now + horizon.as_millis() as i64)
+                .map(|(id, _)| id.clone())
+                .collect()
+        };
+
+        let mut refreshed = 0;
+        for market_id in due {
+            match self.fetch(&market_id, now_ms()).await {
+                Ok(_) => refreshed += 1,
+                Err(e) => tracing::debug!("Market refresh failed for {}: {}", market_id, e),
+            }
+        }
+        refreshed
+    }
+
+    /// Refreshes markets in use every half TTL.
+    pub fn spawn_refresh(self: Arc<Self>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        tokio::spawn(async move {
+            let period = self.ttl / 2;
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                self.refresh_due(period).await;
+            }
+        });
+    }
+
+    async fn fetch(&self, market_id: &str, now: i64) -> Result<Market> {
+        let market = self.lookup(market_id).await?;
+        let mut expires_at = now + self.ttl.as_millis() as i64;
+        if let Some(end) = market.end_date.map(|s| s * 1000).filter(|end| *end > now) {
+            expires_at = expires_at.min(end);
+        }
+        let record = MarketRecord {
+            market: market.clone(),
+            fetched_at: now,
+            expires_at,
+        };
+
+        self.index.lock().unwrap().insert(market_id, &market);
+        if let Some(storage) = &self.storage {
+            if let Err(e) = storage.save_market(&record).await {
+                tracing::warn!("Failed to cache market {}: {}", market_id, e);
+            }
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(market_id.to_string(), Entry { record, used: false });
+        Ok(market)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+
+    fn market(id: &str) -> Market {
+        Market {
+            id: id.to_string(),
+            event_id: "event1".to_string(),
+            question: "Will it?".to_string(),
+            yes_price: 0.5,
+            no_price: 0.5,
+            liquidity: 10_000.0,
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            token_ids: vec!["1".to_string(), "2".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serves_persisted_markets_while_api_is_down() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let now = now_ms();
+        for (id, fetched_at, expires_at) in [
+            ("fresh", now, now + 60_000),
+            ("stale", now - 120_000, now - 60_000),
+            ("ancient", now - 3_600_000, now - 3_540_000),
+        ] {
+            storage
+                .save_market(&MarketRecord {
+                    market: Market {
+                        slug: format!("will-{}", id),
+                        ..market(id)
+                    },
+                    fetched_at,
+                    expires_at,
+                })
+                .await
+                .unwrap();
+        }
+
+        // Nothing listens here, so every API call fails
+        let api = PolymarketApi::new("http://127.0.0.1:9".to_string());
+        let cache = MarketCache::new(api, Some(storage), Duration::from_secs(60), Duration::from_secs(600));
+        assert_eq!(cache.load().await.unwrap(), 3);
+
+        assert_eq!(cache.get("fresh").await.unwrap().token_ids.len(), 2);
+        assert_eq!(cache.get("stale").await.unwrap().id, "stale");
+        assert!(cache.get("ancient").await.is_err());
+        assert!(cache.get("unknown").await.is_err());
+
+        let found = cache.search("Will FRESH", 10).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, "fresh");
+        assert_eq!(cache.search("will", 10).await.len(), 3);
+        assert_eq!(cache.search("stsle", 10).await[0].id, "stale");
+        assert_eq!(cache.find("will-stale").await.unwrap().id, "stale");
+
+        let cache = cache.with_blacklist(vec!["will ancient".to_string()]);
+        assert_eq!(cache.blacklisted(&market("ancient")), Some("will ancient"));
+        assert_eq!(cache.blacklisted(&market("fresh")), None);
+    }
+}
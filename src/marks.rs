@@ -0,0 +1,278 @@
+//! Marks: what a held share is worth now, from the best price to hand.
+//!
+//! Every valuation (PnL, the paper account's equity, the shadow comparison,
+//! `/status/positions` and `mybot positions`) asks [`Marks`] so they all
+//! agree. A market is marked at, in order of preference:
+//!
+//! 1. the mid of its order book, as last sampled
+//! 2. the price of its last trade, a leader's or our own
+//! 3. the price the market API reports
+//!
+//! A price older than `mark_max_age` is stale and only used when nothing
+//! fresher is to hand; the mark then carries the stale flag. The API is
+//! only asked when neither of the first two is fresh.
+//!
+//! Books and trades are passed on to [`LiquidityStats`] when one is
+//! attached.
+
+use crate::clock::Clock;
+use crate::liquidity::LiquidityStats;
+use crate::markets::MarketCache;
+use crate::prices;
+use crate::storage::{Storage, TimeRange};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How far back [`Marks::load`] looks for prices in the journal.
+const LOAD_WINDOW: Duration = Duration::from_secs(24 * 3600);
+
+/// Where a mark came from, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkSource {
+    Mid,
+    LastTrade,
+    Api,
+}
+
+impl MarkSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarkSource::Mid => "mid",
+            MarkSource::LastTrade => "last_trade",
+            MarkSource::Api => "api",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Mark {
+    pub price: f64,
+    pub source: MarkSource,
+    /// Unix ms the price was seen
+    pub at: i64,
+    /// Older than `mark_max_age`
+    pub stale: bool,
+}
+
+/// Prices seen per market, with the unix ms they were seen.
+type Seen = Mutex<HashMap<String, (f64, i64)>>;
+
+/// The marking service; feed it books and trades as they're seen.
+pub struct Marks {
+    markets: Arc<MarketCache>,
+    max_age: Duration,
+    clock: Arc<dyn Clock>,
+    mids: Seen,
+    trades: Seen,
+    liquidity: Option<Arc<LiquidityStats>>,
+}
+
+impl Marks {
+    pub fn new(markets: Arc<MarketCache>, max_age: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            markets,
+            max_age,
+            clock,
+            mids: Mutex::new(HashMap::new()),
+            trades: Mutex::new(HashMap::new()),
+            liquidity: None,
+        }
+    }
+
+    /// Feeds every book and trade recorded to `liquidity` as well.
+    pub fn with_liquidity(mut self, liquidity: Arc<LiquidityStats>) -> Self {
+        self.liquidity = Some(liquidity);
+        self
+    }
+
+    /// Where the API prices and market metadata come from.
+    pub fn markets(&self) -> &Arc<MarketCache> {
+        &self.markets
+    }
+
+    /// Picks up the last day's book mids, leader trades and fills from the
+    /// journal. Returns the markets with a price.
+    pub async fn load(&self, storage: &dyn Storage) -> Result<usize> {
+        let range = TimeRange::since(self.clock.now_ms() - LOAD_WINDOW.as_millis() as i64);
+        for sample in storage.prices(None, range).await? {
+            if let Some(mid) = sample.mid {
+                remember(&self.mids, &sample.market_id, mid, sample.sampled_at);
+            }
+        }
+        for record in storage.leader_trades(range).await? {
+            remember(
+                &self.trades,
+                &record.trade.market_id,
+                record.trade.price,
+                record.observed_at,
+            );
+        }
+        for fill in storage.fills(range).await? {
+            remember(&self.trades, &fill.market_id, fill.price, fill.filled_at);
+        }
+        let mut markets: Vec<String> = self.mids.lock().unwrap().keys().cloned().collect();
+        markets.extend(self.trades.lock().unwrap().keys().cloned());
+        markets.sort();
+        markets.dedup();
+        Ok(markets.len())
+    }
+
+    /// Records the mid of a book seen at `at`; one-sided books have none.
+    pub fn record_book(&self, market_id: &str, bids: &[(f64, f64)], asks: &[(f64, f64)], at: i64) {
+        if let Some(liquidity) = &self.liquidity {
+            liquidity.record_book(market_id, bids, asks, at);
+        }
+        if let Some(mid) = prices::mid_price(bids, asks) {
+            remember(&self.mids, market_id, mid, at);
+        }
+    }
+
+    /// Records a mid seen at `at` without the book behind it.
+    pub fn record_mid(&self, market_id: &str, mid: f64, at: i64) {
+        remember(&self.mids, market_id, mid, at);
+    }
+
+    /// Records a trade in `market_id` at `price`, seen at `at`.
+    pub fn record_trade(&self, market_id: &str, price: f64, at: i64) {
+        if let Some(liquidity) = &self.liquidity {
+            liquidity.record_trade(market_id, at);
+        }
+        remember(&self.trades, market_id, price, at);
+    }
+
+    /// The best mark for `market_id`; `None` when no price is to hand at
+    /// all.
+    pub async fn mark(&self, market_id: &str) -> Option<Mark> {
+        let now = self.clock.now_ms();
+        let seen = |prices: &Seen, source| {
+            prices
+                .lock()
+                .unwrap()
+                .get(market_id)
+                .map(|&(price, at)| self.mark_at(price, source, at, now))
+        };
+        let mut candidates: Vec<Mark> = [
+            seen(&self.mids, MarkSource::Mid),
+            seen(&self.trades, MarkSource::LastTrade),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if let Some(fresh) = candidates.iter().find(|m| !m.stale) {
+            return Some(*fresh);
+        }
+        match self.markets.get(market_id).await {
+            Ok(market) => {
+                let at = self.markets.fetched_at(market_id).unwrap_or(now);
+                let api = self.mark_at(market.yes_price, MarkSource::Api, at, now);
+                if !api.stale {
+                    return Some(api);
+                }
+                candidates.push(api);
+            }
+            Err(e) => tracing::debug!("No API price for {}: {}", market_id, e),
+        }
+        candidates
+            .into_iter()
+            .max_by_key(|m| (m.at, std::cmp::Reverse(m.source)))
+    }
+
+    /// Marks for each of `market_ids` that has one.
+    pub async fn marks(&self, market_ids: &[&str]) -> HashMap<String, Mark> {
+        let mut marks = HashMap::new();
+        for &market_id in market_ids {
+            if marks.contains_key(market_id) {
+                continue;
+            }
+            if let Some(mark) = self.mark(market_id).await {
+                marks.insert(market_id.to_string(), mark);
+            }
+        }
+        marks
+    }
+
+    fn mark_at(&self, price: f64, source: MarkSource, at: i64, now: i64) -> Mark {
+        Mark {
+            price,
+            source,
+            at,
+            stale: now - at > self.max_age.as_millis() as i64,
+        }
+    }
+}
+
+/// Keeps the newest price per market.
+fn remember(prices: &Seen, market_id: &str, price: f64, at: i64) {
+    let mut prices = prices.lock().unwrap();
+    match prices.get_mut(market_id) {
+        Some(seen) if seen.1 > at => {}
+        Some(seen) => *seen = (price, at),
+        None => {
+            prices.insert(market_id.to_string(), (price, at));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::PolymarketApi;
+    use crate::clock::SimClock;
+
+    #[tokio::test]
+    async fn test_marks_fall_back_from_mid_to_last_trade_and_flag_staleness() {
+        // Nothing listens here, so the API never has a price
+        let api = PolymarketApi::new("http://127.0.0.1:9".to_string());
+        let markets = Arc::new(MarketCache::new(
+            api,
+            None,
+            Duration::from_secs(60),
+            Duration::from_secs(600),
+        ));
+        let clock = Arc::new(SimClock::at(1_700_000_000_000));
+        let marks = Marks::new(markets, Duration::from_secs(120), clock.clone());
+        let now = clock.now_ms();
+        assert_eq!(marks.mark("m1").await, None);
+
+        marks.record_trade("m1", 0.40, now);
+        let mark = marks.mark("m1").await.unwrap();
+        assert_eq!(
+            (mark.price, mark.source, mark.stale),
+            (0.40, MarkSource::LastTrade, false)
+        );
+
+        // A one-sided book has no mid; a two-sided one wins over trades
+        marks.record_book("m1", &[(0.44, 10.0)], &[], now);
+        assert_eq!(marks.mark("m1").await.unwrap().source, MarkSource::LastTrade);
+        marks.record_book("m1", &[(0.44, 10.0)], &[(0.48, 10.0)], now);
+        let mark = marks.mark("m1").await.unwrap();
+        assert_eq!(mark.source, MarkSource::Mid);
+        assert!((mark.price - 0.46).abs() < 1e-9);
+
+        // Once the book is old, a fresher trade is preferred
+        clock.advance(Duration::from_secs(100));
+        marks.record_trade("m1", 0.50, clock.now_ms());
+        clock.advance(Duration::from_secs(60));
+        let mark = marks.mark("m1").await.unwrap();
+        assert_eq!(
+            (mark.price, mark.source, mark.stale),
+            (0.50, MarkSource::LastTrade, false)
+        );
+
+        // With everything old, the newest price is used, flagged stale
+        clock.advance(Duration::from_secs(600));
+        let mark = marks.mark("m1").await.unwrap();
+        assert_eq!(
+            (mark.price, mark.source, mark.stale),
+            (0.50, MarkSource::LastTrade, true)
+        );
+        // An older trade doesn't replace a newer one
+        marks.record_trade("m1", 0.10, now);
+        assert_eq!(marks.marks(&["m1", "m1", "m2"]).await.len(), 1);
+        assert_eq!(marks.mark("m1").await.unwrap().price, 0.50);
+    }
+}
@@ -0,0 +1,205 @@
+//! Mempool watching: pending transactions from tracked wallets, seen before
+//! they are mined.
+//!
+//! Pending transaction hashes from the RPC node's subscription are queued
+//! and fetched one at a time; those sent by a tracked wallet to a Polymarket
+//! contract are decoded and logged. RPC health and the decode backlog are
+//! logged every five minutes.
+
+use crate::rpc::{self, Metered, RpcStats};
+use anyhow::{Context, Result};
+use ethers::providers::{Middleware, Provider, Ws};
+use ethers::types::{Address, Bytes, TxHash};
+use futures_util::StreamExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// Pending transactions waiting to be fetched and decoded; beyond this the
+/// newest are dropped rather than letting the backlog grow.
+const DECODE_QUEUE_CAPACITY: usize = 10_000;
+
+/// Watches the mempool of `rpc_url` (a WebSocket URL) for transactions from
+/// `wallets` until the subscription ends.
+pub async fn monitor(rpc_url: &str, wallets: &[String]) -> Result<()> {
+    let wallets: Vec<Address> = wallets.iter().filter_map(|s| s.trim().parse().ok()).collect();
+
+    if wallets.is_empty() {
+        anyhow::bail!("No valid wallet addresses in WALLETS_TO_TRACK");
+    }
+
+    tracing::info!("Tracking {} wallets", wallets.len());
+
+    // Connect with retry logic
+    let stats = Arc::new(RpcStats::new());
+    let provider = connect_with_retry(rpc_url, &stats, 5).await?;
+    let provider = Arc::new(provider);
+
+    // Pending hashes wait here while earlier ones are fetched and decoded
+    let (queue_tx, mut queue_rx) = mpsc::channel::<TxHash>(DECODE_QUEUE_CAPACITY);
+    let dropped = Arc::new(AtomicU64::new(0));
+
+    // Provider health and decode backlog, so a degrading RPC or a pipeline
+    // falling behind shows up before coverage suffers
+    let depth = queue_tx.clone();
+    let dropped_count = Arc::clone(&dropped);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Some(summary) = stats.summary() {
+                tracing::info!("📡 RPC: {}", summary);
+            }
+            tracing::info!(
+                "📥 Decode queue: {}/{} waiting, {} dropped",
+                DECODE_QUEUE_CAPACITY - depth.capacity(),
+                DECODE_QUEUE_CAPACITY,
+                dropped_count.load(Ordering::Relaxed)
+            );
+        }
+    });
+
+    tracing::info!("✅ Connected to RPC");
+
+    // Subscribe to pending transactions
+    let mut stream = provider
+        .subscribe_pending_txs()
+        .await
+        .context("Failed to subscribe to mempool")?;
+
+    tracing::info!("✅ Subscribed to mempool");
+    tracing::info!("🎯 Monitoring pending transactions...");
+
+    let decoder = Arc::clone(&provider);
+    tokio::spawn(async move {
+        while let Some(tx_hash) = queue_rx.recv().await {
+            // Get transaction details
+            match decoder.get_transaction(tx_hash).await {
+                Ok(Some(tx)) => {
+                    // Check if transaction is from a tracked wallet
+                    if wallets.contains(&tx.from) {
+                        tracing::info!("🔔 Detected pending tx from tracked wallet!");
+                        tracing::info!("   From: {:?}", tx.from);
+                        tracing::info!("   To: {:?}", tx.to);
+                        tracing::info!("   Hash: {:?}", tx_hash);
+                        tracing::info!("   Gas: {}", tx.gas);
+                        tracing::info!("   Gas Price: {}", tx.gas_price.unwrap_or_default());
+
+                        // Decode transaction data (if it's a Polymarket trade)
+                        if let Some(to) = tx.to {
+                            if is_polymarket_contract(&to) {
+                                tracing::info!("   ✅ This is a Polymarket trade!");
+
+                                // You can now execute a mirror trade BEFORE this tx is mined
+                                // This gives you the same block execution
+
+                                // Parse trade details from tx.input
+                                if let Some(trade_info) = parse_trade_data(&tx.input) {
+                                    tracing::info!("   Side: {:?}", trade_info.side);
+                                    tracing::info!("   Market: {}", trade_info.market_id);
+                                    tracing::info!("   Shares: {:.2}", trade_info.shares);
+
+                                    // TODO: Execute mirror trade here
+                                    // execute_mirror_trade(trade_info).await;
+                                }
+                            }
+                        }
+
+                        tracing::info!("---");
+                    }
+                }
+                Ok(None) => {
+                    // Transaction not found (might have been dropped)
+                }
+                Err(e) => {
+                    tracing::debug!("Error fetching transaction {}: {}", tx_hash, e);
+                }
+            }
+        }
+    });
+
+    let mut full = false;
+    while let Some(tx_hash) = stream.next().await {
+        match queue_tx.try_send(tx_hash) {
+            Ok(()) => full = false,
+            Err(TrySendError::Full(_)) => {
+                dropped.fetch_add(1, Ordering::Relaxed);
+                if !full {
+                    full = true;
+                    tracing::warn!("Decode queue full, dropping pending transactions until it drains");
+                }
+            }
+            Err(TrySendError::Closed(_)) => break,
+        }
+    }
+
+    Ok(())
+}
+
+async fn connect_with_retry(rpc_url: &str, stats: &Arc<RpcStats>, max_retries: u32) -> Result<Provider<Metered<Ws>>> {
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        tracing::info!("Connecting to RPC (attempt {}/{})", attempts, max_retries);
+
+        match rpc::connect_ws(rpc_url, stats).await {
+            Ok(provider) => return Ok(provider),
+            Err(e) => {
+                if attempts >= max_retries {
+                    return Err(anyhow::anyhow!(
+                        "Failed to connect after {} attempts: {:#}",
+                        max_retries,
+                        e
+                    ));
+                }
+                tracing::warn!("Connection failed: {:#}, retrying in {} seconds...", e, attempts * 2);
+                tokio::time::sleep(tokio::time::Duration::from_secs((attempts * 2) as u64)).await;
+            }
+        }
+    }
+}
+
+fn is_polymarket_contract(address: &Address) -> bool {
+    // Polymarket CLOB contract addresses on Polygon
+    let polymarket_contracts = [
+        "0x4bFb41d5B3570DeFd03C39a9A4D8dE6Bd8B8982E", // Example CLOB
+        "0xC5d563A36AE78145C45a50134d48A1215220f80a", // Example CLOB
+    ];
+
+    polymarket_contracts
+        .iter()
+        .any(|&c| c.parse::<Address>().ok() == Some(*address))
+}
+
+#[derive(Debug)]
+struct TradeInfo {
+    side: String,
+    market_id: String,
+    shares: f64,
+}
+
+fn parse_trade_data(data: &Bytes) -> Option<TradeInfo> {
+    // Parse transaction input data
+    // This is simplified - actual parsing would decode ABI
+
+    if data.len() < 36 {
+        return None;
+    }
+
+    // Method selector (first 4 bytes)
+    let selector = &data[0..4];
+
+    // Common Polymarket function selectors:
+    // 0x3d8b38f6 = placeBid
+    // 0xc62e2971 = placeAsk
+    // 0xa9059cbb = transfer (ERC20)
+
+    // Simplified parsing
+    Some(TradeInfo {
+        side: if selector[0].is_multiple_of(2) { "BUY" } else { "SELL" }.to_string(),
+        market_id: format!("0x{}", hex::encode(&data[4..36])),
+        shares: 100.0, // Decode from data
+    })
+}
@@ -0,0 +1,215 @@
+//! Monte Carlo resampling of a backtest's copied positions.
+//!
+//! A backtest gives one path through history; its drawdown says little
+//! about how bad the same edge could have gone in a different order. Each
+//! copied position's return on what its buys cost is drawn with
+//! replacement, as many times as there were positions, and staked the way
+//! the sizing config would stake it from a running bankroll: fixed sizing
+//! keeps the stake the backtest used, proportional and tier sizing scale it
+//! with the bankroll, and every stake stays within `min_stake`, `max_stake`
+//! and 95% of the cash. A path is ruined once the bankroll can't cover
+//! `min_stake`. Seeded runs are reproducible.
+
+use crate::backtest::BacktestReport;
+use crate::types::{Config, SizingMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+/// Share of the bankroll a stake may use, as in sizing.
+const MAX_STAKE_FRACTION: f64 = 0.95;
+
+/// A distribution's 5th, 25th, 50th, 75th and 95th percentiles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+impl Percentiles {
+    fn of(mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        let at = |q: f64| match values.len() {
+            0 => 0.0,
+            n => values[((n - 1) as f64 * q).round() as usize],
+        };
+        Self {
+            p5: at(0.05),
+            p25: at(0.25),
+            p50: at(0.5),
+            p75: at(0.75),
+            p95: at(0.95),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonteCarloReport {
+    pub runs: usize,
+    /// Positions drawn per run
+    pub positions: usize,
+    pub seed: u64,
+    pub bankroll: f64,
+    pub final_equity: Percentiles,
+    /// Fractions of the peak
+    pub max_drawdown_pct: Percentiles,
+    /// Share of runs that ended unable to stake `min_stake`
+    pub risk_of_ruin: f64,
+}
+
+impl std::fmt::Display for MonteCarloReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Monte Carlo: {} runs of {} resampled positions from ${:.2} (seed {})",
+            self.runs, self.positions, self.bankroll, self.seed
+        )?;
+        writeln!(
+            f,
+            "{:<16} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "", "p5", "p25", "p50", "p75", "p95"
+        )?;
+        let e = &self.final_equity;
+        writeln!(
+            f,
+            "{:<16} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+            "Final equity", e.p5, e.p25, e.p50, e.p75, e.p95
+        )?;
+        let d = &self.max_drawdown_pct;
+        writeln!(
+            f,
+            "{:<16} {:>9.2}% {:>9.2}% {:>9.2}% {:>9.2}% {:>9.2}%",
+            "Max drawdown",
+            d.p5 * 100.0,
+            d.p25 * 100.0,
+            d.p50 * 100.0,
+            d.p75 * 100.0,
+            d.p95 * 100.0
+        )?;
+        writeln!(f, "Risk of ruin:    {:.2}%", self.risk_of_ruin * 100.0)
+    }
+}
+
+/// A copied position as a bet: what was staked and the return on it.
+#[derive(Debug, Clone, Copy)]
+struct Bet {
+    stake: f64,
+    ret: f64,
+}
+
+/// Resamples `report`'s positions `runs` times from `bankroll`, staking as
+/// `config` sizes; `None` when nothing was bought or `runs` is zero.
+pub fn simulate(
+    report: &BacktestReport,
+    config: &Config,
+    bankroll: f64,
+    runs: usize,
+    seed: u64,
+) -> Option<MonteCarloReport> {
+    let bets: Vec<Bet> = report
+        .positions
+        .iter()
+        .filter(|p| p.cost_usd > 0.0)
+        .map(|p| Bet {
+            stake: p.cost_usd,
+            ret: p.pnl() / p.cost_usd,
+        })
+        .collect();
+    if bets.is_empty() || runs == 0 {
+        return None;
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut finals = Vec::with_capacity(runs);
+    let mut drawdowns = Vec::with_capacity(runs);
+    let mut ruined = 0;
+    for _ in 0..runs {
+        let (equity, drawdown, broke) = path(&bets, config, report.starting_balance, bankroll, &mut rng);
+        finals.push(equity);
+        drawdowns.push(drawdown);
+        ruined += broke as usize;
+    }
+    Some(MonteCarloReport {
+        runs,
+        positions: bets.len(),
+        seed,
+        bankroll,
+        final_equity: Percentiles::of(finals),
+        max_drawdown_pct: Percentiles::of(drawdowns),
+        risk_of_ruin: ruined as f64 / runs as f64,
+    })
+}
+
+/// One resampled run: final equity, largest drawdown as a fraction of the
+/// peak, and whether it was ruined. Stakes were sized against `backtested`.
+fn path(bets: &[Bet], config: &Config, backtested: f64, bankroll: f64, rng: &mut StdRng) -> (f64, f64, bool) {
+    let mut equity = bankroll;
+    let mut peak = bankroll;
+    let mut drawdown: f64 = 0.0;
+    for _ in 0..bets.len() {
+        if equity < config.min_stake.as_f64() || equity <= 0.0 {
+            return (equity, drawdown, true);
+        }
+        let bet = bets[rng.gen_range(0..bets.len())];
+        let stake = match config.sizing_mode {
+            SizingMode::Fixed => bet.stake,
+            SizingMode::Proportional | SizingMode::TierBased => bet.stake * equity / backtested.max(f64::EPSILON),
+        };
+        let stake = stake
+            .max(config.min_stake.as_f64())
+            .min(config.max_stake.as_f64())
+            .min(equity * MAX_STAKE_FRACTION);
+        equity += stake * bet.ret;
+        peak = peak.max(equity);
+        drawdown = drawdown.max((peak - equity) / peak.max(f64::EPSILON));
+    }
+    (equity, drawdown, equity < config.min_stake.as_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::UsdcAmount;
+    use crate::backtest::PositionResult;
+
+    #[test]
+    fn test_resampling_is_seeded_and_finds_ruin() {
+        let position = |cost_usd: f64, realized_pnl: f64| PositionResult {
+            cost_usd,
+            realized_pnl,
+            ..Default::default()
+        };
+        let config = Config {
+            sizing_mode: SizingMode::Fixed,
+            min_stake: UsdcAmount(5.0),
+            max_stake: UsdcAmount(100.0),
+            ..Default::default()
+        };
+        let mut report = BacktestReport {
+            starting_balance: 1_000.0,
+            positions: vec![position(50.0, 25.0), position(50.0, -50.0), position(0.0, 3.0)],
+            ..Default::default()
+        };
+
+        let first = simulate(&report, &config, 1_000.0, 500, 7).unwrap();
+        let again = simulate(&report, &config, 1_000.0, 500, 7).unwrap();
+        assert_eq!(first.positions, 2);
+        assert_eq!(first.final_equity, again.final_equity);
+        assert!(first.final_equity.p5 <= first.final_equity.p50 && first.final_equity.p50 <= first.final_equity.p95);
+        // Two draws can lose at most $100 of $1000
+        assert_eq!(first.risk_of_ruin, 0.0);
+        assert!(first.max_drawdown_pct.p95 <= 0.1 + 1e-9);
+
+        // $40 goes broke whenever the loss comes first: 95% of it is staked
+        let small = simulate(&report, &config, 40.0, 500, 7).unwrap();
+        assert!(small.risk_of_ruin > 0.35 && small.risk_of_ruin < 0.65);
+
+        report.positions.truncate(0);
+        assert!(simulate(&report, &config, 1_000.0, 500, 7).is_none());
+        report.positions.push(position(50.0, 25.0));
+        assert!(simulate(&report, &config, 1_000.0, 0, 7).is_none());
+    }
+}
@@ -0,0 +1,219 @@
+//! Alert deduplication and flap suppression.
+//!
+//! Alerts with the same key (the same order market failing, the same feed
+//! dropping) within `notify_dedup_window` are sent once; repeats are
+//! counted and reported together as a [`Notification::Coalesced`] when the
+//! window ends. A feed coming back only produces a
+//! [`Notification::Recovered`] once it has stayed up for
+//! `notify_recovery_delay`, so a flapping connection is one alert and one
+//! recovery instead of a storm.
+
+use super::Notification;
+use crate::events::ConnectionState;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// An alert that has fired and not yet recovered.
+#[derive(Debug)]
+struct Active {
+    first_raised: i64,
+    window_start: i64,
+    suppressed: u32,
+    last: Notification,
+    /// Kept until a recovery is confirmed, rather than until its window ends
+    recoverable: bool,
+    /// When the condition cleared, while waiting for it to stay clear
+    clearing_since: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct AlertDedup {
+    window_ms: i64,
+    recovery_ms: i64,
+    active: HashMap<String, Active>,
+}
+
+impl Notification {
+    /// Identifies repeats of the same alert; `None` for notifications that
+    /// are never deduplicated.
+    fn alert_key(&self) -> Option<String> {
+        match self {
+            Notification::OrderFailed { market_id, .. } => Some(format!("order_failed:{}", market_id)),
+            Notification::RiskTripped { .. } => Some("risk_tripped".to_string()),
+            Notification::StorageFailed { operation, .. } => Some(format!("storage_failed:{}", operation)),
+            Notification::Connection {
+                wallet,
+                state: ConnectionState::Disconnected,
+                ..
+            } => Some(format!("connection:{}", wallet)),
+            _ => None,
+        }
+    }
+
+    /// Whether a later notification can report this alert as cleared.
+    fn recoverable(&self) -> bool {
+        matches!(self, Notification::Connection { .. })
+    }
+
+    /// The alert this notification reports as cleared.
+    fn clears(&self) -> Option<String> {
+        match self {
+            Notification::Connection {
+                wallet,
+                state: ConnectionState::Connected,
+                ..
+            } => Some(format!("connection:{}", wallet)),
+            _ => None,
+        }
+    }
+}
+
+impl AlertDedup {
+    pub fn new(window: Duration, recovery_delay: Duration) -> Self {
+        Self {
+            window_ms: window.as_millis() as i64,
+            recovery_ms: recovery_delay.as_millis() as i64,
+            active: HashMap::new(),
+        }
+    }
+
+    /// Returns `notification` if it should go out now, or holds it back.
+    pub fn filter(&mut self, notification: Notification, now_ms: i64) -> Option<Notification> {
+        if let Some(key) = notification.clears() {
+            return match self.active.get_mut(&key) {
+                Some(alert) => {
+                    alert.clearing_since.get_or_insert(now_ms);
+                    None
+                }
+                None => Some(notification),
+            };
+        }
+        let Some(key) = notification.alert_key() else {
+            return Some(notification);
+        };
+        let Some(alert) = self.active.get_mut(&key) else {
+            self.active.insert(
+                key,
+                Active {
+                    first_raised: now_ms,
+                    window_start: now_ms,
+                    suppressed: 0,
+                    last: notification.clone(),
+                    recoverable: notification.recoverable(),
+                    clearing_since: None,
+                },
+            );
+            return Some(notification);
+        };
+
+        // Back before it was confirmed recovered: still the same incident
+        alert.clearing_since = None;
+        if now_ms - alert.window_start < self.window_ms {
+            alert.suppressed += 1;
+            alert.last = notification;
+            return None;
+        }
+        alert.window_start = now_ms;
+        alert.last = notification.clone();
+        Some(notification)
+    }
+
+    /// Notifications that became due by `now_ms`: counts of repeats whose
+    /// window ended, and recoveries that held.
+    pub fn due(&mut self, now_ms: i64) -> Vec<Notification> {
+        let window_secs = (self.window_ms / 1000) as u64;
+        let mut due = Vec::new();
+        let mut finished = Vec::new();
+        for (key, alert) in self.active.iter_mut() {
+            let recovered_now = alert
+                .clearing_since
+                .is_some_and(|since| now_ms - since >= self.recovery_ms);
+            let window_over = now_ms - alert.window_start >= self.window_ms;
+            let flush = alert.suppressed > 0 && (window_over || recovered_now);
+            if flush {
+                due.push(Notification::Coalesced {
+                    count: alert.suppressed,
+                    window_secs,
+                    last: Box::new(alert.last.clone()),
+                });
+                alert.suppressed = 0;
+                alert.window_start = now_ms;
+            }
+            if recovered_now {
+                let since = alert.clearing_since.unwrap_or(now_ms);
+                due.push(Notification::Recovered {
+                    down_secs: ((since - alert.first_raised).max(0) / 1000) as u64,
+                    last: Box::new(alert.last.clone()),
+                });
+                finished.push(key.clone());
+            } else if window_over && !flush && !alert.recoverable {
+                // Quiet for a whole window; a repeat now alerts afresh
+                finished.push(key.clone());
+            }
+        }
+        for key in finished {
+            self.active.remove(&key);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(state: ConnectionState) -> Notification {
+        Notification::Connection {
+            wallet: "0xabc".to_string(),
+            state,
+            detail: None,
+        }
+    }
+
+    #[test]
+    fn test_flapping_feed_is_one_alert_and_one_recovery() {
+        let mut dedup = AlertDedup::new(Duration::from_secs(60), Duration::from_secs(30));
+        assert!(dedup.filter(connection(ConnectionState::Disconnected), 0).is_some());
+        for t in [5_000, 10_000, 15_000] {
+            assert!(dedup.filter(connection(ConnectionState::Connected), t).is_none());
+            assert!(dedup
+                .filter(connection(ConnectionState::Disconnected), t + 1_000)
+                .is_none());
+        }
+        assert!(dedup.filter(connection(ConnectionState::Connected), 20_000).is_none());
+
+        // Up for less than the recovery delay: nothing yet
+        assert!(dedup.due(40_000).is_empty());
+
+        let due = dedup.due(50_000);
+        assert_eq!(due.len(), 2);
+        assert!(matches!(due[0], Notification::Coalesced { count: 3, .. }));
+        assert!(matches!(due[1], Notification::Recovered { down_secs: 20, .. }));
+
+        // Recovered: the next drop alerts straight away
+        assert!(dedup
+            .filter(connection(ConnectionState::Disconnected), 60_000)
+            .is_some());
+    }
+
+    #[test]
+    fn test_repeats_are_counted_per_window() {
+        let mut dedup = AlertDedup::new(Duration::from_secs(60), Duration::from_secs(30));
+        let failed = |market: &str| Notification::OrderFailed {
+            market_id: market.to_string(),
+            error: "rejected".to_string(),
+        };
+        assert!(dedup.filter(failed("m1"), 0).is_some());
+        assert!(dedup.filter(failed("m2"), 0).is_some());
+        assert!(dedup.filter(failed("m1"), 1_000).is_none());
+        assert!(dedup.filter(failed("m1"), 2_000).is_none());
+        assert!(dedup.due(30_000).is_empty());
+
+        let due = dedup.due(60_000);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].kind(), "order_failed");
+        assert!(due[0].to_string().contains("×2"));
+        assert!(dedup.filter(failed("m1"), 61_000).is_none());
+        assert!(dedup.filter(failed("m1"), 121_000).is_some());
+    }
+}
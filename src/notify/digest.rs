@@ -0,0 +1,227 @@
+//! Digest mode: instead of a message per trade, counts what happened and
+//! sends one summary every `notify_digest_interval`.
+//!
+//! Periods are aligned to the interval in UTC, so "1h" reports on the hour
+//! and "24h" at midnight. Quiet periods send nothing.
+
+use super::{BotControl, Notification};
+use crate::equity::EquityStats;
+use crate::events::ConnectionState;
+use crate::units::format_duration;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Summary of one digest period.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Digest {
+    pub period_secs: u64,
+    pub copied: u32,
+    pub copied_usd: f64,
+    /// Skips per reason
+    pub skipped: BTreeMap<String, u32>,
+    pub filled: u32,
+    pub failed: u32,
+    pub risk_trips: u32,
+    pub disconnects: u32,
+    pub storage_failures: u32,
+    pub realized_pnl_today: f64,
+    pub realized_pnl: f64,
+    /// Open positions at current marks, as of the last PnL refresh
+    pub unrealized_pnl: Option<f64>,
+    pub exposure: f64,
+    pub open_positions: usize,
+    /// Drawdown and returns off the equity curve, when it's tracked
+    pub equity: Option<EquityStats>,
+}
+
+impl Digest {
+    /// Counts `notification` towards this period.
+    pub fn record(&mut self, notification: &Notification) {
+        match notification {
+            Notification::TradeCopied { size_usd, .. } => {
+                self.copied += 1;
+                self.copied_usd += size_usd;
+            }
+            Notification::TradeSkipped { reason, .. } => {
+                *self.skipped.entry(reason.as_str().to_string()).or_default() += 1;
+            }
+            Notification::OrderFilled { .. } => self.filled += 1,
+            Notification::OrderFailed { .. } => self.failed += 1,
+            Notification::RiskTripped { .. } => self.risk_trips += 1,
+            Notification::Connection {
+                state: ConnectionState::Disconnected,
+                ..
+            } => self.disconnects += 1,
+            Notification::StorageFailed { .. } => self.storage_failures += 1,
+            Notification::Connection { .. }
+            | Notification::FeedDown { .. }
+            | Notification::PositionExit { .. }
+            | Notification::PriceAlert { .. }
+            | Notification::MarketAnomaly { .. }
+            | Notification::Arbitrage { .. }
+            | Notification::ResolutionUpdate { .. }
+            | Notification::Anomaly { .. }
+            | Notification::ApprovalRequested { .. }
+            | Notification::Digest(_)
+            | Notification::Coalesced { .. }
+            | Notification::Recovered { .. } => {}
+        }
+    }
+
+    /// Whether nothing was counted this period.
+    pub fn is_quiet(&self) -> bool {
+        self.copied == 0
+            && self.skipped.is_empty()
+            && self.filled == 0
+            && self.failed == 0
+            && self.risk_trips == 0
+            && self.disconnects == 0
+            && self.storage_failures == 0
+    }
+
+    /// Completes the summary with the bot's current PnL and exposure.
+    pub fn finish(mut self, period: Duration, control: &BotControl) -> Self {
+        self.period_secs = period.as_secs();
+        self.realized_pnl_today = control.risk.get_state().realized_pnl_today;
+        self.realized_pnl = control.portfolio.realized_pnl();
+        self.unrealized_pnl = control.pnl().and_then(|pnl| pnl.latest()).map(|r| r.unrealized_pnl);
+        self.exposure = control.portfolio.exposure();
+        self.open_positions = control.portfolio.holdings().len();
+        self.equity = control.equity().and_then(|equity| equity.stats());
+        self
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "📊 Digest for the last {}",
+            format_duration(Duration::from_secs(self.period_secs))
+        )?;
+        writeln!(f, "Copied: {} trades (${:.2})", self.copied, self.copied_usd)?;
+        let skipped: u32 = self.skipped.values().sum();
+        if skipped > 0 {
+            let reasons: Vec<String> = self
+                .skipped
+                .iter()
+                .map(|(reason, count)| format!("{} {}", reason, count))
+                .collect();
+            writeln!(f, "Skipped: {} ({})", skipped, reasons.join(", "))?;
+        } else {
+            writeln!(f, "Skipped: 0")?;
+        }
+        writeln!(f, "Fills: {}, failed orders: {}", self.filled, self.failed)?;
+        writeln!(
+            f,
+            "Realized today: ${:.2}, since start: ${:.2}",
+            self.realized_pnl_today, self.realized_pnl
+        )?;
+        if let Some(unrealized) = self.unrealized_pnl {
+            writeln!(f, "Unrealized: ${:.2}", unrealized)?;
+        }
+        write!(
+            f,
+            "Open exposure: ${:.2} across {} positions",
+            self.exposure, self.open_positions
+        )?;
+        if let Some(equity) = &self.equity {
+            let pct = |r: Option<f64>| {
+                r.map(|r| format!("{:+.2}%", r * 100.0))
+                    .unwrap_or_else(|| "-".to_string())
+            };
+            write!(
+                f,
+                "\nEquity: ${:.2} (1d {}, 7d {}), drawdown {:.2}% (max {:.2}%)",
+                equity.equity,
+                pct(equity.return_1d),
+                pct(equity.return_7d),
+                equity.drawdown_pct * 100.0,
+                equity.max_drawdown_pct * 100.0
+            )?;
+            if let Some(sharpe) = equity.sharpe {
+                write!(f, ", Sharpe {:.2}", sharpe)?;
+            }
+        }
+        if self.disconnects > 0 || self.risk_trips > 0 {
+            write!(
+                f,
+                "\n⚠️ Feed disconnects: {}, circuit breaker trips: {}",
+                self.disconnects, self.risk_trips
+            )?;
+        }
+        if self.storage_failures > 0 {
+            write!(f, "\n⚠️ Storage failures: {}", self.storage_failures)?;
+        }
+        Ok(())
+    }
+}
+
+/// Unix milliseconds of the first period boundary after `now_ms`.
+pub fn next_boundary(now_ms: i64, period: Duration) -> i64 {
+    let step = (period.as_millis() as i64).max(1);
+    (now_ms / step + 1) * step
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SkipReason;
+
+    #[test]
+    fn test_digest_counts() {
+        let mut digest = Digest::default();
+        assert!(digest.is_quiet());
+        for size_usd in [10.0, 15.0] {
+            digest.record(&Notification::TradeCopied {
+                wallet: "0xabc".to_string(),
+                market_id: "m".to_string(),
+                side: "BUY".to_string(),
+                size_usd,
+                shares: 1.0,
+                card: Default::default(),
+            });
+        }
+        digest.record(&Notification::TradeSkipped {
+            wallet: "0xabc".to_string(),
+            market_id: "m".to_string(),
+            side: "BUY".to_string(),
+            reason: SkipReason::Stale,
+            detail: String::new(),
+            card: Default::default(),
+        });
+        digest.record(&Notification::Connection {
+            wallet: "0xabc".to_string(),
+            state: ConnectionState::Disconnected,
+            detail: None,
+        });
+
+        assert_eq!(digest.copied, 2);
+        assert_eq!(digest.copied_usd, 25.0);
+        assert_eq!(digest.skipped.get("stale"), Some(&1));
+        assert_eq!(digest.disconnects, 1);
+        let text = digest.to_string();
+        assert!(text.contains("Copied: 2 trades ($25.00)"));
+        assert!(text.contains("Skipped: 1 (stale 1)"));
+        assert!(text.contains("Feed disconnects: 1"));
+
+        digest.equity = Some(EquityStats {
+            equity: 95.0,
+            drawdown_pct: 0.05,
+            max_drawdown_pct: 0.1,
+            return_1d: Some(0.01),
+            ..Default::default()
+        });
+        let text = digest.to_string();
+        assert!(text.contains("Equity: $95.00 (1d +1.00%, 7d -), drawdown 5.00% (max 10.00%)"));
+    }
+
+    #[test]
+    fn test_boundaries_align_to_period() {
+        let hour = Duration::from_secs(3600);
+        assert_eq!(next_boundary(3_600_000, hour), 7_200_000);
+        assert_eq!(next_boundary(3_599_999, hour), 3_600_000);
+    }
+}
@@ -0,0 +1,288 @@
+//! Discord: notifications through channel webhooks, plus an optional bot
+//! that answers the /pause, /resume, /positions and /pnl slash commands.
+//!
+//! Trade notifications go to `discord_trades_webhook`, errors to
+//! `discord_errors_webhook`; either falls back to `discord_webhook`. The bot
+//! connects to the gateway with `discord_bot_token`, registers the commands
+//! on startup and only obeys them in `discord_command_channel`.
+
+use super::{BotControl, Category, Command, Notification, Notifier};
+use crate::types::Config;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const API_BASE: &str = "https://discord.com/api/v10";
+const GATEWAY_URL: &str = "wss://gateway.discord.gg/?v=10&encoding=json";
+
+pub struct DiscordNotifier {
+    client: reqwest::Client,
+    trades_webhook: Option<String>,
+    errors_webhook: Option<String>,
+    bot: Option<DiscordBot>,
+}
+
+struct DiscordBot {
+    token: String,
+    command_channel: String,
+}
+
+impl DiscordNotifier {
+    /// `None` unless a webhook or the bot is configured.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let pick = |specific: &str| {
+            let url = if specific.is_empty() {
+                &config.discord_webhook
+            } else {
+                specific
+            };
+            (!url.is_empty()).then(|| url.to_string())
+        };
+        let bot = (!config.discord_bot_token.is_empty()).then(|| DiscordBot {
+            token: config.discord_bot_token.clone(),
+            command_channel: config.discord_command_channel.clone(),
+        });
+        let notifier = Self {
+            client: reqwest::Client::new(),
+            trades_webhook: pick(&config.discord_trades_webhook),
+            errors_webhook: pick(&config.discord_errors_webhook),
+            bot,
+        };
+        (notifier.trades_webhook.is_some() || notifier.errors_webhook.is_some() || notifier.bot.is_some())
+            .then_some(notifier)
+    }
+
+    fn webhook_for(&self, category: Category) -> Option<&str> {
+        match category {
+            Category::Trades => self.trades_webhook.as_deref(),
+            Category::Errors => self.errors_webhook.as_deref(),
+        }
+    }
+
+    /// Serves slash commands until the process exits, reconnecting as needed.
+    fn spawn_gateway(self: Arc<Self>, control: Arc<BotControl>) {
+        if self.bot.is_none() {
+            return;
+        }
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.run_gateway(&control).await {
+                    tracing::warn!("Discord gateway connection ended: {}", e);
+                }
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+        });
+    }
+
+    async fn run_gateway(&self, control: &BotControl) -> Result<()> {
+        let Some(bot) = &self.bot else { return Ok(()) };
+        let (ws, _) = connect_async(GATEWAY_URL)
+            .await
+            .context("Failed to connect to the Discord gateway")?;
+        let (mut write, mut read) = ws.split();
+
+        let mut heartbeat: Option<tokio::time::Interval> = None;
+        let mut seq: Option<i64> = None;
+        loop {
+            let tick = async {
+                match heartbeat.as_mut() {
+                    Some(interval) => {
+                        interval.tick().await;
+                    }
+                    None => std::future::pending::<()>().await,
+                }
+            };
+            tokio::select! {
+                _ = tick => {
+                    write.send(Message::Text(json!({"op": 1, "d": seq}).to_string())).await?;
+                }
+                msg = read.next() => {
+                    let text = match msg {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(frame))) => anyhow::bail!("closed by Discord: {:?}", frame),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => return Err(e.into()),
+                        None => anyhow::bail!("stream ended"),
+                    };
+                    let payload: Value = serde_json::from_str(&text)?;
+                    if let Some(s) = payload["s"].as_i64() {
+                        seq = Some(s);
+                    }
+                    match payload["op"].as_i64() {
+                        // Hello: start heartbeating and identify
+                        Some(10) => {
+                            let millis = payload["d"]["heartbeat_interval"].as_u64().unwrap_or(41_250);
+                            let period = Duration::from_millis(millis);
+                            heartbeat = Some(tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+                            let identify = json!({
+                                "op": 2,
+                                "d": {
+                                    "token": bot.token,
+                                    "intents": 0,
+                                    "properties": {
+                                        "os": std::env::consts::OS,
+                                        "browser": "polymarket-bot",
+                                        "device": "polymarket-bot",
+                                    },
+                                },
+                            });
+                            write.send(Message::Text(identify.to_string())).await?;
+                        }
+                        // Heartbeat requested
+                        Some(1) => {
+                            write.send(Message::Text(json!({"op": 1, "d": seq}).to_string())).await?;
+                        }
+                        // Reconnect or invalid session
+                        Some(7) | Some(9) => anyhow::bail!("Discord asked to reconnect"),
+                        Some(0) => match payload["t"].as_str() {
+                            Some("READY") => {
+                                let application_id = payload["d"]["application"]["id"].as_str();
+                                self.register_commands(bot, application_id.unwrap_or_default()).await?;
+                                tracing::info!("🤖 Discord bot connected");
+                            }
+                            Some("INTERACTION_CREATE") => {
+                                if let Some(interaction) = parse_interaction(&payload["d"]) {
+                                    self.answer(bot, control, &interaction).await;
+                                }
+                            }
+                            _ => {}
+                        },
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    async fn register_commands(&self, bot: &DiscordBot, application_id: &str) -> Result<()> {
+        let commands: Vec<Value> = [
+            ("pause", "Stop copying trades"),
+            ("resume", "Start copying trades again"),
+            ("positions", "Show open positions"),
+            ("pnl", "Show profit and loss"),
+        ]
+        .iter()
+        .map(|(name, description)| json!({"name": name, "description": description, "type": 1}))
+        .collect();
+        self.client
+            .put(format!("{}/applications/{}/commands", API_BASE, application_id))
+            .header("Authorization", format!("Bot {}", bot.token))
+            .json(&commands)
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to register Discord commands")?;
+        Ok(())
+    }
+
+    async fn answer(&self, bot: &DiscordBot, control: &BotControl, interaction: &Interaction) {
+        let content = if interaction.channel_id != bot.command_channel {
+            "Commands are not accepted in this channel".to_string()
+        } else {
+            match Command::parse(&format!("/{}", interaction.command)) {
+                Some(command) => control.execute(command),
+                None => format!("Unknown command /{}", interaction.command),
+            }
+        };
+        let result = self
+            .client
+            .post(format!(
+                "{}/interactions/{}/{}/callback",
+                API_BASE, interaction.id, interaction.token
+            ))
+            .json(&json!({"type": 4, "data": {"content": content}}))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            tracing::warn!("Failed to answer Discord command: {}", e);
+        }
+    }
+}
+
+/// A slash command invocation.
+#[derive(Debug, PartialEq)]
+struct Interaction {
+    id: String,
+    token: String,
+    channel_id: String,
+    command: String,
+}
+
+fn parse_interaction(d: &Value) -> Option<Interaction> {
+    // Type 2 is an application (slash) command
+    if d["type"].as_i64() != Some(2) {
+        return None;
+    }
+    Some(Interaction {
+        id: d["id"].as_str()?.to_string(),
+        token: d["token"].as_str()?.to_string(),
+        channel_id: d["channel_id"].as_str().unwrap_or_default().to_string(),
+        command: d["data"]["name"].as_str()?.to_string(),
+    })
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let Some(url) = self.webhook_for(notification.category()) else {
+            return Ok(());
+        };
+        self.client
+            .post(url)
+            .json(&json!({"content": notification.to_string()}))
+            .send()
+            .await
+            .context("Failed to reach Discord")?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    fn listen(self: Arc<Self>, control: Arc<BotControl>) {
+        self.spawn_gateway(control);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhooks_fall_back_per_category() {
+        let config = Config {
+            discord_webhook: "https://discord.test/all".to_string(),
+            discord_errors_webhook: "https://discord.test/errors".to_string(),
+            ..Config::default()
+        };
+        let discord = DiscordNotifier::from_config(&config).unwrap();
+        assert_eq!(discord.webhook_for(Category::Trades), Some("https://discord.test/all"));
+        assert_eq!(
+            discord.webhook_for(Category::Errors),
+            Some("https://discord.test/errors")
+        );
+        assert!(DiscordNotifier::from_config(&Config::default()).is_none());
+    }
+
+    #[test]
+    fn test_parse_interaction() {
+        let d = json!({"type": 2, "id": "1", "token": "t", "channel_id": "42", "data": {"name": "pnl"}});
+        assert_eq!(
+            parse_interaction(&d),
+            Some(Interaction {
+                id: "1".to_string(),
+                token: "t".to_string(),
+                channel_id: "42".to_string(),
+                command: "pnl".to_string(),
+            })
+        );
+        assert_eq!(parse_interaction(&json!({"type": 1})), None);
+    }
+}
@@ -0,0 +1,1416 @@
+//! Operator notifications and remote control.
+//!
+//! The bot and its watchers hand [`Notification`]s to [`Notifications`],
+//! which delivers them to the configured [`Notifier`]s in the background,
+//! each from its own queue, so a slow or retrying destination never delays a
+//! copy or the other destinations. Notifiers that accept commands (see
+//! [`telegram`] and [`discord`]) act on the bot through [`BotControl`].
+//!
+//! Every notification has a [`Severity`]. A [`Router`] built from
+//! `notify_routes` decides which notifiers receive it, e.g.
+//! `order_filled=discord,risk_tripped=telegram+pagerduty`; anything below
+//! `notify_min_severity` is dropped before routing.
+//!
+//! In [`digest`] mode, routine (below warn) notifications are only counted
+//! and summarized periodically; warnings and critical events still go out
+//! as they happen.
+
+pub mod alerts;
+pub mod digest;
+pub mod discord;
+pub mod email;
+pub mod pagerduty;
+pub mod telegram;
+pub mod webhook;
+
+use crate::approval::{Approvals, Verdict};
+use crate::audit::{AuditAction, AuditTrail};
+use crate::equity::EquityTracker;
+use crate::events::ConnectionState;
+use crate::pnl::PnlTracker;
+use crate::portfolio::Portfolio;
+use crate::risk::RiskManager;
+use crate::types::{Config, Market, Severity, SkipReason, Trade};
+use crate::units::format_duration;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Something the operator may want to hear about.
+///
+/// Serialized (for webhooks) with a snake_case `type` tag.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    TradeCopied {
+        wallet: String,
+        market_id: String,
+        side: String,
+        size_usd: f64,
+        shares: f64,
+        card: TradeCard,
+    },
+    TradeSkipped {
+        wallet: String,
+        market_id: String,
+        side: String,
+        reason: SkipReason,
+        detail: String,
+        card: TradeCard,
+    },
+    OrderFilled {
+        market_id: String,
+        side: String,
+        shares: f64,
+        price: f64,
+        card: TradeCard,
+    },
+    OrderFailed {
+        market_id: String,
+        error: String,
+    },
+    /// A position is being closed by an exit rule (see [`crate::ticker`])
+    PositionExit {
+        market_id: String,
+        /// "stop_loss" or "take_profit"
+        reason: String,
+        shares: f64,
+        price: f64,
+        avg_price: f64,
+    },
+    /// A price alert's condition started to hold (see [`crate::price_alerts`])
+    PriceAlert {
+        token_id: String,
+        /// E.g. "> 0.7" or "spread > 0.1"
+        condition: String,
+        /// The price, spread or volume multiple it fired at
+        value: f64,
+    },
+    /// Abnormal volume or holders in a market (see [`crate::spikes`])
+    MarketAnomaly {
+        market_id: String,
+        question: String,
+        /// "volume_spike" or "holder_surge"
+        kind: String,
+        value: f64,
+        baseline: f64,
+    },
+    /// Yes on one venue and no on the other cost under $1 (see
+    /// [`crate::arbitrage`])
+    Arbitrage {
+        market_id: String,
+        question: String,
+        yes_venue: String,
+        no_venue: String,
+        /// Per share, fees included
+        cost: f64,
+        shares: f64,
+    },
+    /// A held market's outcome was proposed or disputed (see
+    /// [`crate::disputes`])
+    ResolutionUpdate {
+        market_id: String,
+        question: String,
+        /// "proposed" or "disputed"
+        status: String,
+        /// Where the outcome is read from
+        source: String,
+    },
+    RiskTripped {
+        reason: String,
+    },
+    Connection {
+        wallet: String,
+        state: ConnectionState,
+        detail: Option<String>,
+    },
+    /// A large copy is waiting for the operator (see [`crate::approval`])
+    ApprovalRequested {
+        id: u64,
+        wallet: String,
+        market_id: String,
+        side: String,
+        size_usd: f64,
+        shares: f64,
+        expires_in_secs: u64,
+        card: TradeCard,
+    },
+    /// A journal or state write failed
+    StorageFailed {
+        operation: String,
+        error: String,
+    },
+    /// A leader feed has been down for `feed_down_alert` or longer
+    FeedDown {
+        wallet: String,
+        down_secs: u64,
+        detail: Option<String>,
+    },
+    /// The copy pipeline is behaving abnormally (see [`crate::anomaly`])
+    Anomaly {
+        rule: String,
+        summary: String,
+    },
+    Digest(digest::Digest),
+    /// `count` more of `last` were held back within the dedup window
+    Coalesced {
+        count: u32,
+        window_secs: u64,
+        last: Box<Notification>,
+    },
+    /// The condition behind `last` has cleared and stayed clear
+    Recovered {
+        down_secs: u64,
+        last: Box<Notification>,
+    },
+}
+
+/// Where copies link to on Polymarket.
+const POLYMARKET_URL: &str = "https://polymarket.com";
+
+/// Market and leader context for a trade notification, so a message says
+/// what was traded and how the copy compares to the leader's trade.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TradeCard {
+    /// The leader's configured label, or the start of their address
+    pub leader: String,
+    /// The leader's full address
+    pub wallet: String,
+    /// Empty when the market couldn't be looked up
+    pub question: String,
+    pub outcome: String,
+    pub leader_shares: f64,
+    pub leader_price: f64,
+    /// Empty when the market's slug isn't known
+    pub url: String,
+}
+
+impl TradeCard {
+    pub fn new(trade: &Trade, leader: String, market: Option<&Market>) -> Self {
+        Self {
+            leader,
+            wallet: trade.wallet.clone(),
+            question: market.map(|m| m.question.clone()).unwrap_or_default(),
+            // Copies trade the market's first (YES) outcome token
+            outcome: market.and_then(|m| m.outcomes.first().cloned()).unwrap_or_default(),
+            leader_shares: trade.shares,
+            leader_price: trade.price,
+            url: market
+                .filter(|m| !m.slug.is_empty())
+                .map(|m| format!("{}/market/{}", POLYMARKET_URL, m.slug))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// USDC value of the leader's trade.
+    pub fn leader_notional(&self) -> f64 {
+        self.leader_shares * self.leader_price
+    }
+
+    /// The market as a headline names it: its question, or its id.
+    fn market<'a>(&'a self, market_id: &'a str) -> &'a str {
+        if self.question.is_empty() {
+            market_id
+        } else {
+            &self.question
+        }
+    }
+
+    fn leader<'a>(&'a self, wallet: &'a str) -> &'a str {
+        if self.leader.is_empty() {
+            short(wallet)
+        } else {
+            &self.leader
+        }
+    }
+
+    /// The lines under a headline: the outcome, the leader's trade against
+    /// the copy (`copy` is shares and price) and the link.
+    fn write_details(&self, f: &mut fmt::Formatter<'_>, copy: Option<(f64, f64)>) -> fmt::Result {
+        if !self.outcome.is_empty() {
+            write!(f, "\nOutcome: {}", self.outcome)?;
+        }
+        write!(
+            f,
+            "\nLeader: {:.2} shares @ ${:.4} (${:.2})",
+            self.leader_shares,
+            self.leader_price,
+            self.leader_notional()
+        )?;
+        if let Some((shares, price)) = copy {
+            write!(
+                f,
+                "\nCopy: {:.2} shares @ ${:.4} (${:.2})",
+                shares,
+                price,
+                shares * price
+            )?;
+            if self.leader_price > 0.0 && price > 0.0 {
+                write!(f, ", {:+.2}% vs leader", (price / self.leader_price - 1.0) * 100.0)?;
+            }
+        }
+        if !self.url.is_empty() {
+            write!(f, "\n{}", self.url)?;
+        }
+        Ok(())
+    }
+}
+
+/// Broad kind of a notification, for sending trades and errors to
+/// different channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Trades,
+    Errors,
+}
+
+impl Category {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Trades => "trades",
+            Category::Errors => "errors",
+        }
+    }
+}
+
+impl Notification {
+    /// The serialized `type` tag of every notification.
+    pub const KINDS: &'static [&'static str] = &[
+        "trade_copied",
+        "trade_skipped",
+        "order_filled",
+        "order_failed",
+        "position_exit",
+        "price_alert",
+        "market_anomaly",
+        "arbitrage",
+        "resolution_update",
+        "risk_tripped",
+        "connection",
+        "approval_requested",
+        "storage_failed",
+        "feed_down",
+        "anomaly",
+        "digest",
+        "recovered",
+    ];
+
+    /// The serialized `type` tag, e.g. "order_filled"; coalesced repeats
+    /// have the kind of the repeated notification.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Notification::TradeCopied { .. } => "trade_copied",
+            Notification::TradeSkipped { .. } => "trade_skipped",
+            Notification::OrderFilled { .. } => "order_filled",
+            Notification::OrderFailed { .. } => "order_failed",
+            Notification::PositionExit { .. } => "position_exit",
+            Notification::PriceAlert { .. } => "price_alert",
+            Notification::MarketAnomaly { .. } => "market_anomaly",
+            Notification::Arbitrage { .. } => "arbitrage",
+            Notification::ResolutionUpdate { .. } => "resolution_update",
+            Notification::RiskTripped { .. } => "risk_tripped",
+            Notification::Connection { .. } => "connection",
+            Notification::ApprovalRequested { .. } => "approval_requested",
+            Notification::StorageFailed { .. } => "storage_failed",
+            Notification::FeedDown { .. } => "feed_down",
+            Notification::Anomaly { .. } => "anomaly",
+            Notification::Digest(_) => "digest",
+            Notification::Coalesced { last, .. } => last.kind(),
+            Notification::Recovered { .. } => "recovered",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Notification::TradeCopied { .. }
+            | Notification::TradeSkipped { .. }
+            | Notification::OrderFilled { .. }
+            | Notification::MarketAnomaly { .. }
+            | Notification::Arbitrage { .. }
+            | Notification::Digest(_)
+            | Notification::Recovered { .. } => Severity::Info,
+            Notification::Connection {
+                state: ConnectionState::Connected,
+                ..
+            } => Severity::Debug,
+            Notification::OrderFailed { .. }
+            | Notification::PositionExit { .. }
+            | Notification::PriceAlert { .. }
+            | Notification::ResolutionUpdate { .. }
+            | Notification::Connection { .. }
+            | Notification::ApprovalRequested { .. } => Severity::Warn,
+            Notification::RiskTripped { .. }
+            | Notification::StorageFailed { .. }
+            | Notification::FeedDown { .. }
+            | Notification::Anomaly { .. } => Severity::Critical,
+            Notification::Coalesced { last, .. } => last.severity(),
+        }
+    }
+
+    pub fn category(&self) -> Category {
+        match self {
+            Notification::TradeCopied { .. }
+            | Notification::TradeSkipped { .. }
+            | Notification::OrderFilled { .. }
+            | Notification::PositionExit { .. }
+            | Notification::PriceAlert { .. }
+            | Notification::MarketAnomaly { .. }
+            | Notification::Arbitrage { .. }
+            | Notification::ResolutionUpdate { .. }
+            | Notification::ApprovalRequested { .. }
+            | Notification::Digest(_) => Category::Trades,
+            Notification::OrderFailed { .. }
+            | Notification::RiskTripped { .. }
+            | Notification::Connection { .. }
+            | Notification::StorageFailed { .. }
+            | Notification::FeedDown { .. }
+            | Notification::Anomaly { .. } => Category::Errors,
+            Notification::Coalesced { last, .. } | Notification::Recovered { last, .. } => last.category(),
+        }
+    }
+}
+
+fn short(wallet: &str) -> &str {
+    &wallet[..10.min(wallet.len())]
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notification::TradeCopied {
+                wallet,
+                market_id,
+                side,
+                size_usd,
+                shares,
+                card,
+            } => {
+                write!(
+                    f,
+                    "✅ Copying {} {} in {}: ${:.2} ({:.2} shares)",
+                    card.leader(wallet),
+                    side,
+                    card.market(market_id),
+                    size_usd,
+                    shares
+                )?;
+                let price = if *shares > 0.0 { size_usd / shares } else { 0.0 };
+                card.write_details(f, Some((*shares, price)))
+            }
+            Notification::TradeSkipped {
+                wallet,
+                market_id,
+                side,
+                reason,
+                detail,
+                card,
+            } => {
+                write!(
+                    f,
+                    "⏭️ Skipped {} {} in {}: {} ({})",
+                    card.leader(wallet),
+                    side,
+                    card.market(market_id),
+                    reason.as_str(),
+                    detail
+                )?;
+                card.write_details(f, None)
+            }
+            Notification::OrderFilled {
+                market_id,
+                side,
+                shares,
+                price,
+                card,
+            } => {
+                write!(
+                    f,
+                    "💵 Filled {} {:.2} shares @ ${:.4} in {}",
+                    side,
+                    shares,
+                    price,
+                    card.market(market_id)
+                )?;
+                card.write_details(f, Some((*shares, *price)))
+            }
+            Notification::OrderFailed { market_id, error } => write!(f, "❌ Order in {} failed: {}", market_id, error),
+            Notification::PositionExit {
+                market_id,
+                reason,
+                shares,
+                price,
+                avg_price,
+            } => {
+                let what = if reason == "take_profit" { "🎯 Take-profit" } else { "🛑 Stop-loss" };
+                write!(
+                    f,
+                    "{} in {}: selling {:.2} shares at ${:.4} (entry ${:.4})",
+                    what, market_id, shares, price, avg_price
+                )
+            }
+            Notification::PriceAlert {
+                token_id,
+                condition,
+                value,
+            } => write!(f, "🔔 Price alert on {}: {} (now {:.4})", token_id, condition, value),
+            Notification::MarketAnomaly {
+                question,
+                kind,
+                value,
+                baseline,
+                ..
+            } => match kind.as_str() {
+                "holder_surge" => write!(f, "👥 Holders of {} up to {:.0} from {:.0}", question, value, baseline),
+                _ => write!(f, "📊 Volume spike in {}: ${:.0} 24h volume vs ${:.0}", question, value, baseline),
+            },
+            Notification::Arbitrage {
+                question,
+                yes_venue,
+                no_venue,
+                cost,
+                shares,
+                ..
+            } => write!(
+                f,
+                "⚖️ Arbitrage in {}: yes on {} + no on {} for ${:.3} a share ({:.1}% edge, {:.0} shares)",
+                question,
+                yes_venue,
+                no_venue,
+                cost,
+                (1.0 - cost) * 100.0,
+                shares
+            ),
+            Notification::ResolutionUpdate {
+                question,
+                status,
+                source,
+                ..
+            } => {
+                match status.as_str() {
+                    "disputed" => write!(f, "⚖️ Resolution of {} disputed", question)?,
+                    _ => write!(f, "⚖️ Outcome of {} proposed, open to dispute", question)?,
+                }
+                if !source.is_empty() {
+                    write!(f, " (source: {})", source)?;
+                }
+                Ok(())
+            }
+            Notification::RiskTripped { reason } => write!(f, "🛑 Circuit breaker tripped: {}", reason),
+            Notification::Connection {
+                wallet,
+                state: ConnectionState::Connected,
+                ..
+            } => write!(f, "🔗 Feed connected for {}", short(wallet)),
+            Notification::Connection { wallet, detail, .. } => match detail {
+                Some(detail) => write!(f, "🔌 Feed lost for {}: {}", short(wallet), detail),
+                None => write!(f, "🔌 Feed closed for {}", short(wallet)),
+            },
+            Notification::ApprovalRequested {
+                id,
+                wallet,
+                market_id,
+                side,
+                size_usd,
+                shares,
+                expires_in_secs,
+                card,
+            } => {
+                write!(
+                    f,
+                    "✋ Approve copy #{}? {} {} in {}: ${:.2} ({:.2} shares), expires in {}",
+                    id,
+                    card.leader(wallet),
+                    side,
+                    card.market(market_id),
+                    size_usd,
+                    shares,
+                    format_duration(Duration::from_secs(*expires_in_secs))
+                )?;
+                let price = if *shares > 0.0 { size_usd / shares } else { 0.0 };
+                card.write_details(f, Some((*shares, price)))
+            }
+            Notification::StorageFailed { operation, error } => {
+                write!(f, "💾 Storage failure: could not {}: {}", operation, error)
+            }
+            Notification::FeedDown {
+                wallet,
+                down_secs,
+                detail,
+            } => {
+                write!(
+                    f,
+                    "🚨 Feed for {} down for {}",
+                    short(wallet),
+                    format_duration(Duration::from_secs(*down_secs))
+                )?;
+                match detail {
+                    Some(detail) => write!(f, ": {}", detail),
+                    None => Ok(()),
+                }
+            }
+            Notification::Anomaly { rule, summary } => write!(f, "🚨 Anomaly ({}): {}", rule, summary),
+            Notification::Digest(digest) => write!(f, "{}", digest),
+            Notification::Coalesced {
+                count,
+                window_secs,
+                last,
+            } => write!(
+                f,
+                "{} (×{} more in {})",
+                last,
+                count,
+                format_duration(Duration::from_secs(*window_secs))
+            ),
+            Notification::Recovered { down_secs, last } => write!(
+                f,
+                "✅ Resolved after {}: {}",
+                format_duration(Duration::from_secs(*down_secs)),
+                last
+            ),
+        }
+    }
+}
+
+/// A destination for notifications.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &str;
+    async fn notify(&self, notification: &Notification) -> Result<()>;
+
+    /// Least severe notification this notifier receives when no route
+    /// names it explicitly.
+    fn min_severity(&self) -> Severity {
+        Severity::Debug
+    }
+
+    /// Starts accepting operator commands, for notifiers that can.
+    fn listen(self: Arc<Self>, _control: Arc<BotControl>) {}
+}
+
+/// Which notifications a route applies to.
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Any,
+    Kind(&'static str),
+    Category(Category),
+    /// This severity or worse
+    AtLeast(Severity),
+}
+
+impl Selector {
+    fn parse(s: &str) -> Option<Self> {
+        if s == "*" {
+            return Some(Selector::Any);
+        }
+        if let Some(kind) = Notification::KINDS.iter().find(|k| **k == s) {
+            return Some(Selector::Kind(kind));
+        }
+        if let Some(category) = [Category::Trades, Category::Errors]
+            .into_iter()
+            .find(|c| c.as_str() == s)
+        {
+            return Some(Selector::Category(category));
+        }
+        Severity::parse(s).map(Selector::AtLeast)
+    }
+
+    fn matches(&self, notification: &Notification) -> bool {
+        match self {
+            Selector::Any => true,
+            Selector::Kind(kind) => notification.kind() == *kind,
+            Selector::Category(category) => notification.category() == *category,
+            Selector::AtLeast(severity) => notification.severity() >= *severity,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Route {
+    selector: Selector,
+    /// Notifier names; empty for "none"
+    destinations: Vec<String>,
+}
+
+/// A leader whose trades are notified, from `wallet` or `wallet=min_usd`.
+#[derive(Debug, Clone, PartialEq)]
+struct Subscription {
+    /// Lowercased
+    wallet: String,
+    /// Least leader notional (USDC) worth a notification
+    min_notional: f64,
+}
+
+impl Subscription {
+    fn parse(entry: &str) -> Result<Self> {
+        let (wallet, min_notional) = match entry.split_once('=') {
+            Some((wallet, min)) => {
+                let min = min.trim().trim_start_matches('$');
+                let min: f64 = min
+                    .parse()
+                    .ok()
+                    .filter(|m: &f64| m.is_finite() && *m >= 0.0)
+                    .with_context(|| format!("invalid minimum notional '{}'", min))?;
+                (wallet, min)
+            }
+            None => (entry, 0.0),
+        };
+        let wallet = wallet.trim().to_lowercase();
+        anyhow::ensure!(!wallet.is_empty(), "expected wallet or wallet=min_usd");
+        Ok(Self { wallet, min_notional })
+    }
+}
+
+/// Decides which notifiers receive a notification.
+///
+/// Rules are `selector=destinations`. A selector is a notification type
+/// (`order_filled`), a category (`trades`, `errors`), a severity meaning
+/// that level or worse (`warn`), or `*`. Destinations are notifier names
+/// joined with `+`, or `none`. The first matching rule decides; with no
+/// match every notifier gets the notification if it meets the notifier's
+/// own minimum (PagerDuty only pages for critical events by default).
+///
+/// With leader subscriptions, copies, skips and fills are only notified for
+/// the subscribed leaders' trades of at least their minimum notional;
+/// approval requests always go out.
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    min_severity: Severity,
+    routes: Vec<Route>,
+    subscriptions: Vec<Subscription>,
+}
+
+impl Router {
+    /// Parses `rules`, whose destinations must be among `notifiers`.
+    pub fn new(rules: &[String], min_severity: Severity, notifiers: &[String]) -> Result<Self> {
+        let routes = rules
+            .iter()
+            .map(|rule| {
+                Self::parse_rule(rule, notifiers).with_context(|| format!("Invalid notification route '{}'", rule))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            min_severity,
+            routes,
+            subscriptions: Vec::new(),
+        })
+    }
+
+    /// Limits trade notifications to the leaders in `entries`
+    /// (`wallet` or `wallet=min_usd`); empty keeps every leader.
+    pub fn with_subscriptions(mut self, entries: &[String]) -> Result<Self> {
+        self.subscriptions = entries
+            .iter()
+            .map(|entry| Subscription::parse(entry).with_context(|| format!("Invalid leader subscription '{}'", entry)))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    pub fn from_config(config: &Config, registry: &NotifierRegistry) -> Result<Self> {
+        Self::new(&config.notify_routes, config.notify_min_severity, &registry.names())?
+            .with_subscriptions(&config.notify_leaders)
+    }
+
+    /// Whether a trade notification is about a subscribed leader's trade
+    /// big enough to mention; anything else passes.
+    fn subscribed(&self, notification: &Notification) -> bool {
+        if self.subscriptions.is_empty() {
+            return true;
+        }
+        let card = match notification {
+            Notification::TradeCopied { card, .. }
+            | Notification::TradeSkipped { card, .. }
+            | Notification::OrderFilled { card, .. } => card,
+            _ => return true,
+        };
+        let wallet = card.wallet.to_lowercase();
+        self.subscriptions
+            .iter()
+            .any(|s| s.wallet == wallet && card.leader_notional() >= s.min_notional)
+    }
+
+    fn parse_rule(rule: &str, notifiers: &[String]) -> Result<Route> {
+        let (selector, destinations) = rule.split_once('=').context("expected selector=notifier+notifier")?;
+        let selector = Selector::parse(&selector.trim().to_lowercase()).with_context(|| {
+            format!(
+                "unknown selector '{}' (use a notification type, trades, errors, a severity or *)",
+                selector.trim()
+            )
+        })?;
+        let destinations = destinations.trim().to_lowercase();
+        let destinations = if destinations == "none" {
+            Vec::new()
+        } else {
+            destinations
+                .split('+')
+                .map(|name| {
+                    let name = name.trim();
+                    anyhow::ensure!(
+                        notifiers.iter().any(|n| n == name),
+                        "unknown notifier '{}' (expected one of {})",
+                        name,
+                        notifiers.join(", ")
+                    );
+                    Ok(name.to_string())
+                })
+                .collect::<Result<_>>()?
+        };
+        Ok(Route { selector, destinations })
+    }
+
+    /// Whether `notifier` should receive `notification`.
+    pub fn wants(&self, notifier: &dyn Notifier, notification: &Notification) -> bool {
+        if notification.severity() < self.min_severity || !self.subscribed(notification) {
+            return false;
+        }
+        match self.routes.iter().find(|route| route.selector.matches(notification)) {
+            Some(route) => route.destinations.iter().any(|name| name == notifier.name()),
+            None => notification.severity() >= notifier.min_severity(),
+        }
+    }
+}
+
+/// Fans notifications out to the configured notifiers. Cheap to clone;
+/// the default sends nowhere.
+#[derive(Clone, Default)]
+pub struct Notifications {
+    notifiers: Vec<Arc<dyn Notifier>>,
+    queues: Vec<mpsc::UnboundedSender<Notification>>,
+    router: Arc<Router>,
+    /// Counts for the current digest period, in digest mode
+    digest: Option<(Duration, Arc<Mutex<digest::Digest>>)>,
+    alerts: Option<Arc<Mutex<alerts::AlertDedup>>>,
+}
+
+impl Notifications {
+    /// Starts delivering to `notifiers` as `router` directs; must be called
+    /// within a runtime.
+    pub fn start(notifiers: Vec<Arc<dyn Notifier>>, router: Router) -> Self {
+        let queues = notifiers
+            .iter()
+            .map(|notifier| {
+                let (tx, mut rx) = mpsc::unbounded_channel::<Notification>();
+                let notifier = Arc::clone(notifier);
+                tokio::spawn(async move {
+                    while let Some(notification) = rx.recv().await {
+                        if let Err(e) = notifier.notify(&notification).await {
+                            tracing::warn!("Failed to notify via {}: {}", notifier.name(), e);
+                        }
+                    }
+                });
+                tx
+            })
+            .collect();
+        Self {
+            notifiers,
+            queues,
+            router: Arc::new(router),
+            digest: None,
+            alerts: None,
+        }
+    }
+
+    /// Coalesces repeated alerts within `window` and holds recoveries back
+    /// until they have lasted `recovery_delay`; a zero window disables.
+    /// Must be called within a runtime.
+    pub fn with_alert_dedup(mut self, window: Duration, recovery_delay: Duration) -> Self {
+        if window.is_zero() || !self.is_enabled() {
+            return self;
+        }
+        let dedup = Arc::new(Mutex::new(alerts::AlertDedup::new(window, recovery_delay)));
+        self.alerts = Some(Arc::clone(&dedup));
+        let notifications = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                let due = dedup.lock().unwrap().due(chrono::Utc::now().timestamp_millis());
+                for notification in due {
+                    notifications.deliver(notification);
+                }
+            }
+        });
+        self
+    }
+
+    /// Summarizes routine notifications every `period` instead of sending
+    /// each one; zero keeps sending them individually.
+    pub fn with_digest(mut self, period: Duration) -> Self {
+        self.digest = (!period.is_zero()).then(|| (period, Arc::new(Mutex::new(digest::Digest::default()))));
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.queues.is_empty()
+    }
+
+    /// Lets every notifier that takes commands act on `control`.
+    pub fn listen(&self, control: &Arc<BotControl>) {
+        for notifier in &self.notifiers {
+            Arc::clone(notifier).listen(Arc::clone(control));
+        }
+    }
+
+    /// In digest mode, sends the summary at the end of every period.
+    pub fn spawn_digest(&self, control: &Arc<BotControl>) {
+        let Some((period, counts)) = self.digest.clone().filter(|_| self.is_enabled()) else {
+            return;
+        };
+        let notifications = self.clone();
+        let control = Arc::clone(control);
+        tokio::spawn(async move {
+            loop {
+                let now = chrono::Utc::now().timestamp_millis();
+                let wait = digest::next_boundary(now, period) - now;
+                tokio::time::sleep(Duration::from_millis(wait.max(0) as u64)).await;
+                let digest = std::mem::take(&mut *counts.lock().unwrap());
+                if !digest.is_quiet() {
+                    notifications.deliver(Notification::Digest(digest.finish(period, &control)));
+                }
+            }
+        });
+    }
+
+    /// Queues `notification` for the notifiers it is routed to; never blocks.
+    /// Repeated alerts may be held back, and in digest mode anything below
+    /// warn is only counted.
+    pub fn send(&self, notification: Notification) {
+        if let Some((_, counts)) = &self.digest {
+            counts.lock().unwrap().record(&notification);
+        }
+        // Dedup sees everything, so it notices recoveries even in digest mode
+        let notification = match &self.alerts {
+            Some(alerts) => {
+                let now = chrono::Utc::now().timestamp_millis();
+                let Some(notification) = alerts.lock().unwrap().filter(notification, now) else {
+                    return;
+                };
+                notification
+            }
+            None => notification,
+        };
+        if self.digest.is_some() && notification.severity() < Severity::Warn {
+            return;
+        }
+        self.deliver(notification);
+    }
+
+    fn deliver(&self, notification: Notification) {
+        for (notifier, queue) in self.notifiers.iter().zip(&self.queues) {
+            if self.router.wants(notifier.as_ref(), &notification) {
+                let _ = queue.send(notification.clone());
+            }
+        }
+    }
+}
+
+/// A command an operator can send from a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Positions,
+    Pnl,
+    Help,
+}
+
+impl Command {
+    /// Parses "/pause", "/pnl@MyBot" and the like; other text is `None`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let word = text.split_whitespace().next()?.strip_prefix('/')?;
+        let word = word.split('@').next().unwrap_or(word);
+        match word.to_lowercase().as_str() {
+            "pause" => Some(Command::Pause),
+            "resume" => Some(Command::Resume),
+            "positions" => Some(Command::Positions),
+            "pnl" => Some(Command::Pnl),
+            "help" | "start" => Some(Command::Help),
+            _ => None,
+        }
+    }
+}
+
+/// The parts of a running bot that remote commands may read or change.
+pub struct BotControl {
+    paused: AtomicBool,
+    portfolio: Arc<Portfolio>,
+    risk: Arc<RiskManager>,
+    approvals: Arc<Approvals>,
+    audit: Arc<AuditTrail>,
+    pnl: Option<Arc<PnlTracker>>,
+    equity: Option<Arc<EquityTracker>>,
+}
+
+impl BotControl {
+    pub fn new(portfolio: Arc<Portfolio>, risk: Arc<RiskManager>) -> Self {
+        Self {
+            paused: AtomicBool::new(false),
+            portfolio,
+            risk,
+            approvals: Arc::new(Approvals::new(0.0, Duration::ZERO)),
+            audit: Arc::new(AuditTrail::default()),
+            pnl: None,
+            equity: None,
+        }
+    }
+
+    /// Records what operators change in `audit`.
+    pub fn with_audit(mut self, audit: Arc<AuditTrail>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn audit(&self) -> &Arc<AuditTrail> {
+        &self.audit
+    }
+
+    /// Lets operators answer approval requests from `approvals`.
+    pub fn with_approvals(mut self, approvals: Arc<Approvals>) -> Self {
+        self.approvals = approvals;
+        self
+    }
+
+    pub fn approvals(&self) -> &Arc<Approvals> {
+        &self.approvals
+    }
+
+    pub fn portfolio(&self) -> &Arc<Portfolio> {
+        &self.portfolio
+    }
+
+    /// Reports unrealized PnL from `pnl` alongside what the portfolio knows.
+    pub fn with_pnl(mut self, pnl: Arc<PnlTracker>) -> Self {
+        self.pnl = Some(pnl);
+        self
+    }
+
+    pub fn pnl(&self) -> Option<&Arc<PnlTracker>> {
+        self.pnl.as_ref()
+    }
+
+    /// Reports drawdown and returns off `equity`'s curve.
+    pub fn with_equity(mut self, equity: Arc<EquityTracker>) -> Self {
+        self.equity = Some(equity);
+        self
+    }
+
+    pub fn equity(&self) -> Option<&Arc<EquityTracker>> {
+        self.equity.as_ref()
+    }
+
+    pub fn risk(&self) -> &Arc<RiskManager> {
+        &self.risk
+    }
+
+    /// Applies an operator's answer to approval request `id`.
+    pub fn resolve_approval(&self, id: u64, verdict: Verdict) -> String {
+        let reply = self
+            .approvals
+            .resolve(id, verdict, chrono::Utc::now().timestamp_millis());
+        tracing::info!("✋ {}", reply);
+        reply
+    }
+
+    /// While paused, leader trades are skipped instead of copied.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    /// Runs `command` and returns the reply for the operator.
+    pub fn execute(&self, command: Command) -> String {
+        match command {
+            Command::Pause => {
+                self.set_paused(true);
+                self.audit.record(AuditAction::Paused, "operator", json!({}));
+                tracing::warn!("⏸️  Copying paused by remote command");
+                "⏸️ Paused: leader trades will be skipped until /resume".to_string()
+            }
+            Command::Resume => {
+                self.set_paused(false);
+                self.audit.record(AuditAction::Resumed, "operator", json!({}));
+                tracing::info!("▶️  Copying resumed by remote command");
+                "▶️ Resumed copying".to_string()
+            }
+            Command::Positions => {
+                let holdings = self.portfolio.holdings();
+                if holdings.is_empty() {
+                    return "No open positions".to_string();
+                }
+                let mut reply = format!("📂 {} open positions", holdings.len());
+                for h in &holdings {
+                    reply.push_str(&format!(
+                        "\n{}: {:.2} shares @ ${:.4} (${:.2})",
+                        h.market_id,
+                        h.shares(),
+                        h.avg_price(),
+                        h.cost()
+                    ));
+                }
+                reply
+            }
+            Command::Pnl => {
+                let state = self.risk.get_state();
+                format!(
+                    "💰 Realized today: ${:.2}\nRealized since start: ${:.2}\nOpen exposure: ${:.2}\nTrades today: {} (${:.2} volume){}",
+                    state.realized_pnl_today,
+                    self.portfolio.realized_pnl(),
+                    self.portfolio.exposure(),
+                    state.total_trades_today,
+                    state.total_volume_today,
+                    if self.is_paused() { "\n⏸️ Paused" } else { "" }
+                )
+            }
+            Command::Help => "/pause - stop copying\n/resume - start copying again\n/positions - open positions\n/pnl - profit and loss".to_string(),
+        }
+    }
+}
+
+/// Builds a notifier from the config; `Ok(None)` when it isn't configured.
+pub type NotifierFactory = Arc<dyn Fn(&Config) -> Result<Option<Arc<dyn Notifier>>> + Send + Sync>;
+
+/// The notifiers a bot can use, by name. The built-in transports are
+/// registered the same way custom ones are, so a library user can add e.g.
+/// Matrix or ntfy.sh without touching this crate:
+///
+/// ```no_run
+/// # use polymarket_copy_bot::notify::{Notification, Notifier};
+/// # use polymarket_copy_bot::builder::BotBuilder;
+/// # use std::sync::Arc;
+/// struct Ntfy;
+///
+/// #[async_trait::async_trait]
+/// impl Notifier for Ntfy {
+///     fn name(&self) -> &str {
+///         "ntfy"
+///     }
+///
+///     async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+///         reqwest::Client::new()
+///             .post("https://ntfy.sh/my-bot")
+///             .body(notification.to_string())
+///             .send()
+///             .await?;
+///         Ok(())
+///     }
+/// }
+///
+/// let bot = BotBuilder::new().notifier(Arc::new(Ntfy)).build();
+/// # let _ = bot;
+/// ```
+///
+/// Registered names can be used as destinations in `notify_routes`.
+#[derive(Clone)]
+pub struct NotifierRegistry {
+    factories: Vec<(String, NotifierFactory)>,
+}
+
+impl Default for NotifierRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl fmt::Debug for NotifierRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+impl NotifierRegistry {
+    pub fn empty() -> Self {
+        Self { factories: Vec::new() }
+    }
+
+    /// Telegram, Discord, webhooks, PagerDuty and email.
+    pub fn builtin() -> Self {
+        fn boxed<N: Notifier + 'static>(notifier: Option<N>) -> Option<Arc<dyn Notifier>> {
+            notifier.map(|n| Arc::new(n) as Arc<dyn Notifier>)
+        }
+        let mut registry = Self::empty();
+        registry.register("telegram", |c| Ok(boxed(telegram::TelegramNotifier::from_config(c))));
+        registry.register("discord", |c| Ok(boxed(discord::DiscordNotifier::from_config(c))));
+        registry.register("webhook", |c| Ok(boxed(webhook::WebhookNotifier::from_config(c))));
+        registry.register("pagerduty", |c| Ok(boxed(pagerduty::PagerDutyNotifier::from_config(c))));
+        registry.register("email", |c| Ok(boxed(email::EmailNotifier::from_config(c)?)));
+        registry
+    }
+
+    /// Adds (or replaces) the notifier called `name`, built from the config
+    /// when the bot starts.
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&Config) -> Result<Option<Arc<dyn Notifier>>> + Send + Sync + 'static,
+    {
+        self.factories.retain(|(n, _)| n != name);
+        self.factories.push((name.to_string(), Arc::new(factory)));
+    }
+
+    /// Adds an already built notifier under its own name.
+    pub fn add(&mut self, notifier: Arc<dyn Notifier>) {
+        let name = notifier.name().to_string();
+        self.register(&name, move |_| Ok(Some(Arc::clone(&notifier))));
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.factories.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// The notifiers enabled in `config`.
+    pub fn build(&self, config: &Config) -> Result<Vec<Arc<dyn Notifier>>> {
+        let mut notifiers = Vec::new();
+        for (name, factory) in &self.factories {
+            if let Some(notifier) =
+                factory(config).with_context(|| format!("Failed to set up the {} notifier", name))?
+            {
+                notifiers.push(notifier);
+            }
+        }
+        Ok(notifiers)
+    }
+}
+
+/// The built-in notifiers enabled in `config`.
+pub fn from_config(config: &Config) -> Result<Vec<Arc<dyn Notifier>>> {
+    NotifierRegistry::builtin().build(config)
+}
+
+/// Turns every notifier off, e.g. for replays.
+pub fn disable(config: &mut Config) {
+    config.telegram_bot_token.clear();
+    config.discord_webhook.clear();
+    config.discord_trades_webhook.clear();
+    config.discord_errors_webhook.clear();
+    config.discord_bot_token.clear();
+    config.webhook_urls.clear();
+    config.pagerduty_routing_key.clear();
+    config.opsgenie_api_key.clear();
+    config.smtp_url.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FillRecord;
+    use crate::types::CostBasis;
+
+    #[test]
+    fn test_command_parsing() {
+        assert_eq!(Command::parse("/pause"), Some(Command::Pause));
+        assert_eq!(Command::parse("/PnL@copy_bot now"), Some(Command::Pnl));
+        assert_eq!(Command::parse("pause"), None);
+        assert_eq!(Command::parse("/sell everything"), None);
+    }
+
+    struct Named(&'static str, Severity);
+
+    #[async_trait]
+    impl Notifier for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn notify(&self, _notification: &Notification) -> Result<()> {
+            Ok(())
+        }
+
+        fn min_severity(&self) -> Severity {
+            self.1
+        }
+    }
+
+    #[test]
+    fn test_kinds_match_serialized_type() {
+        let notification = Notification::OrderFilled {
+            market_id: "m".to_string(),
+            side: "BUY".to_string(),
+            shares: 1.0,
+            price: 0.5,
+            card: TradeCard::default(),
+        };
+        let value = serde_json::to_value(&notification).unwrap();
+        assert_eq!(value["type"], notification.kind());
+        assert!(Notification::KINDS.contains(&notification.kind()));
+    }
+
+    #[test]
+    fn test_trade_card() {
+        let trade = Trade {
+            wallet: "0xabc".to_string(),
+            event_id: "e1".to_string(),
+            market_id: "m1".to_string(),
+            side: crate::types::TradeSide::BUY,
+            shares: 200.0,
+            price: 0.5,
+            timestamp: 0,
+            tx_hash: None,
+        };
+        let market = Market {
+            id: "m1".to_string(),
+            event_id: "e1".to_string(),
+            question: "Will it rain in London tomorrow?".to_string(),
+            yes_price: 0.5,
+            no_price: 0.5,
+            liquidity: 10_000.0,
+            slug: "rain-in-london".to_string(),
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            ..Default::default()
+        };
+        let filled = Notification::OrderFilled {
+            market_id: "m1".to_string(),
+            side: "BUY".to_string(),
+            shares: 20.0,
+            price: 0.51,
+            card: TradeCard::new(&trade, "Theo".to_string(), Some(&market)),
+        };
+        let text = filled.to_string();
+        assert!(text.starts_with("💵 Filled BUY 20.00 shares @ $0.5100 in Will it rain in London tomorrow?"));
+        assert!(text.contains("Outcome: Yes"));
+        assert!(text.contains("Leader: 200.00 shares @ $0.5000 ($100.00)"));
+        assert!(text.contains("Copy: 20.00 shares @ $0.5100 ($10.20), +2.00% vs leader"));
+        assert!(text.ends_with("https://polymarket.com/market/rain-in-london"));
+
+        // Without the market the headline falls back to its id
+        let card = TradeCard::new(&trade, "Theo".to_string(), None);
+        assert!(card.url.is_empty());
+        assert_eq!(card.market("m1"), "m1");
+    }
+
+    #[test]
+    fn test_leader_subscriptions() {
+        let entries = vec!["0xABC=500".to_string(), "0xdef".to_string()];
+        let router = Router::default().with_subscriptions(&entries).unwrap();
+        let telegram = Named("telegram", Severity::Debug);
+        let copied = |wallet: &str, leader_shares: f64| Notification::TradeCopied {
+            wallet: wallet.to_string(),
+            market_id: "m".to_string(),
+            side: "BUY".to_string(),
+            size_usd: 10.0,
+            shares: 20.0,
+            card: TradeCard {
+                wallet: wallet.to_string(),
+                leader_shares,
+                leader_price: 0.5,
+                ..Default::default()
+            },
+        };
+        assert!(router.wants(&telegram, &copied("0xabc", 1_000.0)));
+        assert!(!router.wants(&telegram, &copied("0xabc", 999.0)));
+        assert!(router.wants(&telegram, &copied("0xdef", 1.0)));
+        assert!(!router.wants(&telegram, &copied("0x123", 10_000.0)));
+
+        // Not about a leader's trade
+        let tripped = Notification::RiskTripped { reason: String::new() };
+        assert!(router.wants(&telegram, &tripped));
+
+        assert!(Router::default()
+            .with_subscriptions(&["0xabc=lots".to_string()])
+            .is_err());
+        assert!(Router::default().with_subscriptions(&["=500".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_routing() {
+        let rules: Vec<String> = ["order_filled=discord", "critical=telegram+pagerduty", "trades=none"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let router = Router::new(&rules, Severity::Info, &NotifierRegistry::builtin().names()).unwrap();
+        let telegram = Named("telegram", Severity::Debug);
+        let discord = Named("discord", Severity::Debug);
+        let pagerduty = Named("pagerduty", Severity::Critical);
+
+        let filled = Notification::OrderFilled {
+            market_id: "m".to_string(),
+            side: "BUY".to_string(),
+            shares: 1.0,
+            price: 0.5,
+            card: TradeCard::default(),
+        };
+        assert!(router.wants(&discord, &filled));
+        assert!(!router.wants(&telegram, &filled));
+
+        let tripped = Notification::RiskTripped { reason: String::new() };
+        assert!(router.wants(&telegram, &tripped));
+        assert!(router.wants(&pagerduty, &tripped));
+        assert!(!router.wants(&discord, &tripped));
+
+        let copied = Notification::TradeCopied {
+            wallet: "0xabc".to_string(),
+            market_id: "m".to_string(),
+            side: "BUY".to_string(),
+            size_usd: 10.0,
+            shares: 20.0,
+            card: TradeCard::default(),
+        };
+        assert!(!router.wants(&discord, &copied));
+
+        // Unrouted: each notifier's own minimum applies
+        let failed = Notification::OrderFailed {
+            market_id: "m".to_string(),
+            error: "rejected".to_string(),
+        };
+        assert!(router.wants(&telegram, &failed));
+        assert!(!router.wants(&pagerduty, &failed));
+
+        // Below the global minimum nothing is sent
+        let connected = Notification::Connection {
+            wallet: "0xabc".to_string(),
+            state: ConnectionState::Connected,
+            detail: None,
+        };
+        assert!(!router.wants(&telegram, &connected));
+    }
+
+    #[test]
+    fn test_invalid_routes() {
+        let names = NotifierRegistry::builtin().names();
+        assert!(Router::new(&["fills=discord".to_string()], Severity::Info, &names).is_err());
+        assert!(Router::new(&["warn=slack".to_string()], Severity::Info, &names).is_err());
+        assert!(Router::new(&["warn".to_string()], Severity::Info, &names).is_err());
+    }
+
+    #[test]
+    fn test_registered_notifiers_are_routable() {
+        let mut registry = NotifierRegistry::builtin();
+        registry.add(Arc::new(Named("ntfy", Severity::Debug)));
+        assert!(registry.names().contains(&"ntfy".to_string()));
+        let notifiers = registry.build(&Config::default()).unwrap();
+        assert_eq!(notifiers.len(), 1);
+        assert_eq!(notifiers[0].name(), "ntfy");
+        assert!(Router::new(&["warn=ntfy".to_string()], Severity::Info, &registry.names()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_control_commands() {
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Average, None));
+        portfolio
+            .apply_fill(&FillRecord {
+                id: 0,
+                order_id: 0,
+                market_id: "market1".to_string(),
+                side: "BUY".to_string(),
+                shares: 10.0,
+                price: 0.5,
+                fee: 0.0,
+                filled_at: 0,
+            })
+            .await;
+        let control = BotControl::new(Arc::clone(&portfolio), Arc::new(RiskManager::new(Config::default())));
+
+        control.execute(Command::Pause);
+        assert!(control.is_paused());
+        assert!(control.execute(Command::Pnl).contains("Open exposure: $5.00"));
+        assert!(control.execute(Command::Positions).contains("market1: 10.00 shares"));
+        control.execute(Command::Resume);
+        assert!(!control.is_paused());
+    }
+}
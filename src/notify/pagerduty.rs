@@ -0,0 +1,95 @@
+//! PagerDuty: triggers incidents through the Events API v2 using the
+//! integration's `pagerduty_routing_key`.
+//!
+//! Unless a route sends it more, PagerDuty only hears about critical events
+//! such as a tripped circuit breaker.
+
+use super::{Notification, Notifier};
+use crate::types::{Config, Severity};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+const EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+pub struct PagerDutyNotifier {
+    client: reqwest::Client,
+    routing_key: String,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(routing_key: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            routing_key: routing_key.to_string(),
+        }
+    }
+
+    /// `None` unless a routing key is configured.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (!config.pagerduty_routing_key.is_empty()).then(|| Self::new(&config.pagerduty_routing_key))
+    }
+
+    fn event(&self, notification: &Notification) -> Value {
+        let severity = match notification.severity() {
+            Severity::Critical => "critical",
+            Severity::Warn => "warning",
+            Severity::Info | Severity::Debug => "info",
+        };
+        json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "payload": {
+                "summary": notification.to_string(),
+                "source": "polymarket-bot",
+                "severity": severity,
+                "class": notification.kind(),
+                "custom_details": notification,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for PagerDutyNotifier {
+    fn name(&self) -> &str {
+        "pagerduty"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let resp = self
+            .client
+            .post(EVENTS_URL)
+            .json(&self.event(notification))
+            .send()
+            .await
+            .context("Failed to reach PagerDuty")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            anyhow::bail!("PagerDuty returned {}: {}", status, body);
+        }
+        Ok(())
+    }
+
+    fn min_severity(&self) -> Severity {
+        Severity::Critical
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_payload() {
+        let pagerduty = PagerDutyNotifier::new("key");
+        let event = pagerduty.event(&Notification::RiskTripped {
+            reason: "daily loss".to_string(),
+        });
+        assert_eq!(event["routing_key"], "key");
+        assert_eq!(event["payload"]["severity"], "critical");
+        assert_eq!(event["payload"]["class"], "risk_tripped");
+        assert_eq!(event["payload"]["custom_details"]["reason"], "daily loss");
+    }
+}
@@ -0,0 +1,304 @@
+//! Telegram bot: sends notifications to one chat and answers commands
+//! (/pause, /resume, /positions, /pnl) sent from it.
+//!
+//! Approval requests come with Approve / Reject / Half size buttons; a press
+//! resolves the request and the message is edited to show the outcome.
+//!
+//! Create a bot with @BotFather for `telegram_bot_token`. Commands and
+//! button presses from any chat other than `telegram_chat_id` are ignored.
+
+use super::{BotControl, Command, Notification, Notifier};
+use crate::approval::Verdict;
+use crate::types::Config;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+const API_BASE: &str = "https://api.telegram.org";
+
+pub struct TelegramNotifier {
+    client: reqwest::Client,
+    base_url: String,
+    chat_id: String,
+}
+
+impl TelegramNotifier {
+    pub fn new(api_base: &str, token: &str, chat_id: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: format!("{}/bot{}", api_base.trim_end_matches('/'), token),
+            chat_id: chat_id.to_string(),
+        }
+    }
+
+    /// `None` unless both the token and the chat are configured.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.telegram_bot_token.is_empty() || config.telegram_chat_id.is_empty() {
+            return None;
+        }
+        Some(Self::new(
+            API_BASE,
+            &config.telegram_bot_token,
+            &config.telegram_chat_id,
+        ))
+    }
+
+    pub async fn send_message(&self, text: &str) -> Result<()> {
+        self.call(
+            "sendMessage",
+            json!({
+                "chat_id": self.chat_id,
+                "text": text,
+                "disable_web_page_preview": true,
+            }),
+        )
+        .await
+    }
+
+    /// Sends an approval request with a button per answer.
+    async fn send_approval(&self, id: u64, text: &str) -> Result<()> {
+        let button = |label: &str, verdict: Verdict| json!({"text": label, "callback_data": format!("{}:{}", verdict.as_str(), id)});
+        self.call(
+            "sendMessage",
+            json!({
+                "chat_id": self.chat_id,
+                "text": text,
+                "reply_markup": {
+                    "inline_keyboard": [[
+                        button("✅ Approve", Verdict::Approve),
+                        button("🚫 Reject", Verdict::Reject),
+                        button("½ Half size", Verdict::HalfSize),
+                    ]],
+                },
+            }),
+        )
+        .await
+    }
+
+    /// Acknowledges a button press and replaces its message (and buttons)
+    /// with the outcome.
+    async fn answer_button(&self, press: &ButtonPress, reply: &str) -> Result<()> {
+        self.call(
+            "answerCallbackQuery",
+            json!({"callback_query_id": press.id, "text": reply}),
+        )
+        .await?;
+        self.call(
+            "editMessageText",
+            json!({
+                "chat_id": self.chat_id,
+                "message_id": press.message_id,
+                "text": format!("{}\n\n{}", press.message_text, reply),
+            }),
+        )
+        .await
+    }
+
+    async fn call(&self, method: &str, body: serde_json::Value) -> Result<()> {
+        let resp = self
+            .client
+            .post(format!("{}/{}", self.base_url, method))
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to reach Telegram")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Telegram returned {}: {}", status, body);
+        }
+        Ok(())
+    }
+
+    /// Long-polls for commands from the configured chat and replies to them.
+    fn spawn_commands(self: Arc<Self>, control: Arc<BotControl>) {
+        tokio::spawn(async move {
+            let mut offset = 0i64;
+            loop {
+                match self.poll(offset).await {
+                    Ok(resp) => {
+                        for press in parse_button_presses(&resp) {
+                            offset = offset.max(press.update_id + 1);
+                            let reply = if press.chat_id != self.chat_id {
+                                tracing::warn!("Ignoring Telegram button from unknown chat {}", press.chat_id);
+                                "Not allowed from this chat".to_string()
+                            } else {
+                                match parse_approval(&press.data) {
+                                    Some((verdict, id)) => control.resolve_approval(id, verdict),
+                                    None => "Unknown button".to_string(),
+                                }
+                            };
+                            if let Err(e) = self.answer_button(&press, &reply).await {
+                                tracing::warn!("Failed to answer Telegram button: {}", e);
+                            }
+                        }
+                        for (update_id, chat_id, text) in parse_updates(&resp) {
+                            offset = offset.max(update_id + 1);
+                            if text.is_empty() {
+                                continue;
+                            }
+                            if chat_id != self.chat_id {
+                                tracing::warn!("Ignoring Telegram message from unknown chat {}", chat_id);
+                                continue;
+                            }
+                            let Some(command) = Command::parse(&text) else { continue };
+                            let reply = control.execute(command);
+                            if let Err(e) = self.send_message(&reply).await {
+                                tracing::warn!("Failed to answer Telegram command: {}", e);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        tracing::debug!("Telegram poll failed: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+    }
+
+    async fn poll(&self, offset: i64) -> Result<serde_json::Value> {
+        Ok(self
+            .client
+            .get(format!("{}/getUpdates", self.base_url))
+            .query(&[("offset", offset.to_string()), ("timeout", "30".to_string())])
+            .timeout(Duration::from_secs(40))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+/// A press of one of our inline keyboard buttons.
+#[derive(Debug, PartialEq)]
+struct ButtonPress {
+    update_id: i64,
+    id: String,
+    chat_id: String,
+    message_id: i64,
+    message_text: String,
+    data: String,
+}
+
+fn parse_button_presses(resp: &serde_json::Value) -> Vec<ButtonPress> {
+    resp["result"]
+        .as_array()
+        .map(|updates| {
+            updates
+                .iter()
+                .filter_map(|u| {
+                    let query = u.get("callback_query")?;
+                    let message = &query["message"];
+                    Some(ButtonPress {
+                        update_id: u["update_id"].as_i64()?,
+                        id: query["id"].as_str()?.to_string(),
+                        chat_id: chat_id(&message["chat"]["id"]),
+                        message_id: message["message_id"].as_i64().unwrap_or_default(),
+                        message_text: message["text"].as_str().unwrap_or_default().to_string(),
+                        data: query["data"].as_str().unwrap_or_default().to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses "approve:12" style button data.
+fn parse_approval(data: &str) -> Option<(Verdict, u64)> {
+    let (verdict, id) = data.split_once(':')?;
+    Some((Verdict::parse(verdict)?, id.parse().ok()?))
+}
+
+fn chat_id(id: &serde_json::Value) -> String {
+    match id {
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Returns (update id, chat id, text) of every update; updates that aren't
+/// messages have empty text.
+fn parse_updates(resp: &serde_json::Value) -> Vec<(i64, String, String)> {
+    resp["result"]
+        .as_array()
+        .map(|updates| {
+            updates
+                .iter()
+                .filter_map(|u| {
+                    let update_id = u["update_id"].as_i64()?;
+                    let message = &u["message"];
+                    let chat_id = chat_id(&message["chat"]["id"]);
+                    let text = message["text"].as_str().unwrap_or_default().to_string();
+                    Some((update_id, chat_id, text))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        match notification {
+            Notification::ApprovalRequested { id, .. } => self.send_approval(*id, &notification.to_string()).await,
+            _ => self.send_message(&notification.to_string()).await,
+        }
+    }
+
+    fn listen(self: Arc<Self>, control: Arc<BotControl>) {
+        self.spawn_commands(control);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_updates() {
+        let resp = json!({
+            "ok": true,
+            "result": [
+                {"update_id": 7, "message": {"chat": {"id": -100123}, "text": "/pnl"}},
+                {"update_id": 8, "edited_message": {}},
+            ]
+        });
+        assert_eq!(
+            parse_updates(&resp),
+            vec![
+                (7, "-100123".to_string(), "/pnl".to_string()),
+                (8, String::new(), String::new())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_button_presses() {
+        let resp = json!({
+            "ok": true,
+            "result": [
+                {"update_id": 9, "callback_query": {
+                    "id": "cb1",
+                    "data": "half:12",
+                    "message": {"message_id": 55, "chat": {"id": 42}, "text": "✋ Approve copy #12?"},
+                }},
+                {"update_id": 10, "message": {"chat": {"id": 42}, "text": "/pnl"}},
+            ]
+        });
+        let presses = parse_button_presses(&resp);
+        assert_eq!(presses.len(), 1);
+        assert_eq!(presses[0].chat_id, "42");
+        assert_eq!(presses[0].message_id, 55);
+        assert_eq!(parse_approval(&presses[0].data), Some((Verdict::HalfSize, 12)));
+        assert_eq!(parse_approval("approve:x"), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Generic webhooks: every notification is POSTed as JSON to each URL in
+//! `webhook_urls`, for dashboards and home-grown automation.
+//!
+//! With `webhook_secret` set, each request carries
+//! `X-Webhook-Timestamp: <unix seconds>` and
+//! `X-Webhook-Signature: sha256=<hex HMAC-SHA256 of "<timestamp>.<body>">`;
+//! receivers should recompute it and reject stale timestamps. Failed
+//! deliveries (network errors, 429 and 5xx) are retried with exponential
+//! backoff; `X-Webhook-Id` stays the same across retries.
+
+use super::{Notification, Notifier};
+use crate::types::{Config, Severity};
+use anyhow::Result;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+
+/// What is POSTed: the notification's fields plus delivery metadata.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    id: &'a str,
+    timestamp: i64,
+    severity: Severity,
+    #[serde(flatten)]
+    notification: &'a Notification,
+}
+
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    urls: Vec<String>,
+    secret: Option<String>,
+    retries: u32,
+    base_delay: Duration,
+}
+
+impl WebhookNotifier {
+    pub fn new(urls: Vec<String>, secret: Option<String>, retries: u32) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            urls,
+            secret,
+            retries,
+            base_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// `None` unless at least one URL is configured.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.webhook_urls.is_empty() {
+            return None;
+        }
+        let secret = (!config.webhook_secret.is_empty()).then(|| config.webhook_secret.clone());
+        Some(Self::new(config.webhook_urls.clone(), secret, config.webhook_retries))
+    }
+
+    /// Posts `body` to `url`, retrying transient failures.
+    async fn deliver(&self, url: &str, id: &str, body: &str, timestamp: i64) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .header("X-Webhook-Id", id)
+                .header("X-Webhook-Timestamp", timestamp.to_string())
+                .body(body.to_string());
+            if let Some(secret) = &self.secret {
+                request = request.header(
+                    "X-Webhook-Signature",
+                    format!("sha256={}", sign(secret, timestamp, body)),
+                );
+            }
+
+            let error = match request.send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(()),
+                Ok(resp) if resp.status().as_u16() == 429 || resp.status().is_server_error() => {
+                    anyhow::anyhow!("{} returned {}", url, resp.status())
+                }
+                Ok(resp) => anyhow::bail!("{} rejected the webhook: {}", url, resp.status()),
+                Err(e) => anyhow::anyhow!("{} unreachable: {}", url, e),
+            };
+            if attempt >= self.retries {
+                return Err(error);
+            }
+            let delay = (self.base_delay * 2u32.pow(attempt)).min(Duration::from_secs(60));
+            tracing::debug!("Webhook delivery failed ({}), retrying in {:?}", error, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of `"<timestamp>.<body>"`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let id = format!("{:016x}", rand::random::<u64>());
+        let timestamp = chrono::Utc::now().timestamp();
+        let body = serde_json::to_string(&Payload {
+            id: &id,
+            timestamp,
+            severity: notification.severity(),
+            notification,
+        })?;
+
+        let mut failed = Vec::new();
+        for url in &self.urls {
+            if let Err(e) = self.deliver(url, &id, &body, timestamp).await {
+                failed.push(e.to_string());
+            }
+        }
+        if !failed.is_empty() {
+            anyhow::bail!("{}", failed.join("; "));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_matches_reference() {
+        assert_eq!(
+            sign("secret", 1_700_000_000, r#"{"type":"risk_tripped"}"#),
+            "bb5840729c46aa482e5caf95a91783ad1df785ca4767e125746946610b981075"
+        );
+    }
+
+    #[test]
+    fn test_payload_is_flat_json() {
+        let notification = Notification::RiskTripped {
+            reason: "too many errors".to_string(),
+        };
+        let body = serde_json::to_value(Payload {
+            id: "abc",
+            timestamp: 1,
+            severity: notification.severity(),
+            notification: &notification,
+        })
+        .unwrap();
+        assert_eq!(body["type"], "risk_tripped");
+        assert_eq!(body["reason"], "too many errors");
+        assert_eq!(body["id"], "abc");
+        assert_eq!(body["severity"], "critical");
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_retries() {
+        let mut webhook = WebhookNotifier::new(vec!["http://127.0.0.1:9/hook".to_string()], None, 2);
+        webhook.base_delay = Duration::from_millis(1);
+        let notification = Notification::RiskTripped { reason: String::new() };
+        assert!(webhook.notify(&notification).await.is_err());
+    }
+}
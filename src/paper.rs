@@ -0,0 +1,417 @@
+//! Paper trading's account: virtual USDC, settled when markets resolve.
+//!
+//! With `paper_trading` on, copies are sized against a virtual cash balance
+//! that starts at `paper_balance` rather than the wallet's. Simulated fills
+//! move the cash as real ones would, and every `paper_settle_interval` the
+//! markets still held are checked for resolution: a resolved market is
+//! redeemed at its payout (1 a share for the winning outcome, 0 for the
+//! losing one), realizing its PnL and crediting the cash. Each check also
+//! appends the account's equity, cash plus holdings at their marks, to
+//! a curve, so a long paper run shows what resolutions did to it and not
+//! just where prices went. Cash and curve are kept in the journal's state
+//! and survive restarts; redemptions are journaled as sells without an
+//! order. [`report`] reads a run back as a [`BacktestReport`].
+
+use crate::api::PolymarketApi;
+use crate::backtest::{BacktestReport, CopiedFill, Ledger};
+use crate::clock::Clock;
+use crate::marks::{Mark, Marks};
+use crate::portfolio::Portfolio;
+use crate::risk::RiskManager;
+use crate::storage::{FillRecord, Storage, TimeRange};
+use crate::types::{Config, FillModelKind, Market, TradeSide};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where the account is kept in the journal's state.
+pub const PAPER_STATE_KEY: &str = "paper_account";
+
+/// Equity points kept; over two months at the default interval.
+const MAX_CURVE_POINTS: usize = 10_000;
+
+/// The account's worth at one settlement check.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EquityPoint {
+    /// Unix ms
+    pub at: i64,
+    pub cash: f64,
+    /// Cash plus open positions at their marks (their cost when there's
+    /// no price to hand)
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaperState {
+    pub cash: f64,
+    /// Markets redeemed so far
+    pub settled: u64,
+    /// Oldest first
+    pub curve: Vec<EquityPoint>,
+}
+
+/// A held market redeemed at resolution.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Settlement {
+    pub market_id: String,
+    pub shares: f64,
+    pub payout_per_share: f64,
+    pub realized_pnl: f64,
+}
+
+pub struct PaperAccount {
+    api: PolymarketApi,
+    marks: Arc<Marks>,
+    portfolio: Arc<Portfolio>,
+    storage: Option<Arc<dyn Storage>>,
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+    state: Mutex<PaperState>,
+}
+
+impl PaperAccount {
+    pub fn from_config(
+        config: &Config,
+        api: PolymarketApi,
+        marks: Arc<Marks>,
+        portfolio: Arc<Portfolio>,
+        storage: Option<Arc<dyn Storage>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            api,
+            marks,
+            portfolio,
+            storage,
+            clock,
+            interval: config.paper_settle_interval,
+            state: Mutex::new(PaperState {
+                cash: config.paper_balance,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Picks up the account a previous run left in the journal, if any.
+    pub async fn load(&self) -> Result<()> {
+        let Some(storage) = &self.storage else { return Ok(()) };
+        if let Some(json) = storage.load_state(PAPER_STATE_KEY).await? {
+            let state = serde_json::from_str(&json).context("Stored paper account is corrupt")?;
+            *self.state.lock().unwrap() = state;
+        }
+        Ok(())
+    }
+
+    /// Virtual USDC available to trade.
+    pub fn cash(&self) -> f64 {
+        self.state.lock().unwrap().cash
+    }
+
+    pub fn state(&self) -> PaperState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Pays for a simulated buy or banks a simulated sell, fees included.
+    pub async fn apply_fill(&self, fill: &FillRecord) {
+        let notional = fill.shares * fill.price;
+        {
+            let mut state = self.state.lock().unwrap();
+            if fill.side.eq_ignore_ascii_case("SELL") {
+                state.cash += notional - fill.fee;
+            } else {
+                state.cash -= notional + fill.fee;
+            }
+        }
+        self.save().await;
+    }
+
+    /// Redeems every held market that has resolved, then records the
+    /// account's equity.
+    pub async fn settle(&self) -> Vec<Settlement> {
+        let mut settled = Vec::new();
+        for holding in self.portfolio.holdings() {
+            match self.api.get_resolution(&holding.market_id).await {
+                Ok(Some(payout)) => settled.extend(self.redeem(&holding.market_id, payout).await),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to check {} for resolution: {}", holding.market_id, e),
+            }
+        }
+        self.record_equity().await;
+        settled
+    }
+
+    /// Closes `market_id` at `payout_per_share` and credits the proceeds.
+    pub async fn redeem(&self, market_id: &str, payout_per_share: f64) -> Option<Settlement> {
+        let shares = self.portfolio.holding(market_id)?.shares();
+        let now = self.clock.now_ms();
+        let realized_pnl = self.portfolio.apply_redemption(market_id, payout_per_share, now).await;
+        if let Some(storage) = &self.storage {
+            let redemption = FillRecord {
+                id: 0,
+                order_id: 0,
+                market_id: market_id.to_string(),
+                side: TradeSide::SELL.as_str().to_string(),
+                shares,
+                price: payout_per_share,
+                fee: 0.0,
+                filled_at: now,
+            };
+            if let Err(e) = storage.record_fill(&redemption).await {
+                tracing::warn!("Failed to journal the redemption of {}: {}", market_id, e);
+            }
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            state.cash += shares * payout_per_share;
+            state.settled += 1;
+        }
+        self.save().await;
+        Some(Settlement {
+            market_id: market_id.to_string(),
+            shares,
+            payout_per_share,
+            realized_pnl,
+        })
+    }
+
+    /// Appends the account's current equity to the curve.
+    pub async fn record_equity(&self) -> EquityPoint {
+        let mut holdings_value = 0.0;
+        for holding in self.portfolio.holdings() {
+            holdings_value += match self.marks.mark(&holding.market_id).await {
+                Some(mark) => holding.shares() * mark.price,
+                None => holding.cost(),
+            };
+        }
+        let point = {
+            let mut state = self.state.lock().unwrap();
+            let point = EquityPoint {
+                at: self.clock.now_ms(),
+                cash: state.cash,
+                equity: state.cash + holdings_value,
+            };
+            state.curve.push(point);
+            if state.curve.len() > MAX_CURVE_POINTS {
+                let excess = state.curve.len() - MAX_CURVE_POINTS;
+                state.curve.drain(..excess);
+            }
+            point
+        };
+        self.save().await;
+        point
+    }
+
+    /// Settles every `paper_settle_interval`, booking what resolutions
+    /// realize against the daily loss limit.
+    pub fn spawn(self: Arc<Self>, risk: Arc<RiskManager>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                for s in self.settle().await {
+                    tracing::info!(
+                        "🏁 Paper position in {} settled: {:.2} shares at ${:.2}, PnL ${:.2}",
+                        s.market_id,
+                        s.shares,
+                        s.payout_per_share,
+                        s.realized_pnl
+                    );
+                    risk.record_realized_pnl(s.realized_pnl);
+                }
+            }
+        });
+    }
+
+    /// Account writes never block trading; failures are only warned about.
+    async fn save(&self) {
+        let Some(storage) = &self.storage else { return };
+        let json = match serde_json::to_string(&*self.state.lock().unwrap()) {
+            Ok(json) => json,
+            Err(e) => return tracing::warn!("Failed to encode the paper account: {}", e),
+        };
+        if let Err(e) = storage.save_state(PAPER_STATE_KEY, &json, self.clock.now_ms()).await {
+            tracing::warn!("Failed to save the paper account: {}", e);
+        }
+    }
+}
+
+/// A paper run over `range`, from the journal: its leader trades and
+/// decisions, the fills of the orders copying them and the redemptions
+/// settling them, and the account's equity curve. Fills are attributed to
+/// the leader whose trade the order copied (manual orders are left out),
+/// and what's still open is marked at `marks`; `markets` give categories.
+pub async fn report(
+    storage: &dyn Storage,
+    config: &Config,
+    range: TimeRange,
+    markets: &HashMap<String, Market>,
+    marks: &HashMap<String, Mark>,
+) -> Result<BacktestReport> {
+    let trades = storage.leader_trades(range).await?;
+    let decisions = storage.decisions(range).await?;
+    let orders = storage.orders(range).await?;
+    let fills = storage.fills(range).await?;
+    let state: PaperState = match storage.load_state(PAPER_STATE_KEY).await? {
+        Some(json) => serde_json::from_str(&json).context("Stored paper account is corrupt")?,
+        None => PaperState::default(),
+    };
+
+    let curve: Vec<EquityPoint> = state
+        .curve
+        .into_iter()
+        .filter(|p| p.at >= range.from_ms && p.at <= range.to_ms)
+        .collect();
+    let mut report = BacktestReport {
+        kind: "Paper run",
+        from: trades.first().map(|t| t.trade.timestamp),
+        to: trades.last().map(|t| t.trade.timestamp),
+        fill_model: config.fill_model.as_str(),
+        latency: match config.fill_model {
+            FillModelKind::Latency => config.fill_latency,
+            _ => Duration::ZERO,
+        },
+        trades: trades.len(),
+        starting_balance: curve.first().map_or(config.paper_balance, |p| p.equity),
+        equity_curve: curve,
+        ..Default::default()
+    };
+    let mut ledger = Ledger::default();
+    let leader_price: HashMap<i64, f64> = trades.iter().map(|t| (t.id, t.trade.price)).collect();
+    for trade in &trades {
+        ledger.leader(&trade.trade.wallet).trades += 1;
+    }
+    for decision in decisions.iter().filter(|d| !d.copied) {
+        if let Some(reason) = decision.reason {
+            *report.skipped.entry(reason.as_str()).or_default() += 1;
+        }
+    }
+    let decision_of_order: HashMap<i64, i64> = orders.iter().filter_map(|o| Some((o.id, o.decision_id?))).collect();
+    let decisions: HashMap<i64, _> = decisions.iter().map(|d| (d.id, d)).collect();
+
+    for fill in &fills {
+        let Some(side) = TradeSide::parse(&fill.side) else {
+            continue;
+        };
+        if fill.order_id == 0 {
+            if side == TradeSide::SELL {
+                ledger.settle(&fill.market_id, fill.price);
+            }
+            continue;
+        }
+        let Some(decision) = decision_of_order.get(&fill.order_id).and_then(|id| decisions.get(id)) else {
+            continue;
+        };
+        ledger.fill(
+            &decision.wallet,
+            &fill.market_id,
+            &side,
+            fill.shares,
+            fill.price,
+            fill.fee,
+            fill.filled_at,
+            config.cost_basis,
+        );
+        report.copied += 1;
+        report.volume_usd += fill.shares * fill.price;
+        report.fees += fill.fee;
+        report.fills.push(CopiedFill {
+            at: fill.filled_at,
+            wallet: decision.wallet.clone(),
+            market_id: fill.market_id.clone(),
+            side,
+            leader_price: decision
+                .leader_trade_id
+                .and_then(|id| leader_price.get(&id).copied())
+                .unwrap_or(fill.price),
+            price: fill.price,
+            shares: fill.shares,
+        });
+    }
+
+    let marks: HashMap<String, f64> = marks.iter().map(|(id, m)| (id.clone(), m.price)).collect();
+    ledger.finish(&mut report, &marks, markets);
+    report.final_equity = report.starting_balance + report.realized_pnl + report.unrealized_pnl;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimClock;
+    use crate::markets::MarketCache;
+    use crate::storage::sqlite::SqliteStore;
+    use crate::types::CostBasis;
+
+    fn fill(market_id: &str, side: &str, shares: f64, price: f64) -> FillRecord {
+        FillRecord {
+            id: 0,
+            order_id: 0,
+            market_id: market_id.to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee: 0.0,
+            filled_at: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paper_account_settles_and_survives_restart() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        // Nothing listens here, so marks fall back to cost
+        let api = PolymarketApi::new("http://127.0.0.1:9".to_string());
+        let markets = Arc::new(MarketCache::new(
+            api.clone(),
+            None,
+            Duration::from_secs(60),
+            Duration::from_secs(600),
+        ));
+        let config = Config {
+            paper_balance: 100.0,
+            ..Default::default()
+        };
+        let clock = Arc::new(SimClock::at(1_700_000_000_000));
+        let marks = Arc::new(Marks::new(markets, config.mark_max_age, clock.clone()));
+        let account = |portfolio: &Arc<Portfolio>| {
+            PaperAccount::from_config(
+                &config,
+                api.clone(),
+                Arc::clone(&marks),
+                Arc::clone(portfolio),
+                Some(Arc::clone(&storage)),
+                clock.clone(),
+            )
+        };
+
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Fifo, Some(Arc::clone(&storage))));
+        let paper = account(&portfolio);
+        for f in [fill("won", "BUY", 40.0, 0.50), fill("lost", "BUY", 20.0, 0.40)] {
+            portfolio.apply_fill(&f).await;
+            paper.apply_fill(&f).await;
+        }
+        assert!((paper.cash() - 72.0).abs() < 1e-9);
+
+        let won = paper.redeem("won", 1.0).await.unwrap();
+        assert!((won.realized_pnl - 20.0).abs() < 1e-9);
+        let lost = paper.redeem("lost", 0.0).await.unwrap();
+        assert!((lost.realized_pnl + 8.0).abs() < 1e-9);
+        assert!(paper.redeem("lost", 0.0).await.is_none());
+        assert!(portfolio.holdings().is_empty());
+        clock.advance(Duration::from_secs(600));
+        let point = paper.record_equity().await;
+        assert!((point.equity - 112.0).abs() < 1e-9);
+        assert_eq!(point.at, 1_700_000_600_000);
+
+        // A restart picks the account up where it was
+        let reloaded = account(&portfolio);
+        assert_eq!(reloaded.cash(), 100.0);
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.state(), paper.state());
+        assert_eq!(reloaded.state().settled, 2);
+    }
+}
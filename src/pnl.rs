@@ -0,0 +1,475 @@
+//! Realized and unrealized PnL from the journal.
+//!
+//! Every fill is booked against the leader whose trade its order copied
+//! (orders placed by hand count as [`MANUAL`]), one book per leader and
+//! market, costed with `cost_basis`. Sells realize against the book's lots,
+//! fees come off realized PnL on both sides, and redemptions (journaled as
+//! sells without an order) close every book in the market at the payout.
+//! What's still open is marked by [`Marks`]; positions without a mark stay
+//! at cost and are counted as unmarked, those with a stale one as stale.
+//!
+//! A running bot keeps the latest [`PnlReport`] in a [`PnlTracker`],
+//! refreshed every `pnl_interval`, for `/status/pnl`, `/metrics` and the
+//! digest; `mybot pnl` computes one on demand.
+
+use crate::gauges::Gauges;
+use crate::leaders::{self, LeaderPerformance};
+use crate::marks::{Mark, Marks};
+use crate::portfolio::{self, Lot};
+use crate::storage::{now_ms, DecisionRecord, FillRecord, OrderRecord, Storage, TimeRange};
+use crate::types::{CostBasis, Market};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Who fills of orders placed by hand are booked to.
+pub const MANUAL: &str = "manual";
+
+/// One leader's copies in one market.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PositionPnl {
+    pub leader: String,
+    pub market_id: String,
+    pub category: String,
+    /// Shares still held
+    pub shares: f64,
+    /// What the shares still held cost
+    pub open_cost: f64,
+    /// `None` when no price was to hand
+    pub mark: Option<Mark>,
+    /// Net of fees
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub fees: f64,
+}
+
+impl PositionPnl {
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+/// Positions summed by leader or by category.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PnlTotals {
+    pub name: String,
+    pub positions: usize,
+    pub open_cost: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub fees: f64,
+}
+
+impl PnlTotals {
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+
+    fn add(&mut self, position: &PositionPnl) {
+        self.positions += 1;
+        self.open_cost += position.open_cost;
+        self.realized_pnl += position.realized_pnl;
+        self.unrealized_pnl += position.unrealized_pnl;
+        self.fees += position.fees;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PnlReport {
+    /// Unix ms
+    pub at: i64,
+    pub open_cost: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub fees: f64,
+    /// Open positions left at cost for want of a mark
+    pub unmarked: usize,
+    /// Open positions marked at a stale price
+    pub stale_marks: usize,
+    /// Worst first
+    pub positions: Vec<PositionPnl>,
+    /// Best first
+    pub leaders: Vec<PnlTotals>,
+    /// Best first; markets without a category count as "other"
+    pub categories: Vec<PnlTotals>,
+}
+
+impl PnlReport {
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+impl std::fmt::Display for PnlReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "PnL:           ${:+.2} (realized ${:+.2}, unrealized ${:+.2})",
+            self.pnl(),
+            self.realized_pnl,
+            self.unrealized_pnl
+        )?;
+        writeln!(f, "Fees:          ${:.2}", self.fees)?;
+        writeln!(f, "Open cost:     ${:.2}", self.open_cost)?;
+        if self.unmarked > 0 {
+            writeln!(f, "⚠️  {} open positions left at cost without a mark", self.unmarked)?;
+        }
+        if self.stale_marks > 0 {
+            writeln!(f, "⚠️  {} open positions marked at a stale price", self.stale_marks)?;
+        }
+        for (title, rows) in [("leader", &self.leaders), ("category", &self.categories)] {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{:<44} {:>9} {:>12} {:>12} {:>12} {:>12}",
+                title, "positions", "open_cost", "realized", "unrealized", "pnl"
+            )?;
+            for t in rows {
+                writeln!(
+                    f,
+                    "{:<44} {:>9} {:>12.2} {:>+12.2} {:>+12.2} {:>+12.2}",
+                    t.name,
+                    t.positions,
+                    t.open_cost,
+                    t.realized_pnl,
+                    t.unrealized_pnl,
+                    t.pnl()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Book {
+    lots: Vec<Lot>,
+    realized_pnl: f64,
+    fees: f64,
+}
+
+/// PnL of `fills` as of `at`, attributed through `orders` and `decisions`
+/// and marked at `marks`; `markets` give their categories.
+pub fn compute(
+    decisions: &[DecisionRecord],
+    orders: &[OrderRecord],
+    fills: &[FillRecord],
+    method: CostBasis,
+    markets: &HashMap<String, Market>,
+    marks: &HashMap<String, Mark>,
+    at: i64,
+) -> PnlReport {
+    let leader_of_decision: HashMap<i64, &str> = decisions.iter().map(|d| (d.id, d.wallet.as_str())).collect();
+    let leader_of_order: HashMap<i64, &str> = orders
+        .iter()
+        .map(|o| {
+            let leader = o.decision_id.and_then(|id| leader_of_decision.get(&id).copied());
+            (o.id, leader.unwrap_or(MANUAL))
+        })
+        .collect();
+
+    let mut books: BTreeMap<(&str, &str), Book> = BTreeMap::new();
+    for fill in fills {
+        let sell = fill.side.eq_ignore_ascii_case("SELL");
+        if fill.order_id == 0 {
+            if sell {
+                for ((_, market_id), book) in books.iter_mut() {
+                    if *market_id == fill.market_id {
+                        let shares: f64 = book.lots.iter().map(|l| l.shares).sum();
+                        book.realized_pnl += portfolio::sell(&mut book.lots, shares, fill.price);
+                    }
+                }
+            }
+            continue;
+        }
+        let leader = leader_of_order.get(&fill.order_id).copied().unwrap_or(MANUAL);
+        let book = books.entry((leader, fill.market_id.as_str())).or_default();
+        if sell {
+            book.realized_pnl += portfolio::sell(&mut book.lots, fill.shares, fill.price);
+        } else {
+            portfolio::buy(&mut book.lots, method, fill.shares, fill.price, fill.filled_at);
+        }
+        book.realized_pnl -= fill.fee;
+        book.fees += fill.fee;
+    }
+
+    let mut report = PnlReport {
+        at,
+        ..Default::default()
+    };
+    let mut leaders: BTreeMap<&str, PnlTotals> = BTreeMap::new();
+    let mut categories: BTreeMap<String, PnlTotals> = BTreeMap::new();
+    for ((leader, market_id), book) in books {
+        let shares: f64 = book.lots.iter().map(|l| l.shares).sum();
+        let open_cost: f64 = book.lots.iter().map(|l| l.shares * l.price).sum();
+        let market = markets.get(market_id);
+        let mark = marks.get(market_id).copied();
+        let held = shares > 1e-9;
+        match mark {
+            None if held => report.unmarked += 1,
+            Some(mark) if held && mark.stale => report.stale_marks += 1,
+            _ => {}
+        }
+        let position = PositionPnl {
+            leader: leader.to_string(),
+            market_id: market_id.to_string(),
+            category: market
+                .map(|m| m.category.as_str())
+                .filter(|c| !c.is_empty())
+                .unwrap_or("other")
+                .to_string(),
+            shares,
+            open_cost,
+            mark,
+            realized_pnl: book.realized_pnl,
+            unrealized_pnl: mark
+                .filter(|_| held)
+                .map_or(0.0, |mark| shares * mark.price - open_cost),
+            fees: book.fees,
+        };
+        report.open_cost += position.open_cost;
+        report.realized_pnl += position.realized_pnl;
+        report.unrealized_pnl += position.unrealized_pnl;
+        report.fees += position.fees;
+        leaders
+            .entry(leader)
+            .or_insert_with(|| PnlTotals {
+                name: leader.to_string(),
+                ..Default::default()
+            })
+            .add(&position);
+        categories
+            .entry(position.category.clone())
+            .or_insert_with(|| PnlTotals {
+                name: position.category.clone(),
+                ..Default::default()
+            })
+            .add(&position);
+        report.positions.push(position);
+    }
+    report.positions.sort_by(|a, b| a.pnl().total_cmp(&b.pnl()));
+    report.leaders = leaders.into_values().collect();
+    report.leaders.sort_by(|a, b| b.pnl().total_cmp(&a.pnl()));
+    report.categories = categories.into_values().collect();
+    report.categories.sort_by(|a, b| b.pnl().total_cmp(&a.pnl()));
+    report
+}
+
+/// PnL of the whole journal, marked by `marks`, with every market traded
+/// looked up for its category.
+pub async fn from_journal(storage: &dyn Storage, method: CostBasis, marks: &Marks) -> Result<PnlReport> {
+    let decisions = storage.decisions(TimeRange::all()).await?;
+    let orders = storage.orders(TimeRange::all()).await?;
+    let fills = storage.fills(TimeRange::all()).await?;
+    let mut traded = HashMap::new();
+    for fill in &fills {
+        if traded.contains_key(&fill.market_id) {
+            continue;
+        }
+        match marks.markets().get(&fill.market_id).await {
+            Ok(market) => {
+                traded.insert(fill.market_id.clone(), market);
+            }
+            Err(e) => tracing::warn!("Failed to fetch market {}: {}", fill.market_id, e),
+        }
+    }
+    let traded_ids: Vec<&str> = fills.iter().map(|f| f.market_id.as_str()).collect();
+    let marked = marks.marks(&traded_ids).await;
+    Ok(compute(&decisions, &orders, &fills, method, &traded, &marked, now_ms()))
+}
+
+/// The latest PnL of a running bot.
+pub struct PnlTracker {
+    storage: Arc<dyn Storage>,
+    marks: Arc<Marks>,
+    method: CostBasis,
+    interval: Duration,
+    latest: Mutex<Option<PnlReport>>,
+}
+
+impl PnlTracker {
+    pub fn new(storage: Arc<dyn Storage>, marks: Arc<Marks>, method: CostBasis, interval: Duration) -> Self {
+        Self {
+            storage,
+            marks,
+            method,
+            interval,
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// The report from the last refresh, if there has been one.
+    pub fn latest(&self) -> Option<PnlReport> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Recomputes the report from the journal and current marks.
+    pub async fn refresh(&self) -> Result<PnlReport> {
+        let report = from_journal(self.storage.as_ref(), self.method, &self.marks).await?;
+        *self.latest.lock().unwrap() = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Per-leader attribution over the whole journal, at current marks.
+    pub async fn leaders(&self) -> Result<Vec<LeaderPerformance>> {
+        leaders::from_journal(self.storage.as_ref(), TimeRange::all(), self.method, &self.marks).await
+    }
+
+    /// Overall PnL gauges for `/metrics`, from the last refresh.
+    pub fn register_gauges(self: &Arc<Self>, gauges: &Gauges) {
+        let read = |field: fn(&PnlReport) -> f64| {
+            let tracker = Arc::clone(self);
+            move || tracker.latest.lock().unwrap().as_ref().map(field)
+        };
+        gauges.register(
+            "polymarket_bot_realized_pnl_usd",
+            "PnL realized over the journal, net of fees.",
+            read(|r| r.realized_pnl),
+        );
+        gauges.register(
+            "polymarket_bot_unrealized_pnl_usd",
+            "PnL of open positions at current marks.",
+            read(|r| r.unrealized_pnl),
+        );
+        gauges.register(
+            "polymarket_bot_fees_usd",
+            "Fees paid over the journal.",
+            read(|r| r.fees),
+        );
+        gauges.register(
+            "polymarket_bot_open_cost_usd",
+            "What the open positions cost.",
+            read(|r| r.open_cost),
+        );
+    }
+
+    /// Refreshes every `pnl_interval`; a zero interval disables it.
+    pub fn spawn(self: Arc<Self>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.refresh().await {
+                    tracing::warn!("Failed to refresh PnL: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marks::MarkSource;
+
+    #[test]
+    fn test_pnl_by_position_leader_and_category() {
+        let decision = |id, wallet: &str| DecisionRecord {
+            id,
+            leader_trade_id: None,
+            wallet: wallet.to_string(),
+            market_id: String::new(),
+            side: "BUY".to_string(),
+            copied: true,
+            reason: None,
+            detail: None,
+            size_usd: None,
+            decided_at: 0,
+            arrival_mid: None,
+            arrival_book: None,
+        };
+        let order = |id, decision_id| OrderRecord {
+            id,
+            decision_id,
+            exchange_order_id: None,
+            market_id: String::new(),
+            side: String::new(),
+            shares: 0.0,
+            limit_price: None,
+            order_type: String::new(),
+            status: "filled".to_string(),
+            error: None,
+            submitted_at: 0,
+            client_order_id: None,
+        };
+        let fill = |order_id, market_id: &str, side: &str, shares, price, fee| FillRecord {
+            id: 0,
+            order_id,
+            market_id: market_id.to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee,
+            filled_at: 0,
+        };
+        let decisions = [decision(1, "0xa"), decision(2, "0xa"), decision(3, "0xb")];
+        let orders = [
+            order(10, Some(1)),
+            order(11, Some(2)),
+            order(12, Some(3)),
+            order(13, None),
+        ];
+        let fills = [
+            // 0xa buys 10 in m1 and sells 4 higher
+            fill(10, "m1", "BUY", 10.0, 0.40, 0.10),
+            fill(11, "m1", "SELL", 4.0, 0.50, 0.05),
+            // 0xb and a manual order hold m2 until it resolves
+            fill(12, "m2", "BUY", 20.0, 0.30, 0.0),
+            fill(13, "m2", "BUY", 5.0, 0.20, 0.0),
+            fill(0, "m2", "SELL", 25.0, 1.0, 0.0),
+            // A market that can't be marked
+            fill(12, "m3", "BUY", 10.0, 0.50, 0.0),
+        ];
+        let markets = HashMap::from([(
+            "m1".to_string(),
+            Market {
+                id: "m1".to_string(),
+                event_id: "e1".to_string(),
+                yes_price: 0.60,
+                no_price: 0.40,
+                category: "Sports".to_string(),
+                ..Default::default()
+            },
+        )]);
+
+        let mark = |price, stale| Mark {
+            price,
+            source: MarkSource::Mid,
+            at: 0,
+            stale,
+        };
+        let marks = HashMap::from([
+            ("m1".to_string(), mark(0.60, false)),
+            ("m2".to_string(), mark(0.90, true)),
+        ]);
+
+        let report = compute(&decisions, &orders, &fills, CostBasis::Fifo, &markets, &marks, 7);
+        assert_eq!(report.at, 7);
+        assert_eq!(report.positions.len(), 4);
+        let m1 = report.positions.iter().find(|p| p.market_id == "m1").unwrap();
+        // 4 * 0.10 gained, less 0.15 in fees; 6 held from 0.40 to 0.60
+        assert!((m1.realized_pnl - 0.25).abs() < 1e-9);
+        assert!((m1.unrealized_pnl - 1.2).abs() < 1e-9);
+        assert_eq!((m1.category.as_str(), m1.mark), ("Sports", Some(mark(0.60, false))));
+        let manual = report.positions.iter().find(|p| p.leader == MANUAL).unwrap();
+        assert!((manual.realized_pnl - 4.0).abs() < 1e-9);
+        assert_eq!(manual.shares, 0.0);
+        // m2 is closed, so its stale mark doesn't count
+        assert_eq!((report.unmarked, report.stale_marks), (1, 0));
+
+        let b = report.leaders.iter().find(|l| l.name == "0xb").unwrap();
+        assert_eq!(b.positions, 2);
+        assert!((b.realized_pnl - 14.0).abs() < 1e-9);
+        assert!((b.open_cost - 5.0).abs() < 1e-9);
+        assert_eq!(report.leaders[0].name, "0xb");
+        assert_eq!(report.categories.len(), 2);
+        assert!((report.fees - 0.15).abs() < 1e-9);
+        assert!((report.realized_pnl - 18.25).abs() < 1e-9);
+        assert!((report.pnl() - 19.45).abs() < 1e-9);
+    }
+}
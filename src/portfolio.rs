@@ -0,0 +1,400 @@
+//! Positions with cost-basis accounting.
+//!
+//! Every buy opens a lot and sells consume lots from the front, realizing PnL
+//! against their cost. With [`CostBasis::Average`] a market's lots are kept
+//! merged into one at the blended price, so the same consumption yields
+//! average-cost PnL. Lots are persisted alongside the position summary, so
+//! FIFO order survives restarts.
+
+use crate::storage::{FillRecord, LotRecord, PositionRecord, Storage};
+use crate::types::CostBasis;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Shares bought in one go (or, for average costing, all open shares).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lot {
+    pub shares: f64,
+    pub price: f64,
+    pub opened_at: i64,
+}
+
+/// Open lots in one market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holding {
+    pub market_id: String,
+    pub lots: Vec<Lot>,
+}
+
+impl Holding {
+    pub fn shares(&self) -> f64 {
+        self.lots.iter().map(|l| l.shares).sum()
+    }
+
+    /// What the open shares cost.
+    pub fn cost(&self) -> f64 {
+        self.lots.iter().map(|l| l.shares * l.price).sum()
+    }
+
+    pub fn avg_price(&self) -> f64 {
+        let shares = self.shares();
+        if shares > 0.0 {
+            self.cost() / shares
+        } else {
+            0.0
+        }
+    }
+
+    pub fn unrealized_pnl(&self, mark_price: f64) -> f64 {
+        self.shares() * mark_price - self.cost()
+    }
+}
+
+/// Adds a purchase to `lots`.
+pub fn buy(lots: &mut Vec<Lot>, method: CostBasis, shares: f64, price: f64, at: i64) {
+    match (method, lots.first_mut()) {
+        (CostBasis::Average, Some(lot)) => {
+            let total = lot.shares + shares;
+            if total > 0.0 {
+                lot.price = (lot.shares * lot.price + shares * price) / total;
+            }
+            lot.shares = total;
+        }
+        _ => lots.push(Lot {
+            shares,
+            price,
+            opened_at: at,
+        }),
+    }
+}
+
+/// Removes `shares` from the oldest lots and returns the PnL of selling them
+/// at `price`. Selling more than is held only realizes the held shares.
+pub fn sell(lots: &mut Vec<Lot>, shares: f64, price: f64) -> f64 {
+    let mut remaining = shares;
+    let mut realized = 0.0;
+    while remaining > 0.0 {
+        let Some(lot) = lots.first_mut() else { break };
+        let take = remaining.min(lot.shares);
+        realized += take * (price - lot.price);
+        lot.shares -= take;
+        remaining -= take;
+        if lot.shares <= 1e-9 {
+            lots.remove(0);
+        }
+    }
+    realized
+}
+
+/// Trims `lots` oldest first, or tops them up at `price`, until they hold
+/// `shares`.
+fn resize(lots: &mut Vec<Lot>, method: CostBasis, shares: f64, price: f64, at: i64) {
+    let held: f64 = lots.iter().map(|l| l.shares).sum();
+    if held > shares + 1e-9 {
+        sell(lots, held - shares, 0.0);
+    } else if held < shares - 1e-9 {
+        lots.push(Lot {
+            shares: shares - held,
+            price,
+            opened_at: at,
+        });
+    }
+    if method == CostBasis::Average {
+        average(lots);
+    }
+}
+
+/// Merges lots into one at their blended price.
+fn average(lots: &mut Vec<Lot>) {
+    if lots.len() < 2 {
+        return;
+    }
+    let holding = Holding {
+        market_id: String::new(),
+        lots: std::mem::take(lots),
+    };
+    lots.push(Lot {
+        shares: holding.shares(),
+        price: holding.avg_price(),
+        opened_at: holding.lots[0].opened_at,
+    });
+}
+
+#[derive(Default)]
+struct Books {
+    lots: HashMap<String, Vec<Lot>>,
+    realized_pnl: f64,
+}
+
+/// The bot's positions, updated from fills and redemptions.
+///
+/// Assumes a single writer: instances sharing a Postgres journal should not
+/// trade the same markets.
+pub struct Portfolio {
+    method: CostBasis,
+    storage: Option<Arc<dyn Storage>>,
+    books: Mutex<Books>,
+}
+
+impl Portfolio {
+    pub fn new(method: CostBasis, storage: Option<Arc<dyn Storage>>) -> Self {
+        Self {
+            method,
+            storage,
+            books: Mutex::new(Books::default()),
+        }
+    }
+
+    pub fn cost_basis(&self) -> CostBasis {
+        self.method
+    }
+
+    /// (Re)loads lots from storage. Positions are authoritative (recovery
+    /// corrects them against the exchange), so lots are trimmed or topped up
+    /// at the position's average price until they match.
+    pub async fn load(&self) -> Result<()> {
+        let Some(storage) = &self.storage else { return Ok(()) };
+        let positions = storage.positions().await?;
+        let mut stored: HashMap<String, Vec<Lot>> = HashMap::new();
+        for lot in storage.lots().await? {
+            stored.entry(lot.market_id).or_default().push(Lot {
+                shares: lot.shares,
+                price: lot.price,
+                opened_at: lot.opened_at,
+            });
+        }
+
+        let mut books = HashMap::new();
+        for position in positions {
+            let original = stored.remove(&position.market_id).unwrap_or_default();
+            let mut lots = original.clone();
+            resize(&mut lots, self.method, position.shares, position.avg_price, position.updated_at);
+            if lots != original {
+                let holding = Holding {
+                    market_id: position.market_id.clone(),
+                    lots: lots.clone(),
+                };
+                storage
+                    .save_holding(&position_record(&holding, position.updated_at), &lot_records(&holding))
+                    .await?;
+            }
+            books.insert(position.market_id, lots);
+        }
+
+        // Lots without a position were closed behind our back
+        for market_id in stored.into_keys() {
+            let closed = PositionRecord {
+                market_id,
+                shares: 0.0,
+                avg_price: 0.0,
+                updated_at: crate::storage::now_ms(),
+            };
+            storage.save_holding(&closed, &[]).await?;
+        }
+
+        self.books.lock().unwrap().lots = books;
+        Ok(())
+    }
+
+    /// Applies a fill and returns the PnL it realized, net of fees.
+    pub async fn apply_fill(&self, fill: &FillRecord) -> f64 {
+        let (realized, holding) = {
+            let mut books = self.books.lock().unwrap();
+            let lots = books.lots.entry(fill.market_id.clone()).or_default();
+            let realized = if fill.side.eq_ignore_ascii_case("SELL") {
+                sell(lots, fill.shares, fill.price)
+            } else {
+                buy(lots, self.method, fill.shares, fill.price, fill.filled_at);
+                0.0
+            } - fill.fee;
+            let holding = Holding {
+                market_id: fill.market_id.clone(),
+                lots: lots.clone(),
+            };
+            if holding.lots.is_empty() {
+                books.lots.remove(&fill.market_id);
+            }
+            books.realized_pnl += realized;
+            (realized, holding)
+        };
+        self.persist(&holding, fill.filled_at).await;
+        realized
+    }
+
+    /// Closes a resolved market at `payout_per_share` (1.0 for the winning
+    /// outcome, 0.0 for the losing one) and returns the realized PnL.
+    pub async fn apply_redemption(&self, market_id: &str, payout_per_share: f64, at_ms: i64) -> f64 {
+        let realized = {
+            let mut books = self.books.lock().unwrap();
+            let Some(mut lots) = books.lots.remove(market_id) else {
+                return 0.0;
+            };
+            let shares: f64 = lots.iter().map(|l| l.shares).sum();
+            let realized = sell(&mut lots, shares, payout_per_share);
+            books.realized_pnl += realized;
+            realized
+        };
+        let closed = Holding {
+            market_id: market_id.to_string(),
+            lots: Vec::new(),
+        };
+        self.persist(&closed, at_ms).await;
+        realized
+    }
+
+    /// Sets the shares held in `market_id` without realizing anything, for
+    /// positions that changed outside the bot: lots are trimmed oldest
+    /// first, or topped up at `price`.
+    pub async fn correct(&self, market_id: &str, shares: f64, price: f64, at_ms: i64) {
+        let holding = {
+            let mut books = self.books.lock().unwrap();
+            let lots = books.lots.entry(market_id.to_string()).or_default();
+            resize(lots, self.method, shares, price, at_ms);
+            let holding = Holding {
+                market_id: market_id.to_string(),
+                lots: lots.clone(),
+            };
+            if holding.lots.is_empty() {
+                books.lots.remove(market_id);
+            }
+            holding
+        };
+        self.persist(&holding, at_ms).await;
+    }
+
+    pub fn holding(&self, market_id: &str) -> Option<Holding> {
+        let books = self.books.lock().unwrap();
+        books.lots.get(market_id).map(|lots| Holding {
+            market_id: market_id.to_string(),
+            lots: lots.clone(),
+        })
+    }
+
+    pub fn holdings(&self) -> Vec<Holding> {
+        let books = self.books.lock().unwrap();
+        let mut holdings: Vec<Holding> = books
+            .lots
+            .iter()
+            .map(|(market_id, lots)| Holding {
+                market_id: market_id.clone(),
+                lots: lots.clone(),
+            })
+            .collect();
+        holdings.sort_by(|a, b| a.market_id.cmp(&b.market_id));
+        holdings
+    }
+
+    /// Total cost of all open positions.
+    pub fn exposure(&self) -> f64 {
+        self.holdings().iter().map(Holding::cost).sum()
+    }
+
+    /// PnL realized since the portfolio was created.
+    pub fn realized_pnl(&self) -> f64 {
+        self.books.lock().unwrap().realized_pnl
+    }
+
+    /// Position writes never block trading; failures are only warned about.
+    async fn persist(&self, holding: &Holding, at_ms: i64) {
+        let Some(storage) = &self.storage else { return };
+        if let Err(e) = storage
+            .save_holding(&position_record(holding, at_ms), &lot_records(holding))
+            .await
+        {
+            tracing::warn!("Failed to save position in {}: {}", holding.market_id, e);
+        }
+    }
+}
+
+fn position_record(holding: &Holding, updated_at: i64) -> PositionRecord {
+    PositionRecord {
+        market_id: holding.market_id.clone(),
+        shares: holding.shares(),
+        avg_price: holding.avg_price(),
+        updated_at,
+    }
+}
+
+fn lot_records(holding: &Holding) -> Vec<LotRecord> {
+    holding
+        .lots
+        .iter()
+        .map(|l| LotRecord {
+            market_id: holding.market_id.clone(),
+            shares: l.shares,
+            price: l.price,
+            opened_at: l.opened_at,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+
+    fn fill(side: &str, shares: f64, price: f64, filled_at: i64) -> FillRecord {
+        FillRecord {
+            id: 0,
+            order_id: 0,
+            market_id: "market1".to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee: 0.0,
+            filled_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fifo_and_average_realize_differently() {
+        let fills = [
+            fill("BUY", 10.0, 0.40, 1),
+            fill("BUY", 10.0, 0.60, 2),
+            fill("SELL", 10.0, 0.70, 3),
+        ];
+
+        let fifo = Portfolio::new(CostBasis::Fifo, None);
+        let average = Portfolio::new(CostBasis::Average, None);
+        for f in &fills {
+            fifo.apply_fill(f).await;
+            average.apply_fill(f).await;
+        }
+
+        assert!((fifo.realized_pnl() - 3.0).abs() < 1e-9);
+        assert!((average.realized_pnl() - 2.0).abs() < 1e-9);
+        assert!((fifo.holding("market1").unwrap().avg_price() - 0.60).abs() < 1e-9);
+        assert!((average.holding("market1").unwrap().avg_price() - 0.50).abs() < 1e-9);
+
+        assert!((fifo.apply_redemption("market1", 1.0, 4).await - 4.0).abs() < 1e-9);
+        assert!(fifo.holding("market1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lots_survive_reload_and_follow_positions() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let portfolio = Portfolio::new(CostBasis::Fifo, Some(Arc::clone(&storage)));
+        portfolio.apply_fill(&fill("BUY", 10.0, 0.40, 1)).await;
+        portfolio.apply_fill(&fill("BUY", 10.0, 0.60, 2)).await;
+
+        // Recovery found 5 more shares on the exchange
+        storage
+            .set_position(&PositionRecord {
+                market_id: "market1".to_string(),
+                shares: 25.0,
+                avg_price: 0.50,
+                updated_at: 3,
+            })
+            .await
+            .unwrap();
+
+        let reloaded = Portfolio::new(CostBasis::Fifo, Some(Arc::clone(&storage)));
+        reloaded.load().await.unwrap();
+        let holding = reloaded.holding("market1").unwrap();
+        assert_eq!(holding.lots.len(), 3);
+        assert_eq!(holding.lots[0].price, 0.40);
+        assert_eq!(holding.shares(), 25.0);
+        assert_eq!(storage.lots().await.unwrap().len(), 3);
+    }
+}
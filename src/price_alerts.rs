@@ -0,0 +1,406 @@
+//! Price alerts on any market, independent of copying.
+//!
+//! Each entry of `price_alerts` names a token id and a condition:
+//!
+//! - `<token> > 0.70` / `<token> < 0.30`: the price crosses the level
+//! - `<token> spread > 0.10`: best ask less best bid goes over it
+//! - `<token> volume > 3x`: shares traded in the last five minutes go over
+//!   three times the hour's five-minute average
+//!
+//! [`PriceAlerts`] has the [`BookStream`] stream the alerted tokens and sends
+//! a [`Notification::PriceAlert`] when a condition starts to hold. The price
+//! is the last trade or the book mid, whichever came last. A crossing alert
+//! already past its level when first seen waits for the price to cross;
+//! each alert fires again only after its condition has cleared.
+
+use crate::book::{BookEvent, BookStream};
+use crate::clock::Clock;
+use crate::notify::{Notification, Notifications};
+use crate::types::Config;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Recent volume a spike is measured over.
+const VOLUME_WINDOW_MS: i64 = 5 * 60 * 1000;
+
+/// Volume history the recent window is compared to; spikes are only
+/// looked for once a token has been watched this long.
+const VOLUME_BASELINE_MS: i64 = 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "threshold")]
+pub enum Condition {
+    Above(f64),
+    Below(f64),
+    SpreadAbove(f64),
+    /// Multiple of the average five-minute volume
+    VolumeSpike(f64),
+}
+
+impl Condition {
+    /// Whether the alert is about a level being crossed rather than reached.
+    fn is_crossing(&self) -> bool {
+        matches!(self, Condition::Above(_) | Condition::Below(_))
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Above(level) => write!(f, "> {}", level),
+            Condition::Below(level) => write!(f, "< {}", level),
+            Condition::SpreadAbove(level) => write!(f, "spread > {}", level),
+            Condition::VolumeSpike(factor) => write!(f, "volume > {}x", factor),
+        }
+    }
+}
+
+/// One alert: a token and a condition on it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertRule {
+    pub token_id: String,
+    pub condition: Condition,
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.token_id, self.condition)
+    }
+}
+
+fn threshold(raw: &str, rule: &str) -> Result<f64> {
+    let value: f64 = raw
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid threshold '{}' in price alert '{}'", raw.trim(), rule))?;
+    if value <= 0.0 {
+        bail!("Price alert '{}' needs a positive threshold", rule);
+    }
+    Ok(value)
+}
+
+impl FromStr for AlertRule {
+    type Err = anyhow::Error;
+
+    /// Parses `<token> > 0.7`, `<token><0.3`, `<token> spread > 0.1` and
+    /// `<token> volume > 3x`.
+    fn from_str(s: &str) -> Result<Self> {
+        let rule = s.trim();
+        let Some(at) = rule.find(['>', '<']) else {
+            bail!("Price alert '{}' has no '>' or '<'", rule);
+        };
+        let (left, right) = (&rule[..at], &rule[at + 1..]);
+        let mut words = left.split_whitespace();
+        let Some(token_id) = words.next() else {
+            bail!("Price alert '{}' names no token", rule);
+        };
+        let above = rule[at..].starts_with('>');
+        let condition = match (words.next(), above) {
+            (None, true) => Condition::Above(threshold(right, rule)?),
+            (None, false) => Condition::Below(threshold(right, rule)?),
+            (Some("spread"), true) => Condition::SpreadAbove(threshold(right, rule)?),
+            (Some("volume"), true) => Condition::VolumeSpike(threshold(right.trim().trim_end_matches('x'), rule)?),
+            _ => bail!("Unknown price alert '{}'", rule),
+        };
+        if words.next().is_some() {
+            bail!("Unknown price alert '{}'", rule);
+        }
+        Ok(AlertRule {
+            token_id: token_id.to_string(),
+            condition,
+        })
+    }
+}
+
+/// What the stream says about one token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Observation {
+    Trade { price: f64, size: f64 },
+    Quote { bid: f64, ask: f64 },
+}
+
+impl Observation {
+    /// The trade or quote in a streamed book event, and its token.
+    fn from_event(event: &BookEvent) -> Option<(&str, Self)> {
+        match event {
+            BookEvent::Trade { token_id, price, size } => Some((
+                token_id,
+                Observation::Trade {
+                    price: *price,
+                    size: *size,
+                },
+            )),
+            BookEvent::Snapshot {
+                token_id,
+                best_bid,
+                best_ask,
+            }
+            | BookEvent::Changed {
+                token_id,
+                best_bid,
+                best_ask,
+                ..
+            } => Some((
+                token_id,
+                Observation::Quote {
+                    bid: (*best_bid)?,
+                    ask: (*best_ask)?,
+                },
+            )),
+            BookEvent::Resync { .. } => None,
+        }
+    }
+}
+
+/// What's known of one token.
+#[derive(Debug, Default)]
+struct Market {
+    first_seen: Option<i64>,
+    price: Option<f64>,
+    spread: Option<f64>,
+    trades: VecDeque<(i64, f64)>,
+}
+
+impl Market {
+    fn observe(&mut self, observation: Observation, at: i64) {
+        self.first_seen.get_or_insert(at);
+        match observation {
+            Observation::Trade { price, size } => {
+                self.price = Some(price);
+                self.trades.push_back((at, size));
+                while self.trades.front().is_some_and(|(t, _)| *t < at - VOLUME_BASELINE_MS) {
+                    self.trades.pop_front();
+                }
+            }
+            Observation::Quote { bid, ask } => {
+                self.price = Some((bid + ask) / 2.0);
+                self.spread = Some(ask - bid);
+            }
+        }
+    }
+
+    /// Recent volume over the baseline's average per window; `None` until
+    /// there's an hour of history with some trading in it.
+    fn volume_ratio(&self, now: i64) -> Option<f64> {
+        if now - self.first_seen? < VOLUME_BASELINE_MS {
+            return None;
+        }
+        let recent: f64 = self
+            .trades
+            .iter()
+            .filter(|(t, _)| *t > now - VOLUME_WINDOW_MS)
+            .map(|(_, s)| s)
+            .sum();
+        let before: f64 = self
+            .trades
+            .iter()
+            .filter(|(t, _)| *t <= now - VOLUME_WINDOW_MS)
+            .map(|(_, s)| s)
+            .sum();
+        let windows = ((VOLUME_BASELINE_MS - VOLUME_WINDOW_MS) / VOLUME_WINDOW_MS) as f64;
+        let average = before / windows;
+        (average > 0.0).then(|| recent / average)
+    }
+
+    /// The value `condition` is judged on, and whether it holds.
+    fn check(&self, condition: &Condition, now: i64) -> Option<(f64, bool)> {
+        match *condition {
+            Condition::Above(level) => self.price.map(|p| (p, p > level)),
+            Condition::Below(level) => self.price.map(|p| (p, p < level)),
+            Condition::SpreadAbove(level) => self.spread.map(|s| (s, s > level)),
+            Condition::VolumeSpike(factor) => self.volume_ratio(now).map(|r| (r, r > factor)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    markets: HashMap<String, Market>,
+    /// Whether each rule's condition held when last checked
+    holding: Vec<Option<bool>>,
+}
+
+/// Watches the alerted tokens and notifies as their conditions start to hold.
+pub struct PriceAlerts {
+    rules: Vec<AlertRule>,
+    state: Mutex<State>,
+    notifications: Notifications,
+    clock: Arc<dyn Clock>,
+}
+
+impl PriceAlerts {
+    pub fn new(rules: Vec<AlertRule>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                markets: HashMap::new(),
+                holding: vec![None; rules.len()],
+            }),
+            rules,
+            notifications: Notifications::default(),
+            clock,
+        }
+    }
+
+    /// `None` without `price_alerts`; fails on an alert that doesn't parse.
+    pub fn from_config(config: &Config, clock: Arc<dyn Clock>) -> Result<Option<Self>> {
+        if config.price_alerts.is_empty() {
+            return Ok(None);
+        }
+        let rules = config
+            .price_alerts
+            .iter()
+            .map(|rule| rule.parse())
+            .collect::<Result<Vec<AlertRule>>>()?;
+        Ok(Some(Self::new(rules, clock)))
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    /// The alerted tokens, each once.
+    pub fn token_ids(&self) -> Vec<String> {
+        let tokens: BTreeSet<&String> = self.rules.iter().map(|r| &r.token_id).collect();
+        tokens.into_iter().cloned().collect()
+    }
+
+    /// Applies a book event and returns the alerts it fired, which are also
+    /// sent.
+    pub fn apply(&self, event: &BookEvent) -> Vec<Notification> {
+        let Some((token_id, observation)) = Observation::from_event(event) else {
+            return Vec::new();
+        };
+        if !self.rules.iter().any(|r| r.token_id == token_id) {
+            return Vec::new();
+        }
+        let now = self.clock.now_ms();
+        let mut state = self.state.lock().unwrap();
+        state
+            .markets
+            .entry(token_id.to_string())
+            .or_default()
+            .observe(observation, now);
+        let mut fired = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.token_id != token_id {
+                continue;
+            }
+            let Some((value, holds)) = state.markets[&rule.token_id].check(&rule.condition, now) else {
+                continue;
+            };
+            let was = state.holding[i].replace(holds);
+            let fires = holds
+                && match was {
+                    Some(held) => !held,
+                    None => !rule.condition.is_crossing(),
+                };
+            if fires {
+                tracing::info!("🔔 Price alert {} fired at {:.4}", rule, value);
+                fired.push(Notification::PriceAlert {
+                    token_id: rule.token_id.clone(),
+                    condition: rule.condition.to_string(),
+                    value,
+                });
+            }
+        }
+        drop(state);
+        for notification in &fired {
+            self.notifications.send(notification.clone());
+        }
+        fired
+    }
+
+    /// Has `books` stream the alerted tokens and checks the alerts on its
+    /// events in the background.
+    pub fn spawn(self: Arc<Self>, books: &BookStream) {
+        books.watch(&self.token_ids());
+        let mut events = books.subscribe();
+        tracing::info!(
+            "🔔 Watching {} price alerts on {} tokens",
+            self.rules.len(),
+            self.token_ids().len()
+        );
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        self.apply(&event);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("Price alerts fell behind; {} book events missed", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimClock;
+
+    #[test]
+    fn test_parses_rules_and_fires_on_changes() {
+        let rules: Vec<AlertRule> = ["t1 > 0.70", "t1<0.3", "t2 spread > 0.1", "t2 volume > 3x"]
+            .iter()
+            .map(|r| r.parse().unwrap())
+            .collect();
+        assert_eq!(rules[1].condition, Condition::Below(0.3));
+        assert_eq!(rules[3].condition, Condition::VolumeSpike(3.0));
+        assert_eq!(rules[2].to_string(), "t2 spread > 0.1");
+        assert!("t1 spread < 0.1".parse::<AlertRule>().is_err());
+        assert!("t1 > high".parse::<AlertRule>().is_err());
+
+        let clock = Arc::new(SimClock::at(0));
+        let alerts = PriceAlerts::new(rules, clock.clone());
+        assert_eq!(alerts.token_ids(), ["t1", "t2"]);
+        let trade = |token: &str, price: f64, size: f64| BookEvent::Trade {
+            token_id: token.to_string(),
+            price,
+            size,
+        };
+        let conditions = |fired: Vec<Notification>| -> Vec<String> {
+            fired
+                .into_iter()
+                .map(|n| match n {
+                    Notification::PriceAlert { condition, .. } => condition,
+                    other => panic!("unexpected {:?}", other),
+                })
+                .collect()
+        };
+
+        // Already under 0.3 when first seen: not a crossing
+        assert!(alerts.apply(&trade("t1", 0.25, 10.0)).is_empty());
+        assert!(alerts.apply(&trade("t1", 0.65, 10.0)).is_empty());
+        assert_eq!(conditions(alerts.apply(&trade("t1", 0.72, 10.0))), ["> 0.7"]);
+        assert!(alerts.apply(&trade("t1", 0.75, 10.0)).is_empty());
+        assert_eq!(conditions(alerts.apply(&trade("t1", 0.28, 10.0))), ["< 0.3"]);
+
+        let quote = BookEvent::Snapshot {
+            token_id: "t2".to_string(),
+            best_bid: Some(0.40),
+            best_ask: Some(0.55),
+        };
+        assert_eq!(conditions(alerts.apply(&quote)), ["spread > 0.1"]);
+
+        // An hour of 10 shares a window, then 50 in five minutes
+        for minute in (5..=60).step_by(5) {
+            clock.set(minute * 60_000);
+            alerts.apply(&trade("t2", 0.5, 10.0));
+        }
+        clock.set(64 * 60_000);
+        assert_eq!(conditions(alerts.apply(&trade("t2", 0.5, 40.0))), ["volume > 3x"]);
+    }
+}
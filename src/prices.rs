@@ -0,0 +1,200 @@
+//! Price time series of the markets we hold or watch.
+//!
+//! Every `price_sample_interval` the mid (from the order book) and last price
+//! of each held market, and of each market a leader traded within
+//! `price_watch_window`, is appended to the journal along with the top of
+//! its book. Comparing a copy's fill with the samples after it shows its
+//! slippage and whether the timing paid; backtests fill against them.
+
+use crate::api::PolymarketApi;
+use crate::marks::Marks;
+use crate::markets::MarketCache;
+use crate::portfolio::Portfolio;
+use crate::storage::{now_ms, PriceSample, Storage};
+use crate::types::{Config, OrderBook};
+use anyhow::Result;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Halfway between the best bid and best ask, if both sides have orders.
+pub fn mid_price(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Option<f64> {
+    let best_bid = bids.iter().map(|(price, _)| *price).reduce(f64::max)?;
+    let best_ask = asks.iter().map(|(price, _)| *price).reduce(f64::min)?;
+    Some((best_bid + best_ask) / 2.0)
+}
+
+/// Book levels kept per side in each sample.
+const BOOK_DEPTH: usize = 10;
+
+pub struct PriceRecorder {
+    api: PolymarketApi,
+    markets: Arc<MarketCache>,
+    portfolio: Arc<Portfolio>,
+    storage: Arc<dyn Storage>,
+    interval: Duration,
+    watch_window: Duration,
+    /// Market id to the unix ms its watch ends
+    watched: Mutex<HashMap<String, i64>>,
+    marks: Option<Arc<Marks>>,
+}
+
+impl PriceRecorder {
+    pub fn new(
+        api: PolymarketApi,
+        markets: Arc<MarketCache>,
+        portfolio: Arc<Portfolio>,
+        storage: Arc<dyn Storage>,
+        interval: Duration,
+        watch_window: Duration,
+    ) -> Self {
+        Self {
+            api,
+            markets,
+            portfolio,
+            storage,
+            interval,
+            watch_window,
+            watched: Mutex::new(HashMap::new()),
+            marks: None,
+        }
+    }
+
+    pub fn from_config(
+        config: &Config,
+        api: PolymarketApi,
+        markets: Arc<MarketCache>,
+        portfolio: Arc<Portfolio>,
+        storage: Arc<dyn Storage>,
+    ) -> Self {
+        Self::new(
+            api,
+            markets,
+            portfolio,
+            storage,
+            config.price_sample_interval,
+            config.price_watch_window,
+        )
+    }
+
+    /// Hands every book sampled to `marks` as well.
+    pub fn with_marks(mut self, marks: Arc<Marks>) -> Self {
+        self.marks = Some(marks);
+        self
+    }
+
+    /// Samples `market_id` until `price_watch_window` from `now`.
+    pub fn watch(&self, market_id: &str, now: i64) {
+        let until = now + self.watch_window.as_millis() as i64;
+        let mut watched = self.watched.lock().unwrap();
+        let entry = watched.entry(market_id.to_string()).or_insert(until);
+        *entry = (*entry).max(until);
+    }
+
+    /// Markets to sample at `now`: held ones plus those still watched.
+    pub fn tracked(&self, now: i64) -> Vec<String> {
+        let mut markets: BTreeSet<String> = self.portfolio.holdings().into_iter().map(|h| h.market_id).collect();
+        let mut watched = self.watched.lock().unwrap();
+        watched.retain(|_, until| *until > now);
+        markets.extend(watched.keys().cloned());
+        markets.into_iter().collect()
+    }
+
+    /// Samples every tracked market once and stores the results. Markets
+    /// whose price can't be fetched are left out. Returns the samples stored.
+    pub async fn sample_all(&self, now: i64) -> Result<usize> {
+        let mut samples = Vec::new();
+        for market_id in self.tracked(now) {
+            match self.sample(&market_id, now).await {
+                Ok(sample) => samples.push(sample),
+                Err(e) => tracing::debug!("Price sample failed for {}: {}", market_id, e),
+            }
+        }
+        self.storage.record_prices(&samples).await?;
+        Ok(samples.len())
+    }
+
+    async fn sample(&self, market_id: &str, now: i64) -> Result<PriceSample> {
+        let (bids, asks) = self.api.get_orderbook(market_id).await?;
+        let last = self.markets.get(market_id).await.ok().map(|m| m.yes_price);
+        let mid = mid_price(&bids, &asks);
+        if let Some(marks) = &self.marks {
+            marks.record_book(market_id, &bids, &asks, now);
+        }
+        let mut book = OrderBook::new(bids, asks);
+        book.bids.truncate(BOOK_DEPTH);
+        book.asks.truncate(BOOK_DEPTH);
+        Ok(PriceSample {
+            market_id: market_id.to_string(),
+            sampled_at: now,
+            mid,
+            last,
+            book: Some(book),
+        })
+    }
+
+    /// Samples every `price_sample_interval`; a zero interval disables it.
+    pub fn spawn(self: Arc<Self>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.sample_all(now_ms()).await {
+                    tracing::warn!("Failed to store price samples: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+    use crate::storage::FillRecord;
+    use crate::types::CostBasis;
+
+    #[test]
+    fn test_mid_price_needs_both_sides() {
+        let bids = [(0.45, 100.0), (0.48, 10.0)];
+        let asks = [(0.55, 5.0), (0.52, 20.0)];
+        assert_eq!(mid_price(&bids, &asks), Some(0.5));
+        assert_eq!(mid_price(&bids, &[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_tracks_held_and_recently_watched_markets() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let api = PolymarketApi::new("http://127.0.0.1:9".to_string());
+        let markets = Arc::new(MarketCache::new(api.clone(), None, Duration::ZERO, Duration::ZERO));
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Fifo, None));
+        portfolio
+            .apply_fill(&FillRecord {
+                id: 0,
+                order_id: 0,
+                market_id: "held".to_string(),
+                side: "BUY".to_string(),
+                shares: 10.0,
+                price: 0.5,
+                fee: 0.0,
+                filled_at: 0,
+            })
+            .await;
+
+        let recorder = PriceRecorder::new(
+            api,
+            markets,
+            portfolio,
+            storage,
+            Duration::from_secs(30),
+            Duration::from_secs(60),
+        );
+        recorder.watch("watched", 0);
+        assert_eq!(recorder.tracked(59_999), vec!["held", "watched"]);
+        assert_eq!(recorder.tracked(60_000), vec!["held"]);
+    }
+}
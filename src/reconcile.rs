@@ -0,0 +1,478 @@
+//! Periodic reconciliation of positions against the chain.
+//!
+//! Startup recovery (see [`crate::recovery`]) checks the journal once;
+//! trading by hand outside the bot, a missed fill or an unrecorded
+//! redemption can still move the wallet's holdings afterwards. Every
+//! `reconcile_interval` live trading reads the wallet's ERC-1155
+//! conditional token balance for every market the portfolio holds or the
+//! exchange reports, and flags each market where the chain holds a
+//! different number of shares than the portfolio: a warning, a
+//! `position_drift` anomaly notification and, when corrected, an audit
+//! record.
+//!
+//! Markets already booked at their resolution (see [`crate::resolution`])
+//! are left out: the chain holds their shares until they're redeemed, and
+//! lost shares for good.
+//!
+//! With `reconcile_auto_correct` on, the portfolio is set to the chain's
+//! balance (topping up at the exchange's average price, else the current
+//! one); otherwise drift is only flagged and keeps being flagged until it's
+//! resolved.
+
+use crate::api::PolymarketApi;
+use crate::audit::{AuditAction, AuditTrail};
+use crate::clock::Clock;
+use crate::exchange;
+use crate::markets::MarketCache;
+use crate::notify::{Notification, Notifications};
+use crate::portfolio::Portfolio;
+use crate::recovery::ChainBalances;
+use crate::resolution::Resolutions;
+use crate::rpc::RpcStats;
+use crate::types::{Config, Market, Venue};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Share differences below this are rounding noise.
+const EPSILON: f64 = 1e-6;
+
+/// Where outcome token balances are read from.
+#[async_trait]
+pub trait TokenBalances: Send + Sync {
+    /// Balance of the outcome token `token_id` (decimal), in shares.
+    async fn balance(&self, token_id: &str) -> Result<f64>;
+}
+
+#[async_trait]
+impl TokenBalances for ChainBalances {
+    async fn balance(&self, token_id: &str) -> Result<f64> {
+        ChainBalances::balance(self, token_id).await
+    }
+}
+
+/// A market where the chain and the portfolio disagree.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Drift {
+    pub market_id: String,
+    pub token_id: String,
+    /// Shares the portfolio holds
+    pub local: f64,
+    /// Shares the wallet holds on chain
+    pub chain: f64,
+    /// Whether the portfolio was set to the chain's balance
+    pub corrected: bool,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} holds {:.2} shares on chain but {:.2} in the portfolio",
+            self.market_id, self.chain, self.local
+        )?;
+        if self.corrected {
+            write!(f, " (corrected)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconcileReport {
+    /// Unix ms
+    pub at: i64,
+    pub checked: usize,
+    /// Markets without a known token id, or whose balance couldn't be read
+    pub unchecked: Vec<String>,
+    pub drift: Vec<Drift>,
+}
+
+impl fmt::Display for ReconcileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "checked {} positions on chain", self.checked)?;
+        if !self.unchecked.is_empty() {
+            write!(f, ", {} unchecked", self.unchecked.len())?;
+        }
+        if self.drift.is_empty() {
+            return write!(f, "; no drift");
+        }
+        write!(f, "; {} drifted:", self.drift.len())?;
+        for drift in &self.drift {
+            write!(f, "\n  - {}", drift)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the portfolio against the wallet's token balances.
+pub struct Reconciler {
+    api: PolymarketApi,
+    markets: Arc<MarketCache>,
+    portfolio: Arc<Portfolio>,
+    audit: Arc<AuditTrail>,
+    notifications: Notifications,
+    resolutions: Option<Arc<Resolutions>>,
+    wallet: String,
+    rpc_url: String,
+    rpc: Arc<RpcStats>,
+    interval: Duration,
+    auto_correct: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl Reconciler {
+    pub fn from_config(
+        config: &Config,
+        api: PolymarketApi,
+        markets: Arc<MarketCache>,
+        portfolio: Arc<Portfolio>,
+        rpc: Arc<RpcStats>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            api,
+            markets,
+            portfolio,
+            audit: Arc::new(AuditTrail::default()),
+            notifications: Notifications::default(),
+            resolutions: None,
+            wallet: config.your_wallet.clone(),
+            rpc_url: config.rpc_url.clone(),
+            rpc,
+            interval: config.reconcile_interval,
+            auto_correct: config.reconcile_auto_correct,
+            clock,
+        }
+    }
+
+    pub fn with_audit(mut self, audit: Arc<AuditTrail>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Where resolved markets, left out of the checks, are kept.
+    pub fn with_resolutions(mut self, resolutions: Arc<Resolutions>) -> Self {
+        self.resolutions = Some(resolutions);
+        self
+    }
+
+    /// Compares every position the portfolio or the exchange knows of with
+    /// `balances`, correcting drift if `reconcile_auto_correct` is on.
+    pub async fn reconcile(&self, balances: &dyn TokenBalances) -> Result<ReconcileReport> {
+        let mut report = ReconcileReport {
+            at: self.clock.now_ms(),
+            ..Default::default()
+        };
+        // The exchange knows the token of markets bought outside the bot,
+        // and what they cost
+        let mut tokens: BTreeMap<String, (Option<String>, Option<f64>)> = BTreeMap::new();
+        match self.api.get_positions(&self.wallet).await {
+            Ok(balances) => {
+                for balance in balances.into_iter().filter(|b| !b.market_id.is_empty()) {
+                    tokens.insert(balance.market_id, (balance.token_id, balance.avg_price));
+                }
+            }
+            Err(e) => tracing::debug!("No exchange positions to reconcile with: {:#}", e),
+        }
+        // Kalshi legs aren't on chain; startup recovery checks them with Kalshi
+        let local: HashMap<String, f64> = self
+            .portfolio
+            .holdings()
+            .into_iter()
+            .filter(|h| exchange::venue_of(&h.market_id) == Venue::Polymarket)
+            .map(|h| (h.market_id.clone(), h.shares()))
+            .collect();
+        for market_id in local.keys() {
+            tokens.entry(market_id.clone()).or_default();
+        }
+        if let Some(resolutions) = &self.resolutions {
+            tokens.retain(|market_id, _| !resolutions.is_resolved(market_id));
+        }
+
+        for (market_id, (token_id, avg_price)) in tokens {
+            // Positions are held under the token traded, so that's the token
+            // to check unless the key is the market's own id
+            let token_id = match token_id {
+                Some(token_id) => token_id,
+                None => match self.markets.get(&market_id).await {
+                    Ok(market) if !market.token_ids.contains(&market_id) => match market.token_ids.first() {
+                        Some(token_id) => token_id.clone(),
+                        None => {
+                            report.unchecked.push(market_id);
+                            continue;
+                        }
+                    },
+                    _ => market_id.clone(),
+                },
+            };
+            let chain = match balances.balance(&token_id).await {
+                Ok(chain) => chain,
+                Err(e) => {
+                    tracing::warn!("On-chain balance check failed for {}: {:#}", market_id, e);
+                    report.unchecked.push(market_id);
+                    continue;
+                }
+            };
+            report.checked += 1;
+            let local = local.get(&market_id).copied().unwrap_or(0.0);
+            if (chain - local).abs() <= EPSILON {
+                continue;
+            }
+            let mut drift = Drift {
+                market_id,
+                token_id,
+                local,
+                chain,
+                corrected: false,
+            };
+            if self.auto_correct {
+                let price = match avg_price {
+                    Some(price) => price,
+                    None => self
+                        .markets
+                        .get(&drift.market_id)
+                        .await
+                        .map_or(0.0, |m| token_price(&m, &drift.token_id)),
+                };
+                self.portfolio.correct(&drift.market_id, chain, price, report.at).await;
+                self.audit.record(
+                    AuditAction::PositionCorrected,
+                    "reconciler",
+                    json!({ "market_id": drift.market_id, "from": local, "to": chain, "price": price }),
+                );
+                drift.corrected = true;
+            }
+            report.drift.push(drift);
+        }
+        Ok(report)
+    }
+
+    /// Connects to `rpc_url` and reconciles once, flagging any drift.
+    pub async fn run(&self) -> Result<ReconcileReport> {
+        let chain = ChainBalances::connect(&self.rpc_url, &self.wallet, &self.rpc).await?;
+        let report = self.reconcile(&chain).await?;
+        if report.drift.is_empty() {
+            tracing::debug!("🔎 Reconciliation: {}", report);
+        } else {
+            tracing::warn!("🔎 Reconciliation: {}", report);
+            let summary: Vec<String> = report.drift.iter().map(Drift::to_string).collect();
+            self.notifications.send(Notification::Anomaly {
+                rule: "position_drift".to_string(),
+                summary: summary.join("; "),
+            });
+        }
+        Ok(report)
+    }
+
+    /// Reconciles every `reconcile_interval`, the first time one interval
+    /// after startup recovery; a zero interval or a non-WebSocket
+    /// `rpc_url` disables it.
+    pub fn spawn(self: Arc<Self>) {
+        if self.interval.is_zero() {
+            return;
+        }
+        if !self.rpc_url.starts_with("ws") {
+            tracing::warn!("⚠️  Position reconciliation needs a ws:// or wss:// RPC_URL; disabled");
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + self.interval, self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run().await {
+                    tracing::warn!("Position reconciliation failed: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+/// The price of `token_id`'s side of `market`: the second token is no.
+fn token_price(market: &Market, token_id: &str) -> f64 {
+    match market.token_ids.iter().position(|t| t == token_id) {
+        Some(1) => market.no_price,
+        _ => market.yes_price,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimClock;
+    use crate::storage::sqlite::SqliteStore;
+    use crate::storage::{FillRecord, MarketRecord, Storage};
+    use crate::types::CostBasis;
+
+    struct Balances(HashMap<&'static str, f64>);
+
+    #[async_trait]
+    impl TokenBalances for Balances {
+        async fn balance(&self, token_id: &str) -> Result<f64> {
+            self.0
+                .get(token_id)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("no balance for {}", token_id))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flags_drift_and_corrects_it_when_asked() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        for (id, token) in [("m1", "101"), ("m2", "201"), ("m3", "301")] {
+            let market = Market {
+                id: id.to_string(),
+                yes_price: 0.5,
+                no_price: 0.5,
+                token_ids: vec![token.to_string()],
+                ..Default::default()
+            };
+            storage
+                .save_market(&MarketRecord {
+                    market,
+                    fetched_at: crate::storage::now_ms(),
+                    expires_at: crate::storage::now_ms() + 3_600_000,
+                })
+                .await
+                .unwrap();
+        }
+        // Nothing listens here, so the exchange reports no positions
+        let api = PolymarketApi::new("http://127.0.0.1:9".to_string());
+        let markets = Arc::new(MarketCache::new(
+            api.clone(),
+            Some(storage),
+            Duration::from_secs(60),
+            Duration::from_secs(600),
+        ));
+        markets.load().await.unwrap();
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Fifo, None));
+        for (market_id, shares) in [("m1", 10.0), ("m2", 5.0), ("m3", 4.0), ("m4", 1.0)] {
+            let fill = FillRecord {
+                id: 0,
+                order_id: 1,
+                market_id: market_id.to_string(),
+                side: "BUY".to_string(),
+                shares,
+                price: 0.4,
+                fee: 0.0,
+                filled_at: 0,
+            };
+            portfolio.apply_fill(&fill).await;
+        }
+        let config = Config {
+            reconcile_auto_correct: false,
+            ..Config::default()
+        };
+        let clock = Arc::new(SimClock::at(1_700_000_000_000));
+        let reconciler = Reconciler::from_config(
+            &config,
+            api.clone(),
+            markets.clone(),
+            portfolio.clone(),
+            Arc::new(RpcStats::new()),
+            clock.clone(),
+        );
+        // m2 was partly sold by hand, m3 can't be read and m4 has no token
+        let balances = Balances(HashMap::from([("101", 10.0), ("201", 2.0)]));
+
+        let report = reconciler.reconcile(&balances).await.unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.unchecked, vec!["m3", "m4"]);
+        assert_eq!(report.drift.len(), 1);
+        let drift = &report.drift[0];
+        assert_eq!((drift.market_id.as_str(), drift.local, drift.chain), ("m2", 5.0, 2.0));
+        assert!(!drift.corrected);
+        assert_eq!(portfolio.holding("m2").unwrap().shares(), 5.0);
+
+        let config = Config {
+            reconcile_auto_correct: true,
+            ..config
+        };
+        let reconciler = Reconciler::from_config(
+            &config,
+            api,
+            markets,
+            portfolio.clone(),
+            Arc::new(RpcStats::new()),
+            clock,
+        );
+        let report = reconciler.reconcile(&balances).await.unwrap();
+        assert!(report.drift[0].corrected);
+        let m2 = portfolio.holding("m2").unwrap();
+        assert_eq!((m2.shares(), m2.avg_price()), (2.0, 0.4));
+        assert!(reconciler.reconcile(&balances).await.unwrap().drift.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_no_holdings_are_checked_and_priced_as_no() {
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        // Cached under the no token it was fetched by
+        let market = Market {
+            id: "502".to_string(),
+            yes_price: 0.65,
+            no_price: 0.35,
+            token_ids: vec!["501".to_string(), "502".to_string()],
+            ..Default::default()
+        };
+        storage
+            .save_market(&MarketRecord {
+                market,
+                fetched_at: crate::storage::now_ms(),
+                expires_at: crate::storage::now_ms() + 3_600_000,
+            })
+            .await
+            .unwrap();
+        let api = PolymarketApi::new("http://127.0.0.1:9".to_string());
+        let markets = Arc::new(MarketCache::new(
+            api.clone(),
+            Some(storage),
+            Duration::from_secs(60),
+            Duration::from_secs(600),
+        ));
+        markets.load().await.unwrap();
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Fifo, None));
+        let fill = FillRecord {
+            id: 0,
+            order_id: 1,
+            market_id: "502".to_string(),
+            side: "BUY".to_string(),
+            shares: 4.0,
+            price: 0.3,
+            fee: 0.0,
+            filled_at: 0,
+        };
+        portfolio.apply_fill(&fill).await;
+        let config = Config {
+            reconcile_auto_correct: true,
+            ..Config::default()
+        };
+        let reconciler = Reconciler::from_config(
+            &config,
+            api,
+            markets,
+            portfolio.clone(),
+            Arc::new(RpcStats::new()),
+            Arc::new(SimClock::at(1_700_000_000_000)),
+        );
+        // Two more no shares arrived by hand; the yes balance is unrelated
+        let balances = Balances(HashMap::from([("501", 9.0), ("502", 6.0)]));
+
+        let report = reconciler.reconcile(&balances).await.unwrap();
+        assert_eq!(report.drift.len(), 1);
+        let drift = &report.drift[0];
+        assert_eq!((drift.token_id.as_str(), drift.local, drift.chain), ("502", 4.0, 6.0));
+        let held = portfolio.holding("502").unwrap();
+        assert_eq!(held.shares(), 6.0);
+        assert!((held.avg_price() - (4.0 * 0.3 + 2.0 * 0.35) / 6.0).abs() < 1e-9);
+    }
+}
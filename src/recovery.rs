@@ -0,0 +1,714 @@
+//! Startup reconciliation of the local journal against the exchange and chain.
+//!
+//! A crash can land between submitting an order and recording its outcome,
+//! or between a fill and the position update. Before copying anything the
+//! bot resolves every order it still believes is open and replaces its
+//! positions with what the exchange (or, where possible, the chain) holds.
+//!
+//! Orders are journaled as write-ahead intents (status `submitting`, with the
+//! client id sent to the exchange) before submission, so an intent left over
+//! from a crash is looked up by client id: adopted if the exchange has it,
+//! otherwise marked `not_submitted`. Nothing is copied until this is done.
+//!
+//! Each venue only reconciles the orders and positions journaled under its
+//! own market ids (see [`exchange::venue_of`]), so a Kalshi arbitrage leg
+//! is checked against Kalshi and never zeroed for Polymarket not holding it.
+
+use crate::exchange::{self, Exchange};
+use crate::storage::{now_ms, FillRecord, OrderRecord, PositionRecord, Storage, TimeRange};
+use crate::types::{ExchangeOrder, Venue};
+use anyhow::{Context, Result};
+use crate::rpc::{self, Metered, RpcStats};
+use ethers::providers::{Middleware, Provider, Ws};
+use ethers::types::{Address, Bytes, TransactionRequest, U256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// Share differences below this are rounding noise.
+const EPSILON: f64 = 1e-6;
+
+/// Polymarket's Conditional Tokens (ERC-1155) contract on Polygon.
+pub const CTF_CONTRACT: &str = "0x4D97DCd97eC945f40cF65F87097ACe5EA0476045";
+
+/// `balanceOf(address,uint256)`
+const BALANCE_OF_SELECTOR: [u8; 4] = [0x00, 0xfd, 0xd5, 0x8e];
+
+/// Outcome tokens use the same 6 decimals as USDC.
+const TOKEN_DECIMALS: f64 = 1_000_000.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Discrepancy {
+    /// A locally open order had closed (or filled further) on the exchange.
+    OrderResolved {
+        order_id: i64,
+        exchange_order_id: String,
+        status: String,
+        unrecorded_shares: f64,
+    },
+    /// An order recorded before submission turned out to be live on the exchange.
+    OrderAdopted { order_id: i64, exchange_order_id: String },
+    /// An order was recorded but never acknowledged, and nothing matches it.
+    OrderUnconfirmed { order_id: i64, market_id: String },
+    /// An order intent whose client id the exchange has never seen.
+    OrderNotSubmitted { order_id: i64, market_id: String },
+    /// The exchange has an open order the journal knows nothing about.
+    UntrackedOrder { exchange_order_id: String, market_id: String },
+    /// The local position was replaced by the exchange's or chain's view.
+    PositionCorrected {
+        market_id: String,
+        local: f64,
+        actual: f64,
+        source: &'static str,
+    },
+    /// The exchange API and the chain disagree; the chain wins.
+    ChainMismatch { market_id: String, exchange: f64, chain: f64 },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::OrderResolved { order_id, exchange_order_id, status, unrecorded_shares } => write!(
+                f,
+                "order #{} ({}) is {} on the exchange; recorded {:.2} missing shares",
+                order_id, exchange_order_id, status, unrecorded_shares
+            ),
+            Discrepancy::OrderAdopted { order_id, exchange_order_id } => write!(
+                f,
+                "order #{} was live on the exchange as {}",
+                order_id, exchange_order_id
+            ),
+            Discrepancy::OrderUnconfirmed { order_id, market_id } => write!(
+                f,
+                "order #{} in {} was never acknowledged; marked unknown",
+                order_id, market_id
+            ),
+            Discrepancy::OrderNotSubmitted { order_id, market_id } => write!(
+                f,
+                "order #{} in {} never reached the exchange; marked not submitted",
+                order_id, market_id
+            ),
+            Discrepancy::UntrackedOrder { exchange_order_id, market_id } => write!(
+                f,
+                "untracked open order {} in {} added to the journal",
+                exchange_order_id, market_id
+            ),
+            Discrepancy::PositionCorrected { market_id, local, actual, source } => write!(
+                f,
+                "position in {} corrected from {:.2} to {:.2} shares ({})",
+                market_id, local, actual, source
+            ),
+            Discrepancy::ChainMismatch { market_id, exchange, chain } => write!(
+                f,
+                "exchange reports {:.2} shares in {} but the chain holds {:.2}",
+                exchange, market_id, chain
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryReport {
+    pub orders_checked: usize,
+    pub positions_checked: usize,
+    pub onchain_checked: usize,
+    pub usdc_balance: Option<f64>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl RecoveryReport {
+    pub fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+impl fmt::Display for RecoveryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "checked {} open orders and {} positions ({} on-chain)",
+            self.orders_checked, self.positions_checked, self.onchain_checked
+        )?;
+        if let Some(balance) = self.usdc_balance {
+            write!(f, ", USDC balance ${:.2}", balance)?;
+        }
+        if self.is_clean() {
+            return write!(f, "; no discrepancies");
+        }
+        write!(f, "; {} discrepancies:", self.discrepancies.len())?;
+        for d in &self.discrepancies {
+            write!(f, "\n  - {}", d)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads outcome token balances straight from the CTF contract.
+pub struct ChainBalances {
+    provider: Provider<Metered<Ws>>,
+    owner: Address,
+    contract: Address,
+}
+
+impl ChainBalances {
+    pub async fn connect(rpc_url: &str, owner: &str, stats: &Arc<RpcStats>) -> Result<Self> {
+        let owner: Address = owner.parse().context("Invalid wallet address")?;
+        let provider = rpc::connect_ws(rpc_url, stats).await?;
+        Ok(Self {
+            provider,
+            owner,
+            contract: CTF_CONTRACT.parse()?,
+        })
+    }
+
+    /// Balance of the outcome token `token_id` (decimal), in shares.
+    pub async fn balance(&self, token_id: &str) -> Result<f64> {
+        let token = U256::from_dec_str(token_id).context("Invalid token id")?;
+        let mut data = BALANCE_OF_SELECTOR.to_vec();
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(self.owner.as_bytes());
+        let mut word = [0u8; 32];
+        token.to_big_endian(&mut word);
+        data.extend_from_slice(&word);
+
+        let tx = TransactionRequest::new().to(self.contract).data(Bytes::from(data));
+        let out = self
+            .provider
+            .call(&tx.into(), None)
+            .await
+            .context("balanceOf call failed")?;
+        token_shares(&out)
+    }
+}
+
+/// A `balanceOf` answer in shares. The RPC isn't trusted to send a single
+/// word, and balances past u128 saturate rather than panic.
+fn token_shares(out: &[u8]) -> Result<f64> {
+    anyhow::ensure!(out.len() <= 32, "balanceOf returned {} bytes for a uint256", out.len());
+    let raw = U256::from_big_endian(out).min(U256::from(u128::MAX));
+    Ok(raw.as_u128() as f64 / TOKEN_DECIMALS)
+}
+
+/// Resolves open orders and corrects positions on every venue in
+/// `exchanges`, writing fixes to `storage`. `chain` backs Polymarket's
+/// balances; the cash reported is the first venue's.
+pub async fn recover(
+    exchanges: &[Arc<dyn Exchange>],
+    storage: &dyn Storage,
+    chain: Option<&ChainBalances>,
+) -> Result<RecoveryReport> {
+    let mut report = RecoveryReport::default();
+
+    for exchange in exchanges {
+        let venue = exchange.venue();
+        reconcile_orders(exchange.as_ref(), storage, &mut report)
+            .await
+            .with_context(|| format!("Failed to reconcile {} orders", venue.as_str()))?;
+        let chain = chain.filter(|_| venue == Venue::Polymarket);
+        reconcile_positions(exchange.as_ref(), storage, chain, &mut report)
+            .await
+            .with_context(|| format!("Failed to reconcile {} positions", venue.as_str()))?;
+    }
+
+    if let Some(exchange) = exchanges.first() {
+        report.usdc_balance = exchange.balance().await.ok();
+    }
+    Ok(report)
+}
+
+async fn reconcile_orders(exchange: &dyn Exchange, storage: &dyn Storage, report: &mut RecoveryReport) -> Result<()> {
+    let venue = exchange.venue();
+    let exchange_open = exchange.open_orders().await?;
+    let local_open: Vec<OrderRecord> = storage
+        .open_orders()
+        .await?
+        .into_iter()
+        .filter(|o| exchange::venue_of(&o.market_id) == venue)
+        .collect();
+    report.orders_checked += local_open.len();
+
+    let mut known: HashSet<String> = storage
+        .orders(TimeRange::all())
+        .await?
+        .into_iter()
+        .filter_map(|o| o.exchange_order_id)
+        .collect();
+    let by_id: HashMap<&str, &ExchangeOrder> =
+        exchange_open.iter().map(|o| (o.order_id.as_str(), o)).collect();
+
+    for order in &local_open {
+        match &order.exchange_order_id {
+            Some(exchange_id) => {
+                let remote = match by_id.get(exchange_id.as_str()) {
+                    Some(o) => (*o).clone(),
+                    None => exchange.order(exchange_id).await?,
+                };
+                resolve_order(storage, order, &remote, report).await?;
+            }
+            None if order.client_order_id.is_some() => {
+                // Crashed between recording the intent and the exchange's ack;
+                // the client id tells for certain whether it got there.
+                let client_id = order.client_order_id.as_deref().unwrap_or_default();
+                let remote = match exchange_open.iter().find(|o| o.client_order_id.as_deref() == Some(client_id)) {
+                    Some(o) => Some(o.clone()),
+                    None => exchange.order_by_client_id(client_id).await?,
+                };
+                match remote {
+                    Some(remote) => {
+                        storage
+                            .update_order(order.id, &order.status, Some(&remote.order_id), None)
+                            .await?;
+                        known.insert(remote.order_id.clone());
+                        report.discrepancies.push(Discrepancy::OrderAdopted {
+                            order_id: order.id,
+                            exchange_order_id: remote.order_id.clone(),
+                        });
+                        resolve_order(storage, order, &remote, report).await?;
+                    }
+                    None => {
+                        storage
+                            .update_order(order.id, "not_submitted", None, Some("exchange never received the order"))
+                            .await?;
+                        report.discrepancies.push(Discrepancy::OrderNotSubmitted {
+                            order_id: order.id,
+                            market_id: order.market_id.clone(),
+                        });
+                    }
+                }
+            }
+            None => {
+                // Crashed between recording the order and the exchange's ack,
+                // before orders carried client ids: match on what we can.
+                let adopted = exchange_open.iter().find(|o| {
+                    !known.contains(&o.order_id)
+                        && o.market_id == order.market_id
+                        && o.side.as_str() == order.side
+                        && (o.shares - order.shares).abs() < EPSILON
+                });
+                match adopted {
+                    Some(remote) => {
+                        storage
+                            .update_order(order.id, &remote.status, Some(&remote.order_id), None)
+                            .await?;
+                        known.insert(remote.order_id.clone());
+                        report.discrepancies.push(Discrepancy::OrderAdopted {
+                            order_id: order.id,
+                            exchange_order_id: remote.order_id.clone(),
+                        });
+                    }
+                    None => {
+                        storage
+                            .update_order(
+                                order.id,
+                                "unknown",
+                                None,
+                                Some("no exchange acknowledgement before restart"),
+                            )
+                            .await?;
+                        report.discrepancies.push(Discrepancy::OrderUnconfirmed {
+                            order_id: order.id,
+                            market_id: order.market_id.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    for remote in exchange_open.iter().filter(|o| !known.contains(&o.order_id)) {
+        let record = OrderRecord {
+            id: 0,
+            decision_id: None,
+            exchange_order_id: Some(remote.order_id.clone()),
+            market_id: remote.market_id.clone(),
+            side: remote.side.as_str().to_string(),
+            shares: remote.shares,
+            limit_price: None,
+            order_type: "unknown".to_string(),
+            status: remote.status.clone(),
+            error: None,
+            submitted_at: now_ms(),
+            client_order_id: remote.client_order_id.clone(),
+        };
+        storage.record_order(&record).await?;
+        report.discrepancies.push(Discrepancy::UntrackedOrder {
+            exchange_order_id: remote.order_id.clone(),
+            market_id: remote.market_id.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Records fills the journal missed and brings the order's status up to date.
+async fn resolve_order(
+    storage: &dyn Storage,
+    order: &OrderRecord,
+    remote: &ExchangeOrder,
+    report: &mut RecoveryReport,
+) -> Result<()> {
+    let recorded: f64 = storage
+        .fills(TimeRange::since(order.submitted_at))
+        .await?
+        .iter()
+        .filter(|f| f.order_id == order.id)
+        .map(|f| f.shares)
+        .sum();
+    let missing = remote.filled_shares - recorded;
+
+    if missing > EPSILON {
+        let fill = FillRecord {
+            id: 0,
+            order_id: order.id,
+            market_id: order.market_id.clone(),
+            side: order.side.clone(),
+            shares: missing,
+            price: remote.avg_fill_price,
+            fee: 0.0,
+            filled_at: now_ms(),
+        };
+        storage.record_fill(&fill).await?;
+        storage.apply_fill(&fill).await?;
+    }
+
+    if remote.status != order.status {
+        storage.update_order(order.id, &remote.status, None, None).await?;
+    }
+
+    if remote.is_closed() || missing > EPSILON {
+        report.discrepancies.push(Discrepancy::OrderResolved {
+            order_id: order.id,
+            exchange_order_id: remote.order_id.clone(),
+            status: remote.status.clone(),
+            unrecorded_shares: missing.max(0.0),
+        });
+    }
+
+    Ok(())
+}
+
+async fn reconcile_positions(
+    exchange: &dyn Exchange,
+    storage: &dyn Storage,
+    chain: Option<&ChainBalances>,
+    report: &mut RecoveryReport,
+) -> Result<()> {
+    let venue = exchange.venue();
+    let remote: HashMap<String, _> = exchange
+        .positions()
+        .await?
+        .into_iter()
+        .map(|b| (b.market_id.clone(), b))
+        .collect();
+    let local: HashMap<String, PositionRecord> = storage
+        .positions()
+        .await?
+        .into_iter()
+        .filter(|p| exchange::venue_of(&p.market_id) == venue)
+        .map(|p| (p.market_id.clone(), p))
+        .collect();
+
+    let markets: BTreeSet<&String> = remote.keys().chain(local.keys()).collect();
+    report.positions_checked += markets.len();
+
+    for market_id in markets {
+        let balance = remote.get(market_id);
+        let exchange = balance.map(|b| b.shares).unwrap_or(0.0);
+        let mut actual = exchange;
+        let mut source = "exchange";
+
+        let token_id = balance.and_then(|b| b.token_id.as_deref());
+        if let (Some(chain), Some(token_id)) = (chain, token_id) {
+            match chain.balance(token_id).await {
+                Ok(onchain) => {
+                    report.onchain_checked += 1;
+                    if (onchain - exchange).abs() > EPSILON {
+                        report.discrepancies.push(Discrepancy::ChainMismatch {
+                            market_id: market_id.clone(),
+                            exchange,
+                            chain: onchain,
+                        });
+                    }
+                    actual = onchain;
+                    source = "chain";
+                }
+                Err(e) => tracing::warn!("On-chain balance check failed for {}: {}", market_id, e),
+            }
+        }
+
+        let current = local.get(market_id);
+        let local_shares = current.map(|p| p.shares).unwrap_or(0.0);
+        if (actual - local_shares).abs() <= EPSILON {
+            continue;
+        }
+
+        let avg_price = balance
+            .and_then(|b| b.avg_price)
+            .or(current.map(|p| p.avg_price))
+            .unwrap_or(0.0);
+        storage
+            .set_position(&PositionRecord {
+                market_id: market_id.clone(),
+                shares: actual,
+                avg_price,
+                updated_at: now_ms(),
+            })
+            .await?;
+        report.discrepancies.push(Discrepancy::PositionCorrected {
+            market_id: market_id.clone(),
+            local: local_shares,
+            actual,
+            source,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::PolymarketApi;
+    use crate::exchange::PolymarketExchange;
+    use crate::http::{serve, Handler, Request, Response};
+    use crate::storage::sqlite::SqliteStore;
+    use crate::storage::{Journal, PositionStore};
+    use crate::types::{Market, OrderBook, OrderRequest, OrderResponse, TokenBalance, Trade, TradeSide};
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+
+    /// The exchange after a crash mid-submit: "c-open" is resting, "c-filled"
+    /// filled and left the book, "c-lost" never arrived.
+    struct Clob;
+
+    fn order(order_id: &str, client_order_id: &str, status: &str, filled_shares: f64) -> Value {
+        json!({ "order_id": order_id, "market_id": "m1", "side": "BUY", "shares": 10.0,
+            "filled_shares": filled_shares, "avg_fill_price": 0.4, "status": status,
+            "client_order_id": client_order_id })
+    }
+
+    #[async_trait]
+    impl Handler for Clob {
+        async fn handle(&self, request: &Request) -> Option<Response> {
+            let body = match (request.path.as_str(), request.query_param("client_order_id")) {
+                ("/orders", None) => json!([order("ex-1", "c-open", "open", 0.0)]),
+                ("/orders", Some("c-open")) => json!([order("ex-1", "c-open", "open", 0.0)]),
+                ("/orders", Some("c-filled")) => json!([order("ex-2", "c-filled", "filled", 10.0)]),
+                ("/orders", Some(_)) => json!([]),
+                ("/positions/0xme", _) => json!([{ "market_id": "m1", "shares": 10.0, "avg_price": 0.4 }]),
+                ("/balance/0xme", _) => json!({ "balance": 96.0 }),
+                _ => return None,
+            };
+            Some(Response::json(200, &body))
+        }
+    }
+
+    /// Kalshi holding an arbitrage leg of 5 "T1" yes contracts, with the
+    /// next leg's order "k-1" resting on "T2".
+    struct Kalshi;
+
+    fn leg() -> ExchangeOrder {
+        ExchangeOrder {
+            order_id: "k-1".to_string(),
+            market_id: "T2:no".to_string(),
+            side: TradeSide::BUY,
+            shares: 5.0,
+            filled_shares: 0.0,
+            avg_fill_price: 0.0,
+            status: "open".to_string(),
+            client_order_id: Some("c-leg".to_string()),
+        }
+    }
+
+    #[async_trait]
+    impl Exchange for Kalshi {
+        fn venue(&self) -> Venue {
+            Venue::Kalshi
+        }
+        async fn trades(&self, _account: &str, _since: i64) -> Result<Vec<Trade>> {
+            Ok(vec![])
+        }
+        async fn market(&self, market_id: &str) -> Result<Market> {
+            anyhow::bail!("no market {}", market_id)
+        }
+        async fn markets(&self, _query: &str, _limit: usize) -> Result<Vec<Market>> {
+            Ok(vec![])
+        }
+        async fn orderbook(&self, _market_id: &str) -> Result<OrderBook> {
+            Ok(OrderBook::new(vec![], vec![]))
+        }
+        async fn place_order(&self, _order: &OrderRequest) -> Result<OrderResponse> {
+            anyhow::bail!("read only")
+        }
+        async fn cancel_order(&self, _order_id: &str) -> Result<()> {
+            Ok(())
+        }
+        async fn open_orders(&self) -> Result<Vec<ExchangeOrder>> {
+            Ok(vec![])
+        }
+        async fn order(&self, order_id: &str) -> Result<ExchangeOrder> {
+            anyhow::bail!("no order {}", order_id)
+        }
+        async fn order_by_client_id(&self, client_order_id: &str) -> Result<Option<ExchangeOrder>> {
+            Ok(Some(leg()).filter(|o| o.client_order_id.as_deref() == Some(client_order_id)))
+        }
+        async fn positions(&self) -> Result<Vec<TokenBalance>> {
+            Ok(vec![TokenBalance {
+                market_id: "T1:yes".to_string(),
+                token_id: Some("T1:yes".to_string()),
+                shares: 5.0,
+                avg_price: Some(0.4),
+            }])
+        }
+        async fn balance(&self) -> Result<f64> {
+            Ok(50.0)
+        }
+        async fn verify_credentials(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn polymarket() -> Arc<dyn Exchange> {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        serve(&format!("127.0.0.1:{}", port), vec![Arc::new(Clob)])
+            .await
+            .unwrap();
+        let api = PolymarketApi::new(format!("http://127.0.0.1:{}", port));
+        Arc::new(PolymarketExchange::new(api, "0xme".to_string(), String::new()))
+    }
+
+    fn intent(client_order_id: &str) -> OrderRecord {
+        intent_in("m1", client_order_id)
+    }
+
+    fn intent_in(market_id: &str, client_order_id: &str) -> OrderRecord {
+        OrderRecord {
+            id: 0,
+            decision_id: None,
+            exchange_order_id: None,
+            market_id: market_id.to_string(),
+            side: "BUY".to_string(),
+            shares: 10.0,
+            limit_price: Some(0.4),
+            order_type: "Limit".to_string(),
+            status: "submitting".to_string(),
+            error: None,
+            submitted_at: now_ms(),
+            client_order_id: Some(client_order_id.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_intents_are_adopted_by_client_id_or_marked_not_submitted() {
+        let exchanges = [polymarket().await];
+        let storage = SqliteStore::open_in_memory().unwrap();
+        let open = storage.record_order(&intent("c-open")).await.unwrap();
+        let filled = storage.record_order(&intent("c-filled")).await.unwrap();
+        let lost = storage.record_order(&intent("c-lost")).await.unwrap();
+
+        let report = recover(&exchanges, &storage, None).await.unwrap();
+        assert_eq!(report.orders_checked, 3);
+        assert_eq!(report.usdc_balance, Some(96.0));
+        assert_eq!(
+            report.discrepancies,
+            [
+                Discrepancy::OrderAdopted {
+                    order_id: open,
+                    exchange_order_id: "ex-1".to_string(),
+                },
+                Discrepancy::OrderAdopted {
+                    order_id: filled,
+                    exchange_order_id: "ex-2".to_string(),
+                },
+                Discrepancy::OrderResolved {
+                    order_id: filled,
+                    exchange_order_id: "ex-2".to_string(),
+                    status: "filled".to_string(),
+                    unrecorded_shares: 10.0,
+                },
+                Discrepancy::OrderNotSubmitted {
+                    order_id: lost,
+                    market_id: "m1".to_string(),
+                },
+            ]
+        );
+
+        let orders = storage.orders(TimeRange::all()).await.unwrap();
+        let status = |id: i64| {
+            let order = orders.iter().find(|o| o.id == id).unwrap();
+            (order.status.as_str(), order.exchange_order_id.as_deref())
+        };
+        assert_eq!(status(open), ("open", Some("ex-1")));
+        assert_eq!(status(filled), ("filled", Some("ex-2")));
+        assert_eq!(status(lost), ("not_submitted", None));
+        // The adopted fill is journaled and held, so the position agrees
+        let fills = storage.fills(TimeRange::all()).await.unwrap();
+        assert_eq!((fills.len(), fills[0].order_id, fills[0].shares), (1, filled, 10.0));
+        assert_eq!(storage.positions().await.unwrap()[0].shares, 10.0);
+        assert_eq!(storage.open_orders().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_kalshi_legs_are_reconciled_against_kalshi_only() {
+        let storage = SqliteStore::open_in_memory().unwrap();
+        for (market_id, shares) in [("m1", 10.0), ("T1:yes", 5.0)] {
+            storage
+                .set_position(&PositionRecord {
+                    market_id: market_id.to_string(),
+                    shares,
+                    avg_price: 0.4,
+                    updated_at: now_ms(),
+                })
+                .await
+                .unwrap();
+        }
+        let pending = storage.record_order(&intent_in("T2:no", "c-leg")).await.unwrap();
+
+        // Restarting without Kalshi configured leaves the leg alone
+        let report = recover(&[polymarket().await], &storage, None).await.unwrap();
+        assert_eq!((report.orders_checked, report.positions_checked), (0, 1));
+        assert_eq!(
+            report.discrepancies,
+            [Discrepancy::UntrackedOrder {
+                exchange_order_id: "ex-1".to_string(),
+                market_id: "m1".to_string(),
+            }]
+        );
+        let leg = |orders: Vec<OrderRecord>| orders.into_iter().find(|o| o.id == pending).unwrap();
+        assert_eq!(leg(storage.open_orders().await.unwrap()).status, "submitting");
+
+        // With it, the next leg's intent is found on Kalshi and the held
+        // leg agrees with Kalshi's position
+        let exchanges = [polymarket().await, Arc::new(Kalshi) as Arc<dyn Exchange>];
+        let report = recover(&exchanges, &storage, None).await.unwrap();
+        assert_eq!((report.orders_checked, report.positions_checked), (2, 2));
+        assert_eq!(report.usdc_balance, Some(96.0));
+        assert_eq!(
+            report.discrepancies,
+            [Discrepancy::OrderAdopted {
+                order_id: pending,
+                exchange_order_id: "k-1".to_string(),
+            }]
+        );
+        let order = leg(storage.open_orders().await.unwrap());
+        assert_eq!(order.exchange_order_id.as_deref(), Some("k-1"));
+        let mut positions = storage.positions().await.unwrap();
+        positions.sort_by(|a, b| a.market_id.cmp(&b.market_id));
+        let held: Vec<(&str, f64)> = positions.iter().map(|p| (p.market_id.as_str(), p.shares)).collect();
+        assert_eq!(held, [("T1:yes", 5.0), ("m1", 10.0)]);
+    }
+
+    #[test]
+    fn test_token_balances_are_read_defensively() {
+        let mut word = [0u8; 32];
+        U256::from(2_500_000u64).to_big_endian(&mut word);
+        assert_eq!(token_shares(&word).unwrap(), 2.5);
+        assert_eq!(token_shares(&[]).unwrap(), 0.0);
+        assert_eq!(token_shares(&[0xff; 32]).unwrap(), u128::MAX as f64 / TOKEN_DECIMALS);
+        assert!(token_shares(&[0u8; 64]).is_err());
+    }
+}
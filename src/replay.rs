@@ -0,0 +1,307 @@
+//! Re-drives the decision engine from an event log.
+//!
+//! Each recorded leader trade is evaluated again with the current config,
+//! using the market data and balances logged at the time and the trade's
+//! original arrival time as "now". Risk state evolves exactly as it did live
+//! (recorded order failures count as errors, daily resets are replayed), so
+//! the only differences come from config or code changes.
+
+use crate::bot::{Bot, UNKNOWN_WHALE_BALANCE};
+use crate::clock::SimClock;
+use crate::events::{BotEvent, EventRecord};
+use crate::types::{Decision, Market, SkipReason, Trade};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A trade whose replayed decision differs from the recorded one.
+#[derive(Debug, Clone)]
+pub struct DecisionChange {
+    pub seq: u64,
+    pub market_id: String,
+    pub recorded: Option<Decision>,
+    pub replayed: Decision,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub trades: usize,
+    pub copied: usize,
+    pub copied_usd: f64,
+    pub skipped: BTreeMap<&'static str, usize>,
+    /// Trades skipped before any market data was fetched, which the current
+    /// config would now evaluate further.
+    pub unreplayable: usize,
+    pub changes: Vec<DecisionChange>,
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Replayed {} trades", self.trades)?;
+        writeln!(f, "  copied: {} (${:.2})", self.copied, self.copied_usd)?;
+        for (reason, count) in &self.skipped {
+            writeln!(f, "  skipped ({}): {}", reason, count)?;
+        }
+        if self.unreplayable > 0 {
+            writeln!(f, "  not replayable (no recorded market data): {}", self.unreplayable)?;
+        }
+        writeln!(f, "{} decisions changed", self.changes.len())?;
+        for c in &self.changes {
+            writeln!(
+                f,
+                "  #{} {}: {} -> {}",
+                c.seq,
+                c.market_id,
+                c.recorded.as_ref().map(describe).unwrap_or_else(|| "none".to_string()),
+                describe(&c.replayed)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(decision: &Decision) -> String {
+    match decision {
+        Decision::Copy { size_usd, .. } => format!("copy ${:.2}", size_usd),
+        Decision::Skip { reason, .. } => format!("skip ({})", reason.as_str()),
+    }
+}
+
+/// A trade collected from the log, waiting for its recorded decision.
+struct PendingTrade {
+    seq: u64,
+    at_ms: i64,
+    trade: Trade,
+    market: Option<Market>,
+    balances: Vec<(String, f64)>,
+}
+
+/// A replayed copy whose execution outcome is still to be applied.
+struct PendingFill {
+    trade: Trade,
+    size_usd: f64,
+}
+
+/// Replays `records` through `bot`. Build the bot with paper trading and no
+/// storage or event log so replaying has no side effects, and on `clock`,
+/// which is moved to each trade's arrival.
+pub async fn replay(bot: &Bot, clock: &SimClock, records: &[EventRecord]) -> ReplayReport {
+    let mut report = ReplayReport::default();
+    let mut pending: Option<PendingTrade> = None;
+    let mut fill: Option<PendingFill> = None;
+
+    for record in records {
+        match &record.event {
+            BotEvent::TradeSeen { trade } => {
+                if let Some(p) = pending.take() {
+                    fill = replay_trade(bot, clock, p, None, &mut report).await;
+                }
+                settle(bot, fill.take(), None);
+                pending = Some(PendingTrade {
+                    seq: record.seq,
+                    at_ms: record.at_ms,
+                    trade: trade.clone(),
+                    market: None,
+                    balances: Vec::new(),
+                });
+            }
+            BotEvent::MarketFetched { market } => {
+                if let Some(p) = pending.as_mut() {
+                    p.market = Some(market.clone());
+                }
+            }
+            BotEvent::BalanceFetched { wallet, balance } => {
+                if let Some(p) = pending.as_mut() {
+                    p.balances.push((wallet.clone(), *balance));
+                }
+            }
+            BotEvent::Decision { decision, .. } => {
+                if let Some(p) = pending.take() {
+                    fill = replay_trade(bot, clock, p, Some(decision.clone()), &mut report).await;
+                }
+            }
+            BotEvent::OrderResult { error, .. } => {
+                settle(bot, fill.take(), Some(error.as_deref()));
+            }
+            BotEvent::DailyReset => {
+                settle(bot, fill.take(), None);
+                bot.risk().reset_daily_stats();
+            }
+            BotEvent::OrderSubmitted { .. }
+            | BotEvent::Connection { .. }
+            | BotEvent::MarketAnomaly { .. }
+            | BotEvent::Arbitrage { .. } => {}
+        }
+    }
+
+    if let Some(p) = pending.take() {
+        fill = replay_trade(bot, clock, p, None, &mut report).await;
+    }
+    settle(bot, fill, None);
+
+    report
+}
+
+/// Applies a replayed copy to the risk state. `recorded` is the live order
+/// outcome if the trade was copied live too (`Some(Some(err))` = failed);
+/// otherwise the copy is assumed to fill.
+fn settle(bot: &Bot, fill: Option<PendingFill>, recorded: Option<Option<&str>>) {
+    let Some(fill) = fill else { return };
+    match recorded {
+        Some(Some(error)) => bot.risk().record_error(&format!("Execution failed: {}", error)),
+        _ => bot.risk().record_trade(&fill.trade, fill.size_usd),
+    }
+}
+
+async fn replay_trade(
+    bot: &Bot,
+    clock: &SimClock,
+    p: PendingTrade,
+    recorded: Option<Decision>,
+    report: &mut ReplayReport,
+) -> Option<PendingFill> {
+    clock.set(p.at_ms);
+    let recorded_reason = match &recorded {
+        Some(Decision::Skip { reason, .. }) => Some(*reason),
+        _ => None,
+    };
+
+    let decision = match bot.precheck(&p.trade) {
+        Some(skip) => skip,
+        None => {
+            let your_balance = p.balances.iter().find(|(w, _)| *w != p.trade.wallet).map(|(_, b)| *b);
+            let whale_balance = p
+                .balances
+                .iter()
+                .find(|(w, _)| *w == p.trade.wallet)
+                .map(|(_, b)| *b)
+                .unwrap_or(UNKNOWN_WHALE_BALANCE);
+
+            match (&p.market, your_balance) {
+                (Some(market), Some(balance)) => {
+                    bot.size_and_check(&p.trade, market, balance, whale_balance).await
+                }
+                // Reproduce live fetch failures, which count towards the breaker.
+                (None, _) if recorded_reason == Some(SkipReason::MarketUnavailable) => {
+                    bot.risk().record_error("Market fetch failed (replayed)");
+                    recorded.clone()?
+                }
+                (Some(_), None) if recorded_reason == Some(SkipReason::BalanceUnavailable) => {
+                    bot.risk().record_error("Balance fetch failed (replayed)");
+                    recorded.clone()?
+                }
+                _ => {
+                    report.trades += 1;
+                    report.unreplayable += 1;
+                    return None;
+                }
+            }
+        }
+    };
+
+    report.trades += 1;
+    let changed = recorded.as_ref().is_none_or(|r| !r.same_outcome(&decision));
+    if changed {
+        report.changes.push(DecisionChange {
+            seq: p.seq,
+            market_id: p.trade.market_id.clone(),
+            recorded,
+            replayed: decision.clone(),
+        });
+    }
+
+    match decision {
+        Decision::Copy { size_usd, .. } => {
+            report.copied += 1;
+            report.copied_usd += size_usd;
+            Some(PendingFill {
+                trade: p.trade,
+                size_usd,
+            })
+        }
+        Decision::Skip { reason, .. } => {
+            *report.skipped.entry(reason.as_str()).or_default() += 1;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::UsdcAmount;
+    use crate::builder::{BotBuilder, RiskSettings};
+    use crate::types::TradeSide;
+    use std::sync::Arc;
+
+    fn record(seq: u64, event: BotEvent) -> EventRecord {
+        EventRecord {
+            seq,
+            at_ms: 1_700_000_000_000 + seq as i64,
+            event,
+        }
+    }
+
+    fn log_for_trade(start: u64, size_usd: f64) -> Vec<EventRecord> {
+        let trade = Trade {
+            wallet: "0xleader".to_string(),
+            event_id: "event1".to_string(),
+            market_id: "market1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.5,
+            timestamp: 1_700_000_000,
+            tx_hash: None,
+        };
+        let market = Market {
+            id: "market1".to_string(),
+            event_id: "event1".to_string(),
+            question: "Will it?".to_string(),
+            yes_price: 0.5,
+            no_price: 0.5,
+            liquidity: 50_000.0,
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            ..Default::default()
+        };
+        vec![
+            record(start, BotEvent::TradeSeen { trade }),
+            record(start + 1, BotEvent::MarketFetched { market }),
+            record(start + 2, BotEvent::BalanceFetched { wallet: "0xme".to_string(), balance: 1_000.0 }),
+            record(
+                start + 3,
+                BotEvent::Decision {
+                    wallet: "0xleader".to_string(),
+                    market_id: "market1".to_string(),
+                    decision: Decision::Copy { size_usd, shares: size_usd / 0.5 },
+                },
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_replay_detects_changed_decisions() {
+        let mut records = log_for_trade(0, 25.0);
+        records.extend(log_for_trade(4, 25.0));
+
+        let clock = SimClock::default();
+        let bot = BotBuilder::new()
+            .account("0xme", "a".repeat(64))
+            .watch_wallet("0xleader")
+            .with_risk(RiskSettings {
+                max_daily_volume: UsdcAmount(40.0),
+                ..Default::default()
+            })
+            .clock(Arc::new(clock.clone()))
+            .build()
+            .await
+            .unwrap();
+
+        let report = replay(&bot, &clock, &records).await;
+        // Decided at the second trade's arrival, not today
+        assert_eq!(bot.clock().now_ms(), records[4].at_ms);
+        assert_eq!(report.trades, 2);
+        assert_eq!(report.copied, 1);
+        assert_eq!(report.skipped.get("risk_blocked"), Some(&1));
+        assert_eq!(report.changes.len(), 1);
+        assert_eq!(report.changes[0].seq, 4);
+    }
+}
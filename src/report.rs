@@ -0,0 +1,349 @@
+//! `mybot report wallet <addr>`: what a wallet has been trading, to judge
+//! whether it's worth copying before adding it.
+//!
+//! Trades come from the journal (when the wallet was already watched) and
+//! the data API (the trades API without `data_api`), merged on
+//! [`trade_key`]; the data API also lists the wallet's open positions. PnL is an estimate:
+//! each market's buys are costed on average, sells realize against them,
+//! and what's still held is marked to the market's current price. Shares
+//! sold that were bought before the window have no cost and are counted
+//! as unmatched instead of realized.
+
+use crate::api::PolymarketApi;
+use crate::data_api::{self, Position};
+use crate::dedup::trade_key;
+use crate::portfolio::{self, Lot};
+use crate::storage::{Storage, TimeRange};
+use crate::types::{CostBasis, Trade, TradeSide};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+/// Markets listed in the printed report; JSON has all of them.
+const TOP_MARKETS: usize = 10;
+
+/// One market the wallet traded in the window.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MarketActivity {
+    pub market_id: String,
+    pub trades: u64,
+    pub volume_usd: f64,
+    /// Shares still held from buys in the window
+    pub open_shares: f64,
+    pub open_cost: f64,
+    pub realized_pnl: f64,
+    /// `open_shares` at the current price less `open_cost`, when the
+    /// market could be fetched
+    pub unrealized_pnl: Option<f64>,
+    /// Shares sold that weren't bought in the window
+    pub unmatched_shares: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WalletReport {
+    pub wallet: String,
+    /// Unix seconds
+    pub since: i64,
+    pub trades: u64,
+    pub buys: u64,
+    pub sells: u64,
+    pub volume_usd: f64,
+    pub first_trade: Option<i64>,
+    pub last_trade: Option<i64>,
+    /// Trades found in the journal and in the API (before merging)
+    pub from_journal: usize,
+    pub from_api: usize,
+    /// By volume, largest first
+    pub markets: Vec<MarketActivity>,
+    /// Every open position as the data API values it, whenever opened
+    pub positions: Vec<Position>,
+}
+
+impl WalletReport {
+    /// Summarizes `trades` (in any order) made at or after `since`.
+    pub fn build(wallet: &str, since: i64, trades: &[Trade]) -> Self {
+        let mut trades: Vec<&Trade> = trades.iter().filter(|t| t.timestamp >= since).collect();
+        trades.sort_by_key(|t| t.timestamp);
+
+        let mut report = WalletReport {
+            wallet: wallet.to_string(),
+            since,
+            first_trade: trades.first().map(|t| t.timestamp),
+            last_trade: trades.last().map(|t| t.timestamp),
+            ..Default::default()
+        };
+        let mut markets: BTreeMap<&str, (MarketActivity, Vec<Lot>)> = BTreeMap::new();
+        for trade in trades {
+            let notional = trade.shares * trade.price;
+            report.trades += 1;
+            report.volume_usd += notional;
+            let (market, lots) = markets.entry(&trade.market_id).or_insert_with(|| {
+                let activity = MarketActivity {
+                    market_id: trade.market_id.clone(),
+                    ..Default::default()
+                };
+                (activity, Vec::new())
+            });
+            market.trades += 1;
+            market.volume_usd += notional;
+            match trade.side {
+                TradeSide::BUY => {
+                    report.buys += 1;
+                    portfolio::buy(lots, CostBasis::Average, trade.shares, trade.price, trade.timestamp);
+                }
+                TradeSide::SELL => {
+                    report.sells += 1;
+                    let held: f64 = lots.iter().map(|l| l.shares).sum();
+                    market.realized_pnl += portfolio::sell(lots, trade.shares, trade.price);
+                    market.unmatched_shares += (trade.shares - held).max(0.0);
+                }
+            }
+        }
+        report.markets = markets
+            .into_values()
+            .map(|(mut market, lots)| {
+                market.open_shares = lots.iter().map(|l| l.shares).sum();
+                market.open_cost = lots.iter().map(|l| l.shares * l.price).sum();
+                market
+            })
+            .collect();
+        report.markets.sort_by(|a, b| b.volume_usd.total_cmp(&a.volume_usd));
+        report
+    }
+
+    /// Marks a market's open shares to `price`.
+    pub fn mark(&mut self, market_id: &str, price: f64) {
+        if let Some(market) = self.markets.iter_mut().find(|m| m.market_id == market_id) {
+            market.unrealized_pnl = Some(market.open_shares * price - market.open_cost);
+        }
+    }
+
+    pub fn realized_pnl(&self) -> f64 {
+        self.markets.iter().map(|m| m.realized_pnl).sum()
+    }
+
+    /// Of the markets that could be marked.
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.markets.iter().filter_map(|m| m.unrealized_pnl).sum()
+    }
+
+    /// Markets with shares held that couldn't be marked.
+    pub fn unmarked(&self) -> usize {
+        self.markets
+            .iter()
+            .filter(|m| m.open_shares > 1e-9 && m.unrealized_pnl.is_none())
+            .count()
+    }
+}
+
+impl std::fmt::Display for WalletReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let date = |ts: Option<i64>| {
+            ts.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        writeln!(f, "Wallet {}", self.wallet)?;
+        writeln!(
+            f,
+            "Since {} ({} trades from the journal, {} from the API)",
+            date(Some(self.since)),
+            self.from_journal,
+            self.from_api
+        )?;
+        if self.trades == 0 {
+            return writeln!(f, "\nNo trades in this window");
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Trades:         {} ({} buys, {} sells), {} to {}",
+            self.trades,
+            self.buys,
+            self.sells,
+            date(self.first_trade),
+            date(self.last_trade)
+        )?;
+        writeln!(
+            f,
+            "Volume:         ${:.2} (${:.2} per trade)",
+            self.volume_usd,
+            self.volume_usd / self.trades as f64
+        )?;
+        writeln!(f, "Markets:        {}", self.markets.len())?;
+        writeln!(f, "Realized PnL:   ${:+.2}", self.realized_pnl())?;
+        write!(f, "Unrealized PnL: ${:+.2}", self.unrealized_pnl())?;
+        match self.unmarked() {
+            0 => writeln!(f)?,
+            n => writeln!(f, " ({} markets without a price)", n)?,
+        }
+        writeln!(
+            f,
+            "Estimated PnL:  ${:+.2}",
+            self.realized_pnl() + self.unrealized_pnl()
+        )?;
+        let unmatched: f64 = self.markets.iter().map(|m| m.unmatched_shares).sum();
+        if unmatched > 0.0 {
+            writeln!(
+                f,
+                "                ({:.2} shares sold were bought before the window)",
+                unmatched
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<44} {:>6} {:>12} {:>10} {:>12}",
+            "market", "trades", "volume", "open", "pnl"
+        )?;
+        for m in self.markets.iter().take(TOP_MARKETS) {
+            writeln!(
+                f,
+                "{:<44} {:>6} {:>12.2} {:>10.2} {:>+12.2}",
+                m.market_id,
+                m.trades,
+                m.volume_usd,
+                m.open_shares,
+                m.realized_pnl + m.unrealized_pnl.unwrap_or(0.0)
+            )?;
+        }
+        if self.markets.len() > TOP_MARKETS {
+            writeln!(f, "... and {} more", self.markets.len() - TOP_MARKETS)?;
+        }
+        if !self.positions.is_empty() {
+            let value: f64 = self.positions.iter().map(|p| p.current_value).sum();
+            let pnl: f64 = self.positions.iter().map(|p| p.cash_pnl).sum();
+            writeln!(f)?;
+            writeln!(
+                f,
+                "Open positions: {} worth ${:.2} (${:+.2})",
+                self.positions.len(),
+                value,
+                pnl
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Pulls the wallet's trades of the last `since` from the journal (if any)
+/// and the API (`data` when given), and marks what's still held.
+pub async fn wallet_report(
+    api: &PolymarketApi,
+    data: Option<&data_api::Client>,
+    storage: Option<&dyn Storage>,
+    wallet: &str,
+    since: Duration,
+) -> Result<WalletReport> {
+    let since = chrono::Utc::now().timestamp() - since.as_secs() as i64;
+
+    let journal: Vec<Trade> = match storage {
+        Some(storage) => storage
+            .leader_trades(TimeRange::since(since * 1000))
+            .await?
+            .into_iter()
+            .map(|r| r.trade)
+            .filter(|t| t.wallet.eq_ignore_ascii_case(wallet))
+            .collect(),
+        None => Vec::new(),
+    };
+    let fetched = match data {
+        Some(data) => data.trades(wallet, since).await,
+        None => api.get_trades(wallet, since).await,
+    };
+    let fetched = match fetched {
+        Ok(trades) => trades,
+        Err(e) if !journal.is_empty() => {
+            tracing::warn!("Trades API unavailable, reporting from the journal only: {:#}", e);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+
+    // The feed and the API may spell the address differently
+    let mut seen = HashSet::new();
+    let merged: Vec<Trade> = journal
+        .iter()
+        .chain(&fetched)
+        .map(|t| Trade {
+            wallet: wallet.to_lowercase(),
+            ..t.clone()
+        })
+        .filter(|t| seen.insert(trade_key(t)))
+        .collect();
+    let mut report = WalletReport::build(wallet, since, &merged);
+    report.from_journal = journal.len();
+    report.from_api = fetched.len();
+
+    let held: Vec<String> = report
+        .markets
+        .iter()
+        .filter(|m| m.open_shares > 1e-9)
+        .map(|m| m.market_id.clone())
+        .collect();
+    if let Some(data) = data {
+        match data.positions(wallet).await {
+            Ok(positions) => report.positions = positions,
+            Err(e) => tracing::warn!("Failed to fetch positions of {}: {:#}", wallet, e),
+        }
+    }
+    for market_id in held {
+        let priced = report
+            .positions
+            .iter()
+            .find(|p| p.market_id == market_id && p.current_price > 0.0)
+            .map(|p| p.current_price);
+        if let Some(price) = priced {
+            report.mark(&market_id, price);
+            continue;
+        }
+        match api.get_market(&market_id).await {
+            Ok(market) => report.mark(&market_id, market.yes_price),
+            Err(e) => tracing::debug!("No price for {}: {:#}", market_id, e),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(market: &str, side: TradeSide, shares: f64, price: f64, timestamp: i64) -> Trade {
+        Trade {
+            wallet: "0xwhale".to_string(),
+            event_id: "e1".to_string(),
+            market_id: market.to_string(),
+            side,
+            shares,
+            price,
+            timestamp,
+            tx_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_wallet_report() {
+        let trades = [
+            trade("m1", TradeSide::BUY, 100.0, 0.40, 10),
+            trade("m1", TradeSide::BUY, 100.0, 0.60, 20),
+            trade("m1", TradeSide::SELL, 50.0, 0.70, 30),
+            trade("m2", TradeSide::SELL, 10.0, 0.90, 40),
+            trade("m3", TradeSide::BUY, 10.0, 0.10, 5),
+        ];
+        let mut report = WalletReport::build("0xwhale", 10, &trades);
+        assert_eq!((report.trades, report.buys, report.sells), (4, 2, 2));
+        assert!((report.volume_usd - 144.0).abs() < 1e-9);
+        assert_eq!(report.markets[0].market_id, "m1");
+        assert!((report.realized_pnl() - 10.0).abs() < 1e-9);
+        assert_eq!(report.markets[1].unmatched_shares, 10.0);
+        assert_eq!(report.unmarked(), 1);
+
+        report.mark("m1", 0.30);
+        assert!((report.unrealized_pnl() - -30.0).abs() < 1e-9);
+        assert_eq!(report.unmarked(), 0);
+        assert!(report.to_string().contains("Estimated PnL:  $-20.00"));
+    }
+}
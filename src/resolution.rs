@@ -0,0 +1,556 @@
+//! Settling live positions when their markets resolve.
+//!
+//! Paper trading settles its own positions (see [`crate::paper`]); live
+//! trading has this watcher. Every `resolution_interval` it asks the market
+//! API whether each held market has resolved. A resolved position is booked
+//! as won or lost at its payout (1 a share for the winning outcome, 0 for
+//! the losing one): the portfolio closes it, the journal gets the
+//! redemption as a sell without an order, and the settlement PnL counts
+//! against the daily loss limit.
+//!
+//! Won shares still have to be redeemed on chain for their USDC. With
+//! `auto_redeem` on, each is queued and `redeemPositions` sent to the CTF
+//! contract once the oracle's payout has been reported there (the API
+//! tends to know before the chain does); failed attempts are retried the
+//! next round, and the gas spent goes to the cash flow ledger (see
+//! [`crate::cashflow`]). Lost shares pay nothing and aren't redeemed. Resolved
+//! positions and the queue are kept in the journal's state and survive
+//! restarts.
+
+use crate::api::PolymarketApi;
+use crate::cashflow;
+use crate::clock::Clock;
+use crate::doctor::USDC_CONTRACT;
+use crate::exchange;
+use crate::portfolio::Portfolio;
+use crate::recovery::CTF_CONTRACT;
+use crate::risk::RiskManager;
+use crate::rpc::{self, Metered, RpcStats};
+use crate::storage::{FillRecord, Storage};
+use crate::types::{Config, TradeSide, Venue};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::abi::Token;
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Middleware, Provider, Ws};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, Bytes, TransactionRequest, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Where resolved positions are kept in the journal's state.
+pub const RESOLUTIONS_STATE_KEY: &str = "resolutions";
+
+/// Polygon mainnet.
+const CHAIN_ID: u64 = 137;
+
+/// `payoutDenominator(bytes32)`; non-zero once a condition's payout is
+/// reported.
+const PAYOUT_DENOMINATOR_SELECTOR: [u8; 4] = [0xdd, 0x34, 0xde, 0x67];
+
+/// `redeemPositions(address,bytes32,bytes32,uint256[])`
+const REDEEM_POSITIONS_SELECTOR: [u8; 4] = [0x01, 0xb7, 0x03, 0x7c];
+
+/// Where a resolved position's redemption stands.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum Redemption {
+    /// Lost shares pay nothing, so aren't redeemed
+    NotNeeded,
+    /// Queued; with `auto_redeem` off it's left to be redeemed by hand
+    Pending {
+        attempts: u32,
+    },
+    Redeemed {
+        tx_hash: String,
+        at: i64,
+    },
+}
+
+/// A held market booked at its resolution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedPosition {
+    pub market_id: String,
+    pub shares: f64,
+    pub payout_per_share: f64,
+    /// Net of what the shares cost
+    pub realized_pnl: f64,
+    /// Unix ms
+    pub resolved_at: i64,
+    pub redemption: Redemption,
+}
+
+impl ResolvedPosition {
+    pub fn won(&self) -> bool {
+        self.payout_per_share > 0.0
+    }
+}
+
+/// A mined redemption.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedeemTx {
+    pub tx_hash: String,
+    /// POL spent on gas
+    pub gas: f64,
+}
+
+/// Sends redemptions to the chain.
+#[async_trait]
+pub trait Redeemer: Send + Sync {
+    /// Whether the condition's payout has been reported, so it can be
+    /// redeemed.
+    async fn redeemable(&self, condition_id: &str) -> Result<bool>;
+
+    /// Redeems the wallet's shares in the condition; returns the
+    /// transaction once it's mined.
+    async fn redeem(&self, condition_id: &str) -> Result<RedeemTx>;
+}
+
+/// Redeems through the CTF contract, signing with `private_key`.
+pub struct ChainRedeemer {
+    client: SignerMiddleware<Provider<Metered<Ws>>, LocalWallet>,
+    contract: Address,
+    collateral: Address,
+}
+
+impl ChainRedeemer {
+    pub async fn connect(config: &Config, stats: &Arc<RpcStats>) -> Result<Self> {
+        let wallet: LocalWallet = config
+            .private_key
+            .trim_start_matches("0x")
+            .parse()
+            .context("private key is not a 32-byte hex key")?;
+        let provider = rpc::connect_ws(&config.rpc_url, stats).await?;
+        Ok(Self {
+            client: SignerMiddleware::new(provider, wallet.with_chain_id(CHAIN_ID)),
+            contract: CTF_CONTRACT.parse()?,
+            collateral: USDC_CONTRACT.parse()?,
+        })
+    }
+}
+
+fn condition(condition_id: &str) -> Result<H256> {
+    condition_id
+        .parse()
+        .with_context(|| format!("Invalid condition id {}", condition_id))
+}
+
+/// Calldata redeeming both outcomes of a binary condition for USDC.
+fn redeem_calldata(collateral: Address, condition_id: H256) -> Vec<u8> {
+    let mut data = REDEEM_POSITIONS_SELECTOR.to_vec();
+    data.extend(ethers::abi::encode(&[
+        Token::Address(collateral),
+        Token::FixedBytes(vec![0u8; 32]),
+        Token::FixedBytes(condition_id.as_bytes().to_vec()),
+        Token::Array(vec![Token::Uint(U256::from(1)), Token::Uint(U256::from(2))]),
+    ]));
+    data
+}
+
+#[async_trait]
+impl Redeemer for ChainRedeemer {
+    async fn redeemable(&self, condition_id: &str) -> Result<bool> {
+        let mut data = PAYOUT_DENOMINATOR_SELECTOR.to_vec();
+        data.extend_from_slice(condition(condition_id)?.as_bytes());
+        let tx = TransactionRequest::new().to(self.contract).data(Bytes::from(data));
+        let out = self
+            .client
+            .call(&tx.into(), None)
+            .await
+            .context("payoutDenominator call failed")?;
+        Ok(!U256::from_big_endian(&out).is_zero())
+    }
+
+    async fn redeem(&self, condition_id: &str) -> Result<RedeemTx> {
+        let data = redeem_calldata(self.collateral, condition(condition_id)?);
+        let tx = TransactionRequest::new().to(self.contract).data(Bytes::from(data));
+        let pending = self
+            .client
+            .send_transaction(tx, None)
+            .await
+            .context("Failed to send redeemPositions")?;
+        let hash = format!("{:?}", pending.tx_hash());
+        let receipt = pending
+            .await
+            .context("Failed to confirm redeemPositions")?
+            .with_context(|| format!("redeemPositions {} was dropped", hash))?;
+        anyhow::ensure!(receipt.status == Some(1.into()), "redeemPositions {} reverted", hash);
+        let wei = receipt.gas_used.unwrap_or_default() * receipt.effective_gas_price.unwrap_or_default();
+        Ok(RedeemTx {
+            tx_hash: hash,
+            gas: wei.as_u128() as f64 / 1e18,
+        })
+    }
+}
+
+/// Watches held markets for resolution and redeems what they won.
+pub struct Resolutions {
+    api: PolymarketApi,
+    portfolio: Arc<Portfolio>,
+    risk: Arc<RiskManager>,
+    storage: Option<Arc<dyn Storage>>,
+    rpc: Arc<RpcStats>,
+    clock: Arc<dyn Clock>,
+    config: Config,
+    resolved: Mutex<Vec<ResolvedPosition>>,
+}
+
+impl Resolutions {
+    pub fn from_config(
+        config: &Config,
+        api: PolymarketApi,
+        portfolio: Arc<Portfolio>,
+        risk: Arc<RiskManager>,
+        storage: Option<Arc<dyn Storage>>,
+        rpc: Arc<RpcStats>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            api,
+            portfolio,
+            risk,
+            storage,
+            rpc,
+            clock,
+            config: config.clone(),
+            resolved: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Picks up what a previous run resolved, if anything.
+    pub async fn load(&self) -> Result<()> {
+        let Some(storage) = &self.storage else { return Ok(()) };
+        if let Some(json) = storage.load_state(RESOLUTIONS_STATE_KEY).await? {
+            let resolved = serde_json::from_str(&json).context("Stored resolutions are corrupt")?;
+            *self.resolved.lock().unwrap() = resolved;
+        }
+        Ok(())
+    }
+
+    /// Every position booked at resolution, oldest first.
+    pub fn resolved(&self) -> Vec<ResolvedPosition> {
+        self.resolved.lock().unwrap().clone()
+    }
+
+    /// Whether `market_id` was booked at its resolution; the chain may
+    /// still hold its shares.
+    pub fn is_resolved(&self, market_id: &str) -> bool {
+        self.resolved.lock().unwrap().iter().any(|r| r.market_id == market_id)
+    }
+
+    /// Books every held market that has resolved; returns the positions
+    /// booked.
+    pub async fn settle(&self) -> Vec<ResolvedPosition> {
+        let mut settled = Vec::new();
+        // Kalshi settles its own markets in cash; only Polymarket's are booked here
+        let holdings = self.portfolio.holdings().into_iter();
+        for holding in holdings.filter(|h| exchange::venue_of(&h.market_id) == Venue::Polymarket) {
+            let payout = match self.api.get_resolution(&holding.market_id).await {
+                Ok(Some(payout)) => payout,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!("Failed to check {} for resolution: {}", holding.market_id, e);
+                    continue;
+                }
+            };
+            let shares = holding.shares();
+            let now = self.clock.now_ms();
+            let realized_pnl = self.portfolio.apply_redemption(&holding.market_id, payout, now).await;
+            self.risk.record_realized_pnl(realized_pnl);
+            if let Some(storage) = &self.storage {
+                let redemption = FillRecord {
+                    id: 0,
+                    order_id: 0,
+                    market_id: holding.market_id.clone(),
+                    side: TradeSide::SELL.as_str().to_string(),
+                    shares,
+                    price: payout,
+                    fee: 0.0,
+                    filled_at: now,
+                };
+                if let Err(e) = storage.record_fill(&redemption).await {
+                    tracing::warn!("Failed to journal the redemption of {}: {}", holding.market_id, e);
+                }
+            }
+            let position = ResolvedPosition {
+                market_id: holding.market_id.clone(),
+                shares,
+                payout_per_share: payout,
+                realized_pnl,
+                resolved_at: now,
+                redemption: if payout > 0.0 {
+                    Redemption::Pending { attempts: 0 }
+                } else {
+                    Redemption::NotNeeded
+                },
+            };
+            self.resolved.lock().unwrap().push(position.clone());
+            settled.push(position);
+        }
+        if !settled.is_empty() {
+            self.save().await;
+        }
+        settled
+    }
+
+    /// Sends every queued redemption whose payout the chain has; returns
+    /// the markets redeemed.
+    pub async fn redeem_pending(&self, redeemer: &dyn Redeemer) -> Vec<String> {
+        let pending: Vec<String> = self
+            .resolved
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| matches!(r.redemption, Redemption::Pending { .. }))
+            .map(|r| r.market_id.clone())
+            .collect();
+        let mut redeemed = Vec::new();
+        for market_id in pending {
+            let outcome = async {
+                let condition_id = self.api.get_condition_id(&market_id).await?;
+                if !redeemer.redeemable(&condition_id).await? {
+                    return Ok(None);
+                }
+                redeemer.redeem(&condition_id).await.map(Some)
+            }
+            .await;
+            let redemption = match outcome {
+                Ok(None) => continue,
+                Ok(Some(tx)) => {
+                    tracing::info!("💰 Redeemed {} in {}", market_id, tx.tx_hash);
+                    redeemed.push(market_id.clone());
+                    let at = self.clock.now_ms();
+                    if let Some(storage) = &self.storage {
+                        let gas = cashflow::record_gas(storage.as_ref(), &tx.tx_hash, tx.gas, self.config.gas_token_usd, at);
+                        if let Err(e) = gas.await {
+                            tracing::warn!("Failed to journal gas for {}: {}", tx.tx_hash, e);
+                        }
+                    }
+                    Redemption::Redeemed { tx_hash: tx.tx_hash, at }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to redeem {}: {:#}", market_id, e);
+                    let attempts = self.attempts(&market_id);
+                    Redemption::Pending { attempts: attempts + 1 }
+                }
+            };
+            if let Some(position) = self
+                .resolved
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|r| r.market_id == market_id)
+            {
+                position.redemption = redemption;
+            }
+            self.save().await;
+        }
+        redeemed
+    }
+
+    fn attempts(&self, market_id: &str) -> u32 {
+        match self.resolved.lock().unwrap().iter().find(|r| r.market_id == market_id) {
+            Some(ResolvedPosition {
+                redemption: Redemption::Pending { attempts },
+                ..
+            }) => *attempts,
+            _ => 0,
+        }
+    }
+
+    /// Settles every `resolution_interval` and, with `auto_redeem` on,
+    /// redeems what's queued; a zero interval disables it.
+    pub fn spawn(self: Arc<Self>) {
+        if self.config.resolution_interval.is_zero() {
+            return;
+        }
+        if self.config.auto_redeem && !self.config.rpc_url.starts_with("ws") {
+            tracing::warn!("⚠️  Redeeming resolved positions needs a ws:// or wss:// RPC_URL; redeem them by hand");
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.resolution_interval);
+            loop {
+                interval.tick().await;
+                for p in self.settle().await {
+                    tracing::info!(
+                        "🏁 Position in {} resolved {}: {:.2} shares at ${:.2}, PnL ${:.2}",
+                        p.market_id,
+                        if p.won() { "won" } else { "lost" },
+                        p.shares,
+                        p.payout_per_share,
+                        p.realized_pnl
+                    );
+                }
+                let pending = self
+                    .resolved
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|r| matches!(r.redemption, Redemption::Pending { .. }));
+                if !pending || !self.config.auto_redeem || !self.config.rpc_url.starts_with("ws") {
+                    continue;
+                }
+                match ChainRedeemer::connect(&self.config, &self.rpc).await {
+                    Ok(redeemer) => {
+                        self.redeem_pending(&redeemer).await;
+                    }
+                    Err(e) => tracing::warn!("Failed to connect to redeem positions: {:#}", e),
+                }
+            }
+        });
+    }
+
+    /// State writes never block trading; failures are only warned about.
+    async fn save(&self) {
+        let Some(storage) = &self.storage else { return };
+        let json = match serde_json::to_string(&*self.resolved.lock().unwrap()) {
+            Ok(json) => json,
+            Err(e) => return tracing::warn!("Failed to encode resolved positions: {}", e),
+        };
+        if let Err(e) = storage
+            .save_state(RESOLUTIONS_STATE_KEY, &json, self.clock.now_ms())
+            .await
+        {
+            tracing::warn!("Failed to save resolved positions: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimClock;
+    use crate::http::{serve, Handler, Request, Response};
+    use crate::storage::sqlite::SqliteStore;
+    use crate::storage::TimeRange;
+    use crate::types::CostBasis;
+    use serde_json::json;
+
+    /// The market API, with "won" and "lost" resolved and "open" not.
+    struct MarketApi;
+
+    #[async_trait]
+    impl Handler for MarketApi {
+        async fn handle(&self, request: &Request) -> Option<Response> {
+            let body = match request.path.as_str() {
+                "/markets/won" => json!({ "closed": true, "outcome_prices": ["1", "0"], "condition_id": "0x01" }),
+                "/markets/lost" => json!({ "closed": true, "outcome_prices": ["0", "1"], "condition_id": "0x02" }),
+                "/markets/open" => json!({ "closed": false, "condition_id": "0x03" }),
+                _ => return None,
+            };
+            Some(Response::json(200, &body))
+        }
+    }
+
+    /// Reports payouts for conditions in `reported` and redeems them.
+    struct Chain {
+        reported: Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait]
+    impl Redeemer for Chain {
+        async fn redeemable(&self, condition_id: &str) -> Result<bool> {
+            Ok(self.reported.lock().unwrap().contains(&condition_id))
+        }
+
+        async fn redeem(&self, condition_id: &str) -> Result<RedeemTx> {
+            Ok(RedeemTx {
+                tx_hash: format!("tx-{}", condition_id),
+                gas: 0.01,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_settles_resolved_positions_and_redeems_once_reported() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        serve(&format!("127.0.0.1:{}", port), vec![Arc::new(MarketApi)])
+            .await
+            .unwrap();
+        let api = PolymarketApi::new(format!("http://127.0.0.1:{}", port));
+        let storage: Arc<dyn Storage> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Fifo, None));
+        for market_id in ["won", "lost", "open"] {
+            let fill = FillRecord {
+                id: 0,
+                order_id: 1,
+                market_id: market_id.to_string(),
+                side: "BUY".to_string(),
+                shares: 10.0,
+                price: 0.4,
+                fee: 0.0,
+                filled_at: 0,
+            };
+            portfolio.apply_fill(&fill).await;
+        }
+        let config = Config::default();
+        let risk = Arc::new(RiskManager::new(config.clone()));
+        let resolutions = Resolutions::from_config(
+            &config,
+            api.clone(),
+            portfolio.clone(),
+            risk,
+            Some(storage.clone()),
+            Arc::new(RpcStats::new()),
+            Arc::new(SimClock::at(1_700_000_000_000)),
+        );
+
+        let settled = resolutions.settle().await;
+        assert_eq!(settled.len(), 2);
+        let won = settled.iter().find(|p| p.market_id == "won").unwrap();
+        assert!(won.won());
+        assert!((won.realized_pnl - 6.0).abs() < 1e-9);
+        assert_eq!(won.redemption, Redemption::Pending { attempts: 0 });
+        let lost = settled.iter().find(|p| p.market_id == "lost").unwrap();
+        assert!((lost.realized_pnl + 4.0).abs() < 1e-9);
+        assert_eq!(lost.redemption, Redemption::NotNeeded);
+        assert_eq!(portfolio.holdings().len(), 1);
+        assert!(resolutions.is_resolved("lost") && !resolutions.is_resolved("open"));
+        // Journaled as redemptions, and nothing is booked twice
+        assert_eq!(storage.fills(TimeRange::all()).await.unwrap().len(), 2);
+        assert!(resolutions.settle().await.is_empty());
+
+        // Nothing is sent until the chain has the payout
+        let chain = Chain {
+            reported: Mutex::new(Vec::new()),
+        };
+        assert!(resolutions.redeem_pending(&chain).await.is_empty());
+        chain.reported.lock().unwrap().push("0x01");
+        assert_eq!(resolutions.redeem_pending(&chain).await, vec!["won"]);
+        assert!(resolutions.redeem_pending(&chain).await.is_empty());
+        // Its gas is in the cash flow ledger
+        let gas = storage.cash_flows(TimeRange::all()).await.unwrap();
+        assert_eq!((gas.len(), gas[0].kind.as_str()), (1, cashflow::GAS));
+        assert!((gas[0].amount + 0.01 * config.gas_token_usd).abs() < 1e-9);
+
+        // A restart picks up where it left off
+        let reloaded = Resolutions::from_config(
+            &config,
+            api,
+            portfolio,
+            Arc::new(RiskManager::new(config.clone())),
+            Some(storage),
+            Arc::new(RpcStats::new()),
+            Arc::new(SimClock::at(0)),
+        );
+        reloaded.load().await.unwrap();
+        let won = reloaded.resolved().into_iter().find(|p| p.market_id == "won").unwrap();
+        assert!(matches!(won.redemption, Redemption::Redeemed { tx_hash, .. } if tx_hash == "tx-0x01"));
+
+        assert_eq!(
+            REDEEM_POSITIONS_SELECTOR,
+            ethers::utils::id("redeemPositions(address,bytes32,bytes32,uint256[])")
+        );
+        assert_eq!(
+            PAYOUT_DENOMINATOR_SELECTOR,
+            ethers::utils::id("payoutDenominator(bytes32)")
+        );
+        let calldata = redeem_calldata(Address::zero(), H256::zero());
+        // Selector, four head words, then the two index sets
+        assert_eq!(calldata.len(), 4 + 32 * 7);
+    }
+}
@@ -0,0 +1,136 @@
+//! Keeps the database from growing without bound: expired rows are archived
+//! to parquet, deleted, and the freed space is returned by a vacuum.
+
+use crate::archive;
+use crate::storage::{Storage, TimeRange};
+use crate::types::Config;
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Raw WebSocket frames older than this are removed (zero keeps them).
+    pub ws_frames: Duration,
+    /// Journal rows older than this are removed (zero keeps them).
+    pub journal: Duration,
+    /// Price samples older than this are removed (zero keeps them).
+    pub prices: Duration,
+    /// Where expired rows are archived first; `None` deletes without archiving.
+    pub archive_dir: Option<PathBuf>,
+    pub interval: Duration,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            ws_frames: config.ws_frame_retention,
+            journal: config.journal_retention,
+            prices: config.price_retention,
+            archive_dir: (!config.archive_dir.is_empty()).then(|| PathBuf::from(&config.archive_dir)),
+            interval: config.compaction_interval,
+        }
+    }
+
+    /// Nothing would ever be deleted.
+    pub fn keeps_everything(&self) -> bool {
+        self.ws_frames.is_zero() && self.journal.is_zero() && self.prices.is_zero()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompactionReport {
+    pub frames_deleted: u64,
+    pub journal_rows_deleted: usize,
+    pub prices_deleted: u64,
+    pub archives: Vec<PathBuf>,
+}
+
+impl CompactionReport {
+    pub fn removed_anything(&self) -> bool {
+        self.frames_deleted > 0 || self.journal_rows_deleted > 0 || self.prices_deleted > 0
+    }
+}
+
+/// Runs one archive-delete-vacuum pass as of `now_ms`.
+pub async fn compact(storage: &dyn Storage, policy: &RetentionPolicy, now_ms: i64) -> Result<CompactionReport> {
+    let mut report = CompactionReport::default();
+    let stamp = chrono::DateTime::from_timestamp_millis(now_ms)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%S")
+        .to_string();
+
+    if !policy.ws_frames.is_zero() {
+        let cutoff = now_ms - policy.ws_frames.as_millis() as i64;
+        if let Some(dir) = &policy.archive_dir {
+            let frames = storage
+                .frames(TimeRange {
+                    from_ms: 0,
+                    to_ms: cutoff - 1,
+                })
+                .await?;
+            report.archives.extend(archive::write_frames(dir, &frames, &stamp)?);
+        }
+        report.frames_deleted = storage.delete_frames_before(cutoff).await?;
+    }
+
+    if !policy.journal.is_zero() {
+        let cutoff = now_ms - policy.journal.as_millis() as i64;
+        let expired = storage.expired_journal(cutoff).await?;
+        if !expired.is_empty() {
+            if let Some(dir) = &policy.archive_dir {
+                report.archives.extend(archive::write_journal(dir, &expired, &stamp)?);
+            }
+            storage.delete_journal(&expired).await?;
+            report.journal_rows_deleted = expired.len();
+        }
+    }
+
+    if !policy.prices.is_zero() {
+        let cutoff = now_ms - policy.prices.as_millis() as i64;
+        if let Some(dir) = &policy.archive_dir {
+            let samples = storage
+                .prices(
+                    None,
+                    TimeRange {
+                        from_ms: 0,
+                        to_ms: cutoff - 1,
+                    },
+                )
+                .await?;
+            report.archives.extend(archive::write_prices(dir, &samples, &stamp)?);
+        }
+        report.prices_deleted = storage.delete_prices_before(cutoff).await?;
+        // Bars go with the samples of the same age
+        report.prices_deleted += storage.delete_bars_before(cutoff).await?;
+    }
+
+    if report.removed_anything() {
+        storage.vacuum().await?;
+    }
+
+    Ok(report)
+}
+
+/// Compacts every `policy.interval`, starting one interval from now.
+pub fn spawn(storage: Arc<dyn Storage>, policy: RetentionPolicy) {
+    tokio::spawn(async move {
+        let start = tokio::time::Instant::now() + policy.interval;
+        let mut interval = tokio::time::interval_at(start, policy.interval);
+        loop {
+            interval.tick().await;
+            match compact(storage.as_ref(), &policy, chrono::Utc::now().timestamp_millis()).await {
+                Ok(r) if r.removed_anything() => tracing::info!(
+                    "🧹 Compaction removed {} frames, {} journal rows and {} price samples ({} archive files)",
+                    r.frames_deleted,
+                    r.journal_rows_deleted,
+                    r.prices_deleted,
+                    r.archives.len()
+                ),
+                Ok(_) => tracing::debug!("Compaction found nothing to remove"),
+                Err(e) => tracing::warn!("Compaction failed: {}", e),
+            }
+        }
+    });
+}
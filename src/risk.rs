@@ -1,29 +1,93 @@
-use crate::types::{Config, CircuitBreakerState, Trade, Market};
+use crate::liquidity::LiquidityStats;
+use crate::types::{Config, CircuitBreakerState, Trade, TradeSide, Market};
 use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+
+/// Key of the persisted [`RiskSnapshot`] in the state store.
+pub const RISK_STATE_KEY: &str = "risk";
+
+/// Risk counters as kept across restarts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskSnapshot {
+    /// UTC day ("YYYY-MM-DD") the daily counters belong to
+    pub day: String,
+    pub state: CircuitBreakerState,
+    pub event_exposure: HashMap<String, f64>,
+}
+
+/// How far the bot is from each risk limit; `None` where a limit is off.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RiskHeadroom {
+    pub tripped: bool,
+    pub trip_reason: Option<String>,
+    pub volume_today: f64,
+    pub volume_left: f64,
+    pub trades_today: u32,
+    pub realized_pnl_today: f64,
+    pub loss_left: Option<f64>,
+    pub consecutive_errors: u32,
+    pub errors_until_trip: u32,
+    /// Room left under `max_exposure_per_event` in events with exposure
+    pub event_exposure_left: HashMap<String, f64>,
+}
 
 pub struct RiskManager {
-    config: Config,
+    config: RwLock<Config>,
     state: Arc<Mutex<CircuitBreakerState>>,
     event_exposure: Arc<Mutex<HashMap<String, f64>>>,
+    liquidity: Option<Arc<LiquidityStats>>,
+    /// UTC day the daily counters are kept for, once known
+    day: Mutex<Option<chrono::NaiveDate>>,
 }
 
 impl RiskManager {
     pub fn new(config: Config) -> Self {
         Self {
-            config,
+            config: RwLock::new(config),
             state: Arc::new(Mutex::new(CircuitBreakerState {
                 consecutive_errors: 0,
                 total_trades_today: 0,
                 total_volume_today: 0.0,
                 is_tripped: false,
                 trip_reason: None,
+                realized_pnl_today: 0.0,
             })),
             event_exposure: Arc::new(Mutex::new(HashMap::new())),
+            liquidity: None,
+            day: Mutex::new(None),
         }
     }
+
+    /// Checks copies against the markets' recent liquidity.
+    pub fn with_liquidity(mut self, liquidity: Arc<LiquidityStats>) -> Self {
+        self.liquidity = Some(liquidity);
+        self
+    }
+
+    pub fn liquidity(&self) -> Option<&Arc<LiquidityStats>> {
+        self.liquidity.as_ref()
+    }
     
+    fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap()
+    }
+
+    /// Applies new limits, e.g. after a config reload; counters are kept.
+    pub fn reconfigure(&self, config: Config) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Starts or stops accepting trades from `wallet`.
+    pub fn set_tracked(&self, wallet: &str, tracked: bool) {
+        let mut config = self.config.write().unwrap();
+        config.wallets_to_track.retain(|w| w != wallet);
+        if tracked {
+            config.wallets_to_track.push(wallet.to_string());
+        }
+    }
+
     pub fn check_can_trade(&self, trade: &Trade, market: &Market, size_usd: f64) -> Result<()> {
         // Check if circuit breaker is tripped
         {
@@ -36,9 +100,9 @@ impl RiskManager {
         // Check daily volume limit
         {
             let state = self.state.lock().unwrap();
-            if state.total_volume_today + size_usd > self.config.max_daily_volume {
+            if state.total_volume_today + size_usd > self.config().max_daily_volume.as_f64() {
                 bail!("Daily volume limit exceeded: ${:.2} + ${:.2} > ${:.2}",
-                    state.total_volume_today, size_usd, self.config.max_daily_volume);
+                    state.total_volume_today, size_usd, self.config().max_daily_volume.as_f64());
             }
         }
         
@@ -46,29 +110,58 @@ impl RiskManager {
         {
             let exposure = self.event_exposure.lock().unwrap();
             let current_exposure = exposure.get(&trade.event_id).copied().unwrap_or(0.0);
-            if current_exposure + size_usd > self.config.max_exposure_per_event {
+            if current_exposure + size_usd > self.config().max_exposure_per_event.as_f64() {
                 bail!("Event exposure limit exceeded: ${:.2} + ${:.2} > ${:.2}",
-                    current_exposure, size_usd, self.config.max_exposure_per_event);
+                    current_exposure, size_usd, self.config().max_exposure_per_event.as_f64());
             }
         }
         
         // Check market liquidity
-        if market.liquidity < self.config.min_liquidity {
+        if market.liquidity < self.config().min_liquidity {
             bail!("Insufficient liquidity: ${:.2} < ${:.2}",
-                market.liquidity, self.config.min_liquidity);
+                market.liquidity, self.config().min_liquidity);
         }
         
         // Check orderbook depth
-        let depth_ok = market.liquidity >= self.config.cb_min_depth_usd;
+        let depth_ok = market.liquidity >= self.config().cb_min_depth_usd;
         if !depth_ok {
             bail!("Orderbook depth too low: ${:.2} < ${:.2}",
-                market.liquidity, self.config.cb_min_depth_usd);
+                market.liquidity, self.config().cb_min_depth_usd);
         }
         
         tracing::info!("Risk checks passed for trade on {}", trade.market_id);
         Ok(())
     }
     
+    /// The size a copy on `side` of `market_id` may have given the market's
+    /// liquidity at `now`: `size_usd`, or less if it would take more than
+    /// `max_book_share` of the depth. Fails when the market is too wide or
+    /// too quiet, or the cut size is under `min_stake`.
+    pub fn check_liquidity(&self, market_id: &str, side: &TradeSide, size_usd: f64, now: i64) -> Result<f64> {
+        let Some(quality) = self.liquidity.as_ref().and_then(|l| l.quality(market_id, now)) else {
+            return Ok(size_usd);
+        };
+        let config = self.config();
+        if config.max_spread > 0.0 && quality.spread > config.max_spread {
+            bail!("Spread too wide: {:.3} > {:.3}", quality.spread, config.max_spread);
+        }
+        if config.min_trades_per_hour > 0.0 && quality.trades_per_hour < config.min_trades_per_hour {
+            bail!("Market too quiet: {:.1} trades/h < {:.1}",
+                quality.trades_per_hour, config.min_trades_per_hour);
+        }
+        let cap = quality.depth_for(side) * config.max_book_share;
+        if config.max_book_share <= 0.0 || size_usd <= cap {
+            return Ok(size_usd);
+        }
+        if cap < config.min_stake.as_f64() {
+            bail!("Book too thin: {:.0}% of ${:.2} depth is under the ${:.2} minimum stake",
+                config.max_book_share * 100.0, quality.depth_for(side), config.min_stake.as_f64());
+        }
+        tracing::info!("💧 Cutting ${:.2} to ${:.2}, {:.0}% of ${:.2} depth",
+            size_usd, cap, config.max_book_share * 100.0, quality.depth_for(side));
+        Ok(cap)
+    }
+
     pub fn record_trade(&self, trade: &Trade, size_usd: f64) {
         let mut state = self.state.lock().unwrap();
         state.total_trades_today += 1;
@@ -92,13 +185,37 @@ impl RiskManager {
         
         tracing::warn!("Error recorded: {} (consecutive: {})", error, state.consecutive_errors);
         
-        if state.consecutive_errors >= self.config.cb_consecutive_trigger {
+        if state.consecutive_errors >= self.config().cb_consecutive_trigger {
             state.is_tripped = true;
             state.trip_reason = Some(format!("Too many consecutive errors: {}", state.consecutive_errors));
             tracing::error!("CIRCUIT BREAKER TRIPPED: {}", state.trip_reason.as_ref().unwrap());
         }
     }
     
+    /// Adds realized PnL and trips the breaker once the daily loss limit is hit.
+    pub fn record_realized_pnl(&self, pnl: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.realized_pnl_today += pnl;
+        
+        let limit = self.config().max_daily_loss.as_f64();
+        if limit > 0.0 && -state.realized_pnl_today >= limit && !state.is_tripped {
+            state.is_tripped = true;
+            state.trip_reason = Some(format!(
+                "Daily loss limit reached: ${:.2} >= ${:.2}",
+                -state.realized_pnl_today, limit
+            ));
+            tracing::error!("CIRCUIT BREAKER TRIPPED: {}", state.trip_reason.as_ref().unwrap());
+        }
+    }
+    
+    /// Trips the breaker by hand (the kill switch); only a reset clears it.
+    pub fn trip(&self, reason: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.is_tripped = true;
+        state.trip_reason = Some(reason.to_string());
+        tracing::error!("CIRCUIT BREAKER TRIPPED: {}", reason);
+    }
+
     pub fn reset_circuit_breaker(&self) {
         let mut state = self.state.lock().unwrap();
         state.is_tripped = false;
@@ -111,33 +228,95 @@ impl RiskManager {
         let mut state = self.state.lock().unwrap();
         state.total_trades_today = 0;
         state.total_volume_today = 0.0;
+        state.realized_pnl_today = 0.0;
         
         let mut exposure = self.event_exposure.lock().unwrap();
         exposure.clear();
         
         tracing::info!("Daily stats reset");
     }
+
+    /// Resets the daily counters if `today` isn't the UTC day they were kept
+    /// for; returns whether it did. The first call only notes the day.
+    pub fn roll_over(&self, today: chrono::NaiveDate) -> bool {
+        let previous = self.day.lock().unwrap().replace(today);
+        match previous {
+            Some(day) if day != today => {
+                self.reset_daily_stats();
+                true
+            }
+            _ => false,
+        }
+    }
     
     pub fn get_state(&self) -> CircuitBreakerState {
         self.state.lock().unwrap().clone()
     }
+
+    pub fn headroom(&self) -> RiskHeadroom {
+        let state = self.get_state();
+        let limit = self.config().max_daily_loss.as_f64();
+        RiskHeadroom {
+            tripped: state.is_tripped,
+            trip_reason: state.trip_reason,
+            volume_today: state.total_volume_today,
+            volume_left: (self.config().max_daily_volume.as_f64() - state.total_volume_today).max(0.0),
+            trades_today: state.total_trades_today,
+            realized_pnl_today: state.realized_pnl_today,
+            loss_left: (limit > 0.0).then(|| (limit + state.realized_pnl_today.min(0.0)).max(0.0)),
+            consecutive_errors: state.consecutive_errors,
+            errors_until_trip: self.config().cb_consecutive_trigger.saturating_sub(state.consecutive_errors),
+            event_exposure_left: self
+                .event_exposure
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(event, used)| (event.clone(), (self.config().max_exposure_per_event.as_f64() - used).max(0.0)))
+                .collect(),
+        }
+    }
+    
+    pub fn snapshot(&self, today: chrono::NaiveDate) -> RiskSnapshot {
+        RiskSnapshot {
+            day: today.to_string(),
+            state: self.get_state(),
+            event_exposure: self.event_exposure.lock().unwrap().clone(),
+        }
+    }
+    
+    /// Restores saved counters. Daily counters from another day are dropped,
+    /// but a tripped breaker stays tripped until reset by hand.
+    pub fn restore(&self, snapshot: RiskSnapshot, today: chrono::NaiveDate) {
+        *self.day.lock().unwrap() = Some(today);
+        let mut state = self.state.lock().unwrap();
+        let mut exposure = self.event_exposure.lock().unwrap();
+        if snapshot.day == today.to_string() {
+            *state = snapshot.state;
+            *exposure = snapshot.event_exposure;
+        } else {
+            state.consecutive_errors = snapshot.state.consecutive_errors;
+            state.is_tripped = snapshot.state.is_tripped;
+            state.trip_reason = snapshot.state.trip_reason;
+        }
+    }
     
     pub fn is_whale_verified(&self, wallet: &str) -> bool {
         // Check if wallet is in our tracked list
-        self.config.wallets_to_track.contains(&wallet.to_string())
+        self.config().wallets_to_track.contains(&wallet.to_string())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::units::UsdcAmount;
     
     #[test]
     fn test_circuit_breaker() {
         let config = Config {
             cb_consecutive_trigger: 3,
-            max_daily_volume: 1000.0,
-            max_exposure_per_event: 500.0,
+            max_daily_volume: UsdcAmount(1000.0),
+            max_exposure_per_event: UsdcAmount(500.0),
             min_liquidity: 100.0,
             cb_min_depth_usd: 50.0,
             wallets_to_track: vec!["0xwhale".to_string()],
@@ -162,4 +341,99 @@ mod tests {
         risk.reset_circuit_breaker();
         assert!(!risk.get_state().is_tripped);
     }
+    
+    #[test]
+    fn test_liquidity_limits() {
+        let config = Config {
+            max_spread: 0.05,
+            max_book_share: 0.25,
+            min_trades_per_hour: 2.0,
+            min_stake: UsdcAmount(5.0),
+            ..Default::default()
+        };
+        let liquidity = Arc::new(LiquidityStats::new(std::time::Duration::from_secs(3600)));
+        let risk = RiskManager::new(config).with_liquidity(Arc::clone(&liquidity));
+        // Unknown markets pass as they are
+        assert_eq!(risk.check_liquidity("m1", &TradeSide::BUY, 50.0, 0).unwrap(), 50.0);
+
+        liquidity.record_book("m1", &[(0.48, 500.0)], &[(0.50, 200.0)], 0);
+        liquidity.record_trade("m1", 0);
+        assert!(risk.check_liquidity("m1", &TradeSide::BUY, 50.0, 0).unwrap_err().to_string().contains("quiet"));
+        liquidity.record_trade("m1", 0);
+        // $100 of asks: a $50 buy is cut to $25, a $50 sell fits the bids
+        assert_eq!(risk.check_liquidity("m1", &TradeSide::BUY, 50.0, 0).unwrap(), 25.0);
+        assert_eq!(risk.check_liquidity("m1", &TradeSide::SELL, 50.0, 0).unwrap(), 50.0);
+
+        liquidity.record_book("m2", &[(0.40, 500.0)], &[(0.50, 500.0)], 0);
+        assert!(risk.check_liquidity("m2", &TradeSide::BUY, 10.0, 0).unwrap_err().to_string().contains("Spread"));
+        liquidity.record_book("m3", &[(0.49, 500.0)], &[(0.50, 10.0)], 0);
+        for _ in 0..2 {
+            liquidity.record_trade("m3", 0);
+        }
+        assert!(risk.check_liquidity("m3", &TradeSide::BUY, 10.0, 0).unwrap_err().to_string().contains("thin"));
+    }
+
+    #[test]
+    fn test_daily_loss_limit() {
+        let config = Config {
+            max_daily_loss: UsdcAmount(100.0),
+            ..Default::default()
+        };
+        
+        let risk = RiskManager::new(config);
+        
+        risk.record_realized_pnl(-60.0);
+        assert!(!risk.get_state().is_tripped);
+        
+        risk.record_realized_pnl(-45.0);
+        assert!(risk.get_state().is_tripped);
+        
+        risk.reset_daily_stats();
+        assert_eq!(risk.get_state().realized_pnl_today, 0.0);
+    }
+
+    #[test]
+    fn test_daily_counters_reset_when_the_utc_date_changes() {
+        use crate::clock::{Clock, SimClock};
+        use std::time::Duration;
+
+        // 2026-03-01 23:58 UTC
+        let clock = SimClock::at(1_772_409_480_000);
+        let risk = RiskManager::new(Config::default());
+        assert!(!risk.roll_over(clock.now().date_naive()));
+        risk.record_realized_pnl(-25.0);
+
+        clock.advance(Duration::from_secs(60));
+        assert!(!risk.roll_over(clock.now().date_naive()));
+        assert_eq!(risk.get_state().realized_pnl_today, -25.0);
+
+        // Any tick after midnight resets, however late it lands
+        clock.advance(Duration::from_secs(3 * 3600));
+        assert!(risk.roll_over(clock.now().date_naive()));
+        assert_eq!(risk.get_state().realized_pnl_today, 0.0);
+        assert!(!risk.roll_over(clock.now().date_naive()));
+    }
+    
+    #[test]
+    fn test_restore_drops_counters_from_another_day() {
+        let config = Config {
+            max_daily_loss: UsdcAmount(100.0),
+            ..Default::default()
+        };
+        let day1 = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let day2 = day1.succ_opt().unwrap();
+        
+        let risk = RiskManager::new(config.clone());
+        risk.record_realized_pnl(-150.0);
+        let snapshot = risk.snapshot(day1);
+        
+        let same_day = RiskManager::new(config.clone());
+        same_day.restore(snapshot.clone(), day1);
+        assert_eq!(same_day.get_state().realized_pnl_today, -150.0);
+        
+        let next_day = RiskManager::new(config);
+        next_day.restore(snapshot, day2);
+        assert_eq!(next_day.get_state().realized_pnl_today, 0.0);
+        assert!(next_day.get_state().is_tripped);
+    }
 }
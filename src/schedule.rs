@@ -0,0 +1,176 @@
+use crate::types::Config;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
+
+/// A daily window in local minutes since midnight. `end` may be less than
+/// `start` for windows that cross midnight (e.g. `22:00-02:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradingWindow {
+    start: u32,
+    end: u32,
+}
+
+impl TradingWindow {
+    /// Parses `HH:MM-HH:MM`. `24:00` is accepted as an end time.
+    pub fn parse(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .with_context(|| format!("Trading window '{}' must look like 09:00-23:00", s))?;
+        let window = Self {
+            start: parse_minutes(start)?,
+            end: parse_minutes(end)?,
+        };
+        if window.start == window.end {
+            anyhow::bail!("Trading window '{}' is empty", s);
+        }
+        Ok(window)
+    }
+
+    fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            minute >= self.start && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+fn parse_minutes(s: &str) -> Result<u32> {
+    let s = s.trim();
+    let (h, m) = s
+        .split_once(':')
+        .with_context(|| format!("Invalid time '{}', expected HH:MM", s))?;
+    let h: u32 = h.parse().with_context(|| format!("Invalid hour in '{}'", s))?;
+    let m: u32 = m.parse().with_context(|| format!("Invalid minute in '{}'", s))?;
+    if m >= 60 || h > 24 || (h == 24 && m != 0) {
+        anyhow::bail!("Time '{}' is out of range", s);
+    }
+    Ok(h * 60 + m)
+}
+
+/// When copying is allowed, evaluated in the configured timezone.
+#[derive(Debug, Clone)]
+pub struct TradingSchedule {
+    timezone: Tz,
+    windows: Vec<TradingWindow>,
+    blackout_dates: Vec<NaiveDate>,
+}
+
+impl Default for TradingSchedule {
+    /// Always open.
+    fn default() -> Self {
+        Self {
+            timezone: Tz::UTC,
+            windows: vec![],
+            blackout_dates: vec![],
+        }
+    }
+}
+
+impl TradingSchedule {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let timezone: Tz = config
+            .trading_timezone
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Unknown trading timezone '{}': {}", config.trading_timezone, e))?;
+
+        let windows = config
+            .trading_windows
+            .iter()
+            .map(|w| TradingWindow::parse(w))
+            .collect::<Result<Vec<_>>>()?;
+
+        let blackout_dates = config
+            .blackout_dates
+            .iter()
+            .map(|d| {
+                NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d")
+                    .with_context(|| format!("Invalid blackout date '{}', expected YYYY-MM-DD", d))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            timezone,
+            windows,
+            blackout_dates,
+        })
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    /// True when no windows or blackout dates are configured.
+    pub fn is_always_open(&self) -> bool {
+        self.windows.is_empty() && self.blackout_dates.is_empty()
+    }
+
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.timezone);
+        if self.blackout_dates.contains(&local.date_naive()) {
+            return false;
+        }
+        if self.windows.is_empty() {
+            return true;
+        }
+        let minute = local.hour() * 60 + local.minute();
+        self.windows.iter().any(|w| w.contains(minute))
+    }
+
+    /// The next minute at which `is_open` changes, searching up to eight days
+    /// ahead. `None` if the schedule never changes state in that range.
+    pub fn next_transition(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let open = self.is_open(now);
+        let start = now
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(now);
+        (1..=8 * 24 * 60)
+            .map(|m| start + Duration::minutes(m))
+            .find(|t| self.is_open(*t) != open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(windows: &[&str], blackout: &[&str]) -> TradingSchedule {
+        let config = Config {
+            trading_timezone: "America/New_York".to_string(),
+            trading_windows: windows.iter().map(|s| s.to_string()).collect(),
+            blackout_dates: blackout.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        TradingSchedule::from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn test_window_in_local_time() {
+        let s = schedule(&["09:00-23:00"], &[]);
+        // 12:59 UTC in January is 07:59 in New York
+        assert!(!s.is_open(Utc.with_ymd_and_hms(2024, 1, 10, 12, 59, 0).unwrap()));
+        assert!(s.is_open(Utc.with_ymd_and_hms(2024, 1, 10, 14, 0, 0).unwrap()));
+
+        let next = s.next_transition(Utc.with_ymd_and_hms(2024, 1, 10, 12, 30, 0).unwrap());
+        assert_eq!(next, Some(Utc.with_ymd_and_hms(2024, 1, 10, 14, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_overnight_window_and_blackout() {
+        let s = schedule(&["22:00-02:00"], &["2024-01-11"]);
+        // 04:30 UTC = 23:30 New York on Jan 9
+        assert!(s.is_open(Utc.with_ymd_and_hms(2024, 1, 10, 4, 30, 0).unwrap()));
+        // 04:30 UTC Jan 12 = 23:30 New York on the blackout date
+        assert!(!s.is_open(Utc.with_ymd_and_hms(2024, 1, 12, 4, 30, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_windows_rejected() {
+        assert!(TradingWindow::parse("9-17").is_err());
+        assert!(TradingWindow::parse("09:00-25:00").is_err());
+        assert!(TradingWindow::parse("09:00-24:00").is_ok());
+    }
+}
@@ -0,0 +1,417 @@
+//! `mybot scout`: finding wallets worth copying.
+//!
+//! Candidates are the leaderboard's top wallets over the window, and any
+//! wallets asked for. Each one's trades of the window from the data API
+//! (the trades API without `data_api`) are costed FIFO per market, what's still held marked to the market's
+//! price, and summed up as:
+//!
+//! - ROI: PnL over what its buys cost
+//! - consistency: the share of its markets traded at a profit
+//! - hold time: how long shares it sold had been held, on average by share
+//! - diversity: the effective number of markets, by volume (1 / Σ share²)
+//! - copyability: the share of its trades a copy `latency` later would still
+//!   have filled within `max_slippage`, priced like the latency fill model
+//!   from the price history (as fine as a minute) of its largest markets
+//!
+//! Candidates rank by ROI × consistency × copyability, those with fewer than
+//! [`MIN_TRADES`] trades last: a handful of lucky trades says little.
+
+use crate::api::PolymarketApi;
+use crate::data_api;
+use crate::executor::limit_price;
+use crate::fills::{FillModel, LatencyPenalized, MarketTape, RecordedTape, SimOrder};
+use crate::gamma;
+use crate::portfolio::{self, Lot};
+use crate::storage::PriceSample;
+use crate::types::{CostBasis, Market, Trade, TradeSide};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Fewer trades than this rank last whatever their numbers.
+pub const MIN_TRADES: usize = 10;
+/// Markets per candidate whose price history copyability is judged on.
+const COPYABILITY_MARKETS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct ScoutRequest {
+    pub since: Duration,
+    /// Leaderboard wallets to screen
+    pub limit: usize,
+    /// Screened as well as the leaderboard's
+    pub wallets: Vec<String>,
+    /// Already copied; flagged in the results
+    pub tracked: Vec<String>,
+    pub latency: Duration,
+    pub max_slippage: f64,
+}
+
+fn as_opt_secs<S: serde::Serializer>(d: &Option<Duration>, s: S) -> std::result::Result<S::Ok, S::Error> {
+    match d {
+        Some(d) => s.serialize_some(&d.as_secs_f64()),
+        None => s.serialize_none(),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Candidate {
+    pub wallet: String,
+    pub name: Option<String>,
+    pub tracked: bool,
+    /// As the leaderboard has it
+    pub leaderboard_pnl: Option<f64>,
+    pub trades: usize,
+    pub volume_usd: f64,
+    /// What its buys cost
+    pub cost: f64,
+    /// Realized, plus open shares marked where the market could be priced
+    pub pnl: f64,
+    pub roi: Option<f64>,
+    pub consistency: Option<f64>,
+    #[serde(serialize_with = "as_opt_secs")]
+    pub avg_hold: Option<Duration>,
+    pub markets: usize,
+    pub effective_markets: f64,
+    pub copyability: Option<f64>,
+    pub score: f64,
+}
+
+impl Candidate {
+    /// Sums up `trades` (in any order), marking open shares at `marks` by
+    /// market.
+    pub fn from_trades(wallet: &str, trades: &[Trade], marks: &HashMap<String, f64>) -> Self {
+        let mut trades: Vec<&Trade> = trades.iter().collect();
+        trades.sort_by_key(|t| t.timestamp);
+        let mut candidate = Candidate {
+            wallet: wallet.to_string(),
+            trades: trades.len(),
+            ..Default::default()
+        };
+        // Per market: lots, realized PnL, volume
+        let mut markets: BTreeMap<&str, (Vec<Lot>, f64, f64)> = BTreeMap::new();
+        let (mut held_shares, mut held_secs) = (0.0, 0.0);
+        for trade in trades {
+            let notional = trade.shares * trade.price;
+            candidate.volume_usd += notional;
+            let (lots, realized, volume) = markets.entry(&trade.market_id).or_default();
+            *volume += notional;
+            match trade.side {
+                TradeSide::BUY => {
+                    candidate.cost += notional;
+                    portfolio::buy(lots, CostBasis::Fifo, trade.shares, trade.price, trade.timestamp);
+                }
+                TradeSide::SELL => {
+                    let mut remaining = trade.shares;
+                    for lot in lots.iter() {
+                        let take = remaining.min(lot.shares);
+                        held_shares += take;
+                        held_secs += take * (trade.timestamp - lot.opened_at) as f64;
+                        remaining -= take;
+                        if remaining <= 1e-9 {
+                            break;
+                        }
+                    }
+                    *realized += portfolio::sell(lots, trade.shares, trade.price);
+                }
+            }
+        }
+
+        let mut profitable = 0;
+        for (market_id, (lots, realized, volume)) in &markets {
+            let open: f64 = lots.iter().map(|l| l.shares).sum();
+            let unrealized = match marks.get(*market_id) {
+                Some(mark) if open > 1e-9 => lots.iter().map(|l| l.shares * (mark - l.price)).sum(),
+                _ => 0.0,
+            };
+            candidate.pnl += realized + unrealized;
+            if realized + unrealized > 0.0 {
+                profitable += 1;
+            }
+            let share = volume / candidate.volume_usd.max(f64::EPSILON);
+            candidate.effective_markets += share * share;
+        }
+        candidate.markets = markets.len();
+        if candidate.effective_markets > 0.0 {
+            candidate.effective_markets = 1.0 / candidate.effective_markets;
+        }
+        candidate.roi = (candidate.cost > 0.0).then(|| candidate.pnl / candidate.cost);
+        candidate.consistency = (!markets.is_empty()).then(|| profitable as f64 / markets.len() as f64);
+        candidate.avg_hold = (held_shares > 1e-9).then(|| Duration::from_secs_f64(held_secs / held_shares));
+        candidate
+    }
+
+    fn rank_score(&mut self) {
+        self.score = self.roi.unwrap_or(0.0) * self.consistency.unwrap_or(0.0) * self.copyability.unwrap_or(0.0);
+    }
+}
+
+/// Of `trades` in markets `tape` has prices for, the share a copy `latency`
+/// later would have filled within `max_slippage`.
+pub async fn copyability(
+    tape: Arc<dyn MarketTape>,
+    trades: &[Trade],
+    latency: Duration,
+    max_slippage: f64,
+) -> Option<f64> {
+    let late = LatencyPenalized::new(tape.clone(), latency);
+    let (mut priced, mut filled) = (0, 0);
+    for trade in trades {
+        let at_ms = trade.timestamp * 1000;
+        if tape.price_at(&trade.market_id, at_ms).await.is_none() {
+            continue;
+        }
+        let order = SimOrder {
+            market_id: trade.market_id.clone(),
+            side: trade.side.clone(),
+            shares: trade.shares,
+            reference_price: trade.price,
+            limit: Some(limit_price(trade, max_slippage)),
+            at_ms,
+        };
+        priced += 1;
+        if late.fill(&order).await.is_some() {
+            filled += 1;
+        }
+    }
+    (priced > 0).then(|| filled as f64 / priced as f64)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScoutReport {
+    /// Unix seconds
+    pub since: i64,
+    /// Best first
+    pub candidates: Vec<Candidate>,
+    /// Wallets that couldn't be screened, with why
+    pub failed: Vec<String>,
+}
+
+impl std::fmt::Display for ScoutReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |v: Option<f64>| match v {
+            Some(v) => format!("{:.0}%", v * 100.0),
+            None => "-".to_string(),
+        };
+        writeln!(
+            f,
+            "{:<4} {:<44} {:<16} {:>6} {:>12} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "rank", "wallet", "name", "trades", "pnl", "roi", "consist", "hold", "markets", "copyable"
+        )?;
+        for (i, c) in self.candidates.iter().enumerate() {
+            let hold = match c.avg_hold {
+                Some(hold) if hold.as_secs() >= 86_400 => format!("{:.1}d", hold.as_secs_f64() / 86_400.0),
+                Some(hold) => format!("{:.1}h", hold.as_secs_f64() / 3600.0),
+                None => "-".to_string(),
+            };
+            let mut name: String = c.name.clone().unwrap_or_default().chars().take(15).collect();
+            if c.tracked {
+                name = format!("{}*", name);
+            }
+            writeln!(
+                f,
+                "{:<4} {:<44} {:<16} {:>6} {:>+12.2} {:>8} {:>8} {:>8} {:>8.1} {:>8}",
+                i + 1,
+                c.wallet,
+                name,
+                c.trades,
+                c.pnl,
+                percent(c.roi),
+                percent(c.consistency),
+                hold,
+                c.effective_markets,
+                percent(c.copyability)
+            )?;
+        }
+        if self.candidates.iter().any(|c| c.tracked) {
+            writeln!(f, "* already tracked")?;
+        }
+        for failure in &self.failed {
+            writeln!(f, "❌ {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// The leaderboard's name for a window of `since`.
+fn leaderboard_window(since: Duration) -> &'static str {
+    match since.as_secs() / 86_400 {
+        0..=1 => "day",
+        2..=7 => "week",
+        8..=31 => "month",
+        _ => "all",
+    }
+}
+
+/// Screens the leaderboard's top wallets and `request.wallets`, reading
+/// their trades from `data` and looking their markets up on `gamma` when
+/// given.
+pub async fn scout(
+    api: &PolymarketApi,
+    gamma: Option<&gamma::Client>,
+    data: Option<&data_api::Client>,
+    request: &ScoutRequest,
+) -> Result<ScoutReport> {
+    let now = chrono::Utc::now().timestamp();
+    let mut report = ScoutReport {
+        since: now - request.since.as_secs() as i64,
+        ..Default::default()
+    };
+    let mut wallets: Vec<(String, Option<String>, Option<f64>)> = Vec::new();
+    if request.limit > 0 {
+        for entry in api
+            .get_leaderboard(leaderboard_window(request.since), request.limit)
+            .await?
+        {
+            wallets.push((entry.wallet, entry.name, Some(entry.pnl)));
+        }
+    }
+    for wallet in &request.wallets {
+        if !wallets.iter().any(|(w, ..)| w.eq_ignore_ascii_case(wallet)) {
+            wallets.push((wallet.clone(), None, None));
+        }
+    }
+
+    let mut markets: HashMap<String, Option<Market>> = HashMap::new();
+    let mut prices: HashMap<String, Vec<PriceSample>> = HashMap::new();
+    for (wallet, name, leaderboard_pnl) in wallets {
+        let fetched = match data {
+            Some(data) => data.trades(&wallet, report.since).await,
+            None => api.get_trades(&wallet, report.since).await,
+        };
+        let trades: Vec<Trade> = match fetched {
+            Ok(trades) => trades.into_iter().filter(|t| t.timestamp >= report.since).collect(),
+            Err(e) => {
+                tracing::warn!("Failed to fetch trades of {}: {:#}", wallet, e);
+                report.failed.push(format!("{}: {:#}", wallet, e));
+                continue;
+            }
+        };
+        tracing::info!("🔎 {} trades of {}", trades.len(), wallet);
+
+        // Markets by volume, largest first, for marks and price history
+        let mut volumes: HashMap<&str, f64> = HashMap::new();
+        for trade in &trades {
+            *volumes.entry(&trade.market_id).or_default() += trade.shares * trade.price;
+        }
+        let mut by_volume: Vec<(&str, f64)> = volumes.into_iter().collect();
+        by_volume.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut marks = HashMap::new();
+        let mut samples = Vec::new();
+        for (i, &(market_id, _)) in by_volume.iter().enumerate() {
+            if !markets.contains_key(market_id) {
+                let market = match gamma {
+                    Some(gamma) => gamma.market(market_id).await,
+                    None => api.get_market(market_id).await,
+                };
+                if let Err(e) = &market {
+                    tracing::debug!("No market data for {}: {:#}", market_id, e);
+                }
+                markets.insert(market_id.to_string(), market.ok());
+            }
+            let Some(market) = &markets[market_id] else {
+                continue;
+            };
+            marks.insert(market_id.to_string(), market.yes_price);
+            if i >= COPYABILITY_MARKETS {
+                continue;
+            }
+            if !prices.contains_key(market_id) {
+                let token = market.token_ids.first().map(String::as_str).unwrap_or(market_id);
+                let history = match api.get_price_history(token, report.since, now, 1).await {
+                    Ok(points) => points,
+                    Err(e) => {
+                        tracing::debug!("No price history for {}: {:#}", market_id, e);
+                        Vec::new()
+                    }
+                };
+                let history = history
+                    .into_iter()
+                    .map(|(t, price)| PriceSample {
+                        market_id: market_id.to_string(),
+                        sampled_at: t * 1000,
+                        mid: None,
+                        last: Some(price),
+                        book: None,
+                    })
+                    .collect();
+                prices.insert(market_id.to_string(), history);
+            }
+            samples.extend(prices[market_id].iter().cloned());
+        }
+
+        let mut candidate = Candidate::from_trades(&wallet, &trades, &marks);
+        let tape = Arc::new(RecordedTape::new(samples));
+        candidate.copyability = copyability(tape, &trades, request.latency, request.max_slippage).await;
+        candidate.tracked = request.tracked.iter().any(|t| t.eq_ignore_ascii_case(&wallet));
+        candidate.name = name;
+        candidate.leaderboard_pnl = leaderboard_pnl;
+        candidate.rank_score();
+        report.candidates.push(candidate);
+    }
+
+    report.candidates.sort_by(|a, b| {
+        (b.trades >= MIN_TRADES)
+            .cmp(&(a.trades >= MIN_TRADES))
+            .then(b.score.total_cmp(&a.score))
+    });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(market_id: &str, side: TradeSide, shares: f64, price: f64, timestamp: i64) -> Trade {
+        Trade {
+            wallet: "0xwhale".to_string(),
+            event_id: market_id.to_string(),
+            market_id: market_id.to_string(),
+            side,
+            shares,
+            price,
+            timestamp,
+            tx_hash: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_candidate_metrics() {
+        let trades = vec![
+            // Held an hour and a day, sold at a profit
+            trade("m1", TradeSide::BUY, 100.0, 0.40, 0),
+            trade("m1", TradeSide::BUY, 100.0, 0.40, 82_800),
+            trade("m1", TradeSide::SELL, 200.0, 0.50, 86_400),
+            // Still held, marked at a loss
+            trade("m2", TradeSide::BUY, 100.0, 0.80, 1_000),
+        ];
+        let marks = HashMap::from([("m2".to_string(), 0.60)]);
+        let candidate = Candidate::from_trades("0xwhale", &trades, &marks);
+
+        // +$20 realized, -$20 marked, on $160 of buys
+        assert!(candidate.roi.unwrap().abs() < 1e-9);
+        assert_eq!(candidate.consistency, Some(0.5));
+        assert_eq!(candidate.avg_hold, Some(Duration::from_secs((86_400 + 3_600) / 2)));
+        assert_eq!(candidate.markets, 2);
+        // $180 and $80 of volume
+        let (a, b) = (180.0 / 260.0, 80.0 / 260.0);
+        assert!((candidate.effective_markets - 1.0 / (a * a + b * b)).abs() < 1e-9);
+
+        // m1 ran away a minute after the first buy and came back after the
+        // second; m2 has no prices and doesn't count
+        let tape = Arc::new(RecordedTape::new(
+            [(0, 0.40), (60, 0.45), (82_800, 0.40), (82_860, 0.40), (86_460, 0.50)]
+                .map(|(t, price)| PriceSample {
+                    market_id: "m1".to_string(),
+                    sampled_at: t * 1000,
+                    mid: None,
+                    last: Some(price),
+                    book: None,
+                })
+                .to_vec(),
+        ));
+        let copyable = copyability(tape, &trades, Duration::from_secs(2), 0.05).await;
+        assert_eq!(copyable, Some(2.0 / 3.0));
+    }
+}
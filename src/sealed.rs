@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use std::env;
+
+/// Prefix of every sealed value, bumped if the format ever changes.
+const SEALED_PREFIX: &str = "v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Reads the unsealing secret from `CONFIG_PASSPHRASE` or the file named by
+/// `CONFIG_KEYFILE`. Returns `None` when neither is set.
+pub fn load_secret() -> Result<Option<Vec<u8>>> {
+    if let Ok(passphrase) = env::var("CONFIG_PASSPHRASE") {
+        return Ok(Some(passphrase.into_bytes()));
+    }
+
+    if let Ok(path) = env::var("CONFIG_KEYFILE") {
+        let contents = std::fs::read(&path)
+            .with_context(|| format!("Failed to read CONFIG_KEYFILE {}", path))?;
+        let secret = keyfile_secret(contents);
+        if secret.is_empty() {
+            anyhow::bail!("CONFIG_KEYFILE {} is empty", path);
+        }
+        return Ok(Some(secret));
+    }
+
+    Ok(None)
+}
+
+/// A keyfile's bytes as the secret, without the trailing newline an editor
+/// leaves. Binary keyfiles are kept byte for byte: anything else would
+/// throw away their entropy.
+fn keyfile_secret(mut contents: Vec<u8>) -> Vec<u8> {
+    while contents.last().is_some_and(u8::is_ascii_whitespace) {
+        contents.pop();
+    }
+    contents
+}
+
+/// Encrypts a config fragment so it can be pasted into a `[sealed]` table.
+pub fn seal(plaintext: &str, secret: &[u8]) -> Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = cipher_for(secret, &salt)?;
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt config section"))?;
+
+    let mut blob = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    blob.extend_from_slice(&salt);
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&ciphertext);
+
+    Ok(format!("{}{}", SEALED_PREFIX, STANDARD.encode(blob)))
+}
+
+/// Decrypts a value produced by [`seal`].
+pub fn unseal(sealed: &str, secret: &[u8]) -> Result<String> {
+    let encoded = sealed
+        .trim()
+        .strip_prefix(SEALED_PREFIX)
+        .context("Sealed value has an unknown format version")?;
+    let blob = STANDARD
+        .decode(encoded)
+        .context("Sealed value is not valid base64")?;
+
+    if blob.len() < SALT_LEN + NONCE_LEN {
+        anyhow::bail!("Sealed value is truncated");
+    }
+
+    let (salt, rest) = blob.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = cipher_for(secret, salt)?;
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt sealed section (wrong passphrase or keyfile?)"))?;
+
+    String::from_utf8(plaintext).context("Sealed section is not valid UTF-8")
+}
+
+fn cipher_for(secret: &[u8], salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive config key: {}", e))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_roundtrip() {
+        let fragment = "wallets_to_track = [\"0xabc\", \"0xdef\"]\n";
+        let sealed = seal(fragment, b"correct horse").unwrap();
+
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("0xabc"));
+        assert_eq!(unseal(&sealed, b"correct horse").unwrap(), fragment);
+        assert!(unseal(&sealed, b"wrong horse").is_err());
+    }
+
+    #[test]
+    fn test_keyfiles_keep_their_bytes() {
+        assert_eq!(keyfile_secret(b"correct horse\r\n".to_vec()), b"correct horse");
+        // Invalid UTF-8 must not collapse to the same replacement characters
+        let a = keyfile_secret(vec![0xff, 0xfe, 0x00, 0x80]);
+        let b = keyfile_secret(vec![0xfe, 0xff, 0x00, 0x81]);
+        assert_eq!(a, [0xff, 0xfe, 0x00, 0x80]);
+        assert_ne!(a, b);
+        assert!(keyfile_secret(b" \n".to_vec()).is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+//! Word index over the market catalog.
+//!
+//! [`SearchIndex`] maps the words of each cached market's question, slug,
+//! category and id to the markets using them, so `markets search` and the
+//! `market_blacklist` rules look words up rather than scanning every market.
+//! A query word matches a catalog word exactly, as its prefix, or with up to
+//! [`max_typos`] edits; typo candidates are the catalog words sharing a
+//! trigram with it. Blacklist rules match whole words only, so a typo never
+//! blocks a market.
+
+use crate::types::Market;
+use std::collections::{HashMap, HashSet};
+
+/// Relevance of an exact, prefix and typo match of one query word.
+const EXACT: f64 = 1.0;
+const PREFIX: f64 = 0.75;
+const TYPO: f64 = 0.5;
+
+/// Lowercased alphanumeric runs of `text`.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Edits a query word of `len` characters may be off by: none for short
+/// words, which would match too much, one from 4 characters, two from 8.
+pub fn max_typos(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+fn market_words(market: &Market) -> Vec<String> {
+    let mut words: Vec<String> = [&market.question, &market.slug, &market.category, &market.id]
+        .into_iter()
+        .flat_map(|text| words(text))
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+fn trigrams(word: &str) -> Vec<String> {
+    let padded: Vec<char> = format!(" {} ", word).chars().collect();
+    padded.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    /// Words of each market, by market id.
+    docs: HashMap<String, Vec<String>>,
+    /// Markets using each word.
+    postings: HashMap<String, HashSet<String>>,
+    /// Catalog words containing each trigram.
+    trigrams: HashMap<String, HashSet<String>>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Indexes `market` under `id` (the id it was looked up by), replacing
+    /// what was indexed under it before.
+    pub fn insert(&mut self, id: &str, market: &Market) {
+        self.remove(id);
+        let words = market_words(market);
+        for word in &words {
+            let markets = self.postings.entry(word.clone()).or_default();
+            if markets.is_empty() {
+                for gram in trigrams(word) {
+                    self.trigrams.entry(gram).or_default().insert(word.clone());
+                }
+            }
+            markets.insert(id.to_string());
+        }
+        self.docs.insert(id.to_string(), words);
+    }
+
+    pub fn remove(&mut self, market_id: &str) {
+        let Some(words) = self.docs.remove(market_id) else { return };
+        for word in words {
+            let Some(markets) = self.postings.get_mut(&word) else { continue };
+            markets.remove(market_id);
+            if !markets.is_empty() {
+                continue;
+            }
+            self.postings.remove(&word);
+            for gram in trigrams(&word) {
+                if let Some(words) = self.trigrams.get_mut(&gram) {
+                    words.remove(&word);
+                    if words.is_empty() {
+                        self.trigrams.remove(&gram);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Catalog words `query_word` matches, with how closely.
+    fn expand(&self, query_word: &str) -> Vec<(&str, f64)> {
+        let typos = max_typos(query_word.chars().count());
+        let candidates: HashSet<&str> = trigrams(query_word)
+            .iter()
+            .filter_map(|gram| self.trigrams.get(gram))
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        candidates
+            .into_iter()
+            .filter_map(|word| {
+                if word == query_word {
+                    Some((word, EXACT))
+                } else if word.starts_with(query_word) {
+                    Some((word, PREFIX))
+                } else if typos > 0 && edit_distance(word, query_word) <= typos {
+                    Some((word, TYPO))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Ids of the markets matching every word of `query`, most relevant
+    /// first, with their relevance: the sum over query words of how closely
+    /// each matched.
+    pub fn search(&self, query: &str) -> Vec<(String, f64)> {
+        let mut scores: Option<HashMap<&str, f64>> = None;
+        for query_word in words(query) {
+            let mut matched: HashMap<&str, f64> = HashMap::new();
+            for (word, closeness) in self.expand(&query_word) {
+                for id in &self.postings[word] {
+                    let best = matched.entry(id.as_str()).or_insert(0.0);
+                    *best = best.max(closeness);
+                }
+            }
+            scores = Some(match scores {
+                None => matched,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(id, score)| matched.get(id).map(|closeness| (id, score + closeness)))
+                    .collect(),
+            });
+        }
+        let mut found: Vec<(String, f64)> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, score)| (id.to_string(), score))
+            .collect();
+        found.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        found
+    }
+
+    /// Whether `market` uses every word of `phrase`, read from the index when
+    /// the market is in it.
+    pub fn has_words(&self, market: &Market, phrase: &str) -> bool {
+        let phrase = words(phrase);
+        if phrase.is_empty() {
+            return false;
+        }
+        match self.docs.get(&market.id) {
+            Some(_) => phrase
+                .iter()
+                .all(|w| self.postings.get(w).is_some_and(|ids| ids.contains(&market.id))),
+            None => {
+                let words = market_words(market);
+                phrase.iter().all(|w| words.binary_search(w).is_ok())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(id: &str, question: &str, slug: &str) -> Market {
+        Market {
+            id: id.to_string(),
+            question: question.to_string(),
+            yes_price: 0.5,
+            no_price: 0.5,
+            slug: slug.to_string(),
+            category: "Politics".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn ids(found: Vec<(String, f64)>) -> Vec<String> {
+        found.into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn test_matches_words_prefixes_and_typos() {
+        let mut index = SearchIndex::new();
+        index.insert("m1", &market("m1", "Will Trump win the 2028 election?", "trump-2028"));
+        index.insert("m2", &market("m2", "Will the Fed cut rates in December?", "fed-december"));
+        index.insert("m3", &market("m3", "Trumpet player wins Grammy?", "grammy-trumpet"));
+
+        assert_eq!(ids(index.search("trump election")), ["m1"]);
+        // Exact matches rank ahead of prefix ones
+        assert_eq!(ids(index.search("trump")), ["m1", "m3"]);
+        assert_eq!(ids(index.search("electon")), ["m1"]);
+        assert_eq!(ids(index.search("decmber fed")), ["m2"]);
+        assert_eq!(ids(index.search("politics")).len(), 3);
+        // Short words have to be exact
+        assert!(index.search("fet").is_empty());
+        assert!(index.search("").is_empty());
+
+        index.insert("m1", &market("m1", "Will Vance win the 2028 election?", "vance-2028"));
+        assert_eq!(ids(index.search("trump")), ["m3"]);
+        index.remove("m3");
+        assert!(index.search("trump").is_empty());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_phrases_match_whole_words() {
+        let mut index = SearchIndex::new();
+        let indexed = market("m1", "Will Trump win the 2028 election?", "trump-2028");
+        index.insert("m1", &indexed);
+        assert!(index.has_words(&indexed, "Trump election"));
+        assert!(!index.has_words(&indexed, "trum"));
+        assert!(!index.has_words(&indexed, "trump senate"));
+
+        let unindexed = market("m2", "Trumpet player wins Grammy?", "");
+        assert!(index.has_words(&unindexed, "grammy"));
+        assert!(!index.has_words(&unindexed, "trump"));
+    }
+}
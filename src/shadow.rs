@@ -0,0 +1,312 @@
+//! A/B shadow mode: a second strategy config copying in paper next to the
+//! live one.
+//!
+//! With `shadow` set, the bot builds a second, paper-trading bot from its
+//! own config with the listed overrides on top. Every leader trade the live
+//! bot decides on is handed to the shadow afterwards, which decides on the
+//! same trade and, when it would copy, simulates the fill against its own
+//! virtual account. The two decisions are compared: disagreements are
+//! logged and tallied, and `/status/shadow` shows the tallies, the latest
+//! divergences and each side's PnL since startup, both marked at the
+//! market cache's prices. The live side only counts fills made since the
+//! shadow started, so positions from earlier runs don't skew the
+//! comparison.
+//!
+//! The shadow never touches the journal, the event log or the notifiers,
+//! and works through a queue of its own so it can't hold up live copying;
+//! when it falls too far behind, trades are dropped from the comparison.
+
+use crate::bot::Bot;
+use crate::clock::Clock;
+use crate::marks::Marks;
+use crate::notify::{self, NotifierRegistry};
+use crate::portfolio::Portfolio;
+use crate::storage::FillRecord;
+use crate::types::{Config, Decision, Trade};
+use anyhow::{Context, Result};
+use async_channel::{bounded, Receiver, Sender};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tracing::Instrument;
+
+/// Trades waiting for the shadow before new ones are dropped.
+const QUEUE_SIZE: usize = 256;
+/// Divergences kept for the status API.
+const RECENT_DIVERGENCES: usize = 50;
+
+/// How often the two strategies agreed, and how they didn't.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ShadowTally {
+    pub agreed: u64,
+    /// Live copied, the shadow skipped.
+    pub live_only: u64,
+    /// The shadow copied, live skipped.
+    pub shadow_only: u64,
+    /// Both copied, at different sizes.
+    pub sized_differently: u64,
+    /// Both skipped, for different reasons.
+    pub skipped_differently: u64,
+}
+
+impl ShadowTally {
+    /// Counts one trade; `true` if the decisions diverged.
+    pub fn record(&mut self, live: &Decision, shadow: &Decision) -> bool {
+        if live.same_outcome(shadow) {
+            self.agreed += 1;
+            return false;
+        }
+        match (live, shadow) {
+            (Decision::Copy { .. }, Decision::Skip { .. }) => self.live_only += 1,
+            (Decision::Skip { .. }, Decision::Copy { .. }) => self.shadow_only += 1,
+            (Decision::Copy { .. }, Decision::Copy { .. }) => self.sized_differently += 1,
+            (Decision::Skip { .. }, Decision::Skip { .. }) => self.skipped_differently += 1,
+        }
+        true
+    }
+
+    pub fn total(&self) -> u64 {
+        self.agreed + self.live_only + self.shadow_only + self.sized_differently + self.skipped_differently
+    }
+}
+
+/// A trade the two strategies decided differently.
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub at: i64,
+    pub wallet: String,
+    pub market_id: String,
+    pub side: String,
+    pub live: Decision,
+    pub shadow: Decision,
+}
+
+/// One strategy's results since the shadow started.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ShadowSide {
+    /// Trades it decided to copy, and for how much.
+    pub copies: u64,
+    pub copied_usd: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub open_positions: usize,
+}
+
+impl ShadowSide {
+    pub fn pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShadowReport {
+    pub started_at: i64,
+    /// Trades the shadow fell too far behind to see.
+    pub dropped: u64,
+    pub tally: ShadowTally,
+    pub live: ShadowSide,
+    pub shadow: ShadowSide,
+    /// Newest first.
+    pub recent: Vec<Divergence>,
+}
+
+#[derive(Default)]
+struct Comparison {
+    tally: ShadowTally,
+    dropped: u64,
+    live: ShadowSide,
+    shadow: ShadowSide,
+    recent: VecDeque<Divergence>,
+}
+
+pub struct Shadow {
+    bot: Bot,
+    // What the live bot filled since the shadow started
+    live: Portfolio,
+    marks: Arc<Marks>,
+    clock: Arc<dyn Clock>,
+    started_at: i64,
+    queue: Sender<(Trade, Decision)>,
+    pending: Receiver<(Trade, Decision)>,
+    comparison: Mutex<Comparison>,
+}
+
+impl Shadow {
+    /// Builds the shadow bot from `config` (the live config's `shadow`),
+    /// tracking the same wallets as `live` and marking positions with the
+    /// live bot's `marks`.
+    pub async fn start(mut config: Config, live: &Config, marks: Arc<Marks>, clock: Arc<dyn Clock>) -> Result<Self> {
+        config.storage_url.clear();
+        config.event_log.clear();
+        notify::disable(&mut config);
+        config.paper_trading = true;
+        config.wallets_to_track = live.wallets_to_track.clone();
+        let bot = Box::pin(Bot::with_clock(config, NotifierRegistry::empty(), Arc::clone(&clock)))
+            .await
+            .context("Failed to build the shadow strategy")?;
+        let (queue, pending) = bounded(QUEUE_SIZE);
+        Ok(Self {
+            bot,
+            live: Portfolio::new(live.cost_basis, None),
+            marks,
+            started_at: clock.now_ms(),
+            clock,
+            queue,
+            pending,
+            comparison: Mutex::new(Comparison::default()),
+        })
+    }
+
+    /// Hands a trade the live bot decided on to the shadow.
+    pub fn observe(&self, trade: &Trade, live: &Decision) {
+        if self.queue.try_send((trade.clone(), live.clone())).is_err() {
+            self.comparison.lock().unwrap().dropped += 1;
+        }
+    }
+
+    /// Books a live fill on the live side of the comparison.
+    pub async fn live_fill(&self, fill: &FillRecord) {
+        self.live.apply_fill(fill).await;
+    }
+
+    /// Works through observed trades, and settles the shadow's account when
+    /// its markets resolve.
+    pub fn spawn(self: Arc<Self>) {
+        if let Some(paper) = self.bot.paper() {
+            Arc::clone(paper).spawn(self.bot.risk());
+        }
+        tokio::spawn(async move {
+            let mut today = self.clock.now().date_naive();
+            while let Ok((trade, live)) = self.pending.recv().await {
+                let date = self.clock.now().date_naive();
+                if date != today {
+                    self.bot.risk().reset_daily_stats();
+                    today = date;
+                }
+                self.compare(trade, live)
+                    .instrument(tracing::info_span!("shadow"))
+                    .await;
+            }
+        });
+    }
+
+    async fn compare(&self, trade: Trade, live: Decision) {
+        let shadow = self.bot.decide(&trade).await;
+        let divergence = {
+            let comparison = &mut *self.comparison.lock().unwrap();
+            for (side, decision) in [(&mut comparison.live, &live), (&mut comparison.shadow, &shadow)] {
+                if let Decision::Copy { size_usd, .. } = decision {
+                    side.copies += 1;
+                    side.copied_usd += size_usd;
+                }
+            }
+            let diverged = comparison.tally.record(&live, &shadow);
+            diverged.then(|| {
+                let divergence = Divergence {
+                    at: self.clock.now_ms(),
+                    wallet: trade.wallet.clone(),
+                    market_id: trade.market_id.clone(),
+                    side: trade.side.as_str().to_string(),
+                    live: live.clone(),
+                    shadow: shadow.clone(),
+                };
+                comparison.recent.push_front(divergence.clone());
+                comparison.recent.truncate(RECENT_DIVERGENCES);
+                divergence
+            })
+        };
+        if let Some(divergence) = divergence {
+            tracing::info!(
+                market_id = %divergence.market_id,
+                "🪞 Shadow diverged: live would {}, shadow would {}",
+                describe(&divergence.live),
+                describe(&divergence.shadow)
+            );
+        }
+        if let Decision::Copy { size_usd, shares } = shadow {
+            self.bot.execute_copy(trade, None, size_usd, shares).await;
+        }
+    }
+
+    pub async fn report(&self) -> ShadowReport {
+        let live = self.side(&self.live).await;
+        let shadow = self.side(&self.bot.portfolio()).await;
+        let comparison = self.comparison.lock().unwrap();
+        ShadowReport {
+            started_at: self.started_at,
+            dropped: comparison.dropped,
+            tally: comparison.tally.clone(),
+            live: ShadowSide {
+                copies: comparison.live.copies,
+                copied_usd: comparison.live.copied_usd,
+                ..live
+            },
+            shadow: ShadowSide {
+                copies: comparison.shadow.copies,
+                copied_usd: comparison.shadow.copied_usd,
+                ..shadow
+            },
+            recent: comparison.recent.iter().cloned().collect(),
+        }
+    }
+
+    /// PnL and open positions of `portfolio`, marked by the live bot's marks
+    /// (at cost without one).
+    async fn side(&self, portfolio: &Portfolio) -> ShadowSide {
+        let holdings = portfolio.holdings();
+        let mut unrealized_pnl = 0.0;
+        for holding in &holdings {
+            if let Some(mark) = self.marks.mark(&holding.market_id).await {
+                unrealized_pnl += holding.unrealized_pnl(mark.price);
+            }
+        }
+        ShadowSide {
+            realized_pnl: portfolio.realized_pnl(),
+            unrealized_pnl,
+            open_positions: holdings.len(),
+            ..Default::default()
+        }
+    }
+}
+
+fn describe(decision: &Decision) -> String {
+    match decision {
+        Decision::Copy { size_usd, .. } => format!("copy ${:.2}", size_usd),
+        Decision::Skip { reason, .. } => format!("skip ({})", reason.as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SkipReason;
+
+    #[test]
+    fn test_tally_classifies_divergences() {
+        let copy = |size_usd| Decision::Copy {
+            size_usd,
+            shares: size_usd * 2.0,
+        };
+        let skip = |reason| Decision::skip(reason, "detail");
+        let mut tally = ShadowTally::default();
+
+        assert!(!tally.record(&copy(10.0), &copy(10.001)));
+        assert!(!tally.record(&skip(SkipReason::Stale), &skip(SkipReason::Stale)));
+        assert!(tally.record(&copy(10.0), &skip(SkipReason::RiskBlocked)));
+        assert!(tally.record(&skip(SkipReason::PriceOutOfRange), &copy(5.0)));
+        assert!(tally.record(&copy(10.0), &copy(20.0)));
+        assert!(tally.record(&skip(SkipReason::Stale), &skip(SkipReason::RiskBlocked)));
+
+        assert_eq!(
+            tally,
+            ShadowTally {
+                agreed: 2,
+                live_only: 1,
+                shadow_only: 1,
+                sized_differently: 1,
+                skipped_differently: 1,
+            }
+        );
+        assert_eq!(tally.total(), 6);
+    }
+}
@@ -1,18 +1,25 @@
 use crate::types::{Config, SizingMode, Trade};
 use anyhow::Result;
+use std::sync::RwLock;
 
 pub struct PositionSizer {
-    config: Config,
+    config: RwLock<Config>,
 }
 
 impl PositionSizer {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self { config: RwLock::new(config) }
+    }
+    
+    /// Applies new sizing settings, e.g. after a config reload.
+    pub fn reconfigure(&self, config: Config) {
+        *self.config.write().unwrap() = config;
     }
     
     pub async fn calculate_size(&self, whale_trade: &Trade, your_balance: f64, whale_balance: f64) -> Result<f64> {
-        let size = match self.config.sizing_mode {
-            SizingMode::Fixed => self.config.fixed_stake,
+        let config = self.config.read().unwrap();
+        let size = match config.sizing_mode {
+            SizingMode::Fixed => config.fixed_stake.as_f64(),
             
             SizingMode::Proportional => {
                 let ratio = your_balance / whale_balance.max(1.0);
@@ -22,13 +29,13 @@ impl PositionSizer {
             SizingMode::TierBased => {
                 let trade_size = whale_trade.shares * whale_trade.price;
                 let multiplier = self.get_tier_multiplier(trade_size);
-                whale_trade.shares * multiplier * self.config.proportional_ratio
+                whale_trade.shares * multiplier * config.proportional_ratio.as_f64()
             },
         };
         
         // Apply limits
-        let size = size.max(self.config.min_stake);
-        let size = size.min(self.config.max_stake);
+        let size = size.max(config.min_stake.as_f64());
+        let size = size.min(config.max_stake.as_f64());
         
         // Check if we have enough balance
         let size = size.min(your_balance * 0.95); // Keep 5% buffer
@@ -36,7 +43,7 @@ impl PositionSizer {
         tracing::info!(
             "Calculated size: ${:.2} (mode: {:?}, whale: ${:.2})",
             size,
-            self.config.sizing_mode,
+            config.sizing_mode,
             whale_trade.shares * whale_trade.price
         );
         
@@ -68,15 +75,16 @@ impl PositionSizer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::units::UsdcAmount;
     use crate::types::TradeSide;
     
     #[tokio::test]
     async fn test_fixed_sizing() {
         let config = Config {
             sizing_mode: SizingMode::Fixed,
-            fixed_stake: 25.0,
-            min_stake: 5.0,
-            max_stake: 100.0,
+            fixed_stake: UsdcAmount(25.0),
+            min_stake: UsdcAmount(5.0),
+            max_stake: UsdcAmount(100.0),
             ..Default::default()
         };
         
@@ -100,8 +108,8 @@ mod tests {
     async fn test_proportional_sizing() {
         let config = Config {
             sizing_mode: SizingMode::Proportional,
-            min_stake: 5.0,
-            max_stake: 100.0,
+            min_stake: UsdcAmount(5.0),
+            max_stake: UsdcAmount(100.0),
             ..Default::default()
         };
         
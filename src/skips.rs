@@ -0,0 +1,186 @@
+//! Why copies are being skipped, for tuning the thresholds behind them.
+//!
+//! Every decision is counted by outcome: `/metrics` exports
+//! `polymarket_bot_skips_total{reason="..."}` and
+//! `polymarket_bot_copies_total` since startup, and `/status/skips?window=6h`
+//! breaks down the decisions within a window. Windows up to
+//! [`SKIP_HISTORY`] are answered from memory, longer ones from the decision
+//! journal.
+
+use crate::storage::DecisionRecord;
+use crate::types::SkipReason;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How far back the in-memory counts go.
+pub const SKIP_HISTORY: Duration = Duration::from_secs(24 * 3600);
+const BUCKET_MS: i64 = 60_000;
+
+#[derive(Debug, Clone, Default)]
+struct Counts {
+    copied: u64,
+    skipped: HashMap<SkipReason, u64>,
+}
+
+impl Counts {
+    fn add(&mut self, skip: Option<SkipReason>) {
+        match skip {
+            Some(reason) => *self.skipped.entry(reason).or_default() += 1,
+            None => self.copied += 1,
+        }
+    }
+}
+
+/// Decisions within a window, by outcome.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkipBreakdown {
+    pub window_secs: u64,
+    pub decisions: u64,
+    pub copied: u64,
+    pub skipped: u64,
+    /// Every reason, including those with no skips
+    pub by_reason: BTreeMap<&'static str, u64>,
+}
+
+impl SkipBreakdown {
+    fn new(window: Duration, counts: &Counts) -> Self {
+        let by_reason: BTreeMap<&'static str, u64> = SkipReason::ALL
+            .iter()
+            .map(|r| (r.as_str(), counts.skipped.get(r).copied().unwrap_or_default()))
+            .collect();
+        let skipped = by_reason.values().sum();
+        Self {
+            window_secs: window.as_secs(),
+            decisions: counts.copied + skipped,
+            copied: counts.copied,
+            skipped,
+            by_reason,
+        }
+    }
+
+    /// Breakdown of journaled decisions.
+    pub fn from_decisions(window: Duration, decisions: &[DecisionRecord]) -> Self {
+        let mut counts = Counts::default();
+        for decision in decisions {
+            counts.add(if decision.copied { None } else { decision.reason });
+        }
+        Self::new(window, &counts)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    total: Counts,
+    /// Counts per minute (start in unix ms), oldest first
+    buckets: VecDeque<(i64, Counts)>,
+}
+
+/// Decision counts by outcome, in total and per minute.
+#[derive(Debug, Default)]
+pub struct SkipStats {
+    state: Mutex<State>,
+}
+
+impl SkipStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A trade was decided on; `skip` is the reason it wasn't copied.
+    pub fn record(&self, now_ms: i64, skip: Option<SkipReason>) {
+        let mut state = self.state.lock().unwrap();
+        state.total.add(skip);
+        let minute = now_ms - now_ms.rem_euclid(BUCKET_MS);
+        if state.buckets.back().is_none_or(|(start, _)| *start < minute) {
+            state.buckets.push_back((minute, Counts::default()));
+        }
+        if let Some((_, counts)) = state.buckets.back_mut() {
+            counts.add(skip);
+        }
+        let oldest = now_ms - SKIP_HISTORY.as_millis() as i64;
+        while state
+            .buckets
+            .front()
+            .is_some_and(|(start, _)| *start + BUCKET_MS <= oldest)
+        {
+            state.buckets.pop_front();
+        }
+    }
+
+    /// Decisions within the last `window`, to the minute; `None` if that
+    /// is longer than [`SKIP_HISTORY`].
+    pub fn breakdown(&self, window: Duration, now_ms: i64) -> Option<SkipBreakdown> {
+        if window > SKIP_HISTORY {
+            return None;
+        }
+        let from = now_ms - window.as_millis() as i64;
+        let state = self.state.lock().unwrap();
+        let mut counts = Counts::default();
+        for (_, bucket) in state.buckets.iter().filter(|(start, _)| *start + BUCKET_MS > from) {
+            counts.copied += bucket.copied;
+            for (reason, n) in &bucket.skipped {
+                *counts.skipped.entry(*reason).or_default() += n;
+            }
+        }
+        Some(SkipBreakdown::new(window, &counts))
+    }
+
+    /// Counters since startup in the Prometheus text format.
+    pub fn render_prometheus(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP polymarket_bot_skips_total Leader trades not copied, by reason."
+        );
+        let _ = writeln!(out, "# TYPE polymarket_bot_skips_total counter");
+        for reason in SkipReason::ALL {
+            let _ = writeln!(
+                out,
+                "polymarket_bot_skips_total{{reason=\"{}\"}} {}",
+                reason.as_str(),
+                state.total.skipped.get(reason).copied().unwrap_or_default()
+            );
+        }
+        let _ = writeln!(out, "# HELP polymarket_bot_copies_total Leader trades decided to copy.");
+        let _ = writeln!(out, "# TYPE polymarket_bot_copies_total counter");
+        let _ = writeln!(out, "polymarket_bot_copies_total {}", state.total.copied);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+
+    #[test]
+    fn test_breakdown_by_window() {
+        let stats = SkipStats::new();
+        stats.record(0, Some(SkipReason::Stale));
+        stats.record(30 * MINUTE, Some(SkipReason::RiskBlocked));
+        stats.record(50 * MINUTE, Some(SkipReason::Stale));
+        stats.record(50 * MINUTE + 10, None);
+
+        let hour = stats.breakdown(Duration::from_secs(3600), 70 * MINUTE).unwrap();
+        assert_eq!((hour.decisions, hour.copied, hour.skipped), (3, 1, 2));
+        assert_eq!(hour.by_reason["stale"], 1);
+        assert_eq!(hour.by_reason["risk_blocked"], 1);
+        assert_eq!(hour.by_reason["paused"], 0);
+        let recent = stats.breakdown(Duration::from_secs(25 * 60), 70 * MINUTE).unwrap();
+        assert_eq!((recent.decisions, recent.by_reason["stale"]), (2, 1));
+        assert!(stats.breakdown(Duration::from_secs(48 * 3600), 70 * MINUTE).is_none());
+
+        // Totals outlive the window
+        stats.record(48 * 60 * MINUTE, None);
+        let day = stats.breakdown(SKIP_HISTORY, 48 * 60 * MINUTE).unwrap();
+        assert_eq!(day.decisions, 1);
+        let metrics = stats.render_prometheus();
+        assert!(metrics.contains("polymarket_bot_skips_total{reason=\"stale\"} 2\n"));
+        assert!(metrics.contains("polymarket_bot_copies_total 2\n"));
+    }
+}
@@ -0,0 +1,406 @@
+//! What copying costs against the leader's price, and where it goes.
+//!
+//! Every decision is journaled with the book mid at the moment the bot
+//! decided (its arrival mid). For each copy that filled, the leader's
+//! price, the arrival mid and the average price of the copy's fills split
+//! its slippage in two:
+//!
+//! - latency: leader's price to arrival mid, how far the market moved
+//!   before the bot could act
+//! - execution: arrival mid to fill, the spread and book depth crossed
+//!
+//! Both are fractions of the leader's price, so they add up to the total,
+//! and positive is a cost for buys and sells alike. Aggregates weight
+//! copies by their notional at the leader's price and are broken down by
+//! leader, by market category and by UTC hour of the decision. Copies
+//! decided before arrival mids were journaled only count towards the total.
+
+use crate::markets::MarketCache;
+use crate::storage::{DecisionRecord, FillRecord, LeaderTradeRecord, OrderRecord, Storage, TimeRange};
+use crate::types::{Market, TradeSide};
+use anyhow::Result;
+use chrono::Timelike;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// One filled copy.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CopySlippage {
+    pub decision_id: i64,
+    pub wallet: String,
+    pub market_id: String,
+    pub category: String,
+    pub side: TradeSide,
+    /// Unix ms
+    pub decided_at: i64,
+    pub leader_price: f64,
+    pub arrival_mid: Option<f64>,
+    /// Average over the copy's fills
+    pub fill_price: f64,
+    pub shares: f64,
+}
+
+impl CopySlippage {
+    /// How much worse `to` is than `from` for this side, per share.
+    fn worse(&self, from: f64, to: f64) -> f64 {
+        match self.side {
+            TradeSide::BUY => to - from,
+            TradeSide::SELL => from - to,
+        }
+    }
+
+    pub fn notional(&self) -> f64 {
+        self.leader_price * self.shares
+    }
+
+    /// Leader's price to fill, as a fraction of the leader's price.
+    pub fn total(&self) -> f64 {
+        self.worse(self.leader_price, self.fill_price) / self.leader_price.max(f64::EPSILON)
+    }
+
+    /// Leader's price to arrival mid.
+    pub fn latency(&self) -> Option<f64> {
+        let mid = self.arrival_mid?;
+        Some(self.worse(self.leader_price, mid) / self.leader_price.max(f64::EPSILON))
+    }
+
+    /// Arrival mid to fill.
+    pub fn execution(&self) -> Option<f64> {
+        let mid = self.arrival_mid?;
+        Some(self.worse(mid, self.fill_price) / self.leader_price.max(f64::EPSILON))
+    }
+}
+
+/// Copies summed by leader, category or hour.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SlippageBucket {
+    pub name: String,
+    pub copies: usize,
+    /// At the leader's price
+    pub notional: f64,
+    /// Paid over the leader's price, in USD
+    pub cost: f64,
+    /// Copies with an arrival mid, and their notional and costs
+    pub with_mid: usize,
+    pub notional_with_mid: f64,
+    pub latency_cost: f64,
+    pub execution_cost: f64,
+}
+
+impl SlippageBucket {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, copy: &CopySlippage) {
+        let notional = copy.notional();
+        self.copies += 1;
+        self.notional += notional;
+        self.cost += copy.total() * notional;
+        if let (Some(latency), Some(execution)) = (copy.latency(), copy.execution()) {
+            self.with_mid += 1;
+            self.notional_with_mid += notional;
+            self.latency_cost += latency * notional;
+            self.execution_cost += execution * notional;
+        }
+    }
+
+    /// Notional-weighted slippage against the leader's price.
+    pub fn total(&self) -> Option<f64> {
+        (self.notional > 0.0).then(|| self.cost / self.notional)
+    }
+
+    pub fn latency(&self) -> Option<f64> {
+        (self.notional_with_mid > 0.0).then(|| self.latency_cost / self.notional_with_mid)
+    }
+
+    pub fn execution(&self) -> Option<f64> {
+        (self.notional_with_mid > 0.0).then(|| self.execution_cost / self.notional_with_mid)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SlippageReport {
+    pub overall: SlippageBucket,
+    /// Copies decided but never filled
+    pub unfilled: usize,
+    /// Costliest first
+    pub leaders: Vec<SlippageBucket>,
+    /// Costliest first; markets without a category count as "other"
+    pub categories: Vec<SlippageBucket>,
+    /// UTC hours of the decision ("00" to "23"), in order
+    pub hours: Vec<SlippageBucket>,
+    /// Oldest first
+    pub copies: Vec<CopySlippage>,
+}
+
+impl std::fmt::Display for SlippageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pct = |v: Option<f64>| {
+            v.map(|v| format!("{:+.2}%", v * 100.0))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let o = &self.overall;
+        writeln!(
+            f,
+            "Copies:        {} filled ({} with an arrival mid), {} unfilled",
+            o.copies, o.with_mid, self.unfilled
+        )?;
+        writeln!(f, "Notional:      ${:.2}", o.notional)?;
+        writeln!(f, "Slippage:      {} (${:.2})", pct(o.total()), o.cost)?;
+        writeln!(f, "  latency:     {} (${:.2})", pct(o.latency()), o.latency_cost)?;
+        writeln!(f, "  execution:   {} (${:.2})", pct(o.execution()), o.execution_cost)?;
+        for (title, rows) in [
+            ("leader", &self.leaders),
+            ("category", &self.categories),
+            ("hour (UTC)", &self.hours),
+        ] {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{:<44} {:>7} {:>12} {:>9} {:>9} {:>9} {:>10}",
+                title, "copies", "notional", "slippage", "latency", "execution", "cost"
+            )?;
+            for b in rows {
+                writeln!(
+                    f,
+                    "{:<44} {:>7} {:>12.2} {:>9} {:>9} {:>9} {:>10.2}",
+                    b.name,
+                    b.copies,
+                    b.notional,
+                    pct(b.total()),
+                    pct(b.latency()),
+                    pct(b.execution()),
+                    b.cost
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Slippage of the copies among `decisions`, each reached from its leader
+/// trade in `trades` and filled through `orders`; `markets` give their
+/// categories.
+pub fn compute(
+    trades: &[LeaderTradeRecord],
+    decisions: &[DecisionRecord],
+    orders: &[OrderRecord],
+    fills: &[FillRecord],
+    markets: &HashMap<String, Market>,
+) -> SlippageReport {
+    let trades: HashMap<i64, &LeaderTradeRecord> = trades.iter().map(|t| (t.id, t)).collect();
+    let decision_of_order: HashMap<i64, i64> = orders.iter().filter_map(|o| o.decision_id.map(|d| (o.id, d))).collect();
+    // Shares and notional filled per decision
+    let mut filled: HashMap<i64, (f64, f64)> = HashMap::new();
+    for fill in fills {
+        if let Some(decision_id) = decision_of_order.get(&fill.order_id) {
+            let (shares, notional) = filled.entry(*decision_id).or_default();
+            *shares += fill.shares;
+            *notional += fill.shares * fill.price;
+        }
+    }
+
+    let mut report = SlippageReport {
+        overall: SlippageBucket::new("all"),
+        ..Default::default()
+    };
+    for decision in decisions.iter().filter(|d| d.copied) {
+        let Some(trade) = decision.leader_trade_id.and_then(|id| trades.get(&id)) else {
+            continue;
+        };
+        let Some(&(shares, notional)) = filled.get(&decision.id).filter(|(shares, _)| *shares > 0.0) else {
+            report.unfilled += 1;
+            continue;
+        };
+        report.copies.push(CopySlippage {
+            decision_id: decision.id,
+            wallet: decision.wallet.clone(),
+            market_id: decision.market_id.clone(),
+            category: markets
+                .get(&decision.market_id)
+                .map(|m| m.category.as_str())
+                .filter(|c| !c.is_empty())
+                .unwrap_or("other")
+                .to_string(),
+            side: trade.trade.side.clone(),
+            decided_at: decision.decided_at,
+            leader_price: trade.trade.price,
+            arrival_mid: decision.arrival_mid,
+            fill_price: notional / shares,
+            shares,
+        });
+    }
+
+    let mut leaders: BTreeMap<&str, SlippageBucket> = BTreeMap::new();
+    let mut categories: BTreeMap<&str, SlippageBucket> = BTreeMap::new();
+    let mut hours: BTreeMap<String, SlippageBucket> = BTreeMap::new();
+    for copy in &report.copies {
+        report.overall.add(copy);
+        leaders
+            .entry(&copy.wallet)
+            .or_insert_with(|| SlippageBucket::new(&copy.wallet))
+            .add(copy);
+        categories
+            .entry(&copy.category)
+            .or_insert_with(|| SlippageBucket::new(&copy.category))
+            .add(copy);
+        let hour = chrono::DateTime::from_timestamp_millis(copy.decided_at)
+            .map(|t| format!("{:02}", t.hour()))
+            .unwrap_or_default();
+        hours
+            .entry(hour.clone())
+            .or_insert_with(|| SlippageBucket::new(&hour))
+            .add(copy);
+    }
+    let costliest = |a: &SlippageBucket, b: &SlippageBucket| b.cost.total_cmp(&a.cost);
+    report.leaders = leaders.into_values().collect();
+    report.leaders.sort_by(costliest);
+    report.categories = categories.into_values().collect();
+    report.categories.sort_by(costliest);
+    report.hours = hours.into_values().collect();
+    report
+}
+
+/// Slippage of the copies decided within `range`, with every market
+/// copied looked up in `markets` for its category.
+pub async fn from_journal(storage: &dyn Storage, range: TimeRange, markets: &MarketCache) -> Result<SlippageReport> {
+    let decisions = storage.decisions(range).await?;
+    // Orders and fills can land after the range's last decision
+    let later = TimeRange::since(range.from_ms);
+    let orders = storage.orders(later).await?;
+    let fills = storage.fills(later).await?;
+    let trades = storage.leader_trades(TimeRange::all()).await?;
+    let mut copied = HashMap::new();
+    for decision in decisions.iter().filter(|d| d.copied) {
+        if copied.contains_key(&decision.market_id) {
+            continue;
+        }
+        match markets.get(&decision.market_id).await {
+            Ok(market) => {
+                copied.insert(decision.market_id.clone(), market);
+            }
+            Err(e) => tracing::warn!("Failed to fetch market {}: {}", decision.market_id, e),
+        }
+    }
+    Ok(compute(&trades, &decisions, &orders, &fills, &copied))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Trade;
+
+    #[test]
+    fn test_splits_slippage_into_latency_and_execution() {
+        let trade = |id, side, price| LeaderTradeRecord {
+            id,
+            trade: Trade {
+                wallet: "0xleader".to_string(),
+                event_id: String::new(),
+                market_id: "m1".to_string(),
+                side,
+                shares: 100.0,
+                price,
+                timestamp: 0,
+                tx_hash: None,
+            },
+            observed_at: 0,
+        };
+        let decision = |id, arrival_mid, decided_at| DecisionRecord {
+            id,
+            leader_trade_id: Some(id),
+            wallet: "0xleader".to_string(),
+            market_id: "m1".to_string(),
+            side: String::new(),
+            copied: true,
+            reason: None,
+            detail: None,
+            size_usd: Some(10.0),
+            decided_at,
+            arrival_mid,
+            arrival_book: None,
+        };
+        let order = |id, decision_id| OrderRecord {
+            id,
+            decision_id: Some(decision_id),
+            exchange_order_id: None,
+            market_id: "m1".to_string(),
+            side: String::new(),
+            shares: 0.0,
+            limit_price: None,
+            order_type: "LIMIT".to_string(),
+            status: "filled".to_string(),
+            error: None,
+            submitted_at: 0,
+            client_order_id: None,
+        };
+        let fill = |order_id, shares, price| FillRecord {
+            id: 0,
+            order_id,
+            market_id: "m1".to_string(),
+            side: String::new(),
+            shares,
+            price,
+            fee: 0.0,
+            filled_at: 0,
+        };
+        let hour = 3_600_000;
+        let trades = [
+            trade(1, TradeSide::BUY, 0.50),
+            trade(2, TradeSide::SELL, 0.40),
+            trade(3, TradeSide::BUY, 0.50),
+        ];
+        let decisions = [
+            // Bought at 0.53 on average after the mid moved to 0.52
+            decision(1, Some(0.52), 14 * hour),
+            // Sold at 0.38 with the mid already at 0.39
+            decision(2, Some(0.39), 15 * hour),
+            // Never filled
+            decision(3, Some(0.50), 15 * hour),
+        ];
+        let orders = [order(10, 1), order(20, 2), order(30, 3)];
+        let fills = [
+            fill(10, 10.0, 0.52),
+            fill(10, 10.0, 0.54),
+            fill(20, 25.0, 0.38),
+            fill(0, 5.0, 1.0),
+        ];
+        let markets = HashMap::from([(
+            "m1".to_string(),
+            Market {
+                id: "m1".to_string(),
+                event_id: "e1".to_string(),
+                question: "Will it?".to_string(),
+                yes_price: 0.5,
+                no_price: 0.5,
+                category: "politics".to_string(),
+                ..Default::default()
+            },
+        )]);
+        let report = compute(&trades, &decisions, &orders, &fills, &markets);
+
+        assert_eq!((report.overall.copies, report.unfilled), (2, 1));
+        let buy = &report.copies[0];
+        assert!((buy.fill_price - 0.53).abs() < 1e-9);
+        assert!((buy.total() - 0.06).abs() < 1e-9);
+        assert!((buy.latency().unwrap() - 0.04).abs() < 1e-9);
+        assert!((buy.execution().unwrap() - 0.02).abs() < 1e-9);
+        let sell = &report.copies[1];
+        assert!((sell.total() - 0.05).abs() < 1e-9);
+
+        // 20 shares at 0.50 and 25 at 0.40: $10 of notional each
+        let o = &report.overall;
+        assert!((o.notional - 20.0).abs() < 1e-9);
+        assert!((o.cost - 1.1).abs() < 1e-9);
+        assert!((o.total().unwrap() - 0.055).abs() < 1e-9);
+        assert!((o.latency().unwrap() + o.execution().unwrap() - 0.055).abs() < 1e-9);
+        assert_eq!(report.categories[0].name, "politics");
+        let hours: Vec<&str> = report.hours.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(hours, ["14", "15"]);
+        assert!(report.to_string().contains("+5.50%"));
+    }
+}
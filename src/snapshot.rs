@@ -0,0 +1,251 @@
+//! `--snapshot` / `--restore`: the bot's persistent state in one file, for
+//! moving it to another server or rolling back after a bad deployment.
+//!
+//! The journal history is not included; archive or export it separately.
+//! Stop the bot before restoring.
+
+use crate::leaders::{LeaderStats, LEADER_STATS_KEY};
+use crate::risk::{RiskSnapshot, RISK_STATE_KEY};
+use crate::storage::{now_ms, LotRecord, OrderRecord, PositionRecord, Storage};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// Bumped whenever the layout changes incompatibly.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub created_at: i64,
+    pub positions: Vec<PositionRecord>,
+    pub lots: Vec<LotRecord>,
+    pub open_orders: Vec<OrderRecord>,
+    pub risk: Option<RiskSnapshot>,
+    pub leaders: Vec<LeaderStats>,
+}
+
+impl Snapshot {
+    pub async fn take(storage: &dyn Storage) -> Result<Self> {
+        let risk = match storage.load_state(RISK_STATE_KEY).await? {
+            Some(json) => Some(serde_json::from_str(&json).context("Stored risk state is corrupt")?),
+            None => None,
+        };
+        let leaders = match storage.load_state(LEADER_STATS_KEY).await? {
+            Some(json) => serde_json::from_str(&json).context("Stored leader stats are corrupt")?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            version: SNAPSHOT_VERSION,
+            created_at: now_ms(),
+            positions: storage.positions().await?,
+            lots: storage.lots().await?,
+            open_orders: storage.open_orders().await?,
+            risk,
+            leaders,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let value: serde_json::Value =
+            serde_json::from_slice(&bytes).with_context(|| format!("{} is not a snapshot", path.display()))?;
+        let version = value["version"].as_u64().unwrap_or(0);
+        if version == 0 || version > SNAPSHOT_VERSION as u64 {
+            anyhow::bail!(
+                "Unsupported snapshot version {} (this build reads up to {})",
+                version,
+                SNAPSHOT_VERSION
+            );
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Makes `storage` hold exactly this snapshot's positions and state.
+    /// Open orders are added unless an order with the same exchange or client
+    /// id is already journaled; they lose their link to the (absent) decision.
+    pub async fn restore(&self, storage: &dyn Storage) -> Result<RestoreReport> {
+        let mut report = RestoreReport::default();
+        let now = now_ms();
+
+        let keep: HashSet<&str> = self.positions.iter().map(|p| p.market_id.as_str()).collect();
+        for stale in storage.positions().await? {
+            if !keep.contains(stale.market_id.as_str()) {
+                let closed = PositionRecord {
+                    shares: 0.0,
+                    updated_at: now,
+                    ..stale
+                };
+                storage.save_holding(&closed, &[]).await?;
+                report.positions_closed += 1;
+            }
+        }
+        for position in &self.positions {
+            let lots: Vec<LotRecord> = self
+                .lots
+                .iter()
+                .filter(|l| l.market_id == position.market_id)
+                .cloned()
+                .collect();
+            storage.save_holding(position, &lots).await?;
+            report.positions += 1;
+        }
+
+        let open = storage.open_orders().await?;
+        let known: HashSet<&str> = open
+            .iter()
+            .flat_map(|o| [o.exchange_order_id.as_deref(), o.client_order_id.as_deref()])
+            .flatten()
+            .collect();
+        for order in &self.open_orders {
+            let ids = [order.exchange_order_id.as_deref(), order.client_order_id.as_deref()];
+            if ids.into_iter().flatten().any(|id| known.contains(id)) {
+                continue;
+            }
+            let restored = OrderRecord {
+                decision_id: None,
+                ..order.clone()
+            };
+            storage.record_order(&restored).await?;
+            report.orders += 1;
+        }
+
+        if let Some(risk) = &self.risk {
+            storage
+                .save_state(RISK_STATE_KEY, &serde_json::to_string(risk)?, now)
+                .await?;
+        }
+        storage
+            .save_state(LEADER_STATS_KEY, &serde_json::to_string(&self.leaders)?, now)
+            .await?;
+
+        Ok(report)
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} positions, {} open orders, {} leaders{}",
+            self.positions.len(),
+            self.open_orders.len(),
+            self.leaders.len(),
+            if self.risk.is_some() { ", risk counters" } else { "" }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RestoreReport {
+    pub positions: usize,
+    pub positions_closed: usize,
+    pub orders: usize,
+}
+
+impl fmt::Display for RestoreReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "restored {} positions ({} closed) and {} open orders",
+            self.positions, self.positions_closed, self.orders
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStore;
+    use crate::storage::{Journal, PositionStore, StateStore};
+
+    #[tokio::test]
+    async fn test_snapshot_roundtrip_between_databases() {
+        let source = SqliteStore::open_in_memory().unwrap();
+        let position = PositionRecord {
+            market_id: "market1".to_string(),
+            shares: 20.0,
+            avg_price: 0.5,
+            updated_at: 1,
+        };
+        let lots = vec![
+            LotRecord {
+                market_id: "market1".to_string(),
+                shares: 10.0,
+                price: 0.4,
+                opened_at: 1,
+            },
+            LotRecord {
+                market_id: "market1".to_string(),
+                shares: 10.0,
+                price: 0.6,
+                opened_at: 2,
+            },
+        ];
+        source.save_holding(&position, &lots).await.unwrap();
+        source
+            .record_order(&OrderRecord {
+                id: 0,
+                decision_id: None,
+                exchange_order_id: Some("ex-1".to_string()),
+                market_id: "market1".to_string(),
+                side: "BUY".to_string(),
+                shares: 5.0,
+                limit_price: Some(0.5),
+                order_type: "GTC".to_string(),
+                status: "open".to_string(),
+                error: None,
+                submitted_at: 3,
+                client_order_id: Some("cb-1".to_string()),
+            })
+            .await
+            .unwrap();
+        let leaders = vec![LeaderStats {
+            wallet: "0xleader".to_string(),
+            trades_seen: 3,
+            ..Default::default()
+        }];
+        source
+            .save_state(LEADER_STATS_KEY, &serde_json::to_string(&leaders).unwrap(), 4)
+            .await
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("snapshot-test-{}.json", std::process::id()));
+        Snapshot::take(&source).await.unwrap().write(&path).unwrap();
+        let snapshot = Snapshot::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let target = SqliteStore::open_in_memory().unwrap();
+        target
+            .set_position(&PositionRecord {
+                market_id: "old".to_string(),
+                shares: 1.0,
+                avg_price: 0.1,
+                updated_at: 0,
+            })
+            .await
+            .unwrap();
+        let report = snapshot.restore(&target).await.unwrap();
+        assert_eq!((report.positions, report.positions_closed, report.orders), (1, 1, 1));
+        assert_eq!(target.positions().await.unwrap(), vec![position]);
+        assert_eq!(target.lots().await.unwrap(), lots);
+
+        // Restoring twice doesn't duplicate orders
+        assert_eq!(snapshot.restore(&target).await.unwrap().orders, 0);
+        assert_eq!(target.open_orders().await.unwrap().len(), 1);
+        assert!(target
+            .load_state(LEADER_STATS_KEY)
+            .await
+            .unwrap()
+            .unwrap()
+            .contains("0xleader"));
+    }
+}
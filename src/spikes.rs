@@ -0,0 +1,310 @@
+//! Abnormal activity in markets, whoever is trading them.
+//!
+//! Every `spike_interval` [`SpikeDetector`] lists the `spike_markets` most
+//! active markets on the Gamma API and samples each one's 24h volume and,
+//! with `data_api` set, how many wallets hold it. A sample is compared with
+//! the mean of that market's samples over the last `spike_window`:
+//!
+//! - **volume_spike**: 24h volume at `spike_factor` times the mean or more
+//! - **holder_surge**: holders up by `spike_holder_change` or more
+//!
+//! Either is published as a [`BotEvent::MarketAnomaly`] for strategies and
+//! embedders, and sent as a [`Notification::MarketAnomaly`]. A market is
+//! flagged once per kind until it drops back under the threshold. Holder
+//! counts are of the largest holders the data API lists per outcome, so a
+//! market past that many holders can't surge.
+
+use crate::data_api;
+use crate::events::{BotEvent, EventBus};
+use crate::gamma::{self, Query};
+use crate::notify::{Notification, Notifications};
+use crate::types::Config;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Earlier samples a market needs before it can be flagged.
+const MIN_SAMPLES: usize = 3;
+
+/// Holders asked for per outcome token.
+const HOLDER_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    VolumeSpike,
+    HolderSurge,
+}
+
+impl AnomalyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::VolumeSpike => "volume_spike",
+            AnomalyKind::HolderSurge => "holder_surge",
+        }
+    }
+}
+
+/// A market whose activity jumped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketAnomaly {
+    pub market_id: String,
+    pub question: String,
+    pub kind: AnomalyKind,
+    /// 24h volume in USDC, or holders
+    pub value: f64,
+    /// Mean of the earlier samples in the window
+    pub baseline: f64,
+    /// Unix ms
+    pub at: i64,
+}
+
+impl MarketAnomaly {
+    pub fn ratio(&self) -> f64 {
+        self.value / self.baseline
+    }
+}
+
+impl From<&MarketAnomaly> for Notification {
+    fn from(anomaly: &MarketAnomaly) -> Self {
+        Notification::MarketAnomaly {
+            market_id: anomaly.market_id.clone(),
+            question: anomaly.question.clone(),
+            kind: anomaly.kind.as_str().to_string(),
+            value: anomaly.value,
+            baseline: anomaly.baseline,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    samples: HashMap<(String, AnomalyKind), VecDeque<(i64, f64)>>,
+    flagged: HashSet<(String, AnomalyKind)>,
+}
+
+pub struct SpikeDetector {
+    gamma: Arc<gamma::Client>,
+    data: Option<data_api::Client>,
+    markets: usize,
+    interval: Duration,
+    window: Duration,
+    factor: f64,
+    holder_change: f64,
+    state: Mutex<State>,
+    events: Arc<EventBus>,
+    notifications: Notifications,
+}
+
+impl SpikeDetector {
+    pub fn new(gamma: Arc<gamma::Client>, config: &Config) -> Self {
+        Self {
+            gamma,
+            data: None,
+            markets: config.spike_markets as usize,
+            interval: config.spike_interval,
+            window: config.spike_window,
+            factor: config.spike_factor,
+            holder_change: config.spike_holder_change,
+            state: Mutex::new(State::default()),
+            events: Arc::new(EventBus::new()),
+            notifications: Notifications::default(),
+        }
+    }
+
+    /// `None` when `spike_markets` is zero or there's no Gamma API.
+    pub fn from_config(config: &Config, gamma: Option<Arc<gamma::Client>>) -> Option<Self> {
+        if config.spike_markets == 0 || config.spike_interval.is_zero() {
+            return None;
+        }
+        let detector = Self::new(gamma?, config);
+        Some(match data_api::Client::from_config(config) {
+            Some(data) => detector.with_data_api(data),
+            None => detector,
+        })
+    }
+
+    /// Counts holders through `data`.
+    pub fn with_data_api(mut self, data: data_api::Client) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn with_events(mut self, events: Arc<EventBus>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    fn threshold(&self, kind: AnomalyKind, baseline: f64) -> f64 {
+        match kind {
+            AnomalyKind::VolumeSpike => baseline * self.factor,
+            AnomalyKind::HolderSurge => baseline * (1.0 + self.holder_change),
+        }
+    }
+
+    /// Records a sample of `kind` for the market at `now`; returns the
+    /// anomaly when this sample is one.
+    pub fn observe(
+        &self,
+        kind: AnomalyKind,
+        market_id: &str,
+        question: &str,
+        value: f64,
+        now: i64,
+    ) -> Option<MarketAnomaly> {
+        let mut state = self.state.lock().unwrap();
+        let key = (market_id.to_string(), kind);
+        let samples = state.samples.entry(key.clone()).or_default();
+        let horizon = now - self.window.as_millis() as i64;
+        while samples.front().is_some_and(|(at, _)| *at < horizon) {
+            samples.pop_front();
+        }
+        let earlier = samples.len();
+        let baseline = samples.iter().map(|(_, v)| v).sum::<f64>() / earlier.max(1) as f64;
+        samples.push_back((now, value));
+        if earlier < MIN_SAMPLES || baseline <= 0.0 {
+            return None;
+        }
+        if value < self.threshold(kind, baseline) {
+            state.flagged.remove(&key);
+            return None;
+        }
+        if !state.flagged.insert(key) {
+            return None;
+        }
+        Some(MarketAnomaly {
+            market_id: market_id.to_string(),
+            question: question.to_string(),
+            kind,
+            value,
+            baseline,
+            at: now,
+        })
+    }
+
+    fn emit(&self, anomaly: &MarketAnomaly) {
+        tracing::info!(
+            "📊 {} in {}: {:.0} vs {:.0} ({:.1}x)",
+            anomaly.kind.as_str(),
+            anomaly.question,
+            anomaly.value,
+            anomaly.baseline,
+            anomaly.ratio()
+        );
+        self.events.publish(BotEvent::MarketAnomaly {
+            anomaly: anomaly.clone(),
+        });
+        self.notifications.send(Notification::from(anomaly));
+    }
+
+    /// Samples every watched market once; returns the anomalies found.
+    pub async fn scan(&self) -> Result<Vec<MarketAnomaly>> {
+        let query = Query {
+            active: Some(true),
+            closed: Some(false),
+            limit: self.markets,
+            ..Default::default()
+        };
+        let markets = self.gamma.markets(&query).await?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut found = Vec::new();
+        for market in &markets {
+            found.extend(self.observe(
+                AnomalyKind::VolumeSpike,
+                &market.id,
+                &market.question,
+                market.volume_24h,
+                now,
+            ));
+            let Some(data) = &self.data else {
+                continue;
+            };
+            match data.holders(&market.id, HOLDER_LIMIT).await {
+                Ok(holders) => {
+                    let count = holders.iter().map(|h| h.wallet.as_str()).collect::<HashSet<_>>().len();
+                    found.extend(self.observe(
+                        AnomalyKind::HolderSurge,
+                        &market.id,
+                        &market.question,
+                        count as f64,
+                        now,
+                    ));
+                }
+                Err(e) => tracing::debug!("No holders for {}: {:#}", market.id, e),
+            }
+        }
+        for anomaly in &found {
+            self.emit(anomaly);
+        }
+        Ok(found)
+    }
+
+    /// Scans every `spike_interval` in the background.
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.scan().await {
+                    tracing::warn!("Market spike scan failed: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_spikes_against_the_window_mean() {
+        let config = Config {
+            spike_window: Duration::from_secs(3600),
+            spike_factor: 3.0,
+            spike_holder_change: 0.5,
+            ..Default::default()
+        };
+        let gamma = Arc::new(gamma::Client::new("https://example", Duration::ZERO));
+        let detector = SpikeDetector::new(gamma, &config);
+        let volume = |value: f64, minute: i64| {
+            detector.observe(AnomalyKind::VolumeSpike, "m1", "Will it?", value, minute * 60_000)
+        };
+
+        // Too few samples to judge the first jump
+        assert!(volume(1_000.0, 0).is_none());
+        assert!(volume(5_000.0, 5).is_none());
+        assert!(volume(1_000.0, 10).is_none());
+        // Mean of 1000, 5000 and 1000 is 2333; 7000 is 3x
+        let spike = volume(7_000.0, 15).unwrap();
+        assert_eq!((spike.kind, spike.value), (AnomalyKind::VolumeSpike, 7_000.0));
+        assert!((spike.ratio() - 3.0).abs() < 1e-9);
+        // Flagged once until it clears
+        assert!(volume(20_000.0, 20).is_none());
+        assert!(volume(1_000.0, 25).is_none());
+        assert!(volume(30_000.0, 30).is_some());
+        // Samples past the window don't count towards the mean
+        assert!(volume(10_000.0, 120).is_none());
+
+        for (minute, holders) in [(0, 100.0), (5, 100.0), (10, 100.0)] {
+            assert!(detector
+                .observe(AnomalyKind::HolderSurge, "m1", "Will it?", holders, minute)
+                .is_none());
+        }
+        assert!(detector
+            .observe(AnomalyKind::HolderSurge, "m1", "Will it?", 140.0, 15)
+            .is_none());
+        // 140 lifted the mean to 110
+        let surge = detector
+            .observe(AnomalyKind::HolderSurge, "m1", "Will it?", 170.0, 20)
+            .unwrap();
+        assert_eq!(Notification::from(&surge).kind(), "market_anomaly");
+    }
+}
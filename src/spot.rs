@@ -0,0 +1,394 @@
+//! Spot prices of the crypto assets markets resolve on.
+//!
+//! With `spot_assets` set (e.g. "BTC,ETH"), [`SpotFeed`] streams their
+//! trades from `spot_ws_url`, Binance's trade streams or Coinbase's ticker
+//! channel. Markets asking whether an asset ends above or below a price, or
+//! reaches one, by their end date are read into a [`Threshold`], and
+//! [`Threshold::probability`] gives the chance the underlying gets there
+//! from its current price: driftless and lognormal at `spot_volatility`
+//! a year, doubled for touch questions ("reach", "dip to"), which only
+//! have to get there once. Copies buying an outcome with less than
+//! `spot_min_probability` left are skipped as hopeless.
+//!
+//! Range questions ("between $X and $Y") and questions without a dollar
+//! strike aren't read. A price older than [`STALE_AFTER`] isn't used.
+
+use crate::api::number;
+use crate::clock::Clock;
+use crate::types::Config;
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// How old a spot price may be and still be used.
+pub const STALE_AFTER: Duration = Duration::from_secs(60);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+const MS_PER_YEAR: f64 = 365.25 * 24.0 * 3600.0 * 1000.0;
+
+/// Names an asset goes by in market questions.
+const ALIASES: &[(&str, &[&str])] = &[
+    ("BTC", &["bitcoin", "btc"]),
+    ("ETH", &["ethereum", "eth", "ether"]),
+    ("SOL", &["solana", "sol"]),
+    ("XRP", &["xrp", "ripple"]),
+    ("DOGE", &["dogecoin", "doge"]),
+];
+
+/// Quote currencies stripped from exchange symbols.
+const QUOTES: &[&str] = &["USDT", "USDC", "USD"];
+
+const BELOW: &[&str] = &["below", "under", "less than", "lower than", "dip", "drop", "fall"];
+const ABOVE: &[&str] = &[
+    "above",
+    "over",
+    "greater than",
+    "higher than",
+    "reach",
+    "hit",
+    "exceed",
+    "surpass",
+];
+/// Words asking whether the price gets there at any time, not where it ends.
+const TOUCH: &[&str] = &["reach", "hit", "dip", "drop", "fall", "surpass"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+/// A market resolving yes when an asset's price is past a strike.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Threshold {
+    /// E.g. "BTC"
+    pub asset: String,
+    pub strike: f64,
+    pub direction: Direction,
+    /// Yes once the price gets there, rather than if it ends there
+    pub touch: bool,
+}
+
+fn has_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(at, _)| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(|c| c.is_alphanumeric() && c != 's')
+    })
+}
+
+/// The first dollar amount in `text`, e.g. "$100,000", "$3.5k" or "$1.2M".
+fn dollars(text: &str) -> Option<f64> {
+    let rest = &text[text.find('$')? + 1..];
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == ',' || c == '.'))
+        .unwrap_or(rest.len());
+    let value: f64 = rest[..end].trim_end_matches('.').replace(',', "").parse().ok()?;
+    let mut suffix = rest[end..].chars();
+    let multiplier = match (suffix.next(), suffix.next()) {
+        (Some(_), Some(c)) if c.is_alphabetic() => 1.0,
+        (Some('k' | 'K'), _) => 1e3,
+        (Some('m' | 'M'), _) => 1e6,
+        (Some('b' | 'B'), _) => 1e9,
+        _ => 1.0,
+    };
+    Some(value * multiplier)
+}
+
+impl Threshold {
+    /// Reads a question such as "Will Bitcoin be above $100,000 on
+    /// December 31?" for one of `assets`.
+    pub fn parse(question: &str, assets: &[String]) -> Option<Self> {
+        let text = question.to_lowercase();
+        if has_word(&text, "between") {
+            return None;
+        }
+        let asset = ALIASES
+            .iter()
+            .filter(|(asset, _)| assets.iter().any(|a| a.eq_ignore_ascii_case(asset)))
+            .find(|(_, names)| names.iter().any(|n| has_word(&text, n)))?
+            .0;
+        let strike = dollars(question).filter(|s| *s > 0.0)?;
+        let direction = if BELOW.iter().any(|w| has_word(&text, w)) {
+            Direction::Below
+        } else if ABOVE.iter().any(|w| has_word(&text, w)) {
+            Direction::Above
+        } else {
+            return None;
+        };
+        Some(Self {
+            asset: asset.to_string(),
+            strike,
+            direction,
+            touch: TOUCH.iter().any(|w| has_word(&text, w)),
+        })
+    }
+
+    /// Whether `spot` is already past the strike.
+    pub fn is_past(&self, spot: f64) -> bool {
+        match self.direction {
+            Direction::Above => spot >= self.strike,
+            Direction::Below => spot <= self.strike,
+        }
+    }
+
+    /// The chance of resolving yes from `spot` with `years_left`, at an
+    /// annual `volatility`.
+    pub fn probability(&self, spot: f64, years_left: f64, volatility: f64) -> f64 {
+        if self.touch && self.is_past(spot) {
+            return 1.0;
+        }
+        let spread = volatility * years_left.max(0.0).sqrt();
+        if spread <= 0.0 {
+            return if self.is_past(spot) { 1.0 } else { 0.0 };
+        }
+        let above = normal_cdf((spot / self.strike).ln() / spread);
+        let ends_past = match self.direction {
+            Direction::Above => above,
+            Direction::Below => 1.0 - above,
+        };
+        if self.touch {
+            (2.0 * ends_past).min(1.0)
+        } else {
+            ends_past
+        }
+    }
+}
+
+/// Standard normal CDF (Abramowitz and Stegun 7.1.26, error under 1e-7).
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+/// Years from `now_ms` to `end` (unix seconds).
+pub fn years_until(end: i64, now_ms: i64) -> f64 {
+    (end * 1000 - now_ms) as f64 / MS_PER_YEAR
+}
+
+/// The asset an exchange symbol trades: "BTCUSDT" and "BTC-USD" are "BTC".
+fn asset_of(symbol: &str) -> String {
+    let symbol = symbol.to_uppercase();
+    if let Some((base, _)) = symbol.split_once('-') {
+        return base.to_string();
+    }
+    QUOTES
+        .iter()
+        .find_map(|q| symbol.strip_suffix(q).filter(|base| !base.is_empty()))
+        .unwrap_or(&symbol)
+        .to_string()
+}
+
+pub struct SpotFeed {
+    ws_url: String,
+    assets: Vec<String>,
+    volatility: f64,
+    // Asset to its latest price and when it arrived (unix ms)
+    prices: Mutex<HashMap<String, (f64, i64)>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SpotFeed {
+    pub fn new(ws_url: impl Into<String>, assets: Vec<String>, volatility: f64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            ws_url: ws_url.into(),
+            assets: assets.iter().map(|a| a.to_uppercase()).collect(),
+            volatility,
+            prices: Mutex::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    /// `None` without `spot_assets`.
+    pub fn from_config(config: &Config, clock: Arc<dyn Clock>) -> Option<Self> {
+        (!config.spot_assets.is_empty()).then(|| {
+            Self::new(
+                &config.spot_ws_url,
+                config.spot_assets.clone(),
+                config.spot_volatility,
+                clock,
+            )
+        })
+    }
+
+    pub fn assets(&self) -> &[String] {
+        &self.assets
+    }
+
+    /// The asset's latest price, unless it's stale.
+    pub fn price(&self, asset: &str) -> Option<f64> {
+        let (price, at) = *self.prices.lock().unwrap().get(&asset.to_uppercase())?;
+        (self.clock.now_ms() - at <= STALE_AFTER.as_millis() as i64).then_some(price)
+    }
+
+    /// The question's threshold and the chance it resolves yes at the
+    /// asset's current price; `None` when it isn't a question on a
+    /// streamed asset or there's no fresh price.
+    pub fn assess(&self, question: &str, end_date: Option<i64>) -> Option<(Threshold, f64, f64)> {
+        let threshold = Threshold::parse(question, &self.assets)?;
+        let spot = self.price(&threshold.asset)?;
+        let years = years_until(end_date?, self.clock.now_ms());
+        let probability = threshold.probability(spot, years, self.volatility);
+        Some((threshold, spot, probability))
+    }
+
+    /// Records the prices in a frame from Binance or Coinbase; returns how
+    /// many.
+    pub fn apply(&self, text: &str) -> usize {
+        let Ok(frame) = serde_json::from_str::<Value>(text) else {
+            return 0;
+        };
+        // Binance combined streams wrap each event
+        let message = if frame["data"].is_object() {
+            &frame["data"]
+        } else {
+            &frame
+        };
+        let quote = match (message["e"].as_str(), message["type"].as_str()) {
+            (Some("trade" | "aggTrade"), _) => message["s"].as_str().zip(number(&message["p"])),
+            (_, Some("ticker")) => message["product_id"].as_str().zip(number(&message["price"])),
+            _ => None,
+        };
+        let Some((symbol, price)) = quote else {
+            return 0;
+        };
+        let asset = asset_of(symbol);
+        if !self.assets.contains(&asset) {
+            return 0;
+        }
+        self.prices.lock().unwrap().insert(asset, (price, self.clock.now_ms()));
+        1
+    }
+
+    fn subscription(&self) -> Message {
+        let request = if self.ws_url.contains("coinbase") {
+            let products: Vec<String> = self.assets.iter().map(|a| format!("{}-USD", a)).collect();
+            json!({ "type": "subscribe", "product_ids": products, "channels": ["ticker"] })
+        } else {
+            let streams: Vec<String> = self
+                .assets
+                .iter()
+                .map(|a| format!("{}usdt@trade", a.to_lowercase()))
+                .collect();
+            json!({ "method": "SUBSCRIBE", "params": streams, "id": 1 })
+        };
+        Message::Text(request.to_string())
+    }
+
+    /// Streams until the connection drops.
+    async fn connect(&self) -> Result<()> {
+        let (ws, _) = tokio::time::timeout(Duration::from_secs(30), connect_async(self.ws_url.as_str()))
+            .await
+            .context("Spot feed connection timeout")?
+            .context("Failed to connect to the spot feed")?;
+        let (mut write, mut read) = ws.split();
+        write.send(self.subscription()).await?;
+        tracing::info!("🪙 Streaming spot prices for {}", self.assets.join(", "));
+        loop {
+            match read.next().await {
+                Some(Ok(Message::Text(text))) => {
+                    self.apply(&text);
+                }
+                Some(Ok(Message::Ping(payload))) => write.send(Message::Pong(payload)).await?,
+                Some(Ok(Message::Close(_))) | None => anyhow::bail!("Spot feed closed"),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e).context("Spot feed error"),
+            }
+        }
+    }
+
+    /// Streams in the background, reconnecting with backoff.
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                let started = tokio::time::Instant::now();
+                if let Err(e) = self.connect().await {
+                    tracing::warn!("Spot price feed dropped: {:#}", e);
+                }
+                if started.elapsed() > MAX_BACKOFF {
+                    backoff = Duration::from_secs(1);
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SimClock;
+
+    #[test]
+    fn test_reads_thresholds_and_prices_them_off_spot() {
+        let assets = vec!["BTC".to_string(), "ETH".to_string()];
+        let above = Threshold::parse("Will Bitcoin be above $100,000 on December 31?", &assets).unwrap();
+        assert_eq!(
+            above,
+            Threshold {
+                asset: "BTC".to_string(),
+                strike: 100_000.0,
+                direction: Direction::Above,
+                touch: false,
+            }
+        );
+        let dip = Threshold::parse("Will ETH dip to $2.5k in October?", &assets).unwrap();
+        assert_eq!(
+            (dip.strike, dip.direction, dip.touch),
+            (2_500.0, Direction::Below, true)
+        );
+        assert!(Threshold::parse("Will Bitcoin close between $90,000 and $95,000?", &assets).is_none());
+        assert!(Threshold::parse("Will Solana reach $500?", &assets).is_none());
+        assert!(Threshold::parse("Will the Fed cut rates above 50 bps?", &assets).is_none());
+
+        // A day out at 60% a year, 20% below the strike is hopeless
+        let day = 1.0 / 365.25;
+        assert!(above.probability(80_000.0, day, 0.6) < 0.001);
+        assert!((above.probability(100_000.0, day, 0.6) - 0.5).abs() < 1e-6);
+        assert!(above.probability(80_000.0, 1.0, 0.6) > 0.3);
+        assert_eq!(above.probability(99_000.0, 0.0, 0.6), 0.0);
+        // Touch questions only have to get there once
+        assert!(
+            (dip.probability(2_600.0, day, 0.6)
+                - 2.0 * (1.0 - normal_cdf((2_600.0f64 / 2_500.0).ln() / (0.6 * day.sqrt()))))
+            .abs()
+                < 1e-9
+        );
+        assert_eq!(dip.probability(2_400.0, day, 0.6), 1.0);
+
+        let clock = Arc::new(SimClock::at(1_000_000));
+        let feed = SpotFeed::new("wss://stream.binance.com:9443/ws", assets, 0.6, clock.clone());
+        let binance = json!({ "e": "trade", "s": "BTCUSDT", "p": "80000.50", "T": 1 });
+        assert_eq!(feed.apply(&binance.to_string()), 1);
+        let coinbase = json!({ "type": "ticker", "product_id": "ETH-USD", "price": "2600.1" });
+        assert_eq!(feed.apply(&coinbase.to_string()), 1);
+        let other = json!({ "stream": "dogeusdt@trade", "data": { "e": "trade", "s": "DOGEUSDT", "p": "0.2" } });
+        assert_eq!(feed.apply(&other.to_string()), 0);
+        assert_eq!(feed.price("btc"), Some(80_000.5));
+
+        let end = (1_000_000 + 24 * 3600 * 1000) / 1000;
+        let (threshold, spot, probability) = feed
+            .assess("Will Bitcoin be above $100,000 on December 31?", Some(end))
+            .unwrap();
+        assert_eq!((threshold.asset.as_str(), spot), ("BTC", 80_000.5));
+        assert!(probability < 0.001);
+        clock.set(1_000_000 + 61_000);
+        assert_eq!(feed.price("BTC"), None);
+    }
+}
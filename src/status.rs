@@ -0,0 +1,565 @@
+//! Read-only status API for dashboards and scripts.
+//!
+//! With `status_api` on, the HTTP server on `health_addr` also answers
+//! `GET` requests for the bot's current state as JSON, from memory (open
+//! orders excepted, which come from the journal):
+//!
+//! - `/status` - overview
+//! - `/status/positions` - open positions, marked to market when a price
+//!   is to hand (see [`crate::marks`])
+//! - `/status/orders` - orders that may still fill (needs `storage_url`)
+//! - `/status/wallets` - tracked leaders with their feed and stats
+//! - `/status/feeds` - connection state of every feed
+//! - `/status/risk` - headroom under each risk limit
+//! - `/status/decisions?limit=N` - the most recent copy decisions
+//! - `/status/rpc` - request counts, error rates, latency and estimated
+//!   compute units per RPC provider
+//! - `/status/skips?window=1h` - decisions within the window by outcome
+//!   and skip reason (windows over a day need `storage_url`)
+//! - `/status/paper` - paper trading's virtual cash and equity curve
+//!   (paper trading only)
+//! - `/status/shadow` - how the shadow strategy's decisions and PnL compare
+//!   with the live ones (`shadow` only)
+//! - `/status/pnl` - realized and unrealized PnL per position, leader and
+//!   category, as of the last refresh (needs `storage_url`)
+//! - `/status/resolutions` - positions booked at their market's resolution,
+//!   won or lost, and where each redemption stands (live trading only)
+//! - `/status/exposure` - open positions' value by category, by time left
+//!   to resolution and by leader, with a category by resolution grid for
+//!   heatmaps (leaders need `storage_url`)
+//! - `/status/equity?limit=N` - drawdown, daily and weekly returns and a
+//!   Sharpe ratio off the equity curve, with its latest points
+//! - `/status/slippage?window=7d` - slippage of the copies decided within
+//!   the window, split into latency and execution, by leader, category and
+//!   hour (needs `storage_url`)
+//! - `/status/aging` - how long each open position has been held, against
+//!   its market's end date and the exits of the leaders copied into it,
+//!   flagging stale ones (needs `storage_url`)
+//! - `/status/ledger` - deposits, withdrawals, gas and fees, and ROI on the
+//!   net capital put in (needs `storage_url`)
+//! - `/status/leaders` - per-leader PnL, win rate, slippage against the
+//!   leader's price and copy latency over the journal (needs `storage_url`)
+//! - `/status/bars?market=ID&interval=5m&window=1d` - OHLC bars of a held
+//!   market's trades, 1m, 5m or 1h (windows past the bars kept in memory
+//!   need `storage_url`)
+//! - `/status/disputes` - where each held market's UMA oracle request
+//!   stands, with its resolution source (needs `gamma_api`)
+
+use crate::aging::PositionAging;
+use crate::bars::{Bars, Interval};
+use crate::cashflow;
+use crate::disputes::DisputeWatcher;
+use crate::exposure;
+use crate::health::FeedStatus;
+use crate::http::{Handler, Request, Response};
+use crate::leaders::LeaderBook;
+use crate::marks::Marks;
+use crate::notify::BotControl;
+use crate::paper::PaperAccount;
+use crate::pnl::PnlTracker;
+use crate::resolution::Resolutions;
+use crate::rpc::RpcStats;
+use crate::shadow::Shadow;
+use crate::skips::{SkipBreakdown, SkipStats};
+use crate::storage::{now_ms, DecisionRecord, Storage, TimeRange};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Decisions kept for `/status/decisions`.
+pub const RECENT_DECISIONS: usize = 200;
+const DEFAULT_DECISIONS_LIMIT: usize = 50;
+const DEFAULT_SKIPS_WINDOW: Duration = Duration::from_secs(3600);
+const DEFAULT_EQUITY_POINTS: usize = 168;
+const DEFAULT_SLIPPAGE_WINDOW: Duration = Duration::from_secs(7 * 86_400);
+const DEFAULT_BARS_WINDOW: Duration = Duration::from_secs(86_400);
+
+/// The latest copy decisions, newest last.
+#[derive(Debug)]
+pub struct RecentDecisions {
+    capacity: usize,
+    decisions: Mutex<VecDeque<DecisionRecord>>,
+}
+
+impl Default for RecentDecisions {
+    fn default() -> Self {
+        Self::new(RECENT_DECISIONS)
+    }
+}
+
+impl RecentDecisions {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            decisions: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn push(&self, decision: DecisionRecord) {
+        let mut decisions = self.decisions.lock().unwrap();
+        if decisions.len() >= self.capacity {
+            decisions.pop_front();
+        }
+        decisions.push_back(decision);
+    }
+
+    /// Up to `limit` decisions, newest first.
+    pub fn latest(&self, limit: usize) -> Vec<DecisionRecord> {
+        self.decisions
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Serves the `/status` endpoints.
+pub struct StatusApi {
+    control: Arc<BotControl>,
+    leaders: Arc<LeaderBook>,
+    feeds: Arc<FeedStatus>,
+    decisions: Arc<RecentDecisions>,
+    storage: Option<Arc<dyn Storage>>,
+    rpc: Arc<RpcStats>,
+    skips: Arc<SkipStats>,
+    marks: Option<Arc<Marks>>,
+    paper: Option<Arc<PaperAccount>>,
+    shadow: Option<Arc<Shadow>>,
+    pnl: Option<Arc<PnlTracker>>,
+    resolutions: Option<Arc<Resolutions>>,
+    aging: Option<Arc<PositionAging>>,
+    bars: Option<Arc<Bars>>,
+    disputes: Option<Arc<DisputeWatcher>>,
+}
+
+impl StatusApi {
+    pub fn new(
+        control: Arc<BotControl>,
+        leaders: Arc<LeaderBook>,
+        feeds: Arc<FeedStatus>,
+        decisions: Arc<RecentDecisions>,
+        storage: Option<Arc<dyn Storage>>,
+    ) -> Self {
+        Self {
+            control,
+            leaders,
+            feeds,
+            decisions,
+            storage,
+            rpc: Arc::new(RpcStats::new()),
+            skips: Arc::new(SkipStats::new()),
+            marks: None,
+            paper: None,
+            shadow: None,
+            pnl: None,
+            resolutions: None,
+            aging: None,
+            bars: None,
+            disputes: None,
+        }
+    }
+
+    pub fn with_rpc_stats(mut self, rpc: Arc<RpcStats>) -> Self {
+        self.rpc = rpc;
+        self
+    }
+
+    pub fn with_skip_stats(mut self, skips: Arc<SkipStats>) -> Self {
+        self.skips = skips;
+        self
+    }
+
+    /// Where positions get their marks from.
+    pub fn with_marks(mut self, marks: Arc<Marks>) -> Self {
+        self.marks = Some(marks);
+        self
+    }
+
+    pub fn with_paper(mut self, paper: Arc<PaperAccount>) -> Self {
+        self.paper = Some(paper);
+        self
+    }
+
+    pub fn with_shadow(mut self, shadow: Arc<Shadow>) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    pub fn with_pnl(mut self, pnl: Arc<PnlTracker>) -> Self {
+        self.pnl = Some(pnl);
+        self
+    }
+
+    pub fn with_resolutions(mut self, resolutions: Arc<Resolutions>) -> Self {
+        self.resolutions = Some(resolutions);
+        self
+    }
+
+    pub fn with_aging(mut self, aging: Arc<PositionAging>) -> Self {
+        self.aging = Some(aging);
+        self
+    }
+
+    pub fn with_bars(mut self, bars: Arc<Bars>) -> Self {
+        self.bars = Some(bars);
+        self
+    }
+
+    pub fn with_disputes(mut self, disputes: Arc<DisputeWatcher>) -> Self {
+        self.disputes = Some(disputes);
+        self
+    }
+
+    /// The last refresh's PnL, refreshing first if there hasn't been one.
+    async fn pnl(&self) -> Response {
+        let Some(pnl) = &self.pnl else {
+            return Response::error(503, "PnL is only tracked with storage_url set");
+        };
+        if let Some(report) = pnl.latest() {
+            return Response::json(200, &report);
+        }
+        match pnl.refresh().await {
+            Ok(report) => Response::json(200, &report),
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+
+    /// Per-leader attribution, computed from the journal on request.
+    async fn leader_performance(&self) -> Response {
+        let Some(pnl) = &self.pnl else {
+            return Response::error(503, "leader performance needs storage_url set");
+        };
+        match pnl.leaders().await {
+            Ok(ranked) => Response::json(200, &ranked),
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+
+    async fn ledger(&self) -> Response {
+        let Some(storage) = &self.storage else {
+            return Response::error(503, "the cash flow ledger needs storage_url set");
+        };
+        match cashflow::from_journal(storage.as_ref()).await {
+            Ok(ledger) => Response::json(200, &ledger),
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+
+    fn overview(&self) -> Value {
+        let portfolio = self.control.portfolio();
+        let risk = self.control.risk().headroom();
+        let feeds = self.feeds.states();
+        json!({
+            "paused": self.control.is_paused(),
+            "tripped": risk.tripped,
+            "trip_reason": risk.trip_reason,
+            "positions": portfolio.holdings().len(),
+            "exposure_usd": portfolio.exposure(),
+            "realized_pnl": portfolio.realized_pnl(),
+            "realized_pnl_today": risk.realized_pnl_today,
+            "trades_today": risk.trades_today,
+            "feeds_connected": feeds.values().filter(|f| f.connected).count(),
+            "feeds": feeds.len(),
+            "pending_approvals": self.control.approvals().pending().len(),
+        })
+    }
+
+    async fn positions(&self) -> Value {
+        let mut positions = Vec::new();
+        for h in self.control.portfolio().holdings() {
+            let mark = match &self.marks {
+                Some(marks) => marks.mark(&h.market_id).await,
+                None => None,
+            };
+            positions.push(json!({
+                "market_id": h.market_id,
+                "shares": h.shares(),
+                "avg_price": h.avg_price(),
+                "cost_usd": h.cost(),
+                "mark": mark.map(|m| m.price),
+                "mark_source": mark.map(|m| m.source),
+                "mark_stale": mark.map(|m| m.stale),
+                "unrealized_pnl": mark.map(|mark| h.unrealized_pnl(mark.price)),
+                "opened_at": h.lots.iter().map(|l| l.opened_at).min(),
+            }));
+        }
+        Value::Array(positions)
+    }
+
+    async fn exposure(&self) -> Response {
+        let Some(marks) = &self.marks else {
+            return Response::error(503, "exposure needs market data");
+        };
+        let pnl = self.pnl.as_ref().and_then(|pnl| pnl.latest());
+        let holdings = self.control.portfolio().holdings();
+        Response::json(200, &exposure::snapshot(&holdings, marks, pnl.as_ref(), now_ms()).await)
+    }
+
+    async fn orders(&self) -> Response {
+        let Some(storage) = &self.storage else {
+            return Response::error(503, "orders are only tracked with storage_url set");
+        };
+        match storage.open_orders().await {
+            Ok(orders) => Response::json(200, &orders),
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+
+    fn wallets(&self) -> Value {
+        let feeds = self.feeds.states();
+        Value::Array(
+            feeds
+                .iter()
+                .map(|(wallet, feed)| {
+                    json!({
+                        "wallet": wallet,
+                        "label": self.leaders.label(wallet),
+                        "connected": feed.connected,
+                        "stats": self.leaders.get(wallet).unwrap_or_default(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn equity(&self, request: &Request) -> Response {
+        let Some(equity) = self.control.equity() else {
+            return Response::error(404, "equity isn't tracked");
+        };
+        let limit = match request.query_param("limit").map(str::parse::<usize>) {
+            None => DEFAULT_EQUITY_POINTS,
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return Response::error(400, "limit must be a number"),
+        };
+        let curve = equity.curve();
+        let latest = &curve[curve.len().saturating_sub(limit)..];
+        Response::json(200, &json!({ "stats": crate::equity::stats(&curve), "curve": latest }))
+    }
+
+    async fn slippage(&self, request: &Request) -> Response {
+        let (Some(storage), Some(marks)) = (&self.storage, &self.marks) else {
+            return Response::error(503, "slippage needs storage_url set");
+        };
+        let window = match request.query_param("window").map(crate::units::parse_duration) {
+            None => DEFAULT_SLIPPAGE_WINDOW,
+            Some(Ok(window)) => window,
+            Some(Err(e)) => return Response::error(400, format!("window: {}", e)),
+        };
+        let range = TimeRange::since(now_ms() - window.as_millis() as i64);
+        match crate::slippage::from_journal(storage.as_ref(), range, marks.markets()).await {
+            Ok(report) => Response::json(200, &report),
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+
+    async fn bars(&self, request: &Request) -> Response {
+        let Some(bars) = &self.bars else {
+            return Response::error(404, "bars need stream_held_markets on");
+        };
+        let Some(market_id) = request.query_param("market") else {
+            return Response::error(400, "market is required");
+        };
+        let interval = match request.query_param("interval").map(Interval::parse) {
+            None => Interval::M5,
+            Some(Some(interval)) => interval,
+            Some(None) => return Response::error(400, "interval must be 1m, 5m or 1h"),
+        };
+        let window = match request.query_param("window").map(crate::units::parse_duration) {
+            None => DEFAULT_BARS_WINDOW,
+            Some(Ok(window)) => window,
+            Some(Err(e)) => return Response::error(400, format!("window: {}", e)),
+        };
+        let range = TimeRange::since(now_ms() - window.as_millis() as i64);
+        match bars.history(market_id, interval, range).await {
+            Ok(bars) => Response::json(200, &json!({ "market_id": market_id, "interval": interval, "bars": bars })),
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+
+    fn decisions(&self, request: &Request) -> Response {
+        let limit = match request.query_param("limit").map(str::parse::<usize>) {
+            None => DEFAULT_DECISIONS_LIMIT,
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return Response::error(400, "limit must be a number"),
+        };
+        Response::json(200, &self.decisions.latest(limit))
+    }
+
+    async fn skips(&self, request: &Request) -> Response {
+        let window = match request.query_param("window").map(crate::units::parse_duration) {
+            None => DEFAULT_SKIPS_WINDOW,
+            Some(Ok(window)) => window,
+            Some(Err(e)) => return Response::error(400, format!("window: {}", e)),
+        };
+        let now = now_ms();
+        if let Some(breakdown) = self.skips.breakdown(window, now) {
+            return Response::json(200, &breakdown);
+        }
+        let Some(storage) = &self.storage else {
+            return Response::error(503, "windows over a day need storage_url");
+        };
+        match storage
+            .decisions(TimeRange::since(now - window.as_millis() as i64))
+            .await
+        {
+            Ok(decisions) => Response::json(200, &SkipBreakdown::from_decisions(window, &decisions)),
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for StatusApi {
+    async fn handle(&self, request: &Request) -> Option<Response> {
+        if request.path != "/status" && !request.path.starts_with("/status/") {
+            return None;
+        }
+        if !matches!(request.method.as_str(), "GET" | "HEAD") {
+            return Some(Response::error(405, "method not allowed"));
+        }
+        Some(match request.path.trim_end_matches('/') {
+            "/status" => Response::json(200, &self.overview()),
+            "/status/positions" => Response::json(200, &self.positions().await),
+            "/status/orders" => self.orders().await,
+            "/status/wallets" => Response::json(200, &self.wallets()),
+            "/status/feeds" => Response::json(200, &self.feeds.states()),
+            "/status/risk" => Response::json(200, &self.control.risk().headroom()),
+            "/status/decisions" => self.decisions(request),
+            "/status/rpc" => Response::json(200, &self.rpc.reports()),
+            "/status/skips" => self.skips(request).await,
+            "/status/paper" => match &self.paper {
+                Some(paper) => Response::json(200, &paper.state()),
+                None => Response::error(404, "not paper trading"),
+            },
+            "/status/shadow" => match &self.shadow {
+                Some(shadow) => Response::json(200, &shadow.report().await),
+                None => Response::error(404, "no shadow strategy"),
+            },
+            "/status/pnl" => self.pnl().await,
+            "/status/exposure" => self.exposure().await,
+            "/status/equity" => self.equity(request),
+            "/status/slippage" => self.slippage(request).await,
+            "/status/aging" => match &self.aging {
+                Some(aging) => Response::json(200, &aging.latest()),
+                None => Response::error(503, "position aging needs storage_url set"),
+            },
+            "/status/ledger" => self.ledger().await,
+            "/status/leaders" => self.leader_performance().await,
+            "/status/bars" => self.bars(request).await,
+            "/status/resolutions" => match &self.resolutions {
+                Some(resolutions) => Response::json(200, &resolutions.resolved()),
+                None => Response::error(404, "positions are only resolved when trading live"),
+            },
+            "/status/disputes" => match &self.disputes {
+                Some(disputes) => Response::json(200, &disputes.resolutions()),
+                None => Response::error(404, "disputes need gamma_api and dispute_interval"),
+            },
+            _ => Response::error(404, "not found"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::UsdcAmount;
+    use crate::events::ConnectionState;
+    use crate::portfolio::Portfolio;
+    use crate::risk::RiskManager;
+    use crate::types::{Config, CostBasis};
+
+    fn decision(id: i64) -> DecisionRecord {
+        DecisionRecord {
+            id,
+            leader_trade_id: None,
+            wallet: "0xabc".to_string(),
+            market_id: "m1".to_string(),
+            side: "BUY".to_string(),
+            copied: true,
+            reason: None,
+            detail: None,
+            size_usd: Some(10.0),
+            decided_at: id,
+            arrival_mid: None,
+            arrival_book: None,
+        }
+    }
+
+    fn get(path: &str) -> Request {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recent_decisions() {
+        let recent = RecentDecisions::new(3);
+        for id in 1..=5 {
+            recent.push(decision(id));
+        }
+        let ids: Vec<i64> = recent.latest(10).iter().map(|d| d.id).collect();
+        assert_eq!(ids, vec![5, 4, 3]);
+        assert_eq!(recent.latest(1)[0].id, 5);
+    }
+
+    #[tokio::test]
+    async fn test_status_routes() {
+        let config = Config {
+            max_daily_volume: UsdcAmount(1000.0),
+            ..Default::default()
+        };
+        let portfolio = Arc::new(Portfolio::new(CostBasis::Average, None));
+        let control = Arc::new(BotControl::new(portfolio, Arc::new(RiskManager::new(config))));
+        let feeds = Arc::new(FeedStatus::new(&["0xabc".to_string(), "0xdef".to_string()], 0));
+        feeds.update("0xabc", ConnectionState::Connected, None, 10);
+        let decisions = Arc::new(RecentDecisions::default());
+        decisions.push(decision(1));
+        decisions.push(decision(2));
+        let api = StatusApi::new(control, Arc::new(LeaderBook::new()), feeds, decisions, None);
+
+        assert!(api.handle(&get("/healthz")).await.is_none());
+        let overview = api.handle(&get("/status")).await.unwrap();
+        let overview: Value = serde_json::from_str(&overview.body).unwrap();
+        assert_eq!(overview["paused"], false);
+        assert_eq!(overview["feeds_connected"], 1);
+        assert_eq!(overview["feeds"], 2);
+
+        let risk = api.handle(&get("/status/risk")).await.unwrap();
+        let risk: Value = serde_json::from_str(&risk.body).unwrap();
+        assert_eq!(risk["volume_left"], 1000.0);
+
+        let latest = api.handle(&get("/status/decisions?limit=1")).await.unwrap();
+        let latest: Value = serde_json::from_str(&latest.body).unwrap();
+        assert_eq!(latest.as_array().unwrap().len(), 1);
+        assert_eq!(latest[0]["id"], 2);
+
+        let skips = api.handle(&get("/status/skips?window=30m")).await.unwrap();
+        let skips: Value = serde_json::from_str(&skips.body).unwrap();
+        assert_eq!(skips["window_secs"], 1800);
+        assert_eq!(skips["by_reason"]["stale"], 0);
+        assert_eq!(api.handle(&get("/status/skips?window=soon")).await.unwrap().status, 400);
+        assert_eq!(api.handle(&get("/status/skips?window=7d")).await.unwrap().status, 503);
+
+        assert_eq!(api.handle(&get("/status/orders")).await.unwrap().status, 503);
+        assert_eq!(api.handle(&get("/status/pnl")).await.unwrap().status, 503);
+        assert_eq!(api.handle(&get("/status/slippage")).await.unwrap().status, 503);
+        assert_eq!(api.handle(&get("/status/exposure")).await.unwrap().status, 503);
+        assert_eq!(api.handle(&get("/status/leaders")).await.unwrap().status, 503);
+        assert_eq!(api.handle(&get("/status/ledger")).await.unwrap().status, 503);
+        assert_eq!(api.handle(&get("/status/aging")).await.unwrap().status, 503);
+        assert_eq!(api.handle(&get("/status/paper")).await.unwrap().status, 404);
+        assert_eq!(api.handle(&get("/status/nope")).await.unwrap().status, 404);
+        let mut post = get("/status");
+        post.method = "POST".to_string();
+        assert_eq!(api.handle(&post).await.unwrap().status, 405);
+    }
+}
@@ -0,0 +1,483 @@
+//! Persistent journal of everything the bot observes and does.
+//!
+//! Backends implement the repository traits below; the rest of the bot only
+//! talks to `Arc<dyn Storage>`.
+
+pub mod sqlite;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+use crate::types::{Market, OrderBook, SkipReason, Trade};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Inclusive range of unix millisecond timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub from_ms: i64,
+    pub to_ms: i64,
+}
+
+impl TimeRange {
+    pub fn all() -> Self {
+        Self {
+            from_ms: 0,
+            to_ms: i64::MAX,
+        }
+    }
+
+    pub fn since(from_ms: i64) -> Self {
+        Self {
+            from_ms,
+            to_ms: i64::MAX,
+        }
+    }
+}
+
+/// A leader trade as seen on the feed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderTradeRecord {
+    pub id: i64,
+    pub trade: Trade,
+    pub observed_at: i64,
+}
+
+/// Whether a leader trade was copied, and if not, why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub id: i64,
+    pub leader_trade_id: Option<i64>,
+    pub wallet: String,
+    pub market_id: String,
+    pub side: String,
+    pub copied: bool,
+    pub reason: Option<SkipReason>,
+    pub detail: Option<String>,
+    pub size_usd: Option<f64>,
+    pub decided_at: i64,
+    /// The market's book mid when the trade was decided on
+    #[serde(default)]
+    pub arrival_mid: Option<f64>,
+    /// The top `decision_book_levels` of the market's book then
+    #[serde(default)]
+    pub arrival_book: Option<OrderBook>,
+}
+
+/// An order we submitted (or tried to).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRecord {
+    pub id: i64,
+    pub decision_id: Option<i64>,
+    pub exchange_order_id: Option<String>,
+    pub market_id: String,
+    pub side: String,
+    pub shares: f64,
+    pub limit_price: Option<f64>,
+    pub order_type: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub submitted_at: i64,
+    /// Idempotency key sent with the order, so a crash or retry can't
+    /// submit it twice and recovery can look it up.
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+}
+
+/// A (partial) execution of one of our orders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FillRecord {
+    pub id: i64,
+    pub order_id: i64,
+    pub market_id: String,
+    pub side: String,
+    pub shares: f64,
+    pub price: f64,
+    pub fee: f64,
+    pub filled_at: i64,
+}
+
+/// Net outcome-token holding in one market, as the bot believes it to be.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionRecord {
+    pub market_id: String,
+    pub shares: f64,
+    pub avg_price: f64,
+    pub updated_at: i64,
+}
+
+/// An open purchase lot. Lots are kept in purchase order so FIFO costing
+/// can consume the oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotRecord {
+    pub market_id: String,
+    pub shares: f64,
+    pub price: f64,
+    pub opened_at: i64,
+}
+
+/// A cached market snapshot; see [`crate::markets::MarketCache`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketRecord {
+    pub market: Market,
+    pub fetched_at: i64,
+    pub expires_at: i64,
+}
+
+/// One observation of a market's price. `mid` is halfway between the best
+/// bid and ask (absent when a side of the book is empty); `last` is the
+/// market's last traded YES price. `book` keeps the top levels of the book
+/// for simulated fills (absent in samples taken before it was recorded).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceSample {
+    pub market_id: String,
+    pub sampled_at: i64,
+    pub mid: Option<f64>,
+    pub last: Option<f64>,
+    #[serde(default)]
+    pub book: Option<OrderBook>,
+}
+
+/// One OHLC bar of a market's trades: the `interval_secs` starting at
+/// `opened_at` (unix ms, a multiple of the interval), with the shares and
+/// trades in it. See [`crate::bars`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bar {
+    pub market_id: String,
+    pub interval_secs: i64,
+    pub opened_at: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trades: i64,
+}
+
+/// A claim by one bot instance on a named resource; see [`crate::lease`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaseRecord {
+    pub key: String,
+    pub holder: String,
+    pub acquired_at: i64,
+    pub heartbeat_at: i64,
+    pub expires_at: i64,
+}
+
+/// A raw WebSocket frame, kept for debugging feed issues.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub id: i64,
+    pub wallet: String,
+    pub payload: String,
+    pub received_at: i64,
+}
+
+/// An action to add to the audit trail.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub action: String,
+    /// `operator` or `bot`
+    pub actor: String,
+    /// JSON object with the specifics
+    pub detail: String,
+    pub recorded_at: i64,
+}
+
+/// An audit trail entry as stored, chained to the one before it; see
+/// [`crate::audit`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: i64,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// Money moving in or out of the trading wallet other than through
+/// trades; see [`crate::cashflow`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CashFlowRecord {
+    pub id: i64,
+    /// Unique per flow, so rescanning the chain records nothing twice
+    pub key: String,
+    /// `opening`, `deposit`, `withdrawal` or `gas`
+    pub kind: String,
+    /// USD, positive into the wallet
+    pub amount: f64,
+    pub tx_hash: Option<String>,
+    pub detail: Option<String>,
+    pub occurred_at: i64,
+}
+
+/// Journal rows old enough to archive and delete together without leaving
+/// newer (or still open) rows pointing at them.
+#[derive(Debug, Clone, Default)]
+pub struct ExpiredJournal {
+    pub leader_trades: Vec<LeaderTradeRecord>,
+    pub decisions: Vec<DecisionRecord>,
+    pub orders: Vec<OrderRecord>,
+    pub fills: Vec<FillRecord>,
+}
+
+impl ExpiredJournal {
+    pub fn len(&self) -> usize {
+        self.leader_trades.len() + self.decisions.len() + self.orders.len() + self.fills.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Order statuses that may still produce fills.
+pub const OPEN_ORDER_STATUSES: &[&str] = &["submitting", "open", "live", "pending", "partially_filled"];
+
+/// Append-mostly history of leader trades, decisions, orders and fills.
+/// Insert methods ignore the record's `id` and return the assigned one.
+#[async_trait]
+pub trait Journal: Send + Sync {
+    async fn record_leader_trade(&self, trade: &Trade, observed_at: i64) -> Result<i64>;
+    async fn record_decision(&self, decision: &DecisionRecord) -> Result<i64>;
+    async fn record_order(&self, order: &OrderRecord) -> Result<i64>;
+    async fn record_fill(&self, fill: &FillRecord) -> Result<i64>;
+
+    /// Updates an order once the exchange has answered (or recovery resolved it).
+    async fn update_order(
+        &self,
+        id: i64,
+        status: &str,
+        exchange_order_id: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()>;
+
+    /// Orders whose status is one of [`OPEN_ORDER_STATUSES`].
+    async fn open_orders(&self) -> Result<Vec<OrderRecord>>;
+
+    async fn leader_trades(&self, range: TimeRange) -> Result<Vec<LeaderTradeRecord>>;
+    async fn decisions(&self, range: TimeRange) -> Result<Vec<DecisionRecord>>;
+    async fn orders(&self, range: TimeRange) -> Result<Vec<OrderRecord>>;
+    async fn fills(&self, range: TimeRange) -> Result<Vec<FillRecord>>;
+}
+
+/// Current positions, kept in step with fills and corrected by recovery.
+#[async_trait]
+pub trait PositionStore: Send + Sync {
+    async fn positions(&self) -> Result<Vec<PositionRecord>>;
+
+    /// Overwrites (or, with zero shares, removes) the position in a market.
+    async fn set_position(&self, position: &PositionRecord) -> Result<()>;
+
+    /// Buys add to the position at a blended price; sells reduce it.
+    async fn apply_fill(&self, fill: &FillRecord) -> Result<PositionRecord>;
+
+    /// Open lots of every market, oldest first.
+    async fn lots(&self) -> Result<Vec<LotRecord>>;
+
+    /// Replaces a market's lots and writes the position they add up to.
+    async fn save_holding(&self, position: &PositionRecord, lots: &[LotRecord]) -> Result<()>;
+}
+
+/// Raw frame capture and the deletes behind retention policies.
+#[async_trait]
+pub trait Retention: Send + Sync {
+    async fn record_frame(&self, wallet: &str, payload: &str, received_at: i64) -> Result<i64>;
+    async fn frames(&self, range: TimeRange) -> Result<Vec<FrameRecord>>;
+    async fn delete_frames_before(&self, before_ms: i64) -> Result<u64>;
+
+    /// Journal rows older than `before_ms` that can go; see [`ExpiredJournal`].
+    async fn expired_journal(&self, before_ms: i64) -> Result<ExpiredJournal>;
+    async fn delete_journal(&self, expired: &ExpiredJournal) -> Result<()>;
+
+    /// Returns freed space to the filesystem and refreshes planner stats.
+    async fn vacuum(&self) -> Result<()>;
+}
+
+/// Keys of leader trades already handled, so a restart doesn't copy them twice.
+#[async_trait]
+pub trait SeenTrades: Send + Sync {
+    /// Marks `key` as seen at `seen_at`, forgetting keys seen before
+    /// `not_before`. Returns `false` if `key` was already seen since then.
+    async fn mark_seen(&self, key: &str, seen_at: i64, not_before: i64) -> Result<bool>;
+}
+
+/// Local copy of the market catalog, so restarts and API outages don't
+/// leave the bot without market data.
+#[async_trait]
+pub trait MarketCatalog: Send + Sync {
+    async fn markets(&self) -> Result<Vec<MarketRecord>>;
+
+    /// Inserts or replaces the record for `record.market.id`.
+    async fn save_market(&self, record: &MarketRecord) -> Result<()>;
+}
+
+/// Small named JSON documents (risk counters, leader stats) that must
+/// survive restarts.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn load_state(&self, key: &str) -> Result<Option<String>>;
+    async fn save_state(&self, key: &str, value: &str, updated_at: i64) -> Result<()>;
+}
+
+/// Price time series, for judging copies against what the market did next.
+#[async_trait]
+pub trait PriceHistory: Send + Sync {
+    /// Inserts samples; one already stored for the same market and time is kept.
+    async fn record_prices(&self, samples: &[PriceSample]) -> Result<()>;
+
+    /// Samples within `range`, for one market or all, ordered by market then time.
+    async fn prices(&self, market_id: Option<&str>, range: TimeRange) -> Result<Vec<PriceSample>>;
+    async fn delete_prices_before(&self, before_ms: i64) -> Result<u64>;
+
+    /// Inserts bars, replacing any stored for the same market, interval and
+    /// open time (a bar is saved again as trades come into it).
+    async fn record_bars(&self, bars: &[Bar]) -> Result<()>;
+
+    /// One market's bars of `interval_secs` opened within `range`, oldest first.
+    async fn bars(&self, market_id: &str, interval_secs: i64, range: TimeRange) -> Result<Vec<Bar>>;
+    async fn delete_bars_before(&self, before_ms: i64) -> Result<u64>;
+}
+
+/// Leases that keep two instances from acting on the same account.
+#[async_trait]
+pub trait Leases: Send + Sync {
+    /// Takes `lease` if it is free, expired, already held by `lease.holder`
+    /// (a renewal, keeping `acquired_at`) or `force` is set, in one atomic
+    /// step. Returns the lease in force afterwards: ours, or the other holder's.
+    async fn acquire_lease(&self, lease: &LeaseRecord, force: bool) -> Result<LeaseRecord>;
+
+    /// Gives up the lease if `holder` still holds it.
+    async fn release_lease(&self, key: &str, holder: &str) -> Result<()>;
+}
+
+/// Append-only, hash-chained record of state-changing actions.
+#[async_trait]
+pub trait AuditLog: Send + Sync {
+    /// Appends `entry` after the latest record, chaining it to that
+    /// record's hash; concurrent appends never fork the chain.
+    async fn append_audit(&self, entry: &AuditEntry) -> Result<AuditRecord>;
+
+    async fn audit_records(&self, range: TimeRange) -> Result<Vec<AuditRecord>>;
+}
+
+/// Ledger of the trading wallet's non-trade cash flows.
+#[async_trait]
+pub trait CashFlows: Send + Sync {
+    /// Records `flow` unless one with its key already is; returns whether
+    /// it was new.
+    async fn record_cash_flow(&self, flow: &CashFlowRecord) -> Result<bool>;
+
+    async fn cash_flows(&self, range: TimeRange) -> Result<Vec<CashFlowRecord>>;
+}
+
+/// Everything a storage backend provides.
+pub trait Storage:
+    Journal
+    + PositionStore
+    + Retention
+    + SeenTrades
+    + MarketCatalog
+    + StateStore
+    + PriceHistory
+    + Leases
+    + AuditLog
+    + CashFlows
+{
+}
+
+impl<T> Storage for T where
+    T: Journal
+        + PositionStore
+        + Retention
+        + SeenTrades
+        + MarketCatalog
+        + StateStore
+        + PriceHistory
+        + Leases
+        + AuditLog
+        + CashFlows
+{
+}
+
+/// SQL conditions for [`Retention::expired_journal`], shared by the backends.
+/// `cutoff` is the backend's placeholder for the cutoff timestamp.
+mod expiry {
+    use super::OPEN_ORDER_STATUSES;
+
+    pub fn order(o: &str, cutoff: &str) -> String {
+        let open = OPEN_ORDER_STATUSES
+            .iter()
+            .map(|s| format!("'{}'", s))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{o}.submitted_at < {cutoff} AND {o}.status NOT IN ({open}) \
+             AND NOT EXISTS (SELECT 1 FROM fills nf WHERE nf.order_id = {o}.id AND nf.filled_at >= {cutoff})"
+        )
+    }
+
+    pub fn decision(d: &str, cutoff: &str) -> String {
+        format!(
+            "{d}.decided_at < {cutoff} \
+             AND NOT EXISTS (SELECT 1 FROM orders ko WHERE ko.decision_id = {d}.id AND NOT ({}))",
+            order("ko", cutoff)
+        )
+    }
+
+    pub fn leader_trade(t: &str, cutoff: &str) -> String {
+        format!(
+            "{t}.observed_at < {cutoff} \
+             AND NOT EXISTS (SELECT 1 FROM decisions kd WHERE kd.leader_trade_id = {t}.id AND NOT ({}))",
+            decision("kd", cutoff)
+        )
+    }
+}
+
+/// Opens the backend named by `url`, e.g. `sqlite://data/bot.db`,
+/// `sqlite::memory:` or (with the `postgres` feature) `postgres://user@host/db`,
+/// running any pending schema migrations.
+pub async fn open(url: &str) -> Result<Arc<dyn Storage>> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Arc::new(postgres::PostgresStore::connect(url).await?));
+        #[cfg(not(feature = "postgres"))]
+        anyhow::bail!("Postgres storage requires building with `--features postgres`");
+    }
+    if let Some(path) = url.strip_prefix("sqlite://") {
+        return Ok(Arc::new(sqlite::SqliteStore::open(path)?));
+    }
+    if url == "sqlite::memory:" {
+        return Ok(Arc::new(sqlite::SqliteStore::open_in_memory()?));
+    }
+    anyhow::bail!("Unsupported storage url '{}'", url)
+}
+
+pub fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// The position after applying `fill` to `current` (if any).
+pub fn position_after_fill(current: Option<&PositionRecord>, fill: &FillRecord) -> PositionRecord {
+    let (shares, avg_price) = current.map(|p| (p.shares, p.avg_price)).unwrap_or((0.0, 0.0));
+    let (shares, avg_price) = if fill.side.eq_ignore_ascii_case("SELL") {
+        let remaining = (shares - fill.shares).max(0.0);
+        (remaining, if remaining > 0.0 { avg_price } else { 0.0 })
+    } else {
+        let total = shares + fill.shares;
+        let blended = if total > 0.0 {
+            (shares * avg_price + fill.shares * fill.price) / total
+        } else {
+            0.0
+        };
+        (total, blended)
+    };
+
+    PositionRecord {
+        market_id: fill.market_id.clone(),
+        shares,
+        avg_price,
+        updated_at: fill.filled_at,
+    }
+}
@@ -0,0 +1,1301 @@
+use super::{
+    expiry, AuditEntry, AuditLog, AuditRecord, Bar, CashFlowRecord, CashFlows, DecisionRecord, ExpiredJournal, FillRecord, FrameRecord, Journal, LeaderTradeRecord,
+    LeaseRecord, Leases, LotRecord, MarketCatalog, MarketRecord, OrderRecord, PositionRecord,
+    PositionStore, PriceHistory, PriceSample, Retention, SeenTrades, StateStore, TimeRange,
+    OPEN_ORDER_STATUSES,
+};
+use crate::audit::{chain_hash, GENESIS_HASH};
+use crate::types::{SkipReason, Trade, TradeSide};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tokio_postgres::config::SslMode;
+use tokio_postgres::tls::MakeTlsConnect;
+use tokio_postgres::{Client, NoTls, Row, Socket};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+/// Schema migrations, applied in order and recorded in `schema_migrations`.
+/// Keep these in step with the SQLite list; never edit a released entry.
+const MIGRATIONS: &[(i64, &str)] = &[
+    (1, V1_JOURNAL),
+    (2, V2_POSITIONS),
+    (3, V3_WS_FRAMES),
+    (4, V4_SEEN_TRADES),
+    (5, V5_POSITION_LOTS),
+    (6, V6_MARKETS),
+    (7, V7_BOT_STATE),
+    (8, V8_CLIENT_ORDER_IDS),
+    (9, V9_PRICE_SAMPLES),
+    (10, V10_INSTANCE_LEASES),
+    (11, V11_AUDIT_LOG),
+    (12, V12_PRICE_BOOKS),
+    (13, V13_ORDERLESS_FILLS),
+    (14, V14_ARRIVAL_MIDS),
+    (15, V15_CASH_FLOWS),
+    (16, V16_BARS),
+    (17, V17_DECISION_BOOKS),
+];
+
+/// Serializes migrations across bot instances starting at the same time.
+const MIGRATION_LOCK: i64 = 0x0070_6f6c_7962_6f74;
+
+const V1_JOURNAL: &str = "CREATE TABLE leader_trades (
+        id BIGSERIAL PRIMARY KEY,
+        wallet TEXT NOT NULL,
+        event_id TEXT NOT NULL,
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        shares DOUBLE PRECISION NOT NULL,
+        price DOUBLE PRECISION NOT NULL,
+        timestamp BIGINT NOT NULL,
+        tx_hash TEXT,
+        observed_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_leader_trades_observed_at ON leader_trades(observed_at);
+
+    CREATE TABLE decisions (
+        id BIGSERIAL PRIMARY KEY,
+        leader_trade_id BIGINT REFERENCES leader_trades(id),
+        wallet TEXT NOT NULL,
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        copied BOOLEAN NOT NULL,
+        reason TEXT,
+        detail TEXT,
+        size_usd DOUBLE PRECISION,
+        decided_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_decisions_decided_at ON decisions(decided_at);
+
+    CREATE TABLE orders (
+        id BIGSERIAL PRIMARY KEY,
+        decision_id BIGINT REFERENCES decisions(id),
+        exchange_order_id TEXT,
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        shares DOUBLE PRECISION NOT NULL,
+        limit_price DOUBLE PRECISION,
+        order_type TEXT NOT NULL,
+        status TEXT NOT NULL,
+        error TEXT,
+        submitted_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_orders_submitted_at ON orders(submitted_at);
+
+    CREATE TABLE fills (
+        id BIGSERIAL PRIMARY KEY,
+        order_id BIGINT NOT NULL REFERENCES orders(id),
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        shares DOUBLE PRECISION NOT NULL,
+        price DOUBLE PRECISION NOT NULL,
+        fee DOUBLE PRECISION NOT NULL DEFAULT 0,
+        filled_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_fills_filled_at ON fills(filled_at);";
+
+const V2_POSITIONS: &str = "CREATE TABLE positions (
+        market_id TEXT PRIMARY KEY,
+        shares DOUBLE PRECISION NOT NULL,
+        avg_price DOUBLE PRECISION NOT NULL,
+        updated_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_orders_status ON orders(status);";
+
+const V3_WS_FRAMES: &str = "CREATE TABLE ws_frames (
+        id BIGSERIAL PRIMARY KEY,
+        wallet TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        received_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_ws_frames_received_at ON ws_frames(received_at);";
+
+const V4_SEEN_TRADES: &str = "CREATE TABLE seen_trades (
+        key TEXT PRIMARY KEY,
+        seen_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_seen_trades_seen_at ON seen_trades(seen_at);";
+
+const V5_POSITION_LOTS: &str = "CREATE TABLE position_lots (
+        id BIGSERIAL PRIMARY KEY,
+        market_id TEXT NOT NULL,
+        shares DOUBLE PRECISION NOT NULL,
+        price DOUBLE PRECISION NOT NULL,
+        opened_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_position_lots_market_id ON position_lots(market_id);";
+
+const V6_MARKETS: &str = "CREATE TABLE markets (
+        market_id TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at BIGINT NOT NULL,
+        expires_at BIGINT NOT NULL
+    );";
+
+const V7_BOT_STATE: &str = "CREATE TABLE bot_state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        updated_at BIGINT NOT NULL
+    );";
+
+const V8_CLIENT_ORDER_IDS: &str = "ALTER TABLE orders ADD COLUMN client_order_id TEXT;
+    CREATE UNIQUE INDEX idx_orders_client_order_id ON orders(client_order_id);";
+
+const V9_PRICE_SAMPLES: &str = "CREATE TABLE price_samples (
+        market_id TEXT NOT NULL,
+        sampled_at BIGINT NOT NULL,
+        mid DOUBLE PRECISION,
+        last DOUBLE PRECISION,
+        PRIMARY KEY (market_id, sampled_at)
+    );
+    CREATE INDEX idx_price_samples_sampled_at ON price_samples(sampled_at);";
+
+const V10_INSTANCE_LEASES: &str = "CREATE TABLE instance_leases (
+        key TEXT PRIMARY KEY,
+        holder TEXT NOT NULL,
+        acquired_at BIGINT NOT NULL,
+        heartbeat_at BIGINT NOT NULL,
+        expires_at BIGINT NOT NULL
+    );";
+
+const V11_AUDIT_LOG: &str = "CREATE TABLE audit_log (
+        id BIGSERIAL PRIMARY KEY,
+        action TEXT NOT NULL,
+        actor TEXT NOT NULL,
+        detail TEXT NOT NULL,
+        recorded_at BIGINT NOT NULL,
+        prev_hash TEXT NOT NULL UNIQUE,
+        hash TEXT NOT NULL
+    );
+    CREATE INDEX idx_audit_log_recorded_at ON audit_log(recorded_at);";
+
+const V12_PRICE_BOOKS: &str = "ALTER TABLE price_samples ADD COLUMN book TEXT;";
+
+// Redemptions settle without an order, so fills.order_id becomes nullable.
+const V13_ORDERLESS_FILLS: &str = "ALTER TABLE fills ALTER COLUMN order_id DROP NOT NULL;";
+
+const V14_ARRIVAL_MIDS: &str = "ALTER TABLE decisions ADD COLUMN arrival_mid DOUBLE PRECISION;";
+
+const V15_CASH_FLOWS: &str = "CREATE TABLE cash_flows (
+        id BIGSERIAL PRIMARY KEY,
+        key TEXT NOT NULL UNIQUE,
+        kind TEXT NOT NULL,
+        amount DOUBLE PRECISION NOT NULL,
+        tx_hash TEXT,
+        detail TEXT,
+        occurred_at BIGINT NOT NULL
+    );
+    CREATE INDEX idx_cash_flows_occurred_at ON cash_flows(occurred_at);";
+
+const V16_BARS: &str = "CREATE TABLE bars (
+        market_id TEXT NOT NULL,
+        interval_secs BIGINT NOT NULL,
+        opened_at BIGINT NOT NULL,
+        open DOUBLE PRECISION NOT NULL,
+        high DOUBLE PRECISION NOT NULL,
+        low DOUBLE PRECISION NOT NULL,
+        close DOUBLE PRECISION NOT NULL,
+        volume DOUBLE PRECISION NOT NULL,
+        trades BIGINT NOT NULL,
+        PRIMARY KEY (market_id, interval_secs, opened_at)
+    );
+    CREATE INDEX idx_bars_opened_at ON bars(opened_at);";
+
+const V17_DECISION_BOOKS: &str = "ALTER TABLE decisions ADD COLUMN arrival_book TEXT;";
+
+/// Appends racing another instance's retried this often before giving up.
+const AUDIT_APPEND_ATTEMPTS: usize = 5;
+
+const ORDER_COLUMNS: &str = "id, decision_id, exchange_order_id, market_id, side, shares, \
+    limit_price, order_type, status, error, submitted_at, client_order_id";
+
+/// Postgres backend, for several bot instances sharing one journal and
+/// position book. Position updates are single atomic statements, so
+/// concurrent writers never lose a fill.
+///
+/// `sslmode=require` in the url connects over TLS, verified against the
+/// system's root certificates; without it the connection is plain.
+pub struct PostgresStore {
+    client: Client,
+}
+
+impl PostgresStore {
+    pub async fn connect(url: &str) -> Result<Self> {
+        let config: tokio_postgres::Config = url.parse().context("Invalid Postgres url")?;
+        let client = match config.get_ssl_mode() {
+            SslMode::Require => open(&config, MakeRustlsConnect::new(crate::tls::client_config()?)).await?,
+            _ => open(&config, NoTls).await?,
+        };
+
+        let store = Self { client };
+        store.migrate().await?;
+        Ok(store)
+    }
+
+    async fn migrate(&self) -> Result<()> {
+        self.client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version BIGINT PRIMARY KEY,
+                    applied_at BIGINT NOT NULL
+                )",
+            )
+            .await?;
+
+        self.client.batch_execute("BEGIN").await?;
+        let result = self.apply_pending().await;
+        let end = if result.is_ok() { "COMMIT" } else { "ROLLBACK" };
+        self.client.batch_execute(end).await?;
+        result
+    }
+
+    async fn apply_pending(&self) -> Result<()> {
+        self.client
+            .execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])
+            .await?;
+        let current: i64 = self
+            .client
+            .query_one("SELECT COALESCE(MAX(version), 0) FROM schema_migrations", &[])
+            .await?
+            .get(0);
+
+        for (version, sql) in MIGRATIONS.iter().filter(|(v, _)| *v > current) {
+            self.client
+                .batch_execute(sql)
+                .await
+                .with_context(|| format!("Storage migration {} failed", version))?;
+            self.client
+                .execute(
+                    "INSERT INTO schema_migrations (version, applied_at) VALUES ($1, $2)",
+                    &[version, &super::now_ms()],
+                )
+                .await?;
+            tracing::info!("Applied storage migration {}", version);
+        }
+
+        Ok(())
+    }
+}
+
+async fn open<T>(config: &tokio_postgres::Config, tls: T) -> Result<Client>
+where
+    T: MakeTlsConnect<Socket>,
+    T::Stream: Send + 'static,
+{
+    let (client, connection) = config.connect(tls).await.context("Failed to connect to Postgres")?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::error!("Postgres connection closed: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+#[async_trait]
+impl Journal for PostgresStore {
+    async fn record_leader_trade(&self, trade: &Trade, observed_at: i64) -> Result<i64> {
+        let row = self
+            .client
+            .query_one(
+                "INSERT INTO leader_trades
+                    (wallet, event_id, market_id, side, shares, price, timestamp, tx_hash, observed_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                 RETURNING id",
+                &[
+                    &trade.wallet,
+                    &trade.event_id,
+                    &trade.market_id,
+                    &trade.side.as_str(),
+                    &trade.shares,
+                    &trade.price,
+                    &trade.timestamp,
+                    &trade.tx_hash,
+                    &observed_at,
+                ],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn record_decision(&self, d: &DecisionRecord) -> Result<i64> {
+        let row = self
+            .client
+            .query_one(
+                "INSERT INTO decisions
+                    (leader_trade_id, wallet, market_id, side, copied, reason, detail, size_usd, decided_at, arrival_mid,
+                     arrival_book)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                 RETURNING id",
+                &[
+                    &d.leader_trade_id,
+                    &d.wallet,
+                    &d.market_id,
+                    &d.side,
+                    &d.copied,
+                    &d.reason.map(|r| r.as_str()),
+                    &d.detail,
+                    &d.size_usd,
+                    &d.decided_at,
+                    &d.arrival_mid,
+                    &d.arrival_book.as_ref().map(serde_json::to_string).transpose()?,
+                ],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn record_order(&self, o: &OrderRecord) -> Result<i64> {
+        let row = self
+            .client
+            .query_one(
+                "INSERT INTO orders
+                    (decision_id, exchange_order_id, market_id, side, shares, limit_price,
+                     order_type, status, error, submitted_at, client_order_id)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                 RETURNING id",
+                &[
+                    &o.decision_id,
+                    &o.exchange_order_id,
+                    &o.market_id,
+                    &o.side,
+                    &o.shares,
+                    &o.limit_price,
+                    &o.order_type,
+                    &o.status,
+                    &o.error,
+                    &o.submitted_at,
+                    &o.client_order_id,
+                ],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn record_fill(&self, f: &FillRecord) -> Result<i64> {
+        let row = self
+            .client
+            .query_one(
+                "INSERT INTO fills (order_id, market_id, side, shares, price, fee, filled_at)
+                 VALUES (NULLIF($1::BIGINT, 0), $2, $3, $4, $5, $6, $7)
+                 RETURNING id",
+                &[&f.order_id, &f.market_id, &f.side, &f.shares, &f.price, &f.fee, &f.filled_at],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn update_order(
+        &self,
+        id: i64,
+        status: &str,
+        exchange_order_id: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        let updated = self
+            .client
+            .execute(
+                "UPDATE orders
+                 SET status = $2,
+                     exchange_order_id = COALESCE($3, exchange_order_id),
+                     error = COALESCE($4, error)
+                 WHERE id = $1",
+                &[&id, &status, &exchange_order_id, &error],
+            )
+            .await?;
+        if updated == 0 {
+            anyhow::bail!("No order with id {}", id);
+        }
+        Ok(())
+    }
+
+    async fn open_orders(&self) -> Result<Vec<OrderRecord>> {
+        let rows = self
+            .client
+            .query(
+                &format!("SELECT {} FROM orders WHERE status = ANY($1) ORDER BY id", ORDER_COLUMNS),
+                &[&OPEN_ORDER_STATUSES],
+            )
+            .await?;
+        Ok(rows.iter().map(order_from_row).collect())
+    }
+
+    async fn leader_trades(&self, range: TimeRange) -> Result<Vec<LeaderTradeRecord>> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {} FROM leader_trades WHERE observed_at BETWEEN $1 AND $2 ORDER BY id",
+                    LEADER_TRADE_COLUMNS
+                ),
+                &[&range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows.iter().map(leader_trade_from_row).collect())
+    }
+
+    async fn decisions(&self, range: TimeRange) -> Result<Vec<DecisionRecord>> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {} FROM decisions WHERE decided_at BETWEEN $1 AND $2 ORDER BY id",
+                    DECISION_COLUMNS
+                ),
+                &[&range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows.iter().map(decision_from_row).collect())
+    }
+
+    async fn orders(&self, range: TimeRange) -> Result<Vec<OrderRecord>> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {} FROM orders WHERE submitted_at BETWEEN $1 AND $2 ORDER BY id",
+                    ORDER_COLUMNS
+                ),
+                &[&range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows.iter().map(order_from_row).collect())
+    }
+
+    async fn fills(&self, range: TimeRange) -> Result<Vec<FillRecord>> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {} FROM fills WHERE filled_at BETWEEN $1 AND $2 ORDER BY id",
+                    FILL_COLUMNS
+                ),
+                &[&range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows.iter().map(fill_from_row).collect())
+    }
+}
+
+#[async_trait]
+impl Retention for PostgresStore {
+    async fn record_frame(&self, wallet: &str, payload: &str, received_at: i64) -> Result<i64> {
+        let row = self
+            .client
+            .query_one(
+                "INSERT INTO ws_frames (wallet, payload, received_at) VALUES ($1, $2, $3) RETURNING id",
+                &[&wallet, &payload, &received_at],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn frames(&self, range: TimeRange) -> Result<Vec<FrameRecord>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT id, wallet, payload, received_at FROM ws_frames
+                 WHERE received_at BETWEEN $1 AND $2 ORDER BY id",
+                &[&range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| FrameRecord {
+                id: row.get(0),
+                wallet: row.get(1),
+                payload: row.get(2),
+                received_at: row.get(3),
+            })
+            .collect())
+    }
+
+    async fn delete_frames_before(&self, before_ms: i64) -> Result<u64> {
+        Ok(self
+            .client
+            .execute("DELETE FROM ws_frames WHERE received_at < $1", &[&before_ms])
+            .await?)
+    }
+
+    async fn expired_journal(&self, before_ms: i64) -> Result<ExpiredJournal> {
+        let select = |columns: &str, table: &str, alias: &str, condition: String| {
+            format!(
+                "SELECT {} FROM {} {} WHERE {} ORDER BY id",
+                columns, table, alias, condition
+            )
+        };
+
+        let rows = self
+            .client
+            .query(
+                &select(LEADER_TRADE_COLUMNS, "leader_trades", "t", expiry::leader_trade("t", "$1")),
+                &[&before_ms],
+            )
+            .await?;
+        let leader_trades = rows.iter().map(leader_trade_from_row).collect();
+
+        let rows = self
+            .client
+            .query(
+                &select(DECISION_COLUMNS, "decisions", "d", expiry::decision("d", "$1")),
+                &[&before_ms],
+            )
+            .await?;
+        let decisions = rows.iter().map(decision_from_row).collect();
+
+        let rows = self
+            .client
+            .query(&select(ORDER_COLUMNS, "orders", "o", expiry::order("o", "$1")), &[&before_ms])
+            .await?;
+        let orders = rows.iter().map(order_from_row).collect();
+
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT {} FROM fills WHERE order_id IN (SELECT o.id FROM orders o WHERE {})
+                        OR (order_id IS NULL AND filled_at < $1) ORDER BY id",
+                    FILL_COLUMNS,
+                    expiry::order("o", "$1")
+                ),
+                &[&before_ms],
+            )
+            .await?;
+        let fills = rows.iter().map(fill_from_row).collect();
+
+        Ok(ExpiredJournal {
+            leader_trades,
+            decisions,
+            orders,
+            fills,
+        })
+    }
+
+    async fn delete_journal(&self, expired: &ExpiredJournal) -> Result<()> {
+        let fills: Vec<i64> = expired.fills.iter().map(|r| r.id).collect();
+        let orders: Vec<i64> = expired.orders.iter().map(|r| r.id).collect();
+        let decisions: Vec<i64> = expired.decisions.iter().map(|r| r.id).collect();
+        let trades: Vec<i64> = expired.leader_trades.iter().map(|r| r.id).collect();
+
+        // Children first so foreign keys never dangle; one statement keeps it atomic
+        self.client
+            .execute(
+                "WITH f AS (DELETE FROM fills WHERE id = ANY($1)),
+                      o AS (DELETE FROM orders WHERE id = ANY($2)),
+                      d AS (DELETE FROM decisions WHERE id = ANY($3))
+                 DELETE FROM leader_trades WHERE id = ANY($4)",
+                &[&fills, &orders, &decisions, &trades],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        self.client
+            .batch_execute("VACUUM (ANALYZE) leader_trades, decisions, orders, fills, ws_frames, seen_trades")
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MarketCatalog for PostgresStore {
+    async fn markets(&self) -> Result<Vec<MarketRecord>> {
+        let rows = self
+            .client
+            .query("SELECT data, fetched_at, expires_at FROM markets ORDER BY market_id", &[])
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(MarketRecord {
+                    market: serde_json::from_str(row.get(0))?,
+                    fetched_at: row.get(1),
+                    expires_at: row.get(2),
+                })
+            })
+            .collect()
+    }
+
+    async fn save_market(&self, record: &MarketRecord) -> Result<()> {
+        self.client
+            .execute(
+                "INSERT INTO markets (market_id, data, fetched_at, expires_at) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (market_id) DO UPDATE SET
+                    data = EXCLUDED.data,
+                    fetched_at = EXCLUDED.fetched_at,
+                    expires_at = EXCLUDED.expires_at",
+                &[
+                    &record.market.id,
+                    &serde_json::to_string(&record.market)?,
+                    &record.fetched_at,
+                    &record.expires_at,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PriceHistory for PostgresStore {
+    async fn record_prices(&self, samples: &[PriceSample]) -> Result<()> {
+        let markets: Vec<&str> = samples.iter().map(|s| s.market_id.as_str()).collect();
+        let sampled: Vec<i64> = samples.iter().map(|s| s.sampled_at).collect();
+        let mids: Vec<Option<f64>> = samples.iter().map(|s| s.mid).collect();
+        let lasts: Vec<Option<f64>> = samples.iter().map(|s| s.last).collect();
+        let books = samples
+            .iter()
+            .map(|s| s.book.as_ref().map(serde_json::to_string).transpose())
+            .collect::<Result<Vec<Option<String>>, _>>()?;
+        self.client
+            .execute(
+                "INSERT INTO price_samples (market_id, sampled_at, mid, last, book)
+                 SELECT * FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::DOUBLE PRECISION[], $4::DOUBLE PRECISION[],
+                     $5::TEXT[])
+                 ON CONFLICT (market_id, sampled_at) DO NOTHING",
+                &[&markets, &sampled, &mids, &lasts, &books],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn prices(&self, market_id: Option<&str>, range: TimeRange) -> Result<Vec<PriceSample>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT market_id, sampled_at, mid, last, book FROM price_samples
+                 WHERE ($1::TEXT IS NULL OR market_id = $1) AND sampled_at BETWEEN $2 AND $3
+                 ORDER BY market_id, sampled_at",
+                &[&market_id, &range.from_ms, &range.to_ms],
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(PriceSample {
+                    market_id: row.get(0),
+                    sampled_at: row.get(1),
+                    mid: row.get(2),
+                    last: row.get(3),
+                    book: row.get::<_, Option<&str>>(4).map(serde_json::from_str).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    async fn delete_prices_before(&self, before_ms: i64) -> Result<u64> {
+        Ok(self
+            .client
+            .execute("DELETE FROM price_samples WHERE sampled_at < $1", &[&before_ms])
+            .await?)
+    }
+
+    async fn record_bars(&self, bars: &[Bar]) -> Result<()> {
+        let column = |f: fn(&Bar) -> f64| bars.iter().map(f).collect::<Vec<f64>>();
+        let markets: Vec<&str> = bars.iter().map(|b| b.market_id.as_str()).collect();
+        let intervals: Vec<i64> = bars.iter().map(|b| b.interval_secs).collect();
+        let opened: Vec<i64> = bars.iter().map(|b| b.opened_at).collect();
+        let trades: Vec<i64> = bars.iter().map(|b| b.trades).collect();
+        self.client
+            .execute(
+                "INSERT INTO bars (market_id, interval_secs, opened_at, open, high, low, close, volume, trades)
+                 SELECT * FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::BIGINT[], $4::DOUBLE PRECISION[],
+                     $5::DOUBLE PRECISION[], $6::DOUBLE PRECISION[], $7::DOUBLE PRECISION[],
+                     $8::DOUBLE PRECISION[], $9::BIGINT[])
+                 ON CONFLICT (market_id, interval_secs, opened_at) DO UPDATE SET
+                     open = EXCLUDED.open, high = EXCLUDED.high, low = EXCLUDED.low, close = EXCLUDED.close,
+                     volume = EXCLUDED.volume, trades = EXCLUDED.trades",
+                &[
+                    &markets,
+                    &intervals,
+                    &opened,
+                    &column(|b| b.open),
+                    &column(|b| b.high),
+                    &column(|b| b.low),
+                    &column(|b| b.close),
+                    &column(|b| b.volume),
+                    &trades,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn bars(&self, market_id: &str, interval_secs: i64, range: TimeRange) -> Result<Vec<Bar>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT market_id, interval_secs, opened_at, open, high, low, close, volume, trades FROM bars
+                 WHERE market_id = $1 AND interval_secs = $2 AND opened_at BETWEEN $3 AND $4
+                 ORDER BY opened_at",
+                &[&market_id, &interval_secs, &range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Bar {
+                market_id: row.get(0),
+                interval_secs: row.get(1),
+                opened_at: row.get(2),
+                open: row.get(3),
+                high: row.get(4),
+                low: row.get(5),
+                close: row.get(6),
+                volume: row.get(7),
+                trades: row.get(8),
+            })
+            .collect())
+    }
+
+    async fn delete_bars_before(&self, before_ms: i64) -> Result<u64> {
+        Ok(self
+            .client
+            .execute("DELETE FROM bars WHERE opened_at < $1", &[&before_ms])
+            .await?)
+    }
+}
+
+#[async_trait]
+impl Leases for PostgresStore {
+    async fn acquire_lease(&self, lease: &LeaseRecord, force: bool) -> Result<LeaseRecord> {
+        self.client
+            .execute(
+                "INSERT INTO instance_leases (key, holder, acquired_at, heartbeat_at, expires_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (key) DO UPDATE SET
+                    acquired_at = CASE WHEN instance_leases.holder = EXCLUDED.holder
+                        THEN instance_leases.acquired_at ELSE EXCLUDED.acquired_at END,
+                    holder = EXCLUDED.holder,
+                    heartbeat_at = EXCLUDED.heartbeat_at,
+                    expires_at = EXCLUDED.expires_at
+                 WHERE $6 OR instance_leases.holder = EXCLUDED.holder
+                    OR instance_leases.expires_at <= EXCLUDED.heartbeat_at",
+                &[
+                    &lease.key,
+                    &lease.holder,
+                    &lease.acquired_at,
+                    &lease.heartbeat_at,
+                    &lease.expires_at,
+                    &force,
+                ],
+            )
+            .await?;
+        let row = self
+            .client
+            .query_one(
+                "SELECT key, holder, acquired_at, heartbeat_at, expires_at FROM instance_leases WHERE key = $1",
+                &[&lease.key],
+            )
+            .await?;
+        Ok(LeaseRecord {
+            key: row.get(0),
+            holder: row.get(1),
+            acquired_at: row.get(2),
+            heartbeat_at: row.get(3),
+            expires_at: row.get(4),
+        })
+    }
+
+    async fn release_lease(&self, key: &str, holder: &str) -> Result<()> {
+        self.client
+            .execute(
+                "DELETE FROM instance_leases WHERE key = $1 AND holder = $2",
+                &[&key, &holder],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuditLog for PostgresStore {
+    async fn append_audit(&self, entry: &AuditEntry) -> Result<AuditRecord> {
+        for _ in 0..AUDIT_APPEND_ATTEMPTS {
+            let prev_hash: String = self
+                .client
+                .query_opt("SELECT hash FROM audit_log ORDER BY id DESC LIMIT 1", &[])
+                .await?
+                .map_or_else(|| GENESIS_HASH.to_string(), |row| row.get(0));
+            let hash = chain_hash(&prev_hash, entry);
+            // prev_hash is unique: if another instance chained to the same
+            // tip first, nothing is inserted and we chain to the new tip
+            let row = self
+                .client
+                .query_opt(
+                    "INSERT INTO audit_log (action, actor, detail, recorded_at, prev_hash, hash)
+                     VALUES ($1, $2, $3, $4, $5, $6)
+                     ON CONFLICT (prev_hash) DO NOTHING
+                     RETURNING id",
+                    &[
+                        &entry.action,
+                        &entry.actor,
+                        &entry.detail,
+                        &entry.recorded_at,
+                        &prev_hash,
+                        &hash,
+                    ],
+                )
+                .await?;
+            if let Some(row) = row {
+                return Ok(AuditRecord {
+                    id: row.get(0),
+                    entry: entry.clone(),
+                    prev_hash,
+                    hash,
+                });
+            }
+        }
+        anyhow::bail!("Audit log kept changing under us; gave up after {} attempts", AUDIT_APPEND_ATTEMPTS)
+    }
+
+    async fn audit_records(&self, range: TimeRange) -> Result<Vec<AuditRecord>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT id, action, actor, detail, recorded_at, prev_hash, hash FROM audit_log
+                 WHERE recorded_at BETWEEN $1 AND $2 ORDER BY id",
+                &[&range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| AuditRecord {
+                id: row.get(0),
+                entry: AuditEntry {
+                    action: row.get(1),
+                    actor: row.get(2),
+                    detail: row.get(3),
+                    recorded_at: row.get(4),
+                },
+                prev_hash: row.get(5),
+                hash: row.get(6),
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl CashFlows for PostgresStore {
+    async fn record_cash_flow(&self, flow: &CashFlowRecord) -> Result<bool> {
+        let inserted = self
+            .client
+            .execute(
+                "INSERT INTO cash_flows (key, kind, amount, tx_hash, detail, occurred_at)
+                 VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (key) DO NOTHING",
+                &[
+                    &flow.key,
+                    &flow.kind,
+                    &flow.amount,
+                    &flow.tx_hash,
+                    &flow.detail,
+                    &flow.occurred_at,
+                ],
+            )
+            .await?;
+        Ok(inserted == 1)
+    }
+
+    async fn cash_flows(&self, range: TimeRange) -> Result<Vec<CashFlowRecord>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT id, key, kind, amount, tx_hash, detail, occurred_at FROM cash_flows
+                 WHERE occurred_at BETWEEN $1 AND $2 ORDER BY occurred_at, id",
+                &[&range.from_ms, &range.to_ms],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| CashFlowRecord {
+                id: row.get(0),
+                key: row.get(1),
+                kind: row.get(2),
+                amount: row.get(3),
+                tx_hash: row.get(4),
+                detail: row.get(5),
+                occurred_at: row.get(6),
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl StateStore for PostgresStore {
+    async fn load_state(&self, key: &str) -> Result<Option<String>> {
+        let row = self
+            .client
+            .query_opt("SELECT value FROM bot_state WHERE key = $1", &[&key])
+            .await?;
+        Ok(row.map(|r| r.get(0)))
+    }
+
+    async fn save_state(&self, key: &str, value: &str, updated_at: i64) -> Result<()> {
+        self.client
+            .execute(
+                "INSERT INTO bot_state (key, value, updated_at) VALUES ($1, $2, $3)
+                 ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at",
+                &[&key, &value, &updated_at],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SeenTrades for PostgresStore {
+    async fn mark_seen(&self, key: &str, seen_at: i64, not_before: i64) -> Result<bool> {
+        self.client
+            .execute("DELETE FROM seen_trades WHERE seen_at < $1", &[&not_before])
+            .await?;
+        // The primary key makes concurrent instances agree on who saw it first
+        let inserted = self
+            .client
+            .execute(
+                "INSERT INTO seen_trades (key, seen_at) VALUES ($1, $2) ON CONFLICT (key) DO NOTHING",
+                &[&key, &seen_at],
+            )
+            .await?;
+        Ok(inserted == 1)
+    }
+}
+
+const LEADER_TRADE_COLUMNS: &str =
+    "id, wallet, event_id, market_id, side, shares, price, timestamp, tx_hash, observed_at";
+
+const DECISION_COLUMNS: &str =
+    "id, leader_trade_id, wallet, market_id, side, copied, reason, detail, size_usd, decided_at, arrival_mid, arrival_book";
+
+// Order-less fills (redemptions) surface as order_id 0
+const FILL_COLUMNS: &str = "id, COALESCE(order_id, 0), market_id, side, shares, price, fee, filled_at";
+
+fn leader_trade_from_row(row: &Row) -> LeaderTradeRecord {
+    let side: String = row.get(4);
+    LeaderTradeRecord {
+        id: row.get(0),
+        trade: Trade {
+            wallet: row.get(1),
+            event_id: row.get(2),
+            market_id: row.get(3),
+            side: TradeSide::parse(&side).unwrap_or(TradeSide::BUY),
+            shares: row.get(5),
+            price: row.get(6),
+            timestamp: row.get(7),
+            tx_hash: row.get(8),
+        },
+        observed_at: row.get(9),
+    }
+}
+
+fn decision_from_row(row: &Row) -> DecisionRecord {
+    let reason: Option<String> = row.get(6);
+    DecisionRecord {
+        id: row.get(0),
+        leader_trade_id: row.get(1),
+        wallet: row.get(2),
+        market_id: row.get(3),
+        side: row.get(4),
+        copied: row.get(5),
+        reason: reason.as_deref().and_then(SkipReason::parse),
+        detail: row.get(7),
+        size_usd: row.get(8),
+        decided_at: row.get(9),
+        arrival_mid: row.get(10),
+        // A book that doesn't parse reads as none
+        arrival_book: row
+            .get::<_, Option<&str>>(11)
+            .and_then(|b| serde_json::from_str(b).ok()),
+    }
+}
+
+fn fill_from_row(row: &Row) -> FillRecord {
+    FillRecord {
+        id: row.get(0),
+        order_id: row.get(1),
+        market_id: row.get(2),
+        side: row.get(3),
+        shares: row.get(4),
+        price: row.get(5),
+        fee: row.get(6),
+        filled_at: row.get(7),
+    }
+}
+
+fn order_from_row(row: &Row) -> OrderRecord {
+    OrderRecord {
+        id: row.get(0),
+        decision_id: row.get(1),
+        exchange_order_id: row.get(2),
+        market_id: row.get(3),
+        side: row.get(4),
+        shares: row.get(5),
+        limit_price: row.get(6),
+        order_type: row.get(7),
+        status: row.get(8),
+        error: row.get(9),
+        submitted_at: row.get(10),
+        client_order_id: row.get(11),
+    }
+}
+
+fn position_from_row(row: &Row) -> PositionRecord {
+    PositionRecord {
+        market_id: row.get(0),
+        shares: row.get(1),
+        avg_price: row.get(2),
+        updated_at: row.get(3),
+    }
+}
+
+#[async_trait]
+impl PositionStore for PostgresStore {
+    async fn positions(&self) -> Result<Vec<PositionRecord>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT market_id, shares, avg_price, updated_at FROM positions ORDER BY market_id",
+                &[],
+            )
+            .await?;
+        Ok(rows.iter().map(position_from_row).collect())
+    }
+
+    async fn set_position(&self, p: &PositionRecord) -> Result<()> {
+        if p.shares <= 0.0 {
+            self.client
+                .execute("DELETE FROM positions WHERE market_id = $1", &[&p.market_id])
+                .await?;
+        } else {
+            self.client
+                .execute(
+                    "INSERT INTO positions (market_id, shares, avg_price, updated_at)
+                     VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (market_id) DO UPDATE SET
+                        shares = EXCLUDED.shares,
+                        avg_price = EXCLUDED.avg_price,
+                        updated_at = EXCLUDED.updated_at",
+                    &[&p.market_id, &p.shares, &p.avg_price, &p.updated_at],
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_fill(&self, fill: &FillRecord) -> Result<PositionRecord> {
+        // One statement per side so concurrent instances can't interleave a
+        // read-modify-write; same arithmetic as `position_after_fill`.
+        let row = if fill.side.eq_ignore_ascii_case("SELL") {
+            self.client
+                .query_one(
+                    "INSERT INTO positions (market_id, shares, avg_price, updated_at)
+                     VALUES ($1, 0, 0, $3)
+                     ON CONFLICT (market_id) DO UPDATE SET
+                        shares = GREATEST(positions.shares - $2, 0),
+                        avg_price = CASE WHEN positions.shares - $2 > 0 THEN positions.avg_price ELSE 0 END,
+                        updated_at = $3
+                     RETURNING market_id, shares, avg_price, updated_at",
+                    &[&fill.market_id, &fill.shares, &fill.filled_at],
+                )
+                .await?
+        } else {
+            self.client
+                .query_one(
+                    "INSERT INTO positions (market_id, shares, avg_price, updated_at)
+                     VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (market_id) DO UPDATE SET
+                        shares = positions.shares + $2,
+                        avg_price = COALESCE(
+                            (positions.shares * positions.avg_price + $2 * $3)
+                                / NULLIF(positions.shares + $2, 0),
+                            0),
+                        updated_at = $4
+                     RETURNING market_id, shares, avg_price, updated_at",
+                    &[&fill.market_id, &fill.shares, &fill.price, &fill.filled_at],
+                )
+                .await?
+        };
+        let position = position_from_row(&row);
+
+        if position.shares <= 0.0 {
+            self.client
+                .execute(
+                    "DELETE FROM positions WHERE market_id = $1 AND shares <= 0",
+                    &[&position.market_id],
+                )
+                .await?;
+        }
+        Ok(position)
+    }
+
+    async fn lots(&self) -> Result<Vec<LotRecord>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT market_id, shares, price, opened_at FROM position_lots ORDER BY market_id, id",
+                &[],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| LotRecord {
+                market_id: row.get(0),
+                shares: row.get(1),
+                price: row.get(2),
+                opened_at: row.get(3),
+            })
+            .collect())
+    }
+
+    async fn save_holding(&self, position: &PositionRecord, lots: &[LotRecord]) -> Result<()> {
+        let shares: Vec<f64> = lots.iter().map(|l| l.shares).collect();
+        let prices: Vec<f64> = lots.iter().map(|l| l.price).collect();
+        let opened: Vec<i64> = lots.iter().map(|l| l.opened_at).collect();
+        // One statement so readers never see a market without its lots
+        self.client
+            .execute(
+                "WITH d AS (DELETE FROM position_lots WHERE market_id = $1)
+                 INSERT INTO position_lots (market_id, shares, price, opened_at)
+                 SELECT $1, l.shares, l.price, l.opened_at
+                 FROM UNNEST($2::DOUBLE PRECISION[], $3::DOUBLE PRECISION[], $4::BIGINT[])
+                     WITH ORDINALITY AS l(shares, price, opened_at, n)
+                 ORDER BY l.n",
+                &[&position.market_id, &shares, &prices, &opened],
+            )
+            .await?;
+        self.set_position(position).await
+    }
+}
+
+/// These run against the database in `DATABASE_URL`, each in a schema of its
+/// own that is dropped afterwards, and pass trivially when it isn't set.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store migrated into a fresh schema, and the client to drop it with.
+    async fn store(name: &str) -> Option<(PostgresStore, Client, String)> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        let schema = format!("mybot_test_{}_{}", name, std::process::id());
+        let admin = PostgresStore::connect(&url).await.unwrap().client;
+        admin
+            .batch_execute(&format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}", schema))
+            .await
+            .unwrap();
+        let store = PostgresStore::connect(&in_schema(&url, &schema)).await.unwrap();
+        Some((store, admin, schema))
+    }
+
+    fn in_schema(url: &str, schema: &str) -> String {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        format!("{}{}options=-csearch_path%3D{}", url, separator, schema)
+    }
+
+    async fn drop_schema(admin: &Client, schema: &str) {
+        admin
+            .batch_execute(&format!("DROP SCHEMA {} CASCADE", schema))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrations_apply_once() {
+        let Some((store, admin, schema)) = store("migrations").await else {
+            return;
+        };
+        // A second instance starting on the same database applies nothing
+        let url = in_schema(&std::env::var("DATABASE_URL").unwrap(), &schema);
+        PostgresStore::connect(&url).await.unwrap();
+
+        let versions: Vec<i64> = store
+            .client
+            .query("SELECT version FROM schema_migrations ORDER BY version", &[])
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        let expected: Vec<i64> = MIGRATIONS.iter().map(|(v, _)| *v).collect();
+        assert_eq!(versions, expected);
+        drop_schema(&admin, &schema).await;
+    }
+
+    #[tokio::test]
+    async fn test_journal_and_positions_roundtrip() {
+        let Some((store, admin, schema)) = store("roundtrip").await else {
+            return;
+        };
+        let trade = Trade {
+            wallet: "0xwhale".to_string(),
+            event_id: "event1".to_string(),
+            market_id: "market1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.5,
+            timestamp: 1_700_000_000,
+            tx_hash: Some("0xtx".to_string()),
+        };
+        let trade_id = store.record_leader_trade(&trade, 1_000).await.unwrap();
+        let decision_id = store
+            .record_decision(&DecisionRecord {
+                id: 0,
+                leader_trade_id: Some(trade_id),
+                wallet: "0xwhale".to_string(),
+                market_id: "market1".to_string(),
+                side: "BUY".to_string(),
+                copied: true,
+                reason: None,
+                detail: None,
+                size_usd: Some(5.0),
+                decided_at: 1_001,
+                arrival_mid: Some(0.51),
+                arrival_book: None,
+            })
+            .await
+            .unwrap();
+        let order_id = store
+            .record_order(&OrderRecord {
+                id: 0,
+                decision_id: Some(decision_id),
+                exchange_order_id: None,
+                market_id: "market1".to_string(),
+                side: "BUY".to_string(),
+                shares: 10.0,
+                limit_price: Some(0.5),
+                order_type: "FAK".to_string(),
+                status: "submitting".to_string(),
+                error: None,
+                submitted_at: 1_002,
+                client_order_id: Some("c-1".to_string()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(store.open_orders().await.unwrap().len(), 1);
+        store.update_order(order_id, "filled", Some("ex-1"), None).await.unwrap();
+        assert!(store.open_orders().await.unwrap().is_empty());
+
+        let fill = |side: &str, shares, price| FillRecord {
+            id: 0,
+            order_id,
+            market_id: "market1".to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee: 0.0,
+            filled_at: 1_003,
+        };
+        store.record_fill(&fill("BUY", 10.0, 0.4)).await.unwrap();
+        store.apply_fill(&fill("BUY", 10.0, 0.4)).await.unwrap();
+        let position = store.apply_fill(&fill("BUY", 10.0, 0.6)).await.unwrap();
+        assert_eq!(position.shares, 20.0);
+        assert!((position.avg_price - 0.5).abs() < 1e-9);
+
+        let trades = store.leader_trades(TimeRange::all()).await.unwrap();
+        assert_eq!(trades[0].trade.tx_hash.as_deref(), Some("0xtx"));
+        let decisions = store.decisions(TimeRange::all()).await.unwrap();
+        assert_eq!((decisions[0].id, decisions[0].arrival_mid), (decision_id, Some(0.51)));
+        let orders = store.orders(TimeRange::all()).await.unwrap();
+        assert_eq!(orders[0].exchange_order_id.as_deref(), Some("ex-1"));
+        assert_eq!(orders[0].client_order_id.as_deref(), Some("c-1"));
+        assert_eq!(store.fills(TimeRange::all()).await.unwrap().len(), 1);
+
+        store.apply_fill(&fill("SELL", 20.0, 0.7)).await.unwrap();
+        assert!(store.positions().await.unwrap().is_empty());
+        drop_schema(&admin, &schema).await;
+    }
+}
@@ -0,0 +1,1233 @@
+use super::{
+    expiry, position_after_fill, AuditEntry, AuditLog, AuditRecord, Bar, CashFlowRecord, CashFlows, DecisionRecord, ExpiredJournal, FillRecord, FrameRecord, Journal,
+    LeaderTradeRecord, LeaseRecord, Leases, LotRecord, MarketCatalog, MarketRecord, OrderRecord,
+    PositionRecord, PositionStore, PriceHistory, PriceSample, Retention, SeenTrades, StateStore,
+    TimeRange, OPEN_ORDER_STATUSES,
+};
+use crate::audit::{chain_hash, GENESIS_HASH};
+use crate::types::{SkipReason, Trade, TradeSide};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// Schema migrations, applied in order and recorded in `schema_migrations`.
+/// Never edit an entry once released; append a new one instead.
+const MIGRATIONS: &[(i64, &str)] = &[
+    (1, V1_JOURNAL),
+    (2, V2_POSITIONS),
+    (3, V3_WS_FRAMES),
+    (4, V4_SEEN_TRADES),
+    (5, V5_POSITION_LOTS),
+    (6, V6_MARKETS),
+    (7, V7_BOT_STATE),
+    (8, V8_CLIENT_ORDER_IDS),
+    (9, V9_PRICE_SAMPLES),
+    (10, V10_INSTANCE_LEASES),
+    (11, V11_AUDIT_LOG),
+    (12, V12_PRICE_BOOKS),
+    (13, V13_ORDERLESS_FILLS),
+    (14, V14_ARRIVAL_MIDS),
+    (15, V15_CASH_FLOWS),
+    (16, V16_BARS),
+    (17, V17_DECISION_BOOKS),
+];
+
+const V1_JOURNAL: &str = "CREATE TABLE leader_trades (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        wallet TEXT NOT NULL,
+        event_id TEXT NOT NULL,
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        shares REAL NOT NULL,
+        price REAL NOT NULL,
+        timestamp INTEGER NOT NULL,
+        tx_hash TEXT,
+        observed_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_leader_trades_observed_at ON leader_trades(observed_at);
+
+    CREATE TABLE decisions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        leader_trade_id INTEGER REFERENCES leader_trades(id),
+        wallet TEXT NOT NULL,
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        copied INTEGER NOT NULL,
+        reason TEXT,
+        detail TEXT,
+        size_usd REAL,
+        decided_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_decisions_decided_at ON decisions(decided_at);
+
+    CREATE TABLE orders (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        decision_id INTEGER REFERENCES decisions(id),
+        exchange_order_id TEXT,
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        shares REAL NOT NULL,
+        limit_price REAL,
+        order_type TEXT NOT NULL,
+        status TEXT NOT NULL,
+        error TEXT,
+        submitted_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_orders_submitted_at ON orders(submitted_at);
+
+    CREATE TABLE fills (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        order_id INTEGER NOT NULL REFERENCES orders(id),
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        shares REAL NOT NULL,
+        price REAL NOT NULL,
+        fee REAL NOT NULL DEFAULT 0,
+        filled_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_fills_filled_at ON fills(filled_at);";
+
+const V2_POSITIONS: &str = "CREATE TABLE positions (
+        market_id TEXT PRIMARY KEY,
+        shares REAL NOT NULL,
+        avg_price REAL NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_orders_status ON orders(status);";
+
+const V3_WS_FRAMES: &str = "CREATE TABLE ws_frames (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        wallet TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        received_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_ws_frames_received_at ON ws_frames(received_at);";
+
+const V4_SEEN_TRADES: &str = "CREATE TABLE seen_trades (
+        key TEXT PRIMARY KEY,
+        seen_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_seen_trades_seen_at ON seen_trades(seen_at);";
+
+const V5_POSITION_LOTS: &str = "CREATE TABLE position_lots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        market_id TEXT NOT NULL,
+        shares REAL NOT NULL,
+        price REAL NOT NULL,
+        opened_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_position_lots_market_id ON position_lots(market_id);";
+
+const V6_MARKETS: &str = "CREATE TABLE markets (
+        market_id TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL
+    );";
+
+const V7_BOT_STATE: &str = "CREATE TABLE bot_state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    );";
+
+const V8_CLIENT_ORDER_IDS: &str = "ALTER TABLE orders ADD COLUMN client_order_id TEXT;
+    CREATE UNIQUE INDEX idx_orders_client_order_id ON orders(client_order_id);";
+
+const V9_PRICE_SAMPLES: &str = "CREATE TABLE price_samples (
+        market_id TEXT NOT NULL,
+        sampled_at INTEGER NOT NULL,
+        mid REAL,
+        last REAL,
+        PRIMARY KEY (market_id, sampled_at)
+    ) WITHOUT ROWID;
+    CREATE INDEX idx_price_samples_sampled_at ON price_samples(sampled_at);";
+
+const V10_INSTANCE_LEASES: &str = "CREATE TABLE instance_leases (
+        key TEXT PRIMARY KEY,
+        holder TEXT NOT NULL,
+        acquired_at INTEGER NOT NULL,
+        heartbeat_at INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL
+    );";
+
+const V11_AUDIT_LOG: &str = "CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        action TEXT NOT NULL,
+        actor TEXT NOT NULL,
+        detail TEXT NOT NULL,
+        recorded_at INTEGER NOT NULL,
+        prev_hash TEXT NOT NULL UNIQUE,
+        hash TEXT NOT NULL
+    );
+    CREATE INDEX idx_audit_log_recorded_at ON audit_log(recorded_at);";
+
+const V12_PRICE_BOOKS: &str = "ALTER TABLE price_samples ADD COLUMN book TEXT;";
+
+// Redemptions settle without an order, so fills.order_id becomes nullable.
+// SQLite can't relax a column constraint in place; rebuild the table.
+const V13_ORDERLESS_FILLS: &str = "CREATE TABLE fills_v13 (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        order_id INTEGER REFERENCES orders(id),
+        market_id TEXT NOT NULL,
+        side TEXT NOT NULL,
+        shares REAL NOT NULL,
+        price REAL NOT NULL,
+        fee REAL NOT NULL DEFAULT 0,
+        filled_at INTEGER NOT NULL
+    );
+    INSERT INTO fills_v13 (id, order_id, market_id, side, shares, price, fee, filled_at)
+        SELECT id, order_id, market_id, side, shares, price, fee, filled_at FROM fills;
+    DROP TABLE fills;
+    ALTER TABLE fills_v13 RENAME TO fills;
+    CREATE INDEX idx_fills_filled_at ON fills(filled_at);";
+
+const V14_ARRIVAL_MIDS: &str = "ALTER TABLE decisions ADD COLUMN arrival_mid REAL;";
+
+const V15_CASH_FLOWS: &str = "CREATE TABLE cash_flows (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        key TEXT NOT NULL UNIQUE,
+        kind TEXT NOT NULL,
+        amount REAL NOT NULL,
+        tx_hash TEXT,
+        detail TEXT,
+        occurred_at INTEGER NOT NULL
+    );
+    CREATE INDEX idx_cash_flows_occurred_at ON cash_flows(occurred_at);";
+
+const V16_BARS: &str = "CREATE TABLE bars (
+        market_id TEXT NOT NULL,
+        interval_secs INTEGER NOT NULL,
+        opened_at INTEGER NOT NULL,
+        open REAL NOT NULL,
+        high REAL NOT NULL,
+        low REAL NOT NULL,
+        close REAL NOT NULL,
+        volume REAL NOT NULL,
+        trades INTEGER NOT NULL,
+        PRIMARY KEY (market_id, interval_secs, opened_at)
+    ) WITHOUT ROWID;
+    CREATE INDEX idx_bars_opened_at ON bars(opened_at);";
+
+const V17_DECISION_BOOKS: &str = "ALTER TABLE decisions ADD COLUMN arrival_book TEXT;";
+
+/// SQLite backend. A single connection behind a mutex is plenty for the
+/// bot's write rate; WAL mode keeps readers (CLI, exports) unblocked.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open SQLite database {}", path))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        migrate(&conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+}
+
+fn migrate(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            applied_at INTEGER NOT NULL
+        )",
+    )?;
+    let current: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )?;
+
+    for (version, sql) in MIGRATIONS.iter().filter(|(v, _)| *v > current) {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(sql)
+            .with_context(|| format!("Storage migration {} failed", version))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, applied_at) VALUES (?1, ?2)",
+            params![version, super::now_ms()],
+        )?;
+        tx.commit()?;
+        tracing::info!("Applied storage migration {}", version);
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl Journal for SqliteStore {
+    async fn record_leader_trade(&self, trade: &Trade, observed_at: i64) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO leader_trades
+                (wallet, event_id, market_id, side, shares, price, timestamp, tx_hash, observed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                trade.wallet,
+                trade.event_id,
+                trade.market_id,
+                trade.side.as_str(),
+                trade.shares,
+                trade.price,
+                trade.timestamp,
+                trade.tx_hash,
+                observed_at,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn record_decision(&self, d: &DecisionRecord) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO decisions
+                (leader_trade_id, wallet, market_id, side, copied, reason, detail, size_usd, decided_at, arrival_mid,
+                 arrival_book)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                d.leader_trade_id,
+                d.wallet,
+                d.market_id,
+                d.side,
+                d.copied,
+                d.reason.map(|r| r.as_str()),
+                d.detail,
+                d.size_usd,
+                d.decided_at,
+                d.arrival_mid,
+                d.arrival_book.as_ref().map(serde_json::to_string).transpose()?,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn record_order(&self, o: &OrderRecord) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO orders
+                (decision_id, exchange_order_id, market_id, side, shares, limit_price,
+                 order_type, status, error, submitted_at, client_order_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                o.decision_id,
+                o.exchange_order_id,
+                o.market_id,
+                o.side,
+                o.shares,
+                o.limit_price,
+                o.order_type,
+                o.status,
+                o.error,
+                o.submitted_at,
+                o.client_order_id,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn record_fill(&self, f: &FillRecord) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO fills (order_id, market_id, side, shares, price, fee, filled_at)
+             VALUES (NULLIF(?1, 0), ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![f.order_id, f.market_id, f.side, f.shares, f.price, f.fee, f.filled_at],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn update_order(
+        &self,
+        id: i64,
+        status: &str,
+        exchange_order_id: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        let updated = self.conn().execute(
+            "UPDATE orders
+             SET status = ?2,
+                 exchange_order_id = COALESCE(?3, exchange_order_id),
+                 error = COALESCE(?4, error)
+             WHERE id = ?1",
+            params![id, status, exchange_order_id, error],
+        )?;
+        if updated == 0 {
+            anyhow::bail!("No order with id {}", id);
+        }
+        Ok(())
+    }
+
+    async fn open_orders(&self) -> Result<Vec<OrderRecord>> {
+        let conn = self.conn();
+        let placeholders = vec!["?"; OPEN_ORDER_STATUSES.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM orders WHERE status IN ({}) ORDER BY id",
+            ORDER_COLUMNS, placeholders
+        ))?;
+        let rows = stmt.query_map(
+            rusqlite::params_from_iter(OPEN_ORDER_STATUSES.iter()),
+            order_from_row,
+        )?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn leader_trades(&self, range: TimeRange) -> Result<Vec<LeaderTradeRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM leader_trades WHERE observed_at BETWEEN ?1 AND ?2 ORDER BY id",
+            LEADER_TRADE_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms], leader_trade_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn decisions(&self, range: TimeRange) -> Result<Vec<DecisionRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM decisions WHERE decided_at BETWEEN ?1 AND ?2 ORDER BY id",
+            DECISION_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms], decision_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn orders(&self, range: TimeRange) -> Result<Vec<OrderRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM orders WHERE submitted_at BETWEEN ?1 AND ?2 ORDER BY id",
+            ORDER_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms], order_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn fills(&self, range: TimeRange) -> Result<Vec<FillRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM fills WHERE filled_at BETWEEN ?1 AND ?2 ORDER BY id",
+            FILL_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms], fill_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+const LEADER_TRADE_COLUMNS: &str =
+    "id, wallet, event_id, market_id, side, shares, price, timestamp, tx_hash, observed_at";
+
+const DECISION_COLUMNS: &str =
+    "id, leader_trade_id, wallet, market_id, side, copied, reason, detail, size_usd, decided_at, arrival_mid, arrival_book";
+
+// Order-less fills (redemptions) surface as order_id 0
+const FILL_COLUMNS: &str = "id, COALESCE(order_id, 0), market_id, side, shares, price, fee, filled_at";
+
+fn leader_trade_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<LeaderTradeRecord> {
+    let side: String = row.get(4)?;
+    Ok(LeaderTradeRecord {
+        id: row.get(0)?,
+        trade: Trade {
+            wallet: row.get(1)?,
+            event_id: row.get(2)?,
+            market_id: row.get(3)?,
+            side: TradeSide::parse(&side).unwrap_or(TradeSide::BUY),
+            shares: row.get(5)?,
+            price: row.get(6)?,
+            timestamp: row.get(7)?,
+            tx_hash: row.get(8)?,
+        },
+        observed_at: row.get(9)?,
+    })
+}
+
+fn decision_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DecisionRecord> {
+    let reason: Option<String> = row.get(6)?;
+    Ok(DecisionRecord {
+        id: row.get(0)?,
+        leader_trade_id: row.get(1)?,
+        wallet: row.get(2)?,
+        market_id: row.get(3)?,
+        side: row.get(4)?,
+        copied: row.get(5)?,
+        reason: reason.as_deref().and_then(SkipReason::parse),
+        detail: row.get(7)?,
+        size_usd: row.get(8)?,
+        decided_at: row.get(9)?,
+        arrival_mid: row.get(10)?,
+        // A book that doesn't parse reads as none
+        arrival_book: row
+            .get::<_, Option<String>>(11)?
+            .and_then(|b| serde_json::from_str(&b).ok()),
+    })
+}
+
+fn fill_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<FillRecord> {
+    Ok(FillRecord {
+        id: row.get(0)?,
+        order_id: row.get(1)?,
+        market_id: row.get(2)?,
+        side: row.get(3)?,
+        shares: row.get(4)?,
+        price: row.get(5)?,
+        fee: row.get(6)?,
+        filled_at: row.get(7)?,
+    })
+}
+
+const ORDER_COLUMNS: &str = "id, decision_id, exchange_order_id, market_id, side, shares, \
+    limit_price, order_type, status, error, submitted_at, client_order_id";
+
+fn order_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<OrderRecord> {
+    Ok(OrderRecord {
+        id: row.get(0)?,
+        decision_id: row.get(1)?,
+        exchange_order_id: row.get(2)?,
+        market_id: row.get(3)?,
+        side: row.get(4)?,
+        shares: row.get(5)?,
+        limit_price: row.get(6)?,
+        order_type: row.get(7)?,
+        status: row.get(8)?,
+        error: row.get(9)?,
+        submitted_at: row.get(10)?,
+        client_order_id: row.get(11)?,
+    })
+}
+
+#[async_trait]
+impl PositionStore for SqliteStore {
+    async fn positions(&self) -> Result<Vec<PositionRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT market_id, shares, avg_price, updated_at FROM positions ORDER BY market_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(PositionRecord {
+                market_id: row.get(0)?,
+                shares: row.get(1)?,
+                avg_price: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn set_position(&self, p: &PositionRecord) -> Result<()> {
+        write_position(&self.conn(), p)
+    }
+
+    async fn apply_fill(&self, fill: &FillRecord) -> Result<PositionRecord> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        let current = tx
+            .query_row(
+                "SELECT market_id, shares, avg_price, updated_at FROM positions WHERE market_id = ?1",
+                params![fill.market_id],
+                |row| {
+                    Ok(PositionRecord {
+                        market_id: row.get(0)?,
+                        shares: row.get(1)?,
+                        avg_price: row.get(2)?,
+                        updated_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?;
+        let position = position_after_fill(current.as_ref(), fill);
+        write_position(&tx, &position)?;
+        tx.commit()?;
+        Ok(position)
+    }
+
+    async fn lots(&self) -> Result<Vec<LotRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT market_id, shares, price, opened_at FROM position_lots ORDER BY market_id, id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(LotRecord {
+                market_id: row.get(0)?,
+                shares: row.get(1)?,
+                price: row.get(2)?,
+                opened_at: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn save_holding(&self, position: &PositionRecord, lots: &[LotRecord]) -> Result<()> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM position_lots WHERE market_id = ?1", params![position.market_id])?;
+        for lot in lots {
+            tx.execute(
+                "INSERT INTO position_lots (market_id, shares, price, opened_at) VALUES (?1, ?2, ?3, ?4)",
+                params![position.market_id, lot.shares, lot.price, lot.opened_at],
+            )?;
+        }
+        write_position(&tx, position)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Retention for SqliteStore {
+    async fn record_frame(&self, wallet: &str, payload: &str, received_at: i64) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO ws_frames (wallet, payload, received_at) VALUES (?1, ?2, ?3)",
+            params![wallet, payload, received_at],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn frames(&self, range: TimeRange) -> Result<Vec<FrameRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, wallet, payload, received_at FROM ws_frames
+             WHERE received_at BETWEEN ?1 AND ?2 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms], |row| {
+            Ok(FrameRecord {
+                id: row.get(0)?,
+                wallet: row.get(1)?,
+                payload: row.get(2)?,
+                received_at: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn delete_frames_before(&self, before_ms: i64) -> Result<u64> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM ws_frames WHERE received_at < ?1", params![before_ms])?;
+        Ok(deleted as u64)
+    }
+
+    async fn expired_journal(&self, before_ms: i64) -> Result<ExpiredJournal> {
+        let conn = self.conn();
+        let query = |sql: String| conn.prepare(&sql);
+
+        let mut stmt = query(format!(
+            "SELECT {} FROM leader_trades t WHERE {} ORDER BY id",
+            LEADER_TRADE_COLUMNS,
+            expiry::leader_trade("t", "?1")
+        ))?;
+        let leader_trades = stmt
+            .query_map(params![before_ms], leader_trade_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = query(format!(
+            "SELECT {} FROM decisions d WHERE {} ORDER BY id",
+            DECISION_COLUMNS,
+            expiry::decision("d", "?1")
+        ))?;
+        let decisions = stmt
+            .query_map(params![before_ms], decision_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = query(format!(
+            "SELECT {} FROM orders o WHERE {} ORDER BY id",
+            ORDER_COLUMNS,
+            expiry::order("o", "?1")
+        ))?;
+        let orders = stmt
+            .query_map(params![before_ms], order_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = query(format!(
+            "SELECT {} FROM fills WHERE order_id IN (SELECT o.id FROM orders o WHERE {})
+                OR (order_id IS NULL AND filled_at < ?1) ORDER BY id",
+            FILL_COLUMNS,
+            expiry::order("o", "?1")
+        ))?;
+        let fills = stmt
+            .query_map(params![before_ms], fill_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ExpiredJournal {
+            leader_trades,
+            decisions,
+            orders,
+            fills,
+        })
+    }
+
+    async fn delete_journal(&self, expired: &ExpiredJournal) -> Result<()> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        // Children first so foreign keys never dangle
+        delete_ids(&tx, "fills", expired.fills.iter().map(|r| r.id))?;
+        delete_ids(&tx, "orders", expired.orders.iter().map(|r| r.id))?;
+        delete_ids(&tx, "decisions", expired.decisions.iter().map(|r| r.id))?;
+        delete_ids(&tx, "leader_trades", expired.leader_trades.iter().map(|r| r.id))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        let conn = self.conn();
+        conn.execute_batch("VACUUM; PRAGMA optimize;")?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MarketCatalog for SqliteStore {
+    async fn markets(&self) -> Result<Vec<MarketRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT data, fetched_at, expires_at FROM markets ORDER BY market_id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        rows.map(|row| {
+            let (data, fetched_at, expires_at) = row?;
+            Ok(MarketRecord {
+                market: serde_json::from_str(&data)?,
+                fetched_at,
+                expires_at,
+            })
+        })
+        .collect()
+    }
+
+    async fn save_market(&self, record: &MarketRecord) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO markets (market_id, data, fetched_at, expires_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(market_id) DO UPDATE SET
+                data = excluded.data,
+                fetched_at = excluded.fetched_at,
+                expires_at = excluded.expires_at",
+            params![
+                record.market.id,
+                serde_json::to_string(&record.market)?,
+                record.fetched_at,
+                record.expires_at
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StateStore for SqliteStore {
+    async fn load_state(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn()
+            .query_row("SELECT value FROM bot_state WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?)
+    }
+
+    async fn save_state(&self, key: &str, value: &str, updated_at: i64) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO bot_state (key, value, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![key, value, updated_at],
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PriceHistory for SqliteStore {
+    async fn record_prices(&self, samples: &[PriceSample]) -> Result<()> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO price_samples (market_id, sampled_at, mid, last, book) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (market_id, sampled_at) DO NOTHING",
+            )?;
+            for s in samples {
+                let book = s.book.as_ref().map(serde_json::to_string).transpose()?;
+                stmt.execute(params![s.market_id, s.sampled_at, s.mid, s.last, book])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn prices(&self, market_id: Option<&str>, range: TimeRange) -> Result<Vec<PriceSample>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT market_id, sampled_at, mid, last, book FROM price_samples
+             WHERE (?1 IS NULL OR market_id = ?1) AND sampled_at BETWEEN ?2 AND ?3
+             ORDER BY market_id, sampled_at",
+        )?;
+        let rows = stmt.query_map(params![market_id, range.from_ms, range.to_ms], |row| {
+            let book: Option<String> = row.get(4)?;
+            Ok((
+                PriceSample {
+                    market_id: row.get(0)?,
+                    sampled_at: row.get(1)?,
+                    mid: row.get(2)?,
+                    last: row.get(3)?,
+                    book: None,
+                },
+                book,
+            ))
+        })?;
+        rows.map(|row| {
+            let (mut sample, book) = row?;
+            sample.book = book.as_deref().map(serde_json::from_str).transpose()?;
+            Ok(sample)
+        })
+        .collect()
+    }
+
+    async fn delete_prices_before(&self, before_ms: i64) -> Result<u64> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM price_samples WHERE sampled_at < ?1", params![before_ms])?;
+        Ok(deleted as u64)
+    }
+
+    async fn record_bars(&self, bars: &[Bar]) -> Result<()> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO bars (market_id, interval_secs, opened_at, open, high, low, close, volume, trades)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for b in bars {
+                stmt.execute(params![
+                    b.market_id, b.interval_secs, b.opened_at, b.open, b.high, b.low, b.close, b.volume, b.trades
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn bars(&self, market_id: &str, interval_secs: i64, range: TimeRange) -> Result<Vec<Bar>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT market_id, interval_secs, opened_at, open, high, low, close, volume, trades FROM bars
+             WHERE market_id = ?1 AND interval_secs = ?2 AND opened_at BETWEEN ?3 AND ?4
+             ORDER BY opened_at",
+        )?;
+        let rows = stmt.query_map(params![market_id, interval_secs, range.from_ms, range.to_ms], |row| {
+            Ok(Bar {
+                market_id: row.get(0)?,
+                interval_secs: row.get(1)?,
+                opened_at: row.get(2)?,
+                open: row.get(3)?,
+                high: row.get(4)?,
+                low: row.get(5)?,
+                close: row.get(6)?,
+                volume: row.get(7)?,
+                trades: row.get(8)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    async fn delete_bars_before(&self, before_ms: i64) -> Result<u64> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM bars WHERE opened_at < ?1", params![before_ms])?;
+        Ok(deleted as u64)
+    }
+}
+
+#[async_trait]
+impl Leases for SqliteStore {
+    async fn acquire_lease(&self, lease: &LeaseRecord, force: bool) -> Result<LeaseRecord> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO instance_leases (key, holder, acquired_at, heartbeat_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(key) DO UPDATE SET
+                acquired_at = CASE WHEN instance_leases.holder = excluded.holder
+                    THEN instance_leases.acquired_at ELSE excluded.acquired_at END,
+                holder = excluded.holder,
+                heartbeat_at = excluded.heartbeat_at,
+                expires_at = excluded.expires_at
+             WHERE ?6 OR instance_leases.holder = excluded.holder
+                OR instance_leases.expires_at <= excluded.heartbeat_at",
+            params![
+                lease.key,
+                lease.holder,
+                lease.acquired_at,
+                lease.heartbeat_at,
+                lease.expires_at,
+                force
+            ],
+        )?;
+        Ok(conn.query_row(
+            "SELECT key, holder, acquired_at, heartbeat_at, expires_at FROM instance_leases WHERE key = ?1",
+            params![lease.key],
+            |row| {
+                Ok(LeaseRecord {
+                    key: row.get(0)?,
+                    holder: row.get(1)?,
+                    acquired_at: row.get(2)?,
+                    heartbeat_at: row.get(3)?,
+                    expires_at: row.get(4)?,
+                })
+            },
+        )?)
+    }
+
+    async fn release_lease(&self, key: &str, holder: &str) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM instance_leases WHERE key = ?1 AND holder = ?2",
+            params![key, holder],
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SeenTrades for SqliteStore {
+    async fn mark_seen(&self, key: &str, seen_at: i64, not_before: i64) -> Result<bool> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM seen_trades WHERE seen_at < ?1", params![not_before])?;
+        let inserted = tx.execute(
+            "INSERT INTO seen_trades (key, seen_at) VALUES (?1, ?2) ON CONFLICT (key) DO NOTHING",
+            params![key, seen_at],
+        )?;
+        tx.commit()?;
+        Ok(inserted == 1)
+    }
+}
+
+#[async_trait]
+impl AuditLog for SqliteStore {
+    async fn append_audit(&self, entry: &AuditEntry) -> Result<AuditRecord> {
+        // The connection lock makes reading the tip and appending one step
+        let conn = self.conn();
+        let prev_hash: String = conn
+            .query_row("SELECT hash FROM audit_log ORDER BY id DESC LIMIT 1", [], |row| row.get(0))
+            .optional()?
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+        let hash = chain_hash(&prev_hash, entry);
+        conn.execute(
+            "INSERT INTO audit_log (action, actor, detail, recorded_at, prev_hash, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![entry.action, entry.actor, entry.detail, entry.recorded_at, prev_hash, hash],
+        )?;
+        Ok(AuditRecord {
+            id: conn.last_insert_rowid(),
+            entry: entry.clone(),
+            prev_hash,
+            hash,
+        })
+    }
+
+    async fn audit_records(&self, range: TimeRange) -> Result<Vec<AuditRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, action, actor, detail, recorded_at, prev_hash, hash FROM audit_log
+             WHERE recorded_at BETWEEN ?1 AND ?2 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms], |row| {
+            Ok(AuditRecord {
+                id: row.get(0)?,
+                entry: AuditEntry {
+                    action: row.get(1)?,
+                    actor: row.get(2)?,
+                    detail: row.get(3)?,
+                    recorded_at: row.get(4)?,
+                },
+                prev_hash: row.get(5)?,
+                hash: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+#[async_trait]
+impl CashFlows for SqliteStore {
+    async fn record_cash_flow(&self, flow: &CashFlowRecord) -> Result<bool> {
+        let inserted = self.conn().execute(
+            "INSERT INTO cash_flows (key, kind, amount, tx_hash, detail, occurred_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT (key) DO NOTHING",
+            params![flow.key, flow.kind, flow.amount, flow.tx_hash, flow.detail, flow.occurred_at],
+        )?;
+        Ok(inserted == 1)
+    }
+
+    async fn cash_flows(&self, range: TimeRange) -> Result<Vec<CashFlowRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, key, kind, amount, tx_hash, detail, occurred_at FROM cash_flows
+             WHERE occurred_at BETWEEN ?1 AND ?2 ORDER BY occurred_at, id",
+        )?;
+        let rows = stmt.query_map(params![range.from_ms, range.to_ms], |row| {
+            Ok(CashFlowRecord {
+                id: row.get(0)?,
+                key: row.get(1)?,
+                kind: row.get(2)?,
+                amount: row.get(3)?,
+                tx_hash: row.get(4)?,
+                detail: row.get(5)?,
+                occurred_at: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+fn delete_ids(conn: &Connection, table: &str, ids: impl Iterator<Item = i64>) -> Result<()> {
+    let ids: Vec<i64> = ids.collect();
+    for chunk in ids.chunks(500) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        conn.execute(
+            &format!("DELETE FROM {} WHERE id IN ({})", table, placeholders),
+            rusqlite::params_from_iter(chunk.iter()),
+        )?;
+    }
+    Ok(())
+}
+
+fn write_position(conn: &Connection, p: &PositionRecord) -> Result<()> {
+    if p.shares <= 0.0 {
+        conn.execute("DELETE FROM positions WHERE market_id = ?1", params![p.market_id])?;
+    } else {
+        conn.execute(
+            "INSERT INTO positions (market_id, shares, avg_price, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(market_id) DO UPDATE SET
+                shares = excluded.shares,
+                avg_price = excluded.avg_price,
+                updated_at = excluded.updated_at",
+            params![p.market_id, p.shares, p.avg_price, p.updated_at],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OrderBook;
+
+    fn trade() -> Trade {
+        Trade {
+            wallet: "0xwhale".to_string(),
+            event_id: "event1".to_string(),
+            market_id: "market1".to_string(),
+            side: TradeSide::BUY,
+            shares: 100.0,
+            price: 0.5,
+            timestamp: 1_700_000_000,
+            tx_hash: Some("0xtx".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_journal_roundtrip() {
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        let trade_id = store.record_leader_trade(&trade(), 1_000).await.unwrap();
+        let decision_id = store
+            .record_decision(&DecisionRecord {
+                id: 0,
+                leader_trade_id: Some(trade_id),
+                wallet: "0xwhale".to_string(),
+                market_id: "market1".to_string(),
+                side: "BUY".to_string(),
+                copied: false,
+                reason: Some(SkipReason::RiskBlocked),
+                detail: Some("Daily volume limit exceeded".to_string()),
+                size_usd: Some(25.0),
+                decided_at: 1_001,
+                arrival_mid: Some(0.51),
+                arrival_book: Some(OrderBook::new(vec![(0.50, 40.0)], vec![(0.52, 15.0)])),
+            })
+            .await
+            .unwrap();
+
+        let trades = store.leader_trades(TimeRange::all()).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].trade.tx_hash.as_deref(), Some("0xtx"));
+
+        let decisions = store.decisions(TimeRange::since(1_001)).await.unwrap();
+        assert_eq!(decisions[0].id, decision_id);
+        assert_eq!(decisions[0].reason, Some(SkipReason::RiskBlocked));
+        assert_eq!(decisions[0].arrival_mid, Some(0.51));
+        assert_eq!(decisions[0].arrival_book.as_ref().unwrap().asks, [(0.52, 15.0)]);
+        assert!(store.decisions(TimeRange::since(2_000)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_open_orders_and_positions() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let order = OrderRecord {
+            id: 0,
+            decision_id: None,
+            exchange_order_id: None,
+            market_id: "market1".to_string(),
+            side: "BUY".to_string(),
+            shares: 10.0,
+            limit_price: Some(0.5),
+            order_type: "FAK".to_string(),
+            status: "submitting".to_string(),
+            error: None,
+            submitted_at: 1_000,
+            client_order_id: None,
+        };
+        let id = store.record_order(&order).await.unwrap();
+        assert_eq!(store.open_orders().await.unwrap().len(), 1);
+
+        store.update_order(id, "filled", Some("ex-1"), None).await.unwrap();
+        assert!(store.open_orders().await.unwrap().is_empty());
+        let orders = store.orders(TimeRange::all()).await.unwrap();
+        assert_eq!(orders[0].exchange_order_id.as_deref(), Some("ex-1"));
+
+        let fill = |side: &str, shares, price| FillRecord {
+            id: 0,
+            order_id: id,
+            market_id: "market1".to_string(),
+            side: side.to_string(),
+            shares,
+            price,
+            fee: 0.0,
+            filled_at: 1_001,
+        };
+        store.apply_fill(&fill("BUY", 10.0, 0.4)).await.unwrap();
+        let position = store.apply_fill(&fill("BUY", 10.0, 0.6)).await.unwrap();
+        assert_eq!(position.shares, 20.0);
+        assert!((position.avg_price - 0.5).abs() < 1e-9);
+
+        store.apply_fill(&fill("SELL", 20.0, 0.7)).await.unwrap();
+        assert!(store.positions().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expired_journal_keeps_referenced_rows() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let decision = |trade_id, at| DecisionRecord {
+            id: 0,
+            leader_trade_id: Some(trade_id),
+            wallet: "0xwhale".to_string(),
+            market_id: "market1".to_string(),
+            side: "BUY".to_string(),
+            copied: true,
+            reason: None,
+            detail: None,
+            size_usd: Some(25.0),
+            decided_at: at,
+            arrival_mid: None,
+            arrival_book: None,
+        };
+        let order = |decision_id, status: &str, at| OrderRecord {
+            id: 0,
+            decision_id: Some(decision_id),
+            exchange_order_id: None,
+            market_id: "market1".to_string(),
+            side: "BUY".to_string(),
+            shares: 50.0,
+            limit_price: Some(0.5),
+            order_type: "FAK".to_string(),
+            status: status.to_string(),
+            error: None,
+            submitted_at: at,
+            client_order_id: None,
+        };
+
+        // An old, finished chain of rows...
+        let old_trade = store.record_leader_trade(&trade(), 100).await.unwrap();
+        let old_decision = store.record_decision(&decision(old_trade, 101)).await.unwrap();
+        store.record_order(&order(old_decision, "filled", 102)).await.unwrap();
+        // ...and an old one whose order is still open.
+        let open_trade = store.record_leader_trade(&trade(), 100).await.unwrap();
+        let open_decision = store.record_decision(&decision(open_trade, 101)).await.unwrap();
+        store.record_order(&order(open_decision, "open", 102)).await.unwrap();
+
+        let expired = store.expired_journal(1_000).await.unwrap();
+        assert_eq!(expired.leader_trades.len(), 1);
+        assert_eq!(expired.leader_trades[0].id, old_trade);
+        assert_eq!(expired.orders.len(), 1);
+
+        store.delete_journal(&expired).await.unwrap();
+        assert_eq!(store.leader_trades(TimeRange::all()).await.unwrap().len(), 1);
+        assert!(store.expired_journal(1_000).await.unwrap().is_empty());
+
+        store.record_frame("0xwhale", "{}", 100).await.unwrap();
+        assert_eq!(store.delete_frames_before(1_000).await.unwrap(), 1);
+        store.vacuum().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_price_samples() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let sample = |market_id: &str, sampled_at: i64, mid: Option<f64>| PriceSample {
+            market_id: market_id.to_string(),
+            sampled_at,
+            mid,
+            last: Some(0.5),
+            book: mid.map(|mid| OrderBook::new(vec![(mid - 0.01, 10.0)], vec![(mid + 0.01, 10.0)])),
+        };
+        store
+            .record_prices(&[sample("a", 1_000, Some(0.5)), sample("b", 1_000, None), sample("a", 2_000, Some(0.6))])
+            .await
+            .unwrap();
+        // A repeated sample keeps the first
+        store.record_prices(&[sample("a", 1_000, Some(0.9))]).await.unwrap();
+
+        let a = store.prices(Some("a"), TimeRange::all()).await.unwrap();
+        assert_eq!(a, vec![sample("a", 1_000, Some(0.5)), sample("a", 2_000, Some(0.6))]);
+        assert_eq!(store.prices(None, TimeRange::since(1_500)).await.unwrap().len(), 1);
+        assert_eq!(store.delete_prices_before(2_000).await.unwrap(), 2);
+
+        let bar = |opened_at: i64, close: f64| Bar {
+            market_id: "a".to_string(),
+            interval_secs: 60,
+            opened_at,
+            open: 0.5,
+            high: close.max(0.5),
+            low: close.min(0.5),
+            close,
+            volume: 10.0,
+            trades: 2,
+        };
+        store.record_bars(&[bar(60_000, 0.55), bar(120_000, 0.6)]).await.unwrap();
+        // A bar saved again as it fills replaces the earlier one
+        store.record_bars(&[bar(120_000, 0.62)]).await.unwrap();
+        let bars = store.bars("a", 60, TimeRange::all()).await.unwrap();
+        assert_eq!(bars, vec![bar(60_000, 0.55), bar(120_000, 0.62)]);
+        assert!(store.bars("a", 300, TimeRange::all()).await.unwrap().is_empty());
+        assert_eq!(store.delete_bars_before(120_000).await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_migrations_are_idempotent() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        migrate(&conn).unwrap();
+        let version: i64 = conn
+            .query_row("SELECT MAX(version) FROM schema_migrations", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.last().unwrap().0);
+    }
+}
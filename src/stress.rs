@@ -0,0 +1,121 @@
+//! Load and chaos test of the watcher and the copy engine.
+//!
+//! [`run`] serves a [`FeedPlan`] from a [`SyntheticFeed`] to a real
+//! [`WalletWatcher`], and puts every trade it hands over through the
+//! engine without the network: the trade deduper, then prechecks, sizing
+//! and risk against a synthetic market. It passes when every trade the
+//! feed sent came through and none was copied twice, however many frames
+//! were malformed, repeated or cut off. `mybot stress` runs it with the
+//! configured sizing and risk settings; integration tests can run it too.
+
+use crate::bench;
+use crate::dedup::TradeDeduper;
+use crate::synthetic::{self, FeedPlan, FeedStats, SyntheticFeed};
+use crate::types::{Config, Decision};
+use crate::watcher::WalletWatcher;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Quiet this long after the feed has sent everything, the run is over.
+const SETTLE: Duration = Duration::from_secs(1);
+/// Quiet this long before then, the watcher is taken to be stuck.
+const STALL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StressReport {
+    pub feed: FeedStats,
+    /// Trades the watcher handed over, repeats included
+    pub received: usize,
+    /// Repeats the deduper caught
+    pub deduplicated: usize,
+    pub copied: usize,
+    pub skipped: BTreeMap<&'static str, usize>,
+    /// Trades sent but never handed over
+    pub lost: usize,
+    /// Trades copied more than once
+    pub copied_twice: usize,
+    #[serde(serialize_with = "crate::backtest::as_secs")]
+    pub elapsed: Duration,
+    /// Trades handed over per second
+    pub throughput: f64,
+}
+
+impl StressReport {
+    pub fn passed(&self) -> bool {
+        self.lost == 0 && self.copied_twice == 0
+    }
+}
+
+impl std::fmt::Display for StressReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let feed = &self.feed;
+        writeln!(
+            f,
+            "Sent:          {} trades over {} connections ({} repeated, {} malformed frames, {} cut mid-frame)",
+            feed.trades, feed.connections, feed.duplicates, feed.malformed, feed.disconnects
+        )?;
+        writeln!(
+            f,
+            "Received:      {} in {:.2}s ({:.0}/s), {} repeats dropped",
+            self.received,
+            self.elapsed.as_secs_f64(),
+            self.throughput,
+            self.deduplicated
+        )?;
+        writeln!(f, "Copied:        {}", self.copied)?;
+        for (reason, count) in &self.skipped {
+            writeln!(f, "Skipped:       {} ({})", count, reason)?;
+        }
+        writeln!(f, "Lost:          {}", self.lost)?;
+        writeln!(f, "Copied twice:  {}", self.copied_twice)?;
+        writeln!(f, "{}", if self.passed() { "✅ Passed" } else { "❌ Failed" })
+    }
+}
+
+/// Serves `plan` to the watcher and engine until the feed has sent it all
+/// and gone quiet.
+pub async fn run(config: Config, plan: FeedPlan) -> Result<StressReport> {
+    let wallet = "0x57e5500000000000000000000000000000000057".to_string();
+    let bot = bench::engine(config, &wallet).await?;
+    let dedup = TradeDeduper::new(bot.config().dedup_window, None);
+    let market = synthetic::market(&plan);
+    let feed = SyntheticFeed::start(plan).await?;
+    let watcher = WalletWatcher::new(feed.url().to_string(), vec![wallet.clone()]);
+    let rx = watcher.start().await?;
+
+    let mut report = StressReport::default();
+    let mut copies: HashMap<String, usize> = HashMap::new();
+    let mut seen = HashSet::new();
+    let started = Instant::now();
+    let mut last = started;
+    loop {
+        let quiet = if feed.finished() { SETTLE } else { STALL };
+        let Ok(Ok(trade)) = tokio::time::timeout(quiet, rx.recv()).await else {
+            break;
+        };
+        last = Instant::now();
+        report.received += 1;
+        seen.insert(trade.event_id.clone());
+        if !dedup.first_seen(&trade, chrono::Utc::now().timestamp_millis()).await {
+            report.deduplicated += 1;
+            continue;
+        }
+        match bench::decide(&bot, &trade, &market).await {
+            Decision::Copy { .. } => {
+                report.copied += 1;
+                *copies.entry(trade.event_id.clone()).or_default() += 1;
+            }
+            Decision::Skip { reason, .. } => *report.skipped.entry(reason.as_str()).or_default() += 1,
+        }
+    }
+    watcher.remove_wallet(&wallet)?;
+
+    report.feed = feed.stats();
+    report.elapsed = last - started;
+    report.throughput = report.received as f64 / report.elapsed.as_secs_f64().max(f64::EPSILON);
+    report.lost = report.feed.trades.saturating_sub(seen.len());
+    report.copied_twice = copies.values().filter(|&&n| n > 1).count();
+    Ok(report)
+}
@@ -0,0 +1,494 @@
+//! Leader trades read from the Polymarket orderbook subgraph.
+//!
+//! [`SubgraphWatcher`] is the [`TradeSource`] for operators without access
+//! to the wallet trade feed: every `subgraph_poll_interval` it asks the
+//! subgraph at `subgraph_url` (Goldsky's hosted one by default) for the
+//! `orderFilledEvents` a tracked wallet was maker or taker of, and turns
+//! each into the [`Trade`] that wallet made. Trades arrive a block or two
+//! after the feed would send them, and ones made before polling started
+//! aren't sent.
+//!
+//! A fill swaps USDC (asset id `0`) for outcome tokens, both in units of
+//! 10^-6. The side giving USDC bought the token the other side gave.
+
+use crate::api::number;
+use crate::events::{ConnectionState, EventBus};
+use crate::health::FeedStatus;
+use crate::incidents::IncidentMonitor;
+use crate::latency::LatencyStats;
+use crate::logging;
+use crate::markets::MarketCache;
+use crate::notify::{Notification, Notifications};
+use crate::telemetry;
+use crate::types::{Config, Trade, TradeSide};
+use crate::watcher::{Recorders, TradeSource, TRADE_CHANNEL_CAPACITY};
+use anyhow::{Context, Result};
+use async_channel::{bounded, Receiver, Sender};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// Fills asked for per role and poll.
+const PAGE: usize = 1000;
+
+/// Outcome tokens and USDC both have six decimals.
+const UNIT: f64 = 1_000_000.0;
+
+const QUERY: &str = r#"
+query Fills($wallets: [String!]!, $since: BigInt!, $first: Int!) {
+  maker: orderFilledEvents(first: $first, orderBy: timestamp, orderDirection: asc,
+    where: { maker_in: $wallets, timestamp_gte: $since }) { ...fill }
+  taker: orderFilledEvents(first: $first, orderBy: timestamp, orderDirection: asc,
+    where: { taker_in: $wallets, timestamp_gte: $since }) { ...fill }
+}
+fragment fill on OrderFilledEvent {
+  id transactionHash timestamp maker taker
+  makerAssetId takerAssetId makerAmountFilled takerAmountFilled
+}
+"#;
+
+/// Which side of a fill a tracked wallet was on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Maker,
+    Taker,
+}
+
+impl Role {
+    fn field(&self) -> &'static str {
+        match self {
+            Role::Maker => "maker",
+            Role::Taker => "taker",
+        }
+    }
+}
+
+/// The trade `wallet` made as `role` of the `orderFilledEvent` `fill`. Its
+/// `event_id` is the token id until resolved against the market.
+pub fn parse_fill(fill: &Value, role: Role, wallet: &str) -> Option<Trade> {
+    let maker = (fill["makerAssetId"].as_str()?, number(&fill["makerAmountFilled"])?);
+    let taker = (fill["takerAssetId"].as_str()?, number(&fill["takerAmountFilled"])?);
+    let (gave, got) = match role {
+        Role::Maker => (maker, taker),
+        Role::Taker => (taker, maker),
+    };
+    // Paying USDC for tokens is a buy of them, giving tokens for USDC a sale
+    let (side, token, shares, usdc) = match (gave.0, got.0) {
+        ("0", "0") => return None,
+        ("0", token) => (TradeSide::BUY, token, got.1, gave.1),
+        (token, "0") => (TradeSide::SELL, token, gave.1, got.1),
+        _ => return None,
+    };
+    if shares <= 0.0 {
+        return None;
+    }
+    Some(Trade {
+        wallet: wallet.to_string(),
+        event_id: token.to_string(),
+        market_id: token.to_string(),
+        side,
+        shares: shares / UNIT,
+        price: usdc / shares,
+        timestamp: fill["timestamp"].as_str()?.parse().ok()?,
+        tx_hash: fill["transactionHash"].as_str().map(str::to_string),
+    })
+}
+
+/// The trades of one poll.
+struct Polled {
+    /// Oldest first, each keyed by the fill and role it came from
+    trades: Vec<(String, Trade)>,
+    /// The latest second trades are complete up to when a page filled up,
+    /// as it may have more fills of its last second, and later ones
+    complete_to: Option<i64>,
+}
+
+/// The trades of `wallets` in a subgraph response.
+fn parse_response(body: &Value, wallets: &[String]) -> Result<Polled> {
+    if let Some(errors) = body.get("errors") {
+        anyhow::bail!("Subgraph query failed: {}", errors);
+    }
+    let data = body.get("data").context("Subgraph response has no data")?;
+    let mut trades = Vec::new();
+    let mut complete_to: Option<i64> = None;
+    for role in [Role::Maker, Role::Taker] {
+        let fills = data[role.field()].as_array().context("Subgraph response has no fills")?;
+        for fill in fills {
+            let Some(address) = fill[role.field()].as_str() else { continue };
+            let Some(wallet) = wallets.iter().find(|w| w.eq_ignore_ascii_case(address)) else { continue };
+            let Some(id) = fill["id"].as_str() else { continue };
+            if let Some(trade) = parse_fill(fill, role, wallet) {
+                trades.push((format!("{}:{}", id, role.field()), trade));
+            }
+        }
+        if fills.len() >= PAGE {
+            let last = fills.last().and_then(|f| f["timestamp"].as_str()?.parse().ok());
+            complete_to = match (complete_to, last) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+    }
+    trades.sort_by_key(|(_, trade)| trade.timestamp);
+    Ok(Polled { trades, complete_to })
+}
+
+pub struct SubgraphWatcher {
+    client: reqwest::Client,
+    url: String,
+    interval: Duration,
+    wallets: Vec<String>,
+    markets: Option<Arc<MarketCache>>,
+    recorders: Recorders,
+    running: Mutex<Option<Running>>,
+}
+
+/// Where polled trades go once started, the wallets polled for, and the
+/// polling task.
+struct Running {
+    tx: Sender<Trade>,
+    wallets: Arc<Mutex<Vec<String>>>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// What one polling task needs.
+#[derive(Clone)]
+struct Poller {
+    client: reqwest::Client,
+    url: String,
+    markets: Option<Arc<MarketCache>>,
+    recorders: Recorders,
+}
+
+impl SubgraphWatcher {
+    pub fn new(url: String, interval: Duration, wallets: Vec<String>) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap_or_default(),
+            url,
+            interval,
+            wallets,
+            markets: None,
+            recorders: Recorders::default(),
+            running: Mutex::new(None),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.subgraph_url.clone(),
+            config.subgraph_poll_interval,
+            config.wallets_to_track.clone(),
+        )
+    }
+
+    /// Fills in each trade's event from `markets`, which the subgraph
+    /// doesn't know; without it trades are grouped by token instead.
+    pub fn with_markets(mut self, markets: Arc<MarketCache>) -> Self {
+        self.markets = Some(markets);
+        self
+    }
+
+    /// Publishes the subgraph becoming reachable and unreachable on `events`.
+    pub fn with_events(mut self, events: Arc<EventBus>) -> Self {
+        self.recorders.events = Some(events);
+        self
+    }
+
+    /// Tells the operator when the subgraph becomes reachable and unreachable.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.recorders.notifications = notifications;
+        self
+    }
+
+    /// Sends a critical alert when polls keep failing for `after`.
+    pub fn with_outage_alert(mut self, after: Duration) -> Self {
+        self.recorders.outage_alert = after;
+        self
+    }
+
+    /// Counts arriving trades, so a trade loop that stops keeping up opens
+    /// an incident.
+    pub fn with_incidents(mut self, incidents: Arc<IncidentMonitor>) -> Self {
+        self.recorders.incidents = Some(incidents);
+        self
+    }
+
+    /// Keeps each wallet's polling state for the readiness check.
+    pub fn with_feed_status(mut self, feeds: Arc<FeedStatus>) -> Self {
+        self.recorders.feeds = Some(feeds);
+        self
+    }
+
+    /// Records how long after the leader's trade each one arrives.
+    pub fn with_latency(mut self, latency: Arc<LatencyStats>) -> Self {
+        self.recorders.latency = Some(latency);
+        self
+    }
+
+    fn poller(&self) -> Poller {
+        Poller {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            markets: self.markets.clone(),
+            recorders: self.recorders.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl TradeSource for SubgraphWatcher {
+    async fn start(&self) -> Result<Receiver<Trade>> {
+        let (tx, rx) = bounded(TRADE_CHANNEL_CAPACITY);
+        let wallets = Arc::new(Mutex::new(self.wallets.clone()));
+        let task = tokio::spawn(self.poller().run(self.interval, Arc::clone(&wallets), tx.clone()));
+        *self.running.lock().unwrap() = Some(Running { tx, wallets, task });
+        Ok(rx)
+    }
+
+    fn queue_depth(&self) -> Option<usize> {
+        self.running.lock().unwrap().as_ref().map(|running| running.tx.len())
+    }
+
+    fn wallets(&self) -> Vec<String> {
+        match &*self.running.lock().unwrap() {
+            Some(running) => running.wallets.lock().unwrap().clone(),
+            None => self.wallets.clone(),
+        }
+    }
+
+    fn add_wallet(&self, wallet: &str) -> Result<bool> {
+        let running = self.running.lock().unwrap();
+        let running = running.as_ref().context("Watchers are not running")?;
+        let mut wallets = running.wallets.lock().unwrap();
+        if wallets.iter().any(|w| w.eq_ignore_ascii_case(wallet)) {
+            return Ok(false);
+        }
+        if let Some(feeds) = &self.recorders.feeds {
+            let now = chrono::Utc::now().timestamp_millis();
+            feeds.update(wallet, ConnectionState::Disconnected, Some("not polled yet".to_string()), now);
+        }
+        wallets.push(wallet.to_string());
+        Ok(true)
+    }
+
+    fn remove_wallet(&self, wallet: &str) -> Result<bool> {
+        let running = self.running.lock().unwrap();
+        let running = running.as_ref().context("Watchers are not running")?;
+        let mut wallets = running.wallets.lock().unwrap();
+        let Some(index) = wallets.iter().position(|w| w.eq_ignore_ascii_case(wallet)) else {
+            return Ok(false);
+        };
+        let removed = wallets.remove(index);
+        if let Some(feeds) = &self.recorders.feeds {
+            feeds.remove(&removed);
+        }
+        Ok(true)
+    }
+}
+
+impl Poller {
+    async fn run(self, interval: Duration, wallets: Arc<Mutex<Vec<String>>>, tx: Sender<Trade>) {
+        // Polls overlap by a second, as more fills of it may still be indexed
+        let mut since = chrono::Utc::now().timestamp();
+        let mut seen: HashSet<String> = HashSet::new();
+        // Wallets reported connected since the last failed poll
+        let mut connected: HashSet<String> = HashSet::new();
+        // First failed poll of the current outage
+        let mut down_since: Option<Instant> = None;
+        let mut alerted = false;
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let wallets = wallets.lock().unwrap().clone();
+            if wallets.is_empty() {
+                continue;
+            }
+            let Polled { trades, complete_to } = match self.poll(&wallets, since).await {
+                Ok(polled) => polled,
+                Err(e) => {
+                    tracing::warn!("Failed to poll subgraph for trades: {:#}", e);
+                    // Reported once an outage, not every poll
+                    if down_since.is_none() {
+                        for wallet in &wallets {
+                            self.recorders.connection(wallet, ConnectionState::Disconnected, Some(e.to_string()));
+                        }
+                    }
+                    connected.clear();
+                    let down_for = down_since.get_or_insert_with(Instant::now).elapsed();
+                    let outage_alert = self.recorders.outage_alert;
+                    if !alerted && !outage_alert.is_zero() && down_for >= outage_alert {
+                        alerted = true;
+                        self.recorders.notifications.send(Notification::FeedDown {
+                            wallet: wallets.join(","),
+                            down_secs: down_for.as_secs(),
+                            detail: Some(e.to_string()),
+                        });
+                    }
+                    continue;
+                }
+            };
+            down_since = None;
+            alerted = false;
+            for wallet in &wallets {
+                if connected.insert(wallet.clone()) {
+                    self.recorders.connection(wallet, ConnectionState::Connected, None);
+                }
+            }
+
+            let received_ns = telemetry::now_ns();
+            for (key, mut trade) in trades {
+                if trade.timestamp < since || complete_to.is_some_and(|to| trade.timestamp > to) {
+                    continue;
+                }
+                if trade.timestamp > since {
+                    since = trade.timestamp;
+                    seen.clear();
+                }
+                if !seen.insert(key) {
+                    continue;
+                }
+                if let Some(markets) = &self.markets {
+                    if let Ok(market) = markets.get(&trade.market_id).await {
+                        trade.event_id = market.event_id;
+                    }
+                }
+                let span = tracing::info_span!(
+                    telemetry::ROOT_SPAN,
+                    cid = %logging::correlation_id(&trade),
+                    wallet = %trade.wallet,
+                    market = %trade.market_id,
+                    otel.start_ns = received_ns,
+                );
+                tracing::info_span!(parent: &span, "parse", otel.start_ns = received_ns)
+                    .in_scope(|| tracing::info!("📥 Leader trade received"));
+                self.recorders.trade(&trade, received_ns);
+                if tx.send(trade).instrument(span).await.is_err() {
+                    return;
+                }
+            }
+            if let Some(to) = complete_to {
+                // A page filled up: carry on past it next poll
+                if to > since {
+                    since = to;
+                    seen.clear();
+                }
+            }
+        }
+    }
+
+    async fn poll(&self, wallets: &[String], since: i64) -> Result<Polled> {
+        let addresses: Vec<String> = wallets.iter().map(|w| w.to_lowercase()).collect();
+        let body: Value = self
+            .client
+            .post(&self.url)
+            .json(&json!({
+                "query": QUERY,
+                "variables": { "wallets": addresses, "since": since.to_string(), "first": PAGE },
+            }))
+            .send()
+            .await
+            .context("Failed to reach subgraph")?
+            .error_for_status()
+            .context("Subgraph returned an error")?
+            .json()
+            .await
+            .context("Failed to parse subgraph response")?;
+        parse_response(&body, wallets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(id: &str, maker: &str, taker: &str, maker_asset: &str, taker_asset: &str, amounts: (u64, u64), ts: i64) -> Value {
+        json!({
+            "id": id,
+            "transactionHash": format!("0x{}", id),
+            "timestamp": ts.to_string(),
+            "maker": maker,
+            "taker": taker,
+            "makerAssetId": maker_asset,
+            "takerAssetId": taker_asset,
+            "makerAmountFilled": amounts.0.to_string(),
+            "takerAmountFilled": amounts.1.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_wallets_are_removed_whatever_their_case() {
+        let watcher = SubgraphWatcher::new(
+            "http://127.0.0.1:9".to_string(),
+            Duration::from_secs(3600),
+            vec!["0xAbCd".to_string(), "0xother".to_string()],
+        );
+        let _rx = watcher.start().await.unwrap();
+        assert!(watcher.remove_wallet("0xabcd").unwrap());
+        assert_eq!(watcher.wallets(), ["0xother"]);
+        assert!(!watcher.remove_wallet("0xABCD").unwrap());
+    }
+
+    #[test]
+    fn test_fills_become_the_wallets_trades() {
+        // Maker pays 6.5 USDC for 10 shares of token 123
+        let buy = fill("a", "0xmaker", "0xtaker", "0", "123", (6_500_000, 10_000_000), 1_700_000_000);
+        let trade = parse_fill(&buy, Role::Maker, "0xMaker").unwrap();
+        assert_eq!(trade.side, TradeSide::BUY);
+        assert_eq!(trade.market_id, "123");
+        assert_eq!(trade.wallet, "0xMaker");
+        assert!((trade.shares - 10.0).abs() < 1e-9);
+        assert!((trade.price - 0.65).abs() < 1e-9);
+        assert_eq!(trade.timestamp, 1_700_000_000);
+        assert_eq!(trade.tx_hash.as_deref(), Some("0xa"));
+
+        // The taker of the same fill sold them
+        let sale = parse_fill(&buy, Role::Taker, "0xtaker").unwrap();
+        assert_eq!(sale.side, TradeSide::SELL);
+        assert!((sale.shares - 10.0).abs() < 1e-9);
+        assert!((sale.price - 0.65).abs() < 1e-9);
+
+        // Token for token (a merge or split leg) isn't a trade
+        let swap = fill("b", "0xmaker", "0xtaker", "123", "456", (1, 1), 0);
+        assert!(parse_fill(&swap, Role::Maker, "0xmaker").is_none());
+    }
+
+    #[test]
+    fn test_response_is_sorted_and_limited_by_full_pages() {
+        let wallets = vec!["0xLeader".to_string()];
+        let body = json!({ "data": {
+            "maker": [fill("m", "0xleader", "0xother", "0", "1", (500_000, 1_000_000), 20)],
+            "taker": [
+                fill("t", "0xother", "0xleader", "0", "1", (400_000, 1_000_000), 10),
+                fill("x", "0xother", "0xsomeone", "0", "1", (400_000, 1_000_000), 11),
+            ],
+        }});
+        let Polled { trades, complete_to } = parse_response(&body, &wallets).unwrap();
+        assert_eq!(complete_to, None);
+        let keys: Vec<&str> = trades.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["t:taker", "m:maker"]);
+        assert_eq!(trades[0].1.side, TradeSide::SELL);
+        assert_eq!(trades[1].1.wallet, "0xLeader");
+
+        let page: Vec<Value> = (0..PAGE as i64)
+            .map(|i| fill(&i.to_string(), "0xleader", "0xother", "0", "1", (1, 1), 100 + i / 10))
+            .collect();
+        let body = json!({ "data": { "maker": page, "taker": [] } });
+        let complete_to = parse_response(&body, &wallets).unwrap().complete_to;
+        assert_eq!(complete_to, Some(100 + (PAGE as i64 - 1) / 10));
+
+        assert!(parse_response(&json!({ "errors": [{ "message": "bad" }] }), &wallets).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+//! `mybot sweep`: tuning copy parameters on history without fitting them to
+//! it.
+//!
+//! Every combination of a [`Grid`] is backtested walk-forward: the history
+//! is cut into `folds + 1` equal stretches of time, and for each fold the
+//! combination with the best PnL over all earlier stretches (in sample) is
+//! backtested on the next one (out of sample). Only out-of-sample results
+//! say how tuning would have done; next to each combination's in-sample
+//! PnL they also show how much of that was fit to noise.
+//!
+//! The latency budget only matters against the time trades were seen, so
+//! it needs journaled trades or a fill model with latency.
+
+use crate::backtest::{self, as_secs, BacktestReport, History};
+use crate::builder::BotBuilder;
+use crate::clock::SimClock;
+use crate::fills::FillModel;
+use crate::types::{Config, SizingMode};
+use crate::units::Ratio;
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The values to try for each parameter.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    /// Proportional copy ratios; empty keeps the configured sizing
+    pub ratios: Vec<f64>,
+    pub slippages: Vec<f64>,
+    pub latency_budgets: Vec<Duration>,
+    /// `(min_price, max_price)`
+    pub price_bands: Vec<(f64, f64)>,
+}
+
+impl Grid {
+    /// Every parameter at its configured value.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            ratios: vec![],
+            slippages: vec![config.max_slippage.as_f64()],
+            latency_budgets: vec![config.latency_budget],
+            price_bands: vec![(config.min_price, config.max_price)],
+        }
+    }
+
+    /// Every combination, the ratio varying slowest.
+    pub fn params(&self) -> Vec<Params> {
+        let ratios: Vec<Option<f64>> = match self.ratios.is_empty() {
+            true => vec![None],
+            false => self.ratios.iter().copied().map(Some).collect(),
+        };
+        let mut params = Vec::new();
+        for &copy_ratio in &ratios {
+            for &max_slippage in &self.slippages {
+                for &latency_budget in &self.latency_budgets {
+                    for &(min_price, max_price) in &self.price_bands {
+                        params.push(Params {
+                            copy_ratio,
+                            max_slippage,
+                            latency_budget,
+                            min_price,
+                            max_price,
+                        });
+                    }
+                }
+            }
+        }
+        params
+    }
+}
+
+/// One combination of swept parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Params {
+    /// Proportional sizing at this ratio; the configured sizing when not set
+    pub copy_ratio: Option<f64>,
+    pub max_slippage: f64,
+    #[serde(serialize_with = "as_secs")]
+    pub latency_budget: Duration,
+    pub min_price: f64,
+    pub max_price: f64,
+}
+
+impl Params {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(ratio) = self.copy_ratio {
+            config.sizing_mode = SizingMode::Proportional;
+            config.proportional_ratio = Ratio(ratio);
+        }
+        config.max_slippage = Ratio(self.max_slippage);
+        config.latency_budget = self.latency_budget;
+        config.min_price = self.min_price;
+        config.max_price = self.max_price;
+    }
+}
+
+impl std::fmt::Display for Params {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ratio = match self.copy_ratio {
+            Some(ratio) => format!("{:.2}%", ratio * 100.0),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "{:>7} {:>7.2}% {:>7}s {:>4.2}-{:<4.2}",
+            ratio,
+            self.max_slippage * 100.0,
+            self.latency_budget.as_secs_f64(),
+            self.min_price,
+            self.max_price
+        )
+    }
+}
+
+/// The figures of one backtest that matter for comparing them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Outcome {
+    pub trades: usize,
+    pub copied: usize,
+    pub pnl: f64,
+    pub max_drawdown: f64,
+    pub hit_rate: Option<f64>,
+}
+
+impl From<&BacktestReport> for Outcome {
+    fn from(report: &BacktestReport) -> Self {
+        Self {
+            trades: report.trades,
+            copied: report.copied,
+            pnl: report.pnl(),
+            max_drawdown: report.max_drawdown,
+            hit_rate: report.hit_rate(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Fold {
+    /// Unix seconds; training runs from the start of the history
+    pub train_from: i64,
+    pub test_from: i64,
+    pub test_to: i64,
+    /// The combination with the best in-sample PnL
+    pub chosen: Params,
+    pub in_sample_pnl: f64,
+    pub out_of_sample: Outcome,
+}
+
+/// A combination's results summed over the folds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub params: Params,
+    pub in_sample_pnl: f64,
+    pub out_of_sample_pnl: f64,
+    /// Folds it was chosen for
+    pub chosen: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepReport {
+    pub folds: Vec<Fold>,
+    /// Best out of sample first
+    pub candidates: Vec<Candidate>,
+}
+
+impl SweepReport {
+    /// What tuning before each fold made over the folds.
+    pub fn out_of_sample_pnl(&self) -> f64 {
+        self.folds.iter().map(|f| f.out_of_sample.pnl).sum()
+    }
+}
+
+impl std::fmt::Display for SweepReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let date = |ts: i64| {
+            chrono::DateTime::from_timestamp(ts, 0)
+                .map(|t| t.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let params = format!("{:>7} {:>8} {:>8} {:<9}", "ratio", "slippage", "budget", "prices");
+        writeln!(
+            f,
+            "Walk-forward over {} folds, {} combinations",
+            self.folds.len(),
+            self.candidates.len()
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<4} {:<10} {:<10} {} {:>12} {:>12} {:>6} {:>10} {:>6}",
+            "fold", "test from", "test to", params, "in-sample", "out-sample", "copied", "drawdown", "hit"
+        )?;
+        for (i, fold) in self.folds.iter().enumerate() {
+            let hit = match fold.out_of_sample.hit_rate {
+                Some(rate) => format!("{:.0}%", rate * 100.0),
+                None => "-".to_string(),
+            };
+            writeln!(
+                f,
+                "{:<4} {:<10} {:<10} {} {:>+12.2} {:>+12.2} {:>6} {:>10.2} {:>6}",
+                i + 1,
+                date(fold.test_from),
+                date(fold.test_to),
+                fold.chosen,
+                fold.in_sample_pnl,
+                fold.out_of_sample.pnl,
+                fold.out_of_sample.copied,
+                fold.out_of_sample.max_drawdown,
+                hit
+            )?;
+        }
+        writeln!(f, "Out of sample: ${:+.2}", self.out_of_sample_pnl())?;
+        writeln!(f)?;
+        writeln!(f, "{} {:>12} {:>12} {:>6}", params, "in-sample", "out-sample", "chosen")?;
+        for candidate in &self.candidates {
+            writeln!(
+                f,
+                "{} {:>+12.2} {:>+12.2} {:>6}",
+                candidate.params, candidate.in_sample_pnl, candidate.out_of_sample_pnl, candidate.chosen
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Backtests `history` with `params` applied to `base`, on a fresh bot.
+async fn evaluate(
+    base: &Config,
+    params: &Params,
+    history: &History,
+    starting_balance: f64,
+    fills: &dyn FillModel,
+    fee_rate: f64,
+) -> Result<BacktestReport> {
+    let mut config = base.clone();
+    params.apply(&mut config);
+    let clock = SimClock::default();
+    let bot = BotBuilder::from_config(config)
+        .clock(Arc::new(clock.clone()))
+        .build()
+        .await?;
+    Ok(backtest::run(&bot, &clock, history, starting_balance, fills, fee_rate).await)
+}
+
+/// Sweeps `grid` over `history` walk-forward. `base` must already be isolated
+/// like a backtest's: no journal, no event log, no notifiers, paper trading.
+pub async fn run(
+    base: &Config,
+    history: &History,
+    grid: &Grid,
+    folds: usize,
+    starting_balance: f64,
+    fills: &dyn FillModel,
+    fee_rate: f64,
+) -> Result<SweepReport> {
+    if folds == 0 {
+        anyhow::bail!("A walk-forward sweep needs at least one fold");
+    }
+    let (Some(first), Some(last)) = (history.trades.first(), history.trades.last()) else {
+        anyhow::bail!("No leader trades to sweep over");
+    };
+    let params = grid.params();
+    if params.is_empty() {
+        anyhow::bail!("Nothing to sweep: every parameter needs at least one value");
+    }
+
+    let (start, end) = (first.timestamp, last.timestamp + 1);
+    let stretch = ((end - start) / (folds as i64 + 1)).max(1);
+    let bound = |i: usize| match i {
+        i if i > folds => end,
+        i => start + stretch * i as i64,
+    };
+    let mut candidates: Vec<Candidate> = params
+        .iter()
+        .map(|&params| Candidate {
+            params,
+            in_sample_pnl: 0.0,
+            out_of_sample_pnl: 0.0,
+            chosen: 0,
+        })
+        .collect();
+    let mut report = SweepReport {
+        folds: Vec::new(),
+        candidates: Vec::new(),
+    };
+
+    for fold in 1..=folds {
+        let (test_from, test_to) = (bound(fold), bound(fold + 1));
+        let train = history.between(start, test_from);
+        let test = history.between(test_from, test_to);
+        tracing::info!(
+            "🔁 Fold {}/{}: {} trades in sample, {} out of sample",
+            fold,
+            folds,
+            train.trades.len(),
+            test.trades.len()
+        );
+
+        // The first of equally good combinations is chosen
+        let mut best: Option<(f64, Outcome, usize)> = None;
+        for (i, candidate) in candidates.iter_mut().enumerate() {
+            let in_sample = evaluate(base, &candidate.params, &train, starting_balance, fills, fee_rate).await?;
+            let out_of_sample = evaluate(base, &candidate.params, &test, starting_balance, fills, fee_rate).await?;
+            candidate.in_sample_pnl += in_sample.pnl();
+            candidate.out_of_sample_pnl += out_of_sample.pnl();
+            if best.as_ref().is_none_or(|(pnl, ..)| in_sample.pnl() > pnl + 1e-9) {
+                best = Some((in_sample.pnl(), Outcome::from(&out_of_sample), i));
+            }
+        }
+        let (in_sample_pnl, out_of_sample, chosen) = best.expect("params is not empty");
+        candidates[chosen].chosen += 1;
+        report.folds.push(Fold {
+            train_from: start,
+            test_from,
+            test_to,
+            chosen: candidates[chosen].params,
+            in_sample_pnl,
+            out_of_sample,
+        });
+    }
+
+    candidates.sort_by(|a, b| b.out_of_sample_pnl.total_cmp(&a.out_of_sample_pnl));
+    report.candidates = candidates;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::UsdcAmount;
+    use crate::builder::{RiskSettings, SizingSettings};
+    use crate::fills::Immediate;
+    use crate::types::{Market, Trade, TradeSide};
+
+    fn trade(market_id: &str, side: TradeSide, price: f64, timestamp: i64) -> Trade {
+        Trade {
+            wallet: "0xleader".to_string(),
+            event_id: market_id.to_string(),
+            market_id: market_id.to_string(),
+            side,
+            shares: 100.0,
+            price,
+            timestamp,
+            tx_hash: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_walk_forward_picks_in_sample_and_scores_out_of_sample() {
+        let base = BotBuilder::new()
+            .account("0xme", "a".repeat(64))
+            .watch_wallet("0xleader")
+            .with_sizing(SizingSettings {
+                mode: SizingMode::Fixed,
+                fixed_stake: UsdcAmount(10.0),
+                min_stake: UsdcAmount(1.0),
+                max_stake: UsdcAmount(10.0),
+                ..Default::default()
+            })
+            .with_risk(RiskSettings {
+                max_daily_volume: UsdcAmount(10_000.0),
+                max_exposure_per_event: UsdcAmount(10_000.0),
+                min_liquidity: 0.0,
+                cb_min_depth_usd: 0.0,
+                ..Default::default()
+            })
+            .config()
+            .clone();
+        // Long shots pay in the first half and lose in the second, so the
+        // band that includes them wins in sample and loses out of sample
+        let mut trades = Vec::new();
+        for (i, (price, exit)) in [(0.10, 0.30), (0.50, 0.55), (0.10, 0.02), (0.50, 0.55)]
+            .into_iter()
+            .enumerate()
+        {
+            let market = format!("m{}", i);
+            let at = 1_700_000_000 + i as i64 * 1_000;
+            trades.push(trade(&market, TradeSide::BUY, price, at));
+            trades.push(trade(&market, TradeSide::SELL, exit, at + 10));
+        }
+        let markets = (0..4)
+            .map(|i| {
+                let id = format!("m{}", i);
+                let market = Market {
+                    id: id.clone(),
+                    event_id: id.clone(),
+                    question: "Will it?".to_string(),
+                    yes_price: 0.5,
+                    no_price: 0.5,
+                    liquidity: 50_000.0,
+                    ..Default::default()
+                };
+                (id, market)
+            })
+            .collect();
+        let history = History {
+            trades,
+            markets,
+            ..Default::default()
+        };
+        let grid = Grid {
+            price_bands: vec![(0.0, 1.0), (0.2, 0.8)],
+            ..Grid::from_config(&base)
+        };
+
+        let report = run(&base, &history, &grid, 1, 100.0, &Immediate, 0.0).await.unwrap();
+        let fold = &report.folds[0];
+        assert_eq!((fold.chosen.min_price, fold.chosen.max_price), (0.0, 1.0));
+        // In sample: +$20 on the long shot, +$1 on the other; out of sample
+        // -$8 and +$1
+        assert!((fold.in_sample_pnl - 21.0).abs() < 1e-9);
+        assert!((fold.out_of_sample.pnl + 7.0).abs() < 1e-9);
+        assert_eq!(report.candidates[0].params.min_price, 0.2);
+        assert!((report.candidates[0].out_of_sample_pnl - 1.0).abs() < 1e-9);
+        assert_eq!(report.candidates[0].chosen, 0);
+    }
+}
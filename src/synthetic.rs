@@ -0,0 +1,375 @@
+//! A synthetic leader-trade feed served over a local WebSocket.
+//!
+//! [`SyntheticFeed`] listens on a free localhost port and speaks the trade
+//! feed's protocol: once a client subscribes to a wallet, it sends that
+//! wallet's trades as `trade` frames at the plan's pace, then holds the
+//! connection open. The time each trade was sent is kept by event id, so a
+//! consumer can time the trade from the wire to wherever it got.
+//!
+//! For load and chaos testing the plan can also send trades in bursts, mix
+//! in malformed frames, send some trades twice, and cut the connection in
+//! the middle of a frame every so many trades. A wallet's trades carry on
+//! from where they were cut when it subscribes again, and the frame that
+//! was cut is sent again in full. The chaos is drawn from `seed`, so a plan
+//! sends the same stream every time; [`FeedStats`] counts what was sent.
+
+use crate::types::Market;
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// What the feed sends each subscriber.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedPlan {
+    pub trades: usize,
+    /// Trades sent back to back before each pause
+    pub burst: usize,
+    /// Between bursts; zero sends everything back to back
+    pub interval: Duration,
+    pub market_id: String,
+    pub price: f64,
+    pub shares: f64,
+    /// Share of trades preceded by a malformed frame
+    pub malformed: f64,
+    /// Share of trades sent a second time, after the next one
+    pub duplicates: f64,
+    /// Cut the connection mid-frame after this many trades; zero never
+    pub disconnect_every: usize,
+    pub seed: u64,
+}
+
+impl Default for FeedPlan {
+    fn default() -> Self {
+        Self {
+            trades: 1_000,
+            burst: 1,
+            interval: Duration::from_millis(5),
+            market_id: "synthetic-market".to_string(),
+            price: 0.5,
+            shares: 100.0,
+            malformed: 0.0,
+            duplicates: 0.0,
+            disconnect_every: 0,
+            seed: 0,
+        }
+    }
+}
+
+/// What the feed has sent, over every connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct FeedStats {
+    pub connections: usize,
+    /// Trades sent in full, each counted once
+    pub trades: usize,
+    pub duplicates: usize,
+    pub malformed: usize,
+    pub disconnects: usize,
+}
+
+/// A wallet's progress through the plan, kept across its connections.
+struct Stream {
+    next: usize,
+    rng: StdRng,
+    /// The last trade's frame, for sending it again
+    last: Option<String>,
+}
+
+#[derive(Default)]
+struct FeedState {
+    sent: HashMap<String, Instant>,
+    streams: HashMap<String, Stream>,
+    stats: FeedStats,
+}
+
+type Shared = Arc<Mutex<FeedState>>;
+
+pub struct SyntheticFeed {
+    url: String,
+    trades: usize,
+    state: Shared,
+    server: JoinHandle<()>,
+}
+
+impl SyntheticFeed {
+    /// Starts serving `plan` on a free localhost port.
+    pub async fn start(plan: FeedPlan) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to bind the synthetic feed")?;
+        let url = format!("ws://{}", listener.local_addr()?);
+        let state = Shared::default();
+        let trades = plan.trades;
+        let plan = Arc::new(plan);
+        let server = {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let (plan, state) = (Arc::clone(&plan), Arc::clone(&state));
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(stream, &plan, &state).await {
+                            tracing::debug!("Synthetic feed connection ended: {}", e);
+                        }
+                    });
+                }
+            })
+        };
+        Ok(Self {
+            url,
+            trades,
+            state,
+            server,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// When the trade with `event_id` went out (first, if it went twice).
+    pub fn sent_at(&self, event_id: &str) -> Option<Instant> {
+        self.state.lock().unwrap().sent.get(event_id).copied()
+    }
+
+    pub fn stats(&self) -> FeedStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// Whether every subscribed wallet has been sent all its trades.
+    pub fn finished(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.streams.is_empty() && state.streams.values().all(|s| s.next >= self.trades)
+    }
+}
+
+impl Drop for SyntheticFeed {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// The `n`th synthetic trade for `wallet`, as the feed frames it.
+pub fn trade_frame(plan: &FeedPlan, wallet: &str, n: usize) -> String {
+    json!({
+        "type": "trade",
+        "data": {
+            "event_id": event_id(wallet, n),
+            "market_id": plan.market_id,
+            "side": if n.is_multiple_of(2) { "BUY" } else { "SELL" },
+            "shares": plan.shares,
+            "price": plan.price,
+            "timestamp": chrono::Utc::now().timestamp(),
+            "tx_hash": format!("0x{:064x}", n),
+        },
+    })
+    .to_string()
+}
+
+/// The market every synthetic trade is in, deep enough for any risk check.
+pub fn market(plan: &FeedPlan) -> Market {
+    Market {
+        id: plan.market_id.clone(),
+        event_id: "synthetic-event".to_string(),
+        question: "Synthetic market".to_string(),
+        yes_price: plan.price,
+        no_price: 1.0 - plan.price,
+        liquidity: 1e9,
+        volume_24h: 1e9,
+        ..Default::default()
+    }
+}
+
+pub fn event_id(wallet: &str, n: usize) -> String {
+    format!("synthetic-{}-{}", &wallet[..10.min(wallet.len())], n)
+}
+
+/// A frame the watcher has to survive: not JSON, cut short, missing a
+/// field, or of a type it doesn't know.
+fn malformed_frame(plan: &FeedPlan, wallet: &str, rng: &mut StdRng) -> String {
+    match rng.gen_range(0..4) {
+        0 => "not json at all {".to_string(),
+        1 => {
+            let frame = trade_frame(plan, wallet, usize::MAX);
+            frame[..frame.len() / 2].to_string()
+        }
+        2 => {
+            json!({ "type": "trade", "data": { "event_id": "synthetic-incomplete", "price": plan.price } }).to_string()
+        }
+        _ => json!({ "type": "synthetic-unknown", "data": [] }).to_string(),
+    }
+}
+
+async fn serve(stream: TcpStream, plan: &FeedPlan, state: &Shared) -> Result<()> {
+    let mut ws = tokio_tungstenite::accept_async(stream).await?;
+    let wallet = loop {
+        match ws.next().await.context("Closed before subscribing")?? {
+            Message::Text(text) => {
+                let subscribe: serde_json::Value = serde_json::from_str(&text)?;
+                if let Some(wallet) = subscribe["wallet"].as_str() {
+                    break wallet.to_string();
+                }
+            }
+            Message::Close(_) => anyhow::bail!("Closed before subscribing"),
+            _ => {}
+        }
+    };
+    ws.send(Message::Text(json!({ "type": "subscribed" }).to_string()))
+        .await?;
+    state.lock().unwrap().stats.connections += 1;
+
+    let mut since_connect = 0;
+    loop {
+        // Decide what to send with the lock held, send without it
+        let (frames, cut) = {
+            let mut state = state.lock().unwrap();
+            let state = &mut *state;
+            let stream = state.streams.entry(wallet.clone()).or_insert_with(|| Stream {
+                next: 0,
+                rng: StdRng::seed_from_u64(plan.seed),
+                last: None,
+            });
+            if stream.next >= plan.trades {
+                break;
+            }
+            let mut frames = Vec::new();
+            if stream.rng.gen_bool(plan.malformed.clamp(0.0, 1.0)) {
+                frames.push(malformed_frame(plan, &wallet, &mut stream.rng));
+                state.stats.malformed += 1;
+            }
+            let frame = trade_frame(plan, &wallet, stream.next);
+            if plan.disconnect_every > 0 && since_connect == plan.disconnect_every {
+                // This trade goes again in full on the next connection
+                state.stats.disconnects += 1;
+                (frames, Some(frame))
+            } else {
+                let duplicate = stream
+                    .last
+                    .take()
+                    .filter(|_| stream.rng.gen_bool(plan.duplicates.clamp(0.0, 1.0)));
+                frames.push(frame.clone());
+                if let Some(duplicate) = duplicate {
+                    frames.push(duplicate);
+                    state.stats.duplicates += 1;
+                }
+                state
+                    .sent
+                    .entry(event_id(&wallet, stream.next))
+                    .or_insert_with(Instant::now);
+                state.stats.trades += 1;
+                stream.last = Some(frame);
+                stream.next += 1;
+                (frames, None)
+            }
+        };
+        for frame in frames {
+            ws.send(Message::Text(frame)).await?;
+        }
+        if let Some(frame) = cut {
+            return cut_mid_frame(ws, &frame).await;
+        }
+        since_connect += 1;
+        if !plan.interval.is_zero() && since_connect % plan.burst.max(1) == 0 {
+            tokio::time::sleep(plan.interval).await;
+        }
+    }
+    // Stay connected so the watcher doesn't resubscribe
+    while let Some(message) = ws.next().await {
+        if let Message::Ping(data) = message? {
+            ws.send(Message::Pong(data)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the header and first half of `frame` as a text frame, then ends
+/// the stream without a close frame. The socket is shut down rather than
+/// dropped, and the client's pings drained, so the reset of an unread
+/// socket doesn't take frames already sent with it.
+async fn cut_mid_frame(mut ws: WebSocketStream<TcpStream>, frame: &str) -> Result<()> {
+    let payload = frame.as_bytes();
+    let mut bytes = vec![0x81, 126];
+    bytes.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&payload[..payload.len() / 2]);
+    let tcp = ws.get_mut();
+    tcp.write_all(&bytes).await?;
+    tcp.shutdown().await?;
+    let mut sink = [0u8; 1024];
+    let _ = tokio::time::timeout(Duration::from_secs(5), async {
+        while matches!(tcp.read(&mut sink).await, Ok(n) if n > 0) {}
+    })
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::connect_async;
+
+    /// Subscribes once and reads text frames until the feed goes quiet.
+    async fn read_connection(url: &str) -> Vec<String> {
+        let (mut ws, _) = connect_async(url).await.unwrap();
+        ws.send(Message::Text(json!({ "wallet": "0xsynthetic" }).to_string()))
+            .await
+            .unwrap();
+        let mut frames = Vec::new();
+        while let Ok(Some(Ok(Message::Text(text)))) = tokio::time::timeout(Duration::from_millis(300), ws.next()).await
+        {
+            frames.push(text);
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_chaos_resumes_after_a_cut_and_repeats_by_seed() {
+        let plan = FeedPlan {
+            trades: 30,
+            interval: Duration::ZERO,
+            malformed: 0.3,
+            duplicates: 0.3,
+            disconnect_every: 20,
+            seed: 9,
+            ..Default::default()
+        };
+        let trade_ids = |frames: &[String]| -> Vec<String> {
+            frames
+                .iter()
+                .filter_map(|f| serde_json::from_str::<serde_json::Value>(f).ok())
+                .filter_map(|v| v["data"]["tx_hash"].as_str().map(str::to_string))
+                .collect()
+        };
+
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let feed = SyntheticFeed::start(plan.clone()).await.unwrap();
+            let first = read_connection(feed.url()).await;
+            assert!(!feed.finished());
+            let second = read_connection(feed.url()).await;
+            assert!(feed.finished());
+
+            let stats = feed.stats();
+            assert_eq!((stats.connections, stats.trades, stats.disconnects), (2, 30, 1));
+            let ids = [trade_ids(&first), trade_ids(&second)].concat();
+            assert_eq!(ids.len(), 30 + stats.duplicates);
+            let mut unique = ids.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), 30);
+            // The trade being sent when the connection was cut comes first on the next
+            assert_eq!(trade_ids(&second)[0], format!("0x{:064x}", 20));
+            assert!(stats.malformed > 0 && stats.duplicates > 0);
+            runs.push(stats);
+        }
+        assert_eq!(runs[0], runs[1]);
+    }
+}
@@ -0,0 +1,323 @@
+//! `mybot tail`: the bot's decisions, fills and feed connections as they
+//! happen, like `kubectl logs` for trades.
+//!
+//! A separate process can't subscribe to a running bot's [`EventBus`], so
+//! this reads the JSONL event log the bus writes (`event_log`), printing
+//! what's there and, with `--follow`, polling for new lines. A log that
+//! shrinks (rotated or truncated) is read again from the start.
+//!
+//! Fills carry no wallet; `--wallet` matches them through the latest copy
+//! decision in the same market.
+//!
+//! [`EventBus`]: crate::events::EventBus
+
+use crate::events::{BotEvent, ConnectionState, EventRecord};
+use crate::types::Decision;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const POLL: Duration = Duration::from_millis(500);
+
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Which events `--only` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Only {
+    Decisions,
+    Copies,
+    Skips,
+    Fills,
+    Connections,
+}
+
+impl std::str::FromStr for Only {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "decisions" => Ok(Only::Decisions),
+            "copies" => Ok(Only::Copies),
+            "skips" => Ok(Only::Skips),
+            "fills" => Ok(Only::Fills),
+            "connections" => Ok(Only::Connections),
+            other => anyhow::bail!(
+                "Unknown --only {} (decisions, copies, skips, fills or connections)",
+                other
+            ),
+        }
+    }
+}
+
+/// The `--wallet`, `--market` and `--only` flags.
+#[derive(Debug, Clone, Default)]
+pub struct TailFilter {
+    pub wallet: Option<String>,
+    pub market: Option<String>,
+    pub only: Option<Only>,
+    /// Market -> wallet of the latest copy there, to attribute fills
+    copied_from: HashMap<String, String>,
+}
+
+impl TailFilter {
+    pub fn new(wallet: Option<String>, market: Option<String>, only: Option<Only>) -> Self {
+        Self {
+            wallet,
+            market,
+            only,
+            copied_from: HashMap::new(),
+        }
+    }
+
+    /// Whether to print `event`. Takes every event in order, printed or
+    /// not, to keep track of which leader each fill belongs to.
+    pub fn matches(&mut self, event: &BotEvent) -> bool {
+        let (wallet, market, only) = match event {
+            BotEvent::Decision {
+                wallet,
+                market_id,
+                decision,
+            } => {
+                let only = match decision {
+                    Decision::Copy { .. } => {
+                        self.copied_from.insert(market_id.clone(), wallet.clone());
+                        Only::Copies
+                    }
+                    Decision::Skip { .. } => Only::Skips,
+                };
+                (Some(wallet.as_str()), Some(market_id.as_str()), only)
+            }
+            BotEvent::OrderResult { market_id, .. } => (
+                self.copied_from.get(market_id).map(String::as_str),
+                Some(market_id.as_str()),
+                Only::Fills,
+            ),
+            BotEvent::Connection { wallet, .. } => (Some(wallet.as_str()), None, Only::Connections),
+            _ => return false,
+        };
+        let kind_ok = match self.only {
+            None => true,
+            Some(Only::Decisions) => matches!(only, Only::Copies | Only::Skips),
+            Some(wanted) => wanted == only,
+        };
+        let wallet_ok = self
+            .wallet
+            .as_ref()
+            .is_none_or(|w| wallet.is_some_and(|e| e.eq_ignore_ascii_case(w)));
+        let market_ok = self.market.as_ref().is_none_or(|m| market == Some(m.as_str()));
+        kind_ok && wallet_ok && market_ok
+    }
+}
+
+/// One line per event, colored for a terminal.
+pub fn format(record: &EventRecord) -> String {
+    let at = chrono::DateTime::from_timestamp_millis(record.at_ms)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    let line = match &record.event {
+        BotEvent::Decision {
+            wallet,
+            market_id,
+            decision: Decision::Copy { size_usd, shares },
+        } => format!(
+            "{}✅ copy{}  {} {} ${:.2} ({:.2} shares)",
+            GREEN,
+            RESET,
+            short(wallet),
+            market_id,
+            size_usd,
+            shares
+        ),
+        BotEvent::Decision {
+            wallet,
+            market_id,
+            decision: Decision::Skip { reason, detail },
+        } => format!(
+            "{}⏭️ skip{}  {} {} {}: {}",
+            YELLOW,
+            RESET,
+            short(wallet),
+            market_id,
+            reason.as_str(),
+            detail
+        ),
+        BotEvent::OrderResult {
+            market_id,
+            response: Some(r),
+            ..
+        } => format!(
+            "{}💰 fill{}  {} {:.2} @ ${:.4} ({} {})",
+            GREEN, RESET, market_id, r.filled_shares, r.avg_fill_price, r.order_id, r.status
+        ),
+        BotEvent::OrderResult { market_id, error, .. } => format!(
+            "{}❌ order failed{}  {}: {}",
+            RED,
+            RESET,
+            market_id,
+            error.as_deref().unwrap_or("no response")
+        ),
+        BotEvent::Connection { wallet, state, detail } => {
+            let (color, icon, word) = match state {
+                ConnectionState::Connected => (GREEN, "🔌", "connected"),
+                ConnectionState::Disconnected => (RED, "⚠️", "disconnected"),
+            };
+            let detail = detail.as_deref().map(|d| format!(": {}", d)).unwrap_or_default();
+            format!("{}{} {}{}  {}{}", color, icon, word, RESET, short(wallet), detail)
+        }
+        other => format!("{:?}", other.kind()),
+    };
+    format!("{}{}{} {}", DIM, at, RESET, line)
+}
+
+fn short(id: &str) -> &str {
+    &id[..10.min(id.len())]
+}
+
+/// Reads the events appended to a log since the last poll.
+pub struct Follower {
+    path: PathBuf,
+    offset: u64,
+    /// A line the bot is still writing
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            offset: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Events written since the last call; none while the log doesn't
+    /// exist yet.
+    pub fn poll(&mut self) -> Result<Vec<EventRecord>> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {}", self.path.display())),
+        };
+        if file.metadata()?.len() < self.offset {
+            tracing::info!("🔄 {} was truncated, reading it from the start", self.path.display());
+            self.offset = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        self.offset += file.read_to_end(&mut self.partial)? as u64;
+
+        let Some(end) = self.partial.iter().rposition(|b| *b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        let mut records = Vec::new();
+        for line in String::from_utf8_lossy(&complete).lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str::<EventRecord>(line) {
+                Ok(record) => records.push(record),
+                Err(e) => tracing::warn!("Skipping unreadable event in {}: {}", self.path.display(), e),
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Prints the matching events in the log at `path`, then with `follow`
+/// keeps printing new ones until interrupted. `json` prints each event as
+/// its log line.
+pub async fn tail(path: PathBuf, mut filter: TailFilter, follow: bool, json: bool) -> Result<()> {
+    if !follow && !path.exists() {
+        anyhow::bail!("No event log at {}", path.display());
+    }
+    let mut follower = Follower::new(path);
+    loop {
+        for record in follower.poll()? {
+            if filter.matches(&record.event) {
+                if json {
+                    println!("{}", serde_json::to_string(&record)?);
+                } else {
+                    println!("{}", format(&record));
+                }
+            }
+        }
+        if !follow {
+            return Ok(());
+        }
+        tokio::time::sleep(POLL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventLog;
+    use crate::types::{OrderResponse, SkipReason};
+
+    fn decision(wallet: &str, market: &str, decision: Decision) -> BotEvent {
+        BotEvent::Decision {
+            wallet: wallet.to_string(),
+            market_id: market.to_string(),
+            decision,
+        }
+    }
+
+    #[test]
+    fn test_follow_and_filter() {
+        let dir = std::env::temp_dir().join(format!("tail-test-{}", std::process::id()));
+        let path = dir.join("events.jsonl");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut follower = Follower::new(&path);
+        assert!(follower.poll().unwrap().is_empty());
+
+        let log = EventLog::open(&path).unwrap();
+        log.append(decision(
+            "0xAAA",
+            "m1",
+            Decision::Copy {
+                size_usd: 5.0,
+                shares: 10.0,
+            },
+        ));
+        log.append(decision("0xbbb", "m2", Decision::skip(SkipReason::Stale, "too old")));
+        log.append(BotEvent::OrderResult {
+            market_id: "m1".to_string(),
+            response: Some(OrderResponse {
+                order_id: "o1".to_string(),
+                status: "filled".to_string(),
+                filled_shares: 10.0,
+                avg_fill_price: 0.5,
+            }),
+            error: None,
+        });
+        log.append(BotEvent::DailyReset);
+        let records = follower.poll().unwrap();
+        assert_eq!(records.len(), 4);
+        assert!(format(&records[2]).contains("10.00 @ $0.5000"));
+
+        let mut leader = TailFilter::new(Some("0xaaa".to_string()), None, None);
+        let shown: Vec<bool> = records.iter().map(|r| leader.matches(&r.event)).collect();
+        assert_eq!(shown, [true, false, true, false]);
+        let mut skips = TailFilter::new(None, None, Some(Only::Skips));
+        let shown: Vec<bool> = records.iter().map(|r| skips.matches(&r.event)).collect();
+        assert_eq!(shown, [false, true, false, false]);
+
+        // A line still being written waits for its newline
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"seq\":4,\"at_ms\":1,").unwrap();
+        assert!(follower.poll().unwrap().is_empty());
+        file.write_all(b"\"type\":\"daily_reset\"}\n").unwrap();
+        assert_eq!(follower.poll().unwrap()[0].seq, 4);
+
+        // Rotated: read the new file from the start
+        std::fs::write(&path, "{\"seq\":0,\"at_ms\":1,\"type\":\"daily_reset\"}\n").unwrap();
+        assert_eq!(follower.poll().unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
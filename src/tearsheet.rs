@@ -0,0 +1,590 @@
+//! Backtests and paper runs as a standalone HTML or Markdown tear sheet:
+//! the equity curve, PnL by leader and by market category, how far copies
+//! filled from the leader's price, and the worst positions.
+//!
+//! Charts are inline SVG (data-URI images in Markdown), so the file opens
+//! on its own.
+
+use crate::backtest::{BacktestReport, CopiedFill};
+use crate::paper::EquityPoint;
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Worst positions listed.
+const WORST_POSITIONS: usize = 10;
+
+/// Upper edges of the slippage buckets, in percent of the leader's price;
+/// the last bucket takes everything above.
+const SLIPPAGE_EDGES: &[f64] = &[-2.0, -1.0, -0.5, 0.0, 0.5, 1.0, 2.0, 5.0];
+
+const CHART_WIDTH: f64 = 720.0;
+const CHART_HEIGHT: f64 = 240.0;
+const CHART_PAD: f64 = 48.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    /// By the file's extension: `.html` or `.md`.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("html" | "htm") => Ok(Format::Html),
+            Some("md" | "markdown") => Ok(Format::Markdown),
+            _ => anyhow::bail!("Can't tell the report format of {} (use .html or .md)", path.display()),
+        }
+    }
+}
+
+/// Writes `report` to `path` in the format its extension names.
+pub fn write(report: &BacktestReport, title: &str, path: &Path) -> Result<()> {
+    let sheet = render(report, title, Format::from_path(path)?);
+    std::fs::write(path, sheet).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Fills per slippage bucket, labelled, most favourable first.
+pub fn slippage_histogram(fills: &[CopiedFill]) -> Vec<(String, usize)> {
+    let mut counts = vec![0; SLIPPAGE_EDGES.len() + 1];
+    for fill in fills {
+        let pct = fill.slippage() * 100.0;
+        let bucket = SLIPPAGE_EDGES
+            .iter()
+            .position(|edge| pct < *edge)
+            .unwrap_or(SLIPPAGE_EDGES.len());
+        counts[bucket] += 1;
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let label = match i {
+                0 => format!("< {}%", SLIPPAGE_EDGES[0]),
+                i if i == SLIPPAGE_EDGES.len() => format!(">= {}%", SLIPPAGE_EDGES[i - 1]),
+                i => format!("{} to {}%", SLIPPAGE_EDGES[i - 1], SLIPPAGE_EDGES[i]),
+            };
+            (label, count)
+        })
+        .collect()
+}
+
+struct Table {
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+enum Section {
+    Heading(String),
+    Text(String),
+    Table(Table),
+    Chart { alt: &'static str, svg: String },
+}
+
+pub fn render(report: &BacktestReport, title: &str, format: Format) -> String {
+    let sections = sections(report, title);
+    match format {
+        Format::Html => html(title, &sections),
+        Format::Markdown => markdown(&sections),
+    }
+}
+
+fn sections(r: &BacktestReport, title: &str) -> Vec<Section> {
+    let date = |ts: Option<i64>| {
+        ts.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let hit = |wins: usize, positions: usize| match positions {
+        0 => "-".to_string(),
+        n => format!("{:.0}%", wins as f64 / n as f64 * 100.0),
+    };
+    let mut s = vec![
+        Section::Heading(title.to_string()),
+        Section::Text(format!(
+            "{} leader trades, {} to {}. Fills: {} model, {}s latency, {:.2}% fees.",
+            r.trades,
+            date(r.from),
+            date(r.to),
+            r.fill_model,
+            r.latency.as_secs_f64(),
+            r.fee_rate * 100.0
+        )),
+    ];
+
+    let mut summary = vec![
+        row(["Starting balance", &usd(r.starting_balance)]),
+        row(["Final equity", &usd(r.final_equity)]),
+        row([
+            "PnL",
+            &format!(
+                "{} ({:+.2}%)",
+                signed_usd(r.pnl()),
+                r.pnl() / r.starting_balance.max(f64::EPSILON) * 100.0
+            ),
+        ]),
+        row(["Realized PnL", &signed_usd(r.realized_pnl)]),
+        row(["Unrealized PnL", &signed_usd(r.unrealized_pnl)]),
+        row(["Fees", &usd(r.fees)]),
+        row([
+            "Max drawdown",
+            &format!("{} ({:.2}%)", usd(r.max_drawdown), r.max_drawdown_pct * 100.0),
+        ]),
+        row([
+            "Hit rate",
+            &r.hit_rate().map_or("-".to_string(), |h| format!("{:.1}%", h * 100.0)),
+        ]),
+        row(["Copied", &format!("{} ({})", r.copied, usd(r.volume_usd))]),
+    ];
+    if r.missed > 0 {
+        summary.push(row(["Unfilled", &r.missed.to_string()]));
+    }
+    if r.nothing_to_sell > 0 {
+        summary.push(row(["Nothing held to sell", &r.nothing_to_sell.to_string()]));
+    }
+    for (reason, count) in &r.skipped {
+        summary.push(row([&format!("Skipped ({})", reason), &count.to_string()]));
+    }
+    s.push(Section::Table(Table {
+        headers: &["", ""],
+        rows: summary,
+    }));
+
+    s.push(Section::Heading("Equity".to_string()));
+    if r.equity_curve.len() < 2 {
+        s.push(Section::Text("Nothing was copied, so there is no curve.".to_string()));
+    } else {
+        s.push(Section::Chart {
+            alt: "Equity curve",
+            svg: equity_chart(&r.equity_curve),
+        });
+    }
+
+    if let Some(mc) = &r.monte_carlo {
+        s.push(Section::Heading("Monte Carlo".to_string()));
+        s.push(Section::Text(format!(
+            "{} runs of {} positions resampled with replacement from {} (seed {}); {:.2}% of runs ended unable to stake the minimum.",
+            mc.runs,
+            mc.positions,
+            usd(mc.bankroll),
+            mc.seed,
+            mc.risk_of_ruin * 100.0
+        )));
+        let (e, d) = (&mc.final_equity, &mc.max_drawdown_pct);
+        let pct = |v: f64| format!("{:.2}%", v * 100.0);
+        s.push(Section::Table(Table {
+            headers: &["", "p5", "p25", "p50", "p75", "p95"],
+            rows: vec![
+                row([
+                    "Final equity",
+                    &usd(e.p5),
+                    &usd(e.p25),
+                    &usd(e.p50),
+                    &usd(e.p75),
+                    &usd(e.p95),
+                ]),
+                row([
+                    "Max drawdown",
+                    &pct(d.p5),
+                    &pct(d.p25),
+                    &pct(d.p50),
+                    &pct(d.p75),
+                    &pct(d.p95),
+                ]),
+            ],
+        }));
+    }
+
+    s.push(Section::Heading("Leaders".to_string()));
+    s.push(Section::Table(Table {
+        headers: &[
+            "leader",
+            "trades",
+            "copied",
+            "volume",
+            "realized",
+            "unrealized",
+            "pnl",
+            "hit",
+        ],
+        rows: r
+            .leaders
+            .iter()
+            .map(|l| {
+                row([
+                    &l.wallet,
+                    &l.trades.to_string(),
+                    &l.copied.to_string(),
+                    &usd(l.volume_usd),
+                    &signed_usd(l.realized_pnl),
+                    &signed_usd(l.unrealized_pnl),
+                    &signed_usd(l.pnl()),
+                    &hit(l.wins, l.positions),
+                ])
+            })
+            .collect(),
+    }));
+
+    s.push(Section::Heading("Categories".to_string()));
+    s.push(Section::Table(Table {
+        headers: &[
+            "category",
+            "positions",
+            "volume",
+            "realized",
+            "unrealized",
+            "pnl",
+            "hit",
+        ],
+        rows: r
+            .categories
+            .iter()
+            .map(|c| {
+                row([
+                    &c.category,
+                    &c.positions.to_string(),
+                    &usd(c.volume_usd),
+                    &signed_usd(c.realized_pnl),
+                    &signed_usd(c.unrealized_pnl),
+                    &signed_usd(c.pnl()),
+                    &hit(c.wins, c.positions),
+                ])
+            })
+            .collect(),
+    }));
+
+    s.push(Section::Heading("Slippage vs the leader's price".to_string()));
+    if r.fills.is_empty() {
+        s.push(Section::Text("No fills.".to_string()));
+    } else {
+        let mut slippage: Vec<f64> = r.fills.iter().map(|f| f.slippage() * 100.0).collect();
+        slippage.sort_by(f64::total_cmp);
+        s.push(Section::Text(format!(
+            "Over {} fills, worse than the leader by {:.2}% on average, {:.2}% at the median and {:.2}% \
+             at the 90th percentile (negative is better).",
+            slippage.len(),
+            slippage.iter().sum::<f64>() / slippage.len() as f64,
+            percentile(&slippage, 0.5),
+            percentile(&slippage, 0.9)
+        )));
+        let histogram = slippage_histogram(&r.fills);
+        s.push(Section::Chart {
+            alt: "Slippage histogram",
+            svg: histogram_chart(&histogram),
+        });
+        s.push(Section::Table(Table {
+            headers: &["slippage", "fills"],
+            rows: histogram
+                .iter()
+                .map(|(label, count)| row([label, &count.to_string()]))
+                .collect(),
+        }));
+    }
+
+    s.push(Section::Heading("Worst positions".to_string()));
+    s.push(Section::Table(Table {
+        headers: &[
+            "leader",
+            "market",
+            "category",
+            "volume",
+            "realized",
+            "unrealized",
+            "pnl",
+        ],
+        rows: r
+            .positions
+            .iter()
+            .take(WORST_POSITIONS)
+            .map(|p| {
+                row([
+                    &p.wallet,
+                    &p.market_id,
+                    &p.category,
+                    &usd(p.volume_usd),
+                    &signed_usd(p.realized_pnl),
+                    &signed_usd(p.unrealized_pnl),
+                    &signed_usd(p.pnl()),
+                ])
+            })
+            .collect(),
+    }));
+    s
+}
+
+fn row<const N: usize>(cells: [&str; N]) -> Vec<String> {
+    cells.iter().map(|c| c.to_string()).collect()
+}
+
+fn usd(amount: f64) -> String {
+    format!("${:.2}", amount)
+}
+
+fn signed_usd(amount: f64) -> String {
+    match amount < 0.0 {
+        true => format!("-${:.2}", -amount),
+        false => format!("+${:.2}", amount),
+    }
+}
+
+/// `q` of the way up `sorted`, by nearest rank.
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let rank = ((sorted.len() as f64 * q).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+fn html(title: &str, sections: &[Section]) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 960px; margin: 2em auto; color: #222; }}\n\
+         table {{ border-collapse: collapse; margin: 1em 0; }}\n\
+         th, td {{ border: 1px solid #ddd; padding: 4px 10px; text-align: right; }}\n\
+         th:first-child, td:first-child {{ text-align: left; font-family: monospace; }}\n\
+         </style>\n</head>\n<body>\n",
+        escape(title)
+    );
+    for (i, section) in sections.iter().enumerate() {
+        match section {
+            Section::Heading(text) => {
+                let level = if i == 0 { 1 } else { 2 };
+                let _ = writeln!(out, "<h{0}>{1}</h{0}>", level, escape(text));
+            }
+            Section::Text(text) => {
+                let _ = writeln!(out, "<p>{}</p>", escape(text));
+            }
+            Section::Table(table) => {
+                out.push_str("<table>\n");
+                if table.headers.iter().any(|h| !h.is_empty()) {
+                    out.push_str("<tr>");
+                    for header in table.headers {
+                        let _ = write!(out, "<th>{}</th>", escape(header));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                for cells in &table.rows {
+                    out.push_str("<tr>");
+                    for cell in cells {
+                        let _ = write!(out, "<td>{}</td>", escape(cell));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+            Section::Chart { svg, .. } => {
+                out.push_str(svg);
+                out.push('\n');
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn markdown(sections: &[Section]) -> String {
+    let mut out = String::new();
+    for (i, section) in sections.iter().enumerate() {
+        match section {
+            Section::Heading(text) => {
+                let _ = writeln!(out, "{} {}\n", if i == 0 { "#" } else { "##" }, text);
+            }
+            Section::Text(text) => {
+                let _ = writeln!(out, "{}\n", text);
+            }
+            Section::Table(table) => {
+                let cells = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+                let _ = writeln!(out, "{}", cells(table.headers.iter().map(|h| h.to_string()).collect()));
+                let _ = writeln!(
+                    out,
+                    "{}",
+                    cells(table.headers.iter().map(|_| "---".to_string()).collect())
+                );
+                for row in &table.rows {
+                    let _ = writeln!(out, "{}", cells(row.iter().map(|c| c.replace('|', "\\|")).collect()));
+                }
+                out.push('\n');
+            }
+            Section::Chart { alt, svg } => {
+                let _ = writeln!(out, "![{}](data:image/svg+xml;base64,{})\n", alt, STANDARD.encode(svg));
+            }
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn svg_open() -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" \
+         font-family=\"sans-serif\" font-size=\"11\">",
+        CHART_WIDTH, CHART_HEIGHT
+    )
+}
+
+/// Equity over time, scaled to fill the chart.
+fn equity_chart(curve: &[EquityPoint]) -> String {
+    let (first, last) = (curve[0].at, curve[curve.len() - 1].at);
+    let span = (last - first).max(1) as f64;
+    let low = curve.iter().map(|p| p.equity).fold(f64::INFINITY, f64::min);
+    let high = curve
+        .iter()
+        .map(|p| p.equity)
+        .fold(f64::NEG_INFINITY, f64::max)
+        .max(low + 1e-6);
+    let x = |at: i64| CHART_PAD + (at - first) as f64 / span * (CHART_WIDTH - 2.0 * CHART_PAD);
+    let y = |equity: f64| CHART_HEIGHT - CHART_PAD - (equity - low) / (high - low) * (CHART_HEIGHT - 2.0 * CHART_PAD);
+    let points: Vec<String> = curve
+        .iter()
+        .map(|p| format!("{:.1},{:.1}", x(p.at), y(p.equity)))
+        .collect();
+    let date = |ms: i64| {
+        chrono::DateTime::from_timestamp_millis(ms)
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    };
+
+    let mut svg = svg_open();
+    let bottom = CHART_HEIGHT - CHART_PAD;
+    let right = CHART_WIDTH - CHART_PAD;
+    let _ = write!(
+        svg,
+        "<line x1=\"{p}\" y1=\"{p}\" x2=\"{p}\" y2=\"{b}\" stroke=\"#999\"/>\
+         <line x1=\"{p}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/>\
+         <text x=\"{t}\" y=\"{p}\" text-anchor=\"end\">{high:.0}</text>\
+         <text x=\"{t}\" y=\"{b}\" text-anchor=\"end\">{low:.0}</text>\
+         <text x=\"{p}\" y=\"{d}\">{from}</text>\
+         <text x=\"{r}\" y=\"{d}\" text-anchor=\"end\">{to}</text>\
+         <polyline fill=\"none\" stroke=\"#2a6fdb\" stroke-width=\"1.5\" points=\"{points}\"/></svg>",
+        p = CHART_PAD,
+        b = bottom,
+        r = right,
+        t = CHART_PAD - 4.0,
+        d = bottom + 16.0,
+        from = date(first),
+        to = date(last),
+        points = points.join(" "),
+    );
+    svg
+}
+
+/// One bar per bucket, labelled underneath.
+fn histogram_chart(buckets: &[(String, usize)]) -> String {
+    let most = buckets.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1) as f64;
+    let slot = (CHART_WIDTH - 2.0 * CHART_PAD) / buckets.len() as f64;
+    let bottom = CHART_HEIGHT - CHART_PAD;
+    let mut svg = svg_open();
+    for (i, (label, count)) in buckets.iter().enumerate() {
+        let height = *count as f64 / most * (CHART_HEIGHT - 2.0 * CHART_PAD);
+        let left = CHART_PAD + i as f64 * slot;
+        let middle = left + slot / 2.0;
+        let _ = write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#2a6fdb\"/>\
+             <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\
+             <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            left + 4.0,
+            bottom - height,
+            slot - 8.0,
+            height,
+            middle,
+            bottom - height - 4.0,
+            count,
+            middle,
+            bottom + 16.0,
+            escape(label),
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{LeaderAttribution, PositionResult};
+    use crate::types::TradeSide;
+
+    fn fill(side: TradeSide, leader_price: f64, price: f64) -> CopiedFill {
+        CopiedFill {
+            at: 1_700_000_000_000,
+            wallet: "0xleader".to_string(),
+            market_id: "m1".to_string(),
+            side,
+            leader_price,
+            price,
+            shares: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_tear_sheet_formats() {
+        let fills = vec![
+            fill(TradeSide::BUY, 0.50, 0.51),
+            fill(TradeSide::SELL, 0.50, 0.51),
+            fill(TradeSide::BUY, 0.40, 0.40),
+        ];
+        let histogram = slippage_histogram(&fills);
+        let count = |label: &str| histogram.iter().find(|(l, _)| l == label).unwrap().1;
+        assert_eq!(count(">= 5%"), 0);
+        assert_eq!(count("2 to 5%"), 1);
+        assert_eq!(count("0 to 0.5%"), 1);
+        assert_eq!(count("< -2%"), 1);
+
+        let report = BacktestReport {
+            starting_balance: 100.0,
+            final_equity: 95.0,
+            leaders: vec![LeaderAttribution {
+                wallet: "0xleader".to_string(),
+                ..Default::default()
+            }],
+            positions: vec![PositionResult {
+                wallet: "0xleader".to_string(),
+                market_id: "will-<it>".to_string(),
+                category: "Sports".to_string(),
+                realized_pnl: -5.0,
+                ..Default::default()
+            }],
+            fills,
+            equity_curve: vec![
+                EquityPoint {
+                    at: 1_700_000_000_000,
+                    cash: 100.0,
+                    equity: 100.0,
+                },
+                EquityPoint {
+                    at: 1_700_086_400_000,
+                    cash: 95.0,
+                    equity: 95.0,
+                },
+            ],
+            ..Default::default()
+        };
+        let html = render(&report, "Backtest", Format::Html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<polyline"));
+        assert!(html.contains("will-&lt;it&gt;"));
+        assert!(html.contains("<td>-$5.00</td>"));
+
+        let markdown = render(&report, "Backtest", Format::Markdown);
+        assert!(markdown.starts_with("# Backtest\n"));
+        assert!(markdown.contains("![Equity curve](data:image/svg+xml;base64,"));
+        assert!(markdown.contains("| 0xleader | will-<it> | Sports |"));
+
+        assert_eq!(Format::from_path(Path::new("out/run.MD")).unwrap(), Format::Markdown);
+        assert!(Format::from_path(Path::new("run.pdf")).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! OpenTelemetry traces of the copy pipeline, exported over OTLP/HTTP.
+//!
+//! Each copied trade becomes one trace: `receive` (frame in, `parse`
+//! inside) in the watcher, then `copy` in the bot with `decide` (`risk`
+//! inside), `prepare` (building and journaling the order), `submit` and
+//! `fill`. The trace id is derived from the trade's correlation id, so the
+//! watcher and the bot agree on it without passing context along, and
+//! `copy` hangs under `receive`. Point `otel_exporter_otlp_endpoint` at a
+//! collector, Jaeger or Tempo (OTLP/HTTP, usually port 4318).
+//!
+//! The layer is always installed but records nothing until [`start`] has
+//! set up the exporter. Spans may set `otel.start_ns` (Unix nanoseconds) to
+//! start earlier than they were created, e.g. when a frame arrived.
+
+use crate::logging::JsonVisitor;
+use crate::types::Config;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Name of the span a trade's trace starts with.
+pub const ROOT_SPAN: &str = "receive";
+
+/// Finished spans kept while the collector is unreachable; older ones are
+/// dropped first.
+const MAX_BUFFERED: usize = 10_000;
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+struct Exporter {
+    service_name: String,
+    finished: Mutex<Vec<FinishedSpan>>,
+}
+
+pub fn now_ns() -> u64 {
+    chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64
+}
+
+/// Trace id and the id of the [`ROOT_SPAN`] for a correlation id.
+fn ids_for(cid: &str) -> ([u8; 16], [u8; 8]) {
+    let digest = Sha256::digest(cid.as_bytes());
+    let mut trace_id = [0u8; 16];
+    let mut root_id = [0u8; 8];
+    trace_id.copy_from_slice(&digest[..16]);
+    root_id.copy_from_slice(&digest[16..24]);
+    (trace_id, root_id)
+}
+
+#[derive(Debug, Clone)]
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_id: Option<[u8; 8]>,
+    start_ns: u64,
+    attributes: Map<String, Value>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct FinishedSpan {
+    name: &'static str,
+    data: SpanData,
+    end_ns: u64,
+}
+
+/// Collects spans for the exporter.
+#[derive(Debug, Default)]
+pub struct OtlpLayer;
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if EXPORTER.get().is_none() {
+            return;
+        }
+        let Some(span) = ctx.span(id) else { return };
+        let mut attributes = Map::new();
+        attrs.record(&mut JsonVisitor(&mut attributes));
+        let start_ns = attributes
+            .remove("otel.start_ns")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_else(now_ns);
+
+        let parent = span.parent().and_then(|parent| {
+            let extensions = parent.extensions();
+            extensions.get::<SpanData>().map(|p| (p.trace_id, p.span_id))
+        });
+        let (trace_id, span_id, parent_id) = match (parent, attributes.get("cid").and_then(|c| c.as_str())) {
+            (Some((trace_id, parent_id)), _) => (trace_id, rand::random(), Some(parent_id)),
+            (None, Some(cid)) => {
+                let (trace_id, root_id) = ids_for(cid);
+                if span.name() == ROOT_SPAN {
+                    (trace_id, root_id, None)
+                } else {
+                    (trace_id, rand::random(), Some(root_id))
+                }
+            }
+            (None, None) => (rand::random(), rand::random(), None),
+        };
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id,
+            parent_id,
+            start_ns,
+            attributes,
+            error: None,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            values.record(&mut JsonVisitor(&mut data.attributes));
+        }
+    }
+
+    /// Errors logged inside a span mark it as failed.
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        let Some(span) = ctx.event_span(event) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            let mut fields = Map::new();
+            event.record(&mut JsonVisitor(&mut fields));
+            let message = fields.remove("message").and_then(|m| m.as_str().map(str::to_string));
+            data.error = Some(message.unwrap_or_default());
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(exporter) = EXPORTER.get() else { return };
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        let mut finished = exporter.finished.lock().unwrap();
+        if finished.len() >= MAX_BUFFERED {
+            finished.remove(0);
+        }
+        finished.push(FinishedSpan {
+            name: span.name(),
+            data,
+            end_ns: now_ns(),
+        });
+    }
+}
+
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n.as_f64() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        other => json!({ "stringValue": other.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// An OTLP/HTTP JSON `ExportTraceServiceRequest`.
+fn export_request(service_name: &str, spans: &[FinishedSpan]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let mut value = json!({
+                "traceId": hex::encode(span.data.trace_id),
+                "spanId": hex::encode(span.data.span_id),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": span.data.start_ns.to_string(),
+                "endTimeUnixNano": span.end_ns.max(span.data.start_ns).to_string(),
+                "attributes": span.data.attributes.iter().map(|(k, v)| attribute(k, v)).collect::<Vec<_>>(),
+            });
+            if let Some(parent_id) = span.data.parent_id {
+                value["parentSpanId"] = Value::from(hex::encode(parent_id));
+            }
+            if let Some(error) = &span.data.error {
+                value["status"] = json!({ "code": 2, "message": error });
+            }
+            value
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [attribute("service.name", &Value::from(service_name))] },
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// Starts recording spans and sending them to the configured OTLP
+/// endpoint every few seconds; does nothing without one. Must be called
+/// within a runtime, at most once.
+pub fn start(config: &Config) {
+    let endpoint = config.otel_exporter_otlp_endpoint.trim_end_matches('/');
+    if endpoint.is_empty() {
+        return;
+    }
+    let url = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    };
+    let exporter = Exporter {
+        service_name: config.otel_service_name.clone(),
+        finished: Mutex::new(Vec::new()),
+    };
+    if EXPORTER.set(exporter).is_err() {
+        return;
+    }
+    tracing::info!("🔭 Exporting traces to {}", url);
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(exporter) = EXPORTER.get() else { return };
+            let spans = std::mem::take(&mut *exporter.finished.lock().unwrap());
+            if spans.is_empty() {
+                continue;
+            }
+            let request = export_request(&exporter.service_name, &spans);
+            let result = client
+                .post(&url)
+                .json(&request)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = result {
+                // Not logged at error level: that would mark spans as failed
+                tracing::warn!("Failed to export {} spans: {}", spans.len(), e);
+                let mut finished = exporter.finished.lock().unwrap();
+                let room = MAX_BUFFERED.saturating_sub(finished.len());
+                finished.splice(0..0, spans.into_iter().rev().take(room).rev());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(name: &'static str, trace_id: [u8; 16], span_id: [u8; 8], parent_id: Option<[u8; 8]>) -> FinishedSpan {
+        let mut attributes = Map::new();
+        attributes.insert("cid".to_string(), Value::from("t-0123456789ab"));
+        attributes.insert("shares".to_string(), Value::from(20.5));
+        attributes.insert("attempt".to_string(), Value::from(2u64));
+        FinishedSpan {
+            name,
+            data: SpanData {
+                trace_id,
+                span_id,
+                parent_id,
+                start_ns: 1_000,
+                attributes,
+                error: None,
+            },
+            end_ns: 3_000,
+        }
+    }
+
+    #[test]
+    fn test_trace_ids_follow_the_correlation_id() {
+        let (trace_id, root_id) = ids_for("t-0123456789ab");
+        assert_eq!((trace_id, root_id), ids_for("t-0123456789ab"));
+        assert_ne!(trace_id, ids_for("t-ba9876543210").0);
+    }
+
+    #[test]
+    fn test_export_request() {
+        let (trace_id, root_id) = ids_for("t-0123456789ab");
+        let mut copy = span("copy", trace_id, [7; 8], Some(root_id));
+        copy.data.error = Some("rejected".to_string());
+        let request = export_request("bot", &[span(ROOT_SPAN, trace_id, root_id, None), copy]);
+
+        let resource = &request["resourceSpans"][0];
+        assert_eq!(resource["resource"]["attributes"][0]["value"]["stringValue"], "bot");
+        let spans = &resource["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["traceId"], hex::encode(trace_id));
+        assert_eq!(spans[0]["spanId"], hex::encode(root_id));
+        assert!(spans[0].get("parentSpanId").is_none());
+        assert_eq!(spans[0]["startTimeUnixNano"], "1000");
+        assert_eq!(spans[1]["parentSpanId"], hex::encode(root_id));
+        assert_eq!(spans[1]["status"]["code"], 2);
+
+        let attributes = spans[1]["attributes"].as_array().unwrap();
+        assert!(attributes.contains(&json!({"key": "attempt", "value": {"intValue": "2"}})));
+        assert!(attributes.contains(&json!({"key": "shares", "value": {"doubleValue": 20.5}})));
+    }
+}
@@ -0,0 +1,243 @@
+//! Trades and price changes in the markets the bot holds.
+//!
+//! [`MarketTicker`] follows the [`BookStream`] events of every market with
+//! an open position, which the stream subscribes to and drops as positions
+//! open and close. Each last trade, book snapshot and best bid and ask
+//! change becomes a fresh mark (see [`crate::marks`]) and a [`Tick`], so
+//! positions are valued without polling and the bot can check its exit
+//! rules (`stop_loss`, `take_profit`) on every price. Last trades also build
+//! the markets' OHLC bars (see [`crate::bars`]).
+
+use crate::bars::Bars;
+use crate::book::{BookEvent, BookStream};
+use crate::clock::Clock;
+use crate::marks::{MarkSource, Marks};
+use crate::portfolio::Portfolio;
+use crate::types::Config;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Ticks a listener may fall behind by before it misses some.
+const TICK_CAPACITY: usize = 1024;
+
+/// A new price for a held market.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tick {
+    pub market_id: String,
+    pub price: f64,
+    /// [`MarkSource::Mid`] or [`MarkSource::LastTrade`]
+    pub source: MarkSource,
+    /// Shares traded, for last trades
+    pub size: Option<f64>,
+    /// Unix ms
+    pub at: i64,
+}
+
+/// Why a position is closed on a price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    StopLoss,
+    TakeProfit,
+}
+
+impl ExitReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExitReason::StopLoss => "stop_loss",
+            ExitReason::TakeProfit => "take_profit",
+        }
+    }
+}
+
+/// Closes a position once its price has moved far enough from the average
+/// entry price; a zero threshold is off.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExitRule {
+    /// Fall below the entry price that closes the position
+    pub stop_loss: f64,
+    /// Rise above the entry price that closes the position
+    pub take_profit: f64,
+}
+
+impl ExitRule {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            stop_loss: config.stop_loss,
+            take_profit: config.take_profit,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.stop_loss > 0.0 || self.take_profit > 0.0
+    }
+
+    /// Whether a position bought at `avg_price` should close at `price`.
+    pub fn check(&self, avg_price: f64, price: f64) -> Option<ExitReason> {
+        if avg_price <= 0.0 || price <= 0.0 {
+            return None;
+        }
+        // A price exactly at a threshold triggers it despite rounding
+        if self.stop_loss > 0.0 && price <= avg_price * (1.0 - self.stop_loss) + 1e-9 {
+            Some(ExitReason::StopLoss)
+        } else if self.take_profit > 0.0 && price >= avg_price * (1.0 + self.take_profit) - 1e-9 {
+            Some(ExitReason::TakeProfit)
+        } else {
+            None
+        }
+    }
+}
+
+/// The price in a streamed book event seen at `at`: a last trade, or the
+/// mid once the book has both sides.
+pub fn tick(event: &BookEvent, at: i64) -> Option<Tick> {
+    let (token_id, price, source, size) = match event {
+        BookEvent::Trade { token_id, price, size } => (token_id, *price, MarkSource::LastTrade, Some(*size)),
+        BookEvent::Snapshot {
+            token_id,
+            best_bid,
+            best_ask,
+        }
+        | BookEvent::Changed {
+            token_id,
+            best_bid,
+            best_ask,
+            ..
+        } => (
+            token_id,
+            (best_bid.as_ref()? + best_ask.as_ref()?) / 2.0,
+            MarkSource::Mid,
+            None,
+        ),
+        BookEvent::Resync { .. } => return None,
+    };
+    (price > 0.0).then(|| Tick {
+        market_id: token_id.clone(),
+        price,
+        source,
+        size,
+        at,
+    })
+}
+
+/// Prices for the markets the portfolio holds, from the book stream.
+pub struct MarketTicker {
+    books: Arc<BookStream>,
+    portfolio: Arc<Portfolio>,
+    marks: Arc<Marks>,
+    clock: Arc<dyn Clock>,
+    ticks: broadcast::Sender<Tick>,
+    bars: Option<Arc<Bars>>,
+}
+
+impl MarketTicker {
+    /// Ticks on `books`' events, which must stream the held markets (see
+    /// [`BookStream::streams_held_markets`]).
+    pub fn new(books: Arc<BookStream>, portfolio: Arc<Portfolio>, marks: Arc<Marks>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            books,
+            portfolio,
+            marks,
+            clock,
+            ticks: broadcast::channel(TICK_CAPACITY).0,
+            bars: None,
+        }
+    }
+
+    /// Folds last trades into `bars`.
+    pub fn with_bars(mut self, bars: Arc<Bars>) -> Self {
+        self.bars = Some(bars);
+        self
+    }
+
+    /// Ticks from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Tick> {
+        self.ticks.subscribe()
+    }
+
+    /// Marks and publishes the price in an event, if its market is held.
+    pub fn apply(&self, event: &BookEvent) -> Option<Tick> {
+        let tick = tick(event, self.clock.now_ms()).filter(|t| self.portfolio.holding(&t.market_id).is_some())?;
+        match tick.source {
+            MarkSource::LastTrade => {
+                self.marks.record_trade(&tick.market_id, tick.price, tick.at);
+                if let (Some(bars), Some(size)) = (&self.bars, tick.size) {
+                    bars.record_trade(&tick.market_id, tick.price, size, tick.at);
+                }
+            }
+            _ => self.marks.record_mid(&tick.market_id, tick.price, tick.at),
+        }
+        if self.ticks.receiver_count() > 0 {
+            let _ = self.ticks.send(tick.clone());
+        }
+        Some(tick)
+    }
+
+    /// Ticks in the background on the book stream's events.
+    pub fn spawn(self: Arc<Self>) {
+        let mut events = self.books.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        self.apply(&event);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("Held market prices fell behind; {} book events missed", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ticks_on_book_events_and_checks_exits() {
+        let books = BookStream::new("wss://example", Vec::new());
+        let mut events = books.subscribe();
+        let frame = json!([
+            { "event_type": "last_trade_price", "asset_id": "t1", "price": "0.61", "size": "20", "side": "BUY" },
+            { "event_type": "book", "asset_id": "t2",
+              "bids": [{ "price": "0.30", "size": "10" }], "asks": [{ "price": "0.34", "size": "10" }] },
+            { "event_type": "book", "asset_id": "t3", "bids": [], "asks": [{ "price": "0.25", "size": "10" }] },
+            { "event_type": "price_change", "market": "0xm", "price_changes": [
+                { "asset_id": "t2", "price": "0.32", "side": "BUY", "size": "5", "best_bid": "0.32", "best_ask": "0.34" }
+            ] },
+            { "event_type": "tick_size_change", "asset_id": "t1" }
+        ]);
+        books.apply(&frame.to_string());
+        let mut ticks = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            ticks.extend(tick(&event, 7));
+        }
+        let seen: Vec<(&str, MarkSource)> = ticks.iter().map(|t| (t.market_id.as_str(), t.source)).collect();
+        assert_eq!(
+            seen,
+            [
+                ("t1", MarkSource::LastTrade),
+                ("t2", MarkSource::Mid),
+                ("t2", MarkSource::Mid)
+            ]
+        );
+        assert!((ticks[1].price - 0.32).abs() < 1e-9);
+        assert!((ticks[2].price - 0.33).abs() < 1e-9);
+        assert!(ticks.iter().all(|t| t.at == 7));
+        assert_eq!((ticks[0].size, ticks[1].size), (Some(20.0), None));
+
+        let rule = ExitRule {
+            stop_loss: 0.25,
+            take_profit: 0.5,
+        };
+        assert_eq!(rule.check(0.40, 0.30), Some(ExitReason::StopLoss));
+        assert_eq!(rule.check(0.40, 0.31), None);
+        assert_eq!(rule.check(0.40, 0.60), Some(ExitReason::TakeProfit));
+        assert_eq!(ExitRule::default().check(0.40, 0.01), None);
+    }
+}
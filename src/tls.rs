@@ -0,0 +1,16 @@
+//! TLS client settings shared by the SMTP and Postgres connections.
+
+use anyhow::{Context, Result};
+use tokio_rustls::rustls;
+
+/// Verifies servers against the system's root certificates.
+pub fn client_config() -> Result<rustls::ClientConfig> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs().context("Failed to load system root certificates")? {
+        // Skip the odd unparsable system certificate rather than failing
+        let _ = roots.add(cert);
+    }
+    Ok(rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
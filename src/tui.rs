@@ -0,0 +1,577 @@
+//! Terminal dashboard for supervising a running bot.
+//!
+//! `mybot tui` polls the status API of the bot on `health_addr` (which
+//! needs `status_api` on) every couple of seconds and redraws one screen:
+//! the overview, the equity curve with its drawdown and returns, leader
+//! activity, open positions marked to market with a chart of their last
+//! few hours' 5m closes, open orders, feed connections and recent skips
+//! with their reasons, each in its own ratatui panel. It only reads, so it
+//! can run alongside the bot in another terminal or over SSH.
+//! Ctrl-C quits.
+
+use crate::equity::EquityStats;
+use crate::health::FeedState;
+use crate::paper::EquityPoint;
+use crate::storage::{Bar, DecisionRecord, OrderRecord};
+use crate::types::Config;
+use anyhow::{Context, Result};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::crossterm::{execute, terminal};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{Frame, Terminal};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const REFRESH: Duration = Duration::from_secs(2);
+const ROWS: usize = 8;
+/// Equity points drawn in the sparkline
+const SPARK_WIDTH: usize = 60;
+/// History charted next to each position, in 5m bars
+const CHART_WINDOW: &str = "4h";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Overview {
+    pub paused: bool,
+    pub tripped: bool,
+    pub trip_reason: Option<String>,
+    pub positions: usize,
+    pub exposure_usd: f64,
+    pub realized_pnl_today: f64,
+    pub trades_today: u32,
+    pub feeds_connected: usize,
+    pub feeds: usize,
+    pub pending_approvals: usize,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PositionRow {
+    pub market_id: String,
+    pub shares: f64,
+    pub avg_price: f64,
+    pub mark: Option<f64>,
+    pub unrealized_pnl: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SkipCounts {
+    pub decisions: u64,
+    pub skipped: u64,
+    pub by_reason: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct BarsView {
+    bars: Vec<Bar>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EquityView {
+    pub stats: Option<EquityStats>,
+    /// Oldest first
+    pub curve: Vec<EquityPoint>,
+}
+
+/// Everything on one screen.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub overview: Overview,
+    pub positions: Vec<PositionRow>,
+    /// Or why they aren't available
+    pub orders: std::result::Result<Vec<OrderRecord>, String>,
+    pub feeds: BTreeMap<String, FeedState>,
+    /// Newest first
+    pub decisions: Vec<DecisionRecord>,
+    pub skips: SkipCounts,
+    /// `None` when the bot doesn't track it
+    pub equity: Option<EquityView>,
+    /// 5m closes of each position's market, oldest first
+    pub charts: BTreeMap<String, Vec<f64>>,
+}
+
+pub struct Dashboard {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl Dashboard {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Points at the bot's own `health_addr`.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        if config.health_addr.is_empty() {
+            anyhow::bail!("tui needs HEALTH_ADDR (and STATUS_API=true) on the bot to read its status");
+        }
+        Ok(Self::new(&format!("http://{}", config.health_addr)))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let resp = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach the bot at {}", self.base_url))?;
+        let status = resp.status();
+        if status.as_u16() == 404 && path == "/status" {
+            anyhow::bail!("The bot's status API is off; set STATUS_API=true");
+        }
+        let body = resp.text().await?;
+        if !status.is_success() {
+            let error = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v["error"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            anyhow::bail!("{} returned {}: {}", path, status, error);
+        }
+        serde_json::from_str(&body).with_context(|| format!("Unexpected response from {}", path))
+    }
+
+    pub async fn fetch(&self) -> Result<Snapshot> {
+        let equity_path = format!("/status/equity?limit={}", SPARK_WIDTH);
+        let (overview, positions, orders, feeds, decisions, skips, equity) = tokio::join!(
+            self.get::<Overview>("/status"),
+            self.get("/status/positions"),
+            self.get("/status/orders"),
+            self.get("/status/feeds"),
+            self.get("/status/decisions?limit=50"),
+            self.get("/status/skips?window=1h"),
+            self.get(&equity_path),
+        );
+        let positions: Vec<PositionRow> = positions?;
+        let charts = futures_util::future::join_all(positions.iter().take(ROWS).map(|p| async move {
+            let path = format!(
+                "/status/bars?market={}&interval=5m&window={}",
+                p.market_id, CHART_WINDOW
+            );
+            let bars = self.get::<BarsView>(&path).await.ok()?.bars;
+            Some((p.market_id.clone(), bars.iter().map(|b| b.close).collect()))
+        }))
+        .await;
+        Ok(Snapshot {
+            overview: overview?,
+            positions,
+            orders: orders.map_err(|e| format!("{:#}", e)),
+            feeds: feeds?,
+            decisions: decisions?,
+            skips: skips?,
+            equity: equity.ok(),
+            charts: charts.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Redraws until Ctrl-C.
+    pub async fn run(&self) -> Result<()> {
+        let mut stdout = std::io::stdout();
+        execute!(stdout, terminal::EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        terminal.hide_cursor()?;
+        let result = self.redraw(&mut terminal).await;
+        terminal.show_cursor()?;
+        execute!(terminal.backend_mut(), terminal::LeaveAlternateScreen)?;
+        result
+    }
+
+    async fn redraw<B: Backend>(&self, terminal: &mut Terminal<B>) -> Result<()> {
+        let mut ticks = tokio::time::interval(REFRESH);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    let screen = match self.fetch().await {
+                        Ok(snapshot) => render(&snapshot, chrono::Utc::now().timestamp_millis()),
+                        Err(e) => Screen::unreachable(&e),
+                    };
+                    terminal.draw(|frame| screen.draw(frame))?;
+                }
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            }
+        }
+    }
+}
+
+/// One titled box of lines.
+#[derive(Debug, Clone)]
+pub struct Panel {
+    pub title: String,
+    pub lines: Vec<Line<'static>>,
+}
+
+impl Panel {
+    fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, spans: Vec<Span<'static>>) {
+        self.lines.push(Line::from(spans));
+    }
+}
+
+/// The status line over the panels, stacked top to bottom.
+#[derive(Debug, Clone)]
+pub struct Screen {
+    pub header: Line<'static>,
+    pub panels: Vec<Panel>,
+}
+
+impl Screen {
+    fn unreachable(error: &anyhow::Error) -> Self {
+        Self {
+            header: Line::from(vec![
+                Span::styled("Can't read the bot's status:", Style::new().fg(Color::Red)),
+                Span::raw(format!(" {:#}", error)),
+            ]),
+            panels: Vec::new(),
+        }
+    }
+
+    /// Panels that don't fit the terminal are cut off at the bottom.
+    pub fn draw(&self, frame: &mut Frame) {
+        let heights = std::iter::once(Constraint::Length(1))
+            .chain(self.panels.iter().map(|p| Constraint::Length(p.lines.len() as u16 + 2)))
+            .chain(std::iter::once(Constraint::Fill(1)));
+        let areas = Layout::vertical(heights).split(frame.area());
+        frame.render_widget(Paragraph::new(self.header.clone()), areas[0]);
+        for (panel, area) in self.panels.iter().zip(areas.iter().skip(1)) {
+            let block = Block::bordered().title(Line::from(format!(" {} ", panel.title)).bold());
+            frame.render_widget(Paragraph::new(panel.lines.clone()).block(block), *area);
+        }
+    }
+}
+
+fn short(id: &str) -> &str {
+    &id[..10.min(id.len())]
+}
+
+fn dim(text: impl Into<String>) -> Span<'static> {
+    Span::styled(text.into(), Style::new().add_modifier(Modifier::DIM))
+}
+
+fn colored(text: impl Into<String>, color: Color) -> Span<'static> {
+    Span::styled(text.into(), Style::new().fg(color))
+}
+
+fn signed(value: f64) -> Span<'static> {
+    let color = if value < 0.0 { Color::Red } else { Color::Green };
+    colored(format!("{:+.2}", value), color)
+}
+
+fn percent(ratio: Option<f64>) -> Span<'static> {
+    match ratio {
+        Some(ratio) => {
+            let color = if ratio < 0.0 { Color::Red } else { Color::Green };
+            colored(format!("{:+.2}%", ratio * 100.0), color)
+        }
+        None => Span::raw("-"),
+    }
+}
+
+/// One block character per point, scaled between the lowest and highest.
+fn sparkline(values: &[f64]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            let level = if high > low { (v - low) / (high - low) } else { 0.5 };
+            BLOCKS[((level * 7.0).round() as usize).min(7)]
+        })
+        .collect()
+}
+
+fn clock(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|t| t.format("%H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+/// The screen for `snapshot`.
+pub fn render(snapshot: &Snapshot, now_ms: i64) -> Screen {
+    let o = &snapshot.overview;
+    let state = if o.tripped {
+        vec![
+            colored("TRIPPED", Color::Red),
+            Span::raw(format!(" ({})", o.trip_reason.as_deref().unwrap_or_default())),
+        ]
+    } else if o.paused {
+        vec![colored("PAUSED", Color::Yellow)]
+    } else {
+        vec![colored("LIVE", Color::Green)]
+    };
+    let mut header = vec![Span::raw("mybot").bold(), Span::raw(format!(" {}  ", clock(now_ms)))];
+    header.extend(state);
+    header.extend([
+        Span::raw(format!("  exposure ${:.2}  pnl today ", o.exposure_usd)),
+        signed(o.realized_pnl_today),
+        Span::raw(format!(
+            "  trades today {}  feeds {}/{}  approvals {}",
+            o.trades_today, o.feeds_connected, o.feeds, o.pending_approvals
+        )),
+    ]);
+    let mut panels = Vec::new();
+
+    if let Some(EquityView { stats: Some(s), curve }) = &snapshot.equity {
+        let mut panel = Panel::new("Equity");
+        let drawdown = format!("{:.2}%", s.drawdown_pct * 100.0);
+        panel.push(vec![
+            Span::raw(format!("${:.2}  peak ${:.2}  drawdown ", s.equity, s.peak)),
+            if s.drawdown > 0.0 {
+                colored(drawdown, Color::Red)
+            } else {
+                dim(drawdown)
+            },
+            Span::raw(format!(" (max {:.2}%)  1d ", s.max_drawdown_pct * 100.0)),
+            percent(s.return_1d),
+            Span::raw("  7d "),
+            percent(s.return_7d),
+            Span::raw(format!(
+                "  sharpe {}",
+                s.sharpe.map(|r| format!("{:.2}", r)).unwrap_or_else(|| "-".to_string())
+            )),
+        ]);
+        if curve.len() > 1 {
+            let equity: Vec<f64> = curve.iter().map(|p| p.equity).collect();
+            panel.push(vec![Span::raw(sparkline(&equity))]);
+        }
+        panels.push(panel);
+    }
+
+    let mut panel = Panel::new("Leader activity");
+    for d in snapshot.decisions.iter().take(ROWS) {
+        let outcome = match (d.copied, d.reason) {
+            (true, _) => colored(format!("copy ${:.2}", d.size_usd.unwrap_or_default()), Color::Green),
+            (false, Some(reason)) => dim(format!("skip {}", reason.as_str())),
+            (false, None) => Span::raw("skip"),
+        };
+        panel.push(vec![
+            Span::raw(format!(
+                "{}  {}  {:<4} {}  ",
+                clock(d.decided_at),
+                short(&d.wallet),
+                d.side,
+                short(&d.market_id)
+            )),
+            outcome,
+        ]);
+    }
+    if snapshot.decisions.is_empty() {
+        panel.push(vec![dim("no leader trades yet")]);
+    }
+    panels.push(panel);
+
+    let mut panel = Panel::new(format!("Positions ({})", o.positions));
+    let mut unrealized = 0.0;
+    for p in &snapshot.positions {
+        let mark = p.mark.map(|m| format!("{:.4}", m)).unwrap_or_else(|| "?".to_string());
+        let pnl = p.unrealized_pnl.map(signed).unwrap_or_else(|| Span::raw("?"));
+        unrealized += p.unrealized_pnl.unwrap_or_default();
+        let chart = match snapshot.charts.get(&p.market_id) {
+            Some(closes) if closes.len() > 1 => format!("  {}", sparkline(closes)),
+            _ => String::new(),
+        };
+        panel.push(vec![
+            Span::raw(format!(
+                "{}  {:>10.2} sh  avg {:.4}  mark {}  upnl ",
+                short(&p.market_id),
+                p.shares,
+                p.avg_price,
+                mark
+            )),
+            pnl,
+            Span::raw(chart),
+        ]);
+    }
+    if !snapshot.positions.is_empty() {
+        panel.push(vec![Span::raw("unrealized total "), signed(unrealized)]);
+    }
+    panels.push(panel);
+
+    let mut panel = Panel::new("Open orders");
+    match &snapshot.orders {
+        Ok(orders) if orders.is_empty() => panel.push(vec![dim("none")]),
+        Ok(orders) => {
+            for order in orders.iter().take(ROWS) {
+                panel.push(vec![Span::raw(format!(
+                    "#{:<5} {}  {:<4} {:>10.2} @ {}  {}",
+                    order.id,
+                    short(&order.market_id),
+                    order.side,
+                    order.shares,
+                    order.limit_price.map(|p| format!("{:.4}", p)).unwrap_or_default(),
+                    order.status
+                ))]);
+            }
+        }
+        Err(e) => panel.push(vec![dim(e.clone())]),
+    }
+    panels.push(panel);
+
+    let mut panel = Panel::new("Connections");
+    for (wallet, feed) in &snapshot.feeds {
+        let dot = if feed.connected {
+            colored("●", Color::Green)
+        } else {
+            colored("○", Color::Red)
+        };
+        let since = crate::units::format_duration(Duration::from_millis((now_ms - feed.since).max(0) as u64));
+        let mut spans = vec![dot, Span::raw(format!(" {}  for {}", short(wallet), since))];
+        if let Some(detail) = feed.detail.as_ref().filter(|_| !feed.connected) {
+            spans.extend([Span::raw("  "), dim(detail.clone())]);
+        }
+        panel.push(spans);
+    }
+    panels.push(panel);
+
+    let mut panel = Panel::new(format!(
+        "Skips (last hour: {} of {})",
+        snapshot.skips.skipped, snapshot.skips.decisions
+    ));
+    let mut reasons: Vec<(&String, &u64)> = snapshot.skips.by_reason.iter().filter(|(_, n)| **n > 0).collect();
+    reasons.sort_by(|a, b| b.1.cmp(a.1));
+    if !reasons.is_empty() {
+        let counts: Vec<String> = reasons.iter().map(|(r, n)| format!("{} {}", r, n)).collect();
+        panel.push(vec![Span::raw(counts.join("  "))]);
+    }
+    for d in snapshot.decisions.iter().filter(|d| !d.copied).take(ROWS) {
+        panel.push(vec![Span::raw(format!(
+            "{}  {}  {}: {}",
+            clock(d.decided_at),
+            short(&d.wallet),
+            d.reason.map(|r| r.as_str()).unwrap_or_default(),
+            d.detail.as_deref().unwrap_or_default()
+        ))]);
+    }
+    panels.push(panel);
+
+    Screen {
+        header: Line::from(header),
+        panels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SkipReason;
+
+    fn decision(copied: bool, reason: Option<SkipReason>) -> DecisionRecord {
+        DecisionRecord {
+            id: 1,
+            leader_trade_id: None,
+            wallet: "0xleader0000".to_string(),
+            market_id: "0xmarket0000".to_string(),
+            side: "BUY".to_string(),
+            copied,
+            reason,
+            detail: reason.map(|_| "price moved 4%".to_string()),
+            size_usd: copied.then_some(12.5),
+            decided_at: 0,
+            arrival_mid: None,
+            arrival_book: None,
+        }
+    }
+
+    #[test]
+    fn test_render_panels() {
+        let snapshot = Snapshot {
+            overview: Overview {
+                paused: true,
+                positions: 1,
+                feeds: 1,
+                ..Default::default()
+            },
+            positions: vec![PositionRow {
+                market_id: "0xmarket0000".to_string(),
+                shares: 10.0,
+                avg_price: 0.4,
+                mark: Some(0.5),
+                unrealized_pnl: Some(1.0),
+            }],
+            orders: Err("orders are only tracked with storage_url set".to_string()),
+            feeds: BTreeMap::from([(
+                "0xleader0000".to_string(),
+                FeedState {
+                    connected: false,
+                    since: 0,
+                    detail: Some("timed out".to_string()),
+                },
+            )]),
+            decisions: vec![decision(false, Some(SkipReason::Stale)), decision(true, None)],
+            skips: SkipCounts {
+                decisions: 2,
+                skipped: 1,
+                by_reason: BTreeMap::from([("stale".to_string(), 1), ("paused".to_string(), 0)]),
+            },
+            equity: Some(EquityView {
+                stats: Some(EquityStats {
+                    equity: 95.0,
+                    peak: 100.0,
+                    drawdown: 5.0,
+                    drawdown_pct: 0.05,
+                    max_drawdown_pct: 0.05,
+                    return_1d: Some(-0.05),
+                    ..Default::default()
+                }),
+                curve: [100.0, 97.5, 95.0]
+                    .iter()
+                    .map(|&equity| EquityPoint {
+                        at: 0,
+                        cash: 0.0,
+                        equity,
+                    })
+                    .collect(),
+            }),
+            charts: BTreeMap::from([("0xmarket0000".to_string(), vec![0.4, 0.45, 0.5])]),
+        };
+        let screen = render(&snapshot, 90_000);
+        let text = std::iter::once(&screen.header)
+            .chain(screen.panels.iter().flat_map(|p| &p.lines))
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let style = |content: &str| {
+            std::iter::once(&screen.header)
+                .chain(screen.panels.iter().flat_map(|p| &p.lines))
+                .flat_map(|line| &line.spans)
+                .find(|s| s.content == content)
+                .map(|s| s.style)
+        };
+        assert!(text.contains("PAUSED"));
+        assert_eq!(style("PAUSED").and_then(|s| s.fg), Some(Color::Yellow));
+        assert!(text.contains("$95.00  peak $100.00"));
+        assert!(text.contains("1d -5.00%"));
+        assert_eq!(style("-5.00%").and_then(|s| s.fg), Some(Color::Red));
+        assert!(text.contains("█▅▁"));
+        assert!(text.contains("copy $12.50"));
+        assert!(text.contains("mark 0.5000"));
+        assert!(text.contains("upnl +1.00  ▁▅█"));
+        assert_eq!(style("+1.00").and_then(|s| s.fg), Some(Color::Green));
+        assert!(text.contains("storage_url"));
+        assert!(text.contains("for 90s  timed out"));
+        assert!(style("timed out").is_some_and(|s| s.add_modifier.contains(Modifier::DIM)));
+        assert!(text.contains("stale 1"));
+        assert!(!text.contains("paused 0"));
+        assert!(text.contains("stale: price moved 4%"));
+
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(100, 40)).unwrap();
+        terminal.draw(|frame| screen.draw(frame)).unwrap();
+        let drawn: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(drawn.contains("┌ Equity ─"));
+        assert!(drawn.contains("│0xmarket00       10.00 sh  avg 0.4000  mark 0.5000  upnl +1.00  ▁▅█"));
+    }
+}
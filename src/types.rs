@@ -1,4 +1,6 @@
+use crate::units::{Ratio, UsdcAmount};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
@@ -18,6 +20,130 @@ pub enum TradeSide {
     SELL,
 }
 
+impl TradeSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeSide::BUY => "BUY",
+            TradeSide::SELL => "SELL",
+        }
+    }
+    
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "BUY" => Some(TradeSide::BUY),
+            "SELL" => Some(TradeSide::SELL),
+            _ => None,
+        }
+    }
+}
+
+/// Why a leader trade was not copied.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    UnverifiedWallet,
+    OutsideTradingWindow,
+    Stale,
+    PriceOutOfRange,
+    MarketUnavailable,
+    BalanceUnavailable,
+    SizingFailed,
+    RiskBlocked,
+    Paused,
+    Rejected,
+    ApprovalExpired,
+}
+
+impl SkipReason {
+    pub const ALL: &'static [SkipReason] = &[
+        SkipReason::UnverifiedWallet,
+        SkipReason::OutsideTradingWindow,
+        SkipReason::Stale,
+        SkipReason::PriceOutOfRange,
+        SkipReason::MarketUnavailable,
+        SkipReason::BalanceUnavailable,
+        SkipReason::SizingFailed,
+        SkipReason::RiskBlocked,
+        SkipReason::Paused,
+        SkipReason::Rejected,
+        SkipReason::ApprovalExpired,
+    ];
+    
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|r| r.as_str() == s)
+    }
+    
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::UnverifiedWallet => "unverified_wallet",
+            SkipReason::OutsideTradingWindow => "outside_trading_window",
+            SkipReason::Stale => "stale",
+            SkipReason::PriceOutOfRange => "price_out_of_range",
+            SkipReason::MarketUnavailable => "market_unavailable",
+            SkipReason::BalanceUnavailable => "balance_unavailable",
+            SkipReason::SizingFailed => "sizing_failed",
+            SkipReason::RiskBlocked => "risk_blocked",
+            SkipReason::Paused => "paused",
+            SkipReason::Rejected => "rejected",
+            SkipReason::ApprovalExpired => "approval_expired",
+        }
+    }
+}
+
+/// How urgent a notification is, from least to most.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: &'static [Severity] = &[Severity::Debug, Severity::Info, Severity::Warn, Severity::Critical];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.to_lowercase();
+        Self::ALL.iter().copied().find(|l| l.as_str() == s || (s == "warning" && *l == Severity::Warn))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Outcome of evaluating a leader trade.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Decision {
+    Skip { reason: SkipReason, detail: String },
+    Copy { size_usd: f64, shares: f64 },
+}
+
+impl Decision {
+    pub fn skip(reason: SkipReason, detail: impl Into<String>) -> Self {
+        Decision::Skip {
+            reason,
+            detail: detail.into(),
+        }
+    }
+    
+    /// Same action, skip reason and (to the cent) size; details may differ.
+    pub fn same_outcome(&self, other: &Decision) -> bool {
+        match (self, other) {
+            (Decision::Skip { reason: a, .. }, Decision::Skip { reason: b, .. }) => a == b,
+            (Decision::Copy { size_usd: a, .. }, Decision::Copy { size_usd: b, .. }) => (a - b).abs() < 0.005,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Market {
     pub id: String,
@@ -27,6 +153,50 @@ pub struct Market {
     pub no_price: f64,
     pub liquidity: f64,
     pub volume_24h: f64,
+    // Catalog metadata (missing from event logs recorded before it was kept)
+    #[serde(default)]
+    pub slug: String,
+    #[serde(default)]
+    pub outcomes: Vec<String>,
+    #[serde(default)]
+    pub token_ids: Vec<String>,
+    #[serde(default = "default_tick_size")]
+    pub tick_size: f64,
+    /// Unix seconds
+    #[serde(default)]
+    pub end_date: Option<i64>,
+    /// E.g. "Politics" or "Sports"; empty when the catalog has none
+    #[serde(default)]
+    pub category: String,
+    /// Where the outcome is read from, usually a URL; empty when unknown
+    #[serde(default)]
+    pub resolution_source: String,
+}
+
+fn default_tick_size() -> f64 {
+    0.01
+}
+
+/// An empty market at the usual tick size, for filling in the rest of one.
+impl Default for Market {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            event_id: String::new(),
+            question: String::new(),
+            yes_price: 0.0,
+            no_price: 0.0,
+            liquidity: 0.0,
+            volume_24h: 0.0,
+            slug: String::new(),
+            outcomes: Vec::new(),
+            token_ids: Vec::new(),
+            tick_size: default_tick_size(),
+            end_date: None,
+            category: String::new(),
+            resolution_source: String::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,9 +217,12 @@ pub struct OrderRequest {
     pub shares: f64,
     pub price: Option<f64>,
     pub order_type: OrderType,
+    /// Idempotency key: resubmitting the same id never creates a second order
+    #[serde(default)]
+    pub client_order_id: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OrderType {
     MARKET,
     LIMIT,
@@ -57,6 +230,19 @@ pub enum OrderType {
     GTD,  // Good-Till-Date
 }
 
+impl OrderType {
+    /// A limit order's time in force: `gtc` rests until cancelled, `gtd`
+    /// until its expiry and `fak` fills what it can at once.
+    pub fn from_tif(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "gtc" => Some(OrderType::LIMIT),
+            "gtd" => Some(OrderType::GTD),
+            "fak" | "ioc" => Some(OrderType::FAK),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderResponse {
     pub order_id: String,
@@ -65,41 +251,360 @@ pub struct OrderResponse {
     pub avg_fill_price: f64,
 }
 
-#[derive(Debug, Clone)]
+/// An order as the exchange reports it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeOrder {
+    pub order_id: String,
+    pub market_id: String,
+    pub side: TradeSide,
+    pub shares: f64,
+    pub filled_shares: f64,
+    pub avg_fill_price: f64,
+    pub status: String,
+    pub client_order_id: Option<String>,
+}
+
+impl ExchangeOrder {
+    /// True once the order can no longer fill any further.
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self.status.to_lowercase().as_str(),
+            "filled" | "matched" | "cancelled" | "canceled" | "expired" | "rejected"
+        )
+    }
+}
+
+/// Outcome tokens held by a wallet in one market.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenBalance {
+    pub market_id: String,
+    pub token_id: Option<String>,
+    pub shares: f64,
+    pub avg_price: Option<f64>,
+}
+
+/// A wallet on the public leaderboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub wallet: String,
+    pub name: Option<String>,
+    /// Over the leaderboard's window
+    pub pnl: f64,
+    pub volume: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CircuitBreakerState {
     pub consecutive_errors: u32,
     pub total_trades_today: u32,
     pub total_volume_today: f64,
     pub is_tripped: bool,
     pub trip_reason: Option<String>,
+    pub realized_pnl_today: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub wallets_to_track: Vec<String>,
+    // Names for leaders in notifications, as "wallet=label" entries
+    pub leader_labels: Vec<String>,
     pub your_wallet: String,
     pub private_key: String,
     pub polymarket_api: String,
+    // Venue copies are placed on (only polymarket passes validation for
+    // now); Kalshi signs requests with the RSA key kalshi_private_key (PEM,
+    // or a path to it) registered as kalshi_key_id
+    pub exchange: Venue,
+    pub kalshi_api: String,
+    pub kalshi_key_id: String,
+    pub kalshi_private_key: String,
+    // Catalog markets are looked up on (empty uses polymarket_api)
+    pub gamma_api: String,
+    // Wallet activity, positions and holders are read from (empty uses
+    // polymarket_api), at most data_api_rate requests a second
+    pub data_api: String,
+    pub data_api_rate: u32,
     pub ws_url: String,
+    // Where leader trades come from: ws_url's wallet feed, or subgraph_url
+    // polled every subgraph_poll_interval for fills of the tracked wallets
+    pub trade_source: TradeFeed,
+    pub subgraph_url: String,
+    pub subgraph_poll_interval: Duration,
+    // Market channel order books are streamed from, for the book_tokens
+    // token ids, the price_alerts tokens and with book_held_markets or
+    // stream_held_markets every held market (none disables), sharded
+    // book_tokens_per_connection to a connection (0 is no limit)
+    pub book_ws_url: String,
+    pub book_tokens: Vec<String>,
+    pub book_held_markets: bool,
+    pub book_tokens_per_connection: u32,
+    // Levels of each side of the market's book journaled with every
+    // decision, for slippage analysis and backtest fills (0 keeps none;
+    // needs storage_url)
+    pub decision_book_levels: u32,
+    // Skip copies into a streamed book leaning more than max_chase_imbalance
+    // away from them within imbalance_ticks of the mid (0 disables)
+    pub imbalance_ticks: u32,
+    pub max_chase_imbalance: f64,
+    // Alerts on tokens streamed from book_ws_url, e.g. "<token> > 0.7",
+    // "<token> spread > 0.1" or "<token> volume > 3x" (none disables)
+    pub price_alerts: Vec<String>,
+    // Stream trades and price changes from book_ws_url for every held
+    // market, for marks and exit rules
+    pub stream_held_markets: bool,
     pub rpc_url: String,
     
     // Sizing
     pub sizing_mode: SizingMode,
-    pub fixed_stake: f64,
-    pub proportional_ratio: f64,
-    pub min_stake: f64,
-    pub max_stake: f64,
+    pub fixed_stake: UsdcAmount,
+    pub proportional_ratio: Ratio,
+    pub min_stake: UsdcAmount,
+    pub max_stake: UsdcAmount,
     
     // Risk
-    pub max_exposure_per_event: f64,
-    pub max_daily_volume: f64,
+    pub max_exposure_per_event: UsdcAmount,
+    pub max_daily_volume: UsdcAmount,
     pub min_liquidity: f64,
+    // Over the last liquidity_window of books and trades seen, skip markets
+    // whose average spread is over max_spread or that trade less than
+    // min_trades_per_hour, and cut copies to max_book_share of the top
+    // levels' depth they take from; zero disables each
+    pub liquidity_window: Duration,
+    pub max_spread: f64,
+    pub max_book_share: f64,
+    pub min_trades_per_hour: f64,
+    // Skip copies in a neg-risk event whose price puts the event's yes
+    // prices more than this off summing to 1 while the rest agree (0 disables)
+    pub max_neg_risk_deviation: f64,
+    // Spot prices of spot_assets (none disables) streamed from spot_ws_url;
+    // copies buying into a crypto price question with under
+    // spot_min_probability left at spot_volatility a year are skipped
+    // (0 disables)
+    pub spot_ws_url: String,
+    pub spot_assets: Vec<String>,
+    pub spot_volatility: f64,
+    pub spot_min_probability: f64,
+    // Polymarket markets paired with Kalshi ones in arb_pairs (slug=TICKER),
+    // and by question with arb_auto_pair, are checked every arb_interval
+    // (zero disables) for yes on one and no on the other costing under $1
+    // by arb_min_edge after fees; arb_auto_execute takes up to
+    // arb_max_stake of each
+    pub arb_pairs: Vec<String>,
+    pub arb_auto_pair: bool,
+    pub arb_interval: Duration,
+    pub arb_min_edge: f64,
+    pub arb_polymarket_fee: f64,
+    pub arb_kalshi_fee: f64,
+    pub arb_max_stake: f64,
+    pub arb_auto_execute: bool,
     pub cb_consecutive_trigger: u32,
     pub cb_min_depth_usd: f64,
     
     // Execution
     pub retry_attempts: u32,
     pub retry_delay_ms: u64,
+    pub max_slippage: Ratio,     // fraction of the leader's price
+    pub max_daily_loss: UsdcAmount, // 0 disables the limit
+    // Close a position once its price is this far below (stop_loss) or
+    // above (take_profit) the average entry price; zero disables each
+    pub stop_loss: f64,
+    pub take_profit: f64,
+    pub paper_trading: bool,     // simulate fills instead of submitting orders
+    // How simulated orders fill (paper trading, and backtests by default),
+    // and the delay the latency model charges
+    pub fill_model: FillModelKind,
+    pub fill_latency: Duration,
+    // Paper trading's virtual USDC, and how often held markets are checked
+    // for resolution (zero disables settlement)
+    pub paper_balance: f64,
+    pub paper_settle_interval: Duration,
+    // A second strategy copying in paper alongside this one, for comparison:
+    // this config with the SHADOW overrides on top
+    pub shadow: Option<Box<Config>>,
+    
+    // Storage ("sqlite://path"); empty disables the journal
+    pub storage_url: String,
+    
+    // How positions are costed when realizing PnL, and how often a running
+    // bot recomputes its PnL from the journal (zero disables)
+    pub cost_basis: CostBasis,
+    pub pnl_interval: Duration,
+    
+    // How often the account's equity (cash plus marked positions) is added
+    // to the equity curve (zero disables)
+    pub equity_interval: Duration,
+    
+    // How often live trading scans the chain for deposits to and
+    // withdrawals from your_wallet (zero disables; needs a ws rpc_url), and
+    // the USD price of POL that gas spent on chain is booked at
+    pub cash_flow_interval: Duration,
+    pub gas_token_usd: f64,
+    
+    // How often live trading checks positions against the wallet's
+    // conditional token balances on chain (zero disables; needs a ws
+    // rpc_url), and whether drift is corrected or only flagged
+    pub reconcile_interval: Duration,
+    pub reconcile_auto_correct: bool,
+    
+    // How long after every leader copied into a position has exited it an
+    // alert goes out that it's still held (zero disables; needs storage_url)
+    pub stale_position_after: Duration,
+    
+    // How often live trading checks held markets for resolution (zero
+    // disables), and whether won shares are redeemed on chain (needs a ws
+    // rpc_url)
+    pub resolution_interval: Duration,
+    pub auto_redeem: bool,
+    // How often held markets' UMA requests are checked for proposals and
+    // disputes (zero disables; needs gamma_api)
+    pub dispute_interval: Duration,
+    
+    // Live trading takes a lease on your_wallet in the journal so a second
+    // instance can't trade the same account (zero TTL disables; force takes
+    // the lease over)
+    pub instance_lease_ttl: Duration,
+    pub force_instance_lease: bool,
+    
+    // JSONL event log for replay; empty disables it
+    pub event_log: String,
+    
+    // Retention (zero keeps forever); expired rows go to parquet in archive_dir first
+    pub capture_ws_frames: bool,
+    pub ws_frame_retention: Duration,
+    pub journal_retention: Duration,
+    pub archive_dir: String,
+    pub compaction_interval: Duration,
+    
+    // Leader trades older than this are skipped as stale (zero disables)
+    pub latency_budget: Duration,
+    
+    // Leader trades priced outside [min_price, max_price] are skipped
+    pub min_price: f64,
+    pub max_price: f64,
+    
+    // A leader trade seen again within this window is ignored (zero disables)
+    pub dedup_window: Duration,
+    
+    // Market data is reused for market_cache_ttl (zero disables caching) and
+    // served up to market_max_stale old while the API is unreachable
+    pub market_cache_ttl: Duration,
+    pub market_max_stale: Duration,
+    // Copies buying into markets using every word of any of these phrases
+    // are skipped
+    pub market_blacklist: Vec<String>,
+    
+    // Prices of held markets, and of markets a leader traded within
+    // price_watch_window, are sampled every price_sample_interval (zero disables)
+    pub price_sample_interval: Duration,
+    pub price_watch_window: Duration,
+    pub price_retention: Duration,
+    // Positions are marked at the book's mid, else the last trade, else the
+    // market API's price; a price older than mark_max_age is flagged stale
+    pub mark_max_age: Duration,
+    
+    // Trading windows ("09:00-23:00") in trading_timezone; empty means always on
+    pub trading_timezone: String,
+    pub trading_windows: Vec<String>,
+    pub blackout_dates: Vec<String>,
+    
+    // Telegram notifications and commands; empty disables
+    pub telegram_bot_token: String,
+    pub telegram_chat_id: String,
+    
+    // Discord webhooks (per-category ones override discord_webhook) and an
+    // optional slash-command bot; empty disables
+    pub discord_webhook: String,
+    pub discord_trades_webhook: String,
+    pub discord_errors_webhook: String,
+    pub discord_bot_token: String,
+    pub discord_command_channel: String,
+    
+    // Signed JSON webhooks for every notification; empty disables
+    pub webhook_urls: Vec<String>,
+    pub webhook_secret: String,
+    pub webhook_retries: u32,
+
+    // PagerDuty Events API v2 integration key; empty disables
+    pub pagerduty_routing_key: String,
+
+    // Critical alerts by email (smtps:// or smtp:// with STARTTLS); empty disables
+    pub smtp_url: String,
+    pub email_from: String,
+    pub email_to: Vec<String>,
+    pub email_max_per_hour: u32,
+    // Alert when a leader feed stays down this long; zero disables
+    pub feed_down_alert: Duration,
+
+    // Copies of approval_threshold USDC or more wait for the operator's
+    // approval, expiring after approval_timeout; zero disables
+    pub approval_threshold: f64,
+    pub approval_timeout: Duration,
+
+    // Serve /healthz and /readyz on this address (e.g. 127.0.0.1:9090);
+    // empty disables
+    pub health_addr: String,
+    // Also serve the read-only /status API there
+    pub status_api: bool,
+    // Bearer token for the /admin control API there; empty disables it
+    pub admin_token: String,
+
+    // Service mode: write the process id here while running (empty
+    // disables), and keep or cancel resting orders on SIGTERM/Ctrl-C
+    pub pid_file: String,
+    pub shutdown_orders: ShutdownOrders,
+
+    // Send traces of the copy pipeline to this OTLP/HTTP endpoint (e.g.
+    // http://localhost:4318 for Jaeger or Tempo); empty disables
+    pub otel_exporter_otlp_endpoint: String,
+    pub otel_service_name: String,
+
+    // Log p50/p95/p99 stage latencies this often; zero disables (they are
+    // also served at /metrics on health_addr)
+    pub latency_summary_interval: Duration,
+
+    // Dead man's switch: POST a status ping to this URL (e.g. a
+    // healthchecks.io check) every heartbeat_interval; empty disables
+    pub heartbeat_url: String,
+    pub heartbeat_interval: Duration,
+
+    // On-call incidents (PagerDuty via pagerduty_routing_key, Opsgenie) for
+    // a trade loop that stops keeping up or executor errors above a rate
+    pub opsgenie_api_key: String,
+    pub opsgenie_url: String,
+    pub incident_stall_after: Duration,
+    pub incident_error_rate: f64,
+    pub incident_error_window: Duration,
+    pub incident_min_orders: u32,
+
+    // Critical alerts when leader trades arrive but the pipeline stops
+    // deciding, placing orders or skips everything for one reason, judged
+    // over anomaly_window (zero disables) once anomaly_min_events happened
+    pub anomaly_window: Duration,
+    pub anomaly_min_events: u32,
+
+    // Volume and holder spikes in the spike_markets most active markets
+    // (zero disables), sampled every spike_interval against the mean over
+    // spike_window: 24h volume at spike_factor times it, holders up by
+    // spike_holder_change
+    pub spike_markets: u32,
+    pub spike_interval: Duration,
+    pub spike_window: Duration,
+    pub spike_factor: f64,
+    pub spike_holder_change: f64,
+
+    // Which notifiers hear about what: "selector=notifier+notifier" rules,
+    // first match wins; nothing below notify_min_severity is sent anywhere
+    pub notify_routes: Vec<String>,
+    pub notify_min_severity: Severity,
+    // Only these leaders' trades ("wallet" or "wallet=min_usd" of leader
+    // notional) are notified; empty notifies every leader
+    pub notify_leaders: Vec<String>,
+    // Summarize routine notifications this often instead; zero disables
+    pub notify_digest_interval: Duration,
+    // Send repeats of an alert once per window; announce recoveries only
+    // after they have held this long
+    pub notify_dedup_window: Duration,
+    pub notify_recovery_delay: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,6 +614,153 @@ pub enum SizingMode {
     TierBased,
 }
 
+/// Which purchase a sale is matched against when realizing PnL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CostBasis {
+    /// Oldest lot first
+    Fifo,
+    /// Weighted average of all open lots
+    Average,
+}
+
+/// How simulated orders fill; see [`crate::fills`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FillModelKind {
+    /// All at the leader's price
+    #[default]
+    Immediate,
+    /// Walking the order book as recorded (or, paper trading, as it is)
+    Book,
+    /// All at the price `fill_latency` later, if still within the limit
+    Latency,
+}
+
+impl FillModelKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FillModelKind::Immediate => "immediate",
+            FillModelKind::Book => "book",
+            FillModelKind::Latency => "latency",
+        }
+    }
+}
+
+impl std::str::FromStr for FillModelKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "immediate" => Ok(FillModelKind::Immediate),
+            "book" => Ok(FillModelKind::Book),
+            "latency" => Ok(FillModelKind::Latency),
+            other => anyhow::bail!("Unknown fill model '{}' (immediate, book or latency)", other),
+        }
+    }
+}
+
+/// A venue orders can be placed on; see [`crate::exchange`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Venue {
+    #[default]
+    Polymarket,
+    Kalshi,
+}
+
+impl Venue {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Venue::Polymarket => "polymarket",
+            Venue::Kalshi => "kalshi",
+        }
+    }
+}
+
+impl std::str::FromStr for Venue {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "polymarket" => Ok(Venue::Polymarket),
+            "kalshi" => Ok(Venue::Kalshi),
+            other => anyhow::bail!("Unknown exchange '{}' (polymarket or kalshi)", other),
+        }
+    }
+}
+
+/// Where leader trades are read from; see [`crate::watcher::TradeSource`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeFeed {
+    #[default]
+    WebSocket,
+    Subgraph,
+}
+
+impl TradeFeed {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeFeed::WebSocket => "websocket",
+            TradeFeed::Subgraph => "subgraph",
+        }
+    }
+}
+
+impl std::str::FromStr for TradeFeed {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "websocket" => Ok(TradeFeed::WebSocket),
+            "subgraph" => Ok(TradeFeed::Subgraph),
+            other => anyhow::bail!("Unknown trade source '{}' (websocket or subgraph)", other),
+        }
+    }
+}
+
+/// Resting orders of one market, best price first on each side.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrderBook {
+    /// (price, shares)
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+impl OrderBook {
+    /// Sorts levels as the exchange returned them into best-first order.
+    pub fn new(mut bids: Vec<(f64, f64)>, mut asks: Vec<(f64, f64)>) -> Self {
+        bids.sort_by(|a, b| b.0.total_cmp(&a.0));
+        asks.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { bids, asks }
+    }
+
+    /// The best `levels` levels of each side.
+    pub fn top(&self, levels: usize) -> OrderBook {
+        OrderBook {
+            bids: self.bids.iter().take(levels).copied().collect(),
+            asks: self.asks.iter().take(levels).copied().collect(),
+        }
+    }
+
+    /// The levels an order on `side` takes from.
+    pub fn opposite(&self, side: &TradeSide) -> &[(f64, f64)] {
+        match side {
+            TradeSide::BUY => &self.asks,
+            TradeSide::SELL => &self.bids,
+        }
+    }
+}
+
+/// What happens to resting orders when the bot is stopped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShutdownOrders {
+    /// Leave them on the book
+    #[default]
+    Keep,
+    /// Cancel every open order on the account before exiting
+    Cancel,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebSocketEvent {
     pub event_type: String,
@@ -120,23 +772,151 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             wallets_to_track: vec![],
+            leader_labels: vec![],
             your_wallet: String::new(),
             private_key: String::new(),
             polymarket_api: String::new(),
+            exchange: Venue::Polymarket,
+            kalshi_api: "https://api.elections.kalshi.com/trade-api/v2".to_string(),
+            kalshi_key_id: String::new(),
+            kalshi_private_key: String::new(),
+            gamma_api: String::new(),
+            data_api: String::new(),
+            data_api_rate: 5,
             ws_url: String::new(),
+            trade_source: TradeFeed::WebSocket,
+            subgraph_url: String::new(),
+            subgraph_poll_interval: Duration::from_secs(5),
+            book_ws_url: String::new(),
+            book_tokens: vec![],
+            book_held_markets: false,
+            book_tokens_per_connection: 200,
+            decision_book_levels: 10,
+            imbalance_ticks: 5,
+            max_chase_imbalance: 0.0,
+            price_alerts: vec![],
+            stream_held_markets: true,
             rpc_url: String::new(),
             sizing_mode: SizingMode::Fixed,
-            fixed_stake: 25.0,
-            proportional_ratio: 0.02,
-            min_stake: 5.0,
-            max_stake: 100.0,
-            max_exposure_per_event: 500.0,
-            max_daily_volume: 2000.0,
+            fixed_stake: UsdcAmount(25.0),
+            proportional_ratio: Ratio(0.02),
+            min_stake: UsdcAmount(5.0),
+            max_stake: UsdcAmount(100.0),
+            max_exposure_per_event: UsdcAmount(500.0),
+            max_daily_volume: UsdcAmount(2000.0),
             min_liquidity: 1000.0,
+            liquidity_window: Duration::from_secs(30 * 60),
+            max_spread: 0.0,
+            max_book_share: 0.0,
+            min_trades_per_hour: 0.0,
+            max_neg_risk_deviation: 0.0,
+            spot_ws_url: "wss://stream.binance.com:9443/ws".to_string(),
+            spot_assets: vec![],
+            spot_volatility: 0.6,
+            spot_min_probability: 0.0,
+            arb_pairs: vec![],
+            arb_auto_pair: false,
+            arb_interval: Duration::ZERO,
+            arb_min_edge: 0.02,
+            arb_polymarket_fee: 0.0,
+            arb_kalshi_fee: 0.07,
+            arb_max_stake: 50.0,
+            arb_auto_execute: false,
             cb_consecutive_trigger: 3,
             cb_min_depth_usd: 100.0,
             retry_attempts: 4,
             retry_delay_ms: 500,
+            max_slippage: Ratio(0.0),
+            max_daily_loss: UsdcAmount(0.0),
+            stop_loss: 0.0,
+            take_profit: 0.0,
+            paper_trading: false,
+            fill_model: FillModelKind::Immediate,
+            fill_latency: Duration::from_secs(1),
+            paper_balance: 1000.0,
+            paper_settle_interval: Duration::from_secs(10 * 60),
+            shadow: None,
+            storage_url: String::new(),
+            cost_basis: CostBasis::Average,
+            pnl_interval: Duration::from_secs(60),
+            equity_interval: Duration::from_secs(3600),
+            cash_flow_interval: Duration::from_secs(5 * 60),
+            gas_token_usd: 0.25,
+            reconcile_interval: Duration::from_secs(10 * 60),
+            reconcile_auto_correct: false,
+            stale_position_after: Duration::from_secs(6 * 3600),
+            resolution_interval: Duration::from_secs(10 * 60),
+            dispute_interval: Duration::from_secs(10 * 60),
+            auto_redeem: true,
+            instance_lease_ttl: Duration::from_secs(30),
+            force_instance_lease: false,
+            event_log: String::new(),
+            capture_ws_frames: false,
+            ws_frame_retention: Duration::from_secs(7 * 86_400),
+            journal_retention: Duration::from_secs(180 * 86_400),
+            archive_dir: "archive".to_string(),
+            compaction_interval: Duration::from_secs(6 * 3600),
+            latency_budget: Duration::ZERO,
+            min_price: 0.0,
+            max_price: 1.0,
+            dedup_window: Duration::from_secs(24 * 3600),
+            market_cache_ttl: Duration::from_secs(60),
+            market_max_stale: Duration::from_secs(600),
+            market_blacklist: vec![],
+            price_sample_interval: Duration::from_secs(30),
+            price_watch_window: Duration::from_secs(15 * 60),
+            price_retention: Duration::from_secs(90 * 86_400),
+            mark_max_age: Duration::from_secs(120),
+            trading_timezone: "UTC".to_string(),
+            trading_windows: vec![],
+            blackout_dates: vec![],
+            telegram_bot_token: String::new(),
+            telegram_chat_id: String::new(),
+            discord_webhook: String::new(),
+            discord_trades_webhook: String::new(),
+            discord_errors_webhook: String::new(),
+            discord_bot_token: String::new(),
+            discord_command_channel: String::new(),
+            webhook_urls: vec![],
+            webhook_secret: String::new(),
+            webhook_retries: 3,
+            pagerduty_routing_key: String::new(),
+            smtp_url: String::new(),
+            email_from: String::new(),
+            email_to: vec![],
+            email_max_per_hour: 6,
+            feed_down_alert: Duration::from_secs(300),
+            approval_threshold: 0.0,
+            approval_timeout: Duration::from_secs(300),
+            health_addr: String::new(),
+            status_api: false,
+            admin_token: String::new(),
+            pid_file: String::new(),
+            shutdown_orders: ShutdownOrders::Keep,
+            otel_exporter_otlp_endpoint: String::new(),
+            otel_service_name: "polymarket-bot".to_string(),
+            latency_summary_interval: Duration::from_secs(15 * 60),
+            heartbeat_url: String::new(),
+            heartbeat_interval: Duration::from_secs(60),
+            opsgenie_api_key: String::new(),
+            opsgenie_url: "https://api.opsgenie.com".to_string(),
+            incident_stall_after: Duration::from_secs(30 * 60),
+            incident_error_rate: 0.5,
+            incident_error_window: Duration::from_secs(15 * 60),
+            incident_min_orders: 5,
+            anomaly_window: Duration::from_secs(30 * 60),
+            anomaly_min_events: 5,
+            spike_markets: 0,
+            spike_interval: Duration::from_secs(5 * 60),
+            spike_window: Duration::from_secs(6 * 3600),
+            spike_factor: 3.0,
+            spike_holder_change: 0.5,
+            notify_routes: vec![],
+            notify_min_severity: Severity::Info,
+            notify_leaders: vec![],
+            notify_digest_interval: Duration::ZERO,
+            notify_dedup_window: Duration::from_secs(300),
+            notify_recovery_delay: Duration::from_secs(120),
         }
     }
 }
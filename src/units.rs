@@ -0,0 +1,200 @@
+//! Config values with units: USDC amounts, ratios and durations.
+//!
+//! The sizing and risk limits in `Config` (stakes, exposure and volume caps,
+//! the daily loss limit, the copy ratio and max slippage) keep their
+//! `UsdcAmount` and `Ratio` types; other amounts and fractions are parsed
+//! the same way and stored as plain `f64`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum UnitError {
+    #[error("empty value")]
+    Empty,
+    #[error("'{0}' is not a number")]
+    NotANumber(String),
+    #[error("unknown unit '{unit}' in '{value}'")]
+    UnknownUnit { value: String, unit: String },
+    #[error("'{0}' is missing a unit (e.g. 750ms, 5s, 2m)")]
+    MissingUnit(String),
+    #[error("'{0}' must not be negative")]
+    Negative(String),
+}
+
+/// A USDC amount. Accepts `250`, `250 USDC`, `$250` or `250usd`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub struct UsdcAmount(pub f64);
+
+impl UsdcAmount {
+    pub fn as_f64(self) -> f64 {
+        self.0
+    }
+}
+
+impl FromStr for UsdcAmount {
+    type Err = UnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw = s.trim();
+        let (number, unit) = split_number(raw.strip_prefix('$').unwrap_or(raw))?;
+        match unit.to_lowercase().as_str() {
+            "" | "usdc" | "usd" => {}
+            _ => {
+                return Err(UnitError::UnknownUnit {
+                    value: raw.to_string(),
+                    unit: unit.to_string(),
+                })
+            }
+        }
+        if number < 0.0 {
+            return Err(UnitError::Negative(raw.to_string()));
+        }
+        Ok(UsdcAmount(number))
+    }
+}
+
+impl fmt::Display for UsdcAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} USDC", self.0)
+    }
+}
+
+/// A fraction. Accepts `0.025`, `2.5%` or `2.5 %`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub struct Ratio(pub f64);
+
+impl Ratio {
+    pub fn as_f64(self) -> f64 {
+        self.0
+    }
+}
+
+impl FromStr for Ratio {
+    type Err = UnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw = s.trim();
+        let (number, unit) = split_number(raw)?;
+        let value = match unit {
+            "" => number,
+            "%" => number / 100.0,
+            "bps" => number / 10_000.0,
+            _ => {
+                return Err(UnitError::UnknownUnit {
+                    value: raw.to_string(),
+                    unit: unit.to_string(),
+                })
+            }
+        };
+        if value < 0.0 {
+            return Err(UnitError::Negative(raw.to_string()));
+        }
+        Ok(Ratio(value))
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0 * 100.0)
+    }
+}
+
+/// Parses durations like `750ms`, `5s`, `2m`, `1h` or `7d`. A bare `0` is
+/// accepted; any other number needs a unit.
+pub fn parse_duration(s: &str) -> Result<Duration, UnitError> {
+    let raw = s.trim();
+    let (number, unit) = split_number(raw)?;
+    if number < 0.0 {
+        return Err(UnitError::Negative(raw.to_string()));
+    }
+    let millis = match unit {
+        "" if number == 0.0 => 0.0,
+        "" => return Err(UnitError::MissingUnit(raw.to_string())),
+        "ms" => number,
+        "s" | "sec" | "secs" => number * 1_000.0,
+        "m" | "min" | "mins" => number * 60_000.0,
+        "h" | "hr" | "hrs" => number * 3_600_000.0,
+        "d" | "day" | "days" => number * 86_400_000.0,
+        _ => {
+            return Err(UnitError::UnknownUnit {
+                value: raw.to_string(),
+                unit: unit.to_string(),
+            })
+        }
+    };
+    Ok(Duration::from_millis(millis.round() as u64))
+}
+
+/// Formats a duration back into the shortest exact unit `parse_duration` accepts.
+pub fn format_duration(d: Duration) -> String {
+    let ms = d.as_millis();
+    if ms == 0 {
+        "0s".to_string()
+    } else if ms.is_multiple_of(86_400_000) {
+        format!("{}d", ms / 86_400_000)
+    } else if ms.is_multiple_of(3_600_000) {
+        format!("{}h", ms / 3_600_000)
+    } else if ms.is_multiple_of(60_000) {
+        format!("{}m", ms / 60_000)
+    } else if ms.is_multiple_of(1_000) {
+        format!("{}s", ms / 1_000)
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+/// Splits `"12.5 USDC"` into `(12.5, "USDC")`.
+fn split_number(raw: &str) -> Result<(f64, &str), UnitError> {
+    if raw.is_empty() {
+        return Err(UnitError::Empty);
+    }
+    let end = raw
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == '_'))
+        .unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(end);
+    let number: f64 = number
+        .replace('_', "")
+        .parse()
+        .map_err(|_| UnitError::NotANumber(raw.to_string()))?;
+    Ok((number, unit.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usdc_amounts() {
+        assert_eq!("250".parse::<UsdcAmount>().unwrap(), UsdcAmount(250.0));
+        assert_eq!("250 USDC".parse::<UsdcAmount>().unwrap(), UsdcAmount(250.0));
+        assert_eq!("$12.5".parse::<UsdcAmount>().unwrap(), UsdcAmount(12.5));
+        assert!(matches!(
+            "250 ETH".parse::<UsdcAmount>(),
+            Err(UnitError::UnknownUnit { .. })
+        ));
+        assert!(matches!("-5".parse::<UsdcAmount>(), Err(UnitError::Negative(_))));
+    }
+
+    #[test]
+    fn test_ratios() {
+        assert_eq!("2.5%".parse::<Ratio>().unwrap(), Ratio(0.025));
+        assert_eq!("0.02".parse::<Ratio>().unwrap(), Ratio(0.02));
+        assert_eq!("50 bps".parse::<Ratio>().unwrap(), Ratio(0.005));
+        assert!("abc%".parse::<Ratio>().is_err());
+    }
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(parse_duration("750ms").unwrap(), Duration::from_millis(750));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert!(matches!(parse_duration("750"), Err(UnitError::MissingUnit(_))));
+        assert!(matches!(parse_duration("5 weeks"), Err(UnitError::UnknownUnit { .. })));
+        assert_eq!(format_duration(Duration::from_millis(750)), "750ms");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h");
+    }
+}
@@ -1,59 +1,290 @@
+use crate::events::{BotEvent, ConnectionState, EventBus};
+use crate::health::FeedStatus;
+use crate::incidents::IncidentMonitor;
+use crate::latency::{LatencyStats, Stage};
+use crate::logging;
+use crate::notify::{Notification, Notifications};
+use crate::storage::Storage;
+use crate::telemetry;
 use crate::types::{Trade, TradeSide};
 use anyhow::{Context, Result};
 use async_channel::{Sender, Receiver, bounded};
+use async_trait::async_trait;
 use futures_util::{SinkExt, StreamExt};
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
+/// Trades the feeds can queue before they block on the trade loop.
+pub const TRADE_CHANNEL_CAPACITY: usize = 1000;
+
+/// Somewhere the tracked wallets' trades come from: [`WalletWatcher`]'s
+/// WebSocket feed, or [`SubgraphWatcher`](crate::subgraph::SubgraphWatcher)
+/// polling the orderbook subgraph. `trade_source` picks one.
+#[async_trait]
+pub trait TradeSource: Send + Sync {
+    /// Starts watching every wallet, sending their trades on the receiver.
+    async fn start(&self) -> Result<Receiver<Trade>>;
+
+    /// Trades waiting for the trade loop; `None` before [`start`](Self::start).
+    fn queue_depth(&self) -> Option<usize>;
+
+    /// Wallets being watched, in no particular order.
+    fn wallets(&self) -> Vec<String>;
+
+    /// Starts watching another wallet; `false` if it already was.
+    fn add_wallet(&self, wallet: &str) -> Result<bool>;
+
+    /// Stops watching a wallet; `false` if it wasn't watched.
+    fn remove_wallet(&self, wallet: &str) -> Result<bool>;
+}
+
 pub struct WalletWatcher {
     ws_url: String,
     wallets: Vec<String>,
+    recorders: Recorders,
+    running: std::sync::Mutex<Option<Running>>,
+}
+
+/// Where watchers send trades once started, and the task watching each wallet.
+struct Running {
+    tx: Sender<Trade>,
+    tasks: HashMap<String, JoinHandle<()>>,
+}
+
+/// Optional sinks for connection events and raw frames.
+#[derive(Clone, Default)]
+pub(crate) struct Recorders {
+    pub(crate) events: Option<Arc<EventBus>>,
+    pub(crate) frames: Option<Arc<dyn Storage>>,
+    pub(crate) notifications: Notifications,
+    /// Raise a FeedDown alert once a feed has been down this long; zero disables
+    pub(crate) outage_alert: Duration,
+    pub(crate) incidents: Option<Arc<IncidentMonitor>>,
+    pub(crate) feeds: Option<Arc<FeedStatus>>,
+    pub(crate) latency: Option<Arc<LatencyStats>>,
+}
+
+impl Recorders {
+    pub(crate) fn connection(&self, wallet: &str, state: ConnectionState, detail: Option<String>) {
+        if let Some(feeds) = &self.feeds {
+            feeds.update(wallet, state, detail.clone(), chrono::Utc::now().timestamp_millis());
+        }
+        self.notifications.send(Notification::Connection {
+            wallet: wallet.to_string(),
+            state,
+            detail: detail.clone(),
+        });
+        if let Some(events) = &self.events {
+            events.publish(BotEvent::Connection {
+                wallet: wallet.to_string(),
+                state,
+                detail,
+            });
+        }
+    }
+    
+    /// Records `trade` arriving at `received_ns`, how long after the leader
+    /// made it, and that the trade loop has one more to take.
+    pub(crate) fn trade(&self, trade: &Trade, received_ns: u64) {
+        if let Some(latency) = &self.latency {
+            let delay_ms = (received_ns / 1_000_000) as i64 - trade.timestamp * 1000;
+            latency.record(Stage::Feed, Duration::from_millis(delay_ms.max(0) as u64));
+        }
+        if let Some(incidents) = &self.incidents {
+            incidents.trade_seen(chrono::Utc::now().timestamp_millis());
+        }
+    }
+
+    async fn frame(&self, wallet: &str, payload: &str) {
+        if let Some(frames) = &self.frames {
+            let now = chrono::Utc::now().timestamp_millis();
+            if let Err(e) = frames.record_frame(wallet, payload, now).await {
+                tracing::warn!("Failed to store WebSocket frame: {}", e);
+            }
+        }
+    }
 }
 
 impl WalletWatcher {
     pub fn new(ws_url: String, wallets: Vec<String>) -> Self {
-        Self { ws_url, wallets }
+        Self { ws_url, wallets, recorders: Recorders::default(), running: Default::default() }
+    }
+    
+    /// Publishes connects and disconnects on `events`.
+    pub fn with_events(mut self, events: Arc<EventBus>) -> Self {
+        self.recorders.events = Some(events);
+        self
+    }
+    
+    /// Tells the operator about connects and disconnects.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.recorders.notifications = notifications;
+        self
+    }
+    
+    /// Sends a critical alert when a wallet's feed stays down for `after`.
+    pub fn with_outage_alert(mut self, after: Duration) -> Self {
+        self.recorders.outage_alert = after;
+        self
+    }
+    
+    /// Counts arriving trades, so a trade loop that stops keeping up opens
+    /// an incident.
+    pub fn with_incidents(mut self, incidents: Arc<IncidentMonitor>) -> Self {
+        self.recorders.incidents = Some(incidents);
+        self
+    }
+    
+    /// Keeps each feed's connection state for the readiness check.
+    pub fn with_feed_status(mut self, feeds: Arc<FeedStatus>) -> Self {
+        self.recorders.feeds = Some(feeds);
+        self
+    }
+    
+    /// Records how long after the leader's trade each one arrives.
+    pub fn with_latency(mut self, latency: Arc<LatencyStats>) -> Self {
+        self.recorders.latency = Some(latency);
+        self
+    }
+    
+    /// Stores every raw text frame in `storage` for later debugging.
+    pub fn with_frame_capture(mut self, storage: Option<Arc<dyn Storage>>) -> Self {
+        self.recorders.frames = storage;
+        self
     }
     
     pub async fn start(&self) -> Result<Receiver<Trade>> {
-        let (tx, rx) = bounded(1000);
+        let (tx, rx) = bounded(TRADE_CHANNEL_CAPACITY);
+        let mut running = Running { tx, tasks: HashMap::new() };
         
         for wallet in &self.wallets {
-            let wallet_clone = wallet.clone();
-            let ws_url = self.ws_url.clone();
-            let tx_clone = tx.clone();
-            
-            tokio::spawn(async move {
-                if let Err(e) = watch_wallet(ws_url, wallet_clone, tx_clone).await {
-                    tracing::error!("Wallet watcher error: {}", e);
-                }
-            });
+            self.spawn(&mut running, wallet);
         }
         
+        *self.running.lock().unwrap() = Some(running);
         Ok(rx)
     }
+    
+    fn spawn(&self, running: &mut Running, wallet: &str) {
+        let wallet_clone = wallet.to_string();
+        let ws_url = self.ws_url.clone();
+        let tx_clone = running.tx.clone();
+        let recorders = self.recorders.clone();
+        
+        let task = tokio::spawn(async move {
+            if let Err(e) = watch_wallet(ws_url, wallet_clone, tx_clone, recorders).await {
+                tracing::error!("Wallet watcher error: {}", e);
+            }
+        });
+        running.tasks.insert(wallet.to_string(), task);
+    }
+    
+    /// Trades waiting for the trade loop; `None` before [`start`](Self::start).
+    pub fn queue_depth(&self) -> Option<usize> {
+        self.running.lock().unwrap().as_ref().map(|running| running.tx.len())
+    }
+    
+    /// Wallets being watched, in no particular order.
+    pub fn wallets(&self) -> Vec<String> {
+        match &*self.running.lock().unwrap() {
+            Some(running) => running.tasks.keys().cloned().collect(),
+            None => self.wallets.clone(),
+        }
+    }
+    
+    /// Starts watching another wallet; `false` if it already was.
+    pub fn add_wallet(&self, wallet: &str) -> Result<bool> {
+        let mut running = self.running.lock().unwrap();
+        let running = running.as_mut().context("Watchers are not running")?;
+        if running.tasks.contains_key(wallet) {
+            return Ok(false);
+        }
+        if let Some(feeds) = &self.recorders.feeds {
+            let now = chrono::Utc::now().timestamp_millis();
+            feeds.update(wallet, ConnectionState::Disconnected, Some("not connected yet".to_string()), now);
+        }
+        self.spawn(running, wallet);
+        Ok(true)
+    }
+    
+    /// Stops watching a wallet; `false` if it wasn't watched.
+    pub fn remove_wallet(&self, wallet: &str) -> Result<bool> {
+        let mut running = self.running.lock().unwrap();
+        let running = running.as_mut().context("Watchers are not running")?;
+        let Some(task) = running.tasks.remove(wallet) else {
+            return Ok(false);
+        };
+        task.abort();
+        if let Some(feeds) = &self.recorders.feeds {
+            feeds.remove(wallet);
+        }
+        Ok(true)
+    }
 }
 
-async fn watch_wallet(ws_url: String, wallet: String, tx: Sender<Trade>) -> Result<()> {
+#[async_trait]
+impl TradeSource for WalletWatcher {
+    async fn start(&self) -> Result<Receiver<Trade>> {
+        WalletWatcher::start(self).await
+    }
+
+    fn queue_depth(&self) -> Option<usize> {
+        WalletWatcher::queue_depth(self)
+    }
+
+    fn wallets(&self) -> Vec<String> {
+        WalletWatcher::wallets(self)
+    }
+
+    fn add_wallet(&self, wallet: &str) -> Result<bool> {
+        WalletWatcher::add_wallet(self, wallet)
+    }
+
+    fn remove_wallet(&self, wallet: &str) -> Result<bool> {
+        WalletWatcher::remove_wallet(self, wallet)
+    }
+}
+
+async fn watch_wallet(ws_url: String, wallet: String, tx: Sender<Trade>, recorders: Recorders) -> Result<()> {
     let mut retry_count = 0;
     let max_retries = 10;
     let base_delay = 5;
+    // First failed connect of the current outage
+    let mut down_since: Option<Instant> = None;
+    let mut alerted = false;
     
     loop {
         tracing::info!("Attempting WebSocket connection for wallet {}...", &wallet[..10.min(wallet.len())]);
         
-        match connect_and_watch(&ws_url, &wallet, &tx).await {
+        match connect_and_watch(&ws_url, &wallet, &tx, &recorders).await {
             Ok(_) => {
                 tracing::info!("WebSocket connection closed normally for {}", &wallet[..10.min(wallet.len())]);
+                recorders.connection(&wallet, ConnectionState::Disconnected, None);
                 retry_count = 0; // Reset on successful connection
+                down_since = None;
+                alerted = false;
             }
             Err(e) => {
                 retry_count += 1;
                 let delay = base_delay * retry_count.min(6); // Max 30 seconds delay
                 
+                recorders.connection(&wallet, ConnectionState::Disconnected, Some(e.to_string()));
+                let down_for = down_since.get_or_insert_with(Instant::now).elapsed();
+                if !alerted && !recorders.outage_alert.is_zero() && down_for >= recorders.outage_alert {
+                    alerted = true;
+                    recorders.notifications.send(Notification::FeedDown {
+                        wallet: wallet.clone(),
+                        down_secs: down_for.as_secs(),
+                        detail: Some(e.to_string()),
+                    });
+                }
                 tracing::error!(
                     "WebSocket error for {} (attempt {}/{}): {}",
                     &wallet[..10.min(wallet.len())],
@@ -74,7 +305,12 @@ async fn watch_wallet(ws_url: String, wallet: String, tx: Sender<Trade>) -> Resu
     }
 }
 
-async fn connect_and_watch(ws_url: &str, wallet: &str, tx: &Sender<Trade>) -> Result<()> {
+async fn connect_and_watch(
+    ws_url: &str,
+    wallet: &str,
+    tx: &Sender<Trade>,
+    recorders: &Recorders,
+) -> Result<()> {
     // Parse and validate WebSocket URL
     let url = url::Url::parse(ws_url)
         .context("Invalid WebSocket URL")?;
@@ -112,6 +348,7 @@ async fn connect_and_watch(ws_url: &str, wallet: &str, tx: &Sender<Trade>) -> Re
     }
     
     tracing::info!("Subscribed to trades for wallet: {}", &wallet[..10.min(wallet.len())]);
+    recorders.connection(wallet, ConnectionState::Connected, None);
     
     // Keep connection alive with ping
     let write_clone = Arc::clone(&write);
@@ -139,7 +376,9 @@ async fn connect_and_watch(ws_url: &str, wallet: &str, tx: &Sender<Trade>) -> Re
     while let Some(msg) = read.next().await {
         match msg {
             Ok(Message::Text(text)) => {
+                let received_ns = telemetry::now_ns();
                 tracing::debug!("Received message: {}", &text[..100.min(text.len())]);
+                recorders.frame(wallet, &text).await;
                 
                 if let Ok(event) = serde_json::from_str::<serde_json::Value>(&text) {
                     // Handle different event types
@@ -147,7 +386,18 @@ async fn connect_and_watch(ws_url: &str, wallet: &str, tx: &Sender<Trade>) -> Re
                         match event_type {
                             "trade" => {
                                 if let Some(trade) = parse_trade_event(&event, wallet) {
-                                    if let Err(e) = tx.send(trade).await {
+                                    // Both spans start when the frame arrived
+                                    let span = tracing::info_span!(
+                                        telemetry::ROOT_SPAN,
+                                        cid = %logging::correlation_id(&trade),
+                                        wallet = %trade.wallet,
+                                        market = %trade.market_id,
+                                        otel.start_ns = received_ns,
+                                    );
+                                    tracing::info_span!(parent: &span, "parse", otel.start_ns = received_ns)
+                                        .in_scope(|| tracing::info!("📥 Leader trade received"));
+                                    recorders.trade(&trade, received_ns);
+                                    if let Err(e) = tx.send(trade).instrument(span).await {
                                         tracing::error!("Failed to send trade to channel: {}", e);
                                         break;
                                     }
@@ -0,0 +1,242 @@
+//! `mybot init`: a starter config file from answers on the terminal.
+//!
+//! Asks for the endpoints, the leaders to copy, the signer and the risk
+//! limits, re-asking until each answer parses the way the config loader
+//! will parse it. Paper trading is the default, so the first run can't
+//! place real orders. With `CONFIG_PASSPHRASE` or `CONFIG_KEYFILE` set the
+//! private key is written sealed; otherwise it can be left out and given
+//! as `PRIVATE_KEY` in the environment.
+
+use crate::config::{self, CliOverrides};
+use crate::config_migration::CURRENT_CONFIG_VERSION;
+use crate::sealed;
+use crate::units::UsdcAmount;
+use anyhow::{Context, Result};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use toml_edit::{value, Array, DocumentMut, Item, Table};
+
+/// Asks the questions on `input`/`output` and builds the config file.
+pub struct Wizard<R, W> {
+    input: R,
+    output: W,
+    /// Seals the private key when set
+    secret: Option<Vec<u8>>,
+}
+
+impl<R: BufRead, W: Write> Wizard<R, W> {
+    pub fn new(input: R, output: W, secret: Option<Vec<u8>>) -> Self {
+        Self { input, output, secret }
+    }
+
+    /// Asks until `check` accepts the answer; an empty answer takes the
+    /// default when there is one.
+    fn ask(&mut self, question: &str, default: Option<&str>, check: impl Fn(&str) -> Result<String>) -> Result<String> {
+        loop {
+            match default {
+                Some(default) if !default.is_empty() => write!(self.output, "{} [{}]: ", question, default)?,
+                _ => write!(self.output, "{}: ", question)?,
+            }
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                anyhow::bail!("Input ended before the config was complete");
+            }
+            let answer = match line.trim() {
+                "" => default.unwrap_or_default(),
+                answer => answer,
+            };
+            match check(answer) {
+                Ok(answer) => return Ok(answer),
+                Err(e) => writeln!(self.output, "  {}", e)?,
+            }
+        }
+    }
+
+    fn ask_yes(&mut self, question: &str, default: bool) -> Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        let answer = self.ask(&format!("{} [{}]", question, hint), None, |a| {
+            match a.to_lowercase().as_str() {
+                "" => Ok(default.to_string()),
+                "y" | "yes" => Ok("true".to_string()),
+                "n" | "no" => Ok("false".to_string()),
+                _ => anyhow::bail!("Answer y or n"),
+            }
+        })?;
+        Ok(answer == "true")
+    }
+
+    fn ask_usdc(&mut self, question: &str, key: &str) -> Result<f64> {
+        let answer = self.ask(question, config::default_value(key), |a| {
+            a.parse::<UsdcAmount>()
+                .map(|v| v.as_f64().to_string())
+                .map_err(|e| anyhow::anyhow!("{} (e.g. 25 or $25)", e))
+        })?;
+        Ok(answer.parse()?)
+    }
+
+    /// Walks through every question and returns the config file.
+    pub fn run(&mut self) -> Result<DocumentMut> {
+        let mut doc = DocumentMut::new();
+        doc["config_version"] = value(CURRENT_CONFIG_VERSION);
+
+        writeln!(self.output, "Endpoints")?;
+        let rpc_url = self.ask("Polygon RPC URL (wss:// for mempool mode)", None, |a| {
+            url(a, &["https://", "http://", "wss://", "ws://"])
+        })?;
+        doc["rpc_url"] = value(rpc_url);
+        let ws_url = self.ask("Trade feed URL", config::default_value("ws_url"), |a| {
+            url(a, &["wss://", "ws://"])
+        })?;
+        doc["ws_url"] = value(ws_url);
+
+        writeln!(self.output, "\nLeaders")?;
+        let wallets = self.ask("Wallets to copy (comma separated)", None, |a| {
+            let wallets: Vec<&str> = a.split(',').map(str::trim).filter(|w| !w.is_empty()).collect();
+            if wallets.is_empty() {
+                anyhow::bail!("Give at least one wallet");
+            }
+            for w in &wallets {
+                address(w)?;
+            }
+            Ok(wallets.join(","))
+        })?;
+        doc["wallets_to_track"] = value(wallets.split(',').collect::<Array>());
+
+        writeln!(self.output, "\nSigner")?;
+        doc["your_wallet"] = value(self.ask("Your wallet address", None, address)?);
+        let paper = self.ask_yes("Start in paper trading mode (simulated orders)?", true)?;
+        doc["paper_trading"] = value(paper);
+        let sealing = if self.secret.is_some() {
+            "sealed with CONFIG_PASSPHRASE/CONFIG_KEYFILE"
+        } else {
+            "stored in plain text"
+        };
+        let key = self.ask(
+            &format!("Private key, {} (empty to set PRIVATE_KEY instead)", sealing),
+            None,
+            |a| {
+                let hex = a.strip_prefix("0x").unwrap_or(a);
+                if !a.is_empty() && (hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit())) {
+                    anyhow::bail!("Expected 64 hex characters");
+                }
+                Ok(a.to_string())
+            },
+        )?;
+        match (&self.secret, key.is_empty()) {
+            (_, true) => {}
+            (Some(secret), false) => {
+                let fragment = format!("private_key = {}", value(&key));
+                let mut sealed_table = Table::new();
+                sealed_table["signer"] = value(sealed::seal(&fragment, secret)?);
+                doc["sealed"] = Item::Table(sealed_table);
+            }
+            (None, false) => doc["private_key"] = value(key),
+        }
+
+        writeln!(self.output, "\nRisk limits (USDC)")?;
+        let min_stake: f64 = config::default_value("min_stake").unwrap_or_default().parse()?;
+        loop {
+            let fixed = self.ask_usdc("Stake per copied trade", "fixed_stake")?;
+            let max = self.ask_usdc("Largest stake per trade", "max_stake")?;
+            if fixed >= min_stake && max >= fixed {
+                doc["fixed_stake"] = value(fixed);
+                doc["max_stake"] = value(max);
+                break;
+            }
+            writeln!(
+                self.output,
+                "  The stake must be at least {} and at most the largest stake",
+                min_stake
+            )?;
+        }
+        doc["max_daily_volume"] = value(self.ask_usdc("Most to trade per day", "max_daily_volume")?);
+        doc["max_daily_loss"] = value(self.ask_usdc("Loss per day that stops trading (0 for none)", "max_daily_loss")?);
+        Ok(doc)
+    }
+}
+
+fn url(answer: &str, schemes: &[&str]) -> Result<String> {
+    if !schemes.iter().any(|s| answer.starts_with(s)) || answer.len() <= 8 {
+        anyhow::bail!("Expected a URL starting with {}", schemes.join(" or "));
+    }
+    Ok(answer.to_string())
+}
+
+fn address(answer: &str) -> Result<String> {
+    let hex = answer.strip_prefix("0x").unwrap_or_default();
+    if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Expected a 0x address with 40 hex characters");
+    }
+    Ok(answer.to_string())
+}
+
+/// Runs the wizard on the terminal and writes the answers to `path`.
+pub fn init(path: &Path) -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut wizard = Wizard::new(stdin.lock(), std::io::stdout(), sealed::load_secret()?);
+    if path.exists() && !wizard.ask_yes(&format!("{} exists. Overwrite it?", path.display()), false)? {
+        println!("Nothing written");
+        return Ok(());
+    }
+    let doc = wizard.run()?;
+    std::fs::write(path, doc.to_string()).with_context(|| format!("Failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    println!("\nWrote {}", path.display());
+
+    let cli = CliOverrides {
+        config_file: Some(path.to_path_buf()),
+        ..Default::default()
+    };
+    match config::load_layered(&cli).and_then(|loaded| config::validate_config(&loaded.config)) {
+        Ok(()) => println!("Config OK. Start with: mybot --config {}", path.display()),
+        Err(e) => println!("Not ready yet: {:#}\nFix that, then check with: mybot check-config", e),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wizard_reasks_invalid_answers() {
+        let leader = format!("0x{}", "a".repeat(40));
+        let mine = format!("0x{}", "b".repeat(40));
+        let answers = [
+            "localhost:8545",
+            "wss://polygon.example/ws",
+            "",
+            "0x123",
+            leader.as_str(),
+            mine.as_str(),
+            "",
+            "",
+            "2",
+            "100",
+            "30",
+            "",
+            "",
+            "",
+        ];
+        let input = answers.join("\n") + "\n";
+        let mut output = Vec::new();
+        let doc = Wizard::new(input.as_bytes(), &mut output, None).run().unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("Expected a URL"));
+        assert!(output.contains("Expected a 0x address"));
+        assert!(output.contains("The stake must be at least"));
+        assert_eq!(doc["rpc_url"].as_str(), Some("wss://polygon.example/ws"));
+        assert_eq!(doc["ws_url"].as_str(), config::default_value("ws_url"));
+        assert_eq!(doc["wallets_to_track"][0].as_str(), Some(leader.as_str()));
+        assert_eq!(doc["paper_trading"].as_bool(), Some(true));
+        assert!(doc.get("private_key").is_none());
+        assert_eq!(doc["fixed_stake"].as_float(), Some(30.0));
+        assert_eq!(doc["max_daily_volume"].as_float(), Some(2000.0));
+    }
+}
@@ -153,6 +153,7 @@ mod api_tests {
 mod circuit_breaker_tests {
     use polymarket_copy_bot::risk::RiskManager;
     use polymarket_copy_bot::types::{Config, SizingMode};
+    use polymarket_copy_bot::units::{Ratio, UsdcAmount};
     
     #[test]
     fn test_circuit_breaker_trips() {
@@ -164,17 +165,18 @@ mod circuit_breaker_tests {
             ws_url: "".to_string(),
             rpc_url: "".to_string(),
             sizing_mode: SizingMode::Fixed,
-            fixed_stake: 25.0,
-            proportional_ratio: 0.02,
-            min_stake: 5.0,
-            max_stake: 100.0,
-            max_exposure_per_event: 500.0,
-            max_daily_volume: 2000.0,
+            fixed_stake: UsdcAmount(25.0),
+            proportional_ratio: Ratio(0.02),
+            min_stake: UsdcAmount(5.0),
+            max_stake: UsdcAmount(100.0),
+            max_exposure_per_event: UsdcAmount(500.0),
+            max_daily_volume: UsdcAmount(2000.0),
             min_liquidity: 1000.0,
             cb_consecutive_trigger: 3,
             cb_min_depth_usd: 100.0,
             retry_attempts: 4,
             retry_delay_ms: 500,
+            ..Default::default()
         };
         
         let risk = RiskManager::new(config);
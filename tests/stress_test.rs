@@ -0,0 +1,34 @@
+// Chaos tests of the watcher and engine against the synthetic feed
+
+#[cfg(test)]
+mod tests {
+    use polymarket_copy_bot::builder::BotBuilder;
+    use polymarket_copy_bot::stress;
+    use polymarket_copy_bot::synthetic::FeedPlan;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_no_trade_lost_or_copied_twice_under_chaos() {
+        let config = BotBuilder::new().account("0xme", "a".repeat(64)).config().clone();
+        let plan = FeedPlan {
+            trades: 300,
+            burst: 50,
+            interval: Duration::from_millis(20),
+            malformed: 0.1,
+            duplicates: 0.1,
+            disconnect_every: 120,
+            seed: 42,
+            ..Default::default()
+        };
+
+        let report = stress::run(config, plan).await.unwrap();
+
+        assert_eq!(report.feed.trades, 300);
+        assert_eq!(report.feed.disconnects, 2);
+        assert!(report.feed.malformed > 0 && report.feed.duplicates > 0);
+        assert_eq!(report.received, 300 + report.feed.duplicates);
+        assert_eq!(report.deduplicated, report.feed.duplicates);
+        assert_eq!(report.copied, 300);
+        assert!(report.passed(), "{}", report);
+    }
+}